@@ -0,0 +1,62 @@
+//! cargo bench --bench fs_get_object --features binary,mmap
+
+use s3_server::dto::GetObjectRequest;
+use s3_server::storages::fs::FileSystem;
+use s3_server::S3Storage;
+
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+
+/// reads the object `iters` times and returns the average duration
+async fn bench_get_object(fs: &FileSystem, key: &str, iters: u32) -> Result<Duration> {
+    let t0 = Instant::now();
+    for _ in 0..iters {
+        let input = GetObjectRequest {
+            bucket: "bench".into(),
+            key: key.into(),
+            ..GetObjectRequest::default()
+        };
+        let output = fs
+            .get_object(input)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let body = output.body.ok_or_else(|| anyhow::anyhow!("missing body"))?;
+        let _ = body
+            .map_ok(|b| b.len())
+            .try_fold(0, |acc, n| async move { Ok(acc + n) })
+            .await?;
+    }
+    Ok(t0.elapsed() / iters)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let root = env::temp_dir().join("s3-server-bench");
+    fs::create_dir_all(root.join("bench"))?;
+
+    let sizes: &[(&str, usize)] = &[("4KiB", 4 << 10), ("1MiB", 1 << 20), ("16MiB", 16 << 20)];
+    for &(key, size) in sizes {
+        fs::write(root.join("bench").join(key), vec![0xa5_u8; size])?;
+    }
+
+    let buffered = FileSystem::new(&root)?;
+    let mut mapped = FileSystem::new(&root)?;
+    mapped.set_mmap_limit(Some(u64::MAX));
+
+    for &(key, _) in sizes {
+        let iters = 32;
+        let t_buffered = bench_get_object(&buffered, key, iters).await?;
+        let t_mapped = bench_get_object(&mapped, key, iters).await?;
+        println!(
+            "get_object {:>6}: buffered {:>12?}, mmap {:>12?}",
+            key, t_buffered, t_mapped
+        );
+    }
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
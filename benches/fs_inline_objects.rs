@@ -0,0 +1,94 @@
+//! cargo bench --bench fs_inline_objects --features binary
+//!
+//! Writes and reads 1 KiB objects stored as files and stored inline in the object index.
+
+use s3_server::dto::{ByteStream, GetObjectRequest, PutObjectRequest};
+use s3_server::storages::fs::index::LogIndex;
+use s3_server::storages::fs::{FileSystem, DEFAULT_INLINE_THRESHOLD};
+use s3_server::S3Storage;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+
+/// object size
+const SIZE: usize = 1024;
+
+/// objects written and read by each round
+const OBJECTS: usize = 1000;
+
+/// puts the objects and returns the average duration
+async fn bench_put_object(fs: &FileSystem) -> Result<Duration> {
+    let t0 = Instant::now();
+    for idx in 0..OBJECTS {
+        let input = PutObjectRequest {
+            bucket: "bench".into(),
+            key: format!("object-{:04}", idx),
+            content_length: Some(SIZE as i64),
+            body: Some(ByteStream::from(vec![0xa5_u8; SIZE])),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs
+            .put_object(input)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+    }
+    Ok(t0.elapsed() / OBJECTS as u32)
+}
+
+/// gets the objects and returns the average duration
+async fn bench_get_object(fs: &FileSystem) -> Result<Duration> {
+    let t0 = Instant::now();
+    for idx in 0..OBJECTS {
+        let input = GetObjectRequest {
+            bucket: "bench".into(),
+            key: format!("object-{:04}", idx),
+            ..GetObjectRequest::default()
+        };
+        let output = fs
+            .get_object(input)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let body = output.body.ok_or_else(|| anyhow::anyhow!("missing body"))?;
+        let len = body
+            .map_ok(|b| b.len())
+            .try_fold(0, |acc, n| async move { Ok(acc + n) })
+            .await?;
+        assert_eq!(len, SIZE);
+    }
+    Ok(t0.elapsed() / OBJECTS as u32)
+}
+
+/// creates a root with an empty bucket and a storage with an index
+fn setup(root: &Path, inline_threshold: Option<u64>) -> Result<FileSystem> {
+    let _ = fs::remove_dir_all(root);
+    fs::create_dir_all(root.join("bench"))?;
+    let mut fs = FileSystem::new(root)?;
+    fs.set_index(LogIndex::open(root.join(".index.log"))?);
+    fs.set_inline_threshold(inline_threshold);
+    Ok(fs)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let root = env::temp_dir().join("s3-server-bench-inline");
+
+    let layouts: &[(&str, Option<u64>)] =
+        &[("files", None), ("inline", Some(DEFAULT_INLINE_THRESHOLD))];
+    for &(name, inline_threshold) in layouts {
+        let fs = setup(&root, inline_threshold)?;
+        let t_put = bench_put_object(&fs).await?;
+        let t_get = bench_get_object(&fs).await?;
+        println!(
+            "1KiB objects {:>6}: put_object {:>12?}, get_object {:>12?}",
+            name, t_put, t_get
+        );
+    }
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
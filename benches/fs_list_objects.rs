@@ -0,0 +1,92 @@
+//! cargo bench --bench fs_list_objects --features binary
+//!
+//! Lists one page of a bucket by walking directories and by the object index.
+
+use s3_server::dto::ListObjectsV2Request;
+use s3_server::storages::fs::index::{IndexEntry, LogIndex, ObjectIndex};
+use s3_server::storages::fs::FileSystem;
+use s3_server::S3Storage;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+/// max keys of a page
+const PAGE: i64 = 100;
+
+/// lists a page from the middle of the bucket `iters` times and returns the average duration
+async fn bench_list(fs: &FileSystem, len: usize, iters: u32) -> Result<Duration> {
+    let t0 = Instant::now();
+    for _ in 0..iters {
+        let input = ListObjectsV2Request {
+            bucket: "bench".into(),
+            max_keys: Some(PAGE),
+            start_after: Some(key(len / 2)),
+            ..ListObjectsV2Request::default()
+        };
+        let output = fs
+            .list_objects_v2(input)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        assert!(output.contents.is_some());
+    }
+    Ok(t0.elapsed() / iters)
+}
+
+fn key(idx: usize) -> String {
+    format!("dir-{:03}/object-{:08}", idx % 100, idx)
+}
+
+/// creates a root whose bucket has `len` object files
+fn setup_files(root: &Path, len: usize) -> Result<()> {
+    let _ = fs::remove_dir_all(root);
+    for idx in 0..len {
+        let path = root.join("bench").join(key(idx));
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, b"x")?;
+    }
+    Ok(())
+}
+
+/// creates a root with an empty bucket and an index of `len` objects
+fn setup_index(root: &Path, len: usize) -> Result<FileSystem> {
+    let _ = fs::remove_dir_all(root);
+    fs::create_dir_all(root.join("bench"))?;
+    let index = LogIndex::open(root.join(".index.log"))?;
+    let entry = IndexEntry {
+        size: 1,
+        last_modified: "2021-05-01T00:00:00+00:00".into(),
+        inline: None,
+    };
+    let records = (0..len)
+        .map(|idx| ("bench".to_owned(), key(idx), entry.clone()))
+        .collect();
+    index.replace_all(records)?;
+    let mut fs = FileSystem::new(root)?;
+    fs.set_index(index);
+    Ok(fs)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let root = env::temp_dir().join("s3-server-bench-list");
+
+    for &len in &[1_000, 10_000] {
+        setup_files(&root, len)?;
+        let fs = FileSystem::new(&root)?;
+        let t_walk = bench_list(&fs, len, 8).await?;
+        println!("list_objects_v2 {:>9} objects: walk  {:>12?}", len, t_walk);
+    }
+
+    for &len in &[1_000, 10_000, 100_000, 1_000_000] {
+        let fs = setup_index(&root, len)?;
+        let t_index = bench_list(&fs, len, 256).await?;
+        println!("list_objects_v2 {:>9} objects: index {:>12?}", len, t_index);
+    }
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
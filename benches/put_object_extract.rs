@@ -0,0 +1,75 @@
+//! cargo bench --bench put_object_extract
+//!
+//! Counts the heap allocations of extracting a `PutObjectRequest`.
+
+use s3_server::ops::{put_object, ReqContext};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use hyper::{Body, Method, Request};
+
+/// counts allocations
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn build_request(metadata: bool) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method(Method::PUT)
+        .uri("http://localhost/bucket/dir/key")
+        .header("content-type", "text/plain")
+        .header("content-length", "5")
+        .header("cache-control", "no-cache")
+        .header("x-amz-content-sha256", "UNSIGNED-PAYLOAD");
+    if metadata {
+        builder = builder.header("x-amz-meta-color", "blue");
+    }
+    builder.body(Body::from("hello")).unwrap()
+}
+
+/// returns the allocations and the average duration of one extraction
+fn bench_extract(metadata: bool, iters: u32) -> (usize, f64) {
+    let mut allocations = 0;
+    let t0 = Instant::now();
+    for _ in 0..iters {
+        let mut req = build_request(metadata);
+        #[allow(clippy::disallowed_methods)] // measures the extraction without `hyper_call`
+        let body = mem::take(req.body_mut());
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let mut ctx = ReqContext::new(&req, body).unwrap();
+        let input = put_object::extract(&mut ctx).unwrap();
+        allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+        drop(input);
+    }
+    let nanos = t0.elapsed().as_nanos() as f64 / f64::from(iters);
+    (allocations, nanos)
+}
+
+fn main() {
+    for &metadata in &[false, true] {
+        let (allocations, nanos) = bench_extract(metadata, 10_000);
+        println!(
+            "put_object extract (metadata: {:>5}): {:>3} allocations, {:>8.0} ns/iter (including request setup)",
+            metadata, allocations, nanos
+        );
+    }
+}
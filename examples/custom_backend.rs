@@ -0,0 +1,286 @@
+//! A backend which stores objects in a `HashMap`
+//!
+//! ```shell
+//! cargo run --example custom_backend --features binary
+//! ```
+//!
+//! Every operation method of `S3Storage` except `get_bucket_versioning` and `put_bucket_versioning`
+//! is mandatory. A backend may answer the ones it does not support with `NotImplemented`.
+//! `list_buckets` must name every bucket and should date it; the service sorts and paginates them.
+//! The bucket policy hooks (`get_bucket_serving_policy`, `get_bucket_freeze`) have defaults.
+//!
+//! See `s3_server::storages::memory::MemoryStorage` (feature `test-utils`) for a complete in-memory backend.
+
+use s3_server::async_trait;
+use s3_server::dto::*;
+use s3_server::errors::{S3Error, S3ErrorCode, S3StorageError, S3StorageResult};
+use s3_server::{S3Service, S3Storage};
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use hyper::{Body, Method, Request};
+
+/// Creation dates of the buckets and objects by `(bucket, key)`
+#[derive(Default)]
+struct HashMapStorage {
+    buckets: Mutex<HashMap<String, String>>,
+    objects: Mutex<HashMap<(String, String), Vec<u8>>>,
+}
+
+fn error<E>(code: S3ErrorCode, message: &str) -> S3StorageError<E> {
+    S3Error::new(code, message).into()
+}
+
+/// names the operation in the message and in `x-amz-unimplemented-operation`
+fn not_implemented<T, E>(operation: &'static str) -> S3StorageResult<T, E> {
+    Err(S3Error::not_implemented(operation).into())
+}
+
+impl HashMapStorage {
+    fn check_bucket<E>(&self, bucket: &str) -> Result<(), S3StorageError<E>> {
+        if self.buckets.lock().unwrap().contains_key(bucket) {
+            Ok(())
+        } else {
+            Err(error(
+                S3ErrorCode::NoSuchBucket,
+                "The specified bucket does not exist.",
+            ))
+        }
+    }
+
+    fn get<E>(&self, bucket: &str, key: &str) -> Result<Vec<u8>, S3StorageError<E>> {
+        let objects = self.objects.lock().unwrap();
+        let data = objects.get(&(bucket.to_owned(), key.to_owned()));
+        data.cloned()
+            .ok_or_else(|| error(S3ErrorCode::NoSuchKey, "The specified key does not exist."))
+    }
+}
+
+#[async_trait]
+impl S3Storage for HashMapStorage {
+    async fn create_bucket(
+        &self,
+        input: CreateBucketRequest,
+    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
+        match self.buckets.lock().unwrap().entry(input.bucket) {
+            Entry::Occupied(_) => {
+                let err = CreateBucketError::BucketAlreadyOwnedByYou("The bucket exists.".into());
+                return Err(S3StorageError::Operation(err));
+            }
+            Entry::Vacant(entry) => {
+                let _ = entry.insert(chrono::Utc::now().to_rfc3339());
+            }
+        }
+        Ok(CreateBucketOutput::default())
+    }
+
+    async fn head_bucket(
+        &self,
+        input: HeadBucketRequest,
+    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
+        self.check_bucket(&input.bucket)?;
+        Ok(HeadBucketOutput::default())
+    }
+
+    async fn list_buckets(
+        &self,
+        _: ListBucketsRequest,
+    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
+        let buckets = self.buckets.lock().unwrap();
+        // the service sorts the buckets by name
+        let buckets = buckets.iter().map(|(name, creation_date)| Bucket {
+            name: Some(name.clone()),
+            creation_date: Some(creation_date.clone()),
+        });
+        Ok(ListBucketsOutput {
+            buckets: Some(buckets.collect()),
+            owner: None,
+        })
+    }
+
+    async fn put_object(
+        &self,
+        input: PutObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        self.check_bucket(&input.bucket)?;
+        let body = input
+            .body
+            .ok_or_else(|| error(S3ErrorCode::IncompleteBody, "No body"))?;
+        let data = body
+            .map_ok(|bytes| bytes.to_vec())
+            .try_concat()
+            .await
+            .map_err(|e| error(S3ErrorCode::InternalError, &e.to_string()))?;
+        let _ = self
+            .objects
+            .lock()
+            .unwrap()
+            .insert((input.bucket, input.key), data);
+        Ok(PutObjectOutput::default())
+    }
+
+    async fn get_object(
+        &self,
+        input: GetObjectRequest,
+    ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
+        let data = self.get(&input.bucket, &input.key)?;
+        Ok(GetObjectOutput {
+            content_length: Some(data.len() as i64),
+            body: Some(data.into()),
+            ..GetObjectOutput::default()
+        })
+    }
+
+    async fn head_object(
+        &self,
+        input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        let data = self.get(&input.bucket, &input.key)?;
+        Ok(HeadObjectOutput {
+            content_length: Some(data.len() as i64),
+            ..HeadObjectOutput::default()
+        })
+    }
+
+    async fn delete_object(
+        &self,
+        input: DeleteObjectRequest,
+    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
+        let _ = self
+            .objects
+            .lock()
+            .unwrap()
+            .remove(&(input.bucket, input.key));
+        Ok(DeleteObjectOutput::default())
+    }
+
+    async fn list_objects_v2(
+        &self,
+        input: ListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        self.check_bucket(&input.bucket)?;
+        let prefix = input.prefix.clone().unwrap_or_default();
+        let mut contents: Vec<Object> = self
+            .objects
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((bucket, key), _)| *bucket == input.bucket && key.starts_with(&prefix))
+            .map(|((_, key), data)| Object {
+                key: Some(key.clone()),
+                size: Some(data.len() as i64),
+                ..Object::default()
+            })
+            .collect();
+        contents.sort_by(|lhs, rhs| lhs.key.cmp(&rhs.key));
+        Ok(ListObjectsV2Output {
+            key_count: Some(contents.len() as i64),
+            contents: Some(contents),
+            name: Some(input.bucket),
+            prefix: input.prefix,
+            ..ListObjectsV2Output::default()
+        })
+    }
+
+    // the rest are mandatory too, but this backend does not support them
+
+    async fn complete_multipart_upload(
+        &self,
+        _: CompleteMultipartUploadRequest,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        not_implemented("CompleteMultipartUpload")
+    }
+
+    async fn copy_object(
+        &self,
+        _: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        not_implemented("CopyObject")
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        _: CreateMultipartUploadRequest,
+    ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError> {
+        not_implemented("CreateMultipartUpload")
+    }
+
+    async fn delete_bucket(
+        &self,
+        _: DeleteBucketRequest,
+    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
+        not_implemented("DeleteBucket")
+    }
+
+    async fn delete_objects(
+        &self,
+        _: DeleteObjectsRequest,
+    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
+        not_implemented("DeleteObjects")
+    }
+
+    async fn get_bucket_location(
+        &self,
+        _: GetBucketLocationRequest,
+    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
+        not_implemented("GetBucketLocation")
+    }
+
+    async fn list_objects(
+        &self,
+        _: ListObjectsRequest,
+    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
+        not_implemented("ListObjects")
+    }
+
+    async fn upload_part(
+        &self,
+        _: UploadPartRequest,
+    ) -> S3StorageResult<UploadPartOutput, UploadPartError> {
+        not_implemented("UploadPart")
+    }
+}
+
+/// an unsigned request, which is accepted by a service without an auth provider
+fn request(method: Method, uri: &str, body: &'static str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(format!("http://localhost{}", uri))
+        .header("x-amz-content-sha256", "UNSIGNED-PAYLOAD")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut service = S3Service::new(HashMapStorage::default());
+    // tells clients which operation is missing
+    service.set_debug_headers(true);
+
+    let requests = vec![
+        request(Method::PUT, "/asd", ""),
+        request(Method::PUT, "/asd/qwe", "Hello World!"),
+        request(Method::GET, "/asd/qwe", ""),
+        request(Method::GET, "/asd?list-type=2", ""),
+        request(Method::POST, "/asd/zxc?uploads", ""),
+    ];
+    for req in requests {
+        let line = format!("{} {}", req.method(), req.uri());
+        let res = service.hyper_call(req).await.map_err(anyhow::Error::msg)?;
+        let status = res.status();
+        if let Some(operation) = res.headers().get("x-amz-unimplemented-operation") {
+            println!("unimplemented: {:?}", operation);
+        }
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        println!(
+            "{} => {}\n{}\n",
+            line,
+            status,
+            String::from_utf8_lossy(&body)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Composes storage wrappers around a backend
+//!
+//! ```shell
+//! cargo run --example wrappers --features binary,test-utils
+//! ```
+//!
+//! Wrappers implement `S3Storage` by delegating to an inner storage, so they nest:
+//! here reads go through a `ReadOnly` into a `CircuitBreaker` into a `MemoryStorage`,
+//! and writes are rejected by the `ReadOnly` without reaching the others.
+
+use s3_server::dto::{CreateBucketRequest, PutObjectRequest};
+use s3_server::storages::memory::MemoryStorage;
+use s3_server::storages::wrappers::{CircuitBreaker, CircuitBreakerConfig, ReadOnly};
+use s3_server::{S3Service, S3Storage};
+
+use std::time::Duration;
+
+use anyhow::Result;
+use hyper::{Body, Method, Request};
+
+/// an unsigned request, which is accepted by a service without an auth provider
+fn request(method: Method, uri: &str, body: &'static str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(format!("http://localhost{}", uri))
+        .header("x-amz-content-sha256", "UNSIGNED-PAYLOAD")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // seed the backend before it is wrapped
+    let memory = MemoryStorage::new();
+    let _ = memory
+        .create_bucket(CreateBucketRequest {
+            bucket: "asd".into(),
+            ..CreateBucketRequest::default()
+        })
+        .await;
+    let _ = memory
+        .put_object(PutObjectRequest {
+            bucket: "asd".into(),
+            key: "qwe".into(),
+            body: Some(b"Hello World!".to_vec().into()),
+            ..PutObjectRequest::default()
+        })
+        .await;
+
+    // the outermost wrapper sees the request first
+    let config = CircuitBreakerConfig {
+        failure_threshold: 3,
+        cool_down: Duration::from_secs(30),
+        probes: 1,
+    };
+    let storage = ReadOnly::new(CircuitBreaker::new(memory, config));
+    println!("breaker: {:?}", storage.inner().state());
+
+    let service = S3Service::new(storage);
+
+    let requests = vec![
+        request(Method::GET, "/asd/qwe", ""),
+        request(Method::GET, "/asd?list-type=2", ""),
+        request(Method::PUT, "/asd/zxc", "rejected"),
+        request(Method::DELETE, "/asd/qwe", ""),
+    ];
+    for req in requests {
+        let line = format!("{} {}", req.method(), req.uri());
+        let res = service.hyper_call(req).await.map_err(anyhow::Error::msg)?;
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        println!(
+            "{} => {}\n{}\n",
+            line,
+            status,
+            String::from_utf8_lossy(&body)
+        );
+    }
+    Ok(())
+}
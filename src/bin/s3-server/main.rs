@@ -0,0 +1,577 @@
+//! ```shell
+//! s3-server 0.1.0-dev
+//!
+//! USAGE:
+//!     s3-server [OPTIONS] [SUBCOMMAND]
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!         --config <config>                          Loads a TOML config file. See `examples/server.toml`
+//!         --fs-index <fs-index>                      Serves listings from an object index log
+//!         --fs-root <fs-root>                        [default: .]
+//!         --host <host>                              [default: localhost]
+//!         --port <port>                              [default: 8014]
+//!         --access-key <access-key>
+//!         --secret-key <secret-key>
+//!         --audit-log <audit-log>                    Appends successful mutations to a JSON lines file
+//!         --record <record>                          Records raw requests and responses to a directory
+//!         --record-body-limit <record-body-limit>    Truncates recorded bodies to this size [default: 65536]
+//!
+//! SUBCOMMANDS:
+//!     help       Prints this message or the help of the given subcommand(s)
+//!     migrate    Copies the buckets and objects of the fs root to another fs root
+//!     reindex    Rebuilds the object index from the fs root
+//!     replay     Replays recorded requests against a copy of a fixture directory
+//!     selftest   Runs every implemented operation against the fs root in a temporary bucket
+//! ```
+//!
+//! Command line flags override the values in the config file.
+//!
+//! `s3-server replay <recordings> [--fixture <dir>] [--bless]` compares the responses with the recorded ones
+//! (or the `.expected` ones) and exits with an error if any differs. `--bless` writes the replayed responses
+//! as `.expected` files instead.
+//!
+//! `s3-server --fs-index <path> reindex [--check]` rebuilds the object index from the fs root,
+//! or reports the objects which differ from it. Run it while the server is stopped.
+//! A missing index is built when the server starts.
+//!
+//! `s3-server selftest` runs the scenarios of [`s3_server::selftest`] against the configured fs root,
+//! prints a support matrix and exits with an error if a scenario fails. The temporary bucket is deleted afterwards.
+//!
+//! `s3-server migrate <dir> [--include <prefix>] [--exclude <prefix>] [--journal <path>]` copies the objects
+//! of the fs root to the fs root `<dir>` by [`s3_server::migrate`], and exits with an error if an object
+//! failed to be copied. Run it again with the same journal to continue an interrupted migration.
+//!
+//! On unix, `SIGHUP` reloads the credentials from the config file.
+//! In-flight requests are not interrupted.
+
+#![forbid(unsafe_code)]
+
+mod config;
+
+use self::config::{AuthSection, ServerConfig};
+
+use s3_server::migrate::{self, MigrateOptions};
+use s3_server::replay::{self, Recorder};
+use s3_server::selftest;
+use s3_server::server;
+use s3_server::storages::fs::index::LogIndex;
+use s3_server::storages::fs::{FileSystem, FsInitError, InlineMigration};
+use s3_server::{ExtraHeaders, JsonLinesAuditSink, S3Service, TlsPolicy};
+use s3_server::{ReloadableAuth, SimpleAuth};
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use structopt::StructOpt;
+use tracing::{debug, error, info, warn};
+
+#[derive(StructOpt)]
+struct Args {
+    /// Loads a TOML config file. See `examples/server.toml`
+    #[structopt(long)]
+    config: Option<PathBuf>,
+
+    /// [default: .]
+    #[structopt(long)]
+    fs_root: Option<PathBuf>,
+
+    /// Serves listings from an object index log
+    #[structopt(long)]
+    fs_index: Option<PathBuf>,
+
+    /// [default: localhost]
+    #[structopt(long)]
+    host: Option<String>,
+
+    /// [default: 8014]
+    #[structopt(long)]
+    port: Option<u16>,
+
+    #[structopt(long, requires("secret-key"), display_order = 1000)]
+    access_key: Option<String>,
+
+    #[structopt(long, requires("access-key"), display_order = 1000)]
+    secret_key: Option<String>,
+
+    /// Appends successful mutations to a JSON lines file
+    #[structopt(long, display_order = 1001)]
+    audit_log: Option<PathBuf>,
+
+    /// Records raw requests and responses to a directory
+    #[structopt(long, display_order = 1002)]
+    record: Option<PathBuf>,
+
+    /// Truncates recorded bodies to this size
+    #[structopt(long, default_value = "65536", display_order = 1003)]
+    record_body_limit: usize,
+
+    /// Logs xml and json bodies up to this size for requests with `x-s3-server-log-body`
+    #[structopt(long, display_order = 1004)]
+    log_body_limit: Option<usize>,
+
+    /// Adds debug headers to error responses, such as `x-amz-unimplemented-operation`
+    #[structopt(long, display_order = 1005)]
+    debug_headers: bool,
+
+    /// Serves the effective configuration at `/_admin/config` and the runtime snapshot at `/_admin/runtime`, without authentication
+    #[structopt(long, display_order = 1006)]
+    admin: bool,
+
+    /// Serves the supported operations and limits at `/_admin/capabilities`, without authentication
+    #[structopt(long, display_order = 1006)]
+    capabilities: bool,
+
+    /// Rejects anonymous `GET /` with 403 instead of listing buckets
+    #[structopt(long, display_order = 1007)]
+    deny_root_listing: bool,
+
+    /// Rejects unsigned requests with 403 instead of serving them anonymously
+    #[structopt(long, display_order = 1007)]
+    deny_anonymous: bool,
+
+    /// Rejects requests signed by Signature Version 2 instead of verifying them
+    #[structopt(long, display_order = 1007)]
+    no_signature_v2: bool,
+
+    /// Rejects signed requests whose time is further from the server time [default: 900]
+    #[structopt(long, value_name = "SECS", display_order = 1007)]
+    max_clock_skew_secs: Option<u64>,
+
+    /// Rejects requests signed for another region, telling clients to retry in this one [default: any region]
+    #[structopt(long, display_order = 1007)]
+    region: Option<String>,
+
+    /// Serves virtual-hosted-style requests to `<bucket>.<BASE_DOMAIN>` [default: path-style only]
+    #[structopt(long, display_order = 1007)]
+    base_domain: Option<String>,
+
+    /// Rejects requests with credentials over plaintext HTTP with 403
+    #[structopt(long, display_order = 1008)]
+    require_tls_for_auth: bool,
+
+    /// Redirects the rejected `GET` and `HEAD` requests to https instead
+    #[structopt(long, requires("require-tls-for-auth"), display_order = 1008)]
+    redirect_to_https: bool,
+
+    /// Takes the scheme from `X-Forwarded-Proto`, for a server behind a TLS proxy
+    #[structopt(long, display_order = 1008)]
+    trust_forwarded_proto: bool,
+
+    /// Emits the object metadata `x-amz-meta-header-<NAME>` as the response header `<NAME>`
+    #[structopt(long, number_of_values = 1, value_name = "NAME", display_order = 1009)]
+    allow_extra_header: Vec<String>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Replays recorded requests against a copy of a fixture directory
+    Replay {
+        /// Directory of `.s3rec` files
+        recordings: PathBuf,
+
+        /// Directory which seeds the fs backend [default: empty]
+        #[structopt(long)]
+        fixture: Option<PathBuf>,
+
+        /// Writes the replayed responses as `.expected` files
+        #[structopt(long)]
+        bless: bool,
+    },
+
+    /// Rebuilds the object index from the fs root
+    Reindex {
+        /// Reports the differences from the fs root instead
+        #[structopt(long)]
+        check: bool,
+    },
+
+    /// Runs every implemented operation against the fs root in a temporary bucket
+    Selftest,
+
+    /// Copies the buckets and objects of the fs root to another fs root
+    Migrate {
+        /// The destination fs root
+        to: PathBuf,
+
+        /// Copies only the objects whose `bucket/key` starts with a prefix [default: all]
+        #[structopt(long, number_of_values = 1)]
+        include: Vec<String>,
+
+        /// Skips the objects whose `bucket/key` starts with a prefix
+        #[structopt(long, number_of_values = 1)]
+        exclude: Vec<String>,
+
+        /// Records the copied objects, and skips the ones it records
+        #[structopt(long)]
+        journal: Option<PathBuf>,
+
+        /// Max objects copied at the same time
+        #[structopt(long, default_value = "4")]
+        concurrency: usize,
+    },
+}
+
+pub fn setup_tracing() {
+    use tracing_error::ErrorLayer;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::{fmt, EnvFilter};
+
+    tracing_subscriber::fmt()
+        .event_format(fmt::format::Format::default().pretty())
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_timer(fmt::time::ChronoLocal::rfc3339())
+        .finish()
+        .with(ErrorLayer::default())
+        .init();
+}
+
+fn build_auth(auth_config: AuthSection) -> SimpleAuth {
+    let mut auth = SimpleAuth::new();
+    auth.register(auth_config.access_key, auth_config.secret_key.into_inner());
+    auth
+}
+
+/// Reloads the credentials from the config file
+fn reload_auth(args: &Args, auth: &ReloadableAuth<SimpleAuth>) -> Result<()> {
+    let path = match args.config {
+        Some(ref path) => path,
+        None => {
+            warn!("no config file, credentials are not reloaded");
+            return Ok(());
+        }
+    };
+    let mut config = ServerConfig::from_file(path)?;
+    config.override_with(args);
+    match config.auth {
+        Some(auth_config) => {
+            let _prev = auth.replace(build_auth(auth_config));
+            info!("credentials reloaded");
+        }
+        None => warn!("no credentials in config file, keeping the current ones"),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn spawn_reload_on_sighup(args: Args, auth: ReloadableAuth<SimpleAuth>) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    let task = async move {
+        while hangup.recv().await.is_some() {
+            if let Err(err) = reload_auth(&args, &auth) {
+                error!(%err, "failed to reload credentials");
+            }
+        }
+    };
+
+    #[cfg(all(feature = "tokio-unstable", tokio_unstable))]
+    let _handle = tokio::task::Builder::new()
+        .name("reload-credentials")
+        .spawn(task)?;
+
+    #[cfg(not(all(feature = "tokio-unstable", tokio_unstable)))]
+    let _handle = tokio::spawn(task);
+
+    Ok(())
+}
+
+/// Replays recordings against a fresh fs backend seeded from `fixture`
+async fn run_replay(recordings: &Path, fixture: Option<&Path>, bless: bool) -> Result<()> {
+    let root = env::temp_dir().join(format!("s3-replay-{}", std::process::id()));
+    match fixture {
+        Some(fixture) => replay::seed_root(fixture, &root)?,
+        None => fs::create_dir_all(&root)?,
+    }
+    let service = S3Service::new(FileSystem::new(&root)?);
+
+    let mut mismatches = 0_usize;
+    for (path, exchange) in replay::load_dir(recordings)? {
+        let actual = replay::replay(&service, &exchange)
+            .await
+            .map_err(|err| anyhow!(err))?;
+        if bless {
+            fs::write(path.with_extension("expected"), actual.encode_response())?;
+            println!("{}: blessed", path.display());
+            continue;
+        }
+        let diff = exchange.diff(&actual);
+        if diff.is_empty() {
+            println!("{}: ok", path.display());
+        } else {
+            mismatches = mismatches.wrapping_add(1);
+            println!("{}: MISMATCH", path.display());
+            for line in diff {
+                println!("    {}", line);
+            }
+        }
+    }
+
+    drop(service);
+    fs::remove_dir_all(&root)?;
+
+    if mismatches > 0 {
+        bail!("{} responses do not match", mismatches);
+    }
+    Ok(())
+}
+
+/// Rebuilds or checks the index of `fs`
+async fn run_reindex(fs: &FileSystem, check: bool) -> Result<()> {
+    if !check {
+        let count = fs.reindex().await?;
+        println!("indexed {} objects", count);
+        return Ok(());
+    }
+    let mismatches = fs.check_index().await?;
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        bail!("{} objects differ from the index", mismatches.len());
+    }
+    println!("the index is consistent");
+    Ok(())
+}
+
+/// Runs the self test against `fs` and prints the support matrix
+async fn run_selftest(fs: FileSystem) -> Result<()> {
+    let report = selftest::run_selftest(fs).await;
+    println!("{}", report);
+    if !report.is_success() {
+        bail!("the self test of bucket {} failed", report.bucket);
+    }
+    Ok(())
+}
+
+/// Copies the objects of `fs` to the fs root `to`, logging the progress
+async fn run_migrate(fs: &FileSystem, to: &Path, mut opts: MigrateOptions) -> Result<()> {
+    let dst = open_fs(to)?;
+    opts = opts.on_progress(|progress| {
+        let done = progress
+            .copied
+            .wrapping_add(progress.skipped)
+            .wrapping_add(progress.failed);
+        if done % 1000 == 0 {
+            info!(
+                copied = progress.copied,
+                skipped = progress.skipped,
+                failed = progress.failed,
+                bytes = progress.bytes,
+                "migrating"
+            );
+        }
+    });
+    let report = migrate::migrate(fs, &dst, &opts).await?;
+    println!("{}", report);
+    if !report.is_success() {
+        bail!("{} objects failed to be copied", report.failures.len());
+    }
+    Ok(())
+}
+
+/// Opens the fs root, explaining how to fix a bad one
+fn open_fs(root: &Path) -> Result<FileSystem> {
+    let fs = match FileSystem::open(root) {
+        Ok(fs) => fs,
+        Err(err) => {
+            let hint = match err {
+                FsInitError::NotFound { .. } => {
+                    "create the directory or point `--fs-root` (`fs.root`) at an existing one"
+                }
+                FsInitError::NotADirectory { .. } => {
+                    "`--fs-root` (`fs.root`) must be a directory, whose subdirectories are buckets"
+                }
+                FsInitError::NotWritable { .. } => {
+                    "check the permissions and mount options of the directory for the server user"
+                }
+                FsInitError::UnsupportedFilesystem { .. } => {
+                    "objects are replaced by renames, so move the root to a local filesystem such as ext4 or xfs"
+                }
+                _ => "check that the path can be resolved by the server user",
+            };
+            bail!("{}\nhint: {}", err, hint);
+        }
+    };
+    info!(capabilities = ?fs.capabilities(), "fs root checked");
+    for dir in fs.invalid_bucket_dirs()? {
+        warn!(
+            name = %dir.name,
+            "ignoring a directory under the fs root which is not a valid bucket name"
+        );
+    }
+    Ok(fs)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    setup_tracing();
+
+    let args: Args = Args::from_args();
+
+    if let Some(Command::Replay {
+        ref recordings,
+        ref fixture,
+        bless,
+    }) = args.command
+    {
+        return run_replay(recordings, fixture.as_deref(), bless).await;
+    }
+
+    let mut config = match args.config {
+        Some(ref path) => ServerConfig::from_file(path)?,
+        None => ServerConfig::default(),
+    };
+    config.override_with(&args);
+    debug!(?config.server, ?config.fs);
+
+    // setup the storage
+    let mut fs = open_fs(&config.fs.root)?;
+    #[cfg(feature = "mmap")]
+    fs.set_mmap_limit(config.fs.mmap_limit);
+    fs.set_verify_on_read(config.fs.verify_on_read);
+    fs.set_inline_threshold(config.fs.inline_threshold);
+    let is_new_index = match config.fs.index {
+        Some(ref path) => {
+            let is_new = !path.exists();
+            fs.set_index(LogIndex::open(path)?);
+            is_new
+        }
+        None => false,
+    };
+    debug!(?fs);
+
+    if let Some(Command::Reindex { check }) = args.command {
+        if config.fs.index.is_none() {
+            bail!("reindex requires `--fs-index` or `fs.index`");
+        }
+        return run_reindex(&fs, check).await;
+    }
+    if let Some(Command::Selftest) = args.command {
+        return run_selftest(fs).await;
+    }
+    if let Some(Command::Migrate {
+        ref to,
+        ref include,
+        ref exclude,
+        ref journal,
+        concurrency,
+    }) = args.command
+    {
+        let mut opts = MigrateOptions::new().concurrency(concurrency);
+        for prefix in include {
+            opts = opts.include(prefix.as_str());
+        }
+        for prefix in exclude {
+            opts = opts.exclude(prefix.as_str());
+        }
+        if let Some(journal) = journal {
+            opts = opts.journal(journal);
+        }
+        return run_migrate(&fs, to, opts).await;
+    }
+    if is_new_index {
+        let count = fs.reindex().await?;
+        info!(count, "object index built");
+    }
+    if config.fs.index.is_some() {
+        let migration = fs.migrate_inline().await?;
+        if migration != InlineMigration::default() {
+            info!(?migration, "inline objects migrated");
+        }
+    }
+
+    // setup the service
+    let mut service = S3Service::new(fs);
+    service.set_header_limits(config.limits);
+    service.set_bucket_cache(config.bucket_cache.to_config());
+
+    if let Some(ref dir) = args.record {
+        service.set_recorder(Recorder::new(dir, args.record_body_limit)?);
+        warn!(dir = %dir.display(), "recording raw requests, authorization headers are redacted");
+    }
+
+    service.set_debug_headers(args.debug_headers);
+    service.set_admin_endpoint(args.admin);
+    service.set_capabilities_endpoint(args.capabilities);
+    service.set_deny_root_listing(args.deny_root_listing);
+    service.set_anonymous_access(!args.deny_anonymous);
+    service.set_signature_v2(!args.no_signature_v2);
+    if let Some(secs) = args.max_clock_skew_secs {
+        service.set_max_clock_skew(Duration::from_secs(secs));
+    }
+    if let Some(ref region) = args.region {
+        service.set_region(region.as_str());
+    }
+    if let Some(ref base_domain) = args.base_domain {
+        service.set_base_domain(base_domain.as_str());
+    }
+    service.set_tls_policy(
+        TlsPolicy::default()
+            .require_tls_for_auth(args.require_tls_for_auth)
+            .redirect(args.redirect_to_https)
+            .trust_forwarded_proto(args.trust_forwarded_proto),
+    );
+    if !args.allow_extra_header.is_empty() {
+        let extra_headers = args
+            .allow_extra_header
+            .iter()
+            .fold(ExtraHeaders::new(), |extra_headers, name| {
+                extra_headers.allow(name)
+            });
+        service.set_extra_headers(extra_headers);
+    }
+
+    if let Some(limit) = args.log_body_limit {
+        service.set_body_log(limit);
+        warn!(
+            limit,
+            "logging bodies of requests with x-s3-server-log-body"
+        );
+    }
+
+    if let Some(auth_config) = config.auth {
+        let auth = ReloadableAuth::new(build_auth(auth_config));
+        debug!(?auth);
+        service.set_auth(auth.clone());
+
+        #[cfg(unix)]
+        spawn_reload_on_sighup(args, auth)?;
+    }
+
+    if let Some(audit_config) = config.audit {
+        let sink = JsonLinesAuditSink::open(&audit_config.path)?;
+        service.set_audit_sink(sink, audit_config.capacity)?;
+        info!(path = %audit_config.path.display(), "audit log enabled");
+    }
+
+    info!(
+        config = %serde_json::to_string(&service.effective_config())?,
+        "effective config"
+    );
+
+    let listener =
+        tokio::net::TcpListener::bind((config.server.host.as_str(), config.server.port)).await?;
+    let options = config.connections.to_options(&config.limits);
+    debug!(?options);
+
+    info!(
+        "server is running at http://{}:{}/",
+        config.server.host, config.server.port
+    );
+    server::serve(listener, service.into_shared(), options).await;
+
+    Ok(())
+}
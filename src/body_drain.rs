@@ -0,0 +1,270 @@
+//! Draining of request bodies which are not read to their end
+//!
+//! A request which fails before its body is read, such as by a signature or validation error,
+//! leaves the rest of its body in the connection, in front of the next request.
+//! [`S3Service::hyper_call`](crate::S3Service::hyper_call) tracks whether the body is read to its end.
+//! If it is not, the rest is drained before the response is sent,
+//! and a response whose request body can not be drained closes the connection by `Connection: close`.
+//!
+//! Request bodies are taken from a request only by [`BodyTracker::wrap`],
+//! which is enforced by the `disallowed-methods` of `clippy.toml`,
+//! so a handler can fail at any point without breaking the connection.
+
+use crate::utils::time;
+use crate::{Body, Response};
+
+use std::convert::TryFrom;
+use std::mem;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use futures::stream::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderValue, CONNECTION};
+use serde::{Serialize, Serializer};
+use tracing::debug;
+
+/// Limits of draining the rest of a request body, see [`S3Service::set_drain_limits`](crate::S3Service::set_drain_limits)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DrainLimits {
+    /// max bytes which are read and discarded
+    pub max_bytes: u64,
+    /// max time of reading the rest
+    #[serde(rename = "timeout_ms", serialize_with = "serialize_millis")]
+    pub timeout: Duration,
+}
+
+impl Default for DrainLimits {
+    /// 64 KiB in one second
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// serializes a timeout in milliseconds
+pub(crate) fn serialize_millis<S: Serializer>(
+    timeout: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    u64::try_from(timeout.as_millis())
+        .unwrap_or(u64::MAX)
+        .serialize(serializer)
+}
+
+/// The outcome of [`drain_body`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainOutcome {
+    /// the body is read to its end, after discarding this number of bytes
+    Drained(u64),
+    /// the rest of the body is larger than the limit
+    TooLarge,
+    /// the body does not end within the timeout
+    TimedOut,
+    /// the body fails, such as by a broken connection
+    Failed,
+}
+
+/// Reads and discards the rest of `body`, at most `max` bytes within `timeout`
+///
+/// A body which declares a longer length than `max` is not read.
+pub async fn drain_body(mut body: Body, max: u64, timeout: Duration) -> DrainOutcome {
+    if body.is_end_stream() {
+        return DrainOutcome::Drained(0);
+    }
+    if HttpBody::size_hint(&body).lower() > max {
+        return DrainOutcome::TooLarge;
+    }
+
+    let reading = Box::pin(async move {
+        let mut discarded: u64 = 0;
+        while let Some(chunk) = body.data().await {
+            let len = match chunk {
+                Ok(chunk) => u64::try_from(chunk.len()).unwrap_or(u64::MAX),
+                Err(_) => return DrainOutcome::Failed,
+            };
+            discarded = discarded.saturating_add(len);
+            if discarded > max {
+                return DrainOutcome::TooLarge;
+            }
+        }
+        DrainOutcome::Drained(discarded)
+    });
+    match future::select(reading, Box::pin(time::sleep(timeout))).await {
+        Either::Left((outcome, _)) => outcome,
+        Either::Right(((), _)) => DrainOutcome::TimedOut,
+    }
+}
+
+/// the state shared by a [`BodyTracker`] and its body
+#[derive(Debug, Default)]
+struct Shared {
+    /// whether the body has been read to its end
+    is_complete: AtomicBool,
+    /// the rest of a body which is dropped before its end
+    rest: Mutex<Option<Body>>,
+}
+
+impl Shared {
+    /// locks the rest, ignoring poisoning
+    fn lock_rest(&self) -> MutexGuard<'_, Option<Body>> {
+        match self.rest.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Tracks whether a request body is read to its end
+#[derive(Debug)]
+pub(crate) struct BodyTracker {
+    /// the state shared with the body
+    shared: Arc<Shared>,
+}
+
+/// A request body which reports its end to a [`BodyTracker`],
+/// and hands its rest back when it is dropped before the end
+struct TrackedStream {
+    /// the request body
+    body: Option<Body>,
+    /// the state shared with the tracker
+    shared: Arc<Shared>,
+}
+
+impl BodyTracker {
+    /// replaces the body of `req` by a tracked body
+    #[allow(clippy::disallowed_methods)] // the only place which takes a request body
+    pub(crate) fn wrap(req: &mut crate::Request) -> Self {
+        let body = mem::take(req.body_mut());
+        let shared = Arc::new(Shared::default());
+        if body.is_end_stream() {
+            shared.is_complete.store(true, Ordering::Release);
+            return Self { shared };
+        }
+        let stream = TrackedStream {
+            body: Some(body),
+            shared: Arc::clone(&shared),
+        };
+        *req.body_mut() = Body::wrap_stream(stream);
+        Self { shared }
+    }
+
+    /// Drains the rest of the body if it is not read to its end,
+    /// and closes the connection after `resp` if the rest can not be drained.
+    pub(crate) async fn settle(self, limits: DrainLimits, resp: &mut Response) {
+        if self.shared.is_complete.load(Ordering::Acquire) {
+            return;
+        }
+        let unread = self.shared.lock_rest().take();
+        // a rest which is still held elsewhere is not drained
+        let outcome = match unread {
+            Some(body) => drain_body(body, limits.max_bytes, limits.timeout).await,
+            None => DrainOutcome::Failed,
+        };
+        debug!(?outcome, "drained the rest of a request body");
+        if let DrainOutcome::Drained(_) = outcome {
+            return;
+        }
+        let _prev = resp
+            .headers_mut()
+            .insert(CONNECTION, HeaderValue::from_static("close"));
+    }
+}
+
+impl Stream for TrackedStream {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let body = match self.body {
+            Some(ref mut body) => body,
+            None => return Poll::Ready(None),
+        };
+        let ret = Pin::new(body).poll_data(cx);
+        if matches!(ret, Poll::Ready(None)) {
+            self.shared.is_complete.store(true, Ordering::Release);
+            self.body = None;
+        }
+        ret
+    }
+}
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        if let Some(body) = self.body.take() {
+            if body.is_end_stream() {
+                self.shared.is_complete.store(true, Ordering::Release);
+            } else {
+                *self.shared.lock_rest() = Some(body);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+
+    fn chunked(chunks: &[&'static str]) -> Body {
+        let chunks: Vec<Result<Bytes, std::io::Error>> = chunks
+            .iter()
+            .map(|&chunk| Ok(Bytes::from_static(chunk.as_bytes())))
+            .collect();
+        Body::wrap_stream(stream::iter(chunks))
+    }
+
+    #[test]
+    fn drain() {
+        let timeout = Duration::from_secs(5);
+        let outcome = block_on(drain_body(chunked(&["abc", "de"]), 5, timeout));
+        assert_eq!(outcome, DrainOutcome::Drained(5));
+        let outcome = block_on(drain_body(chunked(&["abc", "def"]), 5, timeout));
+        assert_eq!(outcome, DrainOutcome::TooLarge);
+        let outcome = block_on(drain_body(Body::from("abcdef"), 5, timeout));
+        assert_eq!(outcome, DrainOutcome::TooLarge);
+        assert_eq!(
+            block_on(drain_body(Body::empty(), 0, timeout)),
+            DrainOutcome::Drained(0)
+        );
+
+        let (_sender, pending) = Body::channel();
+        let outcome = block_on(drain_body(pending, 5, Duration::from_millis(10)));
+        assert_eq!(outcome, DrainOutcome::TimedOut);
+    }
+
+    #[test]
+    #[allow(clippy::disallowed_methods)]
+    fn tracker() {
+        let settle = |read: usize| {
+            let mut req = crate::Request::new(chunked(&["abc", "def", "ghi"]));
+            let tracker = BodyTracker::wrap(&mut req);
+            let mut body = mem::take(req.body_mut());
+            for _ in 0..read {
+                let _chunk = block_on(body.next());
+            }
+            drop(body);
+            let limits = DrainLimits {
+                max_bytes: 3,
+                ..DrainLimits::default()
+            };
+            let mut resp = Response::new(Body::empty());
+            block_on(tracker.settle(limits, &mut resp));
+            resp.headers().get(CONNECTION).cloned()
+        };
+        // read to its end, drained, or too large to drain
+        assert_eq!(settle(4), None);
+        assert_eq!(settle(2), None);
+        assert_eq!(
+            settle(1).as_ref().map(HeaderValue::as_bytes),
+            Some(&b"close"[..])
+        );
+    }
+}
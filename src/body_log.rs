@@ -0,0 +1,238 @@
+//! Bounded logging of document bodies for debugging
+
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{CONTENT_TYPE, X_S3_SERVER_LOG_BODY};
+use crate::ops::ReqContext;
+use crate::{Body, Request, Response};
+
+use std::mem;
+
+use hyper::body::Bytes;
+use tracing::info;
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+use xml::writer::EmitterConfig;
+
+/// operations whose request body is an xml document
+const DOCUMENT_OPERATIONS: &[&str] = &[
+    "CompleteMultipartUpload",
+    "CreateBucket",
+    "DeleteObjects",
+    "PutBucketVersioning",
+];
+
+/// operations whose response body is object data
+const DATA_OPERATIONS: &[&str] = &["GetObject"];
+
+/// the logged value of a credential
+const REDACTED: &str = "REDACTED";
+
+/// Logs the document bodies of the requests which ask for it by [`X_S3_SERVER_LOG_BODY`]
+///
+/// Object data is never logged.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BodyLog {
+    /// max logged bytes of a body
+    limit: usize,
+}
+
+impl BodyLog {
+    /// Constructs a new `BodyLog`
+    pub(crate) const fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+
+    /// Returns the max logged bytes of a body
+    pub(crate) const fn limit(self) -> usize {
+        self.limit
+    }
+
+    /// Checks whether `req` asks for its bodies to be logged
+    pub(crate) fn is_requested(req: &Request) -> bool {
+        req.headers().contains_key(&*X_S3_SERVER_LOG_BODY)
+    }
+
+    /// Buffers and logs the request body of a document operation
+    pub(crate) async fn tee_request(
+        &self,
+        operation: &str,
+        ctx: &mut ReqContext<'_>,
+    ) -> S3Result<()> {
+        if !DOCUMENT_OPERATIONS.contains(&operation) {
+            return Ok(());
+        }
+        let body = mem::take(&mut ctx.body);
+        let bytes = hyper::body::to_bytes(body)
+            .await
+            .map_err(|err| invalid_request!("Can not obtain the whole request body.", err))?;
+        let content_type = ctx.headers.get(CONTENT_TYPE).unwrap_or("application/xml");
+        if let Some(text) = self.render(content_type, &bytes) {
+            info!(operation, len = bytes.len(), body = %text, "request body");
+        }
+        ctx.body = Body::from(bytes);
+        Ok(())
+    }
+
+    /// Buffers and logs the response body, unless it is object data
+    pub(crate) async fn tee_response(
+        &self,
+        operation: &str,
+        ret: S3Result<Response>,
+    ) -> S3Result<Response> {
+        if DATA_OPERATIONS.contains(&operation) {
+            return ret;
+        }
+        let mut resp = match ret {
+            Ok(resp) => resp,
+            Err(err) => {
+                log_error(operation, &err);
+                return Err(err);
+            }
+        };
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        if document_kind(content_type).is_none() {
+            return Ok(resp);
+        }
+        let content_type = content_type.to_owned();
+        let body = mem::take(resp.body_mut());
+        let bytes: Bytes = hyper::body::to_bytes(body)
+            .await
+            .map_err(|err| internal_error!(err))?;
+        if let Some(text) = self.render(&content_type, &bytes) {
+            info!(operation, len = bytes.len(), body = %text, "response body");
+        }
+        *resp.body_mut() = Body::from(bytes);
+        Ok(resp)
+    }
+
+    /// Renders a document body for the log, or `None` if it is not a document.
+    ///
+    /// An xml body within the limit is pretty-printed.
+    /// A longer body is cut at the limit. Credentials are redacted in both cases.
+    fn render(self, content_type: &str, bytes: &[u8]) -> Option<String> {
+        let kind = document_kind(content_type)?;
+        let text = match bytes.get(..self.limit) {
+            Some(prefix) if prefix.len() < bytes.len() => format!(
+                "{}... ({} bytes)",
+                String::from_utf8_lossy(prefix),
+                bytes.len()
+            ),
+            _ => match kind {
+                DocumentKind::Xml => pretty_xml(bytes),
+                DocumentKind::Json => None,
+            }
+            .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned()),
+        };
+        Some(redact(&text))
+    }
+}
+
+/// the format of a document body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DocumentKind {
+    /// xml
+    Xml,
+    /// json
+    Json,
+}
+
+/// determines the format of a document body by its content type
+fn document_kind(content_type: &str) -> Option<DocumentKind> {
+    let mime: mime::Mime = content_type.parse().ok()?;
+    let sub = mime.subtype();
+    let suffix = mime.suffix();
+    if sub == mime::XML || suffix == Some(mime::XML) {
+        Some(DocumentKind::Xml)
+    } else if sub == mime::JSON || suffix == Some(mime::JSON) {
+        Some(DocumentKind::Json)
+    } else {
+        None
+    }
+}
+
+/// logs an error of an operation
+fn log_error(operation: &str, err: &S3Error) {
+    info!(operation, code = %err.code(), error = %redact(&err.to_string()), "response error");
+}
+
+/// indents an xml document
+fn pretty_xml(bytes: &[u8]) -> Option<String> {
+    let config = ParserConfig::new()
+        .trim_whitespace(true)
+        .ignore_comments(false);
+    let mut out = Vec::with_capacity(bytes.len().saturating_mul(2));
+    {
+        let mut writer = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(&mut out);
+        for event in EventReader::new_with_config(bytes, config) {
+            let event = event.ok()?;
+            if let XmlEvent::Whitespace(_) = event {
+                continue;
+            }
+            if let Some(event) = event.as_writer_event() {
+                writer.write(event).ok()?;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// replaces the values of credentials in xml elements and json strings
+///
+/// A value cut by the limit is redacted too. Principals and other identifiers are kept.
+fn redact(text: &str) -> String {
+    let xml = static_regex!(
+        "<(AccessKeyId|SecretAccessKey|SecretKey|SessionToken|SecurityToken|Password|Signature)>[^<]*"
+    );
+    let json = static_regex!(
+        r#""(AccessKeyId|SecretAccessKey|SecretKey|SessionToken|SecurityToken|Password|Signature)"\s*:\s*"(?:[^"\\]|\\.)*"?"#
+    );
+    let text = xml.replace_all(text, format!("<${{1}}>{}", REDACTED).as_str());
+    let text = json.replace_all(&text, format!("\"${{1}}\": \"{}\"", REDACTED).as_str());
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let log = BodyLog::new(4096);
+        let xml = b"<Delete><Object><Key>qwe</Key></Object><Quiet>true</Quiet></Delete>";
+        let text = log.render("application/xml", xml).unwrap();
+        assert!(
+            text.contains("\n  <Object>\n    <Key>qwe</Key>\n  </Object>"),
+            "{}",
+            text
+        );
+
+        assert_eq!(log.render("application/octet-stream", xml), None);
+        assert_eq!(log.render("", xml), None);
+
+        let log = BodyLog::new(8);
+        let text = log.render("application/xml", xml).unwrap();
+        assert_eq!(text, format!("<Delete>... ({} bytes)", xml.len()));
+    }
+
+    #[test]
+    fn redact_credentials() {
+        let xml = "<Credentials><AccessKeyId>AKIA</AccessKeyId>\
+                   <SecretAccessKey>secret</SecretAccessKey><SessionToken>tok";
+        assert_eq!(
+            redact(xml),
+            "<Credentials><AccessKeyId>REDACTED</AccessKeyId>\
+             <SecretAccessKey>REDACTED</SecretAccessKey><SessionToken>REDACTED"
+        );
+
+        let json = r#"{"Principal": {"AWS": "arn:aws:iam::1:root"}, "SecretKey" : "a\"b", "Password": "cut"#;
+        assert_eq!(
+            redact(json),
+            r#"{"Principal": {"AWS": "arn:aws:iam::1:root"}, "SecretKey": "REDACTED", "Password": "REDACTED""#
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Bucket existence cache
+
+use crate::dto::HeadBucketRequest;
+use crate::errors::{S3Error, S3ErrorCode, S3Result, S3StorageError};
+use crate::storage::S3Storage;
+use crate::utils::scope::{self, Scoped};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Serializer};
+
+/// Options of the bucket existence cache, see [`S3Service::set_bucket_cache`](crate::S3Service::set_bucket_cache)
+///
+/// Both kinds of entries are disabled by default.
+///
+/// It is serialized with the TTLs in milliseconds, as `positive_ttl_ms` and `negative_ttl_ms`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BucketCacheConfig {
+    /// how long an existing bucket is remembered
+    #[serde(rename = "positive_ttl_ms", serialize_with = "serialize_millis")]
+    pub positive_ttl: Option<Duration>,
+    /// how long a missing bucket is remembered
+    #[serde(rename = "negative_ttl_ms", serialize_with = "serialize_millis")]
+    pub negative_ttl: Option<Duration>,
+}
+
+/// serializes a TTL in milliseconds
+#[allow(clippy::ref_option)] // required by `serialize_with`
+pub(crate) fn serialize_millis<S: Serializer>(
+    ttl: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let millis = ttl.map(|ttl| u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX));
+    millis.serialize(serializer)
+}
+
+impl BucketCacheConfig {
+    /// Checks whether any kind of entries is enabled
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.positive_ttl.is_some() || self.negative_ttl.is_some()
+    }
+}
+
+/// Remembers whether buckets exist
+///
+/// Entries are only invalidated by `CreateBucket` and `DeleteBucket` through the service,
+/// so a change made directly to the storage is seen after the entry expires.
+#[derive(Debug)]
+pub(crate) struct BucketCache {
+    /// options
+    config: BucketCacheConfig,
+    /// existence and expiry by bucket, where `None` never expires
+    entries: Mutex<HashMap<String, (bool, Option<Instant>)>>,
+}
+
+impl BucketCache {
+    /// Returns the options
+    pub(crate) const fn config(&self) -> BucketCacheConfig {
+        self.config
+    }
+
+    /// Constructs a new `BucketCache`
+    pub(crate) fn new(config: BucketCacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// locks the entries, ignoring poisoning
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (bool, Option<Instant>)>> {
+        match self.entries.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Returns whether `bucket` exists, if it is remembered at `now`
+    pub(crate) fn get(&self, bucket: &str, now: Instant) -> Option<bool> {
+        let mut entries = self.lock();
+        match entries.get(bucket) {
+            Some(&(exists, expiry)) if expiry.map_or(true, |expiry| now < expiry) => Some(exists),
+            Some(_) => {
+                let _prev = entries.remove(bucket);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remembers whether `bucket` exists from `now`, if that kind of entries is enabled.
+    ///
+    /// A TTL which overflows the clock never expires.
+    pub(crate) fn insert(&self, bucket: &str, exists: bool, now: Instant) {
+        let ttl = if exists {
+            self.config.positive_ttl
+        } else {
+            self.config.negative_ttl
+        };
+        if let Some(ttl) = ttl {
+            let expiry = now.checked_add(ttl);
+            let _prev = self.lock().insert(bucket.to_owned(), (exists, expiry));
+        }
+    }
+
+    /// Forgets `bucket`
+    pub(crate) fn invalidate(&self, bucket: &str) {
+        let _prev = self.lock().remove(bucket);
+    }
+
+    /// Checks that `bucket` exists before an object operation is dispatched.
+    ///
+    /// Returns whether the existence is verified,
+    /// or a `NoSuchBucket` error when the bucket is known to be missing.
+    /// Other errors of `HeadBucket` are left to the operation.
+    pub(crate) async fn check(
+        &self,
+        bucket: &str,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<bool> {
+        match self.get(bucket, Instant::now()) {
+            Some(true) => return Ok(true),
+            Some(false) => return Err(no_such_bucket()),
+            None => {}
+        }
+
+        let input = HeadBucketRequest {
+            bucket: bucket.to_owned(),
+            expected_bucket_owner: None,
+        };
+        let err: S3Error = match storage.head_bucket(input).await {
+            Ok(_) => {
+                self.insert(bucket, true, Instant::now());
+                return Ok(true);
+            }
+            Err(S3StorageError::Operation(e)) => e.into(),
+            Err(S3StorageError::Other(e)) => e,
+        };
+        if err.code() == S3ErrorCode::NoSuchBucket {
+            self.insert(bucket, false, Instant::now());
+            return Err(err);
+        }
+        Ok(false)
+    }
+}
+
+/// the error of a bucket which is remembered as missing
+fn no_such_bucket() -> S3Error {
+    S3Error::new(
+        S3ErrorCode::NoSuchBucket,
+        "The specified bucket does not exist.",
+    )
+}
+
+thread_local! {
+    /// whether the bucket of the request being polled on this thread is verified
+    static VERIFIED: RefCell<Option<bool>> = RefCell::new(None);
+}
+
+/// Returns whether the service has verified that the bucket of the current request exists.
+///
+/// Storages may skip their own existence check of the bucket when it returns `true`.
+/// It is always `false` when the bucket cache is disabled or outside of a request.
+#[must_use]
+pub fn is_bucket_verified() -> bool {
+    scope::current(&VERIFIED).unwrap_or(false)
+}
+
+/// Sets the flag of [`is_bucket_verified`] while polling `future`
+pub(crate) const fn scope<F>(verified: bool, future: F) -> Scoped<bool, F> {
+    scope::scope(&VERIFIED, verified, future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry() {
+        let cache = BucketCache::new(BucketCacheConfig {
+            positive_ttl: Some(Duration::from_secs(10)),
+            negative_ttl: None,
+        });
+        let now = Instant::now();
+
+        cache.insert("asd", true, now);
+        cache.insert("qwe", false, now);
+        assert_eq!(cache.get("asd", now), Some(true));
+        assert_eq!(cache.get("qwe", now), None);
+
+        assert_eq!(cache.get("asd", now + Duration::from_secs(9)), Some(true));
+        assert_eq!(cache.get("asd", now + Duration::from_secs(10)), None);
+        assert_eq!(cache.get("asd", now), None);
+
+        cache.insert("asd", true, now);
+        cache.invalidate("asd");
+        assert_eq!(cache.get("asd", now), None);
+    }
+
+    #[test]
+    fn unbounded_ttl() {
+        let cache = BucketCache::new(BucketCacheConfig {
+            positive_ttl: Some(Duration::from_secs(u64::MAX)),
+            negative_ttl: None,
+        });
+        let now = Instant::now();
+
+        cache.insert("asd", true, now);
+        assert_eq!(
+            cache.get("asd", now + Duration::from_secs(1000)),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn verified() {
+        assert!(!is_bucket_verified());
+        let seen = futures::executor::block_on(scope(true, async { is_bucket_verified() }));
+        assert!(seen);
+        assert!(!is_bucket_verified());
+    }
+}
@@ -0,0 +1,103 @@
+//! Per-bucket write freeze
+
+use crate::errors::{S3Error, S3ErrorCode};
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// An administrative freeze of a bucket for maintenance windows
+///
+/// Reads proceed normally while writes are rejected with `AccessDenied` (403).
+/// `CreateMultipartUpload` is always rejected, so the uploads which may complete under
+/// `allow_multipart_completion` are exactly the ones started before the freeze.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketFreeze {
+    /// allows `UploadPart` and `CompleteMultipartUpload`
+    #[serde(default)]
+    pub allow_multipart_completion: bool,
+
+    /// seconds which rejected clients are told to wait (`Retry-After`)
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+}
+
+impl BucketFreeze {
+    /// Checks whether the freeze rejects `operation`
+    #[must_use]
+    pub fn rejects(&self, operation: &str) -> bool {
+        match operation {
+            "UploadPart" | "CompleteMultipartUpload" => !self.allow_multipart_completion,
+            _ => is_mutating(operation),
+        }
+    }
+
+    /// The error of a rejected operation
+    pub(crate) fn rejection(&self, bucket: &str) -> S3Error {
+        let builder = S3Error::from_code(S3ErrorCode::AccessDenied).message(format!(
+            "The bucket {} is frozen for maintenance. Writes are rejected until it is unfrozen.",
+            bucket
+        ));
+        match self.retry_after {
+            Some(secs) => builder.retry_after(Duration::from_secs(secs)),
+            None => builder,
+        }
+        .finish()
+    }
+}
+
+/// Returns whether `operation` writes to its bucket
+pub(crate) fn is_mutating(operation: &str) -> bool {
+    matches!(
+        operation,
+        "AbortMultipartUpload"
+            | "CompleteMultipartUpload"
+            | "CopyObject"
+            | "CreateBucket"
+            | "CreateMultipartUpload"
+            | "DeleteBucket"
+            | "DeleteBucketCors"
+            | "DeleteBucketPolicy"
+            | "DeleteBucketTagging"
+            | "DeleteObject"
+            | "DeleteObjects"
+            | "PutBucketAcl"
+            | "PutBucketCors"
+            | "PutBucketPolicy"
+            | "PutBucketTagging"
+            | "PutBucketVersioning"
+            | "PutObject"
+            | "PutObjectAcl"
+            | "PutObjectLegalHold"
+            | "PutObjectLockConfiguration"
+            | "PutObjectRetention"
+            | "RestoreObject"
+            | "UploadPart"
+            | "UploadPartCopy"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects() {
+        let mut freeze = BucketFreeze::default();
+        assert!(freeze.rejects("PutObject"));
+        assert!(freeze.rejects("CreateMultipartUpload"));
+        assert!(freeze.rejects("CompleteMultipartUpload"));
+        assert!(!freeze.rejects("GetObject"));
+        assert!(!freeze.rejects("ListObjectsV2"));
+
+        freeze.allow_multipart_completion = true;
+        assert!(freeze.rejects("CreateMultipartUpload"));
+        assert!(!freeze.rejects("UploadPart"));
+        assert!(!freeze.rejects("CompleteMultipartUpload"));
+
+        freeze.retry_after = Some(60);
+        let err = freeze.rejection("asd");
+        assert_eq!(err.code(), S3ErrorCode::AccessDenied);
+        assert_eq!(err.into_xml_response().retry_after, Some(60));
+    }
+}
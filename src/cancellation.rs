@@ -0,0 +1,110 @@
+//! Request cancellation
+
+use crate::utils::scope::{self, Scoped};
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A token which is cancelled when the request future is dropped before completion
+///
+/// The future returned by the storage is dropped together with the request,
+/// so only work detached from it (spawned tasks, blocking threads) needs to check the token.
+/// Storages get the token of the current request by [`CancellationToken::current`].
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    /// cancelled flag
+    cancelled: Arc<AtomicBool>,
+}
+
+thread_local! {
+    /// the token of the request being polled on this thread
+    static CURRENT: RefCell<Option<CancellationToken>> = RefCell::new(None);
+}
+
+impl CancellationToken {
+    /// Constructs a new `CancellationToken`
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the token of the request being handled,
+    /// or a token which is never cancelled outside of a request
+    #[must_use]
+    pub fn current() -> Self {
+        scope::current(&CURRENT).unwrap_or_default()
+    }
+
+    /// Cancels the token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Checks whether the token is cancelled
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Makes the token current while polling `future`
+    pub(crate) const fn scope<F>(self, future: F) -> Scoped<Self, F> {
+        scope::scope(&CURRENT, self, future)
+    }
+}
+
+/// Cancels a token when dropped, unless disarmed
+#[derive(Debug)]
+pub(crate) struct CancelOnDrop {
+    /// token
+    token: Option<CancellationToken>,
+}
+
+impl CancelOnDrop {
+    /// Constructs a new `CancelOnDrop`
+    pub(crate) const fn new(token: CancellationToken) -> Self {
+        Self { token: Some(token) }
+    }
+
+    /// Drops the guard without cancelling the token
+    pub(crate) fn disarm(mut self) {
+        drop(self.token.take());
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(ref token) = self.token {
+            token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard() {
+        let token = CancellationToken::new();
+        CancelOnDrop::new(token.clone()).disarm();
+        assert!(!token.is_cancelled());
+
+        drop(CancelOnDrop::new(token.clone()));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn current() {
+        assert!(!CancellationToken::current().is_cancelled());
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let seen = futures::executor::block_on(
+            token.scope(async { CancellationToken::current().is_cancelled() }),
+        );
+        assert!(seen);
+
+        assert!(!CancellationToken::current().is_cancelled());
+    }
+}
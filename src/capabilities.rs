@@ -0,0 +1,249 @@
+//! Capabilities document of a service
+//!
+//! Clients which are not built on an SDK, such as web consoles, read the document
+//! to hide the features which a deployment does not support, instead of probing by trial and error.
+//! Its shape is versioned by [`Capabilities::SCHEMA_VERSION`]: fields may be added,
+//! but a renamed or removed field bumps the version.
+
+use crate::errors::S3Result;
+use crate::operation_policy::OperationPolicy;
+use crate::output::S3Output;
+use crate::size_limits::SizeLimits;
+use crate::utils::ResponseExt;
+use crate::{Body, Response};
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+/// The capabilities of a service, see [`S3Service::capabilities`](crate::S3Service::capabilities)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// the version of the shape, see [`Capabilities::SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// the version of this crate
+    pub version: &'static str,
+    /// the operations of the service, by name
+    pub operations: Vec<OperationSupport>,
+    /// how requests are authenticated
+    pub auth: AuthCapabilities,
+    /// the checksum algorithms which are verified
+    pub checksum_algorithms: Vec<&'static str>,
+    /// the limits of requests
+    pub limits: RequestLimits,
+}
+
+/// The support of an operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct OperationSupport {
+    /// the operation, such as `GetObject`
+    pub name: &'static str,
+    /// whether it is supported
+    pub status: OperationStatus,
+}
+
+/// Whether an operation is supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    /// served by the service, unless the storage turns out not to implement it
+    Supported,
+    /// disabled by the [`OperationPolicy`]
+    Disabled,
+    /// the storage has answered it with `NotImplemented`
+    Unimplemented,
+}
+
+/// How requests are authenticated
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct AuthCapabilities {
+    /// whether signatures (v4) are checked, which needs an auth provider
+    pub signature_v4: bool,
+    /// whether signatures (v2) are checked, which needs an auth provider and is rejected otherwise
+    pub signature_v2: bool,
+    /// how a request may be signed: `header`, `presigned_url`, `post_policy` and `streaming` chunks
+    pub signing_methods: Vec<&'static str>,
+    /// whether unsigned requests are served
+    pub anonymous_access: bool,
+    /// whether anonymous `GET /` is rejected
+    pub deny_root_listing: bool,
+}
+
+/// The limits of requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct RequestLimits {
+    /// max bytes of a `PutObject`
+    pub max_put_size: u64,
+    /// max bytes of an `UploadPart`
+    pub max_part_size: u64,
+    /// max bytes of an object completed by `CompleteMultipartUpload`
+    pub max_object_size: u64,
+    /// max parts of a multipart upload
+    pub max_parts: i64,
+    /// max parts of a `ListParts` page
+    pub max_list_parts: i64,
+    /// max keys of a `DeleteObjects`
+    pub max_delete_objects: usize,
+}
+
+/// the state of a service which determines its capabilities
+pub(crate) struct ServiceState<'a> {
+    /// the routed operations
+    pub(crate) operations: &'a [&'static str],
+    /// the operations which the storage has answered with `NotImplemented`
+    pub(crate) unimplemented: &'a BTreeSet<&'static str>,
+    /// the operation policy
+    pub(crate) operation_policy: &'a OperationPolicy,
+    /// whether there is an auth provider
+    pub(crate) auth: bool,
+    /// whether unsigned requests are served
+    pub(crate) anonymous_access: bool,
+    /// whether anonymous `GET /` is rejected
+    pub(crate) deny_root_listing: bool,
+    /// whether Signature Version 2 is verified
+    pub(crate) signature_v2: bool,
+    /// the size limits
+    pub(crate) size_limits: SizeLimits,
+}
+
+impl Capabilities {
+    /// The version of the shape of the document
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    /// collects the capabilities of a service
+    pub(crate) fn new(state: &ServiceState<'_>) -> Self {
+        let mut names = state.operations.to_vec();
+        names.sort_unstable();
+        names.dedup();
+        let operations = names
+            .into_iter()
+            .map(|name| {
+                let status = if !state.operation_policy.allows(name) {
+                    OperationStatus::Disabled
+                } else if state.unimplemented.contains(name) {
+                    OperationStatus::Unimplemented
+                } else {
+                    OperationStatus::Supported
+                };
+                OperationSupport { name, status }
+            })
+            .collect();
+
+        let signing_methods = if state.auth {
+            vec!["header", "presigned_url", "post_policy", "streaming"]
+        } else {
+            Vec::new()
+        };
+        // `Content-MD5` of `DeleteObjects`, and `x-amz-content-sha256` of a signed payload
+        let mut checksum_algorithms = vec!["MD5"];
+        if state.auth {
+            checksum_algorithms.push("SHA256");
+        }
+
+        Self {
+            schema_version: Self::SCHEMA_VERSION,
+            version: env!("CARGO_PKG_VERSION"),
+            operations,
+            auth: AuthCapabilities {
+                signature_v4: state.auth,
+                signature_v2: state.auth && state.signature_v2,
+                signing_methods,
+                anonymous_access: state.anonymous_access,
+                deny_root_listing: state.deny_root_listing,
+            },
+            checksum_algorithms,
+            limits: RequestLimits {
+                max_put_size: state.size_limits.max_put_size,
+                max_part_size: state.size_limits.max_part_size,
+                max_object_size: state.size_limits.max_object_size,
+                max_parts: crate::ops::MAX_PART_NUMBER,
+                max_list_parts: crate::ops::MAX_LIST_PARTS,
+                max_delete_objects: crate::ops::MAX_DELETE_OBJECTS,
+            },
+        }
+    }
+}
+
+impl S3Output for Capabilities {
+    fn try_into_response(self) -> S3Result<Response> {
+        let body = serde_json::to_vec(&self).map_err(|e| internal_error!(e))?;
+        let mut res = Response::new(Body::from(body));
+        res.set_mime(&mime::APPLICATION_JSON)
+            .map_err(|e| internal_error!(e))?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn capabilities(auth: bool) -> Capabilities {
+        let unimplemented = std::iter::once("ListParts").collect();
+        let state = ServiceState {
+            operations: &["PutObject", "GetObject", "ListParts", "DeleteBucket"],
+            unimplemented: &unimplemented,
+            operation_policy: &OperationPolicy::deny(&["DeleteBucket"]),
+            auth,
+            anonymous_access: true,
+            deny_root_listing: true,
+            signature_v2: true,
+            size_limits: SizeLimits {
+                max_put_size: 1024,
+                max_part_size: 512,
+                max_object_size: 4096,
+            },
+        };
+        let mut capabilities = Capabilities::new(&state);
+        capabilities.version = "0.0.0";
+        capabilities
+    }
+
+    /// The document only gains fields, unless [`Capabilities::SCHEMA_VERSION`] is bumped
+    #[test]
+    fn schema() {
+        let expected = json!({
+            "schema_version": 1,
+            "version": "0.0.0",
+            "operations": [
+                { "name": "DeleteBucket", "status": "disabled" },
+                { "name": "GetObject", "status": "supported" },
+                { "name": "ListParts", "status": "unimplemented" },
+                { "name": "PutObject", "status": "supported" },
+            ],
+            "auth": {
+                "signature_v4": true,
+                "signature_v2": true,
+                "signing_methods": ["header", "presigned_url", "post_policy", "streaming"],
+                "anonymous_access": true,
+                "deny_root_listing": true,
+            },
+            "checksum_algorithms": ["MD5", "SHA256"],
+            "limits": {
+                "max_put_size": 1024,
+                "max_part_size": 512,
+                "max_object_size": 4096,
+                "max_parts": 10000,
+                "max_list_parts": 1000,
+                "max_delete_objects": 1000,
+            },
+        });
+        let value = serde_json::to_value(capabilities(true)).unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn anonymous() {
+        let capabilities = capabilities(false);
+        assert!(!capabilities.auth.signature_v4);
+        assert!(!capabilities.auth.signature_v2);
+        assert!(capabilities.auth.signing_methods.is_empty());
+        assert_eq!(capabilities.checksum_algorithms, ["MD5"]);
+    }
+}
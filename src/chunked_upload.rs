@@ -0,0 +1,152 @@
+//! Request bodies in the `aws-chunked` content encoding
+//!
+//! A streaming upload sends its body as chunks of `<hex-size>;chunk-signature=<signature>\r\n<data>\r\n`,
+//! which end with a chunk of zero size, and declares the length of the decoded body by `x-amz-decoded-content-length`.
+//!
+//! + A body signed by `STREAMING-AWS4-HMAC-SHA256-PAYLOAD` is decoded and the signature of each chunk is verified.
+//! + Any other body with `aws-chunked` in its `Content-Encoding` is decoded without verifying the chunk signatures,
+//!   since the request has no seed signature to chain them from.
+//!
+//! A body whose chunks are malformed or end before the chunk of zero size fails its request with `IncompleteBody` (400),
+//! and a wrong chunk signature fails it with `SignatureDoesNotMatch` (403).
+
+use crate::errors::S3Result;
+use crate::headers::{AmzDate, CONTENT_ENCODING};
+use crate::ops::ReqContext;
+use crate::service::take_io_body;
+use crate::streams::aws_chunked_stream::{AwsChunkedStream, AwsChunkedStreamError};
+use crate::Body;
+
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::stream::StreamExt;
+
+/// the content coding of chunked uploads
+const AWS_CHUNKED: &str = "aws-chunked";
+
+/// What verifies the chunk signatures of a body, taken from the signature of its request
+#[derive(Debug)]
+pub(crate) struct ChunkSigning {
+    /// the signature of the request
+    pub(crate) seed_signature: Box<str>,
+    /// `x-amz-date`
+    pub(crate) amz_date: AmzDate,
+    /// region
+    pub(crate) region: Box<str>,
+    /// secret key
+    pub(crate) secret_key: Box<str>,
+}
+
+/// A decoded request body, which records why its decoding failed
+#[derive(Debug)]
+pub(crate) struct ChunkedBody {
+    /// the error of the decoding
+    failure: Arc<Mutex<Option<AwsChunkedStreamError>>>,
+}
+
+/// locks a mutex even if it is poisoned
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+impl ChunkedBody {
+    /// replaces the error of a handler whose body failed to decode
+    pub(crate) fn check<T>(&self, ret: S3Result<T>) -> S3Result<T> {
+        let failure = lock(&self.failure).take();
+        let failure = match failure {
+            Some(failure) => failure,
+            None => return ret,
+        };
+        ret.map_err(|err| match failure {
+            AwsChunkedStreamError::Io(_) => err,
+            AwsChunkedStreamError::SignatureMismatch => signature_mismatch!(),
+            AwsChunkedStreamError::FormatError | AwsChunkedStreamError::Incomplete => code_error!(
+                IncompleteBody,
+                "You did not provide the number of bytes specified by the Content-Length HTTP header.",
+                err
+            ),
+        })
+    }
+}
+
+/// whether `aws-chunked` is one of the codings of `Content-Encoding`
+pub(crate) fn is_aws_chunked(content_encoding: &str) -> bool {
+    content_encoding
+        .split(',')
+        .any(|coding| coding.trim().eq_ignore_ascii_case(AWS_CHUNKED))
+}
+
+/// removes `aws-chunked` from `Content-Encoding`, which is not a coding of the stored object
+pub(crate) fn strip_aws_chunked(content_encoding: Option<String>) -> Option<String> {
+    let content_encoding = content_encoding?;
+    if !is_aws_chunked(&content_encoding) {
+        return Some(content_encoding);
+    }
+    let codings: Vec<&str> = content_encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case(AWS_CHUNKED))
+        .collect();
+    Some(codings.join(", ")).filter(|s| !s.is_empty())
+}
+
+/// decodes the body of a request, which is signed by `signing` or has `aws-chunked` in its `Content-Encoding`
+pub(crate) fn decode(
+    ctx: &mut ReqContext<'_>,
+    signing: Option<ChunkSigning>,
+) -> Option<ChunkedBody> {
+    let is_encoded = ctx
+        .headers
+        .get(CONTENT_ENCODING)
+        .map_or(false, is_aws_chunked);
+    if signing.is_none() && (!is_encoded || ctx.multipart.is_some()) {
+        return None;
+    }
+
+    let body = take_io_body(&mut ctx.body);
+    let chunked_stream = match signing {
+        Some(signing) => AwsChunkedStream::new(
+            body,
+            signing.seed_signature,
+            signing.amz_date,
+            signing.region,
+            signing.secret_key,
+        ),
+        None => AwsChunkedStream::with_chunk_callback(body, |_, _| true),
+    };
+
+    let failure = Arc::default();
+    let recorded = Arc::clone(&failure);
+    let body = chunked_stream.map(move |chunk| {
+        chunk.map_err(|e| {
+            let err = io::Error::new(io::ErrorKind::Other, e.to_string());
+            *lock(&recorded) = Some(e);
+            err
+        })
+    });
+    ctx.body = Body::wrap_stream(body);
+    ctx.aws_chunked = true;
+    Some(ChunkedBody { failure })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_encoding() {
+        assert!(is_aws_chunked("aws-chunked"));
+        assert!(is_aws_chunked("gzip, AWS-Chunked"));
+        assert!(!is_aws_chunked("gzip"));
+
+        let strip = |s: &str| strip_aws_chunked(Some(s.to_owned()));
+        assert_eq!(strip("aws-chunked"), None);
+        assert_eq!(strip("aws-chunked,gzip"), Some("gzip".to_owned()));
+        assert_eq!(strip("gzip, aws-chunked, br"), Some("gzip, br".to_owned()));
+        assert_eq!(strip("gzip,br"), Some("gzip,br".to_owned()));
+        assert_eq!(strip_aws_chunked(None), None);
+    }
+}
@@ -0,0 +1,111 @@
+//! Rejection of requests whose time is too far from the server time
+
+use crate::data_structures::OrderedHeaders;
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+use crate::headers::{AmzDate, DATE, X_AMZ_DATE};
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+/// the default max clock skew, like AWS
+#[allow(clippy::duration_suboptimal_units)] // `Duration::from_mins` is not stable
+pub(crate) const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(15 * 60);
+
+/// parses the time of a request, in ISO 8601 basic format or, for `Date`, RFC 1123
+fn parse_request_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Some(time) = AmzDate::from_header_str(value)
+        .ok()
+        .and_then(AmzDate::to_date_time)
+    {
+        return Some(time);
+    }
+    DateTime::parse_from_rfc2822(value).ok().map(Into::into)
+}
+
+/// Checks the time of a request signed by headers, which is `x-amz-date` or `Date`.
+///
+/// A request whose time differs from `now` by more than `max_skew` is `RequestTimeTooSkewed` (403).
+/// A request without a valid time is left to the signature check.
+pub(crate) fn check(
+    headers: &OrderedHeaders<'_>,
+    max_skew: Duration,
+    now: DateTime<Utc>,
+) -> S3Result<()> {
+    let value = match headers.get(&*X_AMZ_DATE).or_else(|| headers.get(DATE)) {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+    let time = match parse_request_time(value) {
+        Some(t) => t,
+        None => return Ok(()),
+    };
+
+    let skew = now
+        .signed_duration_since(time)
+        .num_milliseconds()
+        .unsigned_abs();
+    let max_skew = u64::try_from(max_skew.as_millis()).unwrap_or(u64::MAX);
+    if skew <= max_skew {
+        return Ok(());
+    }
+
+    Err(S3Error::from_code(S3ErrorCode::RequestTimeTooSkewed)
+        .message("The difference between the request time and the current time is too large.")
+        .request_time(value)
+        .server_time(now.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .max_allowed_skew_milliseconds(max_skew)
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the server time of the tests
+    fn now() -> DateTime<Utc> {
+        AmzDate::from_header_str("20130524T001000Z")
+            .unwrap()
+            .to_date_time()
+            .unwrap()
+    }
+
+    fn check_at(headers: &[(&str, &str)], max_skew: Duration) -> S3Result<()> {
+        check(
+            &OrderedHeaders::from_slice_unchecked(headers),
+            max_skew,
+            now(),
+        )
+    }
+
+    #[test]
+    fn skews() {
+        let max_skew = DEFAULT_MAX_CLOCK_SKEW;
+
+        check_at(&[("x-amz-date", "20130524T000000Z")], max_skew).unwrap();
+        check_at(&[("x-amz-date", "20130524T002500Z")], max_skew).unwrap();
+        check_at(&[("date", "Fri, 24 May 2013 00:00:00 GMT")], max_skew).unwrap();
+        check_at(&[("date", "20130524T001000Z")], max_skew).unwrap();
+        check_at(&[], max_skew).unwrap();
+        check_at(&[("x-amz-date", "yesterday")], max_skew).unwrap();
+
+        let err = check_at(&[("x-amz-date", "20130523T235459Z")], max_skew).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::RequestTimeTooSkewed);
+        let res = err.into_xml_response();
+        assert_eq!(res.request_time.as_deref(), Some("20130523T235459Z"));
+        assert_eq!(res.server_time.as_deref(), Some("2013-05-24T00:10:00Z"));
+        assert_eq!(res.max_allowed_skew_milliseconds, Some(900_000));
+
+        // `x-amz-date` takes the place of `Date`
+        let headers = [
+            ("date", "Fri, 24 May 2013 00:00:00 GMT"),
+            ("x-amz-date", "20130524T003001Z"),
+        ];
+        let err = check_at(&headers, max_skew).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::RequestTimeTooSkewed);
+
+        // the skew may be as large as the window
+        check_at(&headers, Duration::from_secs(1201)).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+//! Response compression
+
+use crate::headers::{
+    HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+    VARY,
+};
+use crate::{Body, BoxStdError, Mime, Request, Response, StatusCode};
+
+use std::fmt::{self, Debug};
+use std::io;
+#[cfg(any(feature = "compress-gzip", feature = "compress-zstd"))]
+use std::io::Write;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::{Bytes, HttpBody};
+use pin_project_lite::pin_project;
+use serde::Serialize;
+
+/// Options of response compression, see [`S3Service::set_compression`](crate::S3Service::set_compression)
+///
+/// The codings are enabled by the features `compress-gzip` (`gzip` and `deflate`)
+/// and `compress-zstd` (`zstd`). Without them, responses are never compressed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompressionConfig {
+    /// min `Content-Length` of a streamed body, such as the data of `GetObject`
+    ///
+    /// Buffered bodies, such as listings and errors, are compressed regardless of their size.
+    pub min_size: u64,
+    /// compressible content types: `type/subtype`, or `type/*` for all subtypes
+    pub content_types: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        let content_types = [
+            "text/*",
+            "application/xml",
+            "application/json",
+            "application/javascript",
+            "application/x-ndjson",
+            "image/svg+xml",
+        ];
+        Self {
+            min_size: 1024,
+            content_types: content_types.iter().map(|&s| s.to_owned()).collect(),
+        }
+    }
+}
+
+/// A content coding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Coding {
+    /// `zstd`
+    #[cfg(feature = "compress-zstd")]
+    Zstd,
+    /// `gzip`
+    #[cfg(feature = "compress-gzip")]
+    Gzip,
+    /// `deflate`, which is the zlib format
+    #[cfg(feature = "compress-gzip")]
+    Deflate,
+}
+
+/// the enabled codings, the preferred first
+const CODINGS: &[Coding] = &[
+    #[cfg(feature = "compress-zstd")]
+    Coding::Zstd,
+    #[cfg(feature = "compress-gzip")]
+    Coding::Gzip,
+    #[cfg(feature = "compress-gzip")]
+    Coding::Deflate,
+];
+
+impl Coding {
+    /// the token in `Accept-Encoding` and `Content-Encoding`
+    const fn as_str(self) -> &'static str {
+        match self {
+            #[cfg(feature = "compress-zstd")]
+            Self::Zstd => "zstd",
+            #[cfg(feature = "compress-gzip")]
+            Self::Gzip => "gzip",
+            #[cfg(feature = "compress-gzip")]
+            Self::Deflate => "deflate",
+        }
+    }
+
+    /// constructs an encoder
+    fn encoder(self) -> io::Result<Encoder> {
+        match self {
+            #[cfg(feature = "compress-zstd")]
+            Self::Zstd => {
+                let encoder = zstd::stream::write::Encoder::new(Vec::new(), 0)?;
+                Ok(Encoder::Zstd(encoder))
+            }
+            #[cfg(feature = "compress-gzip")]
+            Self::Gzip => {
+                let level = flate2::Compression::default();
+                Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    level,
+                )))
+            }
+            #[cfg(feature = "compress-gzip")]
+            Self::Deflate => {
+                let level = flate2::Compression::default();
+                Ok(Encoder::Deflate(flate2::write::ZlibEncoder::new(
+                    Vec::new(),
+                    level,
+                )))
+            }
+        }
+    }
+}
+
+/// Chooses the coding of a response by the `Accept-Encoding` of `req`
+///
+/// The highest quality wins, and the order of [`CODINGS`] breaks ties.
+/// `*` covers the codings which are not listed, and a zero quality excludes a coding.
+pub(crate) fn negotiate(req: &Request) -> Option<Coding> {
+    let mut accepted: Vec<(&str, u32)> = Vec::new();
+    for value in req.headers().get_all(ACCEPT_ENCODING) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        for item in value.split(',') {
+            let mut params = item.split(';');
+            let token = params.next().unwrap_or("").trim();
+            if token.is_empty() {
+                continue;
+            }
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1000), parse_quality);
+            if let Some(quality) = quality {
+                accepted.push((token, quality));
+            }
+        }
+    }
+
+    let quality_of = |coding: Coding| {
+        let find = |token: &str| {
+            accepted
+                .iter()
+                .find(|&&(t, _)| t.eq_ignore_ascii_case(token))
+                .map(|&(_, q)| q)
+        };
+        find(coding.as_str()).or_else(|| find("*")).unwrap_or(0)
+    };
+
+    let mut best: Option<(Coding, u32)> = None;
+    for &coding in CODINGS {
+        let quality = quality_of(coding);
+        if quality > 0 && best.map_or(true, |(_, q)| quality > q) {
+            best = Some((coding, quality));
+        }
+    }
+    best.map(|(coding, _)| coding)
+}
+
+/// parses a quality value into thousandths
+fn parse_quality(s: &str) -> Option<u32> {
+    let mut parts = s.trim().splitn(2, '.');
+    let int = parts.next()?;
+    let frac = parts.next().unwrap_or("");
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let frac: u32 = format!("{:0<3}", frac).parse().ok()?;
+    match int {
+        "0" => Some(frac),
+        "1" if frac == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+impl CompressionConfig {
+    /// Checks whether a response should be compressed
+    fn is_compressible(&self, resp: &Response) -> bool {
+        // partial content describes a range of the stored bytes
+        let status = resp.status();
+        bool_try!(status == StatusCode::OK || status.is_client_error() || status.is_server_error());
+        let headers = resp.headers();
+        bool_try!(!headers.contains_key(CONTENT_ENCODING) && !headers.contains_key(CONTENT_RANGE));
+
+        let content_type = bool_try_some!(headers.get(CONTENT_TYPE));
+        let mime: Mime = bool_try_some!(content_type.to_str().ok().and_then(|s| s.parse().ok()));
+        bool_try!(self.content_types.iter().any(|allowed| {
+            allowed.strip_suffix("/*").map_or_else(
+                || mime.essence_str().eq_ignore_ascii_case(allowed),
+                |type_| mime.type_().as_str().eq_ignore_ascii_case(type_),
+            )
+        }));
+
+        // a buffered body has an exact size, while a streamed one has `Content-Length`
+        HttpBody::size_hint(resp.body()).exact().map_or_else(
+            || {
+                headers
+                    .get(CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .map_or(false, |len| len >= self.min_size)
+            },
+            |len| len > 0,
+        )
+    }
+
+    /// Compresses a response with `coding` if it is compressible.
+    ///
+    /// `ETag` and the checksum headers are kept, so they still describe the stored object.
+    /// A buffered body is compressed at once, so it still has an exact size,
+    /// while a streamed body is compressed chunk by chunk.
+    pub(crate) async fn apply(&self, coding: Coding, mut resp: Response) -> Response {
+        if !self.is_compressible(&resp) {
+            return resp;
+        }
+        let encoder = match coding.encoder() {
+            Ok(encoder) => encoder,
+            Err(_) => return resp,
+        };
+
+        let headers = resp.headers_mut();
+        let _prev = headers.remove(CONTENT_LENGTH);
+        let _prev = headers.insert(CONTENT_ENCODING, HeaderValue::from_static(coding.as_str()));
+        let _prev = headers.append(VARY, HeaderValue::from_static("Accept-Encoding"));
+
+        let body = mem::take(resp.body_mut());
+        let is_buffered = HttpBody::size_hint(&body).exact().is_some();
+        let mut body = Body::wrap_stream(Compress {
+            inner: body,
+            encoder: Some(encoder),
+        });
+        if is_buffered {
+            body = match hyper::body::to_bytes(body).await {
+                Ok(bytes) => Body::from(bytes),
+                Err(e) => Body::wrap_stream(futures::stream::once(async { Err::<Bytes, _>(e) })),
+            };
+        }
+        *resp.body_mut() = body;
+        resp
+    }
+}
+
+/// A streaming encoder
+enum Encoder {
+    /// `zstd`
+    #[cfg(feature = "compress-zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    /// `gzip`
+    #[cfg(feature = "compress-gzip")]
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    /// `deflate`
+    #[cfg(feature = "compress-gzip")]
+    Deflate(flate2::write::ZlibEncoder<Vec<u8>>),
+}
+
+impl Debug for Encoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encoder {{...}}")
+    }
+}
+
+impl Encoder {
+    /// compresses a chunk, returning the output which is ready
+    #[cfg_attr(
+        not(any(feature = "compress-gzip", feature = "compress-zstd")),
+        allow(unused_variables)
+    )]
+    fn write(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+        match *self {
+            #[cfg(feature = "compress-zstd")]
+            Self::Zstd(ref mut e) => e.write_all(chunk).map(|()| take_output(e.get_mut())),
+            #[cfg(feature = "compress-gzip")]
+            Self::Gzip(ref mut e) => e.write_all(chunk).map(|()| take_output(e.get_mut())),
+            #[cfg(feature = "compress-gzip")]
+            Self::Deflate(ref mut e) => e.write_all(chunk).map(|()| take_output(e.get_mut())),
+        }
+    }
+
+    /// finishes the stream, returning the rest of the output
+    fn finish(self) -> io::Result<Bytes> {
+        match self {
+            #[cfg(feature = "compress-zstd")]
+            Self::Zstd(e) => e.finish().map(Bytes::from),
+            #[cfg(feature = "compress-gzip")]
+            Self::Gzip(e) => e.finish().map(Bytes::from),
+            #[cfg(feature = "compress-gzip")]
+            Self::Deflate(e) => e.finish().map(Bytes::from),
+        }
+    }
+}
+
+/// takes the output written by an encoder so far
+#[cfg(any(feature = "compress-gzip", feature = "compress-zstd"))]
+fn take_output(out: &mut Vec<u8>) -> Bytes {
+    mem::take(out).into()
+}
+
+pin_project! {
+    /// A compressed body
+    struct Compress {
+        #[pin]
+        inner: Body,
+        encoder: Option<Encoder>,
+    }
+}
+
+impl Stream for Compress {
+    type Item = Result<Bytes, BoxStdError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let encoder = match *this.encoder {
+                Some(ref mut encoder) => encoder,
+                None => return Poll::Ready(None),
+            };
+            match futures::ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => match encoder.write(&chunk) {
+                    Ok(out) if out.is_empty() => {}
+                    Ok(out) => return Poll::Ready(Some(Ok(out))),
+                    Err(e) => return Poll::Ready(Some(Err(e.into()))),
+                },
+                Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                None => {
+                    let ret = this.encoder.take().map(|encoder| {
+                        encoder.finish().map_err(|e| -> BoxStdError { Box::new(e) })
+                    });
+                    return Poll::Ready(ret);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(accept_encoding: &str) -> Request {
+        let mut req = Request::new(Body::empty());
+        let value = HeaderValue::from_str(accept_encoding).unwrap();
+        let _prev = req.headers_mut().insert(ACCEPT_ENCODING, value);
+        req
+    }
+
+    #[test]
+    fn compressible() {
+        let config = CompressionConfig::default();
+        let response = |status: StatusCode, content_type: &'static str, body: Body| {
+            let mut resp = Response::new(body);
+            *resp.status_mut() = status;
+            let value = HeaderValue::from_static(content_type);
+            let _prev = resp.headers_mut().insert(CONTENT_TYPE, value);
+            resp
+        };
+        let xml = || Body::from("<ListBucketResult></ListBucketResult>");
+
+        assert!(config.is_compressible(&response(StatusCode::OK, "application/xml", xml())));
+        assert!(config.is_compressible(&response(StatusCode::NOT_FOUND, "text/xml", xml())));
+        assert!(!config.is_compressible(&response(StatusCode::OK, "image/png", xml())));
+        assert!(!config.is_compressible(&response(StatusCode::OK, "text/plain", Body::empty())));
+
+        let mut resp = response(StatusCode::PARTIAL_CONTENT, "text/plain", xml());
+        assert!(!config.is_compressible(&resp));
+        *resp.status_mut() = StatusCode::OK;
+        let value = HeaderValue::from_static("bytes 0-4/10");
+        let _prev = resp.headers_mut().insert(CONTENT_RANGE, value);
+        assert!(!config.is_compressible(&resp));
+
+        let mut resp = response(StatusCode::OK, "text/plain", xml());
+        let value = HeaderValue::from_static("gzip");
+        let _prev = resp.headers_mut().insert(CONTENT_ENCODING, value);
+        assert!(!config.is_compressible(&resp));
+
+        // a streamed body is compressed from the min size
+        let streamed = |len: &'static str| {
+            let chunks = vec![Ok::<_, io::Error>(Bytes::from_static(b"Hello"))];
+            let body = Body::wrap_stream(futures::stream::iter(chunks));
+            let mut resp = response(StatusCode::OK, "text/plain; charset=utf-8", body);
+            let value = HeaderValue::from_static(len);
+            let _prev = resp.headers_mut().insert(CONTENT_LENGTH, value);
+            resp
+        };
+        assert!(config.is_compressible(&streamed("1024")));
+        assert!(!config.is_compressible(&streamed("1023")));
+    }
+
+    #[test]
+    fn quality() {
+        assert_eq!(parse_quality("1"), Some(1000));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("0.5"), Some(500));
+        assert_eq!(parse_quality("0.05"), Some(50));
+        assert_eq!(parse_quality("0"), Some(0));
+        assert_eq!(parse_quality("1.5"), None);
+        assert_eq!(parse_quality("0.1234"), None);
+        assert_eq!(parse_quality("abc"), None);
+    }
+
+    #[cfg(feature = "compress-gzip")]
+    #[test]
+    fn negotiation() {
+        let coding = |value: &str| negotiate(&request(value)).map(Coding::as_str);
+        assert_eq!(coding("gzip"), Some("gzip"));
+        assert_eq!(coding("deflate, gzip;q=0.5"), Some("deflate"));
+        assert_eq!(coding("gzip;q=0, deflate;q=0.1"), Some("deflate"));
+        assert_eq!(coding("identity"), None);
+        assert_eq!(coding("br, *;q=0"), None);
+        assert_eq!(coding("GZIP"), Some("gzip"));
+        assert_eq!(negotiate(&Request::new(Body::empty())), None);
+        if cfg!(feature = "compress-zstd") {
+            assert_eq!(coding("*"), Some("zstd"));
+            assert_eq!(coding("gzip, zstd;q=0.9"), Some("gzip"));
+        } else {
+            assert_eq!(coding("*"), Some("gzip"));
+        }
+    }
+}
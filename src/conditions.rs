@@ -0,0 +1,214 @@
+//! Conditional requests, evaluated against the ETag and the modification time of an object
+//!
+//! + `If-Match` fails unless the ETag matches. Without it, `If-Unmodified-Since` fails if the object was modified after the date.
+//! + `If-None-Match` fails if the ETag matches. Without it, `If-Modified-Since` fails unless the object was modified after the date.
+//! + A failed `If-Match` side is `PreconditionFailed` (412), which wins over a failed `If-None-Match` side.
+//!   The latter is `304 Not Modified` for a `GET` or a `HEAD`.
+//!
+//! ETags are compared without their quotes and the weak `W/` prefix.
+//! A date which can not be parsed is ignored,
+//! and so is a condition on an ETag or a modification time which the object does not have.
+
+use crate::data_structures::OrderedHeaders;
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{
+    ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+};
+use crate::utils::{time, ResponseExt};
+use crate::{Body, Response, StatusCode};
+
+use chrono::{DateTime, FixedOffset};
+
+/// The conditions of a request
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::struct_field_names)] // Why? The fields are named after the headers.
+pub(crate) struct Conditions<'a> {
+    /// `If-Match`
+    pub(crate) if_match: Option<&'a str>,
+    /// `If-None-Match`
+    pub(crate) if_none_match: Option<&'a str>,
+    /// `If-Modified-Since`
+    pub(crate) if_modified_since: Option<&'a str>,
+    /// `If-Unmodified-Since`
+    pub(crate) if_unmodified_since: Option<&'a str>,
+}
+
+/// The result of [`Conditions::evaluate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Evaluation {
+    /// all of the conditions hold
+    Holds,
+    /// the `If-None-Match` side failed
+    NotModified,
+    /// the `If-Match` side failed
+    Failed,
+}
+
+impl<'a> Conditions<'a> {
+    /// The `If-*` headers of a request
+    pub(crate) fn from_headers(h: &OrderedHeaders<'a>) -> Self {
+        Self {
+            if_match: h.get(IF_MATCH),
+            if_none_match: h.get(IF_NONE_MATCH),
+            if_modified_since: h.get(IF_MODIFIED_SINCE),
+            if_unmodified_since: h.get(IF_UNMODIFIED_SINCE),
+        }
+    }
+
+    /// Evaluates the conditions against the quoted ETag and the rfc3339 modification time of an object
+    pub(crate) fn evaluate(&self, e_tag: Option<&str>, last_modified: Option<&str>) -> Evaluation {
+        let last_modified = last_modified.and_then(|s| DateTime::parse_from_rfc3339(s).ok());
+        let modified_after = |date: &str| -> Option<bool> {
+            Some(last_modified?.timestamp() > parse_http_date(date)?.timestamp())
+        };
+        let if_match = self.if_match.filter(|_| e_tag.is_some());
+        let if_none_match = self.if_none_match.filter(|_| e_tag.is_some());
+
+        let holds_match = if_match.map_or_else(
+            || {
+                self.if_unmodified_since
+                    .and_then(modified_after)
+                    .map_or(true, |modified| !modified)
+            },
+            |if_match| matches_e_tag(if_match, e_tag),
+        );
+        let holds_none_match = if_none_match.map_or_else(
+            || {
+                self.if_modified_since
+                    .and_then(modified_after)
+                    .unwrap_or(true)
+            },
+            |if_none_match| !matches_e_tag(if_none_match, e_tag),
+        );
+
+        if !holds_match {
+            Evaluation::Failed
+        } else if !holds_none_match {
+            Evaluation::NotModified
+        } else {
+            Evaluation::Holds
+        }
+    }
+}
+
+/// the error of a failed `If-Match` side
+pub(crate) fn precondition_failed() -> S3Error {
+    code_error!(
+        PreconditionFailed,
+        "At least one of the pre-conditions you specified did not hold"
+    )
+}
+
+/// the `304 Not Modified` response of a `GET` or a `HEAD`
+pub(crate) fn not_modified(
+    e_tag: Option<String>,
+    last_modified: Option<String>,
+) -> S3Result<Response> {
+    let last_modified =
+        time::map_opt_rfc3339_to_last_modified(last_modified).map_err(|e| internal_error!(e))?;
+    let mut res = Response::new_with_status(Body::empty(), StatusCode::NOT_MODIFIED);
+    res.set_optional_header(ETAG, e_tag)
+        .map_err(|e| internal_error!(e))?;
+    res.set_optional_header(LAST_MODIFIED, last_modified)
+        .map_err(|e| internal_error!(e))?;
+    Ok(res)
+}
+
+/// whether an ETag matches a list of ETags or `*`
+fn matches_e_tag(list: &str, e_tag: Option<&str>) -> bool {
+    let unquote = |s: &str| {
+        let s = s.trim();
+        let s = s.strip_prefix("W/").unwrap_or(s);
+        s.trim_matches('"').to_owned()
+    };
+    let e_tag = e_tag.map(unquote);
+    list.split(',').any(|item| {
+        item.trim() == "*"
+            || e_tag
+                .as_deref()
+                .map_or(false, |e_tag| unquote(item) == e_tag)
+    })
+}
+
+/// parses an HTTP date, such as `Wed, 21 Oct 2015 07:28:00 GMT`, or an rfc3339 time
+fn parse_http_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc2822(date)
+        .or_else(|_| DateTime::parse_from_rfc3339(date))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E_TAG: &str = "\"a7d414b9133d6483d9a1c4e04e856e3b\"";
+    const LAST_MODIFIED: &str = "2021-06-01T10:00:00.500+00:00";
+
+    fn evaluate(conditions: Conditions<'_>) -> Evaluation {
+        conditions.evaluate(Some(E_TAG), Some(LAST_MODIFIED))
+    }
+
+    #[test]
+    fn precedence() {
+        let before = "Tue, 01 Jun 2021 09:00:00 GMT";
+        let after = "Tue, 01 Jun 2021 11:00:00 GMT";
+        let none = Conditions::default();
+
+        assert_eq!(evaluate(none), Evaluation::Holds);
+
+        let weak = Conditions {
+            if_match: Some("W/\"a7d414b9133d6483d9a1c4e04e856e3b\""),
+            ..none
+        };
+        assert_eq!(evaluate(weak), Evaluation::Holds);
+        let conditions = Conditions {
+            if_none_match: Some("\"other\", W/\"a7d414b9133d6483d9a1c4e04e856e3b\""),
+            ..none
+        };
+        assert_eq!(evaluate(conditions), Evaluation::NotModified);
+
+        // a failed `If-Match` side wins
+        let conditions = Conditions {
+            if_match: Some("\"other\""),
+            if_none_match: Some(E_TAG),
+            ..none
+        };
+        assert_eq!(evaluate(conditions), Evaluation::Failed);
+        let conditions = Conditions {
+            if_unmodified_since: Some(before),
+            if_modified_since: Some(after),
+            ..none
+        };
+        assert_eq!(evaluate(conditions), Evaluation::Failed);
+
+        // a matching ETag overrides the date
+        let conditions = Conditions {
+            if_match: Some(E_TAG),
+            if_unmodified_since: Some(before),
+            if_modified_since: Some(after),
+            ..none
+        };
+        assert_eq!(evaluate(conditions), Evaluation::NotModified);
+        let conditions = Conditions {
+            if_none_match: Some("\"other\""),
+            if_modified_since: Some(after),
+            ..none
+        };
+        assert_eq!(evaluate(conditions), Evaluation::Holds);
+    }
+
+    #[test]
+    fn missing_validators() {
+        let conditions = Conditions {
+            if_match: Some("\"other\""),
+            if_unmodified_since: Some("Tue, 01 Jun 2021 09:00:00 GMT"),
+            ..Conditions::default()
+        };
+        assert_eq!(
+            conditions.evaluate(None, Some(LAST_MODIFIED)),
+            Evaluation::Failed
+        );
+        assert_eq!(conditions.evaluate(None, None), Evaluation::Holds);
+        assert_eq!(conditions.evaluate(Some(E_TAG), None), Evaluation::Failed);
+    }
+}
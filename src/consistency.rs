@@ -0,0 +1,81 @@
+//! Read-after-write barriers for eventually consistent storages
+
+use crate::errors::S3Result;
+use crate::ops::ReqContext;
+use crate::storage::S3Storage;
+
+use tracing::warn;
+
+/// Identifies a write which should become readable, see [`S3Storage::wait_for`]
+///
+/// The outputs of the storage are the DTOs of `rusoto_s3`, which can not carry a token,
+/// so the handlers of `PutObject`, `CopyObject` and `CompleteMultipartUpload`
+/// build it from the written key and the `ETag` and version of the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConsistencyToken {
+    /// bucket
+    pub bucket: String,
+    /// key
+    pub key: String,
+    /// the version of the written object, if the bucket is versioned
+    pub version_id: Option<String>,
+    /// the `ETag` of the written object
+    pub e_tag: Option<String>,
+}
+
+impl ConsistencyToken {
+    /// Constructs a token of a write to `bucket` and `key`
+    pub fn new(bucket: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            key: key.into(),
+            version_id: None,
+            e_tag: None,
+        }
+    }
+
+    /// Sets the version of the written object
+    #[must_use]
+    pub fn version_id(mut self, version_id: Option<String>) -> Self {
+        self.version_id = version_id;
+        self
+    }
+
+    /// Sets the `ETag` of the written object
+    #[must_use]
+    pub fn e_tag(mut self, e_tag: Option<String>) -> Self {
+        self.e_tag = e_tag;
+        self
+    }
+}
+
+/// waits until a successful write is readable, if the barrier is enabled
+///
+/// The write has succeeded, so a barrier which times out or fails is logged and the response is sent anyway.
+pub(crate) async fn barrier(
+    ctx: &ReqContext<'_>,
+    storage: &(dyn S3Storage + Send + Sync),
+    token: ConsistencyToken,
+) {
+    let timeout = match ctx.read_after_write_barrier {
+        Some(timeout) => timeout,
+        None => return,
+    };
+    let ret: S3Result<bool> = storage.wait_for(&token, timeout).await;
+    match ret {
+        Ok(true) => {}
+        Ok(false) => warn!(
+            bucket = %token.bucket,
+            key = %token.key,
+            ?timeout,
+            "the write is not readable after the barrier"
+        ),
+        Err(err) => warn!(
+            bucket = %token.bucket,
+            key = %token.key,
+            %err,
+            "the barrier failed"
+        ),
+    }
+}
@@ -0,0 +1,317 @@
+//! Cross-origin resource sharing, evaluated by the CORS rules of the buckets
+//!
+//! A preflight `OPTIONS` request is answered before routing and signature checks, since browsers do not sign it.
+//! It is allowed by the first rule which matches its `Origin`, its `Access-Control-Request-Method`
+//! and every header of its `Access-Control-Request-Headers`, or rejected with `AccessForbidden` (403).
+//!
+//! An actual request with an `Origin` gets the CORS headers of the first rule
+//! which matches its origin and its method. Its response is unchanged otherwise.
+//!
+//! An origin or a header of a rule may have one `*`, which matches any characters.
+//! Origins are case-sensitive while headers are not.
+
+use crate::dto::{CORSRule, GetBucketCorsRequest};
+use crate::errors::{S3ErrorCode, S3Result, S3StorageError};
+use crate::headers::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use crate::ops::ReqContext;
+use crate::path::S3Path;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{Body, Response};
+
+use hyper::header::InvalidHeaderValue;
+
+/// the request headers which select the CORS headers of a response
+const VARY_HEADERS: &str = "Origin, Access-Control-Request-Headers, Access-Control-Request-Method";
+
+/// Answers a preflight `OPTIONS` request
+///
+/// # Errors
+/// Returns `InvalidRequest` without `Origin` or `Access-Control-Request-Method`,
+/// or `AccessForbidden` if no rule allows the request
+pub(crate) async fn preflight(
+    ctx: &ReqContext<'_>,
+    storage: &(dyn S3Storage + Send + Sync),
+) -> S3Result<Response> {
+    let h = &ctx.headers;
+    let (origin, method) = match (h.get(ORIGIN), h.get(ACCESS_CONTROL_REQUEST_METHOD)) {
+        (Some(origin), Some(method)) => (origin, method),
+        _ => {
+            return Err(code_error!(
+                InvalidRequest,
+                "Insufficient information. Origin request header needed."
+            ))
+        }
+    };
+    let request_headers = h.get(ACCESS_CONTROL_REQUEST_HEADERS);
+    let headers = split_headers(request_headers.unwrap_or_default());
+
+    let rules = match ctx.path {
+        S3Path::Root => Vec::new(),
+        S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => {
+            load_rules(bucket, storage).await?
+        }
+    };
+    let rule = match find_rule(&rules, origin, method, &headers) {
+        Some(rule) => rule,
+        None => {
+            return Err(code_error!(
+                AccessForbidden,
+                "CORSResponse: This CORS request is not allowed. This is usually because the evaluation of Origin, request method / Access-Control-Request-Method or Access-Control-Request-Headers are not whitelisted by the resource's CORS spec."
+            ))
+        }
+    };
+
+    let mut res = Response::new(Body::empty());
+    set_headers(&mut res, rule, origin, request_headers).map_err(|e| internal_error!(e))?;
+    Ok(res)
+}
+
+/// Adds the CORS headers to the response of an actual request with an `Origin`
+///
+/// The response is unchanged if no rule allows the request, or the rules can not be read.
+pub(crate) async fn apply(
+    ctx: &ReqContext<'_>,
+    storage: &(dyn S3Storage + Send + Sync),
+    res: &mut Response,
+) {
+    let origin = match ctx.headers.get(ORIGIN) {
+        Some(origin) => origin,
+        None => return,
+    };
+    let bucket = match ctx.path {
+        S3Path::Root => return,
+        S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => bucket,
+    };
+    let rules = match load_rules(bucket, storage).await {
+        Ok(rules) => rules,
+        Err(_) => return,
+    };
+    if let Some(rule) = find_rule(&rules, origin, ctx.req.method().as_str(), &[]) {
+        if set_headers(res, rule, origin, None).is_err() {
+            tracing::debug!(?rule, "ignored a CORS rule with invalid header values");
+        }
+    }
+}
+
+/// reads the CORS rules of a bucket, which has none if the storage does not implement them
+async fn load_rules(
+    bucket: &str,
+    storage: &(dyn S3Storage + Send + Sync),
+) -> S3Result<Vec<CORSRule>> {
+    let input = GetBucketCorsRequest {
+        bucket: bucket.to_owned(),
+        expected_bucket_owner: None,
+    };
+    match storage.get_bucket_cors(input).await {
+        Ok(output) => Ok(output.cors_rules.unwrap_or_default()),
+        Err(S3StorageError::Operation(e)) => match e {},
+        Err(S3StorageError::Other(err)) if err.code() == S3ErrorCode::NotImplemented => {
+            Ok(Vec::new())
+        }
+        Err(S3StorageError::Other(err)) => Err(err),
+    }
+}
+
+/// splits `Access-Control-Request-Headers` into lowercase header names
+fn split_headers(headers: &str) -> Vec<String> {
+    headers
+        .split(',')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+/// whether a value matches a pattern, which has at most one `*`
+fn matches_wildcard(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.splitn(2, '*');
+    let prefix = parts.next().unwrap_or_default();
+    parts.next().map_or_else(
+        || pattern == value,
+        |suffix| {
+            value
+                .strip_prefix(prefix)
+                .map_or(false, |rest| rest.ends_with(suffix))
+        },
+    )
+}
+
+/// finds the first rule which allows an origin, a method and all of the lowercase headers
+fn find_rule<'r>(
+    rules: &'r [CORSRule],
+    origin: &str,
+    method: &str,
+    headers: &[String],
+) -> Option<&'r CORSRule> {
+    rules.iter().find(|rule| {
+        let allowed_headers = rule.allowed_headers.as_deref().unwrap_or_default();
+        rule.allowed_origins
+            .iter()
+            .any(|pattern| matches_wildcard(pattern, origin))
+            && rule.allowed_methods.iter().any(|m| m == method)
+            && headers.iter().all(|header| {
+                allowed_headers
+                    .iter()
+                    .any(|pattern| matches_wildcard(&pattern.to_ascii_lowercase(), header))
+            })
+    })
+}
+
+/// sets the CORS headers of a rule, echoing the requested headers of a preflight request
+fn set_headers(
+    res: &mut Response,
+    rule: &CORSRule,
+    origin: &str,
+    request_headers: Option<&str>,
+) -> Result<(), InvalidHeaderValue> {
+    let any_origin = rule.allowed_origins.iter().any(|pattern| pattern == "*");
+    if any_origin {
+        res.set_optional_header(ACCESS_CONTROL_ALLOW_ORIGIN, Some("*".to_owned()))?;
+    } else {
+        res.set_optional_header(ACCESS_CONTROL_ALLOW_ORIGIN, Some(origin.to_owned()))?;
+        res.set_optional_header(ACCESS_CONTROL_ALLOW_CREDENTIALS, Some("true".to_owned()))?;
+    }
+    res.set_optional_header(
+        ACCESS_CONTROL_ALLOW_METHODS,
+        Some(rule.allowed_methods.join(", ")),
+    )?;
+    res.set_optional_header(
+        ACCESS_CONTROL_ALLOW_HEADERS,
+        request_headers.map(str::to_owned),
+    )?;
+    res.set_optional_header(
+        ACCESS_CONTROL_EXPOSE_HEADERS,
+        rule.expose_headers
+            .as_ref()
+            .map(|headers| headers.join(", ")),
+    )?;
+    res.set_optional_header(
+        ACCESS_CONTROL_MAX_AGE,
+        rule.max_age_seconds.map(|n| n.to_string()),
+    )?;
+    res.set_optional_header(VARY, Some(VARY_HEADERS.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(origins: &[&str], methods: &[&str], headers: &[&str]) -> CORSRule {
+        let strings = |values: &[&str]| values.iter().map(|&s| s.to_owned()).collect();
+        CORSRule {
+            allowed_origins: strings(origins),
+            allowed_methods: strings(methods),
+            allowed_headers: Some(strings(headers)).filter(|v: &Vec<String>| !v.is_empty()),
+            ..CORSRule::default()
+        }
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches_wildcard("*", ""));
+        assert!(matches_wildcard("*", "https://example.com"));
+        assert!(matches_wildcard(
+            "https://*.example.com",
+            "https://www.example.com"
+        ));
+        assert!(!matches_wildcard(
+            "https://*.example.com",
+            "http://www.example.com"
+        ));
+        assert!(!matches_wildcard(
+            "https://*.example.com",
+            "https://example.com"
+        ));
+        assert!(matches_wildcard("x-amz-*", "x-amz-date"));
+        assert!(!matches_wildcard("a*a", "a"));
+        assert!(matches_wildcard(
+            "https://example.com",
+            "https://example.com"
+        ));
+        assert!(!matches_wildcard(
+            "https://example.com",
+            "https://example.com.evil"
+        ));
+    }
+
+    #[test]
+    fn rules() {
+        let rules = vec![
+            rule(&["https://app.example.com"], &["PUT"], &["content-type"]),
+            rule(&["*"], &["GET", "HEAD"], &["*"]),
+        ];
+        let find = |origin, method, headers: &str| {
+            let headers = split_headers(headers);
+            let found = find_rule(&rules, origin, method, &headers);
+            found.map(|rule| rule.allowed_methods[0].as_str())
+        };
+
+        assert_eq!(
+            find("https://app.example.com", "PUT", "Content-Type"),
+            Some("PUT")
+        );
+        assert_eq!(find("https://app.example.com", "PUT", ""), Some("PUT"));
+        assert_eq!(
+            find("https://app.example.com", "PUT", "content-type, x-amz-date"),
+            None
+        );
+        assert_eq!(find("https://other.example.com", "PUT", ""), None);
+        assert_eq!(
+            find("https://other.example.com", "GET", "x-amz-date, Range"),
+            Some("GET")
+        );
+        assert_eq!(find("https://other.example.com", "DELETE", ""), None);
+
+        let no_headers = [rule(&["*"], &["GET"], &[])];
+        let found = find_rule(&no_headers, "https://a.com", "GET", &split_headers("range"));
+        assert!(found.is_none());
+    }
+
+    #[test]
+    fn headers() {
+        let mut res = Response::new(Body::empty());
+        let exposing = CORSRule {
+            expose_headers: Some(vec!["ETag".to_owned()]),
+            max_age_seconds: Some(600),
+            ..rule(&["https://*.example.com"], &["GET", "PUT"], &["*"])
+        };
+        set_headers(
+            &mut res,
+            &exposing,
+            "https://app.example.com",
+            Some("x-amz-date"),
+        )
+        .unwrap();
+        let header = |name| res.headers().get(name).unwrap().to_str().unwrap();
+        assert_eq!(
+            header(ACCESS_CONTROL_ALLOW_ORIGIN),
+            "https://app.example.com"
+        );
+        assert_eq!(header(ACCESS_CONTROL_ALLOW_CREDENTIALS), "true");
+        assert_eq!(header(ACCESS_CONTROL_ALLOW_METHODS), "GET, PUT");
+        assert_eq!(header(ACCESS_CONTROL_ALLOW_HEADERS), "x-amz-date");
+        assert_eq!(header(ACCESS_CONTROL_EXPOSE_HEADERS), "ETag");
+        assert_eq!(header(ACCESS_CONTROL_MAX_AGE), "600");
+        assert_eq!(header(VARY), VARY_HEADERS);
+
+        let mut res = Response::new(Body::empty());
+        set_headers(
+            &mut res,
+            &rule(&["*"], &["GET"], &[]),
+            "https://a.com",
+            None,
+        )
+        .unwrap();
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(res
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+            .is_none());
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_HEADERS).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! `MmapStream`
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use memmap2::Mmap;
+
+/// A stream of bytes served from a read-only memory map
+///
+/// The map is owned by the stream, so it lives as long as the response body.
+///
+/// Chunks are copied out of the mapping, which avoids a `read` syscall per chunk
+/// but still allocates. Pages are faulted in on demand by the kernel.
+#[derive(Debug)]
+pub struct MmapStream {
+    /// memory map
+    map: Arc<Mmap>,
+    /// current position
+    pos: usize,
+    /// chunk size
+    chunk_size: usize,
+}
+
+impl MmapStream {
+    /// Maps the file at `path`
+    ///
+    /// # Safety (internal)
+    /// A memory map is only sound while no one truncates or rewrites the underlying inode.
+    /// The fs backend never writes an existing object file in place:
+    /// new contents are written to a temporary file and renamed over the old path.
+    /// An existing map keeps referring to the old inode,
+    /// so a stream started before a PUT keeps serving the old snapshot.
+    #[allow(unsafe_code)]
+    pub fn open(path: &Path, chunk_size: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: see the doc comment above
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self {
+            map: Arc::new(map),
+            pos: 0,
+            chunk_size,
+        })
+    }
+
+    /// Returns the length of the map
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl Stream for MmapStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let end = this.pos.saturating_add(this.chunk_size).min(this.map.len());
+        let ans = this.map.get(this.pos..end).and_then(|chunk| {
+            if chunk.is_empty() {
+                None
+            } else {
+                Some(Ok(Bytes::copy_from_slice(chunk)))
+            }
+        });
+        this.pos = end;
+        Poll::Ready(ans)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.map.len().saturating_sub(self.pos);
+        let n = remaining
+            .wrapping_add(self.chunk_size.saturating_sub(1))
+            .checked_div(self.chunk_size)
+            .unwrap_or(0);
+        (n, Some(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::TryStreamExt;
+
+    #[tokio::test]
+    async fn read_chunks() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("s3-server-mmap-{}", uuid::Uuid::new_v4()));
+        let content = b"Hello World!";
+        std::fs::write(&path, content).unwrap();
+
+        let stream = MmapStream::open(&path, 5).unwrap();
+        assert_eq!(stream.len(), content.len());
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+
+        // replace the file like the fs backend does
+        let tmp = dir.join(format!("s3-server-mmap-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp, b"replaced").unwrap();
+        std::fs::rename(&tmp, &path).unwrap();
+
+        let chunks: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), content);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
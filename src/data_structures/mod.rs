@@ -1,9 +1,13 @@
 //! Internal data structures
 
 mod bytes_stream;
+#[cfg(feature = "mmap")]
+mod mmap_stream;
 mod ordered_headers;
 mod ordered_qs;
 
 pub use self::bytes_stream::BytesStream;
+#[cfg(feature = "mmap")]
+pub use self::mmap_stream::MmapStream;
 pub use self::ordered_headers::OrderedHeaders;
 pub use self::ordered_qs::OrderedQs;
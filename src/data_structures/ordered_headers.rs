@@ -19,7 +19,7 @@ impl<'a> OrderedHeaders<'a> {
     ///
     /// + header names must be lowercase
     /// + header values must be valid
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn from_slice_unchecked(slice: &[(&'a str, &'a str)]) -> Self {
         let mut headers = SmallVec::new();
         headers.extend_from_slice(slice);
@@ -7,9 +7,20 @@ use std::str::FromStr;
 use smallvec::SmallVec;
 
 /// Immutable query string container
-#[derive(Debug)]
+///
+/// Duplicate names are resolved deterministically:
+/// + value lookups take the first occurrence in the original query
+/// + a presence-only name (`?uploads`) is a flag, whatever value it carries
+///
+/// Zero-length values (`?prefix=` or `?prefix`) are the same as each other:
+/// + [`OrderedQs::assign_str`] keeps them as empty strings
+/// + [`OrderedQs::assign_non_empty_str`] treats them as absent
+/// + [`OrderedQs::assign`] rejects them, as it rejects whitespace around typed values
+///
+/// Values are never trimmed, so `?marker=%20` is a single space.
+#[derive(Debug, Clone)]
 pub struct OrderedQs {
-    /// Ascending query strings
+    /// Query strings ascending by name, in original order for the same name
     qs: SmallVec<[(String, String); 16]>,
 }
 
@@ -17,27 +28,44 @@ impl OrderedQs {
     /// Constructs `OrderedQs` from vec
     ///
     /// + strings must be url-decoded
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn from_vec_unchecked(v: Vec<(String, String)>) -> Self {
         Self {
-            qs: v.also(|v| v.sort()).into(),
+            qs: v.also(|v| v.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0))).into(),
         }
     }
 
     /// Parses `OrderedQs` from query
     pub fn from_query(query: &str) -> Result<Self, serde_urlencoded::de::Error> {
         serde_urlencoded::from_str::<Vec<(String, String)>>(query)?
-            .also(|v| v.sort())
+            // stable sort keeps the original order of duplicate names
+            .also(|v| v.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0)))
             .apply(|qs| Ok(Self { qs: qs.into() }))
     }
 
-    /// Gets query value by name. Time `O(logn)`
+    /// Gets the first query value by name. Time `O(logn)`
     pub fn get(&self, name: &str) -> Option<&str> {
         let qs = self.qs.as_ref();
-        match qs.binary_search_by_key(&name, |&(ref n, _)| n.as_str()) {
-            Ok(idx) => qs.get(idx).map(|&(_, ref v)| v.as_str()),
-            Err(_) => None,
+        let mut idx = qs
+            .binary_search_by_key(&name, |&(ref n, _)| n.as_str())
+            .ok()?;
+        while let Some(&(ref n, _)) = idx.checked_sub(1).and_then(|i| qs.get(i)) {
+            if n != name {
+                break;
+            }
+            idx = idx.wrapping_sub(1);
         }
+        qs.get(idx).map(|&(_, ref v)| v.as_str())
+    }
+
+    /// Removes the query strings whose names do not satisfy `f`
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        self.qs.retain(|&mut (ref name, _)| f(name));
+    }
+
+    /// Checks whether the query contains `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
     }
 
     /// Assigns value from optional query
@@ -55,6 +83,14 @@ impl OrderedQs {
             *opt = Some(s.to_owned());
         }
     }
+
+    /// Assigns string from optional query, unless it is empty
+    pub fn assign_non_empty_str(&self, name: &str, opt: &mut Option<String>) {
+        match self.get(name) {
+            None | Some("") => {}
+            Some(s) => *opt = Some(s.to_owned()),
+        }
+    }
 }
 
 impl AsRef<[(String, String)]> for OrderedQs {
@@ -62,3 +98,44 @@ impl AsRef<[(String, String)]> for OrderedQs {
         self.qs.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_names() {
+        let qs = OrderedQs::from_query("partNumber=2&uploadId=a&partNumber=1&uploads").unwrap();
+        assert_eq!(qs.get("partNumber"), Some("2"));
+        assert_eq!(qs.get("uploadId"), Some("a"));
+        assert_eq!(qs.get("uploads"), Some(""));
+        assert_eq!(qs.get("delete"), None);
+
+        let qs = OrderedQs::from_query("uploads&uploads=1").unwrap();
+        assert!(qs.contains("uploads"));
+        assert_eq!(qs.get("uploads"), Some(""));
+
+        let qs = OrderedQs::from_query("a=3&a=1&a=2&b=0").unwrap();
+        assert_eq!(qs.get("a"), Some("3"));
+        assert_eq!(qs.get("b"), Some("0"));
+    }
+
+    #[test]
+    fn empty_values() {
+        let qs = OrderedQs::from_query("prefix=&delimiter&marker=%20&max-keys=").unwrap();
+
+        let mut value = None;
+        qs.assign_str("prefix", &mut value);
+        assert_eq!(value.as_deref(), Some(""));
+
+        let mut value = None;
+        qs.assign_non_empty_str("prefix", &mut value);
+        qs.assign_non_empty_str("delimiter", &mut value);
+        assert_eq!(value, None);
+        qs.assign_non_empty_str("marker", &mut value);
+        assert_eq!(value.as_deref(), Some(" "));
+
+        let mut max_keys: Option<i64> = None;
+        assert!(qs.assign("max-keys", &mut max_keys).is_err());
+    }
+}
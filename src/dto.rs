@@ -2,19 +2,51 @@
 
 pub use rusoto_core::ByteStream;
 pub use rusoto_s3::{
-    Bucket, CompleteMultipartUploadError, CompleteMultipartUploadOutput,
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
+    AccessControlPolicy, Bucket, CORSConfiguration, CORSRule,
+    CommonPrefix, CompleteMultipartUploadError, CompleteMultipartUploadOutput,
     CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart, CopyObjectError,
-    CopyObjectOutput, CopyObjectRequest, CopyObjectResult, CreateBucketConfiguration,
+    CopyObjectOutput, CopyObjectRequest, CopyObjectResult, CopyPartResult,
+    CreateBucketConfiguration,
     CreateBucketError, CreateBucketOutput, CreateBucketRequest, CreateMultipartUploadError,
-    CreateMultipartUploadOutput, CreateMultipartUploadRequest, Delete, DeleteBucketError,
-    DeleteBucketRequest, DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest,
-    DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, DeletedObject,
-    GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest, GetObjectError,
-    GetObjectOutput, GetObjectRequest, HeadBucketError, HeadBucketRequest, HeadObjectError,
-    HeadObjectOutput, HeadObjectRequest, ListBucketsError, ListBucketsOutput, ListObjectsError,
-    ListObjectsOutput, ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output,
-    ListObjectsV2Request, Object, ObjectIdentifier, PutObjectError, PutObjectOutput,
-    PutObjectRequest, UploadPartError, UploadPartOutput, UploadPartRequest,
+    CreateMultipartUploadOutput, CreateMultipartUploadRequest, DefaultRetention, Delete,
+    DeleteBucketCorsError,
+    DeleteBucketCorsRequest, DeleteBucketError, DeleteBucketPolicyError, DeleteBucketPolicyRequest,
+    DeleteBucketRequest, DeleteBucketTaggingError,
+    DeleteBucketTaggingRequest, DeleteMarkerEntry,
+    DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError,
+    DeleteObjectsOutput, DeleteObjectsRequest, DeletedObject, GetBucketAclError,
+    GetBucketAclOutput, GetBucketAclRequest, GetBucketCorsError, GetBucketCorsOutput,
+    GetBucketCorsRequest, GetBucketLocationError,
+    GetBucketLocationOutput, GetBucketLocationRequest, GetBucketPolicyError, GetBucketPolicyOutput,
+    GetBucketPolicyRequest, GetBucketTaggingError,
+    GetBucketTaggingOutput, GetBucketTaggingRequest, GetBucketVersioningError,
+    GetBucketVersioningOutput, GetBucketVersioningRequest, GetObjectAclError, GetObjectAclOutput,
+    GetObjectAclRequest, GetObjectError, GetObjectLegalHoldError, GetObjectLegalHoldOutput,
+    GetObjectLegalHoldRequest, GetObjectLockConfigurationError, GetObjectLockConfigurationOutput,
+    GetObjectLockConfigurationRequest, GetObjectOutput, GetObjectRequest,
+    GetObjectRetentionError, GetObjectRetentionOutput, GetObjectRetentionRequest,
+    GlacierJobParameters, Grant, Grantee,
+    HeadBucketError, HeadBucketRequest,
+    HeadObjectError, HeadObjectOutput, HeadObjectRequest, Initiator, ListBucketsError,
+    ListBucketsOutput, ListMultipartUploadsError, ListMultipartUploadsOutput,
+    ListMultipartUploadsRequest, ListObjectVersionsError, ListObjectVersionsRequest,
+    ListObjectsError, ListObjectsOutput, ListObjectsRequest, ListObjectsV2Error,
+    ListObjectsV2Output, ListObjectsV2Request, ListPartsError, ListPartsOutput, ListPartsRequest,
+    MultipartUpload, Object, ObjectIdentifier, ObjectLockConfiguration, ObjectLockLegalHold,
+    ObjectLockRetention, ObjectLockRule, ObjectVersion, Owner, Part, PutBucketAclError,
+    PutBucketAclRequest, PutBucketCorsError, PutBucketCorsRequest, PutBucketPolicyError,
+    PutBucketPolicyRequest, PutBucketTaggingError,
+    PutBucketTaggingRequest, PutBucketVersioningError,
+    PutBucketVersioningRequest, PutObjectAclError, PutObjectAclOutput, PutObjectAclRequest,
+    PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldOutput, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest, PutObjectOutput, PutObjectRequest,
+    PutObjectRetentionError, PutObjectRetentionOutput, PutObjectRetentionRequest,
+    RestoreObjectError, RestoreObjectRequest,
+    RestoreRequest, Tag, Tagging,
+    UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest, UploadPartError,
+    UploadPartOutput, UploadPartRequest, VersioningConfiguration,
 };
 
 /// `DeleteBucketOutput`
@@ -22,12 +54,118 @@ pub use rusoto_s3::{
 #[allow(clippy::exhaustive_structs)]
 pub struct DeleteBucketOutput;
 
-/// `HeadBucketOutput`
+/// `DeleteBucketCorsOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct DeleteBucketCorsOutput;
+
+/// `DeleteBucketPolicyOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct DeleteBucketPolicyOutput;
+
+/// `DeleteBucketTaggingOutput`
 #[derive(Debug, Clone, Copy)]
 #[allow(clippy::exhaustive_structs)]
-pub struct HeadBucketOutput;
+pub struct DeleteBucketTaggingOutput;
 
 /// `HeadBucketOutput`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct HeadBucketOutput {
+    /// `x-amz-bucket-region`
+    pub bucket_region: Option<String>,
+}
+
+/// `ListBucketsRequest`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct ListBucketsRequest {
+    /// `max-buckets`, between 1 and 10000
+    pub max_buckets: Option<i64>,
+    /// `continuation-token`
+    pub continuation_token: Option<String>,
+}
+
+/// `PutBucketAclOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PutBucketAclOutput;
+
+/// `PutBucketCorsOutput`
 #[derive(Debug, Clone, Copy)]
 #[allow(clippy::exhaustive_structs)]
-pub struct ListBucketsRequest;
+pub struct PutBucketCorsOutput;
+
+/// `PutBucketPolicyOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PutBucketPolicyOutput;
+
+/// `PutBucketTaggingOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PutBucketTaggingOutput;
+
+/// `PutBucketVersioningOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PutBucketVersioningOutput;
+
+/// `RestoreObjectOutput`
+///
+/// Unlike the one of rusoto, it tells a restore which is already in progress from an accepted one,
+/// since they are answered with different status codes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct RestoreObjectOutput {
+    /// `x-amz-request-charged`
+    pub request_charged: Option<String>,
+    /// `x-amz-restore-output-path`
+    pub restore_output_path: Option<String>,
+    /// whether the object is already being restored, which is `200 OK` instead of `202 Accepted`
+    pub already_in_progress: bool,
+}
+
+/// A version or a delete marker in `ListObjectVersionsOutput`
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum ListedVersion {
+    /// `Version`
+    Version(ObjectVersion),
+    /// `DeleteMarker`
+    DeleteMarker(DeleteMarkerEntry),
+}
+
+/// `ListObjectVersionsOutput`
+///
+/// Unlike the one of rusoto, versions and delete markers are kept in one list,
+/// because their order is significant.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct ListObjectVersionsOutput {
+    /// `CommonPrefixes`
+    pub common_prefixes: Option<Vec<CommonPrefix>>,
+    /// `Delimiter`
+    pub delimiter: Option<String>,
+    /// `EncodingType`
+    pub encoding_type: Option<String>,
+    /// `IsTruncated`
+    pub is_truncated: Option<bool>,
+    /// `KeyMarker`
+    pub key_marker: Option<String>,
+    /// `MaxKeys`
+    pub max_keys: Option<i64>,
+    /// `Name`
+    pub name: Option<String>,
+    /// `NextKeyMarker`
+    pub next_key_marker: Option<String>,
+    /// `NextVersionIdMarker`
+    pub next_version_id_marker: Option<String>,
+    /// `Prefix`
+    pub prefix: Option<String>,
+    /// `VersionIdMarker`
+    pub version_id_marker: Option<String>,
+    /// `Version` and `DeleteMarker`, ordered by key, then newest first
+    pub versions: Option<Vec<ListedVersion>>,
+}
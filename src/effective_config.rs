@@ -0,0 +1,254 @@
+//! Snapshot of the effective configuration of a service
+
+use crate::body_drain::{self, DrainLimits};
+use crate::bucket_cache::{serialize_millis, BucketCacheConfig};
+use crate::compression::CompressionConfig;
+use crate::errors::S3Result;
+use crate::extra_headers::ExtraHeaders;
+use crate::header_limits::HeaderLimits;
+use crate::operation_policy::OperationPolicy;
+use crate::output::S3Output;
+use crate::path::KeyEncoding;
+use crate::redirect_offload::RedirectOffload;
+use crate::request_id::CorrelationConfig;
+use crate::size_limits::SizeLimits;
+use crate::tls_policy::TlsPolicy;
+use crate::utils::ResponseExt;
+use crate::{Body, Response};
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// the serialized value of a secret
+const REDACTED: &str = "REDACTED";
+
+/// A value which is never revealed by serialization or formatting
+///
+/// `Serialize` writes `"REDACTED"` and `Debug` writes `Secret("REDACTED")`,
+/// so a config which holds secrets can be logged or exposed as a whole.
+/// `Deserialize` reads the plain value.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    /// Wraps `value`
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the plain value
+    pub const fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Unwraps the plain value
+    #[allow(clippy::missing_const_for_fn)] // false positive: a generic value can not be dropped in const
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({:?})", REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+/// The type and options of a storage, see [`S3Storage::storage_config`](crate::S3Storage::storage_config)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct StorageConfig {
+    /// the type of the storage, such as `fs`
+    pub kind: String,
+    /// the wrappers around the storage, outermost first
+    pub wrappers: Vec<String>,
+    /// the serialized options
+    pub options: BTreeMap<String, serde_json::Value>,
+}
+
+impl StorageConfig {
+    /// Constructs a `StorageConfig` without options
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            wrappers: Vec::new(),
+            options: BTreeMap::new(),
+        }
+    }
+
+    /// Adds an option. A secret value must be wrapped in [`Secret`].
+    #[must_use]
+    pub fn option(mut self, name: &str, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).unwrap_or(serde_json::Value::Null);
+        let _prev = self.options.insert(name.to_owned(), value);
+        self
+    }
+
+    /// Adds a wrapper outside the current ones
+    #[must_use]
+    pub fn wrapped(mut self, wrapper: &str) -> Self {
+        self.wrappers.insert(0, wrapper.to_owned());
+        self
+    }
+}
+
+/// The effective configuration of a service, see [`S3Service::effective_config`](crate::S3Service::effective_config)
+#[allow(clippy::struct_excessive_bools)] // flags of a snapshot, not a state machine
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct EffectiveConfig {
+    /// the version of this crate
+    pub version: &'static str,
+    /// the enabled cargo features
+    pub features: Vec<&'static str>,
+    /// the supported operations, in the order they are matched
+    pub operations: Vec<&'static str>,
+    /// whether requests are checked by an auth provider
+    pub auth: bool,
+    /// how keys which are not valid UTF-8 are stored
+    pub key_encoding: KeyEncoding,
+    /// the limits of request headers
+    pub header_limits: HeaderLimits,
+    /// the bucket existence cache
+    pub bucket_cache: Option<BucketCacheConfig>,
+    /// response compression
+    pub compression: Option<CompressionConfig>,
+    /// max logged bytes of a document body, if bodies can be logged
+    pub body_log_limit: Option<usize>,
+    /// whether error responses carry debug headers
+    pub debug_headers: bool,
+    /// whether unsigned requests are served
+    pub anonymous_access: bool,
+    /// whether anonymous `GET /` is rejected
+    pub deny_root_listing: bool,
+    /// whether requests signed by Signature Version 2 are verified instead of rejected
+    pub signature_v2: bool,
+    /// the max difference between the time of a request and the server time, in milliseconds
+    #[serde(
+        rename = "max_clock_skew_ms",
+        serialize_with = "body_drain::serialize_millis"
+    )]
+    pub max_clock_skew: Duration,
+    /// the region which requests must be signed for, or any region
+    pub region: Option<String>,
+    /// the domain whose subdomains are buckets, if virtual-hosted-style requests are served
+    pub base_domain: Option<String>,
+    /// TLS enforcement
+    pub tls_policy: TlsPolicy,
+    /// operations disabled regardless of the requester
+    pub operation_policy: OperationPolicy,
+    /// correlation ids of clients
+    pub correlation: Option<CorrelationConfig>,
+    /// allowed extra response headers of objects
+    pub extra_headers: Option<ExtraHeaders>,
+    /// redirection of large downloads
+    pub redirect_offload: Option<RedirectOffload>,
+    /// how long a write waits until it is readable, in milliseconds, if the barrier is enabled
+    #[serde(
+        rename = "read_after_write_barrier_ms",
+        serialize_with = "serialize_millis"
+    )]
+    pub read_after_write_barrier: Option<Duration>,
+    /// size limits of uploads
+    pub size_limits: SizeLimits,
+    /// limits of draining unread request bodies
+    pub drain_limits: DrainLimits,
+    /// the region of the buckets whose storage returns no location
+    pub default_region: Option<String>,
+    /// whether mutations are audited
+    pub audit: bool,
+    /// whether raw exchanges are recorded
+    pub recorder: bool,
+    /// the storage
+    pub storage: StorageConfig,
+}
+
+/// lists the enabled cargo features
+pub(crate) fn enabled_features() -> Vec<&'static str> {
+    let features = [
+        ("binary", cfg!(feature = "binary")),
+        ("compress-gzip", cfg!(feature = "compress-gzip")),
+        ("compress-zstd", cfg!(feature = "compress-zstd")),
+        ("mmap", cfg!(feature = "mmap")),
+        ("server", cfg!(feature = "server")),
+        ("test-utils", cfg!(feature = "test-utils")),
+        ("tokio-unstable", cfg!(feature = "tokio-unstable")),
+    ];
+    features
+        .iter()
+        .filter(|&&(_, enabled)| enabled)
+        .map(|&(name, _)| name)
+        .collect()
+}
+
+impl S3Output for EffectiveConfig {
+    fn try_into_response(self) -> S3Result<Response> {
+        let body = serde_json::to_vec(&self).map_err(|e| internal_error!(e))?;
+        let mut res = Response::new(Body::from(body));
+        res.set_mime(&mime::APPLICATION_JSON)
+            .map_err(|e| internal_error!(e))?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+
+    #[test]
+    fn secret() {
+        let secret = Secret::new(SECRET.to_owned());
+        assert_eq!(secret.expose(), SECRET);
+        assert_eq!(format!("{:?}", secret), r#"Secret("REDACTED")"#);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), r#""REDACTED""#);
+
+        let parsed: Secret<String> = serde_json::from_str(&format!("{:?}", SECRET)).unwrap();
+        assert_eq!(parsed.into_inner(), SECRET);
+    }
+
+    #[test]
+    fn storage_options() {
+        let config = StorageConfig::new("proxy")
+            .option("endpoint", "http://localhost:9000")
+            .option("secret_key", Secret::new(SECRET))
+            .option("credentials", vec![Secret::new(SECRET)])
+            .wrapped("read-only")
+            .wrapped("circuit-breaker");
+        assert_eq!(config.wrappers, ["circuit-breaker", "read-only"]);
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains(SECRET), "{}", json);
+        assert!(json.contains(r#""secret_key":"REDACTED""#), "{}", json);
+        assert!(
+            json.contains(r#""endpoint":"http://localhost:9000""#),
+            "{}",
+            json
+        );
+
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains(SECRET), "{}", debug);
+    }
+}
@@ -5,18 +5,68 @@ use crate::{BoxStdError, StatusCode};
 
 use std::error::Error;
 use std::fmt::{self, Debug, Display};
+use std::time::Duration;
 
 use backtrace::Backtrace;
 use tracing_error::SpanTrace;
 
+/// The part of a failed upload which a storage has persisted
+///
+/// A storage attaches it to the error of a `PutObject` whose body fails midway,
+/// see [`S3ErrorBuilder::partial_upload`].
+/// It is not rendered to clients, since S3 errors have no field for it.
+/// [`S3Service::hyper_call`](crate::S3Service::hyper_call) inserts it into the extensions of the error response,
+/// and [`RuntimeSnapshot::partial_upload_bytes`](crate::RuntimeSnapshot::partial_upload_bytes) sums up its bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialUpload {
+    /// bytes of the body which the storage consumed and wrote before the failure
+    pub bytes_persisted: u64,
+    /// an opaque token of the storage for resuming the upload, if it keeps the persisted bytes
+    pub resume_token: Option<String>,
+}
+
 /// Type representing an error response
-pub(crate) struct XmlErrorResponse {
+///
+/// It is rendered as an xml body by [`S3Output`](crate::S3Output).
+///
+/// ```
+/// use s3_server::errors::{S3Error, S3ErrorCode};
+/// use s3_server::S3Output;
+///
+/// let err = S3Error::new(S3ErrorCode::NoSuchKey, "The specified key does not exist.");
+/// let res = err.into_xml_response().try_into_response().unwrap();
+/// assert_eq!(res.status(), 404);
+/// ```
+#[derive(Debug)]
+pub struct XmlErrorResponse {
     /// code
     pub(crate) code: S3ErrorCode,
     /// message
     pub(crate) message: Option<String>,
-    // resource: Option<String>, // unimplemented
-    // request_id: Option<String>, // unimplemented
+    /// `Retry-After` in seconds
+    pub(crate) retry_after: Option<u64>,
+    /// the size of an upload which is too large or too small
+    pub(crate) proposed_size: Option<u64>,
+    /// the max size of an upload which is too large
+    pub(crate) max_size_allowed: Option<u64>,
+    /// the min size of an upload which is too small
+    pub(crate) min_size_allowed: Option<u64>,
+    /// the `Range` of a read which is not satisfiable
+    pub(crate) range_requested: Option<String>,
+    /// the size of an object whose range is not satisfiable
+    pub(crate) actual_object_size: Option<u64>,
+    /// the time of a request which is too skewed
+    pub(crate) request_time: Option<String>,
+    /// the time of the server when a request is too skewed
+    pub(crate) server_time: Option<String>,
+    /// the max clock skew allowed, in milliseconds
+    pub(crate) max_allowed_skew_milliseconds: Option<u64>,
+    /// the region which a request should be signed for
+    pub(crate) region: Option<String>,
+    /// the bucket or object of the error
+    pub(crate) resource: Option<String>,
+    /// the id of the request, which is set by the service
+    pub(crate) request_id: Option<String>,
 }
 
 /// `S3ErrorInner`
@@ -26,14 +76,38 @@ struct S3ErrorInner {
     code: S3ErrorCode,
     /// message
     message: Option<String>,
+    /// `Retry-After` in seconds
+    retry_after: Option<u64>,
+    /// the size of an upload which is too large or too small
+    proposed_size: Option<u64>,
+    /// the max size of an upload which is too large
+    max_size_allowed: Option<u64>,
+    /// the min size of an upload which is too small
+    min_size_allowed: Option<u64>,
+    /// the `Range` of a read which is not satisfiable
+    range_requested: Option<String>,
+    /// the size of an object whose range is not satisfiable
+    actual_object_size: Option<u64>,
+    /// the time of a request which is too skewed
+    request_time: Option<String>,
+    /// the time of the server when a request is too skewed
+    server_time: Option<String>,
+    /// the max clock skew allowed, in milliseconds
+    max_allowed_skew_milliseconds: Option<u64>,
+    /// the region which a request should be signed for
+    region: Option<String>,
+    /// the bucket or object of the error
+    resource: Option<String>,
+    /// the storage operation which is not implemented
+    unimplemented_operation: Option<&'static str>,
+    /// the part of a failed upload which the storage has persisted
+    partial_upload: Option<PartialUpload>,
     /// error source
     source: Option<BoxStdError>,
     /// span trace
     span_trace: Option<SpanTrace>,
     /// stack trace
     backtrace: Option<Backtrace>,
-    // resource: Option<String>, // unimplemented
-    // request_id: Option<String>, // unimplemented
 }
 
 // `S3Error` uses `Box` to avoid moving too much bytes.
@@ -88,6 +162,19 @@ impl S3Error {
         S3ErrorInner {
             code,
             message: None,
+            retry_after: None,
+            proposed_size: None,
+            max_size_allowed: None,
+            min_size_allowed: None,
+            range_requested: None,
+            actual_object_size: None,
+            request_time: None,
+            server_time: None,
+            max_allowed_skew_milliseconds: None,
+            region: None,
+            resource: None,
+            unimplemented_operation: None,
+            partial_upload: None,
             source: None,
             span_trace: None,
             backtrace: None,
@@ -95,14 +182,58 @@ impl S3Error {
         .apply(|e| S3ErrorBuilder(Box::new(e)))
     }
 
+    /// Constructs a `NotImplemented` error of a storage operation
+    ///
+    /// The message names the operation, so that clients can tell which operation is missing.
+    #[must_use]
+    pub fn not_implemented(operation: &'static str) -> Self {
+        let mut builder = Self::from_code(S3ErrorCode::NotImplemented).message(format!(
+            "{} is not implemented by this storage backend",
+            operation
+        ));
+        builder.0.unimplemented_operation = Some(operation);
+        builder.finish()
+    }
+
+    /// get the storage operation which is not implemented, see [`S3Error::not_implemented`]
+    #[must_use]
+    pub const fn unimplemented_operation(&self) -> Option<&'static str> {
+        self.0.unimplemented_operation
+    }
+
+    /// get the part of a failed upload which the storage has persisted, see [`PartialUpload`]
+    #[must_use]
+    pub fn partial_upload(&self) -> Option<&PartialUpload> {
+        self.0.partial_upload.as_ref()
+    }
+
     /// consume the error and return an xml response
-    pub(crate) fn into_xml_response(self) -> XmlErrorResponse {
+    #[must_use]
+    pub fn into_xml_response(self) -> XmlErrorResponse {
         XmlErrorResponse {
             code: self.0.code,
             message: self.0.message,
+            retry_after: self.0.retry_after,
+            proposed_size: self.0.proposed_size,
+            max_size_allowed: self.0.max_size_allowed,
+            min_size_allowed: self.0.min_size_allowed,
+            range_requested: self.0.range_requested,
+            actual_object_size: self.0.actual_object_size,
+            request_time: self.0.request_time,
+            server_time: self.0.server_time,
+            max_allowed_skew_milliseconds: self.0.max_allowed_skew_milliseconds,
+            region: self.0.region,
+            resource: self.0.resource,
+            request_id: None,
         }
     }
 
+    /// get error code
+    #[must_use]
+    pub const fn code(&self) -> S3ErrorCode {
+        self.0.code
+    }
+
     /// get span trace
     #[allow(clippy::missing_const_for_fn)] // See <https://github.com/rust-lang/rust-clippy/issues/5995>
     #[must_use]
@@ -130,6 +261,111 @@ impl S3ErrorBuilder {
         self
     }
 
+    /// set `Retry-After`, rounded up to seconds
+    #[inline]
+    #[must_use]
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        let secs = delay.as_secs();
+        let secs = if delay.subsec_nanos() > 0 {
+            secs.saturating_add(1)
+        } else {
+            secs
+        };
+        self.0.retry_after = Some(secs);
+        self
+    }
+
+    /// set the size of an upload which is too large, rendered as `ProposedSize`
+    #[inline]
+    #[must_use]
+    pub fn proposed_size(mut self, size: u64) -> Self {
+        self.0.proposed_size = Some(size);
+        self
+    }
+
+    /// set the max size of an upload, rendered as `MaxSizeAllowed`
+    #[inline]
+    #[must_use]
+    pub fn max_size_allowed(mut self, size: u64) -> Self {
+        self.0.max_size_allowed = Some(size);
+        self
+    }
+
+    /// set the min size of an upload, rendered as `MinSizeAllowed`
+    #[inline]
+    #[must_use]
+    pub fn min_size_allowed(mut self, size: u64) -> Self {
+        self.0.min_size_allowed = Some(size);
+        self
+    }
+
+    /// set the `Range` of a read which is not satisfiable, rendered as `RangeRequested`
+    #[inline]
+    #[must_use]
+    pub fn range_requested(mut self, range: impl Into<String>) -> Self {
+        self.0.range_requested = Some(range.into());
+        self
+    }
+
+    /// set the size of an object whose range is not satisfiable,
+    /// rendered as `ActualObjectSize` and in `Content-Range`
+    #[inline]
+    #[must_use]
+    pub fn actual_object_size(mut self, size: u64) -> Self {
+        self.0.actual_object_size = Some(size);
+        self
+    }
+
+    /// set the time of a request which is too skewed, rendered as `RequestTime`
+    #[inline]
+    #[must_use]
+    pub fn request_time(mut self, time: impl Into<String>) -> Self {
+        self.0.request_time = Some(time.into());
+        self
+    }
+
+    /// set the time of the server, rendered as `ServerTime`
+    #[inline]
+    #[must_use]
+    pub fn server_time(mut self, time: impl Into<String>) -> Self {
+        self.0.server_time = Some(time.into());
+        self
+    }
+
+    /// set the max clock skew allowed, rendered as `MaxAllowedSkewMilliseconds`
+    #[inline]
+    #[must_use]
+    pub fn max_allowed_skew_milliseconds(mut self, millis: u64) -> Self {
+        self.0.max_allowed_skew_milliseconds = Some(millis);
+        self
+    }
+
+    /// set the region which a request should be signed for, rendered as `Region`
+    #[inline]
+    #[must_use]
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.0.region = Some(region.into());
+        self
+    }
+
+    /// set the bucket or object of the error, rendered as `Resource`
+    ///
+    /// [`S3Service`](crate::S3Service) renders the path of the request if it is not set.
+    #[inline]
+    #[must_use]
+    pub fn resource(mut self, resource: impl Into<String>) -> Self {
+        self.0.resource = Some(resource.into());
+        self
+    }
+
+    /// set the part of a failed upload which the storage has persisted, which is not rendered
+    #[inline]
+    #[must_use]
+    pub fn partial_upload(mut self, partial: PartialUpload) -> Self {
+        self.0.partial_upload = Some(partial);
+        self
+    }
+
     /// set error source
     #[inline]
     pub fn source(mut self, e: impl Into<BoxStdError>) -> Self {
@@ -230,13 +466,16 @@ impl From<S3Error> for S3AuthError {
 /// S3 error code enum
 ///
 /// See [`ErrorResponses`](https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(clippy::upper_case_acronyms)]
 #[non_exhaustive]
 pub enum S3ErrorCode {
     /// Access Denied
     AccessDenied,
 
+    /// A CORS request is not allowed by the CORS configuration of the bucket.
+    AccessForbidden,
+
     /// There is a problem with your AWS account that prevents the operation from completing successfully.
     AccountProblem,
 
@@ -327,6 +566,9 @@ pub enum S3ErrorCode {
     /// The part might not have been uploaded, or the specified entity tag might not have matched the part's entity tag.
     InvalidPart,
 
+    /// The requested part number is beyond the parts of the object.
+    InvalidPartNumber,
+
     /// The list of parts was not in ascending order. Parts list must be specified in order by part number.
     InvalidPartOrder,
 
@@ -360,6 +602,9 @@ pub enum S3ErrorCode {
     /// The storage class you specified is not valid.
     InvalidStorageClass,
 
+    /// The tag provided was not a valid tag. This error can occur if the tag did not pass input validation.
+    InvalidTag,
+
     /// The target bucket for logging does not exist, is not owned by you, or does not have the appropriate grants for the log-delivery group.
     InvalidTargetBucketForLogging,
 
@@ -378,6 +623,9 @@ pub enum S3ErrorCode {
     /// The body of your POST request is not well-formed multipart/form-data.
     MalformedPOSTRequest,
 
+    /// The bucket policy is not a valid policy document.
+    MalformedPolicy,
+
     /// This happens when the user sends malformed XML (XML that doesn't conform to the published XSD) for the configuration. The error message is, \"The XML you provided was not well-formed or did not validate against our published schema.\"
     MalformedXML,
 
@@ -417,12 +665,18 @@ pub enum S3ErrorCode {
     /// The specified bucket does not have a bucket policy.
     NoSuchBucketPolicy,
 
+    /// The CORS configuration does not exist.
+    NoSuchCORSConfiguration,
+
     /// The specified key does not exist.
     NoSuchKey,
 
     /// The lifecycle configuration does not exist.
     NoSuchLifecycleConfiguration,
 
+    /// The TagSet does not exist.
+    NoSuchTagSet,
+
     /// The specified multipart upload does not exist. The upload ID might be invalid, or the multipart upload might have been aborted or completed.
     NoSuchUpload,
 
@@ -438,6 +692,12 @@ pub enum S3ErrorCode {
     /// [Custom error code]
     NotSupported,
 
+    /// The object is already in the active tier, so it can not be restored.
+    ObjectAlreadyInActiveTierError,
+
+    /// The bucket or the object has no Object Lock configuration.
+    ObjectLockConfigurationNotFoundError,
+
     /// The source object of the COPY operation is not in the active tier and is only stored in Amazon S3 Glacier.
     ObjectNotInActiveTierError,
 
@@ -456,6 +716,9 @@ pub enum S3ErrorCode {
     /// Object restore is already in progress.
     RestoreAlreadyInProgress,
 
+    /// Your request header section exceeds the maximum allowed size.
+    RequestHeaderSectionTooLarge,
+
     /// Bucket POST must be of the enclosure-type multipart/form-data.
     RequestIsNotMultiPartContent,
 
@@ -516,6 +779,7 @@ impl S3ErrorCode {
     pub const fn as_status_code(self) -> Option<StatusCode> {
         match self {
             Self::AccessDenied => Some(StatusCode::FORBIDDEN),
+            Self::AccessForbidden => Some(StatusCode::FORBIDDEN),
             Self::AccountProblem => Some(StatusCode::FORBIDDEN),
             Self::AllAccessDisabled => Some(StatusCode::FORBIDDEN),
             Self::AmbiguousGrantByEmailAddress => Some(StatusCode::BAD_REQUEST),
@@ -545,6 +809,7 @@ impl S3ErrorCode {
             Self::InvalidLocationConstraint => Some(StatusCode::BAD_REQUEST),
             Self::InvalidObjectState => Some(StatusCode::FORBIDDEN),
             Self::InvalidPart => Some(StatusCode::BAD_REQUEST),
+            Self::InvalidPartNumber => Some(StatusCode::RANGE_NOT_SATISFIABLE),
             Self::InvalidPartOrder => Some(StatusCode::BAD_REQUEST),
             Self::InvalidPayer => Some(StatusCode::FORBIDDEN),
             Self::InvalidPolicyDocument => Some(StatusCode::BAD_REQUEST),
@@ -553,12 +818,14 @@ impl S3ErrorCode {
             Self::InvalidSecurity => Some(StatusCode::FORBIDDEN),
             Self::InvalidSOAPRequest => Some(StatusCode::BAD_REQUEST),
             Self::InvalidStorageClass => Some(StatusCode::BAD_REQUEST),
+            Self::InvalidTag => Some(StatusCode::BAD_REQUEST),
             Self::InvalidTargetBucketForLogging => Some(StatusCode::BAD_REQUEST),
             Self::InvalidToken => Some(StatusCode::BAD_REQUEST),
             Self::InvalidURI => Some(StatusCode::BAD_REQUEST),
             Self::KeyTooLongError => Some(StatusCode::BAD_REQUEST),
             Self::MalformedACLError => Some(StatusCode::BAD_REQUEST),
             Self::MalformedPOSTRequest => Some(StatusCode::BAD_REQUEST),
+            Self::MalformedPolicy => Some(StatusCode::BAD_REQUEST),
             Self::MalformedXML => Some(StatusCode::BAD_REQUEST),
             Self::MaxMessageLengthExceeded => Some(StatusCode::BAD_REQUEST),
             Self::MaxPostPreDataLengthExceededError => Some(StatusCode::BAD_REQUEST),
@@ -572,19 +839,24 @@ impl S3ErrorCode {
             Self::NoLoggingStatusForKey => Some(StatusCode::BAD_REQUEST),
             Self::NoSuchBucket => Some(StatusCode::NOT_FOUND),
             Self::NoSuchBucketPolicy => Some(StatusCode::NOT_FOUND),
+            Self::NoSuchCORSConfiguration => Some(StatusCode::NOT_FOUND),
             Self::NoSuchKey => Some(StatusCode::NOT_FOUND),
             Self::NoSuchLifecycleConfiguration => Some(StatusCode::NOT_FOUND),
+            Self::NoSuchTagSet => Some(StatusCode::NOT_FOUND),
             Self::NoSuchUpload => Some(StatusCode::NOT_FOUND),
             Self::NoSuchVersion => Some(StatusCode::NOT_FOUND),
             Self::NotImplemented => Some(StatusCode::NOT_IMPLEMENTED),
             Self::NotSignedUp => Some(StatusCode::FORBIDDEN),
             Self::NotSupported => None,
+            Self::ObjectAlreadyInActiveTierError => Some(StatusCode::FORBIDDEN),
+            Self::ObjectLockConfigurationNotFoundError => Some(StatusCode::NOT_FOUND),
             Self::ObjectNotInActiveTierError => Some(StatusCode::OK),
             Self::OperationAborted => Some(StatusCode::CONFLICT),
             Self::PermanentRedirect => Some(StatusCode::MOVED_PERMANENTLY),
             Self::PreconditionFailed => Some(StatusCode::PRECONDITION_FAILED),
             Self::Redirect => Some(StatusCode::TEMPORARY_REDIRECT),
             Self::RestoreAlreadyInProgress => Some(StatusCode::CONFLICT),
+            Self::RequestHeaderSectionTooLarge => Some(StatusCode::BAD_REQUEST),
             Self::RequestIsNotMultiPartContent => Some(StatusCode::BAD_REQUEST),
             Self::RequestTimeout => Some(StatusCode::BAD_REQUEST),
             Self::RequestTimeTooSkewed => Some(StatusCode::FORBIDDEN),
@@ -618,6 +890,7 @@ impl S3ErrorCode {
 
         map_variant_to_str![
             AccessDenied,
+            AccessForbidden,
             AccountProblem,
             AllAccessDisabled,
             AmbiguousGrantByEmailAddress,
@@ -647,6 +920,7 @@ impl S3ErrorCode {
             InvalidLocationConstraint,
             InvalidObjectState,
             InvalidPart,
+            InvalidPartNumber,
             InvalidPartOrder,
             InvalidPayer,
             InvalidPolicyDocument,
@@ -655,12 +929,14 @@ impl S3ErrorCode {
             InvalidSecurity,
             InvalidSOAPRequest,
             InvalidStorageClass,
+            InvalidTag,
             InvalidTargetBucketForLogging,
             InvalidToken,
             InvalidURI,
             KeyTooLongError,
             MalformedACLError,
             MalformedPOSTRequest,
+            MalformedPolicy,
             MalformedXML,
             MaxMessageLengthExceeded,
             MaxPostPreDataLengthExceededError,
@@ -674,19 +950,24 @@ impl S3ErrorCode {
             NoLoggingStatusForKey,
             NoSuchBucket,
             NoSuchBucketPolicy,
+            NoSuchCORSConfiguration,
             NoSuchKey,
             NoSuchLifecycleConfiguration,
+            NoSuchTagSet,
             NoSuchUpload,
             NoSuchVersion,
             NotImplemented,
             NotSignedUp,
             NotSupported,
+            ObjectAlreadyInActiveTierError,
+            ObjectLockConfigurationNotFoundError,
             ObjectNotInActiveTierError,
             OperationAborted,
             PermanentRedirect,
             PreconditionFailed,
             Redirect,
             RestoreAlreadyInProgress,
+            RequestHeaderSectionTooLarge,
             RequestIsNotMultiPartContent,
             RequestTimeout,
             RequestTimeTooSkewed,
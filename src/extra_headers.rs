@@ -0,0 +1,221 @@
+//! Response headers of objects, stored as user metadata in a reserved namespace
+
+use crate::errors::S3Result;
+use crate::ops::ReqContext;
+use crate::Response;
+
+use std::collections::BTreeSet;
+
+use hyper::header::{HeaderName, HeaderValue};
+use serde::Serialize;
+
+/// the metadata prefix of extra headers, without `x-amz-meta-`
+const METADATA_KEY_PREFIX: &str = "header-";
+
+/// the request and response headers of extra headers
+const HEADER_PREFIX: &str = "x-amz-meta-header-";
+
+/// hop-by-hop, framing and security-sensitive headers, and the headers of the standard fields
+const RESERVED: &[&str] = &[
+    "accept-ranges",
+    "authorization",
+    "cache-control",
+    "connection",
+    "content-disposition",
+    "content-encoding",
+    "content-language",
+    "content-length",
+    "content-location",
+    "content-range",
+    "content-security-policy",
+    "content-type",
+    "date",
+    "etag",
+    "expires",
+    "host",
+    "keep-alive",
+    "last-modified",
+    "location",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "refresh",
+    "retry-after",
+    "server",
+    "set-cookie",
+    "set-cookie2",
+    "strict-transport-security",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "vary",
+    "www-authenticate",
+];
+
+/// prefixes of reserved headers
+const RESERVED_PREFIXES: &[&str] = &["access-control-", "proxy-", "sec-", "x-amz-"];
+
+/// whether an operation writes the metadata of an object
+pub(crate) fn is_write(operation: &str) -> bool {
+    matches!(
+        operation,
+        "PutObject" | "CreateMultipartUpload" | "CopyObject"
+    )
+}
+
+/// Options of extra response headers, see [`S3Service::set_extra_headers`](crate::S3Service::set_extra_headers)
+///
+/// An object carries an extra header `<name>: <value>` as the user metadata `header-<name>`,
+/// which is written by `PutObject`, `CreateMultipartUpload` and `CopyObject`
+/// as `x-amz-meta-header-<name>: <value>`, or set directly by a storage (see [`ExtraHeaders::metadata_key`]).
+/// `GetObject` and `HeadObject` emit it as `<name>: <value>` after the standard headers.
+///
+/// Only the allowed names are accepted: a write with another name fails with `InvalidArgument` (400).
+/// Hop-by-hop, framing, cookie, CORS, `x-amz-*` and the headers of the standard fields,
+/// such as `Content-Type` and `Cache-Control`, are never passed through even if they are allowed,
+/// so an extra header never overrides a standard header.
+/// At read time, metadata of a name which is not allowed is dropped from the response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExtraHeaders {
+    /// allowed names in lowercase
+    allowed: BTreeSet<String>,
+}
+
+impl ExtraHeaders {
+    /// Constructs an empty allowlist
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the header `name`, case-insensitively
+    #[must_use]
+    pub fn allow(mut self, name: &str) -> Self {
+        let _prev = self.allowed.insert(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Checks whether the header `name` is passed through
+    #[must_use]
+    pub fn is_allowed(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        let is_reserved = RESERVED.contains(&name.as_str())
+            || RESERVED_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix));
+        !is_reserved && self.allowed.contains(&name)
+    }
+
+    /// Returns the metadata key which carries the header `name`, such as `header-x-robots-tag`
+    #[must_use]
+    pub fn metadata_key(name: &str) -> String {
+        format!("{}{}", METADATA_KEY_PREFIX, name.to_ascii_lowercase())
+    }
+
+    /// rejects the extra headers of a write which are not allowed
+    pub(crate) fn check_request(&self, ctx: &ReqContext<'_>) -> S3Result<()> {
+        for &(name, _) in ctx.headers.as_ref() {
+            self.check_name(name)?;
+        }
+        if let Some(ref multipart) = ctx.multipart {
+            for &(ref name, ref value) in &multipart.fields {
+                let name = name.to_ascii_lowercase();
+                self.check_name(&name)?;
+                if name.starts_with(HEADER_PREFIX) && HeaderValue::from_str(value).is_err() {
+                    return Err(code_error!(
+                        InvalidArgument,
+                        format!("The value of the extra header {} is invalid.", name)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// rejects a request header or form field of an extra header which is not allowed
+    fn check_name(&self, name: &str) -> S3Result<()> {
+        let header = match name.strip_prefix(HEADER_PREFIX) {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        if HeaderName::from_bytes(header.as_bytes()).is_err() || !self.is_allowed(header) {
+            return Err(code_error!(
+                InvalidArgument,
+                format!("The extra header {:?} is not allowed.", header)
+            ));
+        }
+        Ok(())
+    }
+
+    /// replaces the metadata headers of extra headers by the headers
+    pub(crate) fn apply(&self, res: &mut Response) {
+        let names: Vec<HeaderName> = res
+            .headers()
+            .keys()
+            .filter(|name| name.as_str().starts_with(HEADER_PREFIX))
+            .cloned()
+            .collect();
+        let mut extra = Vec::new();
+        for name in names {
+            let header = name.as_str().get(HEADER_PREFIX.len()..).unwrap_or_default();
+            let header = HeaderName::from_bytes(header.as_bytes())
+                .ok()
+                .filter(|header| self.is_allowed(header.as_str()));
+            let values = res.headers_mut().remove(&name);
+            if let (Some(header), Some(value)) = (header, values) {
+                extra.push((header, value));
+            }
+        }
+        for (header, value) in extra {
+            if !res.headers().contains_key(&header) {
+                let _prev = res.headers_mut().insert(header, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Body;
+
+    #[test]
+    fn allowlist() {
+        let extra = ExtraHeaders::new()
+            .allow("X-Robots-Tag")
+            .allow("set-cookie")
+            .allow("x-amz-website-redirect-location");
+        assert!(extra.is_allowed("x-robots-tag"));
+        assert!(extra.is_allowed("X-ROBOTS-TAG"));
+        assert!(!extra.is_allowed("x-provenance"));
+        assert!(!extra.is_allowed("set-cookie"));
+        assert!(!extra.is_allowed("x-amz-website-redirect-location"));
+        assert_eq!(
+            ExtraHeaders::metadata_key("X-Robots-Tag"),
+            "header-x-robots-tag"
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let extra = ExtraHeaders::new().allow("x-robots-tag");
+        let mut res = Response::new(Body::empty());
+        for &(name, value) in &[
+            ("x-amz-meta-header-x-robots-tag", "noindex"),
+            ("x-amz-meta-header-set-cookie", "a=b"),
+            ("x-amz-meta-color", "blue"),
+        ] {
+            let _prev = res
+                .headers_mut()
+                .insert(name, HeaderValue::from_static(value));
+        }
+        extra.apply(&mut res);
+        let headers = res.headers();
+        assert_eq!(headers["x-robots-tag"], "noindex");
+        assert_eq!(headers["x-amz-meta-color"], "blue");
+        assert!(!headers.contains_key("set-cookie"));
+        assert_eq!(headers.len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+//! Limits of request headers
+
+use crate::errors::S3Result;
+use crate::Request;
+
+use serde::{Deserialize, Serialize};
+
+/// The prefix of user-defined metadata headers
+const METADATA_PREFIX: &str = "x-amz-meta-";
+
+/// The default max buffer size of hyper, which holds the whole request head
+const HYPER_MAX_BUF_SIZE: usize = 8192 + 4096 * 100;
+
+/// Limits of request headers which are checked before dispatching
+///
+/// Requests over the limits are rejected with `MetadataTooLarge` or `RequestHeaderSectionTooLarge` (400),
+/// and the connection stays usable.
+///
+/// hyper rejects a request head larger than its buffer, or with more than 100 headers,
+/// with a bare `431` before the service sees it.
+/// Configure the server by [`HeaderLimits::http1_max_buf_size`] so that only the count limit is left to hyper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderLimits {
+    /// max number of headers
+    pub max_header_count: usize,
+
+    /// max total bytes of header names and values
+    pub max_header_bytes: usize,
+
+    /// max number of `x-amz-meta-*` headers
+    pub max_metadata_count: usize,
+
+    /// max total bytes of metadata keys (without the prefix) and values
+    pub max_metadata_bytes: usize,
+}
+
+impl Default for HeaderLimits {
+    /// The limits of S3: 8 KB of headers, of which 2 KB are user-defined metadata
+    fn default() -> Self {
+        Self {
+            max_header_count: 100,
+            max_header_bytes: 8 * 1024,
+            max_metadata_count: 100,
+            max_metadata_bytes: 2 * 1024,
+        }
+    }
+}
+
+impl HeaderLimits {
+    /// The buffer size to configure hyper with (`Server::http1_max_buf_size`)
+    ///
+    /// It is large enough for an oversize request head to reach the service.
+    #[must_use]
+    pub fn http1_max_buf_size(&self) -> usize {
+        self.max_header_bytes
+            .saturating_mul(2)
+            .max(HYPER_MAX_BUF_SIZE)
+    }
+
+    /// Checks the headers of `req`
+    pub(crate) fn check(&self, req: &Request) -> S3Result<()> {
+        let mut metadata_count: usize = 0;
+        let mut metadata_bytes: usize = 0;
+        let mut header_bytes: usize = 0;
+
+        for (name, value) in req.headers() {
+            let (name, value) = (name.as_str(), value.as_bytes());
+            header_bytes = header_bytes
+                .saturating_add(name.len())
+                .saturating_add(value.len());
+            if let Some(key) = name.strip_prefix(METADATA_PREFIX) {
+                metadata_count = metadata_count.saturating_add(1);
+                metadata_bytes = metadata_bytes
+                    .saturating_add(key.len())
+                    .saturating_add(value.len());
+            }
+        }
+
+        if metadata_count > self.max_metadata_count || metadata_bytes > self.max_metadata_bytes {
+            return Err(code_error!(
+                MetadataTooLarge,
+                format!(
+                    "Your metadata headers exceed the maximum allowed metadata size: \
+                     {} headers of {} bytes, the limits are {} headers of {} bytes.",
+                    metadata_count,
+                    metadata_bytes,
+                    self.max_metadata_count,
+                    self.max_metadata_bytes
+                )
+            ));
+        }
+
+        let header_count = req.headers().len();
+        if header_count > self.max_header_count || header_bytes > self.max_header_bytes {
+            return Err(code_error!(
+                RequestHeaderSectionTooLarge,
+                format!(
+                    "Your request header section exceeds the maximum allowed size: \
+                     {} headers of {} bytes, the limits are {} headers of {} bytes.",
+                    header_count, header_bytes, self.max_header_count, self.max_header_bytes
+                )
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+    use crate::Body;
+
+    use hyper::header::{HeaderName, HeaderValue};
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut req = Request::new(Body::empty());
+        for &(name, value) in headers {
+            let _prev = req.headers_mut().append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        req
+    }
+
+    #[test]
+    fn check() {
+        let limits = HeaderLimits {
+            max_header_count: 4,
+            max_header_bytes: 64,
+            max_metadata_count: 2,
+            max_metadata_bytes: 16,
+        };
+        let code =
+            |headers: &[(&str, &str)]| limits.check(&request(headers)).err().map(|e| e.code());
+
+        assert_eq!(code(&[("host", "localhost"), ("x-amz-meta-a", "b")]), None);
+        assert_eq!(
+            code(&[
+                ("x-amz-meta-a", "1"),
+                ("x-amz-meta-b", "2"),
+                ("x-amz-meta-c", "3")
+            ]),
+            Some(S3ErrorCode::MetadataTooLarge)
+        );
+        assert_eq!(
+            code(&[("x-amz-meta-key", "0123456789abcdef")]),
+            Some(S3ErrorCode::MetadataTooLarge)
+        );
+        assert_eq!(
+            code(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4"), ("e", "5")]),
+            Some(S3ErrorCode::RequestHeaderSectionTooLarge)
+        );
+        assert_eq!(
+            code(&[("a", &"x".repeat(64))]),
+            Some(S3ErrorCode::RequestHeaderSectionTooLarge)
+        );
+
+        assert_eq!(
+            HeaderLimits::default().http1_max_buf_size(),
+            HYPER_MAX_BUF_SIZE
+        );
+    }
+}
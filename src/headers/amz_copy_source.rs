@@ -1,4 +1,6 @@
 //! x-amz-copy-source
+//!
+//! The source is `bucket/key` or `/bucket/key`, optionally followed by `?versionId=<version id>`.
 
 use crate::path::S3Path;
 
@@ -14,6 +16,8 @@ pub enum AmzCopySource<'a> {
         bucket: &'a str,
         /// key
         key: &'a str,
+        /// the version of the source, if it is given
+        version_id: Option<&'a str>,
     },
     /// access point repr
     AccessPoint {
@@ -68,6 +72,9 @@ impl<'a> AmzCopySource<'a> {
         // TODO: support access point
         // TODO: use nom parser
 
+        let header = header.strip_prefix('/').unwrap_or(header);
+        let (header, version_id) = Self::split_version_id(header);
+
         // bucket pattern
         let pattern: &Regex = static_regex!("^(.+?)/(.+)$");
 
@@ -85,8 +92,57 @@ impl<'a> AmzCopySource<'a> {
                     return Err(ParseAmzCopySourceError::InvalidKey);
                 }
 
-                Ok(Self::Bucket { bucket, key })
+                Ok(Self::Bucket {
+                    bucket,
+                    key,
+                    version_id,
+                })
+            }
+        }
+    }
+
+    /// Splits `?versionId=<version id>` off the end of a copy source
+    #[must_use]
+    pub fn split_version_id(header: &str) -> (&str, Option<&str>) {
+        const VERSION_ID: &str = "?versionId=";
+        header.rfind(VERSION_ID).map_or((header, None), |idx| {
+            let version_id = header.get(idx.saturating_add(VERSION_ID.len())..);
+            (header.get(..idx).unwrap_or(header), version_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket() {
+        let cases = [
+            ("asd/qwe", ("asd", "qwe", None)),
+            ("/asd/a/b", ("asd", "a/b", None)),
+            (
+                "/asd/qwe?versionId=3sL4kqtJ",
+                ("asd", "qwe", Some("3sL4kqtJ")),
+            ),
+        ];
+        for &(header, expected) in &cases {
+            match AmzCopySource::from_header_str(header).unwrap() {
+                AmzCopySource::Bucket {
+                    bucket,
+                    key,
+                    version_id,
+                } => assert_eq!((bucket, key, version_id), expected),
+                AmzCopySource::AccessPoint { .. } => panic!("unexpected access point"),
             }
         }
+        for &header in &["asd", "/asd/?versionId=1"] {
+            let ret = AmzCopySource::from_header_str(header);
+            assert!(
+                matches!(ret, Err(ParseAmzCopySourceError::PatternMismatch)),
+                "{:?}",
+                ret
+            );
+        }
     }
 }
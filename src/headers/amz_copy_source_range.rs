@@ -0,0 +1,84 @@
+//! x-amz-copy-source-range
+
+/// x-amz-copy-source-range
+///
+/// The value is `bytes=first-last`, the zero-based offsets of the first and the last byte to copy.
+/// Unlike `Range`, both offsets are required and there is only one range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmzCopySourceRange {
+    /// offset of the first byte
+    pub first: u64,
+    /// offset of the last byte, inclusive
+    pub last: u64,
+}
+
+/// `ParseAmzCopySourceRangeError`
+#[allow(missing_copy_implementations)] // Why? See `crate::path::ParseS3PathError`.
+#[derive(Debug, thiserror::Error)]
+#[error("ParseAmzCopySourceRangeError")]
+pub struct ParseAmzCopySourceRangeError {
+    /// private place holder
+    _priv: (),
+}
+
+impl AmzCopySourceRange {
+    /// Parses `AmzCopySourceRange` from header
+    /// # Errors
+    /// Returns an error if the header is invalid, or the last offset is before the first
+    pub fn from_header_str(header: &str) -> Result<Self, ParseAmzCopySourceRangeError> {
+        let err = || ParseAmzCopySourceRangeError { _priv: () };
+
+        let range = header.strip_prefix("bytes=").ok_or_else(err)?;
+        let mut iter = range.splitn(2, '-');
+        let mut offset = || {
+            let digits = iter
+                .next()
+                .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()));
+            digits.and_then(|s| s.parse::<u64>().ok()).ok_or_else(err)
+        };
+        let first = offset()?;
+        let last = offset()?;
+        if last < first {
+            return Err(err());
+        }
+
+        Ok(Self { first, last })
+    }
+
+    /// the number of bytes in the range, which is at least one
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        self.last.saturating_sub(self.first).saturating_add(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let range = AmzCopySourceRange::from_header_str("bytes=0-9").unwrap();
+        assert_eq!((range.first, range.last, range.size()), (0, 9, 10));
+
+        let range = AmzCopySourceRange::from_header_str("bytes=5-5").unwrap();
+        assert_eq!(range.size(), 1);
+
+        for &header in &[
+            "bytes=9-0",
+            "bytes=0-",
+            "bytes=-9",
+            "bytes=0-9,20-29",
+            "bytes= 0-9",
+            "bytes=+0-9",
+            "0-9",
+            "items=0-9",
+        ] {
+            assert!(
+                AmzCopySourceRange::from_header_str(header).is_err(),
+                "{}",
+                header
+            );
+        }
+    }
+}
@@ -1,5 +1,9 @@
 //! x-amz-date
 
+use std::convert::TryInto;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
 /// x-amz-date
 #[derive(Debug, Clone, Copy)]
 pub struct AmzDate {
@@ -104,4 +108,11 @@ impl AmzDate {
     pub fn to_date(&self) -> String {
         format!("{:04}{:02}{:02}", self.year, self.month, self.day,)
     }
+
+    /// the time in UTC, or `None` if the date does not exist
+    pub(crate) fn to_date_time(self) -> Option<DateTime<Utc>> {
+        let date = NaiveDate::from_ymd_opt(self.year.try_into().ok()?, self.month, self.day)?;
+        let time = date.and_hms_opt(self.hour, self.minute, self.second)?;
+        Some(Utc.from_utc_datetime(&time))
+    }
 }
@@ -0,0 +1,129 @@
+//! x-amz-grant-*
+
+use crate::dto::{Grant, Grantee};
+
+/// A grantee of an `x-amz-grant-*` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmzGrantee<'a> {
+    /// `id="..."`, the canonical user id of an account
+    Id(&'a str),
+    /// `uri="..."`, a predefined group
+    Uri(&'a str),
+    /// `emailAddress="..."`, the email address of an account
+    EmailAddress(&'a str),
+}
+
+/// x-amz-grant-read, x-amz-grant-write, x-amz-grant-read-acp, x-amz-grant-write-acp
+/// and x-amz-grant-full-control
+///
+/// The value is a comma-separated list of grantees, such as
+/// `id="111122223333", uri="http://acs.amazonaws.com/groups/global/AllUsers"`.
+/// The quotes are optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmzGrant<'a> {
+    /// the grantees, in the order of the header
+    pub grantees: Vec<AmzGrantee<'a>>,
+}
+
+/// `ParseAmzGrantError`
+#[allow(missing_copy_implementations)] // Why? See `crate::path::ParseS3PathError`.
+#[derive(Debug, thiserror::Error)]
+#[error("ParseAmzGrantError")]
+pub struct ParseAmzGrantError {
+    /// private place holder
+    _priv: (),
+}
+
+impl<'a> AmzGrant<'a> {
+    /// Parses `AmzGrant` from header
+    /// # Errors
+    /// Returns an error if the header is empty, or a grantee is malformed or of an unknown type
+    pub fn from_header_str(header: &'a str) -> Result<Self, ParseAmzGrantError> {
+        let err = || ParseAmzGrantError { _priv: () };
+
+        let mut grantees = Vec::new();
+        for spec in header.split(',') {
+            let mut iter = spec.splitn(2, '=');
+            let kind = iter.next().ok_or_else(err)?.trim();
+            let value = iter.next().ok_or_else(err)?.trim();
+
+            let value = match value.strip_prefix('"') {
+                Some(quoted) => quoted.strip_suffix('"').ok_or_else(err)?,
+                None => value,
+            };
+            if value.is_empty() || value.contains('"') {
+                return Err(err());
+            }
+
+            let grantee = match kind {
+                "id" => AmzGrantee::Id(value),
+                "uri" => AmzGrantee::Uri(value),
+                "emailAddress" => AmzGrantee::EmailAddress(value),
+                _ => return Err(err()),
+            };
+            grantees.push(grantee);
+        }
+
+        Ok(Self { grantees })
+    }
+
+    /// Converts the grantees into the grants of an ACL with `permission`,
+    /// such as `READ` or `FULL_CONTROL`
+    #[must_use]
+    pub fn into_grants(self, permission: &str) -> Vec<Grant> {
+        self.grantees
+            .into_iter()
+            .map(|grantee| Grant {
+                grantee: Some(grantee.into()),
+                permission: Some(permission.to_owned()),
+            })
+            .collect()
+    }
+}
+
+impl From<AmzGrantee<'_>> for Grantee {
+    fn from(grantee: AmzGrantee<'_>) -> Self {
+        let (type_, id, uri, email_address) = match grantee {
+            AmzGrantee::Id(id) => ("CanonicalUser", Some(id), None, None),
+            AmzGrantee::Uri(uri) => ("Group", None, Some(uri), None),
+            AmzGrantee::EmailAddress(email) => ("AmazonCustomerByEmail", None, None, Some(email)),
+        };
+        Self {
+            display_name: None,
+            email_address: email_address.map(ToOwned::to_owned),
+            id: id.map(ToOwned::to_owned),
+            type_: type_.to_owned(),
+            uri: uri.map(ToOwned::to_owned),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_grants() {
+        let header = r#"id="a", uri="http://acs.amazonaws.com/groups/global/AllUsers""#;
+        let grants = AmzGrant::from_header_str(header)
+            .unwrap()
+            .into_grants("READ");
+        assert_eq!(grants.len(), 2);
+        assert!(grants
+            .iter()
+            .all(|grant| grant.permission.as_deref() == Some("READ")));
+
+        let user = grants.first().and_then(|grant| grant.grantee.as_ref());
+        let user = user.unwrap();
+        assert_eq!(user.type_, "CanonicalUser");
+        assert_eq!(user.id.as_deref(), Some("a"));
+
+        let group = grants.last().and_then(|grant| grant.grantee.as_ref());
+        let group = group.unwrap();
+        assert_eq!(group.type_, "Group");
+        assert_eq!(
+            group.uri.as_deref(),
+            Some("http://acs.amazonaws.com/groups/global/AllUsers")
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! x-amz-mfa
+
+/// x-amz-mfa
+///
+/// The value is the serial number of the device and the code on it, separated by a space.
+#[derive(Debug, Clone, Copy)]
+pub struct AmzMfa<'a> {
+    /// serial number or ARN of the device
+    pub serial: &'a str,
+    /// authentication code
+    pub code: &'a str,
+}
+
+/// `ParseAmzMfaError`
+#[allow(missing_copy_implementations)] // Why? See `crate::path::ParseS3PathError`.
+#[derive(Debug, thiserror::Error)]
+#[error("ParseAmzMfaError")]
+pub struct ParseAmzMfaError {
+    /// private place holder
+    _priv: (),
+}
+
+impl<'a> AmzMfa<'a> {
+    /// Parses `AmzMfa` from header
+    /// # Errors
+    /// Returns an error if the header is invalid
+    pub fn from_header_str(header: &'a str) -> Result<Self, ParseAmzMfaError> {
+        let err = || ParseAmzMfaError { _priv: () };
+
+        let mut iter = header.splitn(2, ' ');
+        let serial = iter.next().ok_or_else(err)?;
+        let code = iter.next().ok_or_else(err)?;
+
+        let is_valid_code = !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit());
+        if serial.is_empty() || !is_valid_code {
+            return Err(err());
+        }
+
+        Ok(Self { serial, code })
+    }
+}
@@ -2,13 +2,21 @@
 
 mod amz_content_sha256;
 mod amz_copy_source;
+mod amz_copy_source_range;
 mod amz_date;
+mod amz_grant;
+mod amz_mfa;
 mod authorization_v4;
+mod range;
 
 pub use self::amz_content_sha256::AmzContentSha256;
 pub use self::amz_copy_source::AmzCopySource;
+pub use self::amz_copy_source_range::{AmzCopySourceRange, ParseAmzCopySourceRangeError};
 pub use self::amz_date::AmzDate;
+pub use self::amz_grant::{AmzGrant, AmzGrantee, ParseAmzGrantError};
+pub use self::amz_mfa::AmzMfa;
 pub use self::authorization_v4::{AuthorizationV4, CredentialV4};
+pub use self::range::{ParseRangeError, Range};
 
 pub use hyper::header::*;
 
@@ -69,6 +77,9 @@ declare_header_name! {
     /// x-amz-copy-source-if-unmodified-since
     X_AMZ_COPY_SOURCE_IF_UNMODIFIED_SINCE: "x-amz-copy-source-if-unmodified-since";
 
+    /// x-amz-copy-source-range
+    X_AMZ_COPY_SOURCE_RANGE: "x-amz-copy-source-range";
+
     /// x-amz-grant-full-control
     X_AMZ_GRANT_FULL_CONTROL: "x-amz-grant-full-control";
 
@@ -84,6 +95,9 @@ declare_header_name! {
     /// x-amz-grant-write-acp
     X_AMZ_GRANT_WRITE_ACP: "x-amz-grant-write-acp";
 
+    /// x-amz-id-2
+    X_AMZ_ID_2: "x-amz-id-2";
+
     /// x-amz-metadata-directive
     X_AMZ_METADATA_DIRECTIVE: "x-amz-metadata-directive";
 
@@ -123,6 +137,9 @@ declare_header_name! {
     /// x-amz-copy-source-server-side-encryption-customer-key-MD5
     X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5: "x-amz-copy-source-server-side-encryption-customer-key-md5";
 
+    /// x-amz-request-id
+    X_AMZ_REQUEST_ID: "x-amz-request-id";
+
     /// x-amz-request-payer
     X_AMZ_REQUEST_PAYER: "x-amz-request-payer";
 
@@ -144,6 +161,9 @@ declare_header_name! {
     /// x-amz-restore
     X_AMZ_RESTORE: "x-amz-restore";
 
+    /// x-amz-restore-output-path
+    X_AMZ_RESTORE_OUTPUT_PATH: "x-amz-restore-output-path";
+
     /// x-amz-missing-meta
     X_AMZ_MISSING_META: "x-amz-missing-meta";
 
@@ -162,15 +182,27 @@ declare_header_name! {
     /// x-amz-bucket-object-lock-enabled
     X_AMZ_BUCKET_OBJECT_LOCK_ENABLED: "x-amz-bucket-object-lock-enabled";
 
+    /// x-amz-bucket-object-lock-token
+    X_AMZ_BUCKET_OBJECT_LOCK_TOKEN: "x-amz-bucket-object-lock-token";
+
+    /// x-amz-bucket-region
+    X_AMZ_BUCKET_REGION: "x-amz-bucket-region";
+
     /// x-amz-bypass-governance-retention
     X_AMZ_BYPASS_GOVERNANCE_RETENTION: "x-amz-bypass-governance-retention";
 
+    /// x-amz-confirm-remove-self-bucket-access
+    X_AMZ_CONFIRM_REMOVE_SELF_BUCKET_ACCESS: "x-amz-confirm-remove-self-bucket-access";
+
     /// x-amz-date
     X_AMZ_DATE: "x-amz-date";
 
     /// x-amz-content-sha256
     X_AMZ_CONTENT_SHA256: "x-amz-content-sha256";
 
+    /// x-amz-decoded-content-length
+    X_AMZ_DECODED_CONTENT_LENGTH: "x-amz-decoded-content-length";
+
     /// x-amz-abort-date
     X_AMZ_ABORT_DATE: "x-amz-abort-date";
 
@@ -179,4 +211,15 @@ declare_header_name! {
 
     /// x-amz-expected-bucket-owner
     X_AMZ_EXPECTED_BUCKET_OWNER: "x-amz-expected-bucket-owner";
+
+    /// x-amz-source-expected-bucket-owner
+    X_AMZ_SOURCE_EXPECTED_BUCKET_OWNER: "x-amz-source-expected-bucket-owner";
+
+    /// x-amz-unimplemented-operation, the storage operation of a `NotImplemented` error
+    /// (see [`S3Service::set_debug_headers`](crate::S3Service::set_debug_headers))
+    X_AMZ_UNIMPLEMENTED_OPERATION: "x-amz-unimplemented-operation";
+
+    /// x-s3-server-log-body, which asks for the bodies of the request to be logged
+    /// (see [`S3Service::set_body_log`](crate::S3Service::set_body_log))
+    X_S3_SERVER_LOG_BODY: "x-s3-server-log-body";
 }
@@ -0,0 +1,157 @@
+//! Range
+
+/// Range
+///
+/// The value is `bytes=first-last`, `bytes=first-` or `bytes=-length`, the zero-based offsets
+/// of the first and the last byte to read, or the length of a suffix.
+/// Only one range is supported, although the syntax allows several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Range {
+    /// `bytes=first-last` or `bytes=first-`
+    Normal {
+        /// offset of the first byte
+        first: u64,
+        /// offset of the last byte, inclusive, or the end of the object
+        last: Option<u64>,
+    },
+    /// `bytes=-length`
+    Suffix {
+        /// the number of the last bytes
+        length: u64,
+    },
+}
+
+/// `ParseRangeError`
+#[allow(missing_copy_implementations)] // Why? See `crate::path::ParseS3PathError`.
+#[derive(Debug, thiserror::Error)]
+#[error("ParseRangeError")]
+pub struct ParseRangeError {
+    /// whether the header has several ranges
+    multiple: bool,
+}
+
+impl ParseRangeError {
+    /// Whether the header has several ranges, which are valid but not supported
+    #[must_use]
+    pub const fn is_multiple(&self) -> bool {
+        self.multiple
+    }
+}
+
+impl Range {
+    /// Parses `Range` from header
+    /// # Errors
+    /// Returns an error if the header is invalid, has several ranges, or the last offset is before the first
+    pub fn from_header_str(header: &str) -> Result<Self, ParseRangeError> {
+        let err = || ParseRangeError { multiple: false };
+
+        let range = header.strip_prefix("bytes=").ok_or_else(err)?;
+        if range.contains(',') {
+            return Err(ParseRangeError { multiple: true });
+        }
+        let parse = |s: &str| {
+            let digits = Some(s).filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()));
+            digits.and_then(|s| s.parse::<u64>().ok()).ok_or_else(err)
+        };
+
+        let mut iter = range.splitn(2, '-');
+        let first = iter.next().ok_or_else(err)?;
+        let last = iter.next().ok_or_else(err)?;
+        if first.is_empty() {
+            return Ok(Self::Suffix {
+                length: parse(last)?,
+            });
+        }
+        let first = parse(first)?;
+        let last = if last.is_empty() {
+            None
+        } else {
+            Some(parse(last)?)
+        };
+        if last.map_or(false, |last| last < first) {
+            return Err(err());
+        }
+
+        Ok(Self::Normal { first, last })
+    }
+
+    /// The bytes of an object of `size` bytes in the range, which is cut at the end of the object
+    ///
+    /// Returns `None` if the range is not satisfiable, which is when it starts past the end,
+    /// or it is an empty suffix, or the object is empty.
+    #[must_use]
+    pub fn satisfy(&self, size: u64) -> Option<std::ops::Range<u64>> {
+        match *self {
+            Self::Normal { first, last } => {
+                if first >= size {
+                    return None;
+                }
+                let end = last.map_or(size, |last| last.saturating_add(1).min(size));
+                Some(first..end)
+            }
+            Self::Suffix { length } => {
+                if length == 0 || size == 0 {
+                    return None;
+                }
+                Some(size.saturating_sub(length)..size)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parse = |header| Range::from_header_str(header).unwrap();
+        assert_eq!(
+            parse("bytes=0-1023"),
+            Range::Normal {
+                first: 0,
+                last: Some(1023)
+            }
+        );
+        assert_eq!(
+            parse("bytes=100-"),
+            Range::Normal {
+                first: 100,
+                last: None
+            }
+        );
+        assert_eq!(parse("bytes=-500"), Range::Suffix { length: 500 });
+
+        for &header in &[
+            "bytes=9-0",
+            "bytes=-",
+            "bytes= 0-9",
+            "bytes=+0-9",
+            "bytes=0-9-",
+            "0-9",
+            "items=0-9",
+        ] {
+            let err = Range::from_header_str(header).unwrap_err();
+            assert!(!err.is_multiple(), "{}", header);
+        }
+        let err = Range::from_header_str("bytes=0-9,20-29").unwrap_err();
+        assert!(err.is_multiple());
+    }
+
+    #[test]
+    fn satisfy() {
+        let satisfy = |header, size| Range::from_header_str(header).unwrap().satisfy(size);
+        assert_eq!(satisfy("bytes=0-1023", 2000), Some(0..1024));
+        assert_eq!(satisfy("bytes=0-1023", 100), Some(0..100));
+        assert_eq!(satisfy("bytes=100-", 2000), Some(100..2000));
+        assert_eq!(satisfy("bytes=-500", 2000), Some(1500..2000));
+        assert_eq!(satisfy("bytes=-500", 100), Some(0..100));
+        assert_eq!(satisfy("bytes=99-", 100), Some(99..100));
+
+        assert_eq!(satisfy("bytes=100-", 100), None);
+        assert_eq!(satisfy("bytes=200-300", 100), None);
+        assert_eq!(satisfy("bytes=-0", 100), None);
+        assert_eq!(satisfy("bytes=0-0", 0), None);
+        assert_eq!(satisfy("bytes=-500", 0), None);
+    }
+}
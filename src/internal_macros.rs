@@ -101,6 +101,15 @@ macro_rules! code_error {
     }};
 }
 
+/// Create a `NotImplemented` error of a storage operation
+macro_rules! not_implemented {
+    ($operation:literal) => {{
+        let err = $crate::errors::S3Error::not_implemented($operation);
+        tracing::debug!("generated s3 error: {}", err);
+        err
+    }};
+}
+
 /// Create a `NotSupported` error
 macro_rules! not_supported {
     ($msg:expr) => {{
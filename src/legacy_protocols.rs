@@ -0,0 +1,151 @@
+//! Rejection of legacy protocols which are not supported
+//!
+//! Old clients and scanners still send SOAP envelopes and requests signed by Signature Version 2,
+//! which is only rejected if it is disabled by [`S3Service::set_signature_v2`](crate::S3Service::set_signature_v2).
+//! Without detection, they are routed like REST requests and get misleading errors,
+//! such as `NoSuchBucket` for a SOAP `POST /soap`.
+//! They are rejected with `InvalidRequest` (400) before their signatures are checked instead,
+//! which names the protocol, and they are grouped as `legacy` in logs and metrics.
+
+use crate::errors::S3Error;
+use crate::headers::{AUTHORIZATION, CONTENT_TYPE};
+use crate::ops::ReqContext;
+use crate::signature_v2;
+use crate::{Body, Method};
+
+use std::mem;
+
+use futures::stream::{self, StreamExt};
+use hyper::body::HttpBody;
+
+/// the header of SOAP 1.1 requests
+const SOAP_ACTION: &str = "soapaction";
+
+/// max bytes read to find the root element of a body
+const MAX_PEEK: usize = 1024;
+
+/// A legacy protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LegacyProtocol {
+    /// the SOAP API
+    Soap,
+    /// Signature Version 2, by the `Authorization` header or query strings
+    SignatureV2,
+}
+
+impl LegacyProtocol {
+    /// the error of a request of the protocol
+    pub(crate) fn error(self) -> S3Error {
+        match self {
+            Self::Soap => invalid_request!(
+                "SOAP requests are not supported. Please use the REST API."
+            ),
+            Self::SignatureV2 => invalid_request!(
+                "The authorization mechanism you have provided is not supported. Please use AWS4-HMAC-SHA256."
+            ),
+        }
+    }
+}
+
+/// Detects the legacy protocol of a request
+///
+/// Signature Version 2 is detected only if `v2_supported` is false.
+/// The body of a `POST` with an XML body and no query strings is peeked for a SOAP envelope,
+/// which is never a REST request, and put back.
+pub(crate) async fn detect(ctx: &mut ReqContext<'_>, v2_supported: bool) -> Option<LegacyProtocol> {
+    let is_v2_header = ctx
+        .headers
+        .get(AUTHORIZATION)
+        .map_or(false, signature_v2::is_authorization_v2);
+    let is_v2_query = ctx
+        .presigned_qs
+        .as_ref()
+        .map_or(false, signature_v2::is_presigned);
+    if !v2_supported && (is_v2_header || is_v2_query) {
+        return Some(LegacyProtocol::SignatureV2);
+    }
+
+    // REST `POST`s select a sub-resource or upload a form
+    if ctx.method != Method::POST || ctx.query_strings.is_some() {
+        return None;
+    }
+    let content_type = ctx.headers.get(CONTENT_TYPE).unwrap_or("");
+    if content_type.starts_with("multipart/form-data") {
+        return None;
+    }
+    if ctx.req.uri().path() == "/soap"
+        || ctx.headers.get(SOAP_ACTION).is_some()
+        || content_type.starts_with("application/soap+xml")
+    {
+        return Some(LegacyProtocol::Soap);
+    }
+    if content_type.starts_with("text/xml") && is_envelope(&peek(&mut ctx.body).await) {
+        return Some(LegacyProtocol::Soap);
+    }
+    None
+}
+
+/// reads at most about [`MAX_PEEK`] bytes of `body` and puts them back
+async fn peek(body: &mut Body) -> Vec<u8> {
+    let mut rest = mem::take(body);
+    let mut chunks = Vec::new();
+    let mut prefix = Vec::new();
+    while prefix.len() < MAX_PEEK {
+        match rest.data().await {
+            Some(Ok(chunk)) => {
+                prefix.extend_from_slice(&chunk);
+                chunks.push(Ok(chunk));
+            }
+            Some(Err(err)) => {
+                chunks.push(Err(err));
+                break;
+            }
+            None => break,
+        }
+    }
+    *body = Body::wrap_stream(stream::iter(chunks).chain(rest));
+    prefix
+}
+
+/// whether the root element of an XML document is a SOAP `Envelope`
+fn is_envelope(prefix: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(prefix);
+    let mut rest = text.trim_start();
+    // skips the declaration, comments and whitespace
+    while rest.starts_with("<?") || rest.starts_with("<!") {
+        rest = match rest.find('>') {
+            Some(end) => rest.get(end.saturating_add(1)..).unwrap_or("").trim_start(),
+            None => return false,
+        };
+    }
+    let name = match rest.strip_prefix('<') {
+        Some(element) => element
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or(""),
+        None => return false,
+    };
+    let local_name = name.rsplit(':').next().unwrap_or("");
+    local_name == "Envelope"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope() {
+        let soap = concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n<!-- captured -->\n",
+            r#"<soapenv:Envelope xmlns:soapenv="http://schemas.xmlsoap.org/soap/envelope/">"#,
+        );
+        assert!(is_envelope(soap.as_bytes()));
+        assert!(is_envelope(b"<Envelope>"));
+        assert!(!is_envelope(
+            b"<Delete><Object><Key>a</Key></Object></Delete>"
+        ));
+        assert!(!is_envelope(b"<?xml version=\"1.0\""));
+        assert!(!is_envelope(b"Envelope"));
+    }
+}
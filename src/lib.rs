@@ -14,7 +14,14 @@
 //!
 //! An [`S3Service`] instance can be integrated into a [`hyper`] application.
 //!
-//! See `src/bin/s3-server.rs` for how to setup an [`S3Service`].
+//! See `src/bin/s3-server/main.rs` for how to setup an [`S3Service`].
+//! The runnable scenarios in `examples/` show a minimal server, a custom storage, storage wrappers and presigned URLs.
+//!
+//! ### Trait: `S3Output`
+//!
+//! [`S3Output`] represents types which can be converted into a response.
+//!
+//! [`S3Output`] is implemented for DTOs and results.
 //!
 //! ### Trait: `S3Storage`
 //!
@@ -32,6 +39,45 @@
 //!
 //! [`S3Service`] looks up secret access keys from an auth provider and checks http signature (if any) by the AK and SK.
 //!
+//! ### Trait: `AuditSink`
+//!
+//! An [`AuditSink`] receives an [`AuditEntry`] for each successful mutation, see [`S3Service::set_audit_sink`].
+//! [`JsonLinesAuditSink`] appends entries to a file.
+//!
+//! ### Type: `EffectiveConfig`
+//!
+//! [`S3Service::effective_config`] returns a serializable snapshot of the service and its storage.
+//! Secrets are wrapped in [`Secret`], which serializes as `"REDACTED"`.
+//! The snapshot can be served at `/_admin/config`, see [`S3Service::set_admin_endpoint`].
+//! [`S3Service::runtime_snapshot`] is served with it at `/_admin/runtime`.
+//!
+//! ### Type: `Capabilities`
+//!
+//! [`S3Service::capabilities`] returns the supported operations, auth modes, checksum algorithms and limits,
+//! for clients which hide unsupported features.
+//! They can be served at `/_admin/capabilities`, see [`S3Service::set_capabilities_endpoint`].
+//!
+//! ### Module: `ops`
+//!
+//! [`ops`] exposes the wire format of some operations without the router:
+//! `extract` functions convert a [`ops::ReqContext`] into a DTO,
+//! and [`S3Output`] converts a DTO or an [`errors::XmlErrorResponse`] into a response.
+//!
+//! The header names are exported by [`headers`].
+//!
+//! ### Module: `migrate`
+//!
+//! [`migrate::migrate`] copies the buckets and objects of one storage to another through [`S3Storage`],
+//! with prefix filters and a resume journal.
+//!
+//! ### Module: `replay`
+//!
+//! [`replay`] records raw exchanges of a service (see [`S3Service::set_recorder`]) and replays them later.
+//!
+//! ### Module: `selftest`
+//!
+//! [`selftest`] runs every implemented operation against a storage and reports a support matrix.
+//!
 //! ## Internal API
 //!
 //! ### Type: `S3Error`, `S3StorageError<E>`, `S3AuthError`
@@ -50,23 +96,32 @@
 //! then the handler will be called with two arguments:
 //! `&mut ReqContext<'_>` and `&(dyn S3Storage + Send + Sync)`.
 //!
-//! ### Trait: `S3Output`
-//!
-//! `S3Output` represents types which can be converted into a response.
-//!
-//! `S3Output` is implemented for DTOs and results.
-//!
 //! ### S3 types
 //!
 //! `S3Path` represents a path in the S3 storage.
+//! Its key is percent-decoded, and keys which are not valid UTF-8 are handled by `KeyEncoding`.
 //!
 //! All types in `src/dto.rs` are data transfer objects which represent the input or output of S3 APIs.
 //!
 //! All types in `src/headers` are http headers which may occur in an S3 http request.
 //!
 //! All types in `src/streams` are http body streams which may occur in an S3 http request.
+//!
+//! ## Features
+//!
+//! + `binary`: builds the `s3-server` binary.
+//! + `compress-gzip`, `compress-zstd`: enable the codings of response compression (see [`S3Service::set_compression`]).
+//! + `mmap`: allows the fs backend to serve `GetObject` from memory maps (see [`storages::fs::FileSystem::set_mmap_limit`]).
+//!   This is the only feature which enables `unsafe` code.
+//! + `server`: a server with protections against slow clients (see [`server::serve`]).
+//! + `test-utils`: exposes an in-memory storage for tests and examples (see [`storages::memory::MemoryStorage`]),
+//!   a storage wrapper which injects faults (see [`storages::fault_injector::FaultInjector`]),
+//!   and request builders for testing handlers and storages (see [`test_utils`]).
+//! + `tokio-unstable`: names the background tasks of the binary for `tokio-console`.
+//!   Takes effect only with `RUSTFLAGS="--cfg tokio_unstable"`.
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "mmap", deny(unsafe_code))] // only `MmapStream::open` is allowed to map files
 #![deny(
     // The following are allowed by default lints according to
     // https://doc.rust-lang.org/rustc/lints/listing/allowed-by-default.html
@@ -115,25 +170,83 @@ mod internal_macros;
 pub(crate) mod utils;
 
 mod data_structures;
-mod ops;
+mod metrics;
 mod output;
+mod signature_v2;
 mod signature_v4;
 mod streams;
 
+mod audit;
 mod auth;
+mod body_drain;
+mod body_log;
+mod bucket_cache;
+mod bucket_freeze;
+mod cancellation;
+mod capabilities;
+mod chunked_upload;
+mod clock_skew;
+mod compression;
+mod conditions;
+mod consistency;
+mod cors;
+mod effective_config;
+mod extra_headers;
+mod header_limits;
+mod legacy_protocols;
+mod operation_policy;
+mod payload_hash;
+mod post_policy;
+mod redirect_offload;
+mod request_id;
+mod requester;
 mod service;
+mod serving_policy;
+mod size_limits;
 mod storage;
+mod tls_policy;
 
-pub use self::auth::{S3Auth, SimpleAuth};
+pub use self::audit::{AuditEntry, AuditSink, ClientAddr, JsonLinesAuditSink};
+pub use self::auth::{CanonicalUser, ReloadableAuth, S3Auth, SimpleAuth};
+pub use self::body_drain::{drain_body, DrainLimits, DrainOutcome};
+pub use self::bucket_cache::{is_bucket_verified, BucketCacheConfig};
+pub use self::bucket_freeze::BucketFreeze;
+pub use self::cancellation::CancellationToken;
+pub use self::capabilities::{
+    AuthCapabilities, Capabilities, OperationStatus, OperationSupport, RequestLimits,
+};
+pub use self::compression::CompressionConfig;
+pub use self::consistency::ConsistencyToken;
+pub use self::effective_config::{EffectiveConfig, Secret, StorageConfig};
+pub use self::extra_headers::ExtraHeaders;
+pub use self::header_limits::HeaderLimits;
+pub use self::metrics::RuntimeSnapshot;
+pub use self::operation_policy::{OperationPolicy, OperationRejection};
+pub use self::output::S3Output;
+pub use self::redirect_offload::RedirectOffload;
+pub use self::request_id::{CorrelationConfig, RequestIds};
+pub use self::requester::Requester;
 pub use self::service::{S3Service, SharedS3Service};
-pub use self::storage::S3Storage;
+pub use self::serving_policy::ServingPolicy;
+pub use self::size_limits::SizeLimits;
+pub use self::storage::{migrate, S3Storage};
+pub use self::tls_policy::{ConnectionScheme, TlsPolicy};
 
 pub mod dto;
 pub mod errors;
 pub mod headers;
+pub mod ops;
 pub mod path;
+pub mod replay;
+pub mod selftest;
 pub mod storages;
 
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+
 /// Request type
 pub(crate) type Request = hyper::Request<Body>;
 
@@ -143,6 +256,6 @@ pub(crate) type Response = hyper::Response<Body>;
 /// `Box<dyn std::error::Error + Send + Sync + 'static>`
 pub(crate) type BoxStdError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
-pub(crate) use async_trait::async_trait;
+pub use async_trait::async_trait;
 pub(crate) use hyper::{Body, Method, StatusCode};
 pub(crate) use mime::Mime;
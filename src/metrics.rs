@@ -0,0 +1,127 @@
+//! Runtime metrics
+
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::utils::ResponseExt;
+use crate::{Body, Response};
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+/// Counters of in-flight requests by operation
+///
+/// A request is counted from the dispatch to its handler until the handler returns or is dropped.
+#[derive(Debug)]
+pub(crate) struct ActiveRequests {
+    /// operation names and counters, indexed like the handlers
+    counters: Vec<(&'static str, AtomicUsize)>,
+}
+
+/// Decrements a counter of [`ActiveRequests`] when dropped
+#[derive(Debug)]
+pub(crate) struct ActiveGuard<'a> {
+    /// counter
+    counter: &'a AtomicUsize,
+}
+
+impl ActiveRequests {
+    /// Constructs counters for the operations
+    pub(crate) fn new(names: impl IntoIterator<Item = &'static str>) -> Self {
+        let counters = names
+            .into_iter()
+            .map(|name| (name, AtomicUsize::new(0)))
+            .collect();
+        Self { counters }
+    }
+
+    /// Counts a request of the `idx`-th operation until the guard is dropped
+    pub(crate) fn enter(&self, idx: usize) -> Option<ActiveGuard<'_>> {
+        let &(_, ref counter) = self.counters.get(idx)?;
+        let _prev = counter.fetch_add(1, Ordering::Relaxed);
+        Some(ActiveGuard { counter })
+    }
+
+    /// Returns the current counts by operation name
+    pub(crate) fn snapshot(&self) -> BTreeMap<String, usize> {
+        self.counters
+            .iter()
+            .map(|&(name, ref counter)| (name.to_owned(), counter.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        let _prev = self.counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the service at runtime
+///
+/// It serializes to JSON like `{"active_requests":{"GetObject":2,...},"invalid_requests":0,"legacy_requests":0,"partial_upload_bytes":0}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuntimeSnapshot {
+    /// in-flight requests by operation name
+    pub active_requests: BTreeMap<String, usize>,
+    /// requests which were not routed to an operation since the service is constructed,
+    /// such as scanner probes with invalid paths
+    pub invalid_requests: u64,
+    /// requests of unsupported legacy protocols since the service is constructed,
+    /// such as SOAP and Signature Version 2
+    pub legacy_requests: u64,
+    /// bytes persisted by uploads which failed midway since the service is constructed,
+    /// see [`PartialUpload`](crate::errors::PartialUpload)
+    pub partial_upload_bytes: u64,
+}
+
+impl RuntimeSnapshot {
+    /// Serializes the snapshot to JSON
+    /// # Errors
+    /// Returns an `Err` if serialization fails
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+impl S3Output for RuntimeSnapshot {
+    fn try_into_response(self) -> S3Result<Response> {
+        let body = self.to_json().map_err(|e| internal_error!(e))?;
+        let mut res = Response::new(Body::from(body));
+        res.set_mime(&mime::APPLICATION_JSON)
+            .map_err(|e| internal_error!(e))?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_requests() {
+        let active = ActiveRequests::new(vec!["GetObject", "PutObject"]);
+
+        let first = active.enter(0).unwrap();
+        let second = active.enter(0).unwrap();
+        assert!(active.enter(2).is_none());
+        assert_eq!(active.snapshot()["GetObject"], 2);
+        assert_eq!(active.snapshot()["PutObject"], 0);
+
+        drop(first);
+        drop(second);
+        assert_eq!(active.snapshot()["GetObject"], 0);
+
+        let snapshot = RuntimeSnapshot {
+            active_requests: active.snapshot(),
+            invalid_requests: 1,
+            legacy_requests: 2,
+            partial_upload_bytes: 10,
+        };
+        assert_eq!(
+            snapshot.to_json().unwrap(),
+            r#"{"active_requests":{"GetObject":0,"PutObject":0},"invalid_requests":1,"legacy_requests":2,"partial_upload_bytes":10}"#
+        );
+    }
+}
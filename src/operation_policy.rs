@@ -0,0 +1,127 @@
+//! Operations disabled on a service regardless of the requester
+
+use crate::bucket_freeze;
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+/// The error returned for a disabled operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationRejection {
+    /// `MethodNotAllowed` (405)
+    MethodNotAllowed,
+    /// `AccessDenied` (403)
+    AccessDenied,
+}
+
+impl Default for OperationRejection {
+    fn default() -> Self {
+        Self::MethodNotAllowed
+    }
+}
+
+/// Operations disabled on a service, see [`S3Service::set_operation_policy`](crate::S3Service::set_operation_policy)
+///
+/// Operations are named like the `operation` field of the logs, such as `DeleteBucket`.
+/// A disabled operation is rejected right after it is routed, before its signature is checked
+/// and before its request is extracted, so it never reaches the storage.
+/// Every operation is allowed by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OperationPolicy {
+    /// whether every operation which writes is disabled
+    read_only: bool,
+    /// the disabled operations
+    denied: BTreeSet<String>,
+    /// the error of a disabled operation
+    rejection: OperationRejection,
+}
+
+impl OperationPolicy {
+    /// Disables every operation which writes, such as `PutObject`, `DeleteBucket` and `PutBucketVersioning`
+    #[must_use]
+    pub fn read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Self::default()
+        }
+    }
+
+    /// Disables `operations`
+    #[must_use]
+    pub fn deny(operations: &[&str]) -> Self {
+        Self::default().and_deny(operations)
+    }
+
+    /// Disables `operations` in addition to the disabled ones
+    #[must_use]
+    pub fn and_deny(mut self, operations: &[&str]) -> Self {
+        self.denied
+            .extend(operations.iter().map(|&operation| operation.to_owned()));
+        self
+    }
+
+    /// Sets the error of a disabled operation, which is `MethodNotAllowed` by default
+    #[must_use]
+    pub const fn rejection(mut self, rejection: OperationRejection) -> Self {
+        self.rejection = rejection;
+        self
+    }
+
+    /// Checks whether `operation` is allowed
+    #[must_use]
+    pub fn allows(&self, operation: &str) -> bool {
+        let is_denied = self.denied.contains(operation)
+            || (self.read_only && bucket_freeze::is_mutating(operation));
+        !is_denied
+    }
+
+    /// rejects `operation` if it is disabled
+    pub(crate) fn check(&self, operation: &str) -> S3Result<()> {
+        if self.allows(operation) {
+            return Ok(());
+        }
+        let code = match self.rejection {
+            OperationRejection::MethodNotAllowed => S3ErrorCode::MethodNotAllowed,
+            OperationRejection::AccessDenied => S3ErrorCode::AccessDenied,
+        };
+        let err = S3Error::from_code(code)
+            .message(format!(
+                "The operation {} is disabled on this server.",
+                operation
+            ))
+            .finish();
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows() {
+        let policy = OperationPolicy::default();
+        assert!(policy.allows("DeleteBucket"));
+
+        let policy = OperationPolicy::read_only().and_deny(&["ListBuckets"]);
+        assert!(!policy.allows("PutObject"));
+        assert!(!policy.allows("UploadPart"));
+        assert!(!policy.allows("ListBuckets"));
+        assert!(policy.allows("GetObject"));
+        assert_eq!(
+            policy.check("DeleteObject").unwrap_err().code(),
+            S3ErrorCode::MethodNotAllowed
+        );
+
+        let policy =
+            OperationPolicy::deny(&["DeleteBucket"]).rejection(OperationRejection::AccessDenied);
+        assert!(policy.allows("PutObject"));
+        assert_eq!(
+            policy.check("DeleteBucket").unwrap_err().code(),
+            S3ErrorCode::AccessDenied
+        );
+    }
+}
@@ -1,65 +1,177 @@
 //! S3 operations
+//!
+//! The wire format of some operations is public,
+//! so that it can be reused without [`S3Service`](crate::S3Service):
+//!
+//! + [`get_object::extract`]
+//! + [`put_object::extract`]
+//!
+//! Build a [`ReqContext`] from a request, `extract` the DTO,
+//! then convert the output by [`S3Output`](crate::S3Output).
 
 #![allow(clippy::unnecessary_wraps, clippy::panic_in_result_fn)]
 
+mod abort_multipart_upload;
+mod access_control_policy;
+mod acl_headers;
 mod complete_multipart_upload;
+mod content_md5;
 mod copy_object;
+mod cors_configuration;
 mod create_bucket;
 mod create_multipart_upload;
 mod delete_bucket;
+mod delete_bucket_cors;
+mod delete_bucket_policy;
+mod delete_bucket_tagging;
 mod delete_object;
 mod delete_objects;
+mod get_bucket_acl;
+mod get_bucket_cors;
 mod get_bucket_location;
-mod get_object;
+mod get_bucket_policy;
+mod get_bucket_tagging;
+mod get_bucket_versioning;
+pub mod get_object;
+mod get_object_acl;
+mod get_object_legal_hold;
+mod get_object_lock_configuration;
+mod get_object_retention;
 mod head_bucket;
 mod head_object;
 mod list_buckets;
+mod list_multipart_uploads;
+mod list_object_versions;
 mod list_objects;
 mod list_objects_v2;
-mod put_object;
+mod list_parts;
+mod list_query;
+mod object_lock;
+mod object_write_headers;
+mod owner;
+mod put_bucket_acl;
+mod put_bucket_cors;
+mod put_bucket_policy;
+mod put_bucket_tagging;
+mod put_bucket_versioning;
+pub mod put_object;
+mod put_object_acl;
+mod put_object_legal_hold;
+mod put_object_lock_configuration;
+mod put_object_retention;
+mod restore_object;
+mod tagging;
 mod upload_part;
+mod upload_part_copy;
 
+use crate::auth::{CanonicalUser, S3Auth};
+use crate::cancellation::CancellationToken;
 use crate::data_structures::{OrderedHeaders, OrderedQs};
-use crate::errors::S3Result;
-use crate::path::S3Path;
+use crate::dto::GetBucketVersioningRequest;
+use crate::errors::{S3AuthError, S3Result, S3StorageError};
+use crate::headers::{AmzMfa, CONTENT_LENGTH, X_AMZ_DECODED_CONTENT_LENGTH, X_AMZ_MFA};
+use crate::path::{KeyEncoding, S3Path};
+use crate::post_policy::ContentLengthRange;
+use crate::service;
+use crate::size_limits::SizeLimits;
 use crate::storage::S3Storage;
 use crate::streams::multipart::Multipart;
-use crate::{async_trait, Body, BoxStdError, Mime, Request, Response};
+use crate::tls_policy::ConnectionScheme;
+use crate::{async_trait, Body, BoxStdError, Method, Mime, Request, Response};
 
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::mem;
+use std::time::Duration;
 
 use hyper::header::AsHeaderName;
 
+pub(crate) use self::delete_objects::MAX_OBJECTS as MAX_DELETE_OBJECTS;
+pub(crate) use self::list_parts::MAX_PARTS as MAX_LIST_PARTS;
+pub(crate) use self::upload_part::MAX_PART_NUMBER;
+
+/// S3 operation handler with its operation name
+pub(crate) type NamedHandler = (&'static str, Box<dyn S3Handler + Send + Sync + 'static>);
+
 /// setup handlers
-pub fn setup_handlers() -> Vec<Box<dyn S3Handler + Send + Sync + 'static>> {
+pub(crate) fn setup_handlers() -> Vec<NamedHandler> {
     macro_rules! zst_handlers{
-        [$($m:ident,)+] => {vec![$(Box::new($m::Handler),)+]}
+        [$($m:ident => $name:literal,)+] => {{
+            let handlers: Vec<NamedHandler> = vec![$(($name, Box::new($m::Handler)),)+];
+            handlers
+        }}
     }
 
     zst_handlers![
-        complete_multipart_upload,
-        copy_object,
-        create_bucket,
-        create_multipart_upload,
-        delete_bucket,
-        delete_object,
-        delete_objects,
-        get_bucket_location,
-        get_object,
-        head_bucket,
-        head_object,
-        list_buckets,
-        list_objects,
-        list_objects_v2,
-        put_object,
-        upload_part,
+        abort_multipart_upload => "AbortMultipartUpload",
+        complete_multipart_upload => "CompleteMultipartUpload",
+        copy_object => "CopyObject",
+        create_bucket => "CreateBucket",
+        create_multipart_upload => "CreateMultipartUpload",
+        delete_bucket => "DeleteBucket",
+        delete_bucket_cors => "DeleteBucketCors",
+        delete_bucket_policy => "DeleteBucketPolicy",
+        delete_bucket_tagging => "DeleteBucketTagging",
+        delete_object => "DeleteObject",
+        delete_objects => "DeleteObjects",
+        get_bucket_acl => "GetBucketAcl",
+        get_bucket_cors => "GetBucketCors",
+        get_bucket_location => "GetBucketLocation",
+        get_bucket_policy => "GetBucketPolicy",
+        get_bucket_tagging => "GetBucketTagging",
+        get_bucket_versioning => "GetBucketVersioning",
+        get_object => "GetObject",
+        get_object_acl => "GetObjectAcl",
+        get_object_legal_hold => "GetObjectLegalHold",
+        get_object_lock_configuration => "GetObjectLockConfiguration",
+        get_object_retention => "GetObjectRetention",
+        head_bucket => "HeadBucket",
+        head_object => "HeadObject",
+        list_buckets => "ListBuckets",
+        list_multipart_uploads => "ListMultipartUploads",
+        list_object_versions => "ListObjectVersions",
+        list_objects => "ListObjects",
+        list_objects_v2 => "ListObjectsV2",
+        list_parts => "ListParts",
+        put_bucket_acl => "PutBucketAcl",
+        put_bucket_cors => "PutBucketCors",
+        put_bucket_policy => "PutBucketPolicy",
+        put_bucket_tagging => "PutBucketTagging",
+        put_bucket_versioning => "PutBucketVersioning",
+        put_object => "PutObject",
+        put_object_acl => "PutObjectAcl",
+        put_object_legal_hold => "PutObjectLegalHold",
+        put_object_lock_configuration => "PutObjectLockConfiguration",
+        put_object_retention => "PutObjectRetention",
+        restore_object => "RestoreObject",
+        upload_part => "UploadPart",
+        upload_part_copy => "UploadPartCopy",
     ]
 }
 
+/// Returns whether `operation` acts on a bucket or an object itself, which never serves a sub-resource
+///
+/// A query which selects a sub-resource without a handler would otherwise fall through to these operations,
+/// like `PUT /bucket?lifecycle` to `CreateBucket`.
+pub(crate) fn is_resource_operation(operation: &str) -> bool {
+    matches!(
+        operation,
+        "CopyObject"
+            | "CreateBucket"
+            | "DeleteBucket"
+            | "DeleteObject"
+            | "GetObject"
+            | "HeadBucket"
+            | "HeadObject"
+            | "ListBuckets"
+            | "ListObjects"
+            | "ListObjectsV2"
+            | "PutObject"
+    )
+}
+
 /// S3 operation handler
 #[async_trait]
-pub trait S3Handler {
+pub(crate) trait S3Handler {
     /// determine if the handler matches current request
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool;
 
@@ -75,31 +187,155 @@ pub trait S3Handler {
 #[derive(Debug)]
 pub struct ReqContext<'a> {
     /// req
-    pub req: &'a Request,
+    pub(crate) req: &'a Request,
+    /// method used for routing, which is `GET` when a `HEAD` is served by a `GET` handler
+    pub(crate) method: Method,
     /// ordered headers
-    pub headers: OrderedHeaders<'a>,
-    /// query strings
-    pub query_strings: Option<OrderedQs>,
+    pub(crate) headers: OrderedHeaders<'a>,
+    /// query strings, without the fields of a presigned url
+    pub(crate) query_strings: Option<OrderedQs>,
+    /// all the query strings of a presigned url, until its signature is checked
+    pub(crate) presigned_qs: Option<OrderedQs>,
     /// body
-    pub body: Body,
+    pub(crate) body: Body,
+    /// whether the body has been decoded from the `aws-chunked` encoding
+    pub(crate) aws_chunked: bool,
     /// s3 path
-    pub path: S3Path<'a>,
+    pub(crate) path: S3Path<'a>,
+    /// whether the bucket is taken from the `Host` header instead of the path
+    pub(crate) virtual_hosted: bool,
     /// mime
-    pub mime: Option<Mime>,
+    pub(crate) mime: Option<Mime>,
     /// multipart/form-data
-    pub multipart: Option<Multipart>,
+    pub(crate) multipart: Option<Multipart>,
+    /// the file sizes which the policy of a form upload allows
+    pub(crate) content_length_range: Option<ContentLengthRange>,
+    /// cancelled when the request is dropped
+    pub(crate) cancellation: CancellationToken,
+    /// how keys are stored
+    pub(crate) key_encoding: KeyEncoding,
+    /// access key of a verified signature
+    pub(crate) access_key: Option<String>,
+    /// canonical user of the access key
+    pub(crate) canonical_user: Option<CanonicalUser>,
+    /// auth provider, which verifies MFA
+    pub(crate) auth: Option<AuthRef<'a>>,
+    /// whether the bucket cache has verified that the bucket exists
+    pub(crate) bucket_verified: bool,
+    /// the scheme of the connection
+    pub(crate) scheme: ConnectionScheme,
+    /// how long a write waits until it is readable, if the barrier is enabled
+    pub(crate) read_after_write_barrier: Option<Duration>,
+    /// size limits of uploads
+    pub(crate) size_limits: SizeLimits,
+    /// region of the buckets whose storage returns no location
+    pub(crate) default_region: Option<&'a str>,
+}
+
+/// A borrowed auth provider
+#[derive(Clone, Copy)]
+pub(crate) struct AuthRef<'a>(pub(crate) &'a (dyn S3Auth + Send + Sync));
+
+impl Debug for AuthRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AuthRef{{...}}")
+    }
 }
 
 impl<'a> ReqContext<'a> {
+    /// Parses the path, headers and query strings of `req`.
+    ///
+    /// `body` is the body taken out of `req`.
+    /// The signature is not checked and `multipart/form-data` is not parsed.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the request is malformed
+    pub fn new(req: &'a Request, body: Body) -> S3Result<Self> {
+        Self::with_key_encoding(req, body, KeyEncoding::default())
+    }
+
+    /// Like [`ReqContext::new`], but decodes keys by `key_encoding`
+    ///
+    /// # Errors
+    /// Returns an `Err` if the request is malformed
+    pub fn with_key_encoding(
+        req: &'a Request,
+        body: Body,
+        key_encoding: KeyEncoding,
+    ) -> S3Result<Self> {
+        Self::with_base_domain(req, body, key_encoding, None)
+    }
+
+    /// Like [`ReqContext::with_key_encoding`], but takes the bucket of a virtual-hosted-style request to a subdomain of `base_domain`
+    pub(crate) fn with_base_domain(
+        req: &'a Request,
+        body: Body,
+        key_encoding: KeyEncoding,
+        base_domain: Option<&str>,
+    ) -> S3Result<Self> {
+        let (path, virtual_hosted) = service::extract_s3_path(req, key_encoding, base_domain)?;
+        let headers = service::extract_headers(req)?;
+        let mut query_strings = service::extract_qs(req)?;
+        let presigned_qs = service::take_presigned_qs(&mut query_strings);
+        let mime = service::extract_mime(&headers)?;
+        Ok(Self {
+            req,
+            method: req.method().clone(),
+            headers,
+            query_strings,
+            presigned_qs,
+            body,
+            aws_chunked: false,
+            path,
+            virtual_hosted,
+            mime,
+            multipart: None,
+            content_length_range: None,
+            cancellation: CancellationToken::new(),
+            key_encoding,
+            access_key: None,
+            canonical_user: None,
+            auth: None,
+            bucket_verified: false,
+            scheme: ConnectionScheme::default(),
+            read_after_write_barrier: None,
+            size_limits: SizeLimits::default(),
+            default_region: None,
+        })
+    }
+
+    /// whether the request is a `POST` with a `multipart/form-data` body,
+    /// which is parsed when its signature is checked
+    pub(crate) fn is_form_upload(&self) -> bool {
+        self.req.method() == Method::POST
+            && self.mime.as_ref().map_or(false, |mime| {
+                mime.type_() == mime::MULTIPART && mime.subtype() == mime::FORM_DATA
+            })
+    }
+
     /// take request body
     fn take_body(&mut self) -> Body {
         mem::take(&mut self.body)
     }
 
+    /// the length of the body, which is `x-amz-decoded-content-length` if it is decoded from `aws-chunked`
+    fn content_length(&self) -> S3Result<Option<i64>> {
+        let name = if self.aws_chunked {
+            &*X_AMZ_DECODED_CONTENT_LENGTH
+        } else {
+            &CONTENT_LENGTH
+        };
+        let mut content_length = None;
+        self.headers
+            .assign(name, &mut content_length)
+            .map_err(|err| invalid_request!(format!("Invalid header: {}", name), err))?;
+        Ok(content_length)
+    }
+
     /// get (bucket, key)
-    fn unwrap_object_path(&self) -> (&'a str, &'a str) {
+    fn unwrap_object_path(&self) -> (&'a str, &str) {
         match self.path {
-            S3Path::Object { bucket, key } => (bucket, key),
+            S3Path::Object { bucket, ref key } => (bucket, key),
             S3Path::Root | S3Path::Bucket { .. } => {
                 panic!("expected S3Path::Object, found: {:?}", self.path)
             }
@@ -116,6 +352,26 @@ impl<'a> ReqContext<'a> {
         }
     }
 
+    /// get (bucket, key), or an error if the path is not an object
+    fn object_path(&self) -> S3Result<(&'a str, &str)> {
+        match self.path {
+            S3Path::Object { bucket, ref key } => Ok((bucket, key)),
+            S3Path::Root | S3Path::Bucket { .. } => {
+                Err(invalid_request!("The request path is not an object."))
+            }
+        }
+    }
+
+    /// get bucket, or an error if the path is not a bucket
+    fn bucket_path(&self) -> S3Result<&'a str> {
+        match self.path {
+            S3Path::Bucket { bucket } => Ok(bucket),
+            S3Path::Root | S3Path::Object { .. } => {
+                Err(invalid_request!("The request path is not a bucket."))
+            }
+        }
+    }
+
     /// get query string
     fn unwrap_qs(&self, name: &str) -> &str {
         match self.query_strings.as_ref().and_then(|qs| qs.get(name)) {
@@ -135,6 +391,63 @@ impl<'a> ReqContext<'a> {
     }
 }
 
+/// converts stored keys in a listing for display
+fn display_listed_keys<'k>(
+    key_encoding: KeyEncoding,
+    encoding_type: Option<&str>,
+    keys: impl Iterator<Item = &'k mut Option<String>> + 'k,
+) {
+    let url_encoded = encoding_type == Some("url");
+    if key_encoding == KeyEncoding::Strict && !url_encoded {
+        return;
+    }
+    for key in keys.flatten() {
+        *key = key_encoding.display(key, url_encoded);
+    }
+}
+
+/// checks whether MFA delete is enabled on a bucket
+async fn is_mfa_delete_enabled(
+    storage: &(dyn S3Storage + Send + Sync),
+    bucket: &str,
+) -> S3Result<bool> {
+    let input = GetBucketVersioningRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+    match storage.get_bucket_versioning(input).await {
+        Ok(output) => Ok(output.mfa_delete.as_deref() == Some("Enabled")),
+        Err(S3StorageError::Operation(e)) => Err(e.into()),
+        Err(S3StorageError::Other(e)) => Err(e),
+    }
+}
+
+/// verifies the `x-amz-mfa` header by the auth provider
+async fn verify_mfa(ctx: &ReqContext<'_>) -> S3Result<()> {
+    let header = ctx.headers.get(&*X_AMZ_MFA).ok_or_else(|| {
+        code_error!(
+            AccessDenied,
+            "Mfa Authentication must be used for this request"
+        )
+    })?;
+    let mfa = AmzMfa::from_header_str(header)
+        .map_err(|err| invalid_request!("Invalid header: x-amz-mfa", err))?;
+    let auth = ctx.auth.ok_or_else(|| {
+        code_error!(
+            AccessDenied,
+            "The service has no authentication provider to verify MFA."
+        )
+    })?;
+    match auth.0.verify_mfa(mfa.serial, mfa.code).await {
+        Ok(true) => Ok(()),
+        Ok(false) | Err(S3AuthError::NotSignedUp) => Err(code_error!(
+            AccessDenied,
+            "The MFA authentication code is not valid."
+        )),
+        Err(S3AuthError::Other(e)) => Err(e),
+    }
+}
+
 /// wrap any error as an internal error
 fn wrap_internal_error(
     f: impl FnOnce(&mut Response) -> Result<(), BoxStdError>,
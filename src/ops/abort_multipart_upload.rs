@@ -0,0 +1,78 @@
+//! [`AbortMultipartUpload`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_AbortMultipartUpload.html)
+
+use super::{wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
+};
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+use crate::headers::{X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response, StatusCode};
+
+/// `AbortMultipartUpload` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::DELETE);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("uploadId").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.abort_multipart_upload(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<AbortMultipartUploadRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let upload_id = ctx.unwrap_qs("uploadId").to_owned();
+
+    let mut input = AbortMultipartUploadRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        upload_id,
+        ..AbortMultipartUploadRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+
+    Ok(input)
+}
+
+impl S3Output for AbortMultipartUploadOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_status(StatusCode::NO_CONTENT);
+            res.set_optional_header(&*X_AMZ_REQUEST_CHARGED, self.request_charged)?;
+            Ok(())
+        })
+    }
+}
+
+impl From<AbortMultipartUploadError> for S3Error {
+    fn from(e: AbortMultipartUploadError) -> Self {
+        match e {
+            AbortMultipartUploadError::NoSuchUpload(msg) => {
+                Self::new(S3ErrorCode::NoSuchUpload, msg)
+            }
+        }
+    }
+}
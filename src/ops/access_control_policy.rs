@@ -0,0 +1,367 @@
+//! `AccessControlPolicy` documents, shared by the operations which get or put an ACL
+//!
+//! + [`GetObjectAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectAcl.html)
+//! + [`PutObjectAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectAcl.html)
+//! + [`GetBucketAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketAcl.html)
+//! + [`PutBucketAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAcl.html)
+//!
+//! A grantee is a `CanonicalUser` with an `ID`, a `Group` with a `URI`
+//! or an `AmazonCustomerByEmail` with an `EmailAddress`, given by its `xsi:type`.
+//! A policy with another grantee or an unknown permission is rejected with `MalformedACLError`.
+//! A written policy always has an `Owner` element, even an empty one, because boto3 fails to parse a policy without it.
+
+use crate::dto::{AccessControlPolicy, Grant, Owner};
+use crate::errors::S3Result;
+use crate::utils::body::deserialize_xml_body;
+use crate::utils::XmlWriterExt;
+use crate::Body;
+
+use std::io;
+
+use ::xml::writer::{events::XmlEvent, EventWriter};
+
+/// the namespace of `xsi:type`
+const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// the permissions of a grant
+const PERMISSIONS: &[&str] = &["FULL_CONTROL", "READ", "WRITE", "READ_ACP", "WRITE_ACP"];
+
+/// the message of `MalformedACLError`
+const MALFORMED_ACL: &str =
+    "The XML you provided was not well-formed or did not validate against our published schema";
+
+/// deserializes and validates an `AccessControlPolicy` body, which is `None` if the body is empty
+pub(super) async fn extract_access_control_policy(
+    body: Body,
+) -> S3Result<Option<AccessControlPolicy>> {
+    let policy: Option<xml::AccessControlPolicy> = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedACLError, MALFORMED_ACL, err))?;
+    policy
+        .map(|policy| {
+            if policy.access_control_list.grants.iter().all(is_valid_grant) {
+                Ok(policy.into())
+            } else {
+                Err(code_error!(MalformedACLError, MALFORMED_ACL))
+            }
+        })
+        .transpose()
+}
+
+/// whether a grant has a known permission and a grantee of a known type, which is identified
+fn is_valid_grant(grant: &xml::Grant) -> bool {
+    let permission = grant.permission.as_deref().unwrap_or_default();
+    PERMISSIONS.contains(&permission)
+        && grant
+            .grantee
+            .as_ref()
+            .map_or(false, |grantee| match grantee.type_.as_str() {
+                "CanonicalUser" => grantee.id.is_some(),
+                "Group" => grantee.uri.is_some(),
+                "AmazonCustomerByEmail" => grantee.email_address.is_some(),
+                _ => false,
+            })
+}
+
+/// Resolves the ACL of a `Put*Acl` request, which is given by exactly one of
+/// a canned ACL, grant headers or an `AccessControlPolicy` body
+///
+/// A canned ACL is expanded by `canned`, and the policy of a canned ACL or grant headers is owned by `owner`.
+///
+/// # Errors
+/// Returns `InvalidRequest` if both headers and a body are given, `MissingSecurityHeader` if neither is given,
+/// or the error of `canned`
+pub(super) fn resolve_policy(
+    body_policy: Option<AccessControlPolicy>,
+    acl: Option<&str>,
+    grants: Vec<Grant>,
+    owner: Option<Owner>,
+    canned: impl FnOnce(&str, Option<&Owner>) -> S3Result<Vec<Grant>>,
+) -> S3Result<AccessControlPolicy> {
+    let has_headers = acl.is_some() || !grants.is_empty();
+    if body_policy.is_some() && has_headers {
+        return Err(invalid_request!(
+            "Specifying both an ACL in the headers and an AccessControlPolicy in the body is not allowed"
+        ));
+    }
+    if let Some(policy) = body_policy {
+        return Ok(policy);
+    }
+    if !has_headers {
+        return Err(code_error!(
+            MissingSecurityHeader,
+            "Your request was missing a required header: x-amz-acl"
+        ));
+    }
+
+    let grants = match acl {
+        Some(acl) => canned(acl, owner.as_ref())?,
+        None => grants,
+    };
+    Ok(AccessControlPolicy {
+        grants: Some(grants),
+        owner,
+    })
+}
+
+/// writes an `AccessControlPolicy` document, with an `Owner` element even if it is empty
+pub(super) fn write_access_control_policy<W: io::Write>(
+    w: &mut EventWriter<W>,
+    owner: Owner,
+    grants: Vec<Grant>,
+) -> ::xml::writer::Result<()> {
+    w.stack("AccessControlPolicy", |w| {
+        w.stack("Owner", |w| {
+            w.opt_element("ID", owner.id)?;
+            w.opt_element("DisplayName", owner.display_name)
+        })?;
+        w.stack("AccessControlList", |w| {
+            w.iter_element(grants.into_iter(), |w, grant| {
+                w.stack("Grant", |w| {
+                    if let Some(grantee) = grant.grantee {
+                        w.write(
+                            XmlEvent::start_element("Grantee")
+                                .ns("xsi", XSI_NAMESPACE)
+                                .attr("xsi:type", &grantee.type_),
+                        )?;
+                        w.opt_element("ID", grantee.id)?;
+                        w.opt_element("DisplayName", grantee.display_name)?;
+                        w.opt_element("EmailAddress", grantee.email_address)?;
+                        w.opt_element("URI", grantee.uri)?;
+                        w.write(XmlEvent::end_element())?;
+                    }
+                    w.opt_element("Permission", grant.permission)
+                })
+            })
+        })
+    })
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `Owner`
+    #[derive(Debug, Deserialize)]
+    pub struct Owner {
+        /// ID
+        #[serde(rename = "ID")]
+        pub id: Option<String>,
+        /// DisplayName
+        #[serde(rename = "DisplayName")]
+        pub display_name: Option<String>,
+    }
+
+    /// `Grantee`
+    #[derive(Debug, Deserialize)]
+    pub struct Grantee {
+        /// xsi:type
+        #[serde(rename = "xsi:type")]
+        pub type_: String,
+        /// ID
+        #[serde(rename = "ID")]
+        pub id: Option<String>,
+        /// DisplayName
+        #[serde(rename = "DisplayName")]
+        pub display_name: Option<String>,
+        /// EmailAddress
+        #[serde(rename = "EmailAddress")]
+        pub email_address: Option<String>,
+        /// URI
+        #[serde(rename = "URI")]
+        pub uri: Option<String>,
+    }
+
+    /// `Grant`
+    #[derive(Debug, Deserialize)]
+    pub struct Grant {
+        /// Grantee
+        #[serde(rename = "Grantee")]
+        pub grantee: Option<Grantee>,
+        /// Permission
+        #[serde(rename = "Permission")]
+        pub permission: Option<String>,
+    }
+
+    /// `AccessControlList`
+    #[derive(Debug, Default, Deserialize)]
+    pub struct AccessControlList {
+        /// Grant
+        #[serde(rename = "Grant", default)]
+        pub grants: Vec<Grant>,
+    }
+
+    /// `AccessControlPolicy`
+    #[derive(Debug, Deserialize)]
+    pub struct AccessControlPolicy {
+        /// Owner
+        #[serde(rename = "Owner")]
+        pub owner: Option<Owner>,
+        /// AccessControlList
+        #[serde(rename = "AccessControlList", default)]
+        pub access_control_list: AccessControlList,
+    }
+
+    impl From<AccessControlPolicy> for crate::dto::AccessControlPolicy {
+        fn from(policy: AccessControlPolicy) -> Self {
+            let grants = policy.access_control_list.grants.into_iter();
+            Self {
+                grants: Some(grants.map(Into::into).collect()),
+                owner: policy
+                    .owner
+                    .map(|Owner { id, display_name }| crate::dto::Owner { id, display_name }),
+            }
+        }
+    }
+
+    impl From<Grant> for crate::dto::Grant {
+        fn from(
+            Grant {
+                grantee,
+                permission,
+            }: Grant,
+        ) -> Self {
+            Self {
+                grantee: grantee.map(|grantee| crate::dto::Grantee {
+                    display_name: grantee.display_name,
+                    email_address: grantee.email_address,
+                    id: grantee.id,
+                    type_: grantee.type_,
+                    uri: grantee.uri,
+                }),
+                permission,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+    use crate::utils::ResponseExt;
+    use crate::Response;
+
+    use futures::executor::block_on;
+
+    fn extract(body: &str) -> S3Result<Option<AccessControlPolicy>> {
+        block_on(extract_access_control_policy(Body::from(body.to_owned())))
+    }
+
+    fn grant(type_: &str, grantee: &str, permission: &str) -> String {
+        format!(
+            "<Grant><Grantee xmlns:xsi=\"{}\" xsi:type=\"{}\">{}</Grantee><Permission>{}</Permission></Grant>",
+            XSI_NAMESPACE, type_, grantee, permission
+        )
+    }
+
+    fn policy(grants: &[String]) -> String {
+        format!(
+            "<AccessControlPolicy><Owner><ID>a</ID></Owner><AccessControlList>{}</AccessControlList></AccessControlPolicy>",
+            grants.concat()
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let body = policy(&[
+            grant(
+                "CanonicalUser",
+                "<ID>a</ID><DisplayName>b</DisplayName>",
+                "FULL_CONTROL",
+            ),
+            grant(
+                "AmazonCustomerByEmail",
+                "<EmailAddress>c@example.com</EmailAddress>",
+                "READ",
+            ),
+            grant(
+                "Group",
+                "<URI>http://acs.amazonaws.com/groups/s3/LogDelivery</URI>",
+                "WRITE",
+            ),
+        ]);
+        let policy = extract(&body).unwrap().unwrap();
+        let (owner, grants) = (policy.owner.unwrap(), policy.grants.unwrap());
+        let types: Vec<&str> = grants
+            .iter()
+            .map(|grant| grant.grantee.as_ref().unwrap().type_.as_str())
+            .collect();
+        assert_eq!(types, ["CanonicalUser", "AmazonCustomerByEmail", "Group"]);
+
+        let mut res = Response::new(Body::empty());
+        res.set_xml_body(1024, |w| write_access_control_policy(w, owner, grants))
+            .unwrap();
+        let written = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        let expected = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>{}", body);
+        assert_eq!(String::from_utf8(written.to_vec()).unwrap(), expected);
+
+        assert!(extract("").unwrap().is_none());
+
+        let mut res = Response::new(Body::empty());
+        res.set_xml_body(256, |w| {
+            write_access_control_policy(w, Owner::default(), Vec::new())
+        })
+        .unwrap();
+        let written = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        assert!(String::from_utf8(written.to_vec()).unwrap().ends_with(
+            "<AccessControlPolicy><Owner /><AccessControlList /></AccessControlPolicy>"
+        ));
+    }
+
+    #[test]
+    fn resolve() {
+        let owner = || {
+            Some(Owner {
+                id: Some("a".to_owned()),
+                display_name: None,
+            })
+        };
+        let canned = |acl: &str, owner: Option<&Owner>| {
+            assert_eq!(acl, "private");
+            assert_eq!(owner.unwrap().id.as_deref(), Some("a"));
+            Ok(Vec::new())
+        };
+        let code = |ret: S3Result<AccessControlPolicy>| ret.unwrap_err().code();
+
+        let policy = resolve_policy(None, Some("private"), Vec::new(), owner(), canned).unwrap();
+        assert_eq!(policy.owner, owner());
+        assert_eq!(policy.grants, Some(Vec::new()));
+
+        let grant = Grant {
+            grantee: None,
+            permission: Some("READ".to_owned()),
+        };
+        let policy = resolve_policy(None, None, vec![grant.clone()], owner(), canned).unwrap();
+        assert_eq!(policy.grants, Some(vec![grant.clone()]));
+
+        let body = || Some(AccessControlPolicy::default());
+        let policy = resolve_policy(body(), None, Vec::new(), owner(), canned).unwrap();
+        assert_eq!(policy, AccessControlPolicy::default());
+
+        let ret = resolve_policy(body(), Some("private"), Vec::new(), owner(), canned);
+        assert_eq!(code(ret), S3ErrorCode::InvalidRequest);
+        let ret = resolve_policy(body(), None, vec![grant], owner(), canned);
+        assert_eq!(code(ret), S3ErrorCode::InvalidRequest);
+        let ret = resolve_policy(None, None, Vec::new(), owner(), canned);
+        assert_eq!(code(ret), S3ErrorCode::MissingSecurityHeader);
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            "<AccessControlPolicy>".to_owned(),
+            policy(&[grant("CanonicalUser", "<ID>a</ID>", "DELETE")]),
+            policy(&[grant("CanonicalUser", "", "READ")]),
+            policy(&[grant("Group", "<ID>a</ID>", "READ")]),
+            policy(&[grant("User", "<ID>a</ID>", "READ")]),
+            policy(&["<Grant><Permission>READ</Permission></Grant>".to_owned()]),
+        ];
+        for body in &cases {
+            let err = extract(body).unwrap_err();
+            assert_eq!(err.code(), S3ErrorCode::MalformedACLError, "{}", body);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! `x-amz-acl` and `x-amz-grant-*`, shared by the operations which set an ACL
+//!
+//! + [`CreateBucket`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateBucket.html)
+//! + [`PutObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
+//! + [`CreateMultipartUpload`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html)
+//! + [`CopyObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
+//! + [`PutObjectAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectAcl.html)
+//! + [`PutBucketAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAcl.html)
+//!
+//! A request sets its ACL either by a canned ACL or by explicit grants, never both.
+//! The requests keep the raw header values, which are validated here.
+
+use crate::data_structures::OrderedHeaders;
+use crate::dto::{Grant, Grantee, Owner};
+use crate::errors::S3Result;
+use crate::headers::{
+    AmzGrant, HeaderName, X_AMZ_ACL, X_AMZ_GRANT_FULL_CONTROL, X_AMZ_GRANT_READ,
+    X_AMZ_GRANT_READ_ACP, X_AMZ_GRANT_WRITE, X_AMZ_GRANT_WRITE_ACP,
+};
+
+/// the group of all users
+const ALL_USERS: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+
+/// the group of the authenticated users
+const AUTHENTICATED_USERS: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+
+/// the group which writes server access logs
+const LOG_DELIVERY: &str = "http://acs.amazonaws.com/groups/s3/LogDelivery";
+
+/// a grant header and the permission it grants
+pub(super) type GrantHeader = (&'static HeaderName, &'static str);
+
+/// the grant headers of the operations which write an object
+pub(super) fn object_grant_headers() -> [GrantHeader; 4] {
+    [
+        (&*X_AMZ_GRANT_FULL_CONTROL, "FULL_CONTROL"),
+        (&*X_AMZ_GRANT_READ, "READ"),
+        (&*X_AMZ_GRANT_READ_ACP, "READ_ACP"),
+        (&*X_AMZ_GRANT_WRITE_ACP, "WRITE_ACP"),
+    ]
+}
+
+/// the grant headers of `CreateBucket` and `PutBucketAcl`, where `WRITE` is grantable too
+pub(super) fn bucket_grant_headers() -> [GrantHeader; 5] {
+    [
+        (&*X_AMZ_GRANT_FULL_CONTROL, "FULL_CONTROL"),
+        (&*X_AMZ_GRANT_READ, "READ"),
+        (&*X_AMZ_GRANT_READ_ACP, "READ_ACP"),
+        (&*X_AMZ_GRANT_WRITE, "WRITE"),
+        (&*X_AMZ_GRANT_WRITE_ACP, "WRITE_ACP"),
+    ]
+}
+
+/// Validates the ACL headers and converts the explicit grants into the grants of an ACL
+///
+/// # Errors
+/// Returns `InvalidArgument` if a grant header is malformed,
+/// or `InvalidRequest` if grants are combined with a canned ACL
+pub(super) fn extract_grants(
+    headers: &OrderedHeaders<'_>,
+    grant_headers: &[GrantHeader],
+) -> S3Result<Vec<Grant>> {
+    let mut grants = Vec::new();
+    for &(name, permission) in grant_headers {
+        if let Some(value) = headers.get(name) {
+            let grant = AmzGrant::from_header_str(value).map_err(|err| {
+                code_error!(
+                    InvalidArgument,
+                    format!("Argument format not recognized: {}", name),
+                    err
+                )
+            })?;
+            grants.extend(grant.into_grants(permission));
+        }
+    }
+
+    if !grants.is_empty() && headers.get(&*X_AMZ_ACL).is_some() {
+        return Err(invalid_request!(
+            "Specifying both Canned ACLs and Header Grants is not allowed"
+        ));
+    }
+    Ok(grants)
+}
+
+/// The `FULL_CONTROL` grant of an owner, which an object or a bucket without an explicit ACL has
+pub(super) fn owner_full_control(owner: Option<&Owner>) -> Option<Grant> {
+    let owner = owner?;
+    let grantee = Grantee {
+        type_: "CanonicalUser".to_owned(),
+        id: Some(owner.id.clone()?),
+        display_name: owner.display_name.clone(),
+        ..Grantee::default()
+    };
+    Some(Grant {
+        grantee: Some(grantee),
+        permission: Some("FULL_CONTROL".to_owned()),
+    })
+}
+
+/// Expands the canned ACL of an object into its grants, given the owner of the object
+///
+/// The bucket owner of `bucket-owner-read` and `bucket-owner-full-control` is the owner,
+/// as in a deployment with a single principal.
+/// An owner without id gets no grant.
+///
+/// # Errors
+/// Returns `InvalidArgument` if the canned ACL is unknown
+pub(super) fn canned_object_grants(acl: &str, owner: Option<&Owner>) -> S3Result<Vec<Grant>> {
+    match acl {
+        "aws-exec-read" | "bucket-owner-read" | "bucket-owner-full-control" => {
+            Ok(owner_full_control(owner).into_iter().collect())
+        }
+        _ => canned_grants(acl, owner),
+    }
+}
+
+/// Expands the canned ACL of a bucket into its grants, given the owner of the bucket
+///
+/// An owner without id gets no grant.
+///
+/// # Errors
+/// Returns `InvalidArgument` if the canned ACL is unknown
+pub(super) fn canned_bucket_grants(acl: &str, owner: Option<&Owner>) -> S3Result<Vec<Grant>> {
+    if acl != "log-delivery-write" {
+        return canned_grants(acl, owner);
+    }
+    let mut grants: Vec<Grant> = owner_full_control(owner).into_iter().collect();
+    grants.push(group_grant(LOG_DELIVERY, "WRITE"));
+    grants.push(group_grant(LOG_DELIVERY, "READ_ACP"));
+    Ok(grants)
+}
+
+/// expands a canned ACL of both objects and buckets
+fn canned_grants(acl: &str, owner: Option<&Owner>) -> S3Result<Vec<Grant>> {
+    let mut grants: Vec<Grant> = owner_full_control(owner).into_iter().collect();
+    match acl {
+        "private" => {}
+        "public-read" => grants.push(group_grant(ALL_USERS, "READ")),
+        "public-read-write" => {
+            grants.push(group_grant(ALL_USERS, "READ"));
+            grants.push(group_grant(ALL_USERS, "WRITE"));
+        }
+        "authenticated-read" => grants.push(group_grant(AUTHENTICATED_USERS, "READ")),
+        _ => {
+            return Err(code_error!(
+                InvalidArgument,
+                format!("Invalid canned ACL: {}", acl)
+            ))
+        }
+    }
+    Ok(grants)
+}
+
+/// a grant to a group
+fn group_grant(uri: &str, permission: &str) -> Grant {
+    Grant {
+        grantee: Some(Grantee {
+            type_: "Group".to_owned(),
+            uri: Some(uri.to_owned()),
+            ..Grantee::default()
+        }),
+        permission: Some(permission.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+    use crate::headers::AmzGrantee;
+
+    /// valid grant headers and their grantees
+    const VALID: &[(&str, &[AmzGrantee<'static>])] = &[
+        (r#"id="111122223333""#, &[AmzGrantee::Id("111122223333")]),
+        ("id=111122223333", &[AmzGrantee::Id("111122223333")]),
+        (
+            r#"uri="http://acs.amazonaws.com/groups/global/AllUsers""#,
+            &[AmzGrantee::Uri(
+                "http://acs.amazonaws.com/groups/global/AllUsers",
+            )],
+        ),
+        (
+            r#"id="111122223333", emailAddress="xyz@example.com""#,
+            &[
+                AmzGrantee::Id("111122223333"),
+                AmzGrantee::EmailAddress("xyz@example.com"),
+            ],
+        ),
+        (
+            r#" id = "a" ,id="b""#,
+            &[AmzGrantee::Id("a"), AmzGrantee::Id("b")],
+        ),
+    ];
+
+    /// invalid grant headers
+    const INVALID: &[&str] = &[
+        "",
+        "id",
+        r#"id="""#,
+        r#"id="a"#,
+        r#"id=a""#,
+        r#"id="a"b""#,
+        r#"id="a","#,
+        r#"ID="a""#,
+        r#"email="xyz@example.com""#,
+        r#"canonicalUser="a""#,
+        r#"id="a" uri="b""#,
+    ];
+
+    fn extract(pairs: &[(&str, &str)], grant_headers: &[GrantHeader]) -> S3Result<Vec<Grant>> {
+        let mut pairs = pairs.to_vec();
+        pairs.sort_unstable();
+        extract_grants(&OrderedHeaders::from_slice_unchecked(&pairs), grant_headers)
+    }
+
+    #[test]
+    fn parse() {
+        for &(header, grantees) in VALID {
+            let grant = AmzGrant::from_header_str(header).unwrap();
+            assert_eq!(grant.grantees, grantees, "header: {}", header);
+        }
+        for &header in INVALID {
+            let ret = AmzGrant::from_header_str(header);
+            assert!(ret.is_err(), "header: {}", header);
+        }
+    }
+
+    #[test]
+    fn operations() {
+        let tables: [&[GrantHeader]; 2] = [&object_grant_headers(), &bucket_grant_headers()];
+        for &table in &tables {
+            for &(name, permission) in table {
+                for &(value, grantees) in VALID {
+                    let grants = extract(&[(name.as_str(), value)], table).unwrap();
+                    assert_eq!(grants.len(), grantees.len(), "{}: {}", name, value);
+                    assert!(grants
+                        .iter()
+                        .all(|grant| grant.permission.as_deref() == Some(permission)));
+
+                    let pairs = [(name.as_str(), value), ("x-amz-acl", "private")];
+                    let err = extract(&pairs, table).unwrap_err();
+                    assert_eq!(err.code(), S3ErrorCode::InvalidRequest, "{}", name);
+                }
+                for &value in INVALID {
+                    let err = extract(&[(name.as_str(), value)], table).unwrap_err();
+                    assert_eq!(err.code(), S3ErrorCode::InvalidArgument, "{}", value);
+                }
+            }
+        }
+
+        let grants = extract(&[("x-amz-acl", "public-read")], &bucket_grant_headers()).unwrap();
+        assert!(grants.is_empty());
+
+        // objects have no WRITE permission, so the header is ignored
+        let pairs = [("x-amz-grant-write", "canonicalUser=a")];
+        let grants = extract(&pairs, &object_grant_headers()).unwrap();
+        assert!(grants.is_empty());
+    }
+
+    #[test]
+    fn canned() {
+        let owner = Owner {
+            id: Some("a".to_owned()),
+            display_name: None,
+        };
+        let summary = |acl: &str, canned: fn(&str, Option<&Owner>) -> S3Result<Vec<Grant>>| {
+            canned(acl, Some(&owner)).map(|grants| {
+                grants
+                    .into_iter()
+                    .map(|grant| {
+                        let grantee = grant.grantee.unwrap();
+                        let who = grantee.id.or(grantee.uri).unwrap();
+                        (who, grant.permission.unwrap())
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let owner_full_control = ("a".to_owned(), "FULL_CONTROL".to_owned());
+        let object = |acl| summary(acl, canned_object_grants);
+        let bucket = |acl| summary(acl, canned_bucket_grants);
+        assert_eq!(object("private").unwrap(), [owner_full_control.clone()]);
+        assert_eq!(
+            object("public-read-write").unwrap(),
+            [
+                owner_full_control.clone(),
+                (ALL_USERS.to_owned(), "READ".to_owned()),
+                (ALL_USERS.to_owned(), "WRITE".to_owned()),
+            ]
+        );
+        assert_eq!(
+            object("public-read-write").unwrap(),
+            bucket("public-read-write").unwrap()
+        );
+        assert_eq!(
+            object("bucket-owner-read").unwrap(),
+            [owner_full_control.clone()]
+        );
+        assert_eq!(
+            bucket("log-delivery-write").unwrap(),
+            [
+                owner_full_control,
+                (LOG_DELIVERY.to_owned(), "WRITE".to_owned()),
+                (LOG_DELIVERY.to_owned(), "READ_ACP".to_owned()),
+            ]
+        );
+        for &acl in &["public", "log-delivery-write"] {
+            let err = object(acl).unwrap_err();
+            assert_eq!(err.code(), S3ErrorCode::InvalidArgument, "{}", acl);
+        }
+        for &acl in &["public", "aws-exec-read"] {
+            let err = bucket(acl).unwrap_err();
+            assert_eq!(err.code(), S3ErrorCode::InvalidArgument, "{}", acl);
+        }
+
+        let grants = canned_object_grants("authenticated-read", None).unwrap();
+        assert_eq!(grants.len(), 1);
+    }
+}
@@ -2,6 +2,7 @@
 
 use super::{wrap_internal_error, ReqContext, S3Handler};
 
+use crate::consistency::{self, ConsistencyToken};
 use crate::dto::{
     CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
     CompletedMultipartUpload, CompletedPart,
@@ -12,10 +13,13 @@ use crate::headers::{
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_VERSION_ID,
 };
 use crate::output::S3Output;
+use crate::size_limits;
 use crate::storage::S3Storage;
 use crate::utils::body::deserialize_xml_body;
 use crate::utils::{ResponseExt, XmlWriterExt};
-use crate::{async_trait, Response};
+use crate::{async_trait, Body, Response};
+
+use std::mem;
 
 use hyper::Method;
 
@@ -25,7 +29,7 @@ pub struct Handler;
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::POST);
+        bool_try!(ctx.method == Method::POST);
         bool_try!(ctx.path.is_object());
         let qs = bool_try_some!(ctx.query_strings.as_ref());
         qs.get("uploadId").is_some()
@@ -37,7 +41,15 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx).await?;
+        size_limits::check_completion(ctx, storage, &input).await?;
+        let token = ConsistencyToken::new(input.bucket.clone(), input.key.clone());
         let output = storage.complete_multipart_upload(input).await;
+        if let Ok(ref output) = output {
+            let token = token
+                .version_id(output.version_id.clone())
+                .e_tag(output.e_tag.clone());
+            consistency::barrier(ctx, storage, token).await;
+        }
         output.try_into_response()
     }
 }
@@ -49,6 +61,26 @@ async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CompleteMultipartUploadRe
             .await
             .map_err(|err| invalid_request!("Invalid xml format", err))?;
 
+    let multipart_upload: Option<CompletedMultipartUpload> = multipart_upload.map(Into::into);
+
+    // an upload can not be completed without parts
+    let has_parts = multipart_upload
+        .as_ref()
+        .and_then(|multipart_upload| multipart_upload.parts.as_ref())
+        .map_or(false, |parts| !parts.is_empty());
+    if !has_parts {
+        return Err(code_error!(
+            MalformedXML,
+            "The XML you provided was not well-formed or did not validate against our published schema."
+        ));
+    }
+
+    let parts = multipart_upload
+        .as_ref()
+        .and_then(|multipart_upload| multipart_upload.parts.as_deref())
+        .unwrap_or_default();
+    check_part_order(parts)?;
+
     let (bucket, key) = ctx.unwrap_object_path();
     let upload_id = ctx.unwrap_qs("uploadId").to_owned();
 
@@ -56,7 +88,7 @@ async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CompleteMultipartUploadRe
         bucket: bucket.into(),
         key: key.into(),
         upload_id,
-        multipart_upload: multipart_upload.map(Into::into),
+        multipart_upload,
         ..CompleteMultipartUploadRequest::default()
     };
 
@@ -66,6 +98,21 @@ async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CompleteMultipartUploadRe
     Ok(input)
 }
 
+/// rejects parts which are not in ascending order of part numbers
+fn check_part_order(parts: &[CompletedPart]) -> S3Result<()> {
+    let mut last: Option<i64> = None;
+    for part_number in parts.iter().filter_map(|part| part.part_number) {
+        if last.map_or(false, |last| part_number <= last) {
+            return Err(code_error!(
+                InvalidPartOrder,
+                "The list of parts was not in ascending order. The parts list must be specified in order by part number."
+            ));
+        }
+        last = Some(part_number);
+    }
+    Ok(())
+}
+
 impl From<CompleteMultipartUploadError> for S3Error {
     fn from(err: CompleteMultipartUploadError) -> Self {
         match err {}
@@ -99,6 +146,11 @@ impl S3Output for CompleteMultipartUploadOutput {
                 })
             })?;
 
+            // the result is sent as a stream of an unknown length,
+            // so that whitespace can be sent ahead of it while a long completion is running
+            let document = mem::take(res.body_mut());
+            *res.body_mut() = Body::wrap_stream(document);
+
             Ok(())
         })
     }
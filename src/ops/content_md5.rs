@@ -0,0 +1,147 @@
+//! `Content-MD5` of uploaded bodies
+//!
+//! A header which is not the base64 of an MD5 digest is rejected with `InvalidDigest` before the storage is called.
+//! The body of a `PutObject` or an `UploadPart` is hashed while the storage reads it,
+//! and a digest which does not match fails the body at its end, so that the storage does not complete the upload.
+//! Then the error of the storage is replaced by `BadDigest`.
+
+use crate::dto::ByteStream;
+use crate::errors::{S3Error, S3ErrorCode, S3Result, S3StorageError, S3StorageResult};
+use crate::utils::Apply;
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::future;
+use futures::stream::{self, StreamExt};
+use md5::{Digest, Md5};
+
+/// the message of `BadDigest`
+const BAD_DIGEST: &str = "The Content-MD5 you specified did not match what we received.";
+
+/// decodes a base64 `Content-MD5`
+pub(super) fn parse(content_md5: &str) -> S3Result<Vec<u8>> {
+    base64::decode(content_md5)
+        .ok()
+        .filter(|digest| digest.len() == 16)
+        .ok_or_else(|| code_error!(InvalidDigest, "The Content-MD5 you specified is not valid."))
+}
+
+/// checks a base64 `Content-MD5` against a whole body
+pub(super) fn check(content_md5: &str, body: &[u8]) -> S3Result<()> {
+    if *Md5::digest(body) != *parse(content_md5)? {
+        return Err(code_error!(BadDigest, BAD_DIGEST));
+    }
+    Ok(())
+}
+
+/// Verifies a body against its `Content-MD5` while it is streamed
+#[derive(Debug)]
+pub(super) struct DigestCheck {
+    /// whether the digest of the body does not match
+    mismatched: Arc<AtomicBool>,
+}
+
+/// hashes `body` while it is streamed, which fails at its end if it does not match `content_md5`
+pub(super) fn verify(
+    content_md5: Option<&str>,
+    body: &mut Option<ByteStream>,
+) -> S3Result<Option<DigestCheck>> {
+    let expected = match content_md5 {
+        Some(content_md5) => parse(content_md5)?,
+        None => return Ok(None),
+    };
+    let stream = match body.take() {
+        Some(stream) => stream,
+        None => return Ok(None),
+    };
+
+    let mismatched: Arc<AtomicBool> = Arc::default();
+    let recorded = Arc::clone(&mismatched);
+    let hasher = Arc::new(Mutex::new(Md5::new()));
+    let hashing = Arc::clone(&hasher);
+    let stream = stream
+        .map(move |chunk| {
+            let chunk = chunk?;
+            if let Ok(mut hasher) = hashing.lock() {
+                hasher.update(&chunk);
+            }
+            Ok(chunk)
+        })
+        .chain(stream::once(future::ready(())).filter_map(move |()| {
+            let digest = hasher.lock().ok().map(|hasher| hasher.clone().finalize());
+            let matched = digest.map_or(false, |digest| *digest == *expected);
+            let ans = (!matched).then(|| {
+                recorded.store(true, Ordering::Release);
+                Err(io::Error::new(io::ErrorKind::InvalidData, BAD_DIGEST))
+            });
+            future::ready(ans)
+        }))
+        .apply(ByteStream::new);
+
+    *body = Some(stream);
+    Ok(Some(DigestCheck { mismatched }))
+}
+
+impl DigestCheck {
+    /// fails a storage whose body does not match, even if it has succeeded
+    pub(super) fn check<T, E>(&self, ret: S3StorageResult<T, E>) -> S3StorageResult<T, E> {
+        if !self.mismatched.load(Ordering::Acquire) {
+            return ret;
+        }
+        let err = S3Error::from_code(S3ErrorCode::BadDigest).message(BAD_DIGEST);
+        Err(S3StorageError::Other(err.finish()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use futures::stream::TryStreamExt;
+    use hyper::body::Bytes;
+
+    /// base64 MD5 of `Hello World!`
+    const HELLO_MD5: &str = "7Qdih1MuhjZehB6Sv8UNjA==";
+
+    fn stream_body(content_md5: &str, chunks: &[&'static str]) -> (io::Result<Vec<Bytes>>, bool) {
+        let chunks: Vec<io::Result<Bytes>> = chunks.iter().map(|&c| Ok(Bytes::from(c))).collect();
+        let mut body = Some(ByteStream::new(stream::iter(chunks)));
+        let check = verify(Some(content_md5), &mut body).unwrap().unwrap();
+        let read = block_on(body.unwrap().try_collect());
+        let ret: S3StorageResult<(), ()> = check.check(Ok(()));
+        (read, ret.is_err())
+    }
+
+    #[test]
+    fn digests() {
+        check(HELLO_MD5, b"Hello World!").unwrap();
+        assert_eq!(
+            check(HELLO_MD5, b"Hello World?").unwrap_err().code(),
+            S3ErrorCode::BadDigest
+        );
+        for invalid in &["", "not base64", "AAAA"] {
+            assert_eq!(
+                parse(invalid).unwrap_err().code(),
+                S3ErrorCode::InvalidDigest
+            );
+        }
+
+        let (read, failed) = stream_body(HELLO_MD5, &["Hello", " World!"]);
+        assert_eq!(read.unwrap().concat(), b"Hello World!");
+        assert!(!failed);
+
+        let (read, failed) = stream_body(HELLO_MD5, &["Hello", " World?"]);
+        assert_eq!(read.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(failed);
+
+        let mut body = None;
+        assert_eq!(
+            verify(Some("AAAA"), &mut body).unwrap_err().code(),
+            S3ErrorCode::InvalidDigest
+        );
+        assert!(verify(None, &mut body).unwrap().is_none());
+    }
+}
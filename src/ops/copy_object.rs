@@ -1,40 +1,50 @@
 //! [`CopyObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
+//!
+//! The copy source is passed to the storage as `bucket/key`, whose key is decoded like the key in the path,
+//! followed by `?versionId=<version id>` if a version is given.
+//! An object can be copied onto itself only if the copy changes something,
+//! such as its metadata with `x-amz-metadata-directive: REPLACE`.
 
+use super::acl_headers;
+use super::object_write_headers::CommonObjectWriteHeaders;
 use super::{wrap_internal_error, ReqContext, S3Handler};
 
+use crate::consistency::{self, ConsistencyToken};
 use crate::dto::{CopyObjectError, CopyObjectOutput, CopyObjectRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::AmzCopySource;
 use crate::headers::{
-    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_TYPE, EXPIRES,
-    X_AMZ_ACL, X_AMZ_COPY_SOURCE, X_AMZ_COPY_SOURCE_IF_MATCH, X_AMZ_COPY_SOURCE_IF_MODIFIED_SINCE,
+    X_AMZ_COPY_SOURCE, X_AMZ_COPY_SOURCE_IF_MATCH, X_AMZ_COPY_SOURCE_IF_MODIFIED_SINCE,
     X_AMZ_COPY_SOURCE_IF_NONE_MATCH, X_AMZ_COPY_SOURCE_IF_UNMODIFIED_SINCE,
     X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
     X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
     X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_COPY_SOURCE_VERSION_ID,
-    X_AMZ_EXPIRATION, X_AMZ_GRANT_FULL_CONTROL, X_AMZ_GRANT_READ, X_AMZ_GRANT_READ_ACP,
-    X_AMZ_GRANT_WRITE_ACP, X_AMZ_METADATA_DIRECTIVE, X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
-    X_AMZ_OBJECT_LOCK_MODE, X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE, X_AMZ_REQUEST_CHARGED,
-    X_AMZ_REQUEST_PAYER, X_AMZ_SERVER_SIDE_ENCRYPTION, X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
+    X_AMZ_EXPIRATION, X_AMZ_METADATA_DIRECTIVE, X_AMZ_REQUEST_CHARGED,
+    X_AMZ_SERVER_SIDE_ENCRYPTION, X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
     X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-    X_AMZ_STORAGE_CLASS, X_AMZ_TAGGING, X_AMZ_TAGGING_DIRECTIVE, X_AMZ_VERSION_ID,
-    X_AMZ_WEBSITE_REDIRECT_LOCATION,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_TAGGING_DIRECTIVE, X_AMZ_VERSION_ID,
 };
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{async_trait, Method, Response};
 
+use std::borrow::Cow;
+
 /// `CopyObject` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::PUT);
+        bool_try!(ctx.method == Method::PUT);
         bool_try!(ctx.path.is_object());
-        ctx.headers.get(&*X_AMZ_COPY_SOURCE).is_some()
+        bool_try!(ctx.headers.get(&*X_AMZ_COPY_SOURCE).is_some());
+        // `UploadPartCopy` copies into a part instead
+        let qs = ctx.query_strings.as_ref();
+        qs.map_or(true, |qs| {
+            qs.get("partNumber").is_none() && qs.get("uploadId").is_none()
+        })
     }
 
     async fn handle(
@@ -43,33 +53,79 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        let token = ConsistencyToken::new(input.bucket.clone(), input.key.clone());
         let output = storage.copy_object(input).await;
+        if let Ok(ref output) = output {
+            let e_tag = output
+                .copy_object_result
+                .as_ref()
+                .and_then(|result| result.e_tag.clone());
+            let token = token.version_id(output.version_id.clone()).e_tag(e_tag);
+            consistency::barrier(ctx, storage, token).await;
+        }
         output.try_into_response()
     }
 }
 
-/// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CopyObjectRequest> {
-    let (bucket, key) = ctx.unwrap_object_path();
+/// the parsed `x-amz-copy-source` of a request
+pub(super) struct CopySource<'a> {
+    /// source bucket
+    pub(super) bucket: &'a str,
+    /// source key, decoded like the key in the path
+    pub(super) key: Cow<'a, str>,
+    /// `bucket/key`, followed by `?versionId=<version id>` if a version is given
+    pub(super) formatted: String,
+}
+
+/// parses `x-amz-copy-source`, whose key is percent-encoded like the key in the path
+pub(super) fn extract_copy_source<'a>(ctx: &'a ReqContext<'_>) -> S3Result<CopySource<'a>> {
     let copy_source = ctx.unwrap_header(&*X_AMZ_COPY_SOURCE);
 
+    let copy_source = copy_source.strip_prefix('/').unwrap_or(copy_source);
     AmzCopySource::try_match(copy_source)
         .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+    let (copy_source, version_id) = AmzCopySource::split_version_id(copy_source);
+
+    let (src_bucket, src_key) = match copy_source.find('/') {
+        Some(idx) => {
+            let (src_bucket, src_key) = copy_source.split_at(idx);
+            let src_key = src_key.get(1..).unwrap_or("");
+            match ctx.key_encoding.decode(src_key) {
+                Some(src_key) => (src_bucket, src_key),
+                None => return Err(invalid_request!("Invalid header: x-amz-copy-source")),
+            }
+        }
+        None => return Err(invalid_request!("Invalid header: x-amz-copy-source")),
+    };
+    let copy_source = version_id.map_or_else(
+        || format!("{}/{}", src_bucket, src_key),
+        |version_id| format!("{}/{}?versionId={}", src_bucket, src_key, version_id),
+    );
+
+    Ok(CopySource {
+        bucket: src_bucket,
+        key: src_key,
+        formatted: copy_source,
+    })
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<CopyObjectRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let copy_source = extract_copy_source(ctx)?;
+    let is_self_copy = copy_source.bucket == bucket && copy_source.key == key;
+    let copy_source = copy_source.formatted;
 
     let mut input: CopyObjectRequest = CopyObjectRequest {
         bucket: bucket.into(),
         key: key.into(),
-        copy_source: copy_source.into(),
+        copy_source,
         ..CopyObjectRequest::default()
     };
 
     let h = &ctx.headers;
-    h.assign_str(&*X_AMZ_ACL, &mut input.acl);
-    h.assign_str(CACHE_CONTROL, &mut input.cache_control);
-    h.assign_str(CONTENT_DISPOSITION, &mut input.content_disposition);
-    h.assign_str(CONTENT_ENCODING, &mut input.content_encoding);
-    h.assign_str(CONTENT_LANGUAGE, &mut input.content_language);
-    h.assign_str(CONTENT_TYPE, &mut input.content_type);
+    CommonObjectWriteHeaders::extract_from(h)?.apply_to(&mut input);
+    let _grants = acl_headers::extract_grants(h, &acl_headers::object_grant_headers())?;
     h.assign_str(
         &*X_AMZ_COPY_SOURCE_IF_MATCH,
         &mut input.copy_source_if_match,
@@ -86,42 +142,8 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CopyObjectRequest> {
         &*X_AMZ_COPY_SOURCE_IF_UNMODIFIED_SINCE,
         &mut input.copy_source_if_unmodified_since,
     );
-    h.assign_str(EXPIRES, &mut input.expires);
-    h.assign_str(&*X_AMZ_GRANT_FULL_CONTROL, &mut input.grant_full_control);
-    h.assign_str(&*X_AMZ_GRANT_READ, &mut input.grant_read);
-    h.assign_str(&*X_AMZ_GRANT_READ_ACP, &mut input.grant_read_acp);
-    h.assign_str(&*X_AMZ_GRANT_WRITE_ACP, &mut input.grant_write_acp);
     h.assign_str(&*X_AMZ_METADATA_DIRECTIVE, &mut input.metadata_directive);
     h.assign_str(&*X_AMZ_TAGGING_DIRECTIVE, &mut input.tagging_directive);
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION,
-        &mut input.server_side_encryption,
-    );
-    h.assign_str(&*X_AMZ_STORAGE_CLASS, &mut input.storage_class);
-    h.assign_str(
-        &*X_AMZ_WEBSITE_REDIRECT_LOCATION,
-        &mut input.website_redirect_location,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-        &mut input.sse_customer_algorithm,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-        &mut input.sse_customer_key,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-        &mut input.sse_customer_key_md5,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
-        &mut input.ssekms_key_id,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
-        &mut input.ssekms_encryption_context,
-    );
     h.assign_str(
         &*X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
         &mut input.copy_source_sse_customer_algorithm,
@@ -134,17 +156,22 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CopyObjectRequest> {
         &*X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
         &mut input.copy_source_sse_customer_key_md5,
     );
-    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
-    h.assign_str(&*X_AMZ_TAGGING, &mut input.tagging);
-    h.assign_str(&*X_AMZ_OBJECT_LOCK_MODE, &mut input.object_lock_mode);
-    h.assign_str(
-        &*X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
-        &mut input.object_lock_retain_until_date,
-    );
-    h.assign_str(
-        &*X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
-        &mut input.object_lock_legal_hold_status,
-    );
+
+    let is_replace = match input.metadata_directive.as_deref() {
+        None | Some("COPY") => false,
+        Some("REPLACE") => true,
+        Some(_) => return Err(code_error!(InvalidArgument, "Unknown metadata directive.")),
+    };
+    let changes_object = is_replace
+        || input.storage_class.is_some()
+        || input.website_redirect_location.is_some()
+        || input.server_side_encryption.is_some()
+        || input.sse_customer_algorithm.is_some();
+    if is_self_copy && !changes_object {
+        return Err(invalid_request!(
+            "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata, storage class, website redirect location or encryption attributes."
+        ));
+    }
 
     Ok(input)
 }
@@ -0,0 +1,244 @@
+//! `CORSConfiguration` documents, shared by the operations which get or put the CORS rules of a bucket
+//!
+//! + [`GetBucketCors`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketCors.html)
+//! + [`PutBucketCors`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketCors.html)
+//!
+//! A configuration has at most 100 rules, each with at least one `AllowedOrigin` and one `AllowedMethod`.
+//! The methods are `GET`, `PUT`, `POST`, `DELETE` and `HEAD`,
+//! and an origin or a header has at most one `*` wildcard.
+
+use crate::dto::{CORSConfiguration, CORSRule};
+use crate::errors::S3Result;
+use crate::utils::body::deserialize_xml_body;
+use crate::utils::XmlWriterExt;
+use crate::Body;
+
+use std::io;
+
+use ::xml::writer::EventWriter;
+
+/// the max number of rules of a configuration
+const MAX_RULES: usize = 100;
+
+/// the methods which a rule can allow
+const ALLOWED_METHODS: &[&str] = &["GET", "PUT", "POST", "DELETE", "HEAD"];
+
+/// deserializes and validates a `CORSConfiguration` body
+pub(super) async fn extract_cors_configuration(body: Body) -> S3Result<CORSConfiguration> {
+    let config: xml::CORSConfiguration = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedXML, "Invalid xml format", err))?;
+    let rules = config.cors_rules;
+
+    if rules.is_empty() || rules.len() > MAX_RULES {
+        return Err(code_error!(
+            MalformedXML,
+            "The CORSConfiguration must have between 1 and 100 CORSRules"
+        ));
+    }
+    for rule in &rules {
+        if rule.allowed_origins.is_empty() || rule.allowed_methods.is_empty() {
+            return Err(code_error!(
+                MalformedXML,
+                "A CORSRule must have at least one AllowedOrigin and one AllowedMethod"
+            ));
+        }
+        if let Some(method) = rule
+            .allowed_methods
+            .iter()
+            .find(|method| !ALLOWED_METHODS.contains(&method.as_str()))
+        {
+            return Err(code_error!(
+                InvalidRequest,
+                format!(
+                    "Found unsupported HTTP method in CORS config. Unsupported method is {}",
+                    method
+                )
+            ));
+        }
+        if let Some(origin) = rule.allowed_origins.iter().find(|s| has_wildcards(s)) {
+            return Err(code_error!(
+                InvalidRequest,
+                format!(
+                    "AllowedOrigin \"{}\" can not have more than one wildcard.",
+                    origin
+                )
+            ));
+        }
+        if let Some(header) = rule.allowed_headers.iter().find(|s| has_wildcards(s)) {
+            return Err(code_error!(
+                InvalidRequest,
+                format!(
+                    "AllowedHeader \"{}\" can not have more than one wildcard.",
+                    header
+                )
+            ));
+        }
+    }
+
+    Ok(CORSConfiguration {
+        cors_rules: rules.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// whether a pattern has more than one `*`
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.matches('*').nth(1).is_some()
+}
+
+/// writes a `CORSConfiguration` document
+pub(super) fn write_cors_configuration<W: io::Write>(
+    w: &mut EventWriter<W>,
+    cors_rules: Vec<CORSRule>,
+) -> ::xml::writer::Result<()> {
+    fn elements<W: io::Write>(
+        w: &mut EventWriter<W>,
+        name: &str,
+        values: Vec<String>,
+    ) -> ::xml::writer::Result<()> {
+        w.iter_element(values.into_iter(), |w, value| w.element(name, &value))
+    }
+
+    w.stack("CORSConfiguration", |w| {
+        w.iter_element(cors_rules.into_iter(), |w, rule| {
+            w.stack("CORSRule", |w| {
+                elements(w, "AllowedHeader", rule.allowed_headers.unwrap_or_default())?;
+                elements(w, "AllowedMethod", rule.allowed_methods)?;
+                elements(w, "AllowedOrigin", rule.allowed_origins)?;
+                elements(w, "ExposeHeader", rule.expose_headers.unwrap_or_default())?;
+                w.opt_element("MaxAgeSeconds", rule.max_age_seconds.map(|n| n.to_string()))
+            })
+        })
+    })
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `CORSRule`
+    #[derive(Debug, Deserialize)]
+    pub struct CORSRule {
+        /// AllowedHeader
+        #[serde(rename = "AllowedHeader", default)]
+        pub allowed_headers: Vec<String>,
+        /// AllowedMethod
+        #[serde(rename = "AllowedMethod", default)]
+        pub allowed_methods: Vec<String>,
+        /// AllowedOrigin
+        #[serde(rename = "AllowedOrigin", default)]
+        pub allowed_origins: Vec<String>,
+        /// ExposeHeader
+        #[serde(rename = "ExposeHeader", default)]
+        pub expose_headers: Vec<String>,
+        /// MaxAgeSeconds
+        #[serde(rename = "MaxAgeSeconds")]
+        pub max_age_seconds: Option<i64>,
+    }
+
+    /// `CORSConfiguration`
+    #[derive(Debug, Deserialize)]
+    pub struct CORSConfiguration {
+        /// CORSRule
+        #[serde(rename = "CORSRule", default)]
+        pub cors_rules: Vec<CORSRule>,
+    }
+
+    impl From<CORSRule> for super::CORSRule {
+        fn from(rule: CORSRule) -> Self {
+            let non_empty = |values: Vec<String>| Some(values).filter(|v| !v.is_empty());
+            Self {
+                allowed_headers: non_empty(rule.allowed_headers),
+                allowed_methods: rule.allowed_methods,
+                allowed_origins: rule.allowed_origins,
+                expose_headers: non_empty(rule.expose_headers),
+                max_age_seconds: rule.max_age_seconds,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+    use crate::utils::ResponseExt;
+    use crate::Response;
+
+    use futures::executor::block_on;
+
+    fn extract(body: &str) -> Result<Vec<CORSRule>, S3ErrorCode> {
+        let config = block_on(extract_cors_configuration(Body::from(body.to_owned())));
+        config
+            .map(|config| config.cors_rules)
+            .map_err(|err| err.code())
+    }
+
+    fn config(rules: &str) -> String {
+        format!("<CORSConfiguration>{}</CORSConfiguration>", rules)
+    }
+
+    #[test]
+    fn round_trip() {
+        let body = config(concat!(
+            "<CORSRule><AllowedHeader>*</AllowedHeader><AllowedMethod>GET</AllowedMethod>",
+            "<AllowedMethod>PUT</AllowedMethod><AllowedOrigin>https://*.example.com</AllowedOrigin>",
+            "<ExposeHeader>ETag</ExposeHeader><MaxAgeSeconds>3000</MaxAgeSeconds></CORSRule>",
+            "<CORSRule><AllowedMethod>HEAD</AllowedMethod><AllowedOrigin>*</AllowedOrigin></CORSRule>",
+        ));
+        let rules = extract(&body).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].allowed_headers, Some(vec!["*".to_owned()]));
+        assert_eq!(rules[0].allowed_methods, ["GET", "PUT"]);
+        assert_eq!(rules[0].max_age_seconds, Some(3000));
+        assert_eq!(rules[1].allowed_headers, None);
+
+        let mut res = Response::new(Body::empty());
+        res.set_xml_body(256, |w| write_cors_configuration(w, rules))
+            .unwrap();
+        let written = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        let expected = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>{}", body);
+        assert_eq!(String::from_utf8(written.to_vec()).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid() {
+        let rule = |method: &str, origin: &str, header: &str| {
+            format!(
+                "<CORSRule><AllowedHeader>{}</AllowedHeader><AllowedMethod>{}</AllowedMethod><AllowedOrigin>{}</AllowedOrigin></CORSRule>",
+                header, method, origin
+            )
+        };
+        let cases = vec![
+            (config(""), S3ErrorCode::MalformedXML),
+            (
+                config(&rule("GET", "*", "*").repeat(101)),
+                S3ErrorCode::MalformedXML,
+            ),
+            (
+                config("<CORSRule><AllowedMethod>GET</AllowedMethod></CORSRule>"),
+                S3ErrorCode::MalformedXML,
+            ),
+            (
+                config(&rule("PATCH", "*", "*")),
+                S3ErrorCode::InvalidRequest,
+            ),
+            (
+                config(&rule("GET", "*.*", "*")),
+                S3ErrorCode::InvalidRequest,
+            ),
+            (
+                config(&rule("GET", "*", "x-*-*")),
+                S3ErrorCode::InvalidRequest,
+            ),
+            ("<CORSConfiguration>".to_owned(), S3ErrorCode::MalformedXML),
+        ];
+        for (body, code) in &cases {
+            assert_eq!(extract(body).map(|_| ()), Err(*code), "{}", body);
+        }
+        let most = config(&rule("GET", "*", "*").repeat(100));
+        assert_eq!(extract(&most).map(|rules| rules.len()), Ok(100));
+    }
+}
@@ -1,6 +1,6 @@
 //! [`CreateBucket`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateBucket.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{acl_headers, wrap_internal_error, ReqContext, S3Handler};
 
 use crate::dto::{
     CreateBucketConfiguration, CreateBucketError, CreateBucketOutput, CreateBucketRequest,
@@ -22,8 +22,16 @@ pub struct Handler;
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::PUT);
-        ctx.path.is_bucket()
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        ctx.query_strings.as_ref().map_or(true, |qs| {
+            !qs.contains("versioning")
+                && !qs.contains("tagging")
+                && !qs.contains("acl")
+                && !qs.contains("cors")
+                && !qs.contains("policy")
+                && !qs.contains("object-lock")
+        })
     }
 
     async fn handle(
@@ -40,6 +48,7 @@ impl S3Handler for Handler {
 /// extract operation request
 async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CreateBucketRequest> {
     let bucket = ctx.unwrap_bucket_path();
+    let grants = acl_headers::extract_grants(&ctx.headers, &acl_headers::bucket_grant_headers())?;
 
     let config: Option<self::xml::CreateBucketConfiguration> =
         deserialize_xml_body(ctx.take_body())
@@ -65,6 +74,14 @@ async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CreateBucketRequest> {
     )
     .map_err(|err| invalid_request!("Invalid header: x-amz-bucket-object-lock-enabled", err))?;
 
+    // like S3, the requester gets `FULL_CONTROL` of a bucket created without an ACL,
+    // which tells the storage the owner of the bucket
+    if let Some(ref user) = ctx.canonical_user {
+        if input.acl.is_none() && grants.is_empty() {
+            input.grant_full_control = Some(format!("id=\"{}\"", user.id));
+        }
+    }
+
     Ok(input)
 }
 
@@ -16,8 +16,11 @@ pub struct Handler;
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::DELETE);
-        ctx.path.is_bucket()
+        bool_try!(ctx.method == Method::DELETE);
+        bool_try!(ctx.path.is_bucket());
+        ctx.query_strings.as_ref().map_or(true, |qs| {
+            !qs.contains("tagging") && !qs.contains("cors") && !qs.contains("policy")
+        })
     }
 
     async fn handle(
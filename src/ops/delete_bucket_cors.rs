@@ -0,0 +1,65 @@
+//! [`DeleteBucketCors`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucketCors.html)
+//!
+//! Deleting the CORS rules of a bucket without CORS rules succeeds too.
+
+use super::{ReqContext, S3Handler};
+
+use crate::dto::{DeleteBucketCorsError, DeleteBucketCorsOutput, DeleteBucketCorsRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::{Apply, ResponseExt};
+use crate::{async_trait, Body, Method, Response, StatusCode};
+
+/// `DeleteBucketCors` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::DELETE);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("cors")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.delete_bucket_cors(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<DeleteBucketCorsRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = DeleteBucketCorsRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for DeleteBucketCorsOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        Response::new_with_status(Body::empty(), StatusCode::NO_CONTENT).apply(Ok)
+    }
+}
+
+impl From<DeleteBucketCorsError> for S3Error {
+    fn from(e: DeleteBucketCorsError) -> Self {
+        match e {}
+    }
+}
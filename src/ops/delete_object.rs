@@ -1,6 +1,6 @@
 //! [`DeleteObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{is_mfa_delete_enabled, verify_mfa, wrap_internal_error, ReqContext, S3Handler};
 
 use crate::dto::{DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest};
 use crate::errors::{S3Error, S3Result};
@@ -19,8 +19,11 @@ pub struct Handler;
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::DELETE);
-        ctx.path.is_object()
+        bool_try!(ctx.method == Method::DELETE);
+        bool_try!(ctx.path.is_object());
+        ctx.query_strings
+            .as_ref()
+            .map_or(true, |qs| qs.get("uploadId").is_none())
     }
 
     async fn handle(
@@ -29,6 +32,9 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        if input.version_id.is_some() && is_mfa_delete_enabled(storage, &input.bucket).await? {
+            verify_mfa(ctx).await?;
+        }
         let output = storage.delete_object(input).await;
         output.try_into_response()
     }
@@ -1,27 +1,36 @@
 //! [`DeleteObjects`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObjects.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    content_md5, is_mfa_delete_enabled, verify_mfa, wrap_internal_error, ReqContext, S3Handler,
+};
 
 use crate::dto::{
     Delete, DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, ObjectIdentifier,
 };
 use crate::errors::{S3Error, S3Result};
 use crate::headers::{
-    X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_MFA, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER,
+    CONTENT_MD5, X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_MFA, X_AMZ_REQUEST_CHARGED,
+    X_AMZ_REQUEST_PAYER,
 };
 use crate::output::S3Output;
+use crate::path::KeyEncoding;
 use crate::storage::S3Storage;
-use crate::utils::body::deserialize_xml_body;
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{async_trait, Method, Response};
 
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// Max objects of a request
+pub(crate) const MAX_OBJECTS: usize = 1000;
+
 /// `DeleteObject` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::POST);
+        bool_try!(ctx.method == Method::POST);
         bool_try!(ctx.path.is_bucket());
         let qs = bool_try_some!(ctx.query_strings.as_ref());
         qs.get("delete").is_some()
@@ -32,21 +41,58 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
-        let input = extract(ctx).await?;
+        let mut input = extract(ctx).await?;
+        let deletes_versions = input.delete.objects.iter().any(|o| o.version_id.is_some());
+        if deletes_versions && is_mfa_delete_enabled(storage, &input.bucket).await? {
+            verify_mfa(ctx).await?;
+        }
+        let is_quiet = input.delete.quiet == Some(true);
+        let requested = dedup_objects(&mut input.delete.objects);
         let output = storage.delete_objects(input).await;
-        output.try_into_response()
+        let key_encoding = ctx.key_encoding;
+        output
+            .map(|output| expand_results(output, &requested))
+            .map(|mut output| {
+                // the quiet mode only reports errors
+                if is_quiet {
+                    output.deleted = None;
+                }
+                output
+            })
+            .map(|output| display_keys(output, key_encoding))
+            .try_into_response()
     }
 }
 
 /// extract operation request
+///
+/// The body is checked against `Content-MD5` if it is present.
 pub async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectsRequest> {
     let bucket = ctx.unwrap_bucket_path();
-    let delete: self::xml::Delete = deserialize_xml_body(ctx.take_body())
+    let body = hyper::body::to_bytes(ctx.take_body())
         .await
+        .map_err(|err| invalid_request!("Can not obtain the whole request body.", err))?;
+    if let Some(value) = ctx.headers.get(&*CONTENT_MD5) {
+        content_md5::check(value, &body)?;
+    }
+    let delete: xml::Delete = quick_xml::de::from_reader(&*body)
         .map_err(|err| invalid_request!("Invalid xml format", err))?;
+    if delete.objects.len() > MAX_OBJECTS {
+        return Err(code_error!(
+            MalformedXML,
+            "The XML you provided was not well-formed or did not validate against our published schema"
+        ));
+    }
+
+    let mut delete: Delete = delete.into();
+    for object in &mut delete.objects {
+        if let Cow::Owned(key) = ctx.key_encoding.from_text(&object.key) {
+            object.key = key;
+        }
+    }
 
     let mut input: DeleteObjectsRequest = DeleteObjectsRequest {
-        delete: delete.into(),
+        delete,
         bucket: bucket.into(),
         ..DeleteObjectsRequest::default()
     };
@@ -63,6 +109,102 @@ pub async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectsRequest>
     Ok(input)
 }
 
+/// Removes the repeated `(key, version id)` pairs, and returns the requested objects
+///
+/// Like S3, the storage sees each pair once,
+/// and the response reports each requested object, see [`expand_results`].
+fn dedup_objects(objects: &mut Vec<ObjectIdentifier>) -> Vec<ObjectIdentifier> {
+    let requested = objects.clone();
+    let mut seen: HashSet<(String, Option<String>)> = HashSet::with_capacity(objects.len());
+    objects.retain(|object| seen.insert((object.key.clone(), object.version_id.clone())));
+    requested
+}
+
+/// Reports the results of the storage once per requested object, in the order of the request
+///
+/// A result matches the requested objects with its key and version id,
+/// or all versions of its key if it has no version id.
+/// Results which match no requested object are kept after the others.
+fn expand_results(
+    mut output: DeleteObjectsOutput,
+    requested: &[ObjectIdentifier],
+) -> DeleteObjectsOutput {
+    output.deleted = output.deleted.map(|deleted| {
+        expand(deleted, requested, |d| {
+            (d.key.as_deref(), d.version_id.as_deref())
+        })
+    });
+    output.errors = output.errors.map(|errors| {
+        expand(errors, requested, |e| {
+            (e.key.as_deref(), e.version_id.as_deref())
+        })
+    });
+    output
+}
+
+/// repeats the results of the storage for the requested objects, see [`expand_results`]
+fn expand<T: Clone>(
+    results: Vec<T>,
+    requested: &[ObjectIdentifier],
+    id: impl Fn(&T) -> (Option<&str>, Option<&str>),
+) -> Vec<T> {
+    let mut by_key: HashMap<&str, Vec<usize>> = HashMap::with_capacity(results.len());
+    for (idx, result) in results.iter().enumerate() {
+        if let (Some(key), _) = id(result) {
+            by_key.entry(key).or_default().push(idx);
+        }
+    }
+
+    let mut is_matched = vec![false; results.len()];
+    let mut expanded = Vec::with_capacity(requested.len());
+    for object in requested {
+        let candidates = by_key
+            .get(object.key.as_str())
+            .map_or(&[][..], Vec::as_slice);
+        let mut found = None;
+        for &idx in candidates {
+            let result = results.get(idx);
+            let version_id = result.and_then(|result| id(result).1);
+            if version_id.is_some() && version_id != object.version_id.as_deref() {
+                continue;
+            }
+            if let Some(flag) = is_matched.get_mut(idx) {
+                *flag = true;
+            }
+            found = found.or(result);
+        }
+        if let Some(result) = found {
+            expanded.push(result.clone());
+        }
+    }
+
+    let unmatched = results
+        .into_iter()
+        .zip(is_matched)
+        .filter_map(|(result, is_matched)| if is_matched { None } else { Some(result) });
+    expanded.extend(unmatched);
+    expanded
+}
+
+/// converts the stored keys in the output for display
+fn display_keys(mut output: DeleteObjectsOutput, key_encoding: KeyEncoding) -> DeleteObjectsOutput {
+    if key_encoding == KeyEncoding::Strict {
+        return output;
+    }
+    let display = |key: &mut Option<String>| {
+        if let Some(ref mut key) = *key {
+            *key = key_encoding.display(key, false);
+        }
+    };
+    for deleted in output.deleted.iter_mut().flatten() {
+        display(&mut deleted.key);
+    }
+    for error in output.errors.iter_mut().flatten() {
+        display(&mut error.key);
+    }
+    output
+}
+
 impl S3Output for DeleteObjectsOutput {
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
@@ -116,6 +258,82 @@ impl From<DeleteObjectsError> for S3Error {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::DeletedObject;
+
+    use rusoto_s3::S3Error as ErrorEntry;
+
+    fn object(key: &str, version_id: Option<&str>) -> ObjectIdentifier {
+        ObjectIdentifier {
+            key: key.into(),
+            version_id: version_id.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut objects = vec![
+            object("a", None),
+            object("b", Some("1")),
+            object("a", None),
+            object("b", Some("2")),
+            object("b", Some("1")),
+            object("c", None),
+        ];
+        let requested = dedup_objects(&mut objects);
+        assert_eq!(requested.len(), 6);
+        assert_eq!(
+            objects,
+            [
+                object("a", None),
+                object("b", Some("1")),
+                object("b", Some("2")),
+                object("c", None),
+            ]
+        );
+
+        let deleted = |key: &str, version_id: Option<&str>| DeletedObject {
+            key: Some(key.into()),
+            version_id: version_id.map(Into::into),
+            ..DeletedObject::default()
+        };
+        let output = DeleteObjectsOutput {
+            deleted: Some(vec![
+                deleted("b", Some("2")),
+                deleted("a", None),
+                deleted("b", Some("1")),
+            ]),
+            errors: Some(vec![ErrorEntry {
+                key: Some("c".into()),
+                code: Some("AccessDenied".into()),
+                ..ErrorEntry::default()
+            }]),
+            ..DeleteObjectsOutput::default()
+        };
+        let output = expand_results(output, &requested);
+        let ids: Vec<_> = output
+            .deleted
+            .iter()
+            .flatten()
+            .map(|d| (d.key.as_deref(), d.version_id.as_deref()))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                (Some("a"), None),
+                (Some("b"), Some("1")),
+                (Some("a"), None),
+                (Some("b"), Some("2")),
+                (Some("b"), Some("1")),
+            ]
+        );
+        assert_eq!(output.errors.map(|errors| errors.len()), Some(1));
+    }
+}
+
 mod xml {
     //! Xml repr
 
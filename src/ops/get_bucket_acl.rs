@@ -0,0 +1,84 @@
+//! [`GetBucketAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketAcl.html)
+//!
+//! A missing owner is the requester, see [`owner`](super::owner).
+//! A bucket without grants from the storage has the default ACL, which grants `FULL_CONTROL` to its owner.
+
+use super::{
+    access_control_policy, acl_headers, owner, wrap_internal_error, ReqContext, S3Handler,
+};
+
+use crate::dto::{GetBucketAclError, GetBucketAclOutput, GetBucketAclRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetBucketAcl` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("acl")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_bucket_acl(input).await;
+        let user = ctx.canonical_user.as_ref();
+        output
+            .map(|mut output| {
+                owner::fill_owner(&mut output.owner, user);
+                if output.grants.is_none() {
+                    let grant = acl_headers::owner_full_control(output.owner.as_ref());
+                    output.grants = Some(grant.into_iter().collect());
+                }
+                output
+            })
+            .try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetBucketAclRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = GetBucketAclRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for GetBucketAclOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            let owner = self.owner.unwrap_or_default();
+            let grants = self.grants.unwrap_or_default();
+            res.set_xml_body(4096, |w| {
+                access_control_policy::write_access_control_policy(w, owner, grants)
+            })
+        })
+    }
+}
+
+impl From<GetBucketAclError> for S3Error {
+    fn from(e: GetBucketAclError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,78 @@
+//! [`GetBucketCors`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketCors.html)
+//!
+//! A bucket without CORS rules is answered with `NoSuchCORSConfiguration` (404), like a bucket without tags.
+
+use super::{cors_configuration, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{GetBucketCorsError, GetBucketCorsOutput, GetBucketCorsRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetBucketCors` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("cors")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_bucket_cors(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetBucketCorsRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = GetBucketCorsRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for GetBucketCorsOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        let cors_rules = match self.cors_rules {
+            Some(rules) if !rules.is_empty() => rules,
+            _ => {
+                return Err(code_error!(
+                    NoSuchCORSConfiguration,
+                    "The CORS configuration does not exist"
+                ))
+            }
+        };
+        wrap_internal_error(|res| {
+            res.set_xml_body(4096, |w| {
+                cors_configuration::write_cors_configuration(w, cors_rules)
+            })
+        })
+    }
+}
+
+impl From<GetBucketCorsError> for S3Error {
+    fn from(e: GetBucketCorsError) -> Self {
+        match e {}
+    }
+}
@@ -1,4 +1,7 @@
 //! [`GetBucketLocation`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketLocation.html)
+//!
+//! A bucket whose storage returns no location is in the default region of the service, if it is set.
+//! Like S3, `us-east-1` is served as an empty `<LocationConstraint>`, and so is a bucket without a region.
 
 use super::{wrap_internal_error, ReqContext, S3Handler};
 
@@ -10,13 +13,16 @@ use crate::storage::S3Storage;
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{async_trait, Method, Response};
 
+/// the region which S3 serves as an empty location constraint
+const US_EAST_1: &str = "us-east-1";
+
 /// `GetBucketLocation` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::GET);
+        bool_try!(ctx.method == Method::GET);
         bool_try!(ctx.path.is_bucket());
         let qs = bool_try_some!(ctx.query_strings.as_ref());
         qs.get("location").is_some()
@@ -28,8 +34,16 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        let default_region = ctx.default_region;
         let output = storage.get_bucket_location(input).await;
-        output.try_into_response()
+        output
+            .map(|mut output| {
+                if output.location_constraint.is_none() {
+                    output.location_constraint = default_region.map(ToOwned::to_owned);
+                }
+                output
+            })
+            .try_into_response()
     }
 }
 
@@ -55,9 +69,10 @@ impl S3Output for GetBucketLocationOutput {
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
             res.set_xml_body(4096, |w| {
+                let region = self.location_constraint.as_deref();
                 w.element(
                     "LocationConstraint",
-                    self.location_constraint.as_deref().unwrap_or(""),
+                    region.filter(|&region| region != US_EAST_1).unwrap_or(""),
                 )
             })
         })
@@ -69,3 +84,31 @@ impl From<GetBucketLocationError> for S3Error {
         match e {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn xml() {
+        let body = |location_constraint: Option<&str>| {
+            let output = GetBucketLocationOutput {
+                location_constraint: location_constraint.map(ToOwned::to_owned),
+            };
+            let res = output.try_into_response().unwrap();
+            let body = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+        let expected = |location: &str| {
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?><LocationConstraint>{}</LocationConstraint>",
+                location
+            )
+        };
+        assert_eq!(body(Some("eu-central-1")), expected("eu-central-1"));
+        assert_eq!(body(Some("us-east-1")), expected(""));
+        assert_eq!(body(None), expected(""));
+    }
+}
@@ -0,0 +1,72 @@
+//! [`GetBucketPolicy`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketPolicy.html)
+//!
+//! The policy is answered as the JSON document which was put, with `application/json`.
+//! A bucket without a policy is answered with `NoSuchBucketPolicy` (404).
+
+use super::{ReqContext, S3Handler};
+
+use crate::dto::{GetBucketPolicyError, GetBucketPolicyOutput, GetBucketPolicyRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Body, Method, Response};
+
+/// `GetBucketPolicy` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("policy")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_bucket_policy(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetBucketPolicyRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = GetBucketPolicyRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for GetBucketPolicyOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        let policy = self
+            .policy
+            .ok_or_else(|| code_error!(NoSuchBucketPolicy, "The bucket policy does not exist"))?;
+        let mut res = Response::new(Body::from(policy));
+        res.set_mime(&mime::APPLICATION_JSON)
+            .map_err(|e| internal_error!(e))?;
+        Ok(res)
+    }
+}
+
+impl From<GetBucketPolicyError> for S3Error {
+    fn from(e: GetBucketPolicyError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,70 @@
+//! [`GetBucketTagging`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketTagging.html)
+//!
+//! A bucket without tags is answered with `NoSuchTagSet` (404), which the SDKs expect, instead of an empty tag set.
+
+use super::{tagging, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{GetBucketTaggingError, GetBucketTaggingOutput, GetBucketTaggingRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetBucketTagging` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("tagging")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_bucket_tagging(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetBucketTaggingRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = GetBucketTaggingRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for GetBucketTaggingOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        if self.tag_set.is_empty() {
+            return Err(code_error!(NoSuchTagSet, "The TagSet does not exist"));
+        }
+        wrap_internal_error(|res| {
+            res.set_xml_body(4096, |w| tagging::write_tagging(w, self.tag_set))
+        })
+    }
+}
+
+impl From<GetBucketTaggingError> for S3Error {
+    fn from(e: GetBucketTaggingError) -> Self {
+        match e {}
+    }
+}
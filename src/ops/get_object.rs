@@ -1,7 +1,17 @@
 //! [`GetObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html)
+//!
+//! A `Range` or a `partNumber` is validated here and read by the storage, see [`crate::storages::object_range`].
+//! The response of a partial read is `206 Partial Content` with a `Content-Range`,
+//! and the one of a part has the `x-amz-mp-parts-count` of its object.
+//!
+//! The `If-*` conditions are evaluated against the ETag and the modification time of the output.
+//! A failed `If-Match` or `If-Unmodified-Since` is `PreconditionFailed`, which wins over
+//! a failed `If-None-Match` or `If-Modified-Since`, answered by `304 Not Modified`.
 
+use super::upload_part::parse_part_number;
 use super::{wrap_internal_error, ReqContext, S3Handler};
 
+use crate::conditions::{self, Conditions, Evaluation};
 use crate::dto::{GetObjectError, GetObjectOutput, GetObjectRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::{
@@ -17,17 +27,25 @@ use crate::headers::{
 };
 use crate::output::S3Output;
 use crate::storage::S3Storage;
+use crate::storages::object_range;
 use crate::utils::{time, ResponseExt};
-use crate::{async_trait, Body, Method, Response};
+use crate::{async_trait, Body, Method, Response, StatusCode};
 
 /// `GetObject` handler
-pub struct Handler;
+pub(crate) struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::GET);
-        ctx.path.is_object()
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_object());
+        // `ListParts`, `GetObjectAcl`, `GetObjectRetention` and `GetObjectLegalHold`
+        ctx.query_strings.as_ref().map_or(true, |qs| {
+            qs.get("uploadId").is_none()
+                && !qs.contains("acl")
+                && !qs.contains("retention")
+                && !qs.contains("legal-hold")
+        })
     }
 
     async fn handle(
@@ -36,14 +54,129 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
-        let output = storage.get_object(input).await;
+        let (bucket, key) = (input.bucket.clone(), input.key.clone());
+        let overrides = ResponseOverrides::from_request(&input);
+        let conditions = Conditions::from_headers(&ctx.headers);
+        let mut output = storage.get_object(input).await;
+        if let Ok(ref mut output) = output {
+            match conditions.evaluate(output.e_tag.as_deref(), output.last_modified.as_deref()) {
+                Evaluation::Holds => {}
+                Evaluation::NotModified => {
+                    return conditions::not_modified(
+                        output.e_tag.take(),
+                        output.last_modified.take(),
+                    )
+                }
+                Evaluation::Failed => return Err(conditions::precondition_failed()),
+            }
+            if let Some(policy) = storage.get_bucket_serving_policy(&bucket).await? {
+                policy.apply(&key, &mut output.cache_control, &mut output.content_type);
+            }
+            overrides.apply_to(output);
+        }
         output.try_into_response()
     }
 }
 
-/// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetObjectRequest> {
-    let (bucket, key) = ctx.unwrap_object_path();
+/// `response-*` query strings, which override the response headers
+struct ResponseOverrides {
+    /// `response-cache-control`
+    cache_control: Option<String>,
+    /// `response-content-disposition`
+    content_disposition: Option<String>,
+    /// `response-content-encoding`
+    content_encoding: Option<String>,
+    /// `response-content-language`
+    content_language: Option<String>,
+    /// `response-content-type`
+    content_type: Option<String>,
+    /// `response-expires`
+    expires: Option<String>,
+}
+
+impl ResponseOverrides {
+    /// copies the overrides from the request
+    fn from_request(input: &GetObjectRequest) -> Self {
+        Self {
+            cache_control: input.response_cache_control.clone(),
+            content_disposition: input.response_content_disposition.clone(),
+            content_encoding: input.response_content_encoding.clone(),
+            content_language: input.response_content_language.clone(),
+            content_type: input.response_content_type.clone(),
+            expires: input.response_expires.clone(),
+        }
+    }
+
+    /// replaces the fields of `output`
+    fn apply_to(self, output: &mut GetObjectOutput) {
+        fn replace(field: &mut Option<String>, value: Option<String>) {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        replace(&mut output.cache_control, self.cache_control);
+        replace(&mut output.content_disposition, self.content_disposition);
+        replace(&mut output.content_encoding, self.content_encoding);
+        replace(&mut output.content_language, self.content_language);
+        replace(&mut output.content_type, self.content_type);
+        replace(&mut output.expires, self.expires);
+    }
+}
+
+/// Extracts a `GetObjectRequest` from a `GET` request
+///
+/// ```
+/// use s3_server::dto::{ByteStream, GetObjectOutput};
+/// use s3_server::ops::{get_object, ReqContext};
+/// use s3_server::S3Output;
+///
+/// use hyper::{Body, Method, Request, StatusCode};
+///
+/// let req = Request::builder()
+///     .method(Method::GET)
+///     .uri("http://localhost/bucket/key?response-content-type=text%2Fplain")
+///     .header("range", "bytes=0-4")
+///     .body(Body::empty())
+///     .unwrap();
+///
+/// let mut ctx = ReqContext::new(&req, Body::empty()).unwrap();
+/// let input = get_object::extract(&mut ctx).unwrap();
+/// assert_eq!(input.range.as_deref(), Some("bytes=0-4"));
+/// assert_eq!(input.response_content_type.as_deref(), Some("text/plain"));
+///
+/// let req = Request::builder()
+///     .method(Method::GET)
+///     .uri("http://localhost/bucket")
+///     .body(Body::empty())
+///     .unwrap();
+/// let mut ctx = ReqContext::new(&req, Body::empty()).unwrap();
+/// assert!(get_object::extract(&mut ctx).is_err());
+///
+/// let output = GetObjectOutput {
+///     body: Some(ByteStream::from(b"hello".to_vec())),
+///     content_length: Some(5),
+///     ..GetObjectOutput::default()
+/// };
+/// let res = output.try_into_response().unwrap();
+/// assert_eq!(res.status(), StatusCode::OK);
+/// assert_eq!(res.headers()["content-length"], "5");
+///
+/// let output = GetObjectOutput {
+///     body: Some(ByteStream::from(b"hell".to_vec())),
+///     content_length: Some(4),
+///     content_range: Some("bytes 0-3/5".to_owned()),
+///     ..GetObjectOutput::default()
+/// };
+/// let res = output.try_into_response().unwrap();
+/// assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+/// assert_eq!(res.headers()["content-range"], "bytes 0-3/5");
+/// ```
+///
+/// # Errors
+/// Returns an `Err` if the request is invalid, such as a path which is not an object,
+/// a malformed `Range` or several ranges
+pub fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetObjectRequest> {
+    let (bucket, key) = ctx.object_path()?;
 
     let mut input = GetObjectRequest {
         bucket: bucket.into(),
@@ -57,6 +190,9 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetObjectRequest> {
     h.assign_str(IF_NONE_MATCH, &mut input.if_none_match);
     h.assign_str(IF_UNMODIFIED_SINCE, &mut input.if_unmodified_since);
     h.assign_str(RANGE, &mut input.range);
+    if let Some(ref range) = input.range {
+        let _range = object_range::parse_range(range)?;
+    }
     h.assign_str(
         &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
         &mut input.sse_customer_algorithm,
@@ -71,6 +207,33 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetObjectRequest> {
     );
     h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
 
+    if let Some(ref qs) = ctx.query_strings {
+        qs.assign_str("response-cache-control", &mut input.response_cache_control);
+        qs.assign_str(
+            "response-content-disposition",
+            &mut input.response_content_disposition,
+        );
+        qs.assign_str(
+            "response-content-encoding",
+            &mut input.response_content_encoding,
+        );
+        qs.assign_str(
+            "response-content-language",
+            &mut input.response_content_language,
+        );
+        qs.assign_str("response-content-type", &mut input.response_content_type);
+        qs.assign_str("response-expires", &mut input.response_expires);
+        qs.assign_str("versionId", &mut input.version_id);
+        if let Some(part_number) = qs.get("partNumber") {
+            input.part_number = Some(parse_part_number(part_number)?);
+        }
+    }
+    if input.part_number.is_some() && input.range.is_some() {
+        return Err(invalid_request!(
+            "Cannot specify both Range header and partNumber query parameter"
+        ));
+    }
+
     Ok(input)
 }
 
@@ -82,6 +245,9 @@ impl S3Output for GetObjectOutput {
                 self.delete_marker.map(|b| b.to_string()),
             )?;
 
+            if self.content_range.is_some() {
+                res.set_status(StatusCode::PARTIAL_CONTENT);
+            }
             res.set_optional_header(ACCEPT_RANGES, self.accept_ranges)?;
 
             res.set_optional_header(&*X_AMZ_EXPIRATION, self.expiration)?;
@@ -0,0 +1,75 @@
+//! [`GetObjectLegalHold`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectLegalHold.html)
+//!
+//! An object without a legal hold is answered with `ObjectLockConfigurationNotFoundError` (404).
+
+use super::{object_lock, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{GetObjectLegalHoldError, GetObjectLegalHoldOutput, GetObjectLegalHoldRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_REQUEST_PAYER};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetObjectLegalHold` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("legal-hold")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_object_legal_hold(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetObjectLegalHoldRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+
+    let mut input = GetObjectLegalHoldRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        ..GetObjectLegalHoldRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    if let Some(ref qs) = ctx.query_strings {
+        input.version_id = qs.get("versionId").map(ToOwned::to_owned);
+    }
+
+    Ok(input)
+}
+
+impl S3Output for GetObjectLegalHoldOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        let legal_hold = self.legal_hold.ok_or_else(object_lock::not_found)?;
+        wrap_internal_error(|res| {
+            res.set_xml_body(128, |w| object_lock::write_legal_hold(w, legal_hold))
+        })
+    }
+}
+
+impl From<GetObjectLegalHoldError> for S3Error {
+    fn from(e: GetObjectLegalHoldError) -> Self {
+        match e {}
+    }
+}
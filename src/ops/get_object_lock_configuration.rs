@@ -0,0 +1,73 @@
+//! [`GetObjectLockConfiguration`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectLockConfiguration.html)
+//!
+//! A bucket without Object Lock is answered with `ObjectLockConfigurationNotFoundError` (404).
+
+use super::{object_lock, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{
+    GetObjectLockConfigurationError, GetObjectLockConfigurationOutput,
+    GetObjectLockConfigurationRequest,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetObjectLockConfiguration` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("object-lock")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_object_lock_configuration(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetObjectLockConfigurationRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = GetObjectLockConfigurationRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for GetObjectLockConfigurationOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        let config = self
+            .object_lock_configuration
+            .ok_or_else(object_lock::not_found)?;
+        wrap_internal_error(|res| {
+            res.set_xml_body(256, |w| object_lock::write_configuration(w, config))
+        })
+    }
+}
+
+impl From<GetObjectLockConfigurationError> for S3Error {
+    fn from(e: GetObjectLockConfigurationError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,75 @@
+//! [`GetObjectRetention`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectRetention.html)
+//!
+//! An object without a retention is answered with `ObjectLockConfigurationNotFoundError` (404).
+
+use super::{object_lock, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{GetObjectRetentionError, GetObjectRetentionOutput, GetObjectRetentionRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_REQUEST_PAYER};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetObjectRetention` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("retention")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_object_retention(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetObjectRetentionRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+
+    let mut input = GetObjectRetentionRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        ..GetObjectRetentionRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    if let Some(ref qs) = ctx.query_strings {
+        input.version_id = qs.get("versionId").map(ToOwned::to_owned);
+    }
+
+    Ok(input)
+}
+
+impl S3Output for GetObjectRetentionOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        let retention = self.retention.ok_or_else(object_lock::not_found)?;
+        wrap_internal_error(|res| {
+            res.set_xml_body(256, |w| object_lock::write_retention(w, retention))
+        })
+    }
+}
+
+impl From<GetObjectRetentionError> for S3Error {
+    fn from(e: GetObjectRetentionError) -> Self {
+        match e {}
+    }
+}
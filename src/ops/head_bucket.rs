@@ -1,14 +1,14 @@
 //! [`HeadBucket`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadBucket.html)
 
-use super::{ReqContext, S3Handler};
+use super::{wrap_internal_error, ReqContext, S3Handler};
 
 use crate::dto::{HeadBucketError, HeadBucketOutput, HeadBucketRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
-use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::headers::{X_AMZ_BUCKET_REGION, X_AMZ_EXPECTED_BUCKET_OWNER};
 use crate::output::S3Output;
 use crate::storage::S3Storage;
-use crate::utils::Apply;
-use crate::{async_trait, Body, Method, Response};
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
 
 /// `HeadBucket` handler
 pub struct Handler;
@@ -16,7 +16,7 @@ pub struct Handler;
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::HEAD);
+        bool_try!(ctx.method == Method::HEAD);
         ctx.path.is_bucket()
     }
 
@@ -51,7 +51,10 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<HeadBucketRequest> {
 
 impl S3Output for HeadBucketOutput {
     fn try_into_response(self) -> S3Result<Response> {
-        Response::new(Body::empty()).apply(Ok)
+        wrap_internal_error(|res| {
+            res.set_optional_header(&*X_AMZ_BUCKET_REGION, self.bucket_region)?;
+            Ok(())
+        })
     }
 }
 
@@ -1,7 +1,12 @@
 //! [`HeadObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadObject.html)
+//!
+//! The `If-*` conditions are evaluated like the ones of [`GetObject`](super::get_object).
+//! With a `partNumber`, the `Content-Length` is the one of the part.
 
+use super::upload_part::parse_part_number;
 use super::{wrap_internal_error, ReqContext, S3Handler};
 
+use crate::conditions::{self, Conditions, Evaluation};
 use crate::dto::{HeadObjectError, HeadObjectOutput, HeadObjectRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::{
@@ -26,7 +31,7 @@ pub struct Handler;
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::HEAD);
+        bool_try!(ctx.method == Method::HEAD);
         ctx.path.is_object()
     }
 
@@ -36,7 +41,27 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
-        let output = storage.head_object(input).await;
+        let (bucket, key) = (input.bucket.clone(), input.key.clone());
+        let conditions = Conditions::from_headers(&ctx.headers);
+        let mut output = storage.head_object(input).await;
+        if let Ok(ref mut output) = output {
+            match conditions.evaluate(output.e_tag.as_deref(), output.last_modified.as_deref()) {
+                Evaluation::Holds => {}
+                Evaluation::NotModified => {
+                    return conditions::not_modified(
+                        output.e_tag.take(),
+                        output.last_modified.take(),
+                    )
+                }
+                Evaluation::Failed => return Err(conditions::precondition_failed()),
+            }
+            if let Some(policy) = storage.get_bucket_serving_policy(&bucket).await? {
+                policy.apply(&key, &mut output.cache_control, &mut output.content_type);
+            }
+            if output.content_type.is_none() {
+                output.content_type = Some(mime::APPLICATION_OCTET_STREAM.as_ref().to_owned());
+            }
+        }
         output.try_into_response()
     }
 }
@@ -71,6 +96,13 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<HeadObjectRequest> {
     );
     h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
 
+    if let Some(ref qs) = ctx.query_strings {
+        input.version_id = qs.get("versionId").map(ToOwned::to_owned);
+        if let Some(part_number) = qs.get("partNumber") {
+            input.part_number = Some(parse_part_number(part_number)?);
+        }
+    }
+
     Ok(input)
 }
 
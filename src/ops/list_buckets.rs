@@ -1,21 +1,27 @@
 //! [`ListBuckets`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html)
+//!
+//! Buckets are listed in lexicographic order of their names, whatever order the storage returns.
+//! A page ends after `max-buckets` buckets and its `ContinuationToken` resumes the listing
+//! after the last bucket of the page.
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{list_query, owner, wrap_internal_error, ReqContext, S3Handler};
 
-use crate::dto::{ListBucketsError, ListBucketsOutput, ListBucketsRequest};
+use crate::dto::{Bucket, ListBucketsError, ListBucketsOutput, ListBucketsRequest};
 use crate::errors::{S3Error, S3Result};
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{async_trait, Method, Response};
 
+use std::convert::TryFrom;
+
 /// `ListBuckets` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::GET);
+        bool_try!(ctx.method == Method::GET);
         ctx.path.is_root()
     }
 
@@ -25,22 +31,136 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        let start_after = input
+            .continuation_token
+            .as_deref()
+            .map(decode_token)
+            .transpose()?;
+        let max_buckets = input.max_buckets.and_then(|n| usize::try_from(n).ok());
         let output = storage.list_buckets(input).await;
-        output.try_into_response()
+        let user = ctx.canonical_user.as_ref();
+        output
+            .map(|mut output| {
+                owner::fill_owner(&mut output.owner, user);
+                output
+            })
+            .map(|output| Page {
+                output,
+                start_after,
+                max_buckets,
+            })
+            .try_into_response()
     }
 }
 
 /// extract operation request
-fn extract(_: &mut ReqContext<'_>) -> S3Result<ListBucketsRequest> {
-    Ok(ListBucketsRequest)
+fn extract(ctx: &ReqContext<'_>) -> S3Result<ListBucketsRequest> {
+    let mut input = ListBucketsRequest::default();
+    if let Some(ref q) = ctx.query_strings {
+        input.continuation_token = list_query::continuation_token(q)?;
+        input.max_buckets = list_query::max_buckets(q)?;
+    }
+    Ok(input)
+}
+
+/// encodes the name of the last bucket of a page
+fn encode_token(name: &str) -> String {
+    base64::encode_config(name, base64::URL_SAFE_NO_PAD)
+}
+
+/// decodes the name of the last bucket of the previous page
+fn decode_token(token: &str) -> S3Result<String> {
+    base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| {
+            code_error!(
+                InvalidArgument,
+                "The continuation token provided is incorrect"
+            )
+        })
+}
+
+/// the output of a storage and the page of it to respond with
+///
+/// A storage may return all buckets or only the requested page,
+/// because cutting a page twice does not change it.
+struct Page {
+    /// the output of the storage
+    output: ListBucketsOutput,
+    /// the name of the last bucket of the previous page
+    start_after: Option<String>,
+    /// `max-buckets`
+    max_buckets: Option<usize>,
+}
+
+impl Page {
+    /// sorts the buckets and cuts the page
+    ///
+    /// Returns the buckets of the page and the continuation token of the next page.
+    fn cut(
+        buckets: Vec<Bucket>,
+        start_after: Option<&str>,
+        max_buckets: Option<usize>,
+    ) -> S3Result<(Vec<Bucket>, Option<String>)> {
+        let mut named = Vec::with_capacity(buckets.len());
+        for bucket in buckets {
+            match bucket.name {
+                Some(ref name) => named.push((name.clone(), bucket)),
+                None => {
+                    return Err(internal_error!(
+                        "ListBuckets returned a bucket without a name"
+                    ))
+                }
+            }
+        }
+
+        // backends usually return sorted buckets already
+        let is_sorted = named
+            .iter()
+            .zip(named.iter().skip(1))
+            .all(|(lhs, rhs)| lhs.0 <= rhs.0);
+        if !is_sorted {
+            named.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        }
+
+        if let Some(start_after) = start_after {
+            let pos = named
+                .iter()
+                .position(|&(ref name, _)| name.as_str() > start_after)
+                .unwrap_or(named.len());
+            named = named.split_off(pos);
+        }
+
+        let mut continuation_token = None;
+        if let Some(max_buckets) = max_buckets {
+            if named.len() > max_buckets {
+                named.truncate(max_buckets);
+                continuation_token = named.last().map(|&(ref name, _)| encode_token(name));
+            }
+        }
+
+        let buckets = named.into_iter().map(|(_, bucket)| bucket).collect();
+        Ok((buckets, continuation_token))
+    }
 }
 
-impl S3Output for ListBucketsOutput {
+impl S3Output for Page {
     fn try_into_response(self) -> S3Result<Response> {
+        let (buckets, continuation_token) = match self.output.buckets {
+            Some(buckets) => {
+                let start_after = self.start_after.as_deref();
+                let (buckets, token) = Self::cut(buckets, start_after, self.max_buckets)?;
+                (Some(buckets), token)
+            }
+            None => (None, None),
+        };
+        let owner = self.output.owner;
+
         wrap_internal_error(|res| {
             res.set_xml_body(4096, |w| {
                 w.stack("ListBucketsOutput", |w| {
-                    w.opt_stack("Buckets", self.buckets, |w, buckets| {
+                    w.opt_stack("Buckets", buckets, |w, buckets| {
                         for bucket in buckets {
                             w.stack("Bucket", |w| {
                                 w.opt_element("CreationDate", bucket.creation_date)?;
@@ -50,10 +170,11 @@ impl S3Output for ListBucketsOutput {
                         Ok(())
                     })?;
 
-                    w.opt_stack("Owner", self.owner, |w, owner| {
+                    w.opt_stack("Owner", owner, |w, owner| {
                         w.opt_element("DisplayName", owner.display_name)?;
                         w.opt_element("ID", owner.id)
                     })?;
+                    w.opt_element("ContinuationToken", continuation_token)?;
                     Ok(())
                 })
             })
@@ -66,3 +187,53 @@ impl From<ListBucketsError> for S3Error {
         match e {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buckets(names: &[&str]) -> Vec<Bucket> {
+        names
+            .iter()
+            .map(|&name| Bucket {
+                creation_date: None,
+                name: Some(name.to_owned()),
+            })
+            .collect()
+    }
+
+    fn names(buckets: &[Bucket]) -> Vec<&str> {
+        buckets.iter().filter_map(|b| b.name.as_deref()).collect()
+    }
+
+    #[test]
+    fn cut() {
+        let all = buckets(&["qwe", "asd", "zxc", "asd-2"]);
+
+        let (page, token) = Page::cut(all.clone(), None, None).unwrap();
+        assert_eq!(names(&page), ["asd", "asd-2", "qwe", "zxc"]);
+        assert_eq!(token, None);
+
+        let (page, token) = Page::cut(all.clone(), None, Some(3)).unwrap();
+        assert_eq!(names(&page), ["asd", "asd-2", "qwe"]);
+        let start_after = decode_token(&token.unwrap()).unwrap();
+        assert_eq!(start_after, "qwe");
+
+        let (page, token) = Page::cut(all.clone(), Some(&start_after), Some(3)).unwrap();
+        assert_eq!(names(&page), ["zxc"]);
+        assert_eq!(token, None);
+
+        // a page which is already cut by the storage
+        let (page, token) = Page::cut(page, Some(&start_after), Some(3)).unwrap();
+        assert_eq!(names(&page), ["zxc"]);
+        assert_eq!(token, None);
+
+        let (page, _) = Page::cut(all, Some("zzz"), None).unwrap();
+        assert!(page.is_empty());
+
+        let nameless = vec![Bucket::default()];
+        let _ = Page::cut(nameless, None, None).unwrap_err();
+
+        let _ = decode_token("!").unwrap_err();
+    }
+}
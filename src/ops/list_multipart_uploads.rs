@@ -0,0 +1,217 @@
+//! [`ListMultipartUploads`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListMultipartUploads.html)
+//!
+//! `GET /bucket?uploads` lists the uploads which are neither completed nor aborted,
+//! so that orphaned uploads can be found and aborted.
+
+use super::{display_listed_keys, list_query, owner, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{
+    ListMultipartUploadsError, ListMultipartUploadsOutput, ListMultipartUploadsRequest,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::path::KeyEncoding;
+use crate::storage::S3Storage;
+use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::{async_trait, Method, Response};
+
+use std::borrow::Cow;
+
+/// `ListMultipartUploads` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("uploads")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.list_multipart_uploads(input).await;
+        let key_encoding = ctx.key_encoding;
+        let user = ctx.canonical_user.as_ref();
+        output
+            .map(|mut output| {
+                for upload in output.uploads.iter_mut().flatten() {
+                    owner::fill_initiator(&mut upload.initiator, user);
+                    owner::fill_owner(&mut upload.owner, user);
+                }
+                display_keys(output, key_encoding)
+            })
+            .try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<ListMultipartUploadsRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = ListMultipartUploadsRequest {
+        bucket: bucket.into(),
+        ..ListMultipartUploadsRequest::default()
+    };
+
+    if let Some(ref q) = ctx.query_strings {
+        input.delimiter = list_query::key_text(q, "delimiter");
+        input.encoding_type = list_query::encoding_type(q)?;
+        input.key_marker = list_query::key_text(q, "key-marker");
+        input.max_uploads = list_query::max_uploads(q)?;
+        input.prefix = list_query::key_text(q, "prefix");
+        input.upload_id_marker = list_query::key_text(q, "upload-id-marker");
+    }
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    let key_encoding = ctx.key_encoding;
+    for text in vec![
+        &mut input.delimiter,
+        &mut input.prefix,
+        &mut input.key_marker,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if let Cow::Owned(stored) = key_encoding.from_text(text) {
+            *text = stored;
+        }
+    }
+
+    Ok(input)
+}
+
+/// converts the stored keys in the output for display
+fn display_keys(
+    mut output: ListMultipartUploadsOutput,
+    key_encoding: KeyEncoding,
+) -> ListMultipartUploadsOutput {
+    let keys = output
+        .uploads
+        .iter_mut()
+        .flatten()
+        .map(|upload| &mut upload.key)
+        .chain(
+            output
+                .common_prefixes
+                .iter_mut()
+                .flatten()
+                .map(|common_prefix| &mut common_prefix.prefix),
+        )
+        .chain(vec![
+            &mut output.prefix,
+            &mut output.delimiter,
+            &mut output.key_marker,
+            &mut output.next_key_marker,
+        ]);
+    display_listed_keys(key_encoding, output.encoding_type.as_deref(), keys);
+    output
+}
+
+impl S3Output for ListMultipartUploadsOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_xml_body(4096, |w| {
+                w.stack("ListMultipartUploadsResult", |w| {
+                    w.opt_element("Bucket", self.bucket)?;
+                    w.element("KeyMarker", self.key_marker.as_deref().unwrap_or(""))?;
+                    w.element(
+                        "UploadIdMarker",
+                        self.upload_id_marker.as_deref().unwrap_or(""),
+                    )?;
+                    w.opt_element("NextKeyMarker", self.next_key_marker)?;
+                    w.opt_element("NextUploadIdMarker", self.next_upload_id_marker)?;
+                    w.opt_element("Delimiter", self.delimiter)?;
+                    w.opt_element("Prefix", self.prefix)?;
+                    w.opt_element("MaxUploads", self.max_uploads.map(|n| n.to_string()))?;
+                    w.opt_element("EncodingType", self.encoding_type)?;
+                    w.opt_element("IsTruncated", self.is_truncated.map(|b| b.to_string()))?;
+                    for upload in self.uploads.into_iter().flatten() {
+                        w.stack("Upload", |w| {
+                            w.opt_element("Key", upload.key)?;
+                            w.opt_element("UploadId", upload.upload_id)?;
+                            w.opt_stack("Initiator", upload.initiator, |w, initiator| {
+                                w.opt_element("ID", initiator.id)?;
+                                w.opt_element("DisplayName", initiator.display_name)
+                            })?;
+                            w.opt_stack("Owner", upload.owner, |w, owner| {
+                                w.opt_element("ID", owner.id)?;
+                                w.opt_element("DisplayName", owner.display_name)
+                            })?;
+                            w.opt_element("StorageClass", upload.storage_class)?;
+                            w.opt_element("Initiated", upload.initiated)
+                        })?;
+                    }
+                    // each prefix has its own element
+                    let common_prefixes = self.common_prefixes.into_iter().flatten();
+                    w.iter_element(common_prefixes, |w, common_prefix| {
+                        w.stack("CommonPrefixes", |w| {
+                            w.opt_element("Prefix", common_prefix.prefix)
+                        })
+                    })
+                })
+            })
+        })
+    }
+}
+
+impl From<ListMultipartUploadsError> for S3Error {
+    fn from(e: ListMultipartUploadsError) -> Self {
+        match e {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::{CommonPrefix, MultipartUpload};
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn xml() {
+        let output = ListMultipartUploadsOutput {
+            bucket: Some("asd".to_owned()),
+            delimiter: Some("/".to_owned()),
+            is_truncated: Some(true),
+            max_uploads: Some(2),
+            next_key_marker: Some("photos/".to_owned()),
+            uploads: Some(vec![MultipartUpload {
+                initiated: Some("2021-06-01T10:00:00.000Z".to_owned()),
+                key: Some("qwe".to_owned()),
+                storage_class: Some("STANDARD".to_owned()),
+                upload_id: Some("upload".to_owned()),
+                ..MultipartUpload::default()
+            }]),
+            common_prefixes: Some(vec![CommonPrefix {
+                prefix: Some("photos/".to_owned()),
+            }]),
+            ..ListMultipartUploadsOutput::default()
+        };
+        let res = output.try_into_response().unwrap();
+        let body = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        let expected = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+            "<ListMultipartUploadsResult>",
+            "<Bucket>asd</Bucket><KeyMarker></KeyMarker><UploadIdMarker></UploadIdMarker>",
+            "<NextKeyMarker>photos/</NextKeyMarker><Delimiter>/</Delimiter>",
+            "<MaxUploads>2</MaxUploads><IsTruncated>true</IsTruncated>",
+            "<Upload><Key>qwe</Key><UploadId>upload</UploadId><StorageClass>STANDARD</StorageClass>",
+            "<Initiated>2021-06-01T10:00:00.000Z</Initiated></Upload>",
+            "<CommonPrefixes><Prefix>photos/</Prefix></CommonPrefixes>",
+            "</ListMultipartUploadsResult>",
+        );
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap(), expected);
+    }
+}
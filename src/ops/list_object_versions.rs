@@ -0,0 +1,409 @@
+//! [`ListObjectVersions`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectVersions.html)
+
+use super::{display_listed_keys, list_query, owner, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{
+    ListObjectVersionsError, ListObjectVersionsOutput, ListObjectVersionsRequest, ListedVersion,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
+use crate::output::S3Output;
+use crate::path::KeyEncoding;
+use crate::storage::S3Storage;
+use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::{async_trait, Method, Response};
+
+use std::borrow::Cow;
+
+/// `ListObjectVersions` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("versions")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.list_object_versions(input).await;
+        let key_encoding = ctx.key_encoding;
+        let user = ctx.canonical_user.as_ref();
+        output
+            .map(|mut output| {
+                for entry in output.versions.iter_mut().flatten() {
+                    let owner = match *entry {
+                        ListedVersion::Version(ref mut version) => &mut version.owner,
+                        ListedVersion::DeleteMarker(ref mut marker) => &mut marker.owner,
+                    };
+                    owner::fill_owner(owner, user);
+                }
+                display_keys(output, key_encoding)
+            })
+            .try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<ListObjectVersionsRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = ListObjectVersionsRequest {
+        bucket: bucket.into(),
+
+        ..ListObjectVersionsRequest::default()
+    };
+
+    if let Some(ref q) = ctx.query_strings {
+        input.delimiter = list_query::key_text(q, "delimiter");
+        input.encoding_type = list_query::encoding_type(q)?;
+        input.key_marker = list_query::key_text(q, "key-marker");
+        input.max_keys = list_query::max_keys(q)?;
+        input.prefix = list_query::key_text(q, "prefix");
+        input.version_id_marker = list_query::key_text(q, "version-id-marker");
+    }
+
+    if input.version_id_marker.is_some() && input.key_marker.is_none() {
+        return Err(code_error!(
+            InvalidArgument,
+            "A version-id marker cannot be specified without a key marker."
+        ));
+    }
+
+    ctx.headers.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    let key_encoding = ctx.key_encoding;
+    for text in vec![
+        &mut input.delimiter,
+        &mut input.prefix,
+        &mut input.key_marker,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if let Cow::Owned(stored) = key_encoding.from_text(text) {
+            *text = stored;
+        }
+    }
+
+    Ok(input)
+}
+
+/// converts the stored keys in the output for display
+fn display_keys(
+    mut output: ListObjectVersionsOutput,
+    key_encoding: KeyEncoding,
+) -> ListObjectVersionsOutput {
+    let keys = output
+        .versions
+        .iter_mut()
+        .flatten()
+        .map(|entry| match *entry {
+            ListedVersion::Version(ref mut version) => &mut version.key,
+            ListedVersion::DeleteMarker(ref mut marker) => &mut marker.key,
+        })
+        .chain(
+            output
+                .common_prefixes
+                .iter_mut()
+                .flatten()
+                .map(|common_prefix| &mut common_prefix.prefix),
+        )
+        .chain(vec![
+            &mut output.prefix,
+            &mut output.delimiter,
+            &mut output.key_marker,
+            &mut output.next_key_marker,
+        ]);
+    display_listed_keys(key_encoding, output.encoding_type.as_deref(), keys);
+    output
+}
+
+impl S3Output for ListObjectVersionsOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_xml_body(4096, |w| {
+                w.stack("ListVersionsResult", |w| {
+                    w.element("Name", self.name.as_deref().unwrap_or(""))?;
+                    w.element("Prefix", self.prefix.as_deref().unwrap_or(""))?;
+                    w.element("KeyMarker", self.key_marker.as_deref().unwrap_or(""))?;
+                    w.element(
+                        "VersionIdMarker",
+                        self.version_id_marker.as_deref().unwrap_or(""),
+                    )?;
+                    w.opt_element("NextKeyMarker", self.next_key_marker)?;
+                    w.opt_element("NextVersionIdMarker", self.next_version_id_marker)?;
+                    w.opt_element("MaxKeys", self.max_keys.map(|k| k.to_string()))?;
+                    w.opt_element("Delimiter", self.delimiter)?;
+                    w.opt_element("EncodingType", self.encoding_type)?;
+                    w.opt_element("IsTruncated", self.is_truncated.map(|b| b.to_string()))?;
+                    for entry in self.versions.into_iter().flatten() {
+                        match entry {
+                            ListedVersion::Version(version) => w.stack("Version", |w| {
+                                w.opt_element("Key", version.key)?;
+                                w.opt_element("VersionId", version.version_id)?;
+                                w.opt_element(
+                                    "IsLatest",
+                                    version.is_latest.map(|b| b.to_string()),
+                                )?;
+                                w.opt_element("LastModified", version.last_modified)?;
+                                w.opt_element("ETag", version.e_tag)?;
+                                w.opt_element("Size", version.size.map(|s| s.to_string()))?;
+                                w.opt_stack("Owner", version.owner, |w, owner| {
+                                    w.opt_element("ID", owner.id)?;
+                                    w.opt_element("DisplayName", owner.display_name)?;
+                                    Ok(())
+                                })?;
+                                w.opt_element("StorageClass", version.storage_class)
+                            })?,
+                            ListedVersion::DeleteMarker(marker) => {
+                                w.stack("DeleteMarker", |w| {
+                                    w.opt_element("Key", marker.key)?;
+                                    w.opt_element("VersionId", marker.version_id)?;
+                                    w.opt_element(
+                                        "IsLatest",
+                                        marker.is_latest.map(|b| b.to_string()),
+                                    )?;
+                                    w.opt_element("LastModified", marker.last_modified)?;
+                                    w.opt_stack("Owner", marker.owner, |w, owner| {
+                                        w.opt_element("ID", owner.id)?;
+                                        w.opt_element("DisplayName", owner.display_name)?;
+                                        Ok(())
+                                    })
+                                })?
+                            }
+                        }
+                    }
+                    // each prefix has its own element
+                    let common_prefixes = self.common_prefixes.into_iter().flatten();
+                    w.iter_element(common_prefixes, |w, common_prefix| {
+                        w.stack("CommonPrefixes", |w| {
+                            w.opt_element("Prefix", common_prefix.prefix)
+                        })
+                    })
+                })
+            })
+        })
+    }
+}
+
+impl From<ListObjectVersionsError> for S3Error {
+    fn from(e: ListObjectVersionsError) -> Self {
+        match e {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::{DeleteMarkerEntry, ObjectVersion};
+    use crate::errors::S3ErrorCode;
+    use crate::storages::versions;
+    use crate::{Body, Request};
+
+    use futures::executor::block_on;
+
+    fn version(version_id: &str, last_modified: &str, e_tag: &str, size: i64) -> ListedVersion {
+        ListedVersion::Version(ObjectVersion {
+            e_tag: Some(format!("\"{}\"", e_tag)),
+            last_modified: Some(last_modified.to_owned()),
+            size: Some(size),
+            storage_class: Some("STANDARD".to_owned()),
+            version_id: Some(version_id.to_owned()),
+            ..ObjectVersion::default()
+        })
+    }
+
+    fn delete_marker(version_id: &str, last_modified: &str) -> ListedVersion {
+        ListedVersion::DeleteMarker(DeleteMarkerEntry {
+            last_modified: Some(last_modified.to_owned()),
+            version_id: Some(version_id.to_owned()),
+            ..DeleteMarkerEntry::default()
+        })
+    }
+
+    /// a key with three versions and a delete marker between two unversioned keys
+    fn fixture() -> Vec<(String, Vec<ListedVersion>)> {
+        vec![
+            (
+                "apple.txt".to_owned(),
+                vec![version(
+                    "null",
+                    "2021-06-01T10:00:00.000Z",
+                    "d41d8cd98f00b204e9800998ecf8427e",
+                    0,
+                )],
+            ),
+            (
+                "photo.jpg".to_owned(),
+                vec![
+                    version(
+                        "3/L4kqtJl40Nr8X8gdRQBpUMLUo",
+                        "2021-06-04T10:00:00.000Z",
+                        "fba9dede5f27731c9771645a39863328",
+                        434_234,
+                    ),
+                    delete_marker(
+                        "QUpfdndhfd8438MNFDN93jdnJFkdmqnh893",
+                        "2021-06-03T10:00:00.000Z",
+                    ),
+                    version(
+                        "UIORUnfndfhnw89493jJFJ",
+                        "2021-06-02T10:00:00.000Z",
+                        "fba9dede5f27731c9771645a39863328",
+                        434_234,
+                    ),
+                    version(
+                        "null",
+                        "2021-06-01T10:00:00.000Z",
+                        "b5f4b2ae1ccc0d7a4ac8ae2bd2c0e4c6",
+                        166_434,
+                    ),
+                ],
+            ),
+            (
+                "zebra.txt".to_owned(),
+                vec![version(
+                    "Rb_l2T8UHDkFEwCgJjhlgPOZC0qJ.vpD",
+                    "2021-06-05T10:00:00.000Z",
+                    "3858f62230ac3c915f300c664312c11f",
+                    6,
+                )],
+            ),
+        ]
+    }
+
+    fn page(key_marker: Option<&str>, version_id_marker: Option<&str>) -> ListObjectVersionsOutput {
+        let input = ListObjectVersionsRequest {
+            bucket: "asd".to_owned(),
+            key_marker: key_marker.map(ToOwned::to_owned),
+            version_id_marker: version_id_marker.map(ToOwned::to_owned),
+            max_keys: Some(1),
+            ..ListObjectVersionsRequest::default()
+        };
+        versions::list_versions(input, fixture()).unwrap()
+    }
+
+    fn render(output: ListObjectVersionsOutput) -> String {
+        let res = output.try_into_response().unwrap();
+        let body = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    /// the pages of the fixture with `max-keys=1`
+    ///
+    /// Their shape follows the responses in the S3 API reference:
+    /// the element order, the empty markers of the first page, and the next markers which point
+    /// into the middle of `photo.jpg`. The version ids are in the style of S3.
+    const PAGES: &[&str] = &[
+        concat!(
+            "<ListVersionsResult>",
+            "<Name>asd</Name><Prefix></Prefix><KeyMarker></KeyMarker><VersionIdMarker></VersionIdMarker>",
+            "<NextKeyMarker>apple.txt</NextKeyMarker><NextVersionIdMarker>null</NextVersionIdMarker><MaxKeys>1</MaxKeys><IsTruncated>true</IsTruncated>",
+            "<Version><Key>apple.txt</Key><VersionId>null</VersionId><IsLatest>true</IsLatest><LastModified>2021-06-01T10:00:00.000Z</LastModified><ETag>\"d41d8cd98f00b204e9800998ecf8427e\"</ETag><Size>0</Size><StorageClass>STANDARD</StorageClass></Version>",
+            "</ListVersionsResult>",
+        ),
+        concat!(
+            "<ListVersionsResult>",
+            "<Name>asd</Name><Prefix></Prefix><KeyMarker>apple.txt</KeyMarker><VersionIdMarker>null</VersionIdMarker>",
+            "<NextKeyMarker>photo.jpg</NextKeyMarker><NextVersionIdMarker>3/L4kqtJl40Nr8X8gdRQBpUMLUo</NextVersionIdMarker><MaxKeys>1</MaxKeys><IsTruncated>true</IsTruncated>",
+            "<Version><Key>photo.jpg</Key><VersionId>3/L4kqtJl40Nr8X8gdRQBpUMLUo</VersionId><IsLatest>true</IsLatest><LastModified>2021-06-04T10:00:00.000Z</LastModified><ETag>\"fba9dede5f27731c9771645a39863328\"</ETag><Size>434234</Size><StorageClass>STANDARD</StorageClass></Version>",
+            "</ListVersionsResult>",
+        ),
+        concat!(
+            "<ListVersionsResult>",
+            "<Name>asd</Name><Prefix></Prefix><KeyMarker>photo.jpg</KeyMarker><VersionIdMarker>3/L4kqtJl40Nr8X8gdRQBpUMLUo</VersionIdMarker>",
+            "<NextKeyMarker>photo.jpg</NextKeyMarker><NextVersionIdMarker>QUpfdndhfd8438MNFDN93jdnJFkdmqnh893</NextVersionIdMarker><MaxKeys>1</MaxKeys><IsTruncated>true</IsTruncated>",
+            "<DeleteMarker><Key>photo.jpg</Key><VersionId>QUpfdndhfd8438MNFDN93jdnJFkdmqnh893</VersionId><IsLatest>false</IsLatest><LastModified>2021-06-03T10:00:00.000Z</LastModified></DeleteMarker>",
+            "</ListVersionsResult>",
+        ),
+        concat!(
+            "<ListVersionsResult>",
+            "<Name>asd</Name><Prefix></Prefix><KeyMarker>photo.jpg</KeyMarker><VersionIdMarker>QUpfdndhfd8438MNFDN93jdnJFkdmqnh893</VersionIdMarker>",
+            "<NextKeyMarker>photo.jpg</NextKeyMarker><NextVersionIdMarker>UIORUnfndfhnw89493jJFJ</NextVersionIdMarker><MaxKeys>1</MaxKeys><IsTruncated>true</IsTruncated>",
+            "<Version><Key>photo.jpg</Key><VersionId>UIORUnfndfhnw89493jJFJ</VersionId><IsLatest>false</IsLatest><LastModified>2021-06-02T10:00:00.000Z</LastModified><ETag>\"fba9dede5f27731c9771645a39863328\"</ETag><Size>434234</Size><StorageClass>STANDARD</StorageClass></Version>",
+            "</ListVersionsResult>",
+        ),
+        concat!(
+            "<ListVersionsResult>",
+            "<Name>asd</Name><Prefix></Prefix><KeyMarker>photo.jpg</KeyMarker><VersionIdMarker>UIORUnfndfhnw89493jJFJ</VersionIdMarker>",
+            "<NextKeyMarker>photo.jpg</NextKeyMarker><NextVersionIdMarker>null</NextVersionIdMarker><MaxKeys>1</MaxKeys><IsTruncated>true</IsTruncated>",
+            "<Version><Key>photo.jpg</Key><VersionId>null</VersionId><IsLatest>false</IsLatest><LastModified>2021-06-01T10:00:00.000Z</LastModified><ETag>\"b5f4b2ae1ccc0d7a4ac8ae2bd2c0e4c6\"</ETag><Size>166434</Size><StorageClass>STANDARD</StorageClass></Version>",
+            "</ListVersionsResult>",
+        ),
+        concat!(
+            "<ListVersionsResult>",
+            "<Name>asd</Name><Prefix></Prefix><KeyMarker>photo.jpg</KeyMarker><VersionIdMarker>null</VersionIdMarker>",
+            "<MaxKeys>1</MaxKeys><IsTruncated>false</IsTruncated>",
+            "<Version><Key>zebra.txt</Key><VersionId>Rb_l2T8UHDkFEwCgJjhlgPOZC0qJ.vpD</VersionId><IsLatest>true</IsLatest><LastModified>2021-06-05T10:00:00.000Z</LastModified><ETag>\"3858f62230ac3c915f300c664312c11f\"</ETag><Size>6</Size><StorageClass>STANDARD</StorageClass></Version>",
+            "</ListVersionsResult>",
+        ),
+    ];
+
+    #[test]
+    fn golden_pages() {
+        let mut markers: (Option<String>, Option<String>) = (None, None);
+        for (i, &expected) in PAGES.iter().enumerate() {
+            let output = page(markers.0.as_deref(), markers.1.as_deref());
+            let is_truncated = output.is_truncated == Some(true);
+            let next = (
+                output.next_key_marker.clone(),
+                output.next_version_id_marker.clone(),
+            );
+            let expected = format!(r#"<?xml version="1.0" encoding="UTF-8"?>{}"#, expected);
+            assert_eq!(render(output), expected, "page {}", i);
+            assert_eq!(
+                is_truncated,
+                i.saturating_add(1) < PAGES.len(),
+                "page {}",
+                i
+            );
+            markers = next;
+        }
+    }
+
+    #[test]
+    fn markers() {
+        // `key-marker` alone skips all the entries of the key
+        let output = page(Some("photo.jpg"), None);
+        let keys: Vec<_> = output
+            .versions
+            .iter()
+            .flatten()
+            .map(|entry| match *entry {
+                ListedVersion::Version(ref version) => version.key.as_deref(),
+                ListedVersion::DeleteMarker(ref marker) => marker.key.as_deref(),
+            })
+            .collect();
+        assert_eq!(keys, [Some("zebra.txt")]);
+
+        let input = ListObjectVersionsRequest {
+            bucket: "asd".to_owned(),
+            key_marker: Some("photo.jpg".to_owned()),
+            version_id_marker: Some("missing".to_owned()),
+            ..ListObjectVersionsRequest::default()
+        };
+        let err = versions::list_versions(input, fixture()).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::InvalidArgument);
+
+        // `version-id-marker` requires `key-marker`
+        let mut req = Request::new(Body::empty());
+        *req.uri_mut() = "http://localhost/asd?versions&version-id-marker=null"
+            .parse()
+            .unwrap();
+        let ctx = ReqContext::new(&req, Body::empty()).unwrap();
+        let err = extract(&ctx).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::InvalidArgument);
+    }
+}
@@ -1,26 +1,30 @@
 //! [`ListObjects`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjects.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{display_listed_keys, list_query, owner, wrap_internal_error, ReqContext, S3Handler};
 
 use crate::dto::{ListObjectsError, ListObjectsOutput, ListObjectsRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::X_AMZ_REQUEST_PAYER;
 use crate::output::S3Output;
+use crate::path::KeyEncoding;
 use crate::storage::S3Storage;
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{async_trait, Method, Response};
 
+use std::borrow::Cow;
+
 /// `ListObjects` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::GET);
+        bool_try!(ctx.method == Method::GET);
         bool_try!(ctx.path.is_bucket());
+        // `?uploads` lists the multipart uploads of the bucket instead
         match ctx.query_strings {
             None => true,
-            Some(ref qs) => qs.get("list-type").is_none(),
+            Some(ref qs) => qs.get("list-type").is_none() && !qs.contains("uploads"),
         }
     }
 
@@ -31,7 +35,16 @@ impl S3Handler for Handler {
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
         let output = storage.list_objects(input).await;
-        output.try_into_response()
+        let key_encoding = ctx.key_encoding;
+        let user = ctx.canonical_user.as_ref();
+        output
+            .map(|mut output| {
+                for content in output.contents.iter_mut().flatten() {
+                    owner::fill_owner(&mut content.owner, user);
+                }
+                display_keys(output, key_encoding)
+            })
+            .try_into_response()
     }
 }
 
@@ -45,20 +58,53 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<ListObjectsRequest> {
     };
 
     if let Some(ref q) = ctx.query_strings {
-        q.assign_str("delimiter", &mut input.delimiter);
-        q.assign_str("encoding-type", &mut input.encoding_type);
-        q.assign_str("marker", &mut input.marker);
-        q.assign("max-keys", &mut input.max_keys)
-            .map_err(|err| invalid_request!("Invalid query: max-keys", err))?;
-        q.assign_str("prefix", &mut input.prefix);
+        input.delimiter = list_query::key_text(q, "delimiter");
+        input.encoding_type = list_query::encoding_type(q)?;
+        input.marker = list_query::key_text(q, "marker");
+        input.max_keys = list_query::max_keys(q)?;
+        input.prefix = list_query::key_text(q, "prefix");
     }
 
     ctx.headers
         .assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
 
+    let key_encoding = ctx.key_encoding;
+    for text in vec![&mut input.delimiter, &mut input.prefix, &mut input.marker]
+        .into_iter()
+        .flatten()
+    {
+        if let Cow::Owned(stored) = key_encoding.from_text(text) {
+            *text = stored;
+        }
+    }
+
     Ok(input)
 }
 
+/// converts the stored keys in the output for display
+fn display_keys(mut output: ListObjectsOutput, key_encoding: KeyEncoding) -> ListObjectsOutput {
+    let keys = output
+        .contents
+        .iter_mut()
+        .flatten()
+        .map(|content| &mut content.key)
+        .chain(
+            output
+                .common_prefixes
+                .iter_mut()
+                .flatten()
+                .map(|common_prefix| &mut common_prefix.prefix),
+        )
+        .chain(vec![
+            &mut output.prefix,
+            &mut output.delimiter,
+            &mut output.marker,
+            &mut output.next_marker,
+        ]);
+    display_listed_keys(key_encoding, output.encoding_type.as_deref(), keys);
+    output
+}
+
 impl S3Output for ListObjectsOutput {
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
@@ -87,8 +133,10 @@ impl S3Output for ListObjectsOutput {
                     w.opt_element("Prefix", self.prefix)?;
                     w.opt_element("Delimiter", self.delimiter)?;
                     w.opt_element("MaxKeys", self.max_keys.map(|k| k.to_string()))?;
-                    w.opt_stack("CommonPrefixes", self.common_prefixes, |w, prefixes| {
-                        w.iter_element(prefixes.into_iter(), |w, common_prefix| {
+                    // each prefix has its own element
+                    let common_prefixes = self.common_prefixes.into_iter().flatten();
+                    w.iter_element(common_prefixes, |w, common_prefix| {
+                        w.stack("CommonPrefixes", |w| {
                             w.opt_element("Prefix", common_prefix.prefix)
                         })
                     })?;
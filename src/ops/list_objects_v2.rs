@@ -1,24 +1,28 @@
 //! [`ListObjectsV2`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{display_listed_keys, list_query, owner, wrap_internal_error, ReqContext, S3Handler};
 
 use crate::dto::{ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::X_AMZ_REQUEST_PAYER;
 use crate::output::S3Output;
+use crate::path::KeyEncoding;
 use crate::storage::S3Storage;
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{async_trait, Method, Response};
 
+use std::borrow::Cow;
+
 /// `ListObjectsV2` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::GET);
+        bool_try!(ctx.method == Method::GET);
         bool_try!(ctx.path.is_bucket());
         let qs = bool_try_some!(ctx.query_strings.as_ref());
+        bool_try!(!qs.contains("uploads"));
         let list_type = bool_try_some!(qs.get("list-type"));
         list_type == "2"
     }
@@ -29,8 +33,21 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        // owners are listed only if they are fetched
+        let user = ctx
+            .canonical_user
+            .as_ref()
+            .filter(|_| input.fetch_owner == Some(true));
         let output = storage.list_objects_v2(input).await;
-        output.try_into_response()
+        let key_encoding = ctx.key_encoding;
+        output
+            .map(|mut output| {
+                for content in output.contents.iter_mut().flatten() {
+                    owner::fill_owner(&mut content.owner, user);
+                }
+                display_keys(output, key_encoding)
+            })
+            .try_into_response()
     }
 }
 
@@ -50,23 +67,58 @@ fn extract(
     };
 
     if let Some(ref q) = ctx.query_strings {
-        q.assign_str("continuation-token", &mut input.continuation_token);
-        q.assign_str("delimiter", &mut input.delimiter);
-        q.assign_str("encoding-type", &mut input.encoding_type);
-        q.assign("fetch-owner", &mut input.fetch_owner)
-            .map_err(|err| invalid_request!("Invalid query: fetch-owner", err))?;
-        q.assign("max-keys", &mut input.max_keys)
-            .map_err(|err| invalid_request!("Invalid query: max-keys", err))?;
-        q.assign_str("prefix", &mut input.prefix);
-        q.assign_str("start-after", &mut input.start_after);
+        input.continuation_token = list_query::continuation_token(q)?;
+        input.delimiter = list_query::key_text(q, "delimiter");
+        input.encoding_type = list_query::encoding_type(q)?;
+        input.fetch_owner = list_query::fetch_owner(q)?;
+        input.max_keys = list_query::max_keys(q)?;
+        input.prefix = list_query::key_text(q, "prefix");
+        input.start_after = list_query::key_text(q, "start-after");
     }
 
     ctx.headers
         .assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
 
+    let key_encoding = ctx.key_encoding;
+    for text in vec![
+        &mut input.delimiter,
+        &mut input.prefix,
+        &mut input.start_after,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if let Cow::Owned(stored) = key_encoding.from_text(text) {
+            *text = stored;
+        }
+    }
+
     Ok(input)
 }
 
+/// converts the stored keys in the output for display
+fn display_keys(mut output: ListObjectsV2Output, key_encoding: KeyEncoding) -> ListObjectsV2Output {
+    let keys = output
+        .contents
+        .iter_mut()
+        .flatten()
+        .map(|content| &mut content.key)
+        .chain(
+            output
+                .common_prefixes
+                .iter_mut()
+                .flatten()
+                .map(|common_prefix| &mut common_prefix.prefix),
+        )
+        .chain(vec![
+            &mut output.prefix,
+            &mut output.delimiter,
+            &mut output.start_after,
+        ]);
+    display_listed_keys(key_encoding, output.encoding_type.as_deref(), keys);
+    output
+}
+
 impl S3Output for ListObjectsV2Output {
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
@@ -93,13 +145,15 @@ impl S3Output for ListObjectsV2Output {
                     w.opt_element("Prefix", self.prefix)?;
                     w.opt_element("Delimiter", self.delimiter)?;
                     w.opt_element("MaxKeys", self.max_keys.map(|k| k.to_string()))?;
-                    w.opt_stack("CommonPrefixes", self.common_prefixes, |w, prefixes| {
-                        w.iter_element(prefixes.into_iter(), |w, common_prefix| {
+                    // each prefix has its own element
+                    let common_prefixes = self.common_prefixes.into_iter().flatten();
+                    w.iter_element(common_prefixes, |w, common_prefix| {
+                        w.stack("CommonPrefixes", |w| {
                             w.opt_element("Prefix", common_prefix.prefix)
                         })
                     })?;
                     w.opt_element("EncodingType", self.encoding_type)?;
-                    w.opt_element("KeyCount", self.max_keys.map(|k| k.to_string()))?;
+                    w.opt_element("KeyCount", self.key_count.map(|k| k.to_string()))?;
                     w.opt_element("ContinuationToken", self.continuation_token)?;
                     w.opt_element("NextContinuationToken", self.next_continuation_token)?;
                     w.opt_element("StartAfter", self.start_after)?;
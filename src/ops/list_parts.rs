@@ -0,0 +1,236 @@
+//! [`ListParts`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListParts.html)
+//!
+//! Parts are listed in ascending order of their numbers, whatever order the storage returns.
+//! A page starts after `part-number-marker` and ends after `max-parts` parts.
+//! Its `NextPartNumberMarker` resumes the listing after the last part of the page.
+
+use super::{list_query, owner, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{ListPartsError, ListPartsOutput, ListPartsRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::{async_trait, Method, Response};
+
+use std::convert::TryFrom;
+
+/// default and max `max-parts`
+pub(crate) const MAX_PARTS: i64 = 1000;
+
+/// `ListParts` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::GET);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("uploadId").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let part_number_marker = input.part_number_marker.unwrap_or(0);
+        let max_parts = input.max_parts.unwrap_or(MAX_PARTS).min(MAX_PARTS);
+        let output = storage.list_parts(input).await;
+        let user = ctx.canonical_user.as_ref();
+        output
+            .map(|mut output| {
+                owner::fill_initiator(&mut output.initiator, user);
+                owner::fill_owner(&mut output.owner, user);
+                cut(output, part_number_marker, max_parts)
+            })
+            .try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<ListPartsRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let upload_id = ctx.unwrap_qs("uploadId").to_owned();
+
+    let mut input = ListPartsRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        upload_id,
+        ..ListPartsRequest::default()
+    };
+
+    if let Some(ref q) = ctx.query_strings {
+        input.max_parts = list_query::max_parts(q)?;
+        input.part_number_marker = list_query::part_number_marker(q)?;
+    }
+
+    let h = &ctx.headers;
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+
+    Ok(input)
+}
+
+/// sorts the parts and cuts the page
+///
+/// A storage may return all parts or only the requested page,
+/// because cutting a page twice does not change it.
+fn cut(mut output: ListPartsOutput, part_number_marker: i64, max_parts: i64) -> ListPartsOutput {
+    let mut parts = output.parts.take().unwrap_or_default();
+
+    // backends usually return sorted parts already
+    let is_sorted = parts
+        .iter()
+        .zip(parts.iter().skip(1))
+        .all(|(lhs, rhs)| lhs.part_number <= rhs.part_number);
+    if !is_sorted {
+        parts.sort_by_key(|part| part.part_number);
+    }
+    parts.retain(|part| part.part_number.map_or(false, |n| n > part_number_marker));
+
+    let limit = usize::try_from(max_parts).unwrap_or(0);
+    let is_truncated = output.is_truncated == Some(true) || parts.len() > limit;
+    parts.truncate(limit);
+
+    output.next_part_number_marker = if is_truncated {
+        parts.last().and_then(|part| part.part_number)
+    } else {
+        None
+    };
+    output.is_truncated = Some(is_truncated);
+    output.part_number_marker = Some(part_number_marker);
+    output.max_parts = Some(max_parts);
+    output.parts = Some(parts);
+    output
+}
+
+impl S3Output for ListPartsOutput {
+    fn try_into_response(mut self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            let request_charged = self.request_charged.take();
+            res.set_optional_header(&*X_AMZ_REQUEST_CHARGED, request_charged)?;
+
+            res.set_xml_body(4096, |w| {
+                w.stack("ListPartsResult", |w| {
+                    w.opt_element("Bucket", self.bucket)?;
+                    w.opt_element("Key", self.key)?;
+                    w.opt_element("UploadId", self.upload_id)?;
+                    w.opt_element(
+                        "PartNumberMarker",
+                        self.part_number_marker.map(|n| n.to_string()),
+                    )?;
+                    w.opt_element(
+                        "NextPartNumberMarker",
+                        self.next_part_number_marker.map(|n| n.to_string()),
+                    )?;
+                    w.opt_element("MaxParts", self.max_parts.map(|n| n.to_string()))?;
+                    w.opt_element("IsTruncated", self.is_truncated.map(|b| b.to_string()))?;
+                    for part in self.parts.into_iter().flatten() {
+                        w.stack("Part", |w| {
+                            w.opt_element("PartNumber", part.part_number.map(|n| n.to_string()))?;
+                            w.opt_element("LastModified", part.last_modified)?;
+                            w.opt_element("ETag", part.e_tag)?;
+                            w.opt_element("Size", part.size.map(|s| s.to_string()))
+                        })?;
+                    }
+                    w.opt_stack("Initiator", self.initiator, |w, initiator| {
+                        w.opt_element("ID", initiator.id)?;
+                        w.opt_element("DisplayName", initiator.display_name)
+                    })?;
+                    w.opt_stack("Owner", self.owner, |w, owner| {
+                        w.opt_element("ID", owner.id)?;
+                        w.opt_element("DisplayName", owner.display_name)
+                    })?;
+                    w.opt_element("StorageClass", self.storage_class)
+                })
+            })
+        })
+    }
+}
+
+impl From<ListPartsError> for S3Error {
+    fn from(e: ListPartsError) -> Self {
+        match e {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::Part;
+
+    use futures::executor::block_on;
+
+    fn output(part_numbers: &[i64]) -> ListPartsOutput {
+        let parts = part_numbers
+            .iter()
+            .map(|&part_number| Part {
+                e_tag: Some(format!("\"etag-{}\"", part_number)),
+                last_modified: Some("2021-06-01T10:00:00.000Z".to_owned()),
+                part_number: Some(part_number),
+                size: Some(5_242_880),
+            })
+            .collect();
+        ListPartsOutput {
+            bucket: Some("asd".to_owned()),
+            key: Some("qwe".to_owned()),
+            upload_id: Some("upload".to_owned()),
+            parts: Some(parts),
+            storage_class: Some("STANDARD".to_owned()),
+            ..ListPartsOutput::default()
+        }
+    }
+
+    fn part_numbers(output: &ListPartsOutput) -> Vec<i64> {
+        let parts = output.parts.iter().flatten();
+        parts.filter_map(|part| part.part_number).collect()
+    }
+
+    #[test]
+    fn pages() {
+        let page = cut(output(&[3, 1, 5, 2, 4]), 0, 2);
+        assert_eq!(part_numbers(&page), [1, 2]);
+        assert_eq!(page.is_truncated, Some(true));
+        assert_eq!(page.next_part_number_marker, Some(2));
+
+        let page = cut(output(&[3, 1, 5, 2, 4]), 2, 2);
+        assert_eq!(part_numbers(&page), [3, 4]);
+        assert_eq!(page.next_part_number_marker, Some(4));
+
+        let page = cut(output(&[3, 1, 5, 2, 4]), 4, 2);
+        assert_eq!(part_numbers(&page), [5]);
+        assert_eq!(page.is_truncated, Some(false));
+        assert_eq!(page.next_part_number_marker, None);
+
+        // cutting a page again does not change it
+        let again = cut(cut(output(&[1, 2, 3]), 1, 1), 1, 1);
+        assert_eq!(part_numbers(&again), [2]);
+        assert_eq!(again.next_part_number_marker, Some(2));
+    }
+
+    #[test]
+    fn xml() {
+        let res = cut(output(&[2, 1]), 0, 1).try_into_response().unwrap();
+        let body = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        let expected = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+            "<ListPartsResult>",
+            "<Bucket>asd</Bucket><Key>qwe</Key><UploadId>upload</UploadId>",
+            "<PartNumberMarker>0</PartNumberMarker><NextPartNumberMarker>1</NextPartNumberMarker>",
+            "<MaxParts>1</MaxParts><IsTruncated>true</IsTruncated>",
+            "<Part><PartNumber>1</PartNumber><LastModified>2021-06-01T10:00:00.000Z</LastModified>",
+            "<ETag>\"etag-1\"</ETag><Size>5242880</Size></Part>",
+            "<StorageClass>STANDARD</StorageClass>",
+            "</ListPartsResult>",
+        );
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap(), expected);
+    }
+}
@@ -0,0 +1,305 @@
+//! Query parameters shared by the listing operations
+//!
+//! + [`ListBuckets`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html)
+//! + [`ListObjects`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjects.html)
+//! + [`ListObjectsV2`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
+//! + [`ListObjectVersions`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectVersions.html)
+//! + [`ListParts`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListParts.html)
+//! + [`ListMultipartUploads`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListMultipartUploads.html)
+//!
+//! Zero-length and whitespace-only values are handled like S3 does:
+//!
+//! | parameter                 | empty             | whitespace        |
+//! | ------------------------- | ----------------- | ----------------- |
+//! | `prefix`, `delimiter`     | absent            | literal           |
+//! | `marker`, `start-after`   | absent            | literal           |
+//! | `key-marker`              | absent            | literal           |
+//! | `version-id-marker`       | absent            | literal           |
+//! | `upload-id-marker`        | absent            | literal           |
+//! | `continuation-token`      | `InvalidArgument` | literal           |
+//! | `encoding-type`           | absent            | `InvalidArgument` |
+//! | `max-keys`, `fetch-owner` | `InvalidArgument` | `InvalidArgument` |
+//! | `max-buckets`             | `InvalidArgument` | `InvalidArgument` |
+//! | `max-parts`               | `InvalidArgument` | `InvalidArgument` |
+//! | `part-number-marker`      | `InvalidArgument` | `InvalidArgument` |
+//! | `max-uploads`             | `InvalidArgument` | `InvalidArgument` |
+//!
+//! A missing parameter is always absent.
+//! Literal whitespace is a valid key prefix, marker or token,
+//! while typed values must parse without surrounding whitespace.
+
+use crate::data_structures::OrderedQs;
+use crate::errors::S3Result;
+
+/// the max value of `max-keys`, 2147483647
+const MAX_KEYS_LIMIT: i64 = 0x7fff_ffff;
+
+/// the max value of `max-buckets`
+const MAX_BUCKETS_LIMIT: i64 = 10000;
+
+/// Extracts a key, a key prefix or a marker: `prefix`, `delimiter`, `marker`, `start-after`,
+/// `key-marker`, `version-id-marker` or `upload-id-marker`
+pub fn key_text(qs: &OrderedQs, name: &str) -> Option<String> {
+    let mut text = None;
+    qs.assign_non_empty_str(name, &mut text);
+    text
+}
+
+/// Extracts `continuation-token`
+/// # Errors
+/// Returns an `Err` if the token is empty
+pub fn continuation_token(qs: &OrderedQs) -> S3Result<Option<String>> {
+    match qs.get("continuation-token") {
+        Some("") => Err(code_error!(
+            InvalidArgument,
+            "The continuation token provided is incorrect"
+        )),
+        token => Ok(token.map(ToOwned::to_owned)),
+    }
+}
+
+/// Extracts `encoding-type`
+/// # Errors
+/// Returns an `Err` if the encoding is not `url`
+pub fn encoding_type(qs: &OrderedQs) -> S3Result<Option<String>> {
+    match qs.get("encoding-type") {
+        None | Some("") => Ok(None),
+        Some("url") => Ok(Some("url".to_owned())),
+        Some(_) => Err(code_error!(
+            InvalidArgument,
+            "Invalid Encoding Method specified in Request"
+        )),
+    }
+}
+
+/// Extracts `max-keys`
+/// # Errors
+/// Returns an `Err` if the value is not an integer between 0 and 2147483647
+pub fn max_keys(qs: &OrderedQs) -> S3Result<Option<i64>> {
+    let mut max_keys: Option<i64> = None;
+    qs.assign("max-keys", &mut max_keys).map_err(|err| {
+        code_error!(
+            InvalidArgument,
+            "Provided max-keys not an integer or within integer range",
+            err
+        )
+    })?;
+    match max_keys {
+        Some(n) if n > MAX_KEYS_LIMIT => Err(code_error!(
+            InvalidArgument,
+            "Provided max-keys not an integer or within integer range"
+        )),
+        Some(n) if n < 0 => Err(code_error!(
+            InvalidArgument,
+            "Argument maxKeys must be an integer between 0 and 2147483647"
+        )),
+        _ => Ok(max_keys),
+    }
+}
+
+/// Extracts `max-buckets`
+/// # Errors
+/// Returns an `Err` if the value is not an integer between 1 and 10000
+pub fn max_buckets(qs: &OrderedQs) -> S3Result<Option<i64>> {
+    const MESSAGE: &str = "Argument max-buckets must be an integer between 1 and 10000";
+    let mut max_buckets: Option<i64> = None;
+    qs.assign("max-buckets", &mut max_buckets)
+        .map_err(|err| code_error!(InvalidArgument, MESSAGE, err))?;
+    match max_buckets {
+        Some(n) if !(1..=MAX_BUCKETS_LIMIT).contains(&n) => {
+            Err(code_error!(InvalidArgument, MESSAGE))
+        }
+        _ => Ok(max_buckets),
+    }
+}
+
+/// Extracts `max-parts`
+/// # Errors
+/// Returns an `Err` if the value is not an integer between 0 and 2147483647
+pub fn max_parts(qs: &OrderedQs) -> S3Result<Option<i64>> {
+    integer(
+        qs,
+        "max-parts",
+        "Argument max-parts must be an integer between 0 and 2147483647",
+    )
+}
+
+/// Extracts `part-number-marker`
+/// # Errors
+/// Returns an `Err` if the value is not an integer between 0 and 2147483647
+pub fn part_number_marker(qs: &OrderedQs) -> S3Result<Option<i64>> {
+    integer(
+        qs,
+        "part-number-marker",
+        "Argument part-number-marker must be an integer between 0 and 2147483647",
+    )
+}
+
+/// Extracts `max-uploads`
+/// # Errors
+/// Returns an `Err` if the value is not an integer between 0 and 2147483647
+pub fn max_uploads(qs: &OrderedQs) -> S3Result<Option<i64>> {
+    integer(
+        qs,
+        "max-uploads",
+        "Argument max-uploads must be an integer between 0 and 2147483647",
+    )
+}
+
+/// extracts a non-negative integer, which fails with `message`
+fn integer(qs: &OrderedQs, name: &str, message: &'static str) -> S3Result<Option<i64>> {
+    let mut n: Option<i64> = None;
+    qs.assign(name, &mut n)
+        .map_err(|err| code_error!(InvalidArgument, message, err))?;
+    match n {
+        Some(n) if !(0..=MAX_KEYS_LIMIT).contains(&n) => Err(code_error!(InvalidArgument, message)),
+        _ => Ok(n),
+    }
+}
+
+/// Extracts `fetch-owner`
+/// # Errors
+/// Returns an `Err` if the value is not `true` or `false`
+pub fn fetch_owner(qs: &OrderedQs) -> S3Result<Option<bool>> {
+    let mut fetch_owner = None;
+    qs.assign("fetch-owner", &mut fetch_owner).map_err(|err| {
+        code_error!(
+            InvalidArgument,
+            "Invalid Argument: fetch-owner must be true or false",
+            err
+        )
+    })?;
+    Ok(fetch_owner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+
+    /// the extracted value, or the message of `InvalidArgument`
+    type Outcome = Result<Option<String>, &'static str>;
+
+    fn extract(name: &str, qs: &OrderedQs) -> Result<Option<String>, String> {
+        let ret = match name {
+            "prefix" | "delimiter" | "marker" | "start-after" | "key-marker"
+            | "version-id-marker" | "upload-id-marker" => Ok(key_text(qs, name)),
+            "continuation-token" => continuation_token(qs),
+            "encoding-type" => encoding_type(qs),
+            "max-keys" => max_keys(qs).map(|n| n.map(|n| n.to_string())),
+            "max-buckets" => max_buckets(qs).map(|n| n.map(|n| n.to_string())),
+            "fetch-owner" => fetch_owner(qs).map(|b| b.map(|b| b.to_string())),
+            "max-parts" => max_parts(qs).map(|n| n.map(|n| n.to_string())),
+            "part-number-marker" => part_number_marker(qs).map(|n| n.map(|n| n.to_string())),
+            "max-uploads" => max_uploads(qs).map(|n| n.map(|n| n.to_string())),
+            _ => panic!("unknown parameter: {}", name),
+        };
+        ret.map_err(|err| {
+            assert_eq!(err.code(), S3ErrorCode::InvalidArgument, "{}", name);
+            err.into_xml_response().message.unwrap_or_default()
+        })
+    }
+
+    #[test]
+    fn matrix() {
+        const NOT_INTEGER: &str = "Provided max-keys not an integer or within integer range";
+        const BAD_FETCH_OWNER: &str = "Invalid Argument: fetch-owner must be true or false";
+        const BAD_ENCODING: &str = "Invalid Encoding Method specified in Request";
+        const BAD_MAX_BUCKETS: &str = "Argument max-buckets must be an integer between 1 and 10000";
+        const BAD_TOKEN: &str = "The continuation token provided is incorrect";
+        const BAD_MAX_PARTS: &str =
+            "Argument max-parts must be an integer between 0 and 2147483647";
+        const BAD_PART_NUMBER_MARKER: &str =
+            "Argument part-number-marker must be an integer between 0 and 2147483647";
+        const BAD_MAX_UPLOADS: &str =
+            "Argument max-uploads must be an integer between 0 and 2147483647";
+
+        let literal = |s: &str| -> Outcome { Ok(Some(s.to_owned())) };
+
+        // (parameter, empty, whitespace, a valid value)
+        let cases: &[(&str, Outcome, Outcome, &str)] = &[
+            ("prefix", Ok(None), literal(" "), "photos/"),
+            ("delimiter", Ok(None), literal(" "), "/"),
+            ("marker", Ok(None), literal(" "), "photos/2006"),
+            ("start-after", Ok(None), literal(" "), "photos/2006"),
+            ("key-marker", Ok(None), literal(" "), "photos/2006"),
+            ("version-id-marker", Ok(None), literal(" "), "null"),
+            ("upload-id-marker", Ok(None), literal(" "), "VXBsb2FkIElE"),
+            (
+                "continuation-token",
+                Err(BAD_TOKEN),
+                literal(" "),
+                "dG9rZW4",
+            ),
+            ("encoding-type", Ok(None), Err(BAD_ENCODING), "url"),
+            ("max-keys", Err(NOT_INTEGER), Err(NOT_INTEGER), "1000"),
+            (
+                "max-buckets",
+                Err(BAD_MAX_BUCKETS),
+                Err(BAD_MAX_BUCKETS),
+                "1000",
+            ),
+            (
+                "fetch-owner",
+                Err(BAD_FETCH_OWNER),
+                Err(BAD_FETCH_OWNER),
+                "true",
+            ),
+            ("max-parts", Err(BAD_MAX_PARTS), Err(BAD_MAX_PARTS), "1000"),
+            (
+                "part-number-marker",
+                Err(BAD_PART_NUMBER_MARKER),
+                Err(BAD_PART_NUMBER_MARKER),
+                "3",
+            ),
+            (
+                "max-uploads",
+                Err(BAD_MAX_UPLOADS),
+                Err(BAD_MAX_UPLOADS),
+                "1000",
+            ),
+        ];
+
+        for &(name, ref empty, ref whitespace, valid) in cases {
+            let check = |query: &str, expected: &Outcome| {
+                let qs = OrderedQs::from_query(query).unwrap();
+                let expected = expected.clone().map_err(ToOwned::to_owned);
+                assert_eq!(extract(name, &qs), expected, "query: {}", query);
+            };
+            check("list-type=2", &Ok(None));
+            check(&format!("{}=", name), empty);
+            check(name, empty);
+            check(&format!("{}=%20", name), whitespace);
+            check(&format!("{}={}", name, valid), &literal(valid));
+        }
+
+        let qs = OrderedQs::from_query("max-keys=0").unwrap();
+        assert_eq!(extract("max-keys", &qs), Ok(Some("0".to_owned())));
+
+        let qs = OrderedQs::from_query("max-keys=-1").unwrap();
+        let msg = "Argument maxKeys must be an integer between 0 and 2147483647";
+        assert_eq!(extract("max-keys", &qs), Err(msg.to_owned()));
+
+        let qs = OrderedQs::from_query("max-keys=2147483648").unwrap();
+        assert_eq!(extract("max-keys", &qs), Err(NOT_INTEGER.to_owned()));
+
+        let qs = OrderedQs::from_query("max-keys=%201").unwrap();
+        assert_eq!(extract("max-keys", &qs), Err(NOT_INTEGER.to_owned()));
+
+        for query in &["max-buckets=0", "max-buckets=10001", "max-buckets=-1"] {
+            let qs = OrderedQs::from_query(query).unwrap();
+            let expected = Err(BAD_MAX_BUCKETS.to_owned());
+            assert_eq!(extract("max-buckets", &qs), expected, "query: {}", query);
+        }
+
+        for query in &["max-parts=-1", "max-parts=2147483648"] {
+            let qs = OrderedQs::from_query(query).unwrap();
+            let expected = Err(BAD_MAX_PARTS.to_owned());
+            assert_eq!(extract("max-parts", &qs), expected, "query: {}", query);
+        }
+
+        let qs = OrderedQs::from_query("encoding-type=URL").unwrap();
+        assert_eq!(extract("encoding-type", &qs), Err(BAD_ENCODING.to_owned()));
+    }
+}
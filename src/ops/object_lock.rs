@@ -0,0 +1,397 @@
+//! Object Lock documents, shared by the operations which get or put a retention, a legal hold or a bucket configuration
+//!
+//! + [`GetObjectRetention`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectRetention.html)
+//!   and [`PutObjectRetention`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectRetention.html)
+//! + [`GetObjectLegalHold`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectLegalHold.html)
+//!   and [`PutObjectLegalHold`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectLegalHold.html)
+//! + [`GetObjectLockConfiguration`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectLockConfiguration.html)
+//!   and [`PutObjectLockConfiguration`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectLockConfiguration.html)
+//!
+//! A mode other than `GOVERNANCE` or `COMPLIANCE`, a status other than `ON` or `OFF`,
+//! or a default retention without exactly one of `Days` and `Years` is `MalformedXML`.
+//! A retain-until date in the past and a default retention period below 1 are `InvalidArgument`.
+
+use crate::dto::{
+    DefaultRetention, ObjectLockConfiguration, ObjectLockLegalHold, ObjectLockRetention,
+    ObjectLockRule,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::utils::body::deserialize_xml_body;
+use crate::utils::XmlWriterExt;
+use crate::Body;
+
+use std::io;
+
+use ::xml::writer::EventWriter;
+use chrono::{DateTime, Utc};
+
+/// the retention modes
+const MODES: &[&str] = &["GOVERNANCE", "COMPLIANCE"];
+
+/// the legal hold statuses
+const STATUSES: &[&str] = &["ON", "OFF"];
+
+/// the value of `ObjectLockEnabled`
+const ENABLED: &str = "Enabled";
+
+/// the message of `MalformedXML`
+const MALFORMED_XML: &str =
+    "The XML you provided was not well-formed or did not validate against our published schema";
+
+/// the error of a document which does not validate
+fn malformed_xml() -> S3Error {
+    code_error!(MalformedXML, MALFORMED_XML)
+}
+
+/// the error of a bucket or an object without an Object Lock configuration
+pub(super) fn not_found() -> S3Error {
+    code_error!(
+        ObjectLockConfigurationNotFoundError,
+        "Object Lock configuration does not exist for this bucket"
+    )
+}
+
+/// whether a mode is valid
+fn is_valid_mode(mode: Option<&str>) -> bool {
+    mode.map_or(false, |mode| MODES.contains(&mode))
+}
+
+/// deserializes and validates a `Retention` body, whose retain-until date must be after `now`
+pub(super) async fn extract_retention(
+    body: Body,
+    now: DateTime<Utc>,
+) -> S3Result<ObjectLockRetention> {
+    let retention: xml::Retention = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedXML, MALFORMED_XML, err))?;
+
+    if !is_valid_mode(retention.mode.as_deref()) {
+        return Err(malformed_xml());
+    }
+    let retain_until_date = retention.retain_until_date.ok_or_else(malformed_xml)?;
+    let date = DateTime::parse_from_rfc3339(&retain_until_date)
+        .map_err(|err| code_error!(MalformedXML, MALFORMED_XML, err))?;
+    if date <= now {
+        return Err(code_error!(
+            InvalidArgument,
+            "The retain until date must be in the future!"
+        ));
+    }
+
+    Ok(ObjectLockRetention {
+        mode: retention.mode,
+        retain_until_date: Some(retain_until_date),
+    })
+}
+
+/// deserializes and validates a `LegalHold` body
+pub(super) async fn extract_legal_hold(body: Body) -> S3Result<ObjectLockLegalHold> {
+    let legal_hold: xml::LegalHold = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedXML, MALFORMED_XML, err))?;
+
+    let is_valid = legal_hold
+        .status
+        .as_deref()
+        .map_or(false, |status| STATUSES.contains(&status));
+    if !is_valid {
+        return Err(malformed_xml());
+    }
+
+    Ok(ObjectLockLegalHold {
+        status: legal_hold.status,
+    })
+}
+
+/// deserializes and validates an `ObjectLockConfiguration` body
+pub(super) async fn extract_configuration(body: Body) -> S3Result<ObjectLockConfiguration> {
+    let config: xml::ObjectLockConfiguration = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedXML, MALFORMED_XML, err))?;
+
+    if config.object_lock_enabled.as_deref() != Some(ENABLED) {
+        return Err(malformed_xml());
+    }
+    let default_retention = config.rule.map(|rule| rule.default_retention);
+    if let Some(ref retention) = default_retention {
+        if !is_valid_mode(retention.mode.as_deref()) {
+            return Err(malformed_xml());
+        }
+        let period = match (retention.days, retention.years) {
+            (Some(period), None) | (None, Some(period)) => period,
+            _ => return Err(malformed_xml()),
+        };
+        if period < 1 {
+            return Err(code_error!(
+                InvalidArgument,
+                "Default retention period must be a positive integer value"
+            ));
+        }
+    }
+
+    Ok(ObjectLockConfiguration {
+        object_lock_enabled: config.object_lock_enabled,
+        rule: default_retention.map(|retention| ObjectLockRule {
+            default_retention: Some(DefaultRetention {
+                days: retention.days,
+                mode: retention.mode,
+                years: retention.years,
+            }),
+        }),
+    })
+}
+
+/// writes a `Retention` document
+pub(super) fn write_retention<W: io::Write>(
+    w: &mut EventWriter<W>,
+    retention: ObjectLockRetention,
+) -> ::xml::writer::Result<()> {
+    w.stack("Retention", |w| {
+        w.opt_element("Mode", retention.mode)?;
+        w.opt_element("RetainUntilDate", retention.retain_until_date)
+    })
+}
+
+/// writes a `LegalHold` document
+pub(super) fn write_legal_hold<W: io::Write>(
+    w: &mut EventWriter<W>,
+    legal_hold: ObjectLockLegalHold,
+) -> ::xml::writer::Result<()> {
+    w.stack("LegalHold", |w| w.opt_element("Status", legal_hold.status))
+}
+
+/// writes an `ObjectLockConfiguration` document
+pub(super) fn write_configuration<W: io::Write>(
+    w: &mut EventWriter<W>,
+    config: ObjectLockConfiguration,
+) -> ::xml::writer::Result<()> {
+    w.stack("ObjectLockConfiguration", |w| {
+        w.opt_element("ObjectLockEnabled", config.object_lock_enabled)?;
+        let default_retention = config.rule.and_then(|rule| rule.default_retention);
+        w.opt_stack("Rule", default_retention, |w, retention| {
+            w.stack("DefaultRetention", |w| {
+                w.opt_element("Mode", retention.mode)?;
+                w.opt_element("Days", retention.days.map(|days| days.to_string()))?;
+                w.opt_element("Years", retention.years.map(|years| years.to_string()))
+            })
+        })
+    })
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `Retention`
+    #[derive(Debug, Deserialize)]
+    pub struct Retention {
+        /// Mode
+        #[serde(rename = "Mode")]
+        pub mode: Option<String>,
+        /// RetainUntilDate
+        #[serde(rename = "RetainUntilDate")]
+        pub retain_until_date: Option<String>,
+    }
+
+    /// `LegalHold`
+    #[derive(Debug, Deserialize)]
+    pub struct LegalHold {
+        /// Status
+        #[serde(rename = "Status")]
+        pub status: Option<String>,
+    }
+
+    /// `DefaultRetention`
+    #[derive(Debug, Deserialize)]
+    pub struct DefaultRetention {
+        /// Mode
+        #[serde(rename = "Mode")]
+        pub mode: Option<String>,
+        /// Days
+        #[serde(rename = "Days")]
+        pub days: Option<i64>,
+        /// Years
+        #[serde(rename = "Years")]
+        pub years: Option<i64>,
+    }
+
+    /// `Rule`
+    #[derive(Debug, Deserialize)]
+    pub struct Rule {
+        /// DefaultRetention
+        #[serde(rename = "DefaultRetention")]
+        pub default_retention: DefaultRetention,
+    }
+
+    /// `ObjectLockConfiguration`
+    #[derive(Debug, Deserialize)]
+    pub struct ObjectLockConfiguration {
+        /// ObjectLockEnabled
+        #[serde(rename = "ObjectLockEnabled")]
+        pub object_lock_enabled: Option<String>,
+        /// Rule
+        #[serde(rename = "Rule")]
+        pub rule: Option<Rule>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+    use crate::utils::ResponseExt;
+    use crate::Response;
+
+    use futures::executor::block_on;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2021-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn retention(body: &str) -> Result<ObjectLockRetention, S3ErrorCode> {
+        let retention = block_on(extract_retention(Body::from(body.to_owned()), now()));
+        retention.map_err(|err| err.code())
+    }
+
+    fn configuration(body: &str) -> Result<ObjectLockConfiguration, S3ErrorCode> {
+        let config = block_on(extract_configuration(Body::from(body.to_owned())));
+        config.map_err(|err| err.code())
+    }
+
+    fn written(
+        f: impl FnOnce(&mut EventWriter<&mut Vec<u8>>) -> ::xml::writer::Result<()>,
+    ) -> String {
+        let mut res = Response::new(Body::empty());
+        res.set_xml_body(256, f).unwrap();
+        let body = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn retentions() {
+        let parsed = retention(
+            "<Retention><Mode>GOVERNANCE</Mode><RetainUntilDate>2021-06-02T00:00:00.000Z</RetainUntilDate></Retention>",
+        )
+        .unwrap();
+        assert_eq!(parsed.mode.as_deref(), Some("GOVERNANCE"));
+        assert_eq!(
+            parsed.retain_until_date.as_deref(),
+            Some("2021-06-02T00:00:00.000Z")
+        );
+
+        let past = "<Retention><Mode>COMPLIANCE</Mode><RetainUntilDate>2021-05-31T00:00:00Z</RetainUntilDate></Retention>";
+        assert_eq!(retention(past).unwrap_err(), S3ErrorCode::InvalidArgument);
+        for body in &[
+            "<Retention><RetainUntilDate>2021-06-02T00:00:00Z</RetainUntilDate></Retention>",
+            "<Retention><Mode>LOCKED</Mode><RetainUntilDate>2021-06-02T00:00:00Z</RetainUntilDate></Retention>",
+            "<Retention><Mode>GOVERNANCE</Mode></Retention>",
+            "<Retention><Mode>GOVERNANCE</Mode><RetainUntilDate>tomorrow</RetainUntilDate></Retention>",
+        ] {
+            assert_eq!(
+                retention(body).unwrap_err(),
+                S3ErrorCode::MalformedXML,
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn legal_holds() {
+        let legal_hold = |body: &str| {
+            block_on(extract_legal_hold(Body::from(body.to_owned())))
+                .map(|legal_hold| legal_hold.status)
+                .map_err(|err| err.code())
+        };
+        assert_eq!(
+            legal_hold("<LegalHold><Status>ON</Status></LegalHold>"),
+            Ok(Some("ON".to_owned()))
+        );
+        assert_eq!(
+            legal_hold("<LegalHold><Status>on</Status></LegalHold>"),
+            Err(S3ErrorCode::MalformedXML)
+        );
+        assert_eq!(
+            legal_hold("<LegalHold></LegalHold>"),
+            Err(S3ErrorCode::MalformedXML)
+        );
+    }
+
+    #[test]
+    fn configurations() {
+        let config = configuration(
+            "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled><Rule><DefaultRetention><Mode>COMPLIANCE</Mode><Days>30</Days></DefaultRetention></Rule></ObjectLockConfiguration>",
+        )
+        .unwrap();
+        let default_retention = config.rule.and_then(|rule| rule.default_retention);
+        assert_eq!(
+            default_retention,
+            Some(DefaultRetention {
+                days: Some(30),
+                mode: Some("COMPLIANCE".to_owned()),
+                years: None,
+            })
+        );
+
+        let config = configuration(
+            "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled></ObjectLockConfiguration>",
+        )
+        .unwrap();
+        assert_eq!(config.rule, None);
+
+        let zero = "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled><Rule><DefaultRetention><Mode>COMPLIANCE</Mode><Years>0</Years></DefaultRetention></Rule></ObjectLockConfiguration>";
+        assert_eq!(
+            configuration(zero).unwrap_err(),
+            S3ErrorCode::InvalidArgument
+        );
+        for body in &[
+            "<ObjectLockConfiguration></ObjectLockConfiguration>",
+            "<ObjectLockConfiguration><ObjectLockEnabled>Disabled</ObjectLockEnabled></ObjectLockConfiguration>",
+            "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled><Rule><DefaultRetention><Mode>COMPLIANCE</Mode></DefaultRetention></Rule></ObjectLockConfiguration>",
+            "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled><Rule><DefaultRetention><Mode>COMPLIANCE</Mode><Days>1</Days><Years>1</Years></DefaultRetention></Rule></ObjectLockConfiguration>",
+            "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled><Rule><DefaultRetention><Days>1</Days></DefaultRetention></Rule></ObjectLockConfiguration>",
+        ] {
+            assert_eq!(
+                configuration(body).unwrap_err(),
+                S3ErrorCode::MalformedXML,
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn documents() {
+        let retention = ObjectLockRetention {
+            mode: Some("GOVERNANCE".to_owned()),
+            retain_until_date: Some("2021-06-02T00:00:00Z".to_owned()),
+        };
+        assert!(written(|w| write_retention(w, retention)).ends_with(
+            "<Retention><Mode>GOVERNANCE</Mode><RetainUntilDate>2021-06-02T00:00:00Z</RetainUntilDate></Retention>"
+        ));
+
+        let legal_hold = ObjectLockLegalHold {
+            status: Some("OFF".to_owned()),
+        };
+        assert!(written(|w| write_legal_hold(w, legal_hold))
+            .ends_with("<LegalHold><Status>OFF</Status></LegalHold>"));
+
+        let config = ObjectLockConfiguration {
+            object_lock_enabled: Some(ENABLED.to_owned()),
+            rule: Some(ObjectLockRule {
+                default_retention: Some(DefaultRetention {
+                    days: None,
+                    mode: Some("COMPLIANCE".to_owned()),
+                    years: Some(1),
+                }),
+            }),
+        };
+        assert!(written(|w| write_configuration(w, config)).ends_with(
+            "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled><Rule><DefaultRetention><Mode>COMPLIANCE</Mode><Years>1</Years></DefaultRetention></Rule></ObjectLockConfiguration>"
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! Headers shared by the operations which write an object
+//!
+//! + [`PutObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
+//! + [`CreateMultipartUpload`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html)
+//! + [`CopyObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
+
+use crate::data_structures::OrderedHeaders;
+use crate::dto::{CopyObjectRequest, CreateMultipartUploadRequest, PutObjectRequest};
+use crate::errors::S3Result;
+use crate::headers::{
+    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_TYPE, EXPIRES,
+    X_AMZ_ACL, X_AMZ_GRANT_FULL_CONTROL, X_AMZ_GRANT_READ, X_AMZ_GRANT_READ_ACP,
+    X_AMZ_GRANT_WRITE_ACP, X_AMZ_OBJECT_LOCK_LEGAL_HOLD, X_AMZ_OBJECT_LOCK_MODE,
+    X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE, X_AMZ_REQUEST_PAYER, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_STORAGE_CLASS, X_AMZ_TAGGING,
+    X_AMZ_WEBSITE_REDIRECT_LOCATION,
+};
+
+use std::collections::HashMap;
+
+/// Request types which accept [`CommonObjectWriteHeaders`]
+pub trait ApplyCommonHeaders {
+    /// moves the common fields into the request
+    fn apply_common_headers(&mut self, common: CommonObjectWriteHeaders);
+
+    /// lists the common fields by name
+    #[cfg(test)]
+    fn common_fields(&self) -> Vec<(&'static str, Option<&str>)>;
+}
+
+/// Defines `CommonObjectWriteHeaders` and its adapters from one table
+macro_rules! common_object_write_headers {
+    (
+        apply_to: [$($ty:ident),+],
+        fields: $fields:tt
+    ) => {
+        common_object_write_headers!(@struct $fields);
+        $(common_object_write_headers!(@apply $ty, $fields);)+
+    };
+    (@struct { $($field:ident: $header:expr,)+ }) => {
+        /// Headers extracted by every operation which writes an object
+        #[derive(Debug, Default)]
+        pub struct CommonObjectWriteHeaders {
+            $(
+                #[allow(missing_docs)]
+                pub $field: Option<String>,
+            )+
+            /// `x-amz-meta-*`
+            pub metadata: Option<HashMap<String, String>>,
+        }
+
+        impl CommonObjectWriteHeaders {
+            /// extracts the common headers
+            /// # Errors
+            /// Returns an `Err` if a metadata value is invalid
+            pub fn extract_from(headers: &OrderedHeaders<'_>) -> S3Result<Self> {
+                Ok(Self {
+                    $($field: headers.get($header).map(ToOwned::to_owned),)+
+                    metadata: extract_metadata(headers)?,
+                })
+            }
+
+            /// moves the common fields into `input`
+            pub fn apply_to(self, input: &mut impl ApplyCommonHeaders) {
+                input.apply_common_headers(self);
+            }
+
+            /// lists `(header name, field name)`
+            #[cfg(test)]
+            fn header_table() -> Vec<(&'static str, &'static str)> {
+                vec![$(($header.as_str(), stringify!($field)),)+]
+            }
+        }
+    };
+    (@apply $ty:ident, { $($field:ident: $header:expr,)+ }) => {
+        impl ApplyCommonHeaders for $ty {
+            fn apply_common_headers(&mut self, common: CommonObjectWriteHeaders) {
+                $(self.$field = common.$field;)+
+                self.metadata = common.metadata;
+            }
+
+            #[cfg(test)]
+            fn common_fields(&self) -> Vec<(&'static str, Option<&str>)> {
+                vec![$((stringify!($field), self.$field.as_deref()),)+]
+            }
+        }
+    };
+}
+
+common_object_write_headers! {
+    apply_to: [PutObjectRequest, CreateMultipartUploadRequest, CopyObjectRequest],
+    fields: {
+        acl: &*X_AMZ_ACL,
+        cache_control: CACHE_CONTROL,
+        content_disposition: CONTENT_DISPOSITION,
+        content_encoding: CONTENT_ENCODING,
+        content_language: CONTENT_LANGUAGE,
+        content_type: CONTENT_TYPE,
+        expires: EXPIRES,
+        grant_full_control: &*X_AMZ_GRANT_FULL_CONTROL,
+        grant_read: &*X_AMZ_GRANT_READ,
+        grant_read_acp: &*X_AMZ_GRANT_READ_ACP,
+        grant_write_acp: &*X_AMZ_GRANT_WRITE_ACP,
+        server_side_encryption: &*X_AMZ_SERVER_SIDE_ENCRYPTION,
+        storage_class: &*X_AMZ_STORAGE_CLASS,
+        website_redirect_location: &*X_AMZ_WEBSITE_REDIRECT_LOCATION,
+        sse_customer_algorithm: &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
+        sse_customer_key: &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+        sse_customer_key_md5: &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+        ssekms_key_id: &*X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
+        ssekms_encryption_context: &*X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
+        request_payer: &*X_AMZ_REQUEST_PAYER,
+        tagging: &*X_AMZ_TAGGING,
+        object_lock_mode: &*X_AMZ_OBJECT_LOCK_MODE,
+        object_lock_retain_until_date: &*X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
+        object_lock_legal_hold_status: &*X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
+    }
+}
+
+/// extracts `x-amz-meta-*` headers
+fn extract_metadata(headers: &OrderedHeaders<'_>) -> S3Result<Option<HashMap<String, String>>> {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    for &(name, value) in headers.as_ref() {
+        let meta_prefix = "x-amz-meta-";
+        if name.starts_with(meta_prefix) {
+            let (_, meta_key) = name.split_at(meta_prefix.len());
+            if !meta_key.is_empty() {
+                check_metadata_value(value)?;
+                let _prev = metadata.insert(meta_key.to_owned(), value.to_owned());
+            }
+        }
+    }
+    if metadata.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(metadata))
+    }
+}
+
+/// Metadata values must be visible ASCII or spaces, stricter than other header values.
+/// Other characters should be encoded by the client, for example as RFC 2047 words.
+pub(super) fn check_metadata_value(value: &str) -> S3Result<()> {
+    if value.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        Ok(())
+    } else {
+        Err(code_error!(
+            InvalidArgument,
+            "Metadata values must contain only visible ASCII characters."
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_value(name: &str) -> String {
+        format!("value-of-{}", name)
+    }
+
+    fn extract_all() -> CommonObjectWriteHeaders {
+        let values: Vec<(&str, String)> = CommonObjectWriteHeaders::header_table()
+            .into_iter()
+            .map(|(name, _)| (name, header_value(name)))
+            .collect();
+        let mut slice: Vec<(&str, &str)> = values.iter().map(|&(n, ref v)| (n, &**v)).collect();
+        slice.push(("x-amz-meta-color", "blue"));
+        slice.push(("x-amz-meta-", "ignored"));
+        let headers = OrderedHeaders::from_slice_unchecked(&slice);
+        CommonObjectWriteHeaders::extract_from(&headers).unwrap()
+    }
+
+    fn check_fields(input: &impl ApplyCommonHeaders) {
+        let table = CommonObjectWriteHeaders::header_table();
+        let fields = input.common_fields();
+        assert_eq!(fields.len(), table.len());
+
+        for &(name, field) in &table {
+            let value = fields.iter().find(|&&(f, _)| f == field).map(|&(_, v)| v);
+            let expected = header_value(name);
+            assert_eq!(value, Some(Some(expected.as_str())), "header: {}", name);
+        }
+    }
+
+    #[test]
+    fn header_table_is_unique() {
+        let table = CommonObjectWriteHeaders::header_table();
+        for (idx, &(name, field)) in table.iter().enumerate() {
+            assert_eq!(name, name.to_ascii_lowercase(), "field: {}", field);
+            let duplicated = table
+                .iter()
+                .skip(idx.wrapping_add(1))
+                .any(|&(n, f)| n == name || f == field);
+            assert!(!duplicated, "header: {}, field: {}", name, field);
+        }
+    }
+
+    #[test]
+    fn apply_to_requests() {
+        let mut put = PutObjectRequest::default();
+        extract_all().apply_to(&mut put);
+        check_fields(&put);
+
+        let mut create = CreateMultipartUploadRequest::default();
+        extract_all().apply_to(&mut create);
+        check_fields(&create);
+
+        let mut copy = CopyObjectRequest::default();
+        extract_all().apply_to(&mut copy);
+        check_fields(&copy);
+
+        let mut expected_metadata = HashMap::new();
+        let _prev = expected_metadata.insert("color".to_owned(), "blue".to_owned());
+        assert_eq!(put.metadata.as_ref(), Some(&expected_metadata));
+        assert_eq!(create.metadata.as_ref(), Some(&expected_metadata));
+        assert_eq!(copy.metadata.as_ref(), Some(&expected_metadata));
+    }
+
+    #[test]
+    fn missing_headers() {
+        let headers = OrderedHeaders::from_slice_unchecked(&[("content-length", "5")]);
+        let mut put = PutObjectRequest::default();
+        CommonObjectWriteHeaders::extract_from(&headers)
+            .unwrap()
+            .apply_to(&mut put);
+        assert!(put.common_fields().iter().all(|&(_, v)| v.is_none()));
+        assert!(put.metadata.is_none());
+    }
+
+    #[test]
+    fn invalid_metadata() {
+        let headers = OrderedHeaders::from_slice_unchecked(&[("x-amz-meta-color", "a\tb")]);
+        let _err = CommonObjectWriteHeaders::extract_from(&headers).unwrap_err();
+
+        let headers = OrderedHeaders::from_slice_unchecked(&[("x-amz-meta-color", "light blue")]);
+        let _ok = CommonObjectWriteHeaders::extract_from(&headers).unwrap();
+    }
+}
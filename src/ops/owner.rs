@@ -0,0 +1,41 @@
+//! Owners and initiators in outputs
+//!
+//! The bundled storages do not record owners, so a missing owner is the canonical user of the request,
+//! as in a deployment with a single principal.
+//! An owner returned by a storage is kept, and gets the display name of the requester if it has the same id.
+
+use crate::auth::CanonicalUser;
+use crate::dto::{Initiator, Owner};
+
+/// fills the owner by the canonical user of the request
+pub(crate) fn fill_owner(owner: &mut Option<Owner>, user: Option<&CanonicalUser>) {
+    let user = match user {
+        Some(user) => user,
+        None => return,
+    };
+    match *owner {
+        None => *owner = Some(user.owner()),
+        Some(ref mut owner) => {
+            if owner.display_name.is_none() && owner.id.as_deref() == Some(user.id.as_str()) {
+                owner.display_name = Some(user.display_name.clone());
+            }
+        }
+    }
+}
+
+/// fills the initiator by the canonical user of the request
+pub(crate) fn fill_initiator(initiator: &mut Option<Initiator>, user: Option<&CanonicalUser>) {
+    let user = match user {
+        Some(user) => user,
+        None => return,
+    };
+    match *initiator {
+        None => *initiator = Some(user.initiator()),
+        Some(ref mut initiator) => {
+            if initiator.display_name.is_none() && initiator.id.as_deref() == Some(user.id.as_str())
+            {
+                initiator.display_name = Some(user.display_name.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! [`PutBucketAcl`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAcl.html)
+//!
+//! The ACL is given by a canned ACL, by grant headers or by an `AccessControlPolicy` body, exactly one of them.
+//! It is resolved into `access_control_policy` before reaching the storage, like the ACL of `PutObjectAcl`.
+//! Unlike an object, a bucket can grant `WRITE` and has the canned ACL `log-delivery-write`.
+
+use super::{access_control_policy, acl_headers, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::auth::CanonicalUser;
+use crate::dto::{PutBucketAclError, PutBucketAclOutput, PutBucketAclRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{
+    CONTENT_MD5, X_AMZ_ACL, X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_GRANT_FULL_CONTROL,
+    X_AMZ_GRANT_READ, X_AMZ_GRANT_READ_ACP, X_AMZ_GRANT_WRITE, X_AMZ_GRANT_WRITE_ACP,
+};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::{async_trait, Method, Response};
+
+/// `PutBucketAcl` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("acl")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_bucket_acl(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutBucketAclRequest> {
+    let bucket = ctx.unwrap_bucket_path().to_owned();
+    let grants = acl_headers::extract_grants(&ctx.headers, &acl_headers::bucket_grant_headers())?;
+    let body_policy = access_control_policy::extract_access_control_policy(ctx.take_body()).await?;
+
+    let mut input = PutBucketAclRequest {
+        bucket,
+        ..PutBucketAclRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*X_AMZ_ACL, &mut input.acl);
+    h.assign_str(&*X_AMZ_GRANT_FULL_CONTROL, &mut input.grant_full_control);
+    h.assign_str(&*X_AMZ_GRANT_READ, &mut input.grant_read);
+    h.assign_str(&*X_AMZ_GRANT_READ_ACP, &mut input.grant_read_acp);
+    h.assign_str(&*X_AMZ_GRANT_WRITE, &mut input.grant_write);
+    h.assign_str(&*X_AMZ_GRANT_WRITE_ACP, &mut input.grant_write_acp);
+    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    let owner = ctx.canonical_user.as_ref().map(CanonicalUser::owner);
+    let policy = access_control_policy::resolve_policy(
+        body_policy,
+        input.acl.as_deref(),
+        grants,
+        owner,
+        acl_headers::canned_bucket_grants,
+    )?;
+    input.access_control_policy = Some(policy);
+
+    Ok(input)
+}
+
+impl S3Output for PutBucketAclOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|_| Ok(()))
+    }
+}
+
+impl From<PutBucketAclError> for S3Error {
+    fn from(e: PutBucketAclError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,178 @@
+//! [`PutBucketPolicy`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketPolicy.html)
+//!
+//! The body is a JSON policy document of at most 20 KB, which replaces the policy of the bucket.
+//! It is rejected with `MalformedPolicy` if it is larger, is not a JSON object or has no `Statement` array.
+//! The policy is not evaluated: the storage keeps the document as it was put.
+
+use super::{wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{PutBucketPolicyError, PutBucketPolicyOutput, PutBucketPolicyRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{
+    CONTENT_MD5, X_AMZ_CONFIRM_REMOVE_SELF_BUCKET_ACCESS, X_AMZ_EXPECTED_BUCKET_OWNER,
+};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::{async_trait, Body, Method, Response};
+
+use futures::stream::StreamExt;
+
+/// the max bytes of a policy document, 20 KB
+const MAX_POLICY_SIZE: usize = 20 * 1024;
+
+/// `PutBucketPolicy` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("policy")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_bucket_policy(input).await;
+        output.try_into_response()
+    }
+}
+
+/// reads a policy document, which is cut after `MAX_POLICY_SIZE` bytes
+async fn read_policy(mut body: Body) -> S3Result<String> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.next().await {
+        let chunk =
+            chunk.map_err(|err| invalid_request!("Can not obtain the whole request body.", err))?;
+        if buf.len().saturating_add(chunk.len()) > MAX_POLICY_SIZE {
+            return Err(code_error!(
+                MalformedPolicy,
+                "Policies must be no more than 20 KB"
+            ));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    String::from_utf8(buf).map_err(|err| {
+        code_error!(
+            MalformedPolicy,
+            "Policies must be valid JSON and the first byte must be '{'",
+            err
+        )
+    })
+}
+
+/// checks that a policy is a JSON object with a `Statement` array
+fn check_policy(policy: &str) -> S3Result<()> {
+    let document: serde_json::Value = serde_json::from_str(policy).map_err(|err| {
+        code_error!(
+            MalformedPolicy,
+            "Policies must be valid JSON and the first byte must be '{'",
+            err
+        )
+    })?;
+    if !document.is_object() {
+        return Err(code_error!(
+            MalformedPolicy,
+            "Policies must be valid JSON and the first byte must be '{'"
+        ));
+    }
+    if !document
+        .get("Statement")
+        .map_or(false, serde_json::Value::is_array)
+    {
+        return Err(code_error!(
+            MalformedPolicy,
+            "Missing required field Statement"
+        ));
+    }
+    Ok(())
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutBucketPolicyRequest> {
+    let bucket = ctx.unwrap_bucket_path().to_owned();
+    let policy = read_policy(ctx.take_body()).await?;
+    check_policy(&policy)?;
+
+    let mut input = PutBucketPolicyRequest {
+        bucket,
+        policy,
+        ..PutBucketPolicyRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
+    h.assign(
+        &*X_AMZ_CONFIRM_REMOVE_SELF_BUCKET_ACCESS,
+        &mut input.confirm_remove_self_bucket_access,
+    )
+    .map_err(|err| {
+        invalid_request!(
+            "Invalid header: x-amz-confirm-remove-self-bucket-access",
+            err
+        )
+    })?;
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for PutBucketPolicyOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|_| Ok(()))
+    }
+}
+
+impl From<PutBucketPolicyError> for S3Error {
+    fn from(e: PutBucketPolicyError) -> Self {
+        match e {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+
+    use futures::executor::block_on;
+
+    fn read(policy: &str) -> Result<(), S3ErrorCode> {
+        let policy = block_on(read_policy(Body::from(policy.to_owned())));
+        policy
+            .and_then(|policy| check_policy(&policy))
+            .map_err(|err| err.code())
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!(read(r#"{"Version":"2012-10-17","Statement":[]}"#), Ok(()));
+
+        let statement = format!(r#"{{"Sid":"{}"}}"#, "s".repeat(100));
+        let large = format!(r#"{{"Statement":[{}]}}"#, vec![statement; 200].join(","));
+        assert!(large.len() > MAX_POLICY_SIZE);
+        for policy in &[
+            "",
+            "not json",
+            "[]",
+            r#"{"Version":"2012-10-17"}"#,
+            r#"{"Statement":{}}"#,
+            large.as_str(),
+        ] {
+            assert_eq!(
+                read(policy),
+                Err(S3ErrorCode::MalformedPolicy),
+                "{}",
+                policy
+            );
+        }
+    }
+}
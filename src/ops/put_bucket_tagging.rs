@@ -0,0 +1,68 @@
+//! [`PutBucketTagging`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketTagging.html)
+//!
+//! The tag set replaces the tags of the bucket. A bucket has at most 50 tags.
+
+use super::{tagging, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{PutBucketTaggingError, PutBucketTaggingOutput, PutBucketTaggingRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{CONTENT_MD5, X_AMZ_EXPECTED_BUCKET_OWNER};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::{async_trait, Method, Response};
+
+/// `PutBucketTagging` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("tagging")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_bucket_tagging(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutBucketTaggingRequest> {
+    let bucket = ctx.unwrap_bucket_path().to_owned();
+    let tagging = tagging::extract_tagging(ctx.take_body(), tagging::MAX_BUCKET_TAGS).await?;
+
+    let mut input = PutBucketTaggingRequest {
+        bucket,
+        tagging,
+        ..PutBucketTaggingRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for PutBucketTaggingOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|_| Ok(()))
+    }
+}
+
+impl From<PutBucketTaggingError> for S3Error {
+    fn from(e: PutBucketTaggingError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,130 @@
+//! [`PutBucketVersioning`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketVersioning.html)
+//!
+//! A `Status` other than `Enabled` or `Suspended` is an `IllegalVersioningConfigurationException`,
+//! and a `MfaDelete` other than `Enabled` or `Disabled` is `MalformedXML`.
+
+use super::{is_mfa_delete_enabled, verify_mfa, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{
+    PutBucketVersioningError, PutBucketVersioningOutput, PutBucketVersioningRequest,
+    VersioningConfiguration,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{CONTENT_MD5, X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_MFA};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::body::deserialize_xml_body;
+use crate::{async_trait, Method, Response};
+
+/// `PutBucketVersioning` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("versioning").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        if let Some(ref mfa_delete) = input.versioning_configuration.mfa_delete {
+            let enabled = mfa_delete == "Enabled";
+            if enabled != is_mfa_delete_enabled(storage, &input.bucket).await? {
+                verify_mfa(ctx).await?;
+            }
+        }
+        let output = storage.put_bucket_versioning(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutBucketVersioningRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let config: self::xml::VersioningConfiguration = deserialize_xml_body(ctx.take_body())
+        .await
+        .map_err(|err| code_error!(MalformedXML, "Invalid xml format", err))?;
+
+    let is_valid_status = config
+        .status
+        .as_deref()
+        .map_or(true, |s| s == "Enabled" || s == "Suspended");
+    if !is_valid_status {
+        return Err(code_error!(
+            IllegalVersioningConfigurationException,
+            "The Versioning element must be specified as Enabled or Suspended"
+        ));
+    }
+    let is_valid_mfa_delete = config
+        .mfa_delete
+        .as_deref()
+        .map_or(true, |s| s == "Enabled" || s == "Disabled");
+    if !is_valid_mfa_delete {
+        return Err(code_error!(
+            MalformedXML,
+            "The XML you provided was not well-formed or did not validate against our published schema."
+        ));
+    }
+
+    let mut input = PutBucketVersioningRequest {
+        bucket: bucket.into(),
+        versioning_configuration: config.into(),
+        ..PutBucketVersioningRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(CONTENT_MD5.as_str(), &mut input.content_md5);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+    h.assign_str(&*X_AMZ_MFA, &mut input.mfa);
+
+    Ok(input)
+}
+
+impl S3Output for PutBucketVersioningOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|_| Ok(()))
+    }
+}
+
+impl From<PutBucketVersioningError> for S3Error {
+    fn from(e: PutBucketVersioningError) -> Self {
+        match e {}
+    }
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `VersioningConfiguration`
+    #[derive(Debug, Deserialize)]
+    pub struct VersioningConfiguration {
+        /// MfaDelete
+        #[serde(rename = "MfaDelete")]
+        pub mfa_delete: Option<String>,
+        /// Status
+        #[serde(rename = "Status")]
+        pub status: Option<String>,
+    }
+
+    impl From<VersioningConfiguration> for super::VersioningConfiguration {
+        fn from(config: VersioningConfiguration) -> Self {
+            Self {
+                mfa_delete: config.mfa_delete,
+                status: config.status,
+            }
+        }
+    }
+}
@@ -1,19 +1,18 @@
 //! [`PutObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::acl_headers;
+use super::object_write_headers::{check_metadata_value, CommonObjectWriteHeaders};
+use super::{content_md5, wrap_internal_error, ReqContext, S3Handler};
 
+use crate::chunked_upload;
+use crate::consistency::{self, ConsistencyToken};
 use crate::dto::{PutObjectError, PutObjectOutput, PutObjectRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::{
-    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_LENGTH,
-    CONTENT_MD5, CONTENT_TYPE, ETAG, EXPIRES, X_AMZ_ACL, X_AMZ_EXPIRATION,
-    X_AMZ_GRANT_FULL_CONTROL, X_AMZ_GRANT_READ, X_AMZ_GRANT_READ_ACP, X_AMZ_GRANT_WRITE_ACP,
-    X_AMZ_OBJECT_LOCK_LEGAL_HOLD, X_AMZ_OBJECT_LOCK_MODE, X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
-    X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    CONTENT_MD5, ETAG, X_AMZ_EXPIRATION, X_AMZ_REQUEST_CHARGED, X_AMZ_SERVER_SIDE_ENCRYPTION,
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_STORAGE_CLASS, X_AMZ_TAGGING,
-    X_AMZ_VERSION_ID, X_AMZ_WEBSITE_REDIRECT_LOCATION,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+    X_AMZ_VERSION_ID,
 };
 use crate::output::S3Output;
 use crate::path::S3Path;
@@ -27,15 +26,15 @@ use std::collections::HashMap;
 use std::mem;
 
 /// `PutObject` handler
-pub struct Handler;
+pub(crate) struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        if ctx.req.method() == Method::POST {
+        if ctx.method == Method::POST {
             bool_try!(ctx.path.is_bucket());
-            ctx.multipart.is_some()
-        } else if ctx.req.method() == Method::PUT {
+            ctx.is_form_upload()
+        } else if ctx.method == Method::PUT {
             bool_try!(ctx.path.is_object());
             ctx.query_strings.is_none()
         } else {
@@ -48,8 +47,30 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
-        let input = extract(ctx)?;
-        let output = storage.put_object(input).await;
+        let mut input = extract(ctx)?;
+        // the `Content-MD5` of a form covers the whole form
+        let digest_check = if ctx.method == Method::PUT {
+            content_md5::verify(input.content_md5.as_deref(), &mut input.body)?
+        } else {
+            None
+        };
+        let file_limit = ctx
+            .content_length_range
+            .and_then(|range| range.limit(&mut input.body));
+        let token = ConsistencyToken::new(input.bucket.clone(), input.key.clone());
+        let mut output = storage.put_object(input).await;
+        if let Some(ref file_limit) = file_limit {
+            output = file_limit.check(output);
+        }
+        if let Some(ref digest_check) = digest_check {
+            output = digest_check.check(output);
+        }
+        if let Ok(ref output) = output {
+            let token = token
+                .version_id(output.version_id.clone())
+                .e_tag(output.e_tag.clone());
+            consistency::barrier(ctx, storage, token).await;
+        }
         output.try_into_response()
     }
 }
@@ -69,6 +90,7 @@ fn extract_from_multipart(input: &mut PutObjectRequest, mut multipart: Multipart
         if name.starts_with(meta_prefix) {
             let (_, meta_key) = name.split_at(meta_prefix.len());
             if !meta_key.is_empty() {
+                check_metadata_value(value)?;
                 let _prev = metadata.insert(meta_key.to_owned(), mem::take(value));
             }
         }
@@ -85,110 +107,100 @@ fn extract_from_multipart(input: &mut PutObjectRequest, mut multipart: Multipart
     Ok(())
 }
 
-/// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectRequest> {
-    let (bucket, key) = if ctx.req.method() == Method::POST {
-        let bucket = ctx.unwrap_bucket_path();
-
-        #[allow(clippy::unwrap_used)]
-        let multipart = ctx.multipart.as_ref().unwrap();
+/// Extracts a `PutObjectRequest` from a `PUT` request or a `POST` form
+///
+/// ```
+/// use s3_server::dto::PutObjectOutput;
+/// use s3_server::ops::{put_object, ReqContext};
+/// use s3_server::errors::S3ErrorCode;
+/// use s3_server::S3Output;
+///
+/// use hyper::{Body, Method, Request};
+///
+/// let mut req = Request::builder()
+///     .method(Method::PUT)
+///     .uri("http://localhost/bucket/key")
+///     .header("content-type", "text/plain")
+///     .header("x-amz-meta-color", "blue")
+///     .body(Body::from("hello"))
+///     .unwrap();
+/// let body = std::mem::take(req.body_mut());
+///
+/// let mut ctx = ReqContext::new(&req, body).unwrap();
+/// let input = put_object::extract(&mut ctx).unwrap();
+/// assert_eq!(input.bucket, "bucket");
+/// assert_eq!(input.key, "key");
+/// assert_eq!(input.content_type.as_deref(), Some("text/plain"));
+/// assert!(input.body.is_some());
+///
+/// let req = Request::builder()
+///     .method(Method::DELETE)
+///     .uri("http://localhost/bucket/key")
+///     .body(Body::empty())
+///     .unwrap();
+/// let mut ctx = ReqContext::new(&req, Body::empty()).unwrap();
+/// let err = put_object::extract(&mut ctx).unwrap_err();
+/// assert_eq!(err.code(), S3ErrorCode::MethodNotAllowed);
+///
+/// let output = PutObjectOutput {
+///     e_tag: Some("\"5d41402abc4b2a76b9719d911017c592\"".into()),
+///     ..PutObjectOutput::default()
+/// };
+/// let res = output.try_into_response().unwrap();
+/// assert_eq!(res.headers()["etag"], "\"5d41402abc4b2a76b9719d911017c592\"");
+/// ```
+///
+/// # Errors
+/// Returns an `Err` if the request is invalid, such as a request which is
+/// neither `PUT` to an object nor `POST` of a form to a bucket
+pub fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectRequest> {
+    let (bucket, key) = if ctx.method == Method::POST {
+        let bucket = ctx.bucket_path()?;
+
+        let multipart = ctx
+            .multipart
+            .as_ref()
+            .ok_or_else(|| invalid_request!("The POST request is not a form."))?;
 
         let key = multipart
             .find_field_value("key")
-            .ok_or_else(|| S3Error::new(S3ErrorCode::UserKeyMustBeSpecified, "Missing key"))?;
+            .ok_or_else(|| S3Error::new(S3ErrorCode::UserKeyMustBeSpecified, "Missing key"))?
+            .replace("${filename}", &multipart.file.name);
 
-        if !S3Path::check_key(key) {
+        if !S3Path::check_key(&key) {
             return Err(S3Error::new(
                 S3ErrorCode::KeyTooLongError,
                 "Your key is too long.",
             ));
         }
 
-        (bucket, key)
-    } else if ctx.req.method() == Method::PUT {
-        ctx.unwrap_object_path()
+        (bucket, ctx.key_encoding.from_text(&key).into_owned())
+    } else if ctx.method == Method::PUT {
+        let (bucket, key) = ctx.object_path()?;
+        (bucket, key.to_owned())
     } else {
-        panic!("unexpected method");
+        return Err(code_error!(
+            MethodNotAllowed,
+            "The specified method is not allowed against this resource."
+        ));
     };
 
     let mut input: PutObjectRequest = PutObjectRequest {
         bucket: bucket.into(),
-        key: key.into(),
+        key,
         body: None,
         ..PutObjectRequest::default()
     };
 
     let h = &ctx.headers;
-    h.assign(CONTENT_LENGTH, &mut input.content_length)
-        .map_err(|err| invalid_request!("Invalid header: content-length", err))?;
-
-    h.assign_str(&*X_AMZ_ACL, &mut input.acl);
-    h.assign_str(CACHE_CONTROL, &mut input.cache_control);
-    h.assign_str(CONTENT_DISPOSITION, &mut input.content_disposition);
-    h.assign_str(CONTENT_ENCODING, &mut input.content_encoding);
-    h.assign_str(CONTENT_LANGUAGE, &mut input.content_language);
-    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
-    h.assign_str(CONTENT_TYPE, &mut input.content_type);
-    h.assign_str(EXPIRES, &mut input.expires);
-    h.assign_str(&*X_AMZ_GRANT_FULL_CONTROL, &mut input.grant_full_control);
-    h.assign_str(&*X_AMZ_GRANT_READ, &mut input.grant_read);
-    h.assign_str(&*X_AMZ_GRANT_READ_ACP, &mut input.grant_read_acp);
-    h.assign_str(&*X_AMZ_GRANT_WRITE_ACP, &mut input.grant_write_acp);
-
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION,
-        &mut input.server_side_encryption,
-    );
-    h.assign_str(&*X_AMZ_STORAGE_CLASS, &mut input.storage_class);
-    h.assign_str(
-        &*X_AMZ_WEBSITE_REDIRECT_LOCATION,
-        &mut input.website_redirect_location,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-        &mut input.sse_customer_algorithm,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-        &mut input.sse_customer_key,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-        &mut input.sse_customer_key_md5,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
-        &mut input.ssekms_key_id,
-    );
-    h.assign_str(
-        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
-        &mut input.ssekms_encryption_context,
-    );
-    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
-    h.assign_str(&*X_AMZ_TAGGING, &mut input.tagging);
-    h.assign_str(&*X_AMZ_OBJECT_LOCK_MODE, &mut input.object_lock_mode);
-    h.assign_str(
-        &*X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
-        &mut input.object_lock_retain_until_date,
-    );
-    h.assign_str(
-        &*X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
-        &mut input.object_lock_legal_hold_status,
-    );
+    input.content_length = ctx.content_length()?;
 
-    let mut metadata: HashMap<String, String> = HashMap::new();
-    for &(name, value) in ctx.headers.as_ref() {
-        let meta_prefix = "x-amz-meta-";
-        if name.starts_with(meta_prefix) {
-            let (_, meta_key) = name.split_at(meta_prefix.len());
-            if !meta_key.is_empty() {
-                let _prev = metadata.insert(meta_key.to_owned(), value.to_owned());
-            }
-        }
-    }
-    if !metadata.is_empty() {
-        input.metadata = Some(metadata);
+    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
+    CommonObjectWriteHeaders::extract_from(h)?.apply_to(&mut input);
+    if ctx.aws_chunked {
+        input.content_encoding = chunked_upload::strip_aws_chunked(input.content_encoding);
     }
+    let _grants = acl_headers::extract_grants(h, &acl_headers::object_grant_headers())?;
 
     match ctx.multipart.take() {
         None => input.body = ctx.take_body().apply(transform_body_stream).apply(Some),
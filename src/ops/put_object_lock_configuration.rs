@@ -0,0 +1,81 @@
+//! [`PutObjectLockConfiguration`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectLockConfiguration.html)
+//!
+//! The body is an `ObjectLockConfiguration` which is `Enabled`, with an optional default retention
+//! of a mode and either `Days` or `Years`. It replaces the configuration of the bucket.
+
+use super::{object_lock, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{
+    CONTENT_MD5, X_AMZ_BUCKET_OBJECT_LOCK_TOKEN, X_AMZ_EXPECTED_BUCKET_OWNER,
+    X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER,
+};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `PutObjectLockConfiguration` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("object-lock")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_object_lock_configuration(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectLockConfigurationRequest> {
+    let bucket = ctx.unwrap_bucket_path().to_owned();
+    let config = object_lock::extract_configuration(ctx.take_body()).await?;
+
+    let mut input = PutObjectLockConfigurationRequest {
+        bucket,
+        object_lock_configuration: Some(config),
+        ..PutObjectLockConfigurationRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    h.assign_str(&*X_AMZ_BUCKET_OBJECT_LOCK_TOKEN, &mut input.token);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for PutObjectLockConfigurationOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_optional_header(&*X_AMZ_REQUEST_CHARGED, self.request_charged)?;
+            Ok(())
+        })
+    }
+}
+
+impl From<PutObjectLockConfigurationError> for S3Error {
+    fn from(e: PutObjectLockConfigurationError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,90 @@
+//! [`PutObjectRetention`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectRetention.html)
+//!
+//! The body is a `Retention` with a mode and a retain-until date, which must be in the future.
+//! A date in the past is rejected with `InvalidArgument` before reaching the storage.
+
+use super::{object_lock, wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{PutObjectRetentionError, PutObjectRetentionOutput, PutObjectRetentionRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{
+    CONTENT_MD5, X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_EXPECTED_BUCKET_OWNER,
+    X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER,
+};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+use chrono::Utc;
+
+/// `PutObjectRetention` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("retention")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_object_retention(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectRetentionRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let (bucket, key) = (bucket.to_owned(), key.to_owned());
+    let retention = object_lock::extract_retention(ctx.take_body(), Utc::now()).await?;
+
+    let mut input = PutObjectRetentionRequest {
+        bucket,
+        key,
+        retention: Some(retention),
+        ..PutObjectRetentionRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+    h.assign(
+        &*X_AMZ_BYPASS_GOVERNANCE_RETENTION,
+        &mut input.bypass_governance_retention,
+    )
+    .map_err(|err| invalid_request!("Invalid header: x-amz-bypass-governance-retention", err))?;
+
+    if let Some(ref qs) = ctx.query_strings {
+        input.version_id = qs.get("versionId").map(ToOwned::to_owned);
+    }
+
+    Ok(input)
+}
+
+impl S3Output for PutObjectRetentionOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_optional_header(&*X_AMZ_REQUEST_CHARGED, self.request_charged)?;
+            Ok(())
+        })
+    }
+}
+
+impl From<PutObjectRetentionError> for S3Error {
+    fn from(e: PutObjectRetentionError) -> Self {
+        match e {}
+    }
+}
@@ -0,0 +1,224 @@
+//! [`RestoreObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_RestoreObject.html)
+//!
+//! The body is a `RestoreRequest` with the `Days` of the restored copy and an optional `GlacierJobParameters` `Tier`.
+//! A missing `Days` or an unknown tier is `MalformedXML`, and `Days` below 1 is `InvalidArgument`.
+//! An accepted restore is `202 Accepted`, while a restore which is already in progress is `200 OK`.
+
+use super::{wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{RestoreObjectError, RestoreObjectOutput, RestoreObjectRequest};
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+use crate::headers::{
+    X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER,
+    X_AMZ_RESTORE_OUTPUT_PATH,
+};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::body::deserialize_xml_body;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Body, Method, Response, StatusCode};
+
+/// the retrieval tiers of `GlacierJobParameters`
+const TIERS: &[&str] = &["Standard", "Bulk", "Expedited"];
+
+/// the message of `MalformedXML`
+const MALFORMED_XML: &str =
+    "The XML you provided was not well-formed or did not validate against our published schema.";
+
+/// `RestoreObject` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::POST);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.contains("restore")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.restore_object(input).await;
+        output.try_into_response()
+    }
+}
+
+/// deserializes and validates a `RestoreRequest` body
+async fn extract_restore_request(body: Body) -> S3Result<crate::dto::RestoreRequest> {
+    let request: Option<xml::RestoreRequest> = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedXML, MALFORMED_XML, err))?;
+    let request = request.ok_or_else(|| code_error!(MalformedXML, MALFORMED_XML))?;
+
+    let days = request
+        .days
+        .ok_or_else(|| code_error!(MalformedXML, MALFORMED_XML))?;
+    if days < 1 {
+        return Err(code_error!(
+            InvalidArgument,
+            "Lifetime days must be a positive integer"
+        ));
+    }
+    if let Some(ref params) = request.glacier_job_parameters {
+        if !TIERS.contains(&params.tier.as_str()) {
+            return Err(code_error!(MalformedXML, MALFORMED_XML));
+        }
+    }
+    Ok(request.into())
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<RestoreObjectRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let (bucket, key) = (bucket.to_owned(), key.to_owned());
+    let restore_request = extract_restore_request(ctx.take_body()).await?;
+
+    let mut input = RestoreObjectRequest {
+        bucket,
+        key,
+        restore_request: Some(restore_request),
+        ..RestoreObjectRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+
+    if let Some(ref qs) = ctx.query_strings {
+        input.version_id = qs.get("versionId").map(ToOwned::to_owned);
+    }
+
+    Ok(input)
+}
+
+impl S3Output for RestoreObjectOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            let status = if self.already_in_progress {
+                StatusCode::OK
+            } else {
+                StatusCode::ACCEPTED
+            };
+            res.set_status(status);
+            res.set_optional_header(&*X_AMZ_REQUEST_CHARGED, self.request_charged)?;
+            res.set_optional_header(&*X_AMZ_RESTORE_OUTPUT_PATH, self.restore_output_path)?;
+            Ok(())
+        })
+    }
+}
+
+impl From<RestoreObjectError> for S3Error {
+    fn from(e: RestoreObjectError) -> Self {
+        match e {
+            RestoreObjectError::ObjectAlreadyInActiveTierError(msg) => {
+                Self::new(S3ErrorCode::ObjectAlreadyInActiveTierError, msg)
+            }
+        }
+    }
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `RestoreRequest`
+    #[derive(Debug, Deserialize)]
+    pub struct RestoreRequest {
+        /// Days
+        #[serde(rename = "Days")]
+        pub days: Option<i64>,
+        /// GlacierJobParameters
+        #[serde(rename = "GlacierJobParameters")]
+        pub glacier_job_parameters: Option<GlacierJobParameters>,
+    }
+
+    /// `GlacierJobParameters`
+    #[derive(Debug, Deserialize)]
+    pub struct GlacierJobParameters {
+        /// Tier
+        #[serde(rename = "Tier")]
+        pub tier: String,
+    }
+
+    impl From<RestoreRequest> for crate::dto::RestoreRequest {
+        fn from(request: RestoreRequest) -> Self {
+            Self {
+                days: request.days,
+                glacier_job_parameters: request
+                    .glacier_job_parameters
+                    .map(|params| crate::dto::GlacierJobParameters { tier: params.tier }),
+                ..Self::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    fn extract_body(body: &str) -> Result<crate::dto::RestoreRequest, S3ErrorCode> {
+        block_on(extract_restore_request(Body::from(body.to_owned()))).map_err(|err| err.code())
+    }
+
+    #[test]
+    fn restore_requests() {
+        let request = extract_body(
+            "<RestoreRequest><Days>3</Days><GlacierJobParameters><Tier>Bulk</Tier></GlacierJobParameters></RestoreRequest>",
+        )
+        .unwrap();
+        assert_eq!(request.days, Some(3));
+        assert_eq!(
+            request.glacier_job_parameters.map(|params| params.tier),
+            Some("Bulk".to_owned())
+        );
+
+        let request = extract_body("<RestoreRequest><Days>1</Days></RestoreRequest>").unwrap();
+        assert_eq!(request.glacier_job_parameters, None);
+
+        assert_eq!(
+            extract_body("<RestoreRequest><Days>0</Days></RestoreRequest>").unwrap_err(),
+            S3ErrorCode::InvalidArgument
+        );
+        for body in &[
+            "",
+            "<RestoreRequest></RestoreRequest>",
+            "<RestoreRequest><Days>ten</Days></RestoreRequest>",
+            "<RestoreRequest><Days>1</Days><GlacierJobParameters><Tier>Fast</Tier></GlacierJobParameters></RestoreRequest>",
+        ] {
+            assert_eq!(
+                extract_body(body).unwrap_err(),
+                S3ErrorCode::MalformedXML,
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn status_codes() {
+        let res = RestoreObjectOutput::default().try_into_response().unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        assert!(res.headers().get(&*X_AMZ_RESTORE_OUTPUT_PATH).is_none());
+
+        let output = RestoreObjectOutput {
+            already_in_progress: true,
+            restore_output_path: Some("restored/".to_owned()),
+            ..RestoreObjectOutput::default()
+        };
+        let res = output.try_into_response().unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[&*X_AMZ_RESTORE_OUTPUT_PATH], "restored/");
+    }
+}
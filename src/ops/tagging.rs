@@ -0,0 +1,197 @@
+//! `Tagging` documents, shared by the operations which get or put a tag set
+//!
+//! + [`GetBucketTagging`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketTagging.html)
+//! + [`PutBucketTagging`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketTagging.html)
+//!
+//! A tag set is rejected with `InvalidTag` if it has more tags than its resource allows, a duplicate key,
+//! an empty key, a key longer than 128 characters or a value longer than 256 characters.
+//! `GetObjectTagging` and `PutObjectTagging` are not implemented yet; they should (de)serialize their tag sets here too.
+
+use crate::dto::{Tag, Tagging};
+use crate::errors::S3Result;
+use crate::utils::body::deserialize_xml_body;
+use crate::utils::XmlWriterExt;
+use crate::Body;
+
+use std::collections::HashSet;
+use std::io;
+
+use ::xml::writer::EventWriter;
+
+/// the max number of tags of a bucket
+pub(super) const MAX_BUCKET_TAGS: usize = 50;
+
+/// the max number of characters of a tag key
+const MAX_KEY_CHARS: usize = 128;
+
+/// the max number of characters of a tag value
+const MAX_VALUE_CHARS: usize = 256;
+
+/// deserializes and validates a `Tagging` body of at most `max_tags` tags
+pub(super) async fn extract_tagging(body: Body, max_tags: usize) -> S3Result<Tagging> {
+    let tagging: xml::Tagging = deserialize_xml_body(body)
+        .await
+        .map_err(|err| code_error!(MalformedXML, "Invalid xml format", err))?;
+    let tag_set = tagging.tag_set.tags;
+
+    if tag_set.len() > max_tags {
+        return Err(code_error!(
+            InvalidTag,
+            "The TagSet has more tags than allowed"
+        ));
+    }
+    let mut keys = HashSet::with_capacity(tag_set.len());
+    for tag in &tag_set {
+        if tag.key.is_empty() || tag.key.chars().count() > MAX_KEY_CHARS {
+            return Err(code_error!(
+                InvalidTag,
+                "The TagKey you have provided is invalid"
+            ));
+        }
+        if tag.value.chars().count() > MAX_VALUE_CHARS {
+            return Err(code_error!(
+                InvalidTag,
+                "The TagValue you have provided is invalid"
+            ));
+        }
+        if !keys.insert(tag.key.as_str()) {
+            return Err(code_error!(
+                InvalidTag,
+                "Cannot provide multiple Tags with the same key"
+            ));
+        }
+    }
+
+    Ok(Tagging {
+        tag_set: tag_set.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// writes a `Tagging` document
+pub(super) fn write_tagging<W: io::Write>(
+    w: &mut EventWriter<W>,
+    tag_set: Vec<Tag>,
+) -> ::xml::writer::Result<()> {
+    w.stack("Tagging", |w| {
+        w.stack("TagSet", |w| {
+            w.iter_element(tag_set.into_iter(), |w, tag| {
+                w.stack("Tag", |w| {
+                    w.element("Key", &tag.key)?;
+                    w.element("Value", &tag.value)
+                })
+            })
+        })
+    })
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `Tag`
+    #[derive(Debug, Deserialize)]
+    pub struct Tag {
+        /// Key
+        #[serde(rename = "Key")]
+        pub key: String,
+        /// Value
+        #[serde(rename = "Value", default)]
+        pub value: String,
+    }
+
+    /// `TagSet`
+    #[derive(Debug, Deserialize)]
+    pub struct TagSet {
+        /// Tag
+        #[serde(rename = "Tag", default)]
+        pub tags: Vec<Tag>,
+    }
+
+    /// `Tagging`
+    #[derive(Debug, Deserialize)]
+    pub struct Tagging {
+        /// TagSet
+        #[serde(rename = "TagSet")]
+        pub tag_set: TagSet,
+    }
+
+    impl From<Tag> for super::Tag {
+        fn from(Tag { key, value }: Tag) -> Self {
+            Self { key, value }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+    use crate::utils::ResponseExt;
+    use crate::Response;
+
+    use futures::executor::block_on;
+
+    fn extract(body: &str) -> Result<Vec<(String, String)>, S3ErrorCode> {
+        let tagging = block_on(extract_tagging(Body::from(body.to_owned()), 2));
+        tagging
+            .map(|tagging| {
+                let tags = tagging.tag_set.into_iter();
+                tags.map(|tag| (tag.key, tag.value)).collect()
+            })
+            .map_err(|err| err.code())
+    }
+
+    fn tagging(tags: &str) -> String {
+        format!("<Tagging><TagSet>{}</TagSet></Tagging>", tags)
+    }
+
+    #[test]
+    fn round_trip() {
+        let body = tagging(
+            "<Tag><Key>team</Key><Value>storage</Value></Tag><Tag><Key>empty</Key><Value></Value></Tag>",
+        );
+        let tags = extract(&body).unwrap();
+        assert_eq!(
+            tags,
+            [
+                ("team".to_owned(), "storage".to_owned()),
+                ("empty".to_owned(), String::new())
+            ]
+        );
+
+        let tag_set = tags
+            .into_iter()
+            .map(|(key, value)| Tag { key, value })
+            .collect();
+        let mut res = Response::new(Body::empty());
+        res.set_xml_body(256, |w| write_tagging(w, tag_set))
+            .unwrap();
+        let written = block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        let expected = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>{}", body);
+        assert_eq!(String::from_utf8(written.to_vec()).unwrap(), expected);
+
+        assert_eq!(extract(&tagging("")), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn invalid() {
+        let tag = |key: &str, value: &str| {
+            format!("<Tag><Key>{}</Key><Value>{}</Value></Tag>", key, value)
+        };
+        let cases = vec![
+            tagging(&[tag("a", "1"), tag("b", "2"), tag("c", "3")].concat()),
+            tagging(&[tag("a", "1"), tag("a", "2")].concat()),
+            tagging(&tag("", "1")),
+            tagging(&tag(&"k".repeat(129), "1")),
+            tagging(&tag("a", &"v".repeat(257))),
+        ];
+        for body in &cases {
+            assert_eq!(extract(body), Err(S3ErrorCode::InvalidTag), "{}", body);
+        }
+        let longest = tagging(&tag(&"k".repeat(128), &"v".repeat(256)));
+        assert_eq!(extract(&longest).map(|tags| tags.len()), Ok(1));
+        assert_eq!(extract("<Tagging>"), Err(S3ErrorCode::MalformedXML));
+    }
+}
@@ -1,11 +1,11 @@
 //! [`UploadPart`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPart.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{content_md5, wrap_internal_error, ReqContext, S3Handler};
 
 use crate::dto::{UploadPartError, UploadPartOutput, UploadPartRequest};
 use crate::errors::{S3Error, S3Result};
 use crate::headers::{
-    CONTENT_LENGTH, CONTENT_MD5, ETAG, X_AMZ_REQUEST_CHARGED, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    CONTENT_MD5, ETAG, X_AMZ_COPY_SOURCE, X_AMZ_REQUEST_CHARGED, X_AMZ_SERVER_SIDE_ENCRYPTION,
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
     X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
 };
@@ -15,15 +15,20 @@ use crate::utils::body::transform_body_stream;
 use crate::utils::ResponseExt;
 use crate::{async_trait, Method, Response};
 
+/// the max number of parts of an upload
+pub(crate) const MAX_PART_NUMBER: i64 = 10000;
+
 /// `UploadPart` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
-        bool_try!(ctx.req.method() == Method::PUT);
+        bool_try!(ctx.method == Method::PUT);
         let qs = bool_try_some!(ctx.query_strings.as_ref());
-        qs.get("partNumber").is_some() && qs.get("uploadId").is_some()
+        bool_try!(qs.get("partNumber").is_some() && qs.get("uploadId").is_some());
+        // a part with a copy source is `UploadPartCopy`
+        ctx.headers.get(&*X_AMZ_COPY_SOURCE).is_none()
     }
 
     async fn handle(
@@ -31,8 +36,12 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
-        let input = extract(ctx)?;
-        let output = storage.upload_part(input).await;
+        let mut input = extract(ctx)?;
+        let digest_check = content_md5::verify(input.content_md5.as_deref(), &mut input.body)?;
+        let mut output = storage.upload_part(input).await;
+        if let Some(ref digest_check) = digest_check {
+            output = digest_check.check(output);
+        }
         output.try_into_response()
     }
 }
@@ -48,19 +57,17 @@ fn extract(
     // body: Body,
 ) -> S3Result<UploadPartRequest> {
     let (bucket, key) = ctx.unwrap_object_path();
+    let (bucket, key) = (bucket.to_owned(), key.to_owned());
 
-    let part_number = ctx
-        .unwrap_qs("partNumber")
-        .parse::<i64>()
-        .map_err(|err| invalid_request!("Invalid query: partNumber", err))?;
+    let part_number = parse_part_number(ctx.unwrap_qs("partNumber"))?;
 
     let upload_id = ctx.unwrap_qs("uploadId").to_owned();
 
     let body = transform_body_stream(ctx.take_body());
 
     let mut input = UploadPartRequest {
-        bucket: bucket.into(),
-        key: key.into(),
+        bucket,
+        key,
         part_number,
         upload_id,
         body: Some(body),
@@ -68,8 +75,7 @@ fn extract(
     };
 
     let h = &ctx.headers;
-    h.assign(CONTENT_LENGTH, &mut input.content_length)
-        .map_err(|err| invalid_request!("Invalid header: content-length", err))?;
+    input.content_length = ctx.content_length()?;
     h.assign_str(&*CONTENT_MD5, &mut input.content_md5);
     h.assign_str(
         &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
@@ -87,6 +93,18 @@ fn extract(
     Ok(input)
 }
 
+/// parses `partNumber`, which is an integer between 1 and 10000
+pub(super) fn parse_part_number(value: &str) -> S3Result<i64> {
+    const MESSAGE: &str = "Part number must be an integer between 1 and 10000, inclusive";
+    let part_number = value
+        .parse::<i64>()
+        .map_err(|err| code_error!(InvalidArgument, MESSAGE, err))?;
+    if !(1..=MAX_PART_NUMBER).contains(&part_number) {
+        return Err(code_error!(InvalidArgument, MESSAGE));
+    }
+    Ok(part_number)
+}
+
 impl S3Output for UploadPartOutput {
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
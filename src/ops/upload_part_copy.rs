@@ -0,0 +1,167 @@
+//! [`UploadPartCopy`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPartCopy.html)
+//!
+//! `PUT /bucket/key?partNumber=N&uploadId=X` with `x-amz-copy-source` copies a part from an existing object,
+//! optionally only the bytes of `x-amz-copy-source-range`.
+//! The copy source is passed to the storage like the copy source of `CopyObject`.
+//! A malformed range, or one which spans more than 5 GiB, is rejected before reaching the storage.
+
+use super::copy_object::extract_copy_source;
+use super::upload_part::parse_part_number;
+use super::{wrap_internal_error, ReqContext, S3Handler};
+
+use crate::dto::{UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest};
+use crate::errors::{S3Error, S3Result};
+use crate::headers::{
+    X_AMZ_COPY_SOURCE, X_AMZ_COPY_SOURCE_IF_MATCH, X_AMZ_COPY_SOURCE_IF_MODIFIED_SINCE,
+    X_AMZ_COPY_SOURCE_IF_NONE_MATCH, X_AMZ_COPY_SOURCE_IF_UNMODIFIED_SINCE,
+    X_AMZ_COPY_SOURCE_RANGE, X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
+    X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+    X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_COPY_SOURCE_VERSION_ID,
+    X_AMZ_EXPECTED_BUCKET_OWNER, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER,
+    X_AMZ_SERVER_SIDE_ENCRYPTION, X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_SOURCE_EXPECTED_BUCKET_OWNER,
+};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::storages::copy_source;
+use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::{async_trait, Method, Response};
+
+/// `UploadPartCopy` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.method == Method::PUT);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        bool_try!(qs.get("partNumber").is_some() && qs.get("uploadId").is_some());
+        ctx.headers.get(&*X_AMZ_COPY_SOURCE).is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.upload_part_copy(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<UploadPartCopyRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let part_number = parse_part_number(ctx.unwrap_qs("partNumber"))?;
+    let upload_id = ctx.unwrap_qs("uploadId").to_owned();
+    let copy_source = extract_copy_source(ctx)?.formatted;
+
+    let mut input = UploadPartCopyRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        part_number,
+        upload_id,
+        copy_source,
+        ..UploadPartCopyRequest::default()
+    };
+
+    let h = &ctx.headers;
+    h.assign_str(&*X_AMZ_COPY_SOURCE_RANGE, &mut input.copy_source_range);
+    if let Some(ref range) = input.copy_source_range {
+        let _range = copy_source::parse_range(range)?;
+    }
+
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_IF_MATCH,
+        &mut input.copy_source_if_match,
+    );
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_IF_MODIFIED_SINCE,
+        &mut input.copy_source_if_modified_since,
+    );
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_IF_NONE_MATCH,
+        &mut input.copy_source_if_none_match,
+    );
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_IF_UNMODIFIED_SINCE,
+        &mut input.copy_source_if_unmodified_since,
+    );
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
+        &mut input.copy_source_sse_customer_algorithm,
+    );
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+        &mut input.copy_source_sse_customer_key,
+    );
+    h.assign_str(
+        &*X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+        &mut input.copy_source_sse_customer_key_md5,
+    );
+    h.assign_str(
+        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
+        &mut input.sse_customer_algorithm,
+    );
+    h.assign_str(
+        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+        &mut input.sse_customer_key,
+    );
+    h.assign_str(
+        &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+        &mut input.sse_customer_key_md5,
+    );
+    h.assign_str(&*X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    h.assign_str(
+        &*X_AMZ_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_bucket_owner,
+    );
+    h.assign_str(
+        &*X_AMZ_SOURCE_EXPECTED_BUCKET_OWNER,
+        &mut input.expected_source_bucket_owner,
+    );
+
+    Ok(input)
+}
+
+impl S3Output for UploadPartCopyOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_optional_header(&*X_AMZ_COPY_SOURCE_VERSION_ID, self.copy_source_version_id)?;
+            res.set_optional_header(&*X_AMZ_SERVER_SIDE_ENCRYPTION, self.server_side_encryption)?;
+            res.set_optional_header(
+                &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
+                self.sse_customer_algorithm,
+            )?;
+            res.set_optional_header(
+                &*X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+                self.sse_customer_key_md5,
+            )?;
+            res.set_optional_header(
+                &*X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
+                self.ssekms_key_id,
+            )?;
+            res.set_optional_header(&*X_AMZ_REQUEST_CHARGED, self.request_charged)?;
+
+            let copy_part_result = self.copy_part_result;
+
+            res.set_xml_body(64, |w| {
+                w.opt_stack("CopyPartResult", copy_part_result, |w, result| {
+                    w.opt_element("ETag", result.e_tag)?;
+                    w.opt_element("LastModified", result.last_modified)
+                })
+            })?;
+
+            Ok(())
+        })
+    }
+}
+
+impl From<UploadPartCopyError> for S3Error {
+    fn from(e: UploadPartCopyError) -> Self {
+        match e {}
+    }
+}
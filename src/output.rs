@@ -1,6 +1,7 @@
 //! Types which can be converted into a response
 
 use crate::errors::{S3Error, S3Result, S3StorageError, S3StorageResult, XmlErrorResponse};
+use crate::headers::{CONTENT_RANGE, RETRY_AFTER};
 use crate::utils::{ResponseExt, XmlWriterExt};
 use crate::{Body, Response, StatusCode};
 
@@ -38,12 +39,42 @@ impl S3Output for XmlErrorResponse {
 
         let mut res = Response::new_with_status(Body::empty(), status);
 
+        res.set_optional_header(RETRY_AFTER, self.retry_after.map(|secs| secs.to_string()))
+            .map_err(|e| internal_error!(e))?;
+        res.set_optional_header(
+            CONTENT_RANGE,
+            self.actual_object_size
+                .map(|size| format!("bytes */{}", size)),
+        )
+        .map_err(|e| internal_error!(e))?;
+
         res.set_xml_body(64, |w| {
             w.stack("Error", |w| {
                 w.element("Code", self.code.as_static_str())?;
                 w.opt_element("Message", self.message)?;
-                // w.opt_element("Resource", self.resource)?;
-                // w.opt_element("RequestId", self.request_id)?;
+                w.opt_element("ProposedSize", self.proposed_size.map(|n| n.to_string()))?;
+                w.opt_element(
+                    "MaxSizeAllowed",
+                    self.max_size_allowed.map(|n| n.to_string()),
+                )?;
+                w.opt_element(
+                    "MinSizeAllowed",
+                    self.min_size_allowed.map(|n| n.to_string()),
+                )?;
+                w.opt_element("RangeRequested", self.range_requested)?;
+                w.opt_element(
+                    "ActualObjectSize",
+                    self.actual_object_size.map(|n| n.to_string()),
+                )?;
+                w.opt_element("RequestTime", self.request_time)?;
+                w.opt_element("ServerTime", self.server_time)?;
+                w.opt_element(
+                    "MaxAllowedSkewMilliseconds",
+                    self.max_allowed_skew_milliseconds.map(|n| n.to_string()),
+                )?;
+                w.opt_element("Region", self.region)?;
+                w.opt_element("Resource", self.resource)?;
+                w.opt_element("RequestId", self.request_id)?;
                 Ok(())
             })
         })
@@ -2,9 +2,15 @@
 //!
 //! + [Request styles](https://docs.aws.amazon.com/AmazonS3/latest/dev/RESTAPI.html#virtual-hosted-path-style-requests)
 //! + [Bucket nameing rules](https://docs.aws.amazon.com/AmazonS3/latest/dev/BucketRestrictions.html#bucketnamingrules)
+//! + [Object keys](https://docs.aws.amazon.com/AmazonS3/latest/dev/UsingMetadata.html#object-keys)
 
+use crate::utils::percent;
+
+use std::borrow::Cow;
 use std::net::IpAddr;
 
+use serde::Serialize;
+
 /// A path in the S3 storage
 #[derive(Debug)]
 #[allow(clippy::clippy::exhaustive_enums)]
@@ -20,11 +26,119 @@ pub enum S3Path<'a> {
     Object {
         /// Bucket name
         bucket: &'a str,
-        /// Object key
-        key: &'a str,
+        /// Object key (percent-decoded)
+        key: Cow<'a, str>,
     },
 }
 
+/// The max length of an object key in bytes
+const MAX_KEY_LEN: usize = 1024;
+
+/// The prefixes which bucket names must not start with
+const RESERVED_PREFIXES: &[&str] = &["xn--", "sthree-"];
+
+/// The suffixes which bucket names must not end with, which are reserved for access points
+const RESERVED_SUFFIXES: &[&str] = &["-s3alias", "--ol-s3"];
+
+/// How to store object keys which are not valid UTF-8 after percent-decoding
+///
+/// In the lossless mode, a key is stored with its invalid bytes and `%` kept as `%XX`,
+/// so that different byte sequences never map to the same key.
+/// Listings decode the stored keys: lossily by default, or losslessly with `encoding-type=url`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum KeyEncoding {
+    /// Rejects such keys with `InvalidURI` (default)
+    Strict,
+    /// Preserves the raw bytes of such keys
+    Lossless,
+}
+
+impl Default for KeyEncoding {
+    fn default() -> Self {
+        Self::Strict
+    }
+}
+
+impl KeyEncoding {
+    /// Decodes a percent-encoded key into its stored form.
+    /// Returns `None` if the key is malformed or rejected.
+    #[must_use]
+    pub fn decode(self, raw: &str) -> Option<Cow<'_, str>> {
+        if !raw.contains('%') {
+            return Some(Cow::Borrowed(raw));
+        }
+        let bytes = percent::decode(raw)?;
+        match self {
+            Self::Strict => String::from_utf8(bytes).ok().map(Cow::Owned),
+            Self::Lossless => Some(Cow::Owned(escape_lossless(&bytes))),
+        }
+    }
+
+    /// Converts a key received as text (a query string or a body field) into its stored form
+    #[must_use]
+    pub fn from_text(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Lossless if text.contains('%') => Cow::Owned(text.replace('%', "%25")),
+            Self::Strict | Self::Lossless => Cow::Borrowed(text),
+        }
+    }
+
+    /// Returns the raw bytes of a stored key
+    #[must_use]
+    pub fn to_bytes(self, stored: &str) -> Cow<'_, [u8]> {
+        match self {
+            Self::Lossless if stored.contains('%') => {
+                percent::decode(stored).map_or(Cow::Borrowed(stored.as_bytes()), Cow::Owned)
+            }
+            Self::Strict | Self::Lossless => Cow::Borrowed(stored.as_bytes()),
+        }
+    }
+
+    /// Formats a stored key for a listing,
+    /// url-encoded if `url_encoded` is true, or else with invalid sequences replaced by `U+FFFD`
+    #[must_use]
+    pub fn display(self, stored: &str, url_encoded: bool) -> String {
+        let bytes = self.to_bytes(stored);
+        if url_encoded {
+            percent::encode(&bytes)
+        } else {
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+    }
+}
+
+/// keeps valid UTF-8 except `%`, escapes the other bytes
+fn escape_lossless(mut bytes: &[u8]) -> String {
+    let mut ans = String::with_capacity(bytes.len());
+    loop {
+        let (valid, invalid) = match std::str::from_utf8(bytes) {
+            Ok(s) => (s, &[][..]),
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                let invalid_len = err.error_len().unwrap_or(rest.len());
+                let (invalid, rest) = rest.split_at(invalid_len);
+                bytes = rest;
+                (std::str::from_utf8(valid).unwrap_or(""), invalid)
+            }
+        };
+        for c in valid.chars() {
+            if c == '%' {
+                percent::push_escape(&mut ans, b'%');
+            } else {
+                ans.push(c);
+            }
+        }
+        if invalid.is_empty() {
+            break ans;
+        }
+        for &b in invalid {
+            percent::push_escape(&mut ans, b);
+        }
+    }
+}
+
 // Why allow `missing_copy_implementations` ?
 // 1. We can't yet guarantee that the error type is `Copy` in the future.
 // 2. A copyable error type is strange. `std::num::ParseIntError` is `Clone` but not `Copy`.
@@ -56,6 +170,8 @@ pub enum S3PathErrorKind {
     InvalidBucketName,
     /// The object key is too long
     KeyTooLong,
+    /// The object key is malformed or not valid UTF-8
+    InvalidKeyEncoding,
 }
 
 impl<'a> S3Path<'a> {
@@ -96,24 +212,40 @@ impl<'a> S3Path<'a> {
             return false;
         }
 
-        if name.starts_with("xn--") {
+        if name.contains("..") {
+            return false;
+        }
+
+        if RESERVED_PREFIXES.iter().any(|p| name.starts_with(p))
+            || RESERVED_SUFFIXES.iter().any(|s| name.ends_with(s))
+        {
             return false;
         }
 
         true
     }
 
-    /// The name for a key is a sequence of Unicode characters whose UTF-8 encoding is at most 1,024 bytes long.
+    /// The name for a key is a non-empty sequence of Unicode characters whose UTF-8 encoding is at most 1,024 bytes long.
     /// See [object keys](https://docs.aws.amazon.com/AmazonS3/latest/dev/UsingMetadata.html#object-keys)
     #[must_use]
     pub const fn check_key(key: &str) -> bool {
-        key.len() <= 1024
+        !key.is_empty() && key.len() <= MAX_KEY_LEN
     }
 
-    /// Parse a path-style request
+    /// Parse a path-style request, rejecting keys which are not valid UTF-8
     /// # Errors
     /// Returns an `Err` if the s3 path is invalid
     pub fn try_from_path(path: &'a str) -> Result<Self, ParseS3PathError> {
+        Self::try_from_path_with(path, KeyEncoding::Strict)
+    }
+
+    /// Parse a path-style request, decoding the key by `encoding`
+    /// # Errors
+    /// Returns an `Err` if the s3 path is invalid
+    pub fn try_from_path_with(
+        path: &'a str,
+        encoding: KeyEncoding,
+    ) -> Result<Self, ParseS3PathError> {
         if !path.starts_with('/') {
             return Err(ParseS3PathError {
                 kind: S3PathErrorKind::InvalidPath,
@@ -149,7 +281,47 @@ impl<'a> S3Path<'a> {
             Some(_) => &path[bucket.len().saturating_add(2)..],
         };
 
-        if !Self::check_key(key) {
+        Self::decode_object(bucket, key, encoding)
+    }
+
+    /// Parse a virtual-hosted-style request to `bucket`, whose whole path is the key
+    /// # Errors
+    /// Returns an `Err` if the s3 path is invalid
+    pub fn try_from_virtual_host_with(
+        bucket: &'a str,
+        path: &'a str,
+        encoding: KeyEncoding,
+    ) -> Result<Self, ParseS3PathError> {
+        if !Self::check_bucket_name(bucket) {
+            return Err(ParseS3PathError {
+                kind: S3PathErrorKind::InvalidBucketName,
+            });
+        }
+
+        let key = match path.strip_prefix('/') {
+            None => {
+                return Err(ParseS3PathError {
+                    kind: S3PathErrorKind::InvalidPath,
+                })
+            }
+            Some("") => return Ok(S3Path::Bucket { bucket }),
+            Some(key) => key,
+        };
+
+        Self::decode_object(bucket, key, encoding)
+    }
+
+    /// decodes the percent-encoded `key` of an object path
+    fn decode_object(
+        bucket: &'a str,
+        key: &'a str,
+        encoding: KeyEncoding,
+    ) -> Result<Self, ParseS3PathError> {
+        let key = encoding.decode(key).ok_or(ParseS3PathError {
+            kind: S3PathErrorKind::InvalidKeyEncoding,
+        })?;
+
+        if encoding.to_bytes(&key).len() > MAX_KEY_LEN {
             return Err(ParseS3PathError {
                 kind: S3PathErrorKind::KeyTooLong,
             });
@@ -177,6 +349,39 @@ impl<'a> S3Path<'a> {
     }
 }
 
+/// Returns the bucket of a virtual-hosted-style request to `host`, which is `<bucket>.<base_domain>`
+///
+/// The port of `host` is ignored, and the domains are compared case-insensitively.
+/// A request to `base_domain` itself or to another host is path-style.
+pub(crate) fn virtual_host_bucket<'h>(host: &'h str, base_domain: &str) -> Option<&'h str> {
+    let host = strip_port(host);
+    let bucket_len = host.len().checked_sub(base_domain.len())?;
+    let domain = host.get(bucket_len..)?;
+    if !domain.eq_ignore_ascii_case(base_domain) {
+        return None;
+    }
+    let bucket = host.get(..bucket_len)?.strip_suffix('.')?;
+    if bucket.is_empty() {
+        return None;
+    }
+    Some(bucket)
+}
+
+/// removes the port of a `Host` header, keeping an IPv6 address
+fn strip_port(host: &str) -> &str {
+    let mut parts = host.rsplitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(port), Some(name))
+            if !name.contains(':')
+                && !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,10 +405,15 @@ mod tests {
             S3Path::try_from_path("/bucket/dir/object"),
             Ok(S3Path::Object {
                 bucket: "bucket",
-                key: "dir/object"
+                key: Cow::Borrowed("dir/object")
             })
         ));
 
+        assert!(matches!(
+            S3Path::try_from_path("/bucket/dir/a%20b%C3%A9"),
+            Ok(S3Path::Object { bucket: "bucket", ref key }) if key == "dir/a b\u{e9}"
+        ));
+
         assert_eq!(
             S3Path::try_from_path("asd").unwrap_err().kind(),
             &S3PathErrorKind::InvalidPath
@@ -229,5 +439,109 @@ mod tests {
             S3Path::try_from_path(&too_long_path).unwrap_err().kind(),
             &S3PathErrorKind::KeyTooLong
         );
+
+        for path in &["/bucket/a%FFb", "/bucket/a%C3", "/bucket/a%2"] {
+            assert_eq!(
+                S3Path::try_from_path(path).unwrap_err().kind(),
+                &S3PathErrorKind::InvalidKeyEncoding
+            );
+        }
+    }
+
+    #[test]
+    fn bucket_names() {
+        let valid = [
+            "asd",
+            "my-bucket",
+            "my.dotted.bucket",
+            "1bucket",
+            "a-b-c-s3",
+            &"a".repeat(63),
+        ];
+        for name in &valid {
+            assert!(S3Path::check_bucket_name(name), "{}", name);
+        }
+
+        let invalid = [
+            "",
+            "as",
+            "..",
+            "a..b",
+            "-asd",
+            "asd-",
+            ".asd",
+            "asd.",
+            "Asd",
+            "a_b",
+            "192.168.5.4",
+            "xn--asd",
+            "sthree-asd",
+            "asd-s3alias",
+            "asd--ol-s3",
+            &"a".repeat(64),
+        ];
+        for name in &invalid {
+            assert!(!S3Path::check_bucket_name(name), "{}", name);
+        }
+
+        assert!(S3Path::check_key("a"));
+        assert!(S3Path::check_key(&"a".repeat(1024)));
+        assert!(!S3Path::check_key(""));
+        assert!(!S3Path::check_key(&"a".repeat(1025)));
+    }
+
+    #[test]
+    fn virtual_hosts() {
+        let base = "s3.example.com";
+        assert_eq!(virtual_host_bucket("asd.s3.example.com", base), Some("asd"));
+        assert_eq!(
+            virtual_host_bucket("my.dotted.bucket.s3.example.com:8014", base),
+            Some("my.dotted.bucket")
+        );
+        assert_eq!(virtual_host_bucket("asd.S3.Example.com", base), Some("asd"));
+        assert_eq!(virtual_host_bucket("s3.example.com", base), None);
+        assert_eq!(virtual_host_bucket("s3.example.com:8014", base), None);
+        assert_eq!(virtual_host_bucket(".s3.example.com", base), None);
+        assert_eq!(virtual_host_bucket("asds3.example.com", base), None);
+        assert_eq!(virtual_host_bucket("localhost:8014", base), None);
+        assert_eq!(virtual_host_bucket("[::1]:8014", base), None);
+
+        let parse = |path| S3Path::try_from_virtual_host_with("asd", path, KeyEncoding::Strict);
+        assert!(matches!(parse("/"), Ok(S3Path::Bucket { bucket: "asd" })));
+        assert!(matches!(
+            parse("/dir/a%20b"),
+            Ok(S3Path::Object { bucket: "asd", ref key }) if key == "dir/a b"
+        ));
+        // the whole path is the key, even if it looks like a path-style bucket
+        assert!(matches!(
+            parse("/qwe/"),
+            Ok(S3Path::Object { bucket: "asd", ref key }) if key == "qwe/"
+        ));
+        assert_eq!(
+            S3Path::try_from_virtual_host_with("ASD", "/", KeyEncoding::Strict)
+                .unwrap_err()
+                .kind(),
+            &S3PathErrorKind::InvalidBucketName
+        );
+    }
+
+    #[test]
+    fn lossless_key_encoding() {
+        let encoding = KeyEncoding::Lossless;
+
+        let decode = |raw| encoding.decode(raw).unwrap().into_owned();
+        assert_eq!(decode("a%20b"), "a b");
+        assert_eq!(decode("a%FFb"), "a%FFb");
+        assert_eq!(decode("100%25"), "100%25");
+        assert_eq!(decode("%C3%A9%C3"), "\u{e9}%C3");
+        assert!(encoding.decode("a%2").is_none());
+
+        assert_eq!(encoding.from_text("100%"), "100%25");
+        assert_eq!(KeyEncoding::Strict.from_text("100%"), "100%");
+
+        assert_eq!(&*encoding.to_bytes("a%FFb%25"), b"a\xffb%");
+        assert_eq!(encoding.display("a%FFb", false), "a\u{fffd}b");
+        assert_eq!(encoding.display("a%FFb", true), "a%FFb");
+        assert_eq!(KeyEncoding::Strict.display("a b", true), "a%20b");
     }
 }
@@ -0,0 +1,73 @@
+//! Verification of `x-amz-content-sha256`
+//!
+//! + A hex SHA256 is verified while the body is streamed to the handler, without buffering it.
+//!   A body whose digest does not match fails its request with `XAmzContentSHA256Mismatch` (400),
+//!   since the stream fails before the storage sees its end.
+//! + `STREAMING-AWS4-HMAC-SHA256-PAYLOAD` is decoded by the chunked decoder.
+//! + `UNSIGNED-PAYLOAD` is not verified.
+
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+use crate::headers::{AmzContentSha256, X_AMZ_CONTENT_SHA256};
+use crate::ops::ReqContext;
+use crate::service::take_io_body;
+use crate::streams::sha256_stream::{Sha256Stream, Sha256StreamError};
+use crate::Body;
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::stream::StreamExt;
+
+/// A request body whose SHA256 is verified, which records whether it does not match
+#[derive(Debug)]
+pub(crate) struct HashedBody {
+    /// whether the digest of the body does not match
+    mismatched: Arc<AtomicBool>,
+}
+
+impl HashedBody {
+    /// fails a handler whose body does not match its `x-amz-content-sha256`, even if it has succeeded
+    pub(crate) fn check<T>(&self, ret: S3Result<T>) -> S3Result<T> {
+        if !self.mismatched.load(Ordering::Acquire) {
+            return ret;
+        }
+        let mut builder = S3Error::from_code(S3ErrorCode::XAmzContentSHA256Mismatch).message(
+            "The provided 'x-amz-content-sha256' header does not match what was computed.",
+        );
+        if let Err(err) = ret {
+            if let Some(partial) = err.partial_upload().cloned() {
+                builder = builder.partial_upload(partial);
+            }
+            builder = builder.source(err);
+        }
+        Err(builder.finish())
+    }
+}
+
+/// verifies the body of a request whose `x-amz-content-sha256` is a hex SHA256
+pub(crate) fn verify(ctx: &mut ReqContext<'_>) -> Option<HashedBody> {
+    // the fields of a form have been read
+    if ctx.multipart.is_some() {
+        return None;
+    }
+    let value = ctx.headers.get(&*X_AMZ_CONTENT_SHA256)?;
+    let expected = match AmzContentSha256::from_header_str(value) {
+        Ok(AmzContentSha256::SingleChunk { payload_checksum }) => payload_checksum,
+        _ => return None,
+    };
+
+    let body = take_io_body(&mut ctx.body);
+    let mismatched: Arc<AtomicBool> = Arc::default();
+    let recorded = Arc::clone(&mismatched);
+    let body = Sha256Stream::new(body, expected).map(move |chunk| {
+        chunk.map_err(|e| {
+            if matches!(e, Sha256StreamError::Mismatch) {
+                recorded.store(true, Ordering::Release);
+            }
+            io::Error::new(io::ErrorKind::Other, e.to_string())
+        })
+    });
+    ctx.body = Body::wrap_stream(body);
+    Some(HashedBody { mismatched })
+}
@@ -0,0 +1,523 @@
+//! Policy documents of `POST` form uploads
+//!
+//! The `policy` field of a signed form is a base64 JSON document with an `expiration` and a list of `conditions`:
+//!
+//! + `{"<field>": "<value>"}` or `["eq", "$<field>", "<value>"]` requires a field to be `<value>`.
+//! + `["starts-with", "$<field>", "<prefix>"]` requires a field to start with `<prefix>`, which allows any value if it is empty.
+//! + `["content-length-range", <min>, <max>]` limits the size of the file.
+//!
+//! The field `bucket` is the bucket of the request, and a missing field is empty. Field names are case-insensitive.
+//! Every field but `policy`, `x-amz-signature`, `file` and the ones prefixed by `x-ignore-` must be named by a condition.
+//!
+//! A malformed policy is rejected with `InvalidPolicyDocument` (400), and an expired one or a failed condition with `AccessDenied` (403).
+//! A file outside of `content-length-range` fails its upload with `EntityTooSmall` or `EntityTooLarge` (400).
+
+use crate::dto::ByteStream;
+use crate::errors::{S3Error, S3ErrorCode, S3Result, S3StorageError, S3StorageResult};
+use crate::streams::multipart::Multipart;
+use crate::utils::Apply;
+
+use std::convert::TryFrom;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// the fields which are not named by conditions
+const EXEMPT_FIELDS: &[&str] = &["policy", "x-amz-signature", "file"];
+
+/// A condition of a policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    /// `eq`
+    Eq {
+        /// lowercase field name
+        field: String,
+        /// expected value
+        value: String,
+    },
+    /// `starts-with`
+    StartsWith {
+        /// lowercase field name
+        field: String,
+        /// expected prefix
+        prefix: String,
+    },
+    /// `content-length-range`
+    ContentLengthRange(ContentLengthRange),
+}
+
+/// The sizes which `content-length-range` allows for the file of a form upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ContentLengthRange {
+    /// min bytes
+    min: u64,
+    /// max bytes
+    max: u64,
+}
+
+/// A parsed policy document
+#[derive(Debug)]
+pub(crate) struct PostPolicy {
+    /// expiration
+    expiration: DateTime<Utc>,
+    /// conditions
+    conditions: Vec<Condition>,
+}
+
+/// json repr
+#[derive(Debug, Deserialize)]
+struct Document {
+    /// expiration
+    expiration: String,
+    /// conditions
+    #[serde(default)]
+    conditions: Vec<Value>,
+}
+
+/// the error of a malformed policy
+fn invalid_policy(message: &str) -> S3Error {
+    code_error!(
+        InvalidPolicyDocument,
+        format!("Invalid Policy: {}", message)
+    )
+}
+
+/// the error of a form which the policy does not allow
+fn denied(reason: &str) -> S3Error {
+    code_error!(
+        AccessDenied,
+        format!("Invalid according to Policy: {}", reason)
+    )
+}
+
+/// parses a field name of a condition, such as `$key`
+fn parse_field(field: &Value) -> Option<String> {
+    let field = field.as_str()?.strip_prefix('$')?;
+    Some(field.to_ascii_lowercase()).filter(|f| !f.is_empty())
+}
+
+/// parses a condition which is a JSON object or array
+fn parse_condition(condition: &Value, conditions: &mut Vec<Condition>) -> S3Result<()> {
+    if let Some(object) = condition.as_object() {
+        for (field, value) in object {
+            let value = value.as_str().ok_or_else(|| {
+                invalid_policy("Invalid Simple-Condition: value must be a string.")
+            })?;
+            conditions.push(Condition::Eq {
+                field: field.to_ascii_lowercase(),
+                value: value.to_owned(),
+            });
+        }
+        return Ok(());
+    }
+
+    let list = condition
+        .as_array()
+        .ok_or_else(|| invalid_policy("Condition must be an object or an array."))?;
+    let operator = list.first().and_then(Value::as_str).unwrap_or_default();
+    if list.len() != 3 {
+        return Err(invalid_policy(&format!(
+            "Invalid Condition: {}",
+            Value::Array(list.clone())
+        )));
+    }
+    let condition = match operator.to_ascii_lowercase().as_str() {
+        "eq" | "starts-with" => {
+            let field = list.get(1).and_then(parse_field).ok_or_else(|| {
+                invalid_policy("Condition field must be a string which starts with '$'.")
+            })?;
+            let value = list
+                .get(2)
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid_policy("Condition value must be a string."))?
+                .to_owned();
+            if operator.eq_ignore_ascii_case("eq") {
+                Condition::Eq { field, value }
+            } else {
+                Condition::StartsWith {
+                    field,
+                    prefix: value,
+                }
+            }
+        }
+        "content-length-range" => {
+            let bound = |i: usize| list.get(i).and_then(Value::as_u64);
+            match (bound(1), bound(2)) {
+                (Some(min), Some(max)) if min <= max => {
+                    Condition::ContentLengthRange(ContentLengthRange { min, max })
+                }
+                _ => {
+                    return Err(invalid_policy(
+                        "Invalid content-length-range: the bounds must be ordered integers.",
+                    ))
+                }
+            }
+        }
+        _ => {
+            return Err(invalid_policy(&format!(
+                "Invalid Condition: {}",
+                Value::Array(list.clone())
+            )))
+        }
+    };
+    conditions.push(condition);
+    Ok(())
+}
+
+impl PostPolicy {
+    /// Parses a base64 `policy` field
+    pub(crate) fn from_base64(policy: &str) -> S3Result<Self> {
+        let json = base64::decode(policy)
+            .ok()
+            .ok_or_else(|| invalid_policy("Policy is not base64 encoded."))?;
+        let document: Document = serde_json::from_slice(&json).map_err(|err| {
+            code_error!(InvalidPolicyDocument, "Invalid Policy: Invalid JSON.", err)
+        })?;
+
+        let expiration = DateTime::parse_from_rfc3339(&document.expiration)
+            .ok()
+            .ok_or_else(|| invalid_policy("Invalid 'expiration' value."))?
+            .with_timezone(&Utc);
+
+        let mut conditions = Vec::with_capacity(document.conditions.len());
+        for condition in &document.conditions {
+            parse_condition(condition, &mut conditions)?;
+        }
+        Ok(Self {
+            expiration,
+            conditions,
+        })
+    }
+
+    /// Checks the expiration and the conditions against the fields of a form to `bucket` at `now`,
+    /// and returns the sizes which the policy allows for the file
+    pub(crate) fn check(
+        &self,
+        bucket: &str,
+        multipart: &Multipart,
+        now: DateTime<Utc>,
+    ) -> S3Result<Option<ContentLengthRange>> {
+        if now > self.expiration {
+            return Err(denied("Policy expired."));
+        }
+
+        let field_value = |field: &str| -> &str {
+            if field == "bucket" {
+                return bucket;
+            }
+            multipart.find_field_value(field).unwrap_or_default()
+        };
+
+        let mut range = None;
+        for condition in &self.conditions {
+            let holds = match *condition {
+                Condition::Eq {
+                    ref field,
+                    ref value,
+                } => field_value(field) == value,
+                Condition::StartsWith {
+                    ref field,
+                    ref prefix,
+                } => field_value(field).starts_with(prefix.as_str()),
+                Condition::ContentLengthRange(r) => {
+                    range = Some(r);
+                    true
+                }
+            };
+            if !holds {
+                return Err(denied(&format!("Policy Condition failed: {}", condition)));
+            }
+        }
+
+        let is_named = |name: &str| {
+            self.conditions.iter().any(|condition| match *condition {
+                Condition::Eq { ref field, .. } | Condition::StartsWith { ref field, .. } => {
+                    field.eq_ignore_ascii_case(name)
+                }
+                Condition::ContentLengthRange(_) => false,
+            })
+        };
+        let extra: Vec<&str> = multipart
+            .fields
+            .iter()
+            .map(|&(ref name, _)| name.as_str())
+            .filter(|name| {
+                let lowercase = name.to_ascii_lowercase();
+                !EXEMPT_FIELDS.contains(&lowercase.as_str())
+                    && !lowercase.starts_with("x-ignore-")
+                    && !is_named(name)
+            })
+            .collect();
+        if !extra.is_empty() {
+            return Err(denied(&format!("Extra input fields: {}", extra.join(", "))));
+        }
+
+        Ok(range)
+    }
+}
+
+impl std::fmt::Display for Condition {
+    /// the condition in the format of the policy, as AWS reports it
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Eq {
+                ref field,
+                ref value,
+            } => write!(
+                f,
+                "[\"eq\", \"${}\", {}]",
+                field,
+                Value::from(value.as_str())
+            ),
+            Self::StartsWith {
+                ref field,
+                ref prefix,
+            } => write!(
+                f,
+                "[\"starts-with\", \"${}\", {}]",
+                field,
+                Value::from(prefix.as_str())
+            ),
+            Self::ContentLengthRange(ContentLengthRange { min, max }) => {
+                write!(f, "[\"content-length-range\", {}, {}]", min, max)
+            }
+        }
+    }
+}
+
+/// A size of a file which is outside of its [`ContentLengthRange`]
+#[derive(Debug, Clone, Copy)]
+enum Violation {
+    /// the size of the whole file
+    TooSmall(u64),
+    /// the bytes received when the file is cut
+    TooLarge(u64),
+}
+
+/// Limits the file of a form upload to a [`ContentLengthRange`]
+#[derive(Debug)]
+pub(crate) struct FileLimit {
+    /// the range
+    range: ContentLengthRange,
+    /// the size of a file outside of the range
+    violation: Arc<Mutex<Option<Violation>>>,
+}
+
+/// locks a mutex even if it is poisoned
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+impl ContentLengthRange {
+    /// counts a file while it is streamed, which fails after the max size or at its end if it is smaller than the min size
+    pub(crate) fn limit(self, body: &mut Option<ByteStream>) -> Option<FileLimit> {
+        let file = body.take()?;
+        let violation: Arc<Mutex<Option<Violation>>> = Arc::default();
+
+        let (recorded, at_end) = (Arc::clone(&violation), Arc::clone(&violation));
+        let received = Arc::new(AtomicU64::new(0));
+        let counted = Arc::clone(&received);
+        let max = self.max;
+        let min = self.min;
+        let file = file
+            .map(move |chunk| {
+                let chunk = chunk?;
+                let len = u64::try_from(chunk.len()).unwrap_or(u64::MAX);
+                let received = counted
+                    .fetch_add(len, Ordering::Relaxed)
+                    .saturating_add(len);
+                if received > max {
+                    *lock(&recorded) = Some(Violation::TooLarge(received));
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "The file exceeds the content-length-range of the policy",
+                    ));
+                }
+                Ok(chunk)
+            })
+            .chain(stream::once(future::ready(())).filter_map(move |()| {
+                let size = received.load(Ordering::Relaxed);
+                let ans = (size < min).then(|| {
+                    *lock(&at_end) = Some(Violation::TooSmall(size));
+                    Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "The file is smaller than the content-length-range of the policy",
+                    ))
+                });
+                future::ready(ans)
+            }))
+            .apply(ByteStream::new);
+
+        *body = Some(file);
+        Some(FileLimit {
+            range: self,
+            violation,
+        })
+    }
+}
+
+impl FileLimit {
+    /// replaces the error of a storage whose file is outside of the range
+    pub(crate) fn check<T, E>(&self, ret: S3StorageResult<T, E>) -> S3StorageResult<T, E> {
+        let violation = *lock(&self.violation);
+        let err = match violation {
+            None => return ret,
+            Some(Violation::TooSmall(size)) => S3Error::from_code(S3ErrorCode::EntityTooSmall)
+                .message("Your proposed upload is smaller than the minimum allowed size")
+                .proposed_size(size)
+                .min_size_allowed(self.range.min),
+            Some(Violation::TooLarge(size)) => S3Error::from_code(S3ErrorCode::EntityTooLarge)
+                .message("Your proposed upload exceeds the maximum allowed size")
+                .proposed_size(size)
+                .max_size_allowed(self.range.max),
+        };
+        ret.map_err(|_err| S3StorageError::Other(err.finish()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::streams::multipart;
+
+    use std::fmt::Write;
+
+    use futures::executor::block_on;
+    use hyper::body::Bytes;
+
+    fn form(fields: &[(&str, &str)]) -> Multipart {
+        let mut body = String::new();
+        for &(name, value) in fields {
+            write!(
+                body,
+                "--b\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                name, value
+            )
+            .unwrap();
+        }
+        body.push_str("--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b--\r\n");
+        let body = stream::iter(vec![Ok(Bytes::from(body))]);
+        block_on(multipart::transform_multipart(body, b"b")).unwrap()
+    }
+
+    fn policy(conditions: &str) -> PostPolicy {
+        let document = format!(
+            "{{\"expiration\": \"2030-01-01T00:00:00.000Z\", \"conditions\": [{}]}}",
+            conditions
+        );
+        PostPolicy::from_base64(&base64::encode(document)).unwrap()
+    }
+
+    fn check(policy: &PostPolicy, fields: &[(&str, &str)]) -> Result<(), String> {
+        let now = Utc::now();
+        let ans = policy.check("asd", &form(fields), now);
+        ans.map(|_| ()).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn conditions() {
+        let p = policy(concat!(
+            "{\"bucket\": \"asd\"}, [\"starts-with\", \"$key\", \"user/\"], ",
+            "[\"eq\", \"$Content-Type\", \"text/plain\"], [\"starts-with\", \"$x-amz-meta-tag\", \"\"], ",
+            "[\"content-length-range\", 1, 10]"
+        ));
+        let fields = [
+            ("key", "user/${filename}"),
+            ("content-type", "text/plain"),
+            ("x-amz-meta-tag", "any"),
+            ("policy", "ignored"),
+            ("x-ignore-me", "ignored"),
+        ];
+        assert_eq!(check(&p, &fields), Ok(()));
+        let range = p.check("asd", &form(&fields), Utc::now()).unwrap();
+        assert_eq!(range, Some(ContentLengthRange { min: 1, max: 10 }));
+
+        let err = check(&p, &[("key", "other/a"), ("content-type", "text/plain")]).unwrap_err();
+        assert!(err.contains("Invalid according to Policy: Policy Condition failed: [\"starts-with\", \"$key\", \"user/\"]"), "{}", err);
+        let err = check(&p, &[("key", "user/a")]).unwrap_err();
+        assert!(
+            err.contains("Policy Condition failed: [\"eq\", \"$content-type\", \"text/plain\"]"),
+            "{}",
+            err
+        );
+        let fields = [
+            ("key", "user/a"),
+            ("Content-Type", "text/plain"),
+            ("acl", "public-read"),
+        ];
+        let err = check(&p, &fields).unwrap_err();
+        assert!(
+            err.contains("Invalid according to Policy: Extra input fields: acl"),
+            "{}",
+            err
+        );
+        let other_bucket = form(&[("key", "user/a"), ("content-type", "text/plain")]);
+        let err = p.check("qwe", &other_bucket, Utc::now()).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::AccessDenied);
+    }
+
+    #[test]
+    fn expiration() {
+        let p = policy("");
+        let after = DateTime::parse_from_rfc3339("2030-01-01T00:00:01Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let err = p.check("asd", &form(&[]), after).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::AccessDenied);
+        assert!(err.to_string().contains("Policy expired."));
+    }
+
+    #[test]
+    fn invalid() {
+        let documents = [
+            "{\"conditions\": []}",
+            "{\"expiration\": \"tomorrow\"}",
+            "{\"expiration\": \"2030-01-01T00:00:00Z\", \"conditions\": [[\"eq\", \"key\", \"a\"]]}",
+            "{\"expiration\": \"2030-01-01T00:00:00Z\", \"conditions\": [[\"content-length-range\", 10, 1]]}",
+            "{\"expiration\": \"2030-01-01T00:00:00Z\", \"conditions\": [[\"in\", \"$key\", \"a\"]]}",
+            "{\"expiration\": \"2030-01-01T00:00:00Z\", \"conditions\": [{\"key\": 1}]}",
+        ];
+        let mut policies: Vec<String> = documents.iter().map(base64::encode).collect();
+        policies.push("not base64!".to_owned());
+        policies.push(base64::encode("not json"));
+        for policy in &policies {
+            let err = PostPolicy::from_base64(policy).unwrap_err();
+            assert_eq!(err.code(), S3ErrorCode::InvalidPolicyDocument, "{}", policy);
+        }
+    }
+
+    #[test]
+    fn file_limit() {
+        let read = |file: &[u8], min: u64, max: u64| {
+            let file = Bytes::copy_from_slice(file);
+            let mut body = Some(ByteStream::new(stream::iter(vec![Ok(file)])));
+            let limit = ContentLengthRange { min, max }.limit(&mut body).unwrap();
+            let ret = block_on(
+                body.unwrap()
+                    .map(|chunk| chunk.map(|_| ()))
+                    .collect::<Vec<_>>(),
+            );
+            let ret: S3StorageResult<(), ()> = if ret.iter().all(Result::is_ok) {
+                Ok(())
+            } else {
+                Err(S3StorageError::Operation(()))
+            };
+            limit.check(ret).map_err(|err| match err {
+                S3StorageError::Other(err) => err.code(),
+                S3StorageError::Operation(()) => S3ErrorCode::InternalError,
+            })
+        };
+        assert_eq!(read(b"hello", 1, 5), Ok(()));
+        assert_eq!(read(b"hello", 6, 10), Err(S3ErrorCode::EntityTooSmall));
+        assert_eq!(read(b"hello", 0, 4), Err(S3ErrorCode::EntityTooLarge));
+    }
+}
@@ -0,0 +1,130 @@
+//! Redirection of large downloads to presigned URLs of the storage
+
+use crate::dto::HeadObjectRequest;
+use crate::errors::S3Result;
+use crate::headers::{CACHE_CONTROL, LOCATION, RANGE};
+use crate::ops::ReqContext;
+use crate::path::S3Path;
+use crate::storage::S3Storage;
+use crate::{Body, Method, Response, StatusCode};
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use hyper::header::HeaderValue;
+use serde::{Serialize, Serializer};
+
+/// Options of redirect offload, see [`S3Service::set_redirect_offload`](crate::S3Service::set_redirect_offload)
+///
+/// A `GetObject` of an object which is at least `min_size` bytes, or which is in one of `buckets`,
+/// is answered with `307 Temporary Redirect` to the URL returned by
+/// [`S3Storage::presign_get`], after the request is authenticated.
+/// The object is streamed as usual if the storage returns no URL.
+///
+/// Requests with `versionId` or `response-*` query strings are never redirected,
+/// because the URL names the current object without overrides.
+///
+/// It is serialized with the expiry in seconds, as `expiry_secs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedirectOffload {
+    /// min bytes of a redirected object, or `None` to redirect by bucket only
+    pub min_size: Option<u64>,
+    /// buckets whose objects are redirected at any size
+    pub buckets: BTreeSet<String>,
+    /// lifetime of the URLs, 30 seconds by default
+    #[serde(rename = "expiry_secs", serialize_with = "serialize_secs")]
+    pub expiry: Duration,
+    /// whether `HeadObject` is redirected too
+    pub redirect_head: bool,
+    /// whether requests with `Range` or `partNumber` are redirected too
+    pub redirect_range: bool,
+}
+
+impl Default for RedirectOffload {
+    fn default() -> Self {
+        Self {
+            min_size: None,
+            buckets: BTreeSet::new(),
+            expiry: Duration::from_secs(30),
+            redirect_head: false,
+            redirect_range: false,
+        }
+    }
+}
+
+/// serializes the expiry in seconds
+fn serialize_secs<S: Serializer>(expiry: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(expiry.as_secs())
+}
+
+impl RedirectOffload {
+    /// returns the redirect of a `GetObject` or `HeadObject` request which is offloaded
+    pub(crate) async fn check(
+        &self,
+        operation: &str,
+        ctx: &ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Option<Response>> {
+        let (bucket, key) = if let S3Path::Object { bucket, ref key } = ctx.path {
+            (bucket, key.as_ref())
+        } else {
+            return Ok(None);
+        };
+        if !self.is_eligible(operation, ctx) {
+            return Ok(None);
+        }
+
+        if !self.buckets.contains(bucket) {
+            let min_size = match self.min_size {
+                Some(min_size) => min_size,
+                None => return Ok(None),
+            };
+            let input = HeadObjectRequest {
+                bucket: bucket.to_owned(),
+                key: key.to_owned(),
+                ..HeadObjectRequest::default()
+            };
+            // an error is reported by the handler
+            let size = match storage.head_object(input).await {
+                Ok(output) => output.content_length.and_then(|n| u64::try_from(n).ok()),
+                Err(_) => None,
+            };
+            if size.map_or(true, |size| size < min_size) {
+                return Ok(None);
+            }
+        }
+
+        let url = match storage.presign_get(bucket, key, self.expiry).await? {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let location = HeaderValue::from_str(&url).map_err(|e| internal_error!(e))?;
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::TEMPORARY_REDIRECT;
+        let _prev = res.headers_mut().insert(LOCATION, location);
+        // the URL expires
+        let _prev = res
+            .headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        Ok(Some(res))
+    }
+
+    /// whether the request may be redirected before looking at the object
+    fn is_eligible(&self, operation: &str, ctx: &ReqContext<'_>) -> bool {
+        bool_try!(operation == "GetObject" || operation == "HeadObject");
+        bool_try!(self.redirect_head || ctx.req.method() != Method::HEAD);
+
+        let qs = ctx.query_strings.as_ref();
+        let has_range =
+            ctx.headers.get(RANGE).is_some() || qs.map_or(false, |qs| qs.contains("partNumber"));
+        bool_try!(self.redirect_range || !has_range);
+
+        qs.map_or(true, |qs| {
+            qs.as_ref()
+                .iter()
+                .all(|&(ref name, _)| name != "versionId" && !name.starts_with("response-"))
+        })
+    }
+}
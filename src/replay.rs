@@ -0,0 +1,556 @@
+//! Recording and replaying of raw S3 traffic
+//!
+//! A [`Recorder`] writes each exchange of an [`S3Service`](crate::S3Service) to a directory,
+//! one `NNNNNNNN.s3rec` file per request, numbered in arrival order.
+//! [`replay`] feeds a recorded request back through a service and [`Exchange::diff`] compares the responses.
+//!
+//! ## Format
+//!
+//! A file starts with the magic `S3REC\x01`, followed by the request section and the response section.
+//! All integers are big-endian. A field is a `u32` length followed by the bytes.
+//!
+//! + request section: method field, uri field, headers, body
+//! + response section: status `u32`, headers, body
+//! + headers: `u32` count, then a name field and a value field for each header
+//! + body: the original length `u64`, then a field of at most `body_limit` bytes.
+//!   A body is truncated if the field is shorter than the original length.
+//!
+//! A `NNNNNNNN.expected` file holds a response section without the magic.
+//! If it exists, replays are compared with it instead of the recorded response,
+//! which turns the recording of a bug into a test of its fix.
+//!
+//! `Authorization` is recorded as `REDACTED`.
+
+use crate::{Body, BoxStdError, Request, Response, S3Service};
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use hyper::body::Bytes;
+use hyper::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use hyper::http::{request, response};
+use tracing::error;
+
+/// magic of a recording file
+const MAGIC: &[u8] = b"S3REC\x01";
+
+/// recorded value of `Authorization`
+pub const REDACTED: &[u8] = b"REDACTED";
+
+/// response headers which are not compared
+const VOLATILE_HEADERS: &[&str] = &["date", "last-modified", "x-amz-request-id", "x-amz-id-2"];
+
+/// xml elements whose text is not compared
+const VOLATILE_ELEMENTS: &[&str] = &["LastModified", "CreationDate", "Initiated", "RequestId"];
+
+/// A recorded body
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordedBody {
+    /// the recorded prefix
+    pub bytes: Vec<u8>,
+    /// the original length
+    pub len: u64,
+}
+
+impl RecordedBody {
+    /// Records `bytes` truncated to `limit` bytes
+    fn new(bytes: &[u8], limit: usize) -> Self {
+        let len = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+        let bytes = bytes.get(..limit).unwrap_or(bytes).to_vec();
+        Self { bytes, len }
+    }
+
+    /// Checks whether the body was truncated
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        u64::try_from(self.bytes.len()).map_or(true, |n| n < self.len)
+    }
+}
+
+/// A recorded request and its response
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Exchange {
+    /// request method
+    pub method: String,
+    /// request uri
+    pub uri: String,
+    /// request headers in order
+    pub request_headers: Vec<(String, Vec<u8>)>,
+    /// request body
+    pub request_body: RecordedBody,
+    /// response status
+    pub status: u16,
+    /// response headers in order
+    pub response_headers: Vec<(String, Vec<u8>)>,
+    /// response body
+    pub response_body: RecordedBody,
+}
+
+impl Exchange {
+    /// Encodes the exchange in the recording format
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = MAGIC.to_vec();
+        put_field(&mut buf, self.method.as_bytes());
+        put_field(&mut buf, self.uri.as_bytes());
+        put_headers(&mut buf, &self.request_headers);
+        put_body(&mut buf, &self.request_body);
+        self.encode_response_into(&mut buf);
+        buf
+    }
+
+    /// Encodes the response section
+    #[must_use]
+    pub fn encode_response(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_response_into(&mut buf);
+        buf
+    }
+
+    /// Decodes an exchange
+    /// # Errors
+    /// Returns an `Err` if `src` is not in the recording format
+    pub fn decode(src: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader(
+            src.strip_prefix(MAGIC)
+                .ok_or_else(|| invalid_data("missing magic"))?,
+        );
+        let mut ans = Self {
+            method: reader.string()?,
+            uri: reader.string()?,
+            request_headers: reader.headers()?,
+            request_body: reader.body()?,
+            ..Self::default()
+        };
+        ans.decode_response(reader.0)?;
+        Ok(ans)
+    }
+
+    /// Replaces the response with a decoded response section
+    /// # Errors
+    /// Returns an `Err` if `src` is not a response section
+    pub fn decode_response(&mut self, src: &[u8]) -> io::Result<()> {
+        let mut reader = Reader(src);
+        let status = u16::try_from(reader.u32()?).map_err(|_err| invalid_data("invalid status"))?;
+        let headers = reader.headers()?;
+        let body = reader.body()?;
+        if !reader.0.is_empty() {
+            return Err(invalid_data("trailing bytes"));
+        }
+        self.status = status;
+        self.response_headers = headers;
+        self.response_body = body;
+        Ok(())
+    }
+
+    /// encodes the response section into `buf`
+    fn encode_response_into(&self, buf: &mut Vec<u8>) {
+        put_u32(buf, u32::from(self.status));
+        put_headers(buf, &self.response_headers);
+        put_body(buf, &self.response_body);
+    }
+
+    /// Builds the recorded request for a service without auth
+    ///
+    /// `Authorization` is removed and a signed payload hash becomes `UNSIGNED-PAYLOAD`.
+    /// Bodies with chunk signatures (`STREAMING-*`) can not be replayed without the secret key.
+    /// # Errors
+    /// Returns an `Err` if the recorded request is invalid
+    pub fn to_request(&self) -> Result<Request, BoxStdError> {
+        let mut builder = request::Builder::new()
+            .method(self.method.as_str())
+            .uri(self.uri.as_str());
+        for &(ref name, ref value) in &self.request_headers {
+            let name = HeaderName::from_bytes(name.as_bytes())?;
+            if name == AUTHORIZATION {
+                continue;
+            }
+            let is_signed_payload = name == "x-amz-content-sha256"
+                && value.len() == 64
+                && value.iter().all(u8::is_ascii_hexdigit);
+            let value = if is_signed_payload {
+                HeaderValue::from_static("UNSIGNED-PAYLOAD")
+            } else {
+                HeaderValue::from_bytes(value)?
+            };
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(Body::from(self.request_body.bytes.clone()))?)
+    }
+
+    /// Compares the response of a replay with the recorded response
+    ///
+    /// `Date`, `Last-Modified`, request ids and timestamps in xml bodies are ignored.
+    /// A truncated body is compared by its prefix.
+    /// Returns the differences, which are empty if the responses match.
+    #[must_use]
+    pub fn diff(&self, actual: &Self) -> Vec<String> {
+        let mut ans = Vec::new();
+        if self.status != actual.status {
+            ans.push(format!("status: {} != {}", self.status, actual.status));
+        }
+        for &(ref name, ref value) in &self.response_headers {
+            if VOLATILE_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            let found = actual
+                .response_headers
+                .iter()
+                .find(|&&(ref n, _)| n == name)
+                .map(|&(_, ref v)| v);
+            if found != Some(value) {
+                ans.push(format!(
+                    "header {}: {:?} != {:?}",
+                    name,
+                    String::from_utf8_lossy(value),
+                    found.map(|v| String::from_utf8_lossy(v))
+                ));
+            }
+        }
+        let expected_body = mask_timestamps(&self.response_body.bytes);
+        let actual_body = mask_timestamps(&actual.response_body.bytes);
+        let body_matches = if self.response_body.is_truncated() {
+            actual_body.starts_with(&expected_body)
+        } else {
+            actual_body == expected_body
+        };
+        if !body_matches {
+            ans.push(format!(
+                "body: {:?} != {:?}",
+                String::from_utf8_lossy(&expected_body),
+                String::from_utf8_lossy(&actual_body)
+            ));
+        }
+        ans
+    }
+}
+
+/// Records the exchanges of a service to a directory
+#[derive(Debug)]
+pub struct Recorder {
+    /// output directory
+    dir: PathBuf,
+    /// max recorded bytes of a body
+    body_limit: usize,
+    /// number of the next recording
+    next: AtomicU64,
+}
+
+impl Recorder {
+    /// Records to `dir`, truncating bodies to `body_limit` bytes
+    ///
+    /// `dir` is created if it does not exist. New recordings are numbered after the existing ones.
+    /// # Errors
+    /// Returns an `Err` if `dir` can not be created or read
+    pub fn new(dir: impl Into<PathBuf>, body_limit: usize) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let count = list_recordings(&dir)?.len();
+        Ok(Self {
+            dir,
+            body_limit,
+            next: AtomicU64::new(u64::try_from(count).unwrap_or(u64::MAX)),
+        })
+    }
+
+    /// Buffers the request body and records the request
+    pub(crate) async fn capture_request(
+        &self,
+        req: Request,
+    ) -> Result<(Request, Exchange), BoxStdError> {
+        #[allow(clippy::disallowed_methods)] // the whole body is read before it is tracked
+        let (parts, body) = req.into_parts();
+        let bytes = hyper::body::to_bytes(body).await?;
+        let exchange = Exchange {
+            method: parts.method.as_str().to_owned(),
+            uri: parts.uri.to_string(),
+            request_headers: record_headers(&parts.headers, true),
+            request_body: RecordedBody::new(&bytes, self.body_limit),
+            ..Exchange::default()
+        };
+        Ok((Request::from_parts(parts, Body::from(bytes)), exchange))
+    }
+
+    /// Buffers the response body and writes the exchange
+    pub(crate) async fn finish(
+        &self,
+        mut exchange: Exchange,
+        resp: Response,
+    ) -> Result<Response, BoxStdError> {
+        let (parts, body) = resp.into_parts();
+        let bytes: Bytes = hyper::body::to_bytes(body).await?;
+        exchange.status = parts.status.as_u16();
+        exchange.response_headers = record_headers(&parts.headers, false);
+        exchange.response_body = RecordedBody::new(&bytes, self.body_limit);
+
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(format!("{:08}.s3rec", seq));
+        if let Err(err) = async_fs::write(&path, exchange.encode()).await {
+            error!(%err, path = %path.display(), "failed to write recording");
+        }
+        Ok(response::Response::from_parts(parts, Body::from(bytes)))
+    }
+}
+
+/// Loads the recordings of a directory in order
+///
+/// The response of an exchange is replaced by its `.expected` file if there is one.
+/// # Errors
+/// Returns an `Err` if a recording can not be read or decoded
+pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<(PathBuf, Exchange)>> {
+    let mut ans = Vec::new();
+    for path in list_recordings(dir.as_ref())? {
+        let mut exchange = Exchange::decode(&fs::read(&path)?)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        let expected_path = path.with_extension("expected");
+        if expected_path.exists() {
+            exchange.decode_response(&fs::read(&expected_path)?)?;
+        }
+        ans.push((path, exchange));
+    }
+    Ok(ans)
+}
+
+/// Sends a recorded request through `service` and returns the replayed exchange
+/// # Errors
+/// Returns an `Err` if the request is invalid or the service fails
+pub async fn replay(service: &S3Service, exchange: &Exchange) -> Result<Exchange, BoxStdError> {
+    let resp = service.hyper_call(exchange.to_request()?).await?;
+    let (parts, body) = resp.into_parts();
+    let bytes = hyper::body::to_bytes(body).await?;
+    Ok(Exchange {
+        status: parts.status.as_u16(),
+        response_headers: record_headers(&parts.headers, false),
+        response_body: RecordedBody::new(&bytes, usize::MAX),
+        ..exchange.clone()
+    })
+}
+
+/// Copies the fixture directory `fixture` into `root`, which becomes the root of a replay backend
+/// # Errors
+/// Returns an `Err` if the fixture can not be copied
+pub fn seed_root(fixture: impl AsRef<Path>, root: impl AsRef<Path>) -> io::Result<()> {
+    let root = root.as_ref();
+    fs::create_dir_all(root)?;
+    for entry in fs::read_dir(fixture)? {
+        let entry = entry?;
+        let dst = root.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            seed_root(entry.path(), dst)?;
+        } else {
+            let _len = fs::copy(entry.path(), dst)?;
+        }
+    }
+    Ok(())
+}
+
+/// lists the `.s3rec` files of `dir` by name
+fn list_recordings(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut ans = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "s3rec") {
+            ans.push(path);
+        }
+    }
+    ans.sort();
+    Ok(ans)
+}
+
+/// records headers, redacting `Authorization`
+fn record_headers(headers: &hyper::HeaderMap, redact: bool) -> Vec<(String, Vec<u8>)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if redact && *name == AUTHORIZATION {
+                REDACTED
+            } else {
+                value.as_bytes()
+            };
+            (name.as_str().to_owned(), value.to_vec())
+        })
+        .collect()
+}
+
+/// replaces the text of volatile elements, such as timestamps and request ids, with `*`
+fn mask_timestamps(body: &[u8]) -> Vec<u8> {
+    let mut ans = body.to_vec();
+    for &element in VOLATILE_ELEMENTS {
+        let open = format!("<{}>", element);
+        let close = format!("</{}>", element);
+        let mut masked = Vec::with_capacity(ans.len());
+        let mut remain = ans.as_slice();
+        while let Some(start) = find(remain, open.as_bytes()) {
+            let (before, after) = remain.split_at(start.saturating_add(open.len()));
+            masked.extend_from_slice(before);
+            match find(after, close.as_bytes()) {
+                Some(end) => {
+                    masked.push(b'*');
+                    remain = after.get(end..).unwrap_or_default();
+                }
+                None => remain = after,
+            }
+        }
+        masked.extend_from_slice(remain);
+        ans = masked;
+    }
+    ans
+}
+
+/// finds the first occurrence of `needle`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// an `InvalidData` error
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// puts a big-endian `u32`
+fn put_u32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+/// puts a length-prefixed field
+fn put_field(buf: &mut Vec<u8>, bytes: &[u8]) {
+    let len = u32::try_from(bytes.len()).unwrap_or(u32::MAX);
+    put_u32(buf, len);
+    let bytes = bytes
+        .get(..usize::try_from(len).unwrap_or(usize::MAX))
+        .unwrap_or(bytes);
+    buf.extend_from_slice(bytes);
+}
+
+/// puts headers
+fn put_headers(buf: &mut Vec<u8>, headers: &[(String, Vec<u8>)]) {
+    put_u32(buf, u32::try_from(headers.len()).unwrap_or(u32::MAX));
+    for &(ref name, ref value) in headers {
+        put_field(buf, name.as_bytes());
+        put_field(buf, value);
+    }
+}
+
+/// puts a body
+fn put_body(buf: &mut Vec<u8>, body: &RecordedBody) {
+    buf.extend_from_slice(&body.len.to_be_bytes());
+    put_field(buf, &body.bytes);
+}
+
+/// reads the recording format
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// takes `n` bytes
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(invalid_data("unexpected end of recording"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    /// reads a big-endian `u32`
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    /// reads a big-endian `u64`
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    /// reads a field
+    fn field(&mut self) -> io::Result<&'a [u8]> {
+        let len = usize::try_from(self.u32()?).map_err(|_err| invalid_data("field too long"))?;
+        self.take(len)
+    }
+
+    /// reads a utf-8 field
+    fn string(&mut self) -> io::Result<String> {
+        let bytes = self.field()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_err| invalid_data("invalid utf-8"))
+    }
+
+    /// reads headers
+    fn headers(&mut self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let count = self.u32()?;
+        let mut ans = Vec::new();
+        for _ in 0..count {
+            let name = self.string()?;
+            let value = self.field()?.to_vec();
+            ans.push((name, value));
+        }
+        Ok(ans)
+    }
+
+    /// reads a body
+    fn body(&mut self) -> io::Result<RecordedBody> {
+        let len = self.u64()?;
+        let bytes = self.field()?.to_vec();
+        Ok(RecordedBody { bytes, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange() -> Exchange {
+        Exchange {
+            method: "PUT".into(),
+            uri: "/asd/qwe".into(),
+            request_headers: vec![("authorization".into(), REDACTED.to_vec())],
+            request_body: RecordedBody::new(b"Hello World!", 5),
+            status: 200,
+            response_headers: vec![
+                ("etag".into(), b"\"etag\"".to_vec()),
+                ("date".into(), b"Sat, 01 May 2021 00:00:00 GMT".to_vec()),
+            ],
+            response_body: RecordedBody::new(
+                b"<a><LastModified>2021-05-01T00:00:00Z</LastModified></a>",
+                usize::MAX,
+            ),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let exchange = exchange();
+        assert!(exchange.request_body.is_truncated());
+        assert_eq!(exchange.request_body.bytes, b"Hello");
+        assert!(!exchange.response_body.is_truncated());
+
+        let bytes = exchange.encode();
+        assert_eq!(Exchange::decode(&bytes).unwrap(), exchange);
+        let _err = Exchange::decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        let _err = Exchange::decode(&bytes[1..]).unwrap_err();
+
+        let mut other = Exchange::default();
+        other.decode_response(&exchange.encode_response()).unwrap();
+        assert_eq!(other.response_body, exchange.response_body);
+    }
+
+    #[test]
+    fn diff() {
+        let recorded = exchange();
+        let mut actual = exchange();
+        actual.response_headers[1].1 = b"Sun, 02 May 2021 00:00:00 GMT".to_vec();
+        actual.response_body.bytes =
+            b"<a><LastModified>2021-05-02T00:00:00Z</LastModified></a>".to_vec();
+        assert!(recorded.diff(&actual).is_empty());
+
+        actual.status = 404;
+        let _etag = actual.response_headers.remove(0);
+        actual.response_body.bytes = b"<a></a>".to_vec();
+        assert_eq!(recorded.diff(&actual).len(), 3);
+    }
+}
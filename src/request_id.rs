@@ -0,0 +1,208 @@
+//! Request ids and correlation ids
+//!
+//! Every response carries `x-amz-request-id` and `x-amz-id-2`, which the service generates for each request.
+//! The request id is recorded in the tracing span of the request, rendered as the `RequestId` of error responses,
+//! and storages get it by [`RequestIds::current`].
+//! A client or a proxy may also send a correlation id in a configured header, such as `X-Request-Id` or `traceparent`.
+//! It is recorded in the tracing span of the request and in audit entries, and optionally echoed back,
+//! but it is never used as the request id: a client can send any value, and the same value many times.
+
+use crate::utils::scope::{self, Scoped};
+use crate::Request;
+
+use std::cell::RefCell;
+
+use hyper::header::{HeaderName, HeaderValue};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// max length of a correlation id by default
+const DEFAULT_MAX_LEN: usize = 128;
+
+/// The ids of a request, inserted into the request as an extension by [`S3Service::hyper_call`](crate::S3Service::hyper_call)
+///
+/// Storages get the ids of the current request by [`RequestIds::current`], so that their logs can be matched with the responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_field_names)] // they are all ids
+pub struct RequestIds {
+    /// `x-amz-request-id` of the response
+    pub(crate) request_id: String,
+    /// `x-amz-id-2` of the response
+    pub(crate) extended_request_id: String,
+    /// the correlation id sent by the client
+    pub(crate) correlation_id: Option<String>,
+}
+
+thread_local! {
+    /// the ids of the request being polled on this thread
+    static CURRENT: RefCell<Option<RequestIds>> = RefCell::new(None);
+}
+
+impl RequestIds {
+    /// generates a request id, and takes the correlation id of `req`
+    pub(crate) fn new(req: &Request, correlation: Option<&CorrelationConfig>) -> Self {
+        let mut request_id = Uuid::new_v4().to_simple().to_string();
+        request_id.truncate(16);
+        request_id.make_ascii_uppercase();
+        let mut extended_request_id = Vec::with_capacity(32);
+        extended_request_id.extend_from_slice(Uuid::new_v4().as_bytes());
+        extended_request_id.extend_from_slice(Uuid::new_v4().as_bytes());
+        Self {
+            request_id,
+            extended_request_id: base64::encode(extended_request_id),
+            correlation_id: correlation.and_then(|config| config.extract(req)),
+        }
+    }
+
+    /// Returns the ids of the request being handled, or `None` outside of a request
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        scope::current(&CURRENT)
+    }
+
+    /// Returns `x-amz-request-id`, which is 16 uppercase hex digits
+    #[must_use]
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// Returns `x-amz-id-2`, which is 32 random bytes in base64
+    #[must_use]
+    pub fn extended_request_id(&self) -> &str {
+        &self.extended_request_id
+    }
+
+    /// Returns the correlation id sent by the client, see [`CorrelationConfig`]
+    #[must_use]
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Makes the ids current while polling `future`
+    pub(crate) const fn scope<F>(self, future: F) -> Scoped<Self, F> {
+        scope::scope(&CURRENT, self, future)
+    }
+}
+
+/// Options of correlation ids, see [`S3Service::set_correlation`](crate::S3Service::set_correlation)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CorrelationConfig {
+    /// the request header in lowercase
+    header: String,
+    /// the response header which echoes the id, in lowercase
+    echo: Option<String>,
+    /// max length of an id, beyond which it is truncated
+    max_len: usize,
+}
+
+impl CorrelationConfig {
+    /// Takes correlation ids from the request header `header`, case-insensitively
+    #[must_use]
+    pub fn new(header: &str) -> Self {
+        Self {
+            header: header.to_ascii_lowercase(),
+            echo: None,
+            max_len: DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// Echoes the correlation id of a request in the response header `header`. It is not echoed by default.
+    ///
+    /// `x-amz-*` headers are never overwritten, so `x-amz-request-id` is always generated by the service.
+    #[must_use]
+    pub fn echo(mut self, header: &str) -> Self {
+        self.echo = Some(header.to_ascii_lowercase());
+        self
+    }
+
+    /// Truncates an id to `max_len` bytes, which is 128 by default
+    #[must_use]
+    pub const fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// the correlation id of `req`, which is empty or invalid if it is not visible ASCII
+    fn extract(&self, req: &Request) -> Option<String> {
+        let value = req.headers().get(self.header.as_str())?.to_str().ok()?;
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        // a header value is ASCII, so it is cut at a char boundary
+        Some(value.get(..self.max_len).unwrap_or(value).to_owned())
+    }
+
+    /// the response header which echoes `correlation_id`
+    pub(crate) fn echo_header(&self, correlation_id: &str) -> Option<(HeaderName, HeaderValue)> {
+        let name = HeaderName::from_bytes(self.echo.as_ref()?.as_bytes()).ok()?;
+        if name.as_str().starts_with("x-amz-") {
+            return None;
+        }
+        let value = HeaderValue::from_str(correlation_id).ok()?;
+        Some((name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Body;
+
+    fn request(value: Option<&str>) -> Request {
+        let mut req = Request::new(Body::empty());
+        if let Some(value) = value {
+            let _prev = req
+                .headers_mut()
+                .insert("x-request-id", HeaderValue::from_str(value).unwrap());
+        }
+        req
+    }
+
+    #[test]
+    fn correlation() {
+        let config = CorrelationConfig::new("X-Request-Id").max_len(8);
+        assert_eq!(config.extract(&request(None)), None);
+        assert_eq!(config.extract(&request(Some(" "))), None);
+        assert_eq!(
+            config.extract(&request(Some("abc"))).as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            config.extract(&request(Some("0123456789"))).as_deref(),
+            Some("01234567")
+        );
+
+        let ids = RequestIds::new(&request(Some("abc")), Some(&config));
+        assert_eq!(ids.request_id().len(), 16);
+        assert_eq!(base64::decode(ids.extended_request_id()).unwrap().len(), 32);
+        assert_eq!(ids.correlation_id(), Some("abc"));
+        let other = RequestIds::new(&request(None), None);
+        assert_ne!(other.request_id, ids.request_id);
+        assert_ne!(other.extended_request_id, ids.extended_request_id);
+
+        assert_eq!(config.echo_header("abc"), None);
+        let (name, value) = config
+            .clone()
+            .echo("X-Request-Id")
+            .echo_header("abc")
+            .unwrap();
+        assert_eq!(
+            (name.as_str(), value.to_str().unwrap()),
+            ("x-request-id", "abc")
+        );
+        assert_eq!(config.echo("x-amz-request-id").echo_header("abc"), None);
+    }
+
+    #[test]
+    fn current() {
+        assert_eq!(RequestIds::current(), None);
+
+        let ids = RequestIds::new(&request(None), None);
+        let seen = futures::executor::block_on(ids.clone().scope(async { RequestIds::current() }));
+        assert_eq!(seen, Some(ids));
+
+        assert_eq!(RequestIds::current(), None);
+    }
+}
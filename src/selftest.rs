@@ -0,0 +1,976 @@
+//! End-to-end self test of a storage
+//!
+//! [`run_selftest`] serves a storage by an [`S3Service`] and sends it a request for each scenario
+//! of the implemented operations, in a temporary bucket named `s3-server-selftest-*`:
+//! buckets, objects with metadata, conditional and range reads, copies, paginated listings,
+//! a multipart round trip, a listed and aborted upload, versioning and bucket tagging.
+//!
+//! Each scenario passes, fails, or is unimplemented if the storage answers `NotImplemented`
+//! or ignores the condition or range of a read. The results are collected in a [`SelftestReport`],
+//! which displays as a support matrix. `s3-server selftest` prints it for the configured backend
+//! and exits with an error if a scenario fails, which validates a new backend or deployment before clients use it.
+//!
+//! The objects and the bucket are deleted at the end even if a scenario fails.
+//! A multipart upload which failed to complete is aborted.
+
+use crate::storage::S3Storage;
+use crate::utils::percent;
+use crate::{Body, Method, Request, S3Service, StatusCode};
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderValue};
+use tracing::warn;
+use uuid::Uuid;
+
+/// the content of the test object
+const CONTENT: &str = "Hello World!";
+
+/// the keys of the paginated listings
+const LISTING_KEYS: &[&str] = &["list/a", "list/b", "list/c"];
+
+/// the size of the first part of the multipart upload, the minimum size of a part which is not the last
+const PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// The outcome of a scenario
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// the response is as expected
+    Passed,
+    /// the response is not as expected, for the reason
+    Failed(String),
+    /// the storage does not implement the scenario
+    Unimplemented,
+}
+
+impl From<Result<(), String>> for CheckOutcome {
+    fn from(ret: Result<(), String>) -> Self {
+        ret.map_or_else(Self::Failed, |()| Self::Passed)
+    }
+}
+
+/// The result of a scenario of an operation
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// operation, such as `GetObject`
+    pub operation: &'static str,
+    /// scenario, such as `range`
+    pub scenario: &'static str,
+    /// outcome
+    pub outcome: CheckOutcome,
+    /// time of the request
+    pub elapsed: Duration,
+}
+
+/// The results of [`run_selftest`]
+#[derive(Debug, Clone)]
+pub struct SelftestReport {
+    /// the temporary bucket
+    pub bucket: String,
+    /// the results in the order of the scenarios
+    pub checks: Vec<CheckResult>,
+    /// why the objects or the bucket could not be deleted
+    pub cleanup_error: Option<String>,
+}
+
+impl SelftestReport {
+    /// Checks whether no scenario failed and the bucket was deleted
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.cleanup_error.is_none()
+            && self.count(|outcome| matches!(*outcome, CheckOutcome::Failed(_))) == 0
+    }
+
+    /// counts the scenarios whose outcome matches `f`
+    fn count(&self, f: impl Fn(&CheckOutcome) -> bool) -> usize {
+        self.checks.iter().filter(|check| f(&check.outcome)).count()
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<26} {:<20} {:<14} {:>12}",
+            "OPERATION", "SCENARIO", "RESULT", "TIME"
+        )?;
+        for check in &self.checks {
+            let (result, reason) = match check.outcome {
+                CheckOutcome::Passed => ("pass", None),
+                CheckOutcome::Failed(ref reason) => ("FAIL", Some(reason)),
+                CheckOutcome::Unimplemented => ("unimplemented", None),
+            };
+            let elapsed = format!("{:.1?}", check.elapsed);
+            write!(
+                f,
+                "{:<26} {:<20} {:<14} {:>12}",
+                check.operation, check.scenario, result, elapsed
+            )?;
+            match reason {
+                Some(reason) => writeln!(f, "  {}", reason)?,
+                None => writeln!(f)?,
+            }
+        }
+        if let Some(ref err) = self.cleanup_error {
+            writeln!(f, "cleanup of bucket {} failed: {}", self.bucket, err)?;
+        }
+        write!(
+            f,
+            "{} passed, {} failed, {} unimplemented",
+            self.count(|outcome| *outcome == CheckOutcome::Passed),
+            self.count(|outcome| matches!(*outcome, CheckOutcome::Failed(_))),
+            self.count(|outcome| *outcome == CheckOutcome::Unimplemented)
+        )
+    }
+}
+
+/// Runs the scenarios against `storage` in a temporary bucket, and deletes it afterwards
+pub async fn run_selftest(storage: impl S3Storage + Send + Sync + 'static) -> SelftestReport {
+    let mut id = Uuid::new_v4().to_simple().to_string();
+    id.truncate(12);
+    let mut runner = Runner {
+        service: S3Service::new(storage),
+        bucket: format!("s3-server-selftest-{}", id),
+        checks: Vec::new(),
+    };
+    let is_created = runner.run().await;
+    let cleanup_error = if is_created {
+        runner.cleanup().await.err()
+    } else {
+        None
+    };
+    SelftestReport {
+        bucket: runner.bucket,
+        checks: runner.checks,
+        cleanup_error,
+    }
+}
+
+/// A request of a scenario
+struct Call {
+    /// method
+    method: Method,
+    /// path and query
+    uri: String,
+    /// headers
+    headers: Vec<(&'static str, String)>,
+    /// body
+    body: Bytes,
+}
+
+impl Call {
+    /// a request to `uri`, such as `/bucket?versioning`
+    fn new(method: Method, uri: String) -> Self {
+        Self {
+            method,
+            uri,
+            headers: Vec::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    /// appends a header
+    fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// sets the body
+    fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// converts the call into an anonymous request
+    fn into_request(self) -> Result<Request, String> {
+        let uri = format!("http://localhost{}", self.uri)
+            .parse()
+            .map_err(|err| format!("invalid uri {}: {}", self.uri, err))?;
+        let mut req = Request::new(Body::from(self.body));
+        *req.method_mut() = self.method;
+        *req.uri_mut() = uri;
+        let headers = req.headers_mut();
+        let _prev = headers.insert(
+            "x-amz-content-sha256",
+            HeaderValue::from_static("UNSIGNED-PAYLOAD"),
+        );
+        for (name, value) in self.headers {
+            let value = HeaderValue::from_str(&value)
+                .map_err(|err| format!("invalid header {}: {}", name, err))?;
+            let _prev = headers.insert(name, value);
+        }
+        Ok(req)
+    }
+}
+
+/// A response of a scenario
+struct Reply {
+    /// status
+    status: StatusCode,
+    /// headers
+    headers: HeaderMap,
+    /// body
+    body: Bytes,
+}
+
+impl Reply {
+    /// the body as text
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// the value of the header `name`
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    /// checks whether the storage does not implement the operation
+    fn is_unimplemented(&self) -> bool {
+        self.status == StatusCode::NOT_IMPLEMENTED
+            && elements(&self.text(), "Code") == ["NotImplemented"]
+    }
+
+    /// rejects a status other than `status`
+    fn expect_status(&self, status: StatusCode) -> Result<(), String> {
+        if self.status == status {
+            return Ok(());
+        }
+        Err(format!(
+            "expected status {}, got {}: {}",
+            status,
+            self.status,
+            self.text()
+        ))
+    }
+
+    /// the single element `name` of the xml body
+    fn element(&self, name: &str) -> Result<String, String> {
+        let text = self.text();
+        match elements(&text, name).as_slice() {
+            [value] => Ok((*value).to_owned()),
+            _ => Err(format!("expected one <{}> in the response", name)),
+        }
+    }
+}
+
+/// the texts of the elements named `name` in a flat xml document
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    xml.split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.split(close.as_str()).next())
+        .collect()
+}
+
+/// rejects a listing whose keys are not `expected`
+fn expect_keys(reply: &Reply, expected: &[&str]) -> Result<(), String> {
+    reply.expect_status(StatusCode::OK)?;
+    let text = reply.text();
+    let keys = elements(&text, "Key");
+    if keys != expected {
+        return Err(format!("expected keys {:?}, got {:?}", expected, keys));
+    }
+    Ok(())
+}
+
+/// The state of a self test
+struct Runner {
+    /// the service of the tested storage
+    service: S3Service,
+    /// the temporary bucket
+    bucket: String,
+    /// the results so far
+    checks: Vec<CheckResult>,
+}
+
+impl Runner {
+    /// the path of the bucket or one of its objects
+    fn path(&self, key: Option<&str>) -> String {
+        key.map_or_else(
+            || format!("/{}", self.bucket),
+            |key| format!("/{}/{}", self.bucket, percent::encode(key.as_bytes())),
+        )
+    }
+
+    /// sends a request to the service
+    async fn send(&self, call: Call) -> Result<Reply, String> {
+        let req = call.into_request()?;
+        let resp = self
+            .service
+            .hyper_call(req)
+            .await
+            .map_err(|err| err.to_string())?;
+        let (parts, body) = resp.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .map_err(|err| format!("failed to read the body: {}", err))?;
+        Ok(Reply {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        })
+    }
+
+    /// runs a scenario, and returns the response if it passed
+    async fn check<F>(
+        &mut self,
+        operation: &'static str,
+        scenario: &'static str,
+        call: Call,
+        verify: F,
+    ) -> Option<Reply>
+    where
+        F: FnOnce(&Reply) -> CheckOutcome + Send,
+    {
+        let start = Instant::now();
+        let ret = self.send(call).await;
+        let elapsed = start.elapsed();
+        let outcome = match ret {
+            Err(ref err) => CheckOutcome::Failed(err.clone()),
+            Ok(ref reply) if reply.is_unimplemented() => CheckOutcome::Unimplemented,
+            Ok(ref reply) => verify(reply),
+        };
+        let is_passed = outcome == CheckOutcome::Passed;
+        self.checks.push(CheckResult {
+            operation,
+            scenario,
+            outcome,
+            elapsed,
+        });
+        ret.ok().filter(|_| is_passed)
+    }
+
+    /// runs a scenario whose response is not needed
+    async fn expect<F>(
+        &mut self,
+        operation: &'static str,
+        scenario: &'static str,
+        call: Call,
+        verify: F,
+    ) where
+        F: FnOnce(&Reply) -> CheckOutcome + Send,
+    {
+        let _reply = self.check(operation, scenario, call, verify).await;
+    }
+
+    /// runs the scenarios, and returns whether the bucket was created
+    async fn run(&mut self) -> bool {
+        let bucket = self.path(None);
+        let created = self
+            .check(
+                "CreateBucket",
+                "create",
+                Call::new(Method::PUT, bucket.clone()),
+                |reply| reply.expect_status(StatusCode::OK).into(),
+            )
+            .await;
+        if created.is_none() {
+            return false;
+        }
+        self.buckets().await;
+        self.objects().await;
+        self.listings().await;
+        self.multipart().await;
+        self.abort().await;
+        self.versioning().await;
+        self.tagging().await;
+        self.deletions().await;
+        true
+    }
+
+    /// scenarios of buckets
+    async fn buckets(&mut self) {
+        let bucket = self.path(None);
+        let name = self.bucket.clone();
+        self.expect(
+            "HeadBucket",
+            "exists",
+            Call::new(Method::HEAD, bucket.clone()),
+            |reply| reply.expect_status(StatusCode::OK).into(),
+        )
+        .await;
+        self.expect(
+            "GetBucketLocation",
+            "location",
+            Call::new(Method::GET, format!("{}?location", bucket)),
+            |reply| reply.expect_status(StatusCode::OK).into(),
+        )
+        .await;
+        self.expect(
+            "ListBuckets",
+            "lists the bucket",
+            Call::new(Method::GET, "/".to_owned()),
+            |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    let text = reply.text();
+                    if elements(&text, "Name").contains(&name.as_str()) {
+                        return Ok(());
+                    }
+                    Err(format!("the bucket {} is not listed", name))
+                });
+                ret.into()
+            },
+        )
+        .await;
+    }
+
+    /// scenarios of reads and writes of an object
+    async fn objects(&mut self) {
+        let object = self.path(Some("object"));
+        let put = Call::new(Method::PUT, object.clone())
+            .header("content-type", "text/plain")
+            .header("x-amz-meta-selftest", "yes")
+            .body(CONTENT);
+        let e_tag = self
+            .check("PutObject", "with metadata", put, expect_e_tag)
+            .await
+            .and_then(|reply| reply.header("etag").map(ToOwned::to_owned));
+
+        self.expect(
+            "HeadObject",
+            "metadata",
+            Call::new(Method::HEAD, object.clone()),
+            |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    if reply.header("x-amz-meta-selftest") != Some("yes") {
+                        return Err("the metadata is not returned".to_owned());
+                    }
+                    let len = CONTENT.len().to_string();
+                    if reply.header("content-length") != Some(len.as_str()) {
+                        return Err(format!("expected content-length {}", len));
+                    }
+                    Ok(())
+                });
+                ret.into()
+            },
+        )
+        .await;
+        self.expect(
+            "GetObject",
+            "body",
+            Call::new(Method::GET, object.clone()),
+            |reply| expect_body(reply, CONTENT.as_bytes()),
+        )
+        .await;
+
+        self.reads(&object, e_tag).await;
+
+        let copy = self.path(Some("copy"));
+        let source = format!("{}/object", self.bucket);
+        let call = Call::new(Method::PUT, copy.clone()).header("x-amz-copy-source", source);
+        let copied = self
+            .check("CopyObject", "copy", call, |reply| {
+                reply.expect_status(StatusCode::OK).into()
+            })
+            .await;
+        if copied.is_some() {
+            self.expect(
+                "GetObject",
+                "copied body",
+                Call::new(Method::GET, copy),
+                |reply| expect_body(reply, CONTENT.as_bytes()),
+            )
+            .await;
+        }
+    }
+
+    /// scenarios of conditional and range reads
+    async fn reads(&mut self, object: &str, e_tag: Option<String>) {
+        if let Some(e_tag) = e_tag {
+            let call = Call::new(Method::GET, object.to_owned()).header("if-none-match", e_tag);
+            self.expect("GetObject", "if-none-match", call, |reply| {
+                conditional(reply, StatusCode::NOT_MODIFIED)
+            })
+            .await;
+        }
+        let call = Call::new(Method::GET, object.to_owned()).header("if-match", "\"selftest\"");
+        self.expect("GetObject", "if-match", call, |reply| {
+            conditional(reply, StatusCode::PRECONDITION_FAILED)
+        })
+        .await;
+        let call = Call::new(Method::GET, object.to_owned()).header("range", "bytes=6-10");
+        self.expect("GetObject", "range", call, |reply| {
+            if reply.status == StatusCode::OK && reply.body == CONTENT {
+                return CheckOutcome::Unimplemented;
+            }
+            let ret = reply
+                .expect_status(StatusCode::PARTIAL_CONTENT)
+                .and_then(|()| {
+                    if reply.body != "World" {
+                        return Err(format!("unexpected body {:?}", reply.text()));
+                    }
+                    Ok(())
+                });
+            ret.into()
+        })
+        .await;
+    }
+
+    /// scenarios of paginated listings
+    async fn listings(&mut self) {
+        for &key in LISTING_KEYS {
+            let call = Call::new(Method::PUT, self.path(Some(key))).body(key.to_owned());
+            self.expect("PutObject", "listed object", call, |reply| {
+                reply.expect_status(StatusCode::OK).into()
+            })
+            .await;
+        }
+
+        let bucket = self.path(None);
+        let (first_page, next_page) = LISTING_KEYS.split_at(2);
+
+        let call = Call::new(
+            Method::GET,
+            format!("{}?list-type=2&prefix=list/&max-keys=2", bucket),
+        );
+        let token = self
+            .check("ListObjectsV2", "first page", call, |reply| {
+                let ret = expect_keys(reply, first_page).and_then(|()| {
+                    let _next = reply.element("NextContinuationToken")?;
+                    Ok(())
+                });
+                ret.into()
+            })
+            .await
+            .and_then(|reply| reply.element("NextContinuationToken").ok());
+        if let Some(token) = token {
+            let uri = format!(
+                "{}?list-type=2&prefix=list/&max-keys=2&continuation-token={}",
+                bucket,
+                percent::encode(token.as_bytes())
+            );
+            self.expect(
+                "ListObjectsV2",
+                "next page",
+                Call::new(Method::GET, uri),
+                |reply| expect_keys(reply, next_page).into(),
+            )
+            .await;
+        }
+
+        let call = Call::new(Method::GET, format!("{}?prefix=list/&max-keys=2", bucket));
+        let listed = self
+            .check("ListObjects", "first page", call, |reply| {
+                let ret = expect_keys(reply, first_page).and_then(|()| {
+                    if reply.element("IsTruncated")? != "true" {
+                        return Err("the first page is not truncated".to_owned());
+                    }
+                    Ok(())
+                });
+                ret.into()
+            })
+            .await;
+        if listed.is_some() {
+            let marker = first_page.last().copied().unwrap_or_default();
+            let uri = format!("{}?prefix=list/&max-keys=2&marker={}", bucket, marker);
+            self.expect(
+                "ListObjects",
+                "next page",
+                Call::new(Method::GET, uri),
+                |reply| expect_keys(reply, next_page).into(),
+            )
+            .await;
+        }
+
+        let call = Call::new(Method::GET, format!("{}?delimiter=/", bucket));
+        self.expect("ListObjects", "delimiter", call, |reply| {
+            let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                let text = reply.text();
+                if elements(&text, "Prefix").contains(&"list/") {
+                    return Ok(());
+                }
+                Err("the common prefix list/ is not listed".to_owned())
+            });
+            ret.into()
+        })
+        .await;
+    }
+
+    /// scenarios of a multipart upload
+    async fn multipart(&mut self) {
+        let object = self.path(Some("multipart"));
+        let call = Call::new(Method::POST, format!("{}?uploads", object));
+        let upload_id = self
+            .check("CreateMultipartUpload", "create", call, |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    let _upload_id = reply.element("UploadId")?;
+                    Ok(())
+                });
+                ret.into()
+            })
+            .await
+            .and_then(|reply| reply.element("UploadId").ok());
+        let upload_id = match upload_id {
+            Some(upload_id) => upload_id,
+            None => return,
+        };
+
+        let parts = [vec![b'a'; PART_SIZE], b"bcdef".to_vec()];
+        let mut e_tags = Vec::new();
+        for (part_number, content) in (1_u32..).zip(parts.iter()) {
+            let uri = format!(
+                "{}?partNumber={}&uploadId={}",
+                object,
+                part_number,
+                percent::encode(upload_id.as_bytes())
+            );
+            let call = Call::new(Method::PUT, uri).body(content.clone());
+            let e_tag = self
+                .check("UploadPart", "part", call, expect_e_tag)
+                .await
+                .and_then(|reply| reply.header("etag").map(ToOwned::to_owned));
+            match e_tag {
+                Some(e_tag) => e_tags.push((part_number, e_tag)),
+                None => return,
+            }
+        }
+
+        let upload = format!(
+            "{}?uploadId={}",
+            object,
+            percent::encode(upload_id.as_bytes())
+        );
+        self.expect(
+            "ListParts",
+            "parts",
+            Call::new(Method::GET, upload.clone()),
+            |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    let text = reply.text();
+                    let part_numbers = elements(&text, "PartNumber");
+                    if part_numbers != ["1", "2"] {
+                        return Err(format!("unexpected parts {:?}", part_numbers));
+                    }
+                    Ok(())
+                });
+                ret.into()
+            },
+        )
+        .await;
+
+        let mut xml = String::from("<CompleteMultipartUpload>");
+        for &(part_number, ref e_tag) in &e_tags {
+            xml.push_str("<Part><PartNumber>");
+            xml.push_str(&part_number.to_string());
+            xml.push_str("</PartNumber><ETag>");
+            xml.push_str(e_tag);
+            xml.push_str("</ETag></Part>");
+        }
+        xml.push_str("</CompleteMultipartUpload>");
+        let call = Call::new(Method::POST, upload.clone()).body(xml);
+        let completed = self
+            .check("CompleteMultipartUpload", "complete", call, |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    let _e_tag = reply.element("ETag")?;
+                    Ok(())
+                });
+                ret.into()
+            })
+            .await;
+        if completed.is_none() {
+            // the parts are not listed by `cleanup`
+            if let Err(err) = self.send(Call::new(Method::DELETE, upload)).await {
+                warn!(%err, "selftest: failed to abort the upload");
+            }
+            return;
+        }
+
+        let expected: Vec<u8> = parts.concat();
+        self.expect(
+            "GetObject",
+            "multipart body",
+            Call::new(Method::GET, object),
+            |reply| expect_body(reply, &expected),
+        )
+        .await;
+    }
+
+    /// scenarios of an aborted multipart upload
+    async fn abort(&mut self) {
+        let object = self.path(Some("aborted"));
+        let call = Call::new(Method::POST, format!("{}?uploads", object));
+        let upload_id = self
+            .send(call)
+            .await
+            .ok()
+            .and_then(|reply| reply.element("UploadId").ok());
+        let upload_id = match upload_id {
+            Some(upload_id) => upload_id,
+            None => return,
+        };
+        let encoded = percent::encode(upload_id.as_bytes());
+
+        let uri = format!("{}?partNumber=1&uploadId={}", object, encoded);
+        let call = Call::new(Method::PUT, uri).body(CONTENT);
+        if self
+            .check("UploadPart", "aborted part", call, expect_e_tag)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        let uploads = format!("{}?uploads", self.path(None));
+        self.expect(
+            "ListMultipartUploads",
+            "uploads",
+            Call::new(Method::GET, uploads),
+            |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    if !elements(&reply.text(), "UploadId").contains(&upload_id.as_str()) {
+                        return Err(format!("upload {} is not listed", upload_id));
+                    }
+                    Ok(())
+                });
+                ret.into()
+            },
+        )
+        .await;
+
+        let upload = format!("{}?uploadId={}", object, encoded);
+        let aborted = self
+            .check(
+                "AbortMultipartUpload",
+                "abort",
+                Call::new(Method::DELETE, upload.clone()),
+                |reply| reply.expect_status(StatusCode::NO_CONTENT).into(),
+            )
+            .await;
+        if aborted.is_none() {
+            return;
+        }
+
+        self.expect(
+            "ListParts",
+            "aborted upload",
+            Call::new(Method::GET, upload),
+            |reply| {
+                let ret = reply.expect_status(StatusCode::NOT_FOUND).and_then(|()| {
+                    let code = reply.element("Code")?;
+                    if code != "NoSuchUpload" {
+                        return Err(format!("unexpected code {}", code));
+                    }
+                    Ok(())
+                });
+                ret.into()
+            },
+        )
+        .await;
+    }
+
+    /// scenarios of versioning, which is enabled and suspended again if the storage supports it
+    async fn versioning(&mut self) {
+        let versioning = format!("{}?versioning", self.path(None));
+        self.expect(
+            "GetBucketVersioning",
+            "status",
+            Call::new(Method::GET, versioning.clone()),
+            |reply| reply.expect_status(StatusCode::OK).into(),
+        )
+        .await;
+
+        let configuration = |status: &str| {
+            format!(
+                "<VersioningConfiguration><Status>{}</Status></VersioningConfiguration>",
+                status
+            )
+        };
+        let call = Call::new(Method::PUT, versioning.clone()).body(configuration("Enabled"));
+        let enabled = self
+            .check("PutBucketVersioning", "enable", call, |reply| {
+                reply.expect_status(StatusCode::OK).into()
+            })
+            .await;
+
+        if enabled.is_some() {
+            let call = Call::new(Method::PUT, self.path(Some("object"))).body(CONTENT);
+            self.expect("PutObject", "new version", call, |reply| {
+                let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                    match reply.header("x-amz-version-id") {
+                        Some(version_id) if version_id != "null" => Ok(()),
+                        _ => Err("no version id in the response".to_owned()),
+                    }
+                });
+                ret.into()
+            })
+            .await;
+            let call = Call::new(Method::PUT, versioning).body(configuration("Suspended"));
+            self.expect("PutBucketVersioning", "suspend", call, |reply| {
+                reply.expect_status(StatusCode::OK).into()
+            })
+            .await;
+        }
+
+        let call = Call::new(Method::GET, format!("{}?versions", self.path(None)));
+        self.expect("ListObjectVersions", "versions", call, |reply| {
+            let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                let text = reply.text();
+                if elements(&text, "Key").contains(&"object") {
+                    return Ok(());
+                }
+                Err("the object is not listed".to_owned())
+            });
+            ret.into()
+        })
+        .await;
+    }
+
+    /// scenarios of bucket tagging, which ends without tags
+    async fn tagging(&mut self) {
+        let tagging = format!("{}?tagging", self.path(None));
+        let body =
+            "<Tagging><TagSet><Tag><Key>selftest</Key><Value>1</Value></Tag></TagSet></Tagging>";
+        let call = Call::new(Method::PUT, tagging.clone()).body(body);
+        let put = self
+            .check("PutBucketTagging", "put", call, |reply| {
+                reply.expect_status(StatusCode::OK).into()
+            })
+            .await;
+        if put.is_none() {
+            return;
+        }
+
+        let call = Call::new(Method::GET, tagging.clone());
+        self.expect("GetBucketTagging", "tags", call, |reply| {
+            let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                let text = reply.text();
+                if elements(&text, "Key") == ["selftest"] {
+                    return Ok(());
+                }
+                Err(format!("unexpected tags {}", text))
+            });
+            ret.into()
+        })
+        .await;
+
+        let call = Call::new(Method::DELETE, tagging.clone());
+        self.expect("DeleteBucketTagging", "delete", call, |reply| {
+            reply.expect_status(StatusCode::NO_CONTENT).into()
+        })
+        .await;
+        self.expect(
+            "GetBucketTagging",
+            "no tags",
+            Call::new(Method::GET, tagging),
+            |reply| reply.expect_status(StatusCode::NOT_FOUND).into(),
+        )
+        .await;
+    }
+
+    /// scenarios of deletions
+    async fn deletions(&mut self) {
+        let mut xml = String::from("<Delete>");
+        for &key in LISTING_KEYS {
+            xml.push_str("<Object><Key>");
+            xml.push_str(key);
+            xml.push_str("</Key></Object>");
+        }
+        xml.push_str("</Delete>");
+        let call = Call::new(Method::POST, format!("{}?delete", self.path(None))).body(xml);
+        self.expect("DeleteObjects", "batch", call, |reply| {
+            let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+                let text = reply.text();
+                let deleted = elements(&text, "Key");
+                if deleted.len() != LISTING_KEYS.len() {
+                    return Err(format!("unexpected deleted keys {:?}", deleted));
+                }
+                Ok(())
+            });
+            ret.into()
+        })
+        .await;
+
+        let copy = self.path(Some("copy"));
+        self.expect(
+            "DeleteObject",
+            "delete",
+            Call::new(Method::DELETE, copy.clone()),
+            |reply| reply.expect_status(StatusCode::NO_CONTENT).into(),
+        )
+        .await;
+        self.expect(
+            "HeadObject",
+            "deleted object",
+            Call::new(Method::HEAD, copy),
+            |reply| reply.expect_status(StatusCode::NOT_FOUND).into(),
+        )
+        .await;
+    }
+
+    /// deletes the remaining objects, then the bucket as the scenario of `DeleteBucket`
+    async fn cleanup(&mut self) -> Result<(), String> {
+        let bucket = self.path(None);
+        let mut last_keys = Vec::new();
+        loop {
+            let reply = self
+                .send(Call::new(Method::GET, format!("{}?list-type=2", bucket)))
+                .await?;
+            reply.expect_status(StatusCode::OK)?;
+            let text = reply.text();
+            let keys = elements(&text, "Key");
+            if keys.is_empty() {
+                break;
+            }
+            if keys == last_keys {
+                return Err(format!("the objects {:?} are not deleted", keys));
+            }
+            last_keys = keys.iter().map(|&key| key.to_owned()).collect();
+            for key in keys {
+                let reply = self
+                    .send(Call::new(Method::DELETE, self.path(Some(key))))
+                    .await?;
+                reply.expect_status(StatusCode::NO_CONTENT)?;
+            }
+        }
+        let deleted = self
+            .check(
+                "DeleteBucket",
+                "delete",
+                Call::new(Method::DELETE, bucket),
+                |reply| reply.expect_status(StatusCode::NO_CONTENT).into(),
+            )
+            .await;
+        match deleted {
+            Some(_) => Ok(()),
+            None => Err("the bucket is not deleted".to_owned()),
+        }
+    }
+}
+
+/// checks a response with `ETag`
+fn expect_e_tag(reply: &Reply) -> CheckOutcome {
+    let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+        reply
+            .header("etag")
+            .map(drop)
+            .ok_or_else(|| "no ETag in the response".to_owned())
+    });
+    ret.into()
+}
+
+/// checks a response whose body is `expected`
+fn expect_body(reply: &Reply, expected: &[u8]) -> CheckOutcome {
+    let ret = reply.expect_status(StatusCode::OK).and_then(|()| {
+        if reply.body != expected {
+            return Err(format!(
+                "expected {} bytes, got {} bytes: {:?}",
+                expected.len(),
+                reply.body.len(),
+                reply.text()
+            ));
+        }
+        Ok(())
+    });
+    ret.into()
+}
+
+/// checks a conditional read, which the storage does not implement if it answers the object
+fn conditional(reply: &Reply, status: StatusCode) -> CheckOutcome {
+    if reply.status == StatusCode::OK && reply.body == CONTENT {
+        return CheckOutcome::Unimplemented;
+    }
+    reply.expect_status(status).into()
+}
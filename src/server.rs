@@ -0,0 +1,485 @@
+//! A server for [`SharedS3Service`] which protects it from slow clients
+//!
+//! hyper waits for a client as long as its connection is open,
+//! so a client which sends its request slowly pins a connection,
+//! together with a temporary file of the fs storage or the buffered parts of an upload.
+//! [`serve`] runs an accept loop which bounds such clients:
+//!
+//! + A connection is closed if a request head is not complete within [`ServerOptions::header_read_timeout`]
+//!   after the connection is accepted or the previous response is sent, which also closes idle connections.
+//! + A request body which is slower than [`ServerOptions::min_body_rate`] after a grace period fails,
+//!   and the request is answered by `408 RequestTimeout`.
+//! + A source ip holds at most [`ServerOptions::max_connections_per_ip`] connections.
+//!   Its further connections are closed as soon as they are accepted.
+//!
+//! Requests are served over plaintext, so each request has the [`ConnectionScheme::Http`] extension.
+
+use crate::audit::ClientAddr;
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::service::SharedS3Service;
+use crate::tls_policy::ConnectionScheme;
+use crate::{Body, BoxStdError, Request, Response, StatusCode};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use futures::stream::Stream;
+use hyper::body::{Bytes, HttpBody, SizeHint};
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::HeaderMap;
+use pin_project_lite::pin_project;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Instant, Sleep};
+use tracing::{debug, warn};
+
+/// Options of [`serve`]
+///
+/// The protections are disabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerOptions {
+    /// whether connections are kept alive between requests (`Http::http1_keep_alive`), enabled by default
+    pub http1_keep_alive: bool,
+    /// whether a connection stays open after the client shuts down its write half (`Http::http1_half_close`)
+    pub http1_half_close: bool,
+    /// the buffer size of a connection (`Http::max_buf_size`),
+    /// see [`HeaderLimits::http1_max_buf_size`](crate::HeaderLimits::http1_max_buf_size)
+    pub http1_max_buf_size: Option<usize>,
+    /// whether `TCP_NODELAY` is set on connections
+    pub tcp_nodelay: bool,
+    /// max time from the accept or the previous response to a complete request head
+    pub header_read_timeout: Option<Duration>,
+    /// min throughput of request bodies
+    pub min_body_rate: Option<MinBodyRate>,
+    /// max open connections of a source ip
+    pub max_connections_per_ip: Option<usize>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            http1_keep_alive: true,
+            http1_half_close: false,
+            http1_max_buf_size: None,
+            tcp_nodelay: false,
+            header_read_timeout: None,
+            min_body_rate: None,
+            max_connections_per_ip: None,
+        }
+    }
+}
+
+/// The min throughput of a request body, see [`ServerOptions::min_body_rate`]
+///
+/// A body fails when fewer than `bytes_per_sec` bytes per second have arrived on average since its head,
+/// which is not checked before `grace` has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinBodyRate {
+    /// min average bytes per second
+    pub bytes_per_sec: u64,
+    /// time before the rate is checked
+    pub grace: Duration,
+}
+
+/// Accepts connections from `listener` and serves them by `service`. The future never completes.
+///
+/// Connections are served on spawned tasks, which are not stopped when this future is dropped.
+/// A failed accept, such as one which runs out of file descriptors, is retried after a second.
+#[allow(clippy::infinite_loop)] // serves until the process exits
+pub async fn serve(listener: TcpListener, service: SharedS3Service, options: ServerOptions) {
+    let counter = ConnectionCounter::default();
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!(%err, "failed to accept a connection");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let guard = if let Some(guard) = counter.enter(addr.ip(), options.max_connections_per_ip) {
+            guard
+        } else {
+            warn!(%addr, "too many connections from the source ip");
+            continue;
+        };
+        if options.tcp_nodelay {
+            if let Err(err) = stream.set_nodelay(true) {
+                warn!(%addr, %err, "failed to set TCP_NODELAY");
+            }
+        }
+        let service = service.clone();
+        let _handle = tokio::spawn(async move {
+            serve_connection(stream, addr, service, options).await;
+            drop(guard);
+        });
+    }
+}
+
+/// serves the requests of a connection
+async fn serve_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    service: SharedS3Service,
+    options: ServerOptions,
+) {
+    let activity = Arc::new(Activity::new());
+
+    let mut http = Http::new();
+    let _http = http
+        .http1_keep_alive(options.http1_keep_alive)
+        .http1_half_close(options.http1_half_close);
+    if let Some(size) = options.http1_max_buf_size {
+        let _http = http.max_buf_size(size);
+    }
+
+    let conn = {
+        let activity = Arc::clone(&activity);
+        let min_body_rate = options.min_body_rate;
+        http.serve_connection(
+            stream,
+            service_fn(move |req| {
+                let service = service.clone();
+                let guard = RequestGuard::new(Arc::clone(&activity));
+                handle(service, guard, addr, min_body_rate, req)
+            }),
+        )
+    };
+
+    let ret = match options.header_read_timeout {
+        Some(timeout) => {
+            let watchdog = Box::pin(activity.idle_timeout(timeout));
+            match future::select(conn, watchdog).await {
+                Either::Left((ret, _)) => ret,
+                Either::Right(((), _)) => {
+                    debug!(%addr, "closed a connection without a complete request head");
+                    return;
+                }
+            }
+        }
+        None => conn.await,
+    };
+    if let Err(err) = ret {
+        debug!(%addr, %err, "connection error");
+    }
+}
+
+/// handles a request of a connection
+async fn handle(
+    service: SharedS3Service,
+    guard: RequestGuard,
+    addr: SocketAddr,
+    min_body_rate: Option<MinBodyRate>,
+    mut req: Request,
+) -> Result<hyper::Response<TrackedBody>, BoxStdError> {
+    let _prev = req.extensions_mut().insert(ClientAddr(addr));
+    let _prev = req.extensions_mut().insert(ConnectionScheme::Http);
+
+    let timed_out = Arc::new(AtomicBool::new(false));
+    if let Some(rate) = min_body_rate.filter(|rate| rate.bytes_per_sec > 0) {
+        #[allow(clippy::disallowed_methods)] // the body is tracked by `hyper_call`
+        if !req.body().is_end_stream() {
+            let body = mem::take(req.body_mut());
+            let guarded = RateGuard::new(body, rate, Arc::clone(&timed_out));
+            *req.body_mut() = Body::wrap_stream(guarded);
+        }
+    }
+
+    let mut res = service.hyper_call(req).await?;
+    if timed_out.load(Ordering::Acquire) {
+        warn!(%addr, "a request body was slower than the min rate");
+        res = request_timeout()?;
+    }
+    Ok(res.map(|body| TrackedBody {
+        body,
+        _guard: guard,
+    }))
+}
+
+/// the response to a request whose body is too slow
+fn request_timeout() -> S3Result<Response> {
+    let err = code_error!(
+        RequestTimeout,
+        "Your socket connection to the server was not read from or written to within the timeout period."
+    );
+    let mut res = err.into_xml_response().try_into_response()?;
+    *res.status_mut() = StatusCode::REQUEST_TIMEOUT;
+    let _prev = res
+        .headers_mut()
+        .insert(CONNECTION, HeaderValue::from_static("close"));
+    Ok(res)
+}
+
+/// locks a mutex, ignoring poisoning
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// The open connections of each source ip
+#[derive(Debug, Clone, Default)]
+struct ConnectionCounter {
+    /// open connections by ip
+    counts: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+/// An open connection, counted until it is dropped
+#[derive(Debug)]
+struct ConnectionGuard {
+    /// counter
+    counter: ConnectionCounter,
+    /// source ip
+    ip: IpAddr,
+}
+
+impl ConnectionCounter {
+    /// counts a connection, unless `ip` already has `limit` connections
+    fn enter(&self, ip: IpAddr, limit: Option<usize>) -> Option<ConnectionGuard> {
+        let mut counts = lock(&self.counts);
+        let count = counts.entry(ip).or_insert(0);
+        if limit.map_or(false, |limit| *count >= limit) {
+            return None;
+        }
+        *count = count.saturating_add(1);
+        drop(counts);
+        Some(ConnectionGuard {
+            counter: self.clone(),
+            ip,
+        })
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counts = lock(&self.counter.counts);
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                let _prev = counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// The requests in progress on a connection
+#[derive(Debug)]
+struct Activity {
+    /// the number of requests in progress, and when the last one was finished
+    state: Mutex<(usize, Instant)>,
+}
+
+/// A request in progress, until its response body is dropped
+#[derive(Debug)]
+struct RequestGuard {
+    /// activity of the connection
+    activity: Arc<Activity>,
+}
+
+impl Activity {
+    /// constructs the activity of a new connection
+    fn new() -> Self {
+        Self {
+            state: Mutex::new((0, Instant::now())),
+        }
+    }
+
+    /// completes when the connection has been idle for `timeout`
+    async fn idle_timeout(&self, timeout: Duration) {
+        loop {
+            let (active, idle_since) = *lock(&self.state);
+            let now = Instant::now();
+            let deadline = if active == 0 { idle_since } else { now };
+            let deadline = deadline.checked_add(timeout).unwrap_or(now);
+            if active == 0 && deadline <= now {
+                return;
+            }
+            tokio::time::sleep_until(deadline).await;
+        }
+    }
+}
+
+impl RequestGuard {
+    /// counts a request as in progress
+    fn new(activity: Arc<Activity>) -> Self {
+        let mut state = lock(&activity.state);
+        state.0 = state.0.saturating_add(1);
+        drop(state);
+        Self { activity }
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        let mut state = lock(&self.activity.state);
+        state.0 = state.0.saturating_sub(1);
+        state.1 = Instant::now();
+    }
+}
+
+pin_project! {
+    /// A response body which keeps its request in progress until the body is dropped
+    #[derive(Debug)]
+    struct TrackedBody {
+        #[pin]
+        body: Body,
+        _guard: RequestGuard,
+    }
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.project().body.poll_data(cx)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().body.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        HttpBody::size_hint(&self.body)
+    }
+}
+
+pin_project! {
+    /// A request body which fails if it is slower than the min rate
+    #[derive(Debug)]
+    struct RateGuard {
+        #[pin]
+        body: Body,
+        rate: MinBodyRate,
+        started: Instant,
+        received: u64,
+        #[pin]
+        timer: Sleep,
+        timed_out: Arc<AtomicBool>,
+    }
+}
+
+impl RateGuard {
+    /// guards `body`, setting `timed_out` when it fails
+    fn new(body: Body, rate: MinBodyRate, timed_out: Arc<AtomicBool>) -> Self {
+        let started = Instant::now();
+        Self {
+            body,
+            rate,
+            started,
+            received: 0,
+            timer: tokio::time::sleep_until(rate_deadline(started, rate, 0)),
+            timed_out,
+        }
+    }
+}
+
+/// the time before which `received` bytes must have arrived
+fn rate_deadline(started: Instant, rate: MinBodyRate, received: u64) -> Instant {
+    let millis = received
+        .saturating_mul(1000)
+        .checked_div(rate.bytes_per_sec)
+        .unwrap_or(u64::MAX);
+    let allowed = Duration::from_millis(millis).max(rate.grace);
+    // a deadline beyond the range of `Instant` is never reached
+    let far_future = Duration::from_secs(86_400_u64.saturating_mul(365));
+    started
+        .checked_add(allowed)
+        .or_else(|| Instant::now().checked_add(far_future))
+        .unwrap_or(started)
+}
+
+impl Stream for RateGuard {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if this.timed_out.load(Ordering::Acquire) {
+            return Poll::Ready(None);
+        }
+        let too_slow = match this.body.poll_data(cx) {
+            Poll::Ready(Some(Ok(bytes))) => {
+                let len = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+                *this.received = this.received.saturating_add(len);
+                let deadline = rate_deadline(*this.started, *this.rate, *this.received);
+                if deadline > Instant::now() {
+                    this.timer.as_mut().reset(deadline);
+                    return Poll::Ready(Some(Ok(bytes)));
+                }
+                true
+            }
+            Poll::Ready(Some(Err(err))) => {
+                return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::Other, err))))
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => this.timer.as_mut().poll(cx).is_ready(),
+        };
+        if too_slow {
+            this.timed_out.store(true, Ordering::Release);
+            let err = io::Error::new(
+                io::ErrorKind::TimedOut,
+                "The request body is slower than the min rate",
+            );
+            return Poll::Ready(Some(Err(err)));
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines() {
+        let started = Instant::now();
+        let rate = MinBodyRate {
+            bytes_per_sec: 1000,
+            grace: Duration::from_secs(2),
+        };
+        let after = |received| rate_deadline(started, rate, received).duration_since(started);
+        assert_eq!(after(0), Duration::from_secs(2));
+        assert_eq!(after(1500), Duration::from_secs(2));
+        assert_eq!(after(3000), Duration::from_secs(3));
+        assert!(after(u64::MAX) > Duration::from_secs(1000));
+    }
+
+    #[test]
+    fn connection_counter() {
+        let counter = ConnectionCounter::default();
+        let ip: IpAddr = [127, 0, 0, 1].into();
+        let other: IpAddr = [127, 0, 0, 2].into();
+
+        let first = counter.enter(ip, Some(2)).unwrap();
+        let _second = counter.enter(ip, Some(2)).unwrap();
+        assert!(counter.enter(ip, Some(2)).is_none());
+        assert!(counter.enter(other, Some(2)).is_some());
+
+        drop(first);
+        assert!(counter.enter(ip, Some(2)).is_some());
+        assert!(counter.enter(ip, None).is_some());
+    }
+}
@@ -1,43 +1,165 @@
 //! S3 service
 
-use crate::auth::S3Auth;
+use crate::audit::{self, AuditEntry, AuditQueue, AuditSink, ClientAddr};
+use crate::auth::{CanonicalUser, S3Auth};
+use crate::body_drain::{BodyTracker, DrainLimits};
+use crate::body_log::BodyLog;
+use crate::bucket_cache::{self, BucketCache, BucketCacheConfig};
+use crate::bucket_freeze;
+use crate::cancellation::{CancelOnDrop, CancellationToken};
+use crate::capabilities::{Capabilities, ServiceState};
+use crate::chunked_upload::{self, ChunkSigning};
+use crate::clock_skew;
+use crate::compression::{self, CompressionConfig};
+use crate::cors;
 use crate::data_structures::{OrderedHeaders, OrderedQs};
-use crate::errors::{S3AuthError, S3ErrorCode, S3Result};
+use crate::effective_config::{self, EffectiveConfig};
+use crate::errors::{S3AuthError, S3Error, S3ErrorCode, S3Result};
+use crate::extra_headers::{self, ExtraHeaders};
+use crate::header_limits::HeaderLimits;
 use crate::headers::{AmzContentSha256, AmzDate, AuthorizationV4, CredentialV4};
-use crate::headers::{AUTHORIZATION, CONTENT_TYPE, X_AMZ_CONTENT_SHA256, X_AMZ_DATE};
-use crate::ops::{ReqContext, S3Handler};
+use crate::headers::{HeaderName, HeaderValue, X_AMZ_UNIMPLEMENTED_OPERATION, X_AMZ_VERSION_ID};
+use crate::headers::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, DATE, ETAG, HOST, TRANSFER_ENCODING,
+};
+use crate::headers::{X_AMZ_CONTENT_SHA256, X_AMZ_DATE, X_AMZ_ID_2, X_AMZ_REQUEST_ID};
+use crate::legacy_protocols::{self, LegacyProtocol};
+use crate::metrics::{ActiveRequests, RuntimeSnapshot};
+use crate::operation_policy::OperationPolicy;
+use crate::ops::{self, AuthRef, NamedHandler, ReqContext};
 use crate::output::S3Output;
-use crate::path::{S3Path, S3PathErrorKind};
-use crate::signature_v4;
+use crate::path::{self, KeyEncoding, S3Path, S3PathErrorKind};
+use crate::payload_hash;
+use crate::post_policy::PostPolicy;
+use crate::redirect_offload::RedirectOffload;
+use crate::replay::Recorder;
+use crate::request_id::{CorrelationConfig, RequestIds};
+use crate::requester::Requester;
+use crate::size_limits::SizeLimits;
 use crate::storage::S3Storage;
-use crate::streams::aws_chunked_stream::AwsChunkedStream;
 use crate::streams::multipart::{self, Multipart};
-use crate::utils::{crypto, Also, Apply};
-use crate::{Body, BoxStdError, Method, Mime, Request, Response};
+use crate::tls_policy::TlsPolicy;
+use crate::utils::{crypto, Also, Apply, ResponseExt};
+use crate::{signature_v2, signature_v4};
+use crate::{Body, BoxStdError, Method, Mime, Request, Response, StatusCode};
 
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::fmt::{self, Debug};
 use std::io;
 use std::mem;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use futures::future::BoxFuture;
 use futures::stream::{Stream, StreamExt};
-use hyper::body::Bytes;
+use hyper::body::{Bytes, HttpBody};
 
-use tracing::{debug, error};
+use tracing::{debug, error, Span};
 
 /// S3 service
 pub struct S3Service {
     /// handlers
-    handlers: Vec<Box<dyn S3Handler + Send + Sync + 'static>>,
+    handlers: Vec<NamedHandler>,
+
+    /// in-flight requests by handler
+    active_requests: ActiveRequests,
+
+    /// requests which were not routed to an operation
+    invalid_requests: AtomicU64,
+
+    /// count of requests of legacy protocols
+    legacy_requests: AtomicU64,
+
+    /// bytes persisted by failed uploads
+    partial_upload_bytes: AtomicU64,
+
+    /// operations which the storage has answered with `NotImplemented`
+    unimplemented: Mutex<BTreeSet<&'static str>>,
 
     /// storage
     storage: Box<dyn S3Storage + Send + Sync + 'static>,
 
     /// auth
     auth: Option<Box<dyn S3Auth + Send + Sync + 'static>>,
+
+    /// how to store keys which are not valid UTF-8
+    key_encoding: KeyEncoding,
+
+    /// audit log of successful mutations
+    audit: Option<AuditQueue>,
+
+    /// recorder of raw exchanges
+    recorder: Option<Recorder>,
+
+    /// limits of request headers
+    header_limits: HeaderLimits,
+
+    /// bucket existence cache
+    bucket_cache: Option<BucketCache>,
+
+    /// logging of document bodies
+    body_log: Option<BodyLog>,
+
+    /// whether to add debug headers to error responses
+    debug_headers: bool,
+
+    /// response compression
+    compression: Option<CompressionConfig>,
+
+    /// whether to serve `GET /_admin/config` and `GET /_admin/runtime`
+    admin_endpoint: bool,
+
+    /// whether to serve `GET /_admin/capabilities`
+    capabilities_endpoint: bool,
+
+    /// whether to serve unsigned requests
+    anonymous_access: bool,
+
+    /// whether to reject anonymous `GET /`
+    deny_root_listing: bool,
+
+    /// whether to verify requests signed by Signature Version 2
+    signature_v2: bool,
+
+    /// the max difference between the time of a request and the server time
+    max_clock_skew: Duration,
+
+    /// the region which requests must be signed for
+    region: Option<String>,
+
+    /// the domain whose subdomains are buckets
+    base_domain: Option<String>,
+
+    /// TLS enforcement
+    tls_policy: TlsPolicy,
+
+    /// operations disabled regardless of the requester
+    operation_policy: OperationPolicy,
+
+    /// correlation ids of clients
+    correlation: Option<CorrelationConfig>,
+
+    /// redirection of large downloads
+    redirect_offload: Option<RedirectOffload>,
+
+    /// how long a write waits until it is readable
+    read_after_write_barrier: Option<Duration>,
+
+    /// allowed extra response headers of objects
+    extra_headers: Option<ExtraHeaders>,
+
+    /// size limits of uploads
+    size_limits: SizeLimits,
+
+    /// limits of draining unread request bodies
+    drain_limits: DrainLimits,
+
+    /// region of the buckets whose storage returns no location
+    default_region: Option<String>,
 }
 
 /// Shared S3 service
@@ -88,10 +210,53 @@ impl hyper::service::Service<Request> for SharedS3Service {
 impl S3Service {
     /// Constructs a S3 service
     pub fn new(storage: impl S3Storage + Send + Sync + 'static) -> Self {
+        let handlers = crate::ops::setup_handlers();
+        let active_requests = ActiveRequests::new(handlers.iter().map(|&(name, _)| name));
         Self {
-            handlers: crate::ops::setup_handlers(),
+            handlers,
+            active_requests,
+            invalid_requests: AtomicU64::new(0),
+            legacy_requests: AtomicU64::new(0),
+            partial_upload_bytes: AtomicU64::new(0),
+            unimplemented: Mutex::new(BTreeSet::new()),
             storage: Box::new(storage),
             auth: None,
+            key_encoding: KeyEncoding::default(),
+            audit: None,
+            recorder: None,
+            header_limits: HeaderLimits::default(),
+            bucket_cache: None,
+            body_log: None,
+            debug_headers: false,
+            compression: None,
+            admin_endpoint: false,
+            capabilities_endpoint: false,
+            anonymous_access: true,
+            deny_root_listing: false,
+            signature_v2: true,
+            max_clock_skew: clock_skew::DEFAULT_MAX_CLOCK_SKEW,
+            region: None,
+            base_domain: None,
+            tls_policy: TlsPolicy::default(),
+            operation_policy: OperationPolicy::default(),
+            correlation: None,
+            redirect_offload: None,
+            read_after_write_barrier: None,
+            size_limits: SizeLimits::default(),
+            drain_limits: DrainLimits::default(),
+            extra_headers: None,
+            default_region: None,
+        }
+    }
+
+    /// Returns a snapshot of the in-flight requests, the counts of invalid and legacy requests and the bytes of failed uploads
+    #[must_use]
+    pub fn runtime_snapshot(&self) -> RuntimeSnapshot {
+        RuntimeSnapshot {
+            active_requests: self.active_requests.snapshot(),
+            invalid_requests: self.invalid_requests.load(Ordering::Relaxed),
+            legacy_requests: self.legacy_requests.load(Ordering::Relaxed),
+            partial_upload_bytes: self.partial_upload_bytes.load(Ordering::Relaxed),
         }
     }
 
@@ -103,6 +268,277 @@ impl S3Service {
         self.auth = Some(Box::new(auth));
     }
 
+    /// Set how to store object keys which are not valid UTF-8.
+    /// They are rejected by default.
+    pub fn set_key_encoding(&mut self, encoding: KeyEncoding) {
+        self.key_encoding = encoding;
+    }
+
+    /// Set the audit sink.
+    ///
+    /// An entry is recorded after each successful `PutObject`, `CopyObject`, `DeleteObject`, `DeleteObjects`,
+    /// `CompleteMultipartUpload`, `CreateBucket`, `DeleteBucket` and `PutBucketVersioning`.
+    /// The sink runs on a background thread behind a queue of `capacity` entries.
+    /// Entries are dropped with a warning when the queue is full, so responses never wait for the sink.
+    /// # Errors
+    /// Returns an `Err` if the background thread can not be spawned
+    pub fn set_audit_sink(&mut self, sink: impl AuditSink, capacity: usize) -> io::Result<()> {
+        self.audit = Some(AuditQueue::spawn(sink, capacity)?);
+        Ok(())
+    }
+
+    /// Set the recorder of raw exchanges, see [`crate::replay`].
+    ///
+    /// Recording buffers whole request and response bodies, so it is meant for debugging.
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Set the limits of request headers. The limits of S3 are used by default.
+    pub fn set_header_limits(&mut self, limits: HeaderLimits) {
+        self.header_limits = limits;
+    }
+
+    /// Set the size limits of uploads. The limits of S3 are used by default.
+    pub fn set_size_limits(&mut self, limits: SizeLimits) {
+        self.size_limits = limits;
+    }
+
+    /// Set the limits of draining a request body which is not read to its end, 64 KiB in one second by default.
+    ///
+    /// A response whose request body is not drained closes the connection, see [`DrainLimits`].
+    pub fn set_drain_limits(&mut self, limits: DrainLimits) {
+        self.drain_limits = limits;
+    }
+
+    /// Set the bucket existence cache. It is disabled by default.
+    ///
+    /// Object operations fail fast with `NoSuchBucket` when their bucket is remembered as missing,
+    /// and storages are told by [`crate::is_bucket_verified`] when it is remembered as existing.
+    /// `CreateBucket` and `DeleteBucket` invalidate the entry of their bucket,
+    /// but changes made directly to the storage are not seen until the entry expires.
+    pub fn set_bucket_cache(&mut self, config: BucketCacheConfig) {
+        self.bucket_cache = config.is_enabled().then(|| BucketCache::new(config));
+    }
+
+    /// Allow requests to have their document bodies logged, up to `limit` bytes each.
+    ///
+    /// Only requests with the header [`X_S3_SERVER_LOG_BODY`](crate::headers::X_S3_SERVER_LOG_BODY) are logged.
+    /// Their xml and json request bodies (`DeleteObjects`, `CompleteMultipartUpload`, ...) and response bodies
+    /// are buffered and logged at `INFO`, with xml pretty-printed and credentials redacted.
+    /// Object data is never logged or buffered.
+    pub fn set_body_log(&mut self, limit: usize) {
+        self.body_log = Some(BodyLog::new(limit));
+    }
+
+    /// Set whether error responses carry debug headers. They are disabled by default.
+    ///
+    /// A `NotImplemented` error of a storage operation carries its name
+    /// in [`X_AMZ_UNIMPLEMENTED_OPERATION`](crate::headers::X_AMZ_UNIMPLEMENTED_OPERATION).
+    pub fn set_debug_headers(&mut self, enabled: bool) {
+        self.debug_headers = enabled;
+    }
+
+    /// Compress responses for clients which accept it. It is disabled by default.
+    ///
+    /// A response is compressed when it is not already encoded, its content type is allowed by `config`,
+    /// and it is either buffered (listings, errors, ...) or a streamed object of at least `config.min_size` bytes.
+    /// A compressed response has `Content-Encoding`, and `Content-Length` only if its body is buffered,
+    /// while `ETag` and the checksum headers still describe the stored object.
+    /// Partial responses of ranged reads are never compressed.
+    ///
+    /// The codings are enabled by the features `compress-gzip` and `compress-zstd`.
+    pub fn set_compression(&mut self, config: CompressionConfig) {
+        self.compression = Some(config);
+    }
+
+    /// Serve the effective configuration as json at `GET /_admin/config`,
+    /// and the [`RuntimeSnapshot`] at `GET /_admin/runtime`. It is disabled by default.
+    ///
+    /// The endpoints are not authenticated. The configuration contains no secrets,
+    /// but it describes the deployment, so expose them to trusted networks only.
+    /// `_admin` is not a valid bucket name, so the path never shadows a bucket.
+    pub fn set_admin_endpoint(&mut self, enabled: bool) {
+        self.admin_endpoint = enabled;
+    }
+
+    /// Serve the capabilities as json at `GET /_admin/capabilities`, see [`Capabilities`]. It is disabled by default.
+    ///
+    /// The endpoint is not authenticated, like `GET /_admin/config`,
+    /// but it tells no more than a client can learn by probing the operations.
+    pub fn set_capabilities_endpoint(&mut self, enabled: bool) {
+        self.capabilities_endpoint = enabled;
+    }
+
+    /// Serve unsigned requests, which are answered as [`Requester::Anonymous`]. It is enabled by default.
+    ///
+    /// While it is disabled, a request without a signature is rejected with `AccessDenied` (403),
+    /// so a service without an auth provider rejects every request.
+    /// Signed requests are served either way.
+    pub fn set_anonymous_access(&mut self, enabled: bool) {
+        self.anonymous_access = enabled;
+    }
+
+    /// Returns whether unsigned requests are served, see [`S3Service::set_anonymous_access`]
+    #[must_use]
+    pub const fn anonymous_access(&self) -> bool {
+        self.anonymous_access
+    }
+
+    /// Reject anonymous `GET /` with `AccessDenied` (403), without consulting the storage. It is disabled by default.
+    ///
+    /// A server exposed to the internet is probed at `/` by crawlers and scanners.
+    /// Signed `ListBuckets` requests are still served.
+    pub fn set_deny_root_listing(&mut self, enabled: bool) {
+        self.deny_root_listing = enabled;
+    }
+
+    /// Verify requests signed by Signature Version 2, by the `Authorization` header or presigned urls. It is enabled by default.
+    ///
+    /// Their signatures are checked against the same [`S3Auth`] as Signature Version 4.
+    /// While it is disabled, they are rejected with `InvalidRequest` (400) as a legacy protocol.
+    pub fn set_signature_v2(&mut self, enabled: bool) {
+        self.signature_v2 = enabled;
+    }
+
+    /// Set the max difference between the time of a request and the server time. It is 15 minutes by default.
+    ///
+    /// A request signed by headers whose `x-amz-date` or `Date` is further away
+    /// is rejected with `RequestTimeTooSkewed` (403), before its signature is checked.
+    /// Presigned urls and POST form uploads expire by themselves, so they are never rejected.
+    pub fn set_max_clock_skew(&mut self, max_skew: Duration) {
+        self.max_clock_skew = max_skew;
+    }
+
+    /// Set the TLS enforcement, such as [`TlsPolicy::require_tls_for_auth`]. Nothing is enforced by default.
+    ///
+    /// The scheme of a request is taken from its [`ConnectionScheme`](crate::ConnectionScheme) extension,
+    /// which a server with a TLS listener inserts.
+    pub fn set_tls_policy(&mut self, policy: TlsPolicy) {
+        self.tls_policy = policy;
+    }
+
+    /// Disable operations for every requester, such as [`OperationPolicy::read_only`]. Every operation is allowed by default.
+    ///
+    /// A disabled operation is rejected before its signature is checked,
+    /// with `MethodNotAllowed` (405) or the error of [`OperationPolicy::rejection`].
+    pub fn set_operation_policy(&mut self, policy: OperationPolicy) {
+        self.operation_policy = policy;
+    }
+
+    /// Record the correlation ids of clients, see [`CorrelationConfig`]. It is disabled by default.
+    ///
+    /// Every response carries a generated `x-amz-request-id` whether it is enabled or not.
+    /// A correlation id is recorded in the `correlation_id` field of the logs and in audit entries,
+    /// and it is echoed if [`CorrelationConfig::echo`] is set.
+    pub fn set_correlation(&mut self, config: CorrelationConfig) {
+        self.correlation = Some(config);
+    }
+
+    /// Redirect large downloads to URLs of the storage, see [`RedirectOffload`]. It is disabled by default.
+    ///
+    /// Only a storage which implements [`S3Storage::presign_get`] is offloaded.
+    pub fn set_redirect_offload(&mut self, offload: RedirectOffload) {
+        self.redirect_offload = Some(offload);
+    }
+
+    /// Wait until each write is readable before answering it, for `timeout` at most. It is disabled by default.
+    ///
+    /// After a successful `PutObject`, `CopyObject` or `CompleteMultipartUpload`,
+    /// the service calls [`S3Storage::wait_for`], so that a following `GetObject` through the service
+    /// sees the new object even if the storage is eventually consistent.
+    /// A write which is still not readable after `timeout` is answered anyway, with a warning.
+    pub fn set_read_after_write_barrier(&mut self, timeout: Duration) {
+        self.read_after_write_barrier = Some(timeout);
+    }
+
+    /// Pass the allowed extra headers of objects through, see [`ExtraHeaders`]. It is disabled by default.
+    ///
+    /// While it is disabled, `x-amz-meta-header-*` is plain user metadata.
+    pub fn set_extra_headers(&mut self, extra_headers: ExtraHeaders) {
+        self.extra_headers = Some(extra_headers);
+    }
+
+    /// Set the region which requests signed by the `Authorization` header must be signed for. Any region is accepted by default.
+    ///
+    /// A request whose credential scope names another region is rejected with `AuthorizationHeaderMalformed` (400),
+    /// whose `Region` tells the client where to retry, like S3.
+    pub fn set_region(&mut self, region: impl Into<String>) {
+        self.region = Some(region.into());
+    }
+
+    /// Serve virtual-hosted-style requests to the subdomains of `base_domain`, such as `s3.example.com`. It is disabled by default.
+    ///
+    /// A request whose `Host` is `<bucket>.<base_domain>`, with any port, addresses the bucket,
+    /// and its whole path is the key. Bucket names may contain dots.
+    /// Requests to `base_domain` itself or to other hosts are path-style.
+    pub fn set_base_domain(&mut self, base_domain: impl Into<String>) {
+        self.base_domain = Some(base_domain.into());
+    }
+
+    /// Set the region of the buckets whose storage returns no location constraint. It is unset by default.
+    ///
+    /// While it is unset, such a bucket is in `us-east-1`,
+    /// which `GetBucketLocation` answers with an empty `<LocationConstraint>` like S3.
+    pub fn set_default_region(&mut self, region: impl Into<String>) {
+        self.default_region = Some(region.into());
+    }
+
+    /// Returns a snapshot of the effective configuration, see [`EffectiveConfig`]
+    #[must_use]
+    pub fn effective_config(&self) -> EffectiveConfig {
+        EffectiveConfig {
+            version: env!("CARGO_PKG_VERSION"),
+            features: effective_config::enabled_features(),
+            operations: self.handlers.iter().map(|&(name, _)| name).collect(),
+            auth: self.auth.is_some(),
+            key_encoding: self.key_encoding,
+            header_limits: self.header_limits,
+            bucket_cache: self.bucket_cache.as_ref().map(BucketCache::config),
+            compression: self.compression.clone(),
+            body_log_limit: self.body_log.map(BodyLog::limit),
+            debug_headers: self.debug_headers,
+            anonymous_access: self.anonymous_access,
+            deny_root_listing: self.deny_root_listing,
+            signature_v2: self.signature_v2,
+            max_clock_skew: self.max_clock_skew,
+            region: self.region.clone(),
+            base_domain: self.base_domain.clone(),
+            tls_policy: self.tls_policy,
+            operation_policy: self.operation_policy.clone(),
+            correlation: self.correlation.clone(),
+            extra_headers: self.extra_headers.clone(),
+            redirect_offload: self.redirect_offload.clone(),
+            read_after_write_barrier: self.read_after_write_barrier,
+            size_limits: self.size_limits,
+            drain_limits: self.drain_limits,
+            default_region: self.default_region.clone(),
+            audit: self.audit.is_some(),
+            recorder: self.recorder.is_some(),
+            storage: self.storage.storage_config(),
+        }
+    }
+
+    /// Returns the capabilities of the service, see [`Capabilities`]
+    ///
+    /// An operation is reported as unimplemented after the storage has answered it with `NotImplemented`,
+    /// since a storage does not declare the operations it implements.
+    #[must_use]
+    pub fn capabilities(&self) -> Capabilities {
+        let operations: Vec<&'static str> = self.handlers.iter().map(|&(name, _)| name).collect();
+        let unimplemented = lock(&self.unimplemented).clone();
+        Capabilities::new(&ServiceState {
+            operations: &operations,
+            unimplemented: &unimplemented,
+            operation_policy: &self.operation_policy,
+            auth: self.auth.is_some(),
+            anonymous_access: self.anonymous_access,
+            deny_root_listing: self.deny_root_listing,
+            signature_v2: self.signature_v2,
+            size_limits: self.size_limits,
+        })
+    }
+
     /// Converts `S3Service` to `SharedS3Service`
     #[must_use]
     pub fn into_shared(self) -> SharedS3Service {
@@ -112,8 +548,10 @@ impl S3Service {
     }
 
     /// call s3 service with a hyper request
+    ///
+    /// Every [`S3Error`] is answered by its xml response, with the path and the id of the request.
     /// # Errors
-    /// Returns an `Err` if any component failed
+    /// Returns an `Err` if the exchange can not be recorded, see [`S3Service::set_recorder`]
     #[tracing::instrument(
         level = "debug",
         skip(self, req),
@@ -121,44 +559,197 @@ impl S3Service {
             method = ?req.method(),
             uri = ?req.uri(),
             start_time = ?chrono::Utc::now(),
+            operation = tracing::field::Empty,
+            request_id = tracing::field::Empty,
+            correlation_id = tracing::field::Empty,
         )
     )]
-    pub async fn hyper_call(&self, req: Request) -> Result<Response, BoxStdError> {
+    pub async fn hyper_call(&self, mut req: Request) -> Result<Response, BoxStdError> {
+        let ids = self.request_ids(&mut req);
         debug!("req = \n{:#?}", req);
-        let ret = match self.handle(req).await {
-            Ok(resp) => Ok(resp),
-            Err(err) => err.into_xml_response().try_into_response(),
+        let (mut req, recording) = match self.recorder {
+            Some(ref recorder) => {
+                let (req, exchange) = recorder.capture_request(req).await?;
+                (req, Some((recorder, exchange)))
+            }
+            None => (req, None),
         };
-
-        match ret {
-            Ok(ref resp) => debug!("resp = \n{:#?}", resp),
-            Err(ref err) => error!(%err),
+        let tracker = BodyTracker::wrap(&mut req);
+
+        let is_head = req.method() == Method::HEAD;
+        let coding = self
+            .compression
+            .as_ref()
+            .and_then(|config| Some((config, compression::negotiate(&req)?)));
+        let resource = req.uri().path().to_owned();
+        let resp = match self.handle(req).await {
+            Ok(resp) => resp,
+            Err(err) => self.error_response(err, resource, &ids),
+        };
+        let resp = match coding {
+            Some((config, coding)) => config.apply(coding, resp).await,
+            None => resp,
         };
+        let mut resp = frame(resp, is_head);
+        self.set_request_ids(&ids, &mut resp);
+        tracker.settle(self.drain_limits, &mut resp).await;
+        debug!("resp = \n{:#?}", resp);
+
+        match recording {
+            Some((recorder, exchange)) => recorder.finish(exchange, resp).await,
+            None => Ok(resp),
+        }
+    }
 
-        Ok(ret?)
+    /// generates the ids of a request unless it carries them, and records them in the current span
+    fn request_ids(&self, req: &mut Request) -> RequestIds {
+        if let Some(ids) = req.extensions().get::<RequestIds>() {
+            return ids.clone();
+        }
+        let ids = RequestIds::new(req, self.correlation.as_ref());
+        let span = Span::current();
+        let _span = span.record("request_id", ids.request_id.as_str());
+        if let Some(ref correlation_id) = ids.correlation_id {
+            let _span = span.record("correlation_id", correlation_id.as_str());
+        }
+        let _prev = req.extensions_mut().insert(ids.clone());
+        ids
+    }
+
+    /// sets `x-amz-request-id`, `x-amz-id-2` and the echoed correlation id of a response
+    fn set_request_ids(&self, ids: &RequestIds, resp: &mut Response) {
+        if let Ok(value) = HeaderValue::from_str(&ids.request_id) {
+            let _prev = resp.headers_mut().insert(X_AMZ_REQUEST_ID.clone(), value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&ids.extended_request_id) {
+            let _prev = resp.headers_mut().insert(X_AMZ_ID_2.clone(), value);
+        }
+        let echo = self.correlation.as_ref().and_then(|config| {
+            let correlation_id = ids.correlation_id.as_deref()?;
+            config.echo_header(correlation_id)
+        });
+        if let Some((name, value)) = echo {
+            let _prev = resp.headers_mut().insert(name, value);
+        }
+    }
+
+    /// converts an error into a response with the enabled debug headers,
+    /// and with its [`PartialUpload`](crate::errors::PartialUpload) as an extension
+    ///
+    /// The `Resource` is the path of the request unless the error names one.
+    /// An error which can not be rendered is answered by an empty `500 Internal Server Error`.
+    fn error_response(&self, err: S3Error, resource: String, ids: &RequestIds) -> Response {
+        let unimplemented_operation = err
+            .unimplemented_operation()
+            .filter(|_| self.debug_headers)
+            .and_then(|operation| HeaderValue::from_str(operation).ok());
+        let partial_upload = err.partial_upload().cloned();
+        let mut xml = err.into_xml_response();
+        let _prev = xml.resource.get_or_insert(resource);
+        xml.request_id = Some(ids.request_id.clone());
+        let mut resp = match xml.try_into_response() {
+            Ok(resp) => resp,
+            Err(err) => {
+                error!(%err, "failed to render an error response");
+                return Response::new_with_status(Body::empty(), StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+        if let Some(value) = unimplemented_operation {
+            let _prev = resp
+                .headers_mut()
+                .insert(X_AMZ_UNIMPLEMENTED_OPERATION.clone(), value);
+        }
+        if let Some(partial) = partial_upload {
+            let _prev = resp.extensions_mut().insert(partial);
+        }
+        resp
     }
 
     /// handle a request
+    ///
+    /// Dropping the future before completion cancels the [`CancellationToken`] of the request,
+    /// which is observed by the work a storage has detached from it, such as a spawned task.
+    /// The [`RequestIds`] of the request are current while it is handled.
     /// # Errors
     /// Returns an `Err` if any component failed
     pub async fn handle(&self, mut req: Request) -> S3Result<Response> {
+        let ids = self.request_ids(&mut req);
+        let token = CancellationToken::new();
+        let guard = CancelOnDrop::new(token.clone());
+        let ret = ids.scope(self.handle_req(req, token)).await;
+        guard.disarm();
+        ret
+    }
+
+    /// handle a request with its cancellation token
+    async fn handle_req(&self, mut req: Request, token: CancellationToken) -> S3Result<Response> {
+        self.header_limits.check(&req)?;
+        if self.admin_endpoint && is_admin_request(&req, ADMIN_CONFIG_PATH) {
+            return self.effective_config().try_into_response();
+        }
+        if self.admin_endpoint && is_admin_request(&req, ADMIN_RUNTIME_PATH) {
+            return self.runtime_snapshot().try_into_response();
+        }
+        if self.capabilities_endpoint && is_admin_request(&req, ADMIN_CAPABILITIES_PATH) {
+            return self.capabilities().try_into_response();
+        }
+        let body_log = self.body_log.filter(|_| BodyLog::is_requested(&req));
+
+        #[allow(clippy::disallowed_methods)] // the body is tracked by `hyper_call`
         let body = mem::take(req.body_mut());
-        let path = extract_s3_path(&req)?;
-        let headers = extract_headers(&req)?;
-        let query_strings = extract_qs(&req)?;
-        let mime = extract_mime(&headers)?;
-
-        let mut ctx: ReqContext<'_> = ReqContext {
-            req: &req,
-            headers,
-            query_strings,
-            path,
+        let mut ctx: ReqContext<'_> = match ReqContext::with_base_domain(
+            &req,
             body,
-            mime,
-            multipart: None,
+            self.key_encoding,
+            self.base_domain.as_deref(),
+        ) {
+            Ok(ctx) => ctx,
+            Err(err) => return Err(self.reject_invalid(err)),
         };
+        ctx.cancellation = token;
+        ctx.auth = self.auth.as_deref().map(AuthRef);
+        ctx.scheme = self.tls_policy.scheme(&req);
+        ctx.read_after_write_barrier = self.read_after_write_barrier;
+        ctx.size_limits = self.size_limits;
+        ctx.default_region = self.default_region.as_deref();
+
+        if let Some(redirect) = self.tls_policy.check(&ctx)? {
+            return Ok(redirect);
+        }
+        if let Some(protocol) = legacy_protocols::detect(&mut ctx, self.signature_v2).await {
+            return Err(self.reject_legacy(protocol));
+        }
+        if ctx.method == Method::OPTIONS {
+            let _span = Span::current().record("operation", "preflight");
+            return cors::preflight(&ctx, &*self.storage).await;
+        }
+        let found = self.route(&mut ctx);
+        let head_as_get = ctx.method != ctx.req.method();
+        if let Some((_, &(name, _))) = found {
+            let _span = Span::current().record("operation", name);
+            self.operation_policy.check(name)?;
+        }
+        let signing = check_signature(
+            &mut ctx,
+            self.auth.as_deref(),
+            self.max_clock_skew,
+            self.region.as_deref(),
+        )
+        .await?;
+        let hashed_body = payload_hash::verify(&mut ctx);
+        let chunked_body = chunked_upload::decode(&mut ctx, signing);
+        if let (Some(auth), Some(access_key)) = (self.auth.as_deref(), ctx.access_key.as_deref()) {
+            ctx.canonical_user = Some(fetch_canonical_user(auth, access_key).await?);
+        }
 
-        check_signature(&mut ctx, self.auth.as_deref()).await?;
+        if !self.anonymous_access && ctx.access_key.is_none() {
+            return Err(self.reject_invalid(code_error!(AccessDenied, "Access Denied")));
+        }
+        let is_root_listing =
+            ctx.path.is_root() && (ctx.method == Method::GET || ctx.method == Method::HEAD);
+        if self.deny_root_listing && is_root_listing && ctx.access_key.is_none() {
+            return Err(self.reject_invalid(code_error!(AccessDenied, "Access Denied")));
+        }
 
         if ctx.req.method() == Method::POST && ctx.path.is_object() && ctx.multipart.is_some() {
             return Err(code_error!(
@@ -167,22 +758,251 @@ impl S3Service {
             ));
         }
 
-        for handler in &self.handlers {
-            if handler.is_match(&ctx) {
-                return handler.handle(&mut ctx, &*self.storage).await;
+        if let Some(ref qs) = ctx.query_strings {
+            check_query_strings(qs)?;
+        }
+
+        let (idx, &(name, ref handler)) = match found {
+            Some(found) => found,
+            None => return Err(self.reject_unrouted(&ctx)),
+        };
+
+        let _active = self.active_requests.enter(idx);
+        let body_counter = self.size_limits.limit_body(name, &mut ctx)?;
+        if let Some(ref body_log) = body_log {
+            body_log.tee_request(name, &mut ctx).await?;
+        }
+        if bucket_freeze::is_mutating(name) {
+            check_bucket_freeze(&ctx, name, &*self.storage).await?;
+        }
+        if let Some(ref cache) = self.bucket_cache {
+            if let S3Path::Object { bucket, .. } = ctx.path {
+                ctx.bucket_verified = cache.check(bucket, &*self.storage).await?;
+            }
+        }
+        if let Some(ref extra_headers) = self.extra_headers {
+            if extra_headers::is_write(name) {
+                extra_headers.check_request(&ctx)?;
+            }
+        }
+        if let Some(ref offload) = self.redirect_offload {
+            if let Some(redirect) = offload.check(name, &ctx, &*self.storage).await? {
+                return Ok(redirect);
+            }
+        }
+        let token = ctx.cancellation.clone();
+        let verified = ctx.bucket_verified;
+        let requester = Requester::new(ctx.access_key.as_deref());
+        let handling = requester.scope(handler.handle(&mut ctx, &*self.storage));
+        let mut ret = token.scope(bucket_cache::scope(verified, handling)).await;
+        if let Some(ref chunked_body) = chunked_body {
+            ret = chunked_body.check(ret);
+        }
+        if let Some(ref hashed_body) = hashed_body {
+            ret = hashed_body.check(ret);
+        }
+        if let Some(ref counter) = body_counter {
+            ret = counter.check(ret);
+        }
+        if let Some(operation) = ret
+            .as_ref()
+            .err()
+            .and_then(S3Error::unimplemented_operation)
+        {
+            let _new = lock(&self.unimplemented).insert(operation);
+        }
+        if let Some(partial) = ret.as_ref().err().and_then(S3Error::partial_upload) {
+            debug!(?partial, "an upload failed midway");
+            let _prev = self
+                .partial_upload_bytes
+                .fetch_add(partial.bytes_persisted, Ordering::Relaxed);
+        }
+        if let (Some(ref extra_headers), Ok(ref mut resp)) = (&self.extra_headers, &mut ret) {
+            if name == "GetObject" || name == "HeadObject" {
+                extra_headers.apply(resp);
+            }
+        }
+        if let Ok(ref mut resp) = ret {
+            cors::apply(&ctx, &*self.storage, resp).await;
+        }
+        if let (Some(ref cache), S3Path::Bucket { bucket }) = (&self.bucket_cache, &ctx.path) {
+            if name == "CreateBucket" || name == "DeleteBucket" {
+                cache.invalidate(bucket);
             }
         }
+        if let Ok(ref resp) = ret {
+            self.record_audit(name, &ctx, resp);
+        }
+        let ret = match body_log {
+            Some(ref body_log) => body_log.tee_response(name, ret).await,
+            None => ret,
+        };
+        if head_as_get {
+            return ret.map(strip_body);
+        }
+        ret
+    }
+
+    /// counts a request which is not routed to an operation
+    ///
+    /// Such requests are grouped as `invalid` in logs and metrics,
+    /// so that scanner probes do not add a label per path.
+    fn reject_invalid(&self, err: S3Error) -> S3Error {
+        let _prev = self.invalid_requests.fetch_add(1, Ordering::Relaxed);
+        let _span = Span::current().record("operation", "invalid");
+        err
+    }
+
+    /// rejects a request which no handler matches with `NotImplemented`, which names the sub-resource if there is one
+    fn reject_unrouted(&self, ctx: &ReqContext<'_>) -> S3Error {
+        let selector = ctx.query_strings.as_ref().and_then(|qs| {
+            SUBRESOURCE_SELECTORS
+                .iter()
+                .copied()
+                .find(|&name| qs.contains(name))
+        });
+        let err = selector.map_or_else(
+            || {
+                code_error!(
+                    NotImplemented,
+                    format!(
+                        "The {} method is not implemented for this resource.",
+                        ctx.method
+                    )
+                )
+            },
+            |name| {
+                code_error!(
+                    NotImplemented,
+                    format!("The {} sub-resource is not implemented.", name)
+                )
+            },
+        );
+        self.reject_invalid(err)
+    }
+
+    /// counts a request of a legacy protocol, which is grouped as `legacy` in logs and metrics
+    fn reject_legacy(&self, protocol: LegacyProtocol) -> S3Error {
+        let _prev = self.legacy_requests.fetch_add(1, Ordering::Relaxed);
+        let _span = Span::current().record("operation", "legacy");
+        debug!(?protocol, "rejected a request of a legacy protocol");
+        protocol.error()
+    }
+
+    /// routes a request to its handler before its signature is checked
+    ///
+    /// A `HEAD` is served by the `GET` handler when it selects a sub-resource
+    /// or no `HEAD` handler matches, and the body is removed from the response.
+    /// The method of `ctx` is changed to `GET` then.
+    fn route(&self, ctx: &mut ReqContext<'_>) -> Option<(usize, &NamedHandler)> {
+        let found = self.find_handler(ctx);
+        let head_as_get = ctx.method == Method::HEAD
+            && (found.is_none() || ctx.query_strings.as_ref().map_or(false, has_subresource));
+        if !head_as_get {
+            return found;
+        }
+        ctx.method = Method::GET;
+        self.find_handler(ctx)
+    }
+
+    /// finds the first handler which matches the request
+    ///
+    /// A sub-resource is never served by an operation on the bucket or the object itself.
+    fn find_handler(&self, ctx: &ReqContext<'_>) -> Option<(usize, &NamedHandler)> {
+        let found = self
+            .handlers
+            .iter()
+            .enumerate()
+            .find(|&(_, &(_, ref handler))| handler.is_match(ctx))?;
+        let (_, &(name, _)) = found;
+        let unserved = ops::is_resource_operation(name)
+            && ctx.query_strings.as_ref().map_or(false, has_subresource);
+        if unserved {
+            return None;
+        }
+        Some(found)
+    }
+
+    /// records a successful mutation
+    fn record_audit(&self, operation: &str, ctx: &ReqContext<'_>, resp: &Response) {
+        let queue = match self.audit {
+            Some(ref queue) => queue,
+            None => return,
+        };
+        if !resp.status().is_success() || !audit::is_audited(operation) {
+            return;
+        }
+        let (bucket, key) = match ctx.path {
+            S3Path::Root => return,
+            S3Path::Bucket { bucket } => (bucket, None),
+            S3Path::Object { bucket, ref key } => (bucket, Some(key.as_ref().to_owned())),
+        };
+        let header = |name: &HeaderName| {
+            let value = resp.headers().get(name)?.to_str().ok()?;
+            Some(value.to_owned())
+        };
+        let ids = ctx.req.extensions().get::<RequestIds>();
+        queue.push(AuditEntry {
+            operation: operation.to_owned(),
+            bucket: bucket.to_owned(),
+            key,
+            version_id: header(&X_AMZ_VERSION_ID),
+            principal: ctx.access_key.clone(),
+            request_id: ids.map(|ids| ids.request_id.clone()),
+            correlation_id: ids.and_then(|ids| ids.correlation_id.clone()),
+            source_ip: ctx
+                .req
+                .extensions()
+                .get::<ClientAddr>()
+                .map(|addr| addr.0.ip().to_string()),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            content_hash: header(&ETAG),
+        });
+    }
+}
 
-        Err(not_supported!("The operation is not supported yet."))
+/// rejects a write to a frozen bucket
+async fn check_bucket_freeze(
+    ctx: &ReqContext<'_>,
+    operation: &str,
+    storage: &(dyn S3Storage + Send + Sync),
+) -> S3Result<()> {
+    let bucket = match ctx.path {
+        S3Path::Root => return Ok(()),
+        S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => bucket,
+    };
+    match storage.get_bucket_freeze(bucket).await? {
+        Some(freeze) if freeze.rejects(operation) => Err(freeze.rejection(bucket)),
+        _ => Ok(()),
     }
 }
 
-/// util function
-fn extract_s3_path(req: &Request) -> S3Result<S3Path<'_>> {
-    let result = S3Path::try_from_path(req.uri().path());
-    let err = try_err!(result);
+/// extracts the s3 path of a path-style request, or of a virtual-hosted-style request to a subdomain of `base_domain`,
+/// which returns whether the request is virtual-hosted-style
+pub(crate) fn extract_s3_path<'a>(
+    req: &'a Request,
+    encoding: KeyEncoding,
+    base_domain: Option<&str>,
+) -> S3Result<(S3Path<'a>, bool)> {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| req.uri().host());
+    let bucket = base_domain
+        .zip(host)
+        .and_then(|(base_domain, host)| path::virtual_host_bucket(host, base_domain));
+    let path = req.uri().path();
+    let result = bucket.map_or_else(
+        || S3Path::try_from_path_with(path, encoding),
+        |bucket| S3Path::try_from_virtual_host_with(bucket, path, encoding),
+    );
+    let err = match result {
+        Ok(path) => return Ok((path, bucket.is_some())),
+        Err(err) => err,
+    };
     let (code, msg) = match *err.kind() {
-        S3PathErrorKind::InvalidPath => {
+        S3PathErrorKind::InvalidPath | S3PathErrorKind::InvalidKeyEncoding => {
             (S3ErrorCode::InvalidURI, "Couldn't parse the specified URI.")
         }
         S3PathErrorKind::InvalidBucketName => (
@@ -194,21 +1014,174 @@ fn extract_s3_path(req: &Request) -> S3Result<S3Path<'_>> {
     Err(code_error!(code = code, msg, err))
 }
 
+/// the path of the effective configuration
+const ADMIN_CONFIG_PATH: &str = "/_admin/config";
+
+/// the path of the runtime snapshot
+const ADMIN_RUNTIME_PATH: &str = "/_admin/runtime";
+
+/// the path of the capabilities
+const ADMIN_CAPABILITIES_PATH: &str = "/_admin/capabilities";
+
+/// checks whether `req` reads the admin document at `path`
+fn is_admin_request(req: &Request, path: &str) -> bool {
+    let method = req.method();
+    bool_try!(*method == Method::GET || *method == Method::HEAD);
+    req.uri().path() == path
+}
+
+/// locks a mutex, ignoring the poison of a panicked holder
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
 /// extrace `OrderedHeaders<'_>` from request
-fn extract_headers(req: &Request) -> S3Result<OrderedHeaders<'_>> {
+pub(crate) fn extract_headers(req: &Request) -> S3Result<OrderedHeaders<'_>> {
     let err = try_err!(OrderedHeaders::from_req(req));
     invalid_request!("Invalid headers", err).apply(Err)
 }
 
 /// extract `Option<OrderedQs>` from request
-fn extract_qs(req: &Request) -> S3Result<Option<OrderedQs>> {
+pub(crate) fn extract_qs(req: &Request) -> S3Result<Option<OrderedQs>> {
     let query = try_some!(req.uri().query());
     let err = try_err!(OrderedQs::from_query(query).map(Some));
     invalid_request!("Invalid query strings", err).apply(Err)
 }
 
+/// removes the fields of a presigned url from the query strings, which do not select its operation,
+/// and returns all the query strings of the url, which are signed
+pub(crate) fn take_presigned_qs(query_strings: &mut Option<OrderedQs>) -> Option<OrderedQs> {
+    let qs = query_strings.as_mut()?;
+    let is_presigned_query_string = if qs.contains("X-Amz-Signature") {
+        signature_v4::is_presigned_query_string
+    } else if signature_v2::is_presigned(qs) {
+        signature_v2::is_presigned_query_string
+    } else {
+        return None;
+    };
+    let presigned_qs = qs.clone();
+    qs.retain(|name| !is_presigned_query_string(name));
+    if qs.as_ref().is_empty() {
+        *query_strings = None;
+    }
+    Some(presigned_qs)
+}
+
+/// Sub-resource selectors which determine the operation of a request.
+///
+/// At most one of them may occur in a query.
+/// `uploadId` is allowed to be combined with `partNumber`, which is not a selector.
+const SUBRESOURCE_SELECTORS: &[&str] = &[
+    "accelerate",
+    "acl",
+    "analytics",
+    "cors",
+    "delete",
+    "encryption",
+    "intelligent-tiering",
+    "inventory",
+    "legal-hold",
+    "lifecycle",
+    "location",
+    "logging",
+    "metrics",
+    "notification",
+    "object-lock",
+    "ownershipControls",
+    "policy",
+    "policyStatus",
+    "publicAccessBlock",
+    "replication",
+    "requestPayment",
+    "restore",
+    "retention",
+    "select",
+    "tagging",
+    "torrent",
+    "uploadId",
+    "uploads",
+    "versioning",
+    "versions",
+    "website",
+];
+
+/// checks whether the query selects a sub-resource
+///
+/// A `HEAD` with a selector is served by the `GET` handler of the sub-resource,
+/// instead of `HeadBucket` or `HeadObject`.
+fn has_subresource(qs: &OrderedQs) -> bool {
+    SUBRESOURCE_SELECTORS.iter().any(|&name| qs.contains(name))
+}
+
+/// makes the framing of a response deterministic
+///
+/// + A response without content (`1xx`, `204` and `304`) has no body, `Content-Length` or `Transfer-Encoding`.
+/// + A body of known size, such as a buffered document, is sent with `Content-Length`.
+/// + A streamed body is sent with the `Content-Length` from its handler, such as the size of an object,
+///   and only a stream of unknown size is sent chunked.
+/// + A response to `HEAD` has the `Content-Length` of the body which `GET` would have.
+///
+/// `Transfer-Encoding` is decided by hyper, so it is removed if a handler sets it.
+fn frame(mut resp: Response, is_head: bool) -> Response {
+    let _prev = resp.headers_mut().remove(TRANSFER_ENCODING);
+
+    let status = resp.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        let _prev = resp.headers_mut().remove(CONTENT_LENGTH);
+        *resp.body_mut() = Body::empty();
+        return resp;
+    }
+    if is_head {
+        return strip_body(resp);
+    }
+    if let Some(len) = HttpBody::size_hint(resp.body()).exact() {
+        let _prev = resp.headers_mut().insert(CONTENT_LENGTH, len.into());
+    }
+    resp
+}
+
+/// removes the body of a response to `HEAD`
+///
+/// `Content-Length` is kept, or set to the length of the removed body if it is known.
+fn strip_body(mut resp: Response) -> Response {
+    let body = mem::take(resp.body_mut());
+    if !resp.headers().contains_key(CONTENT_LENGTH) {
+        if let Some(len) = HttpBody::size_hint(&body).exact() {
+            let _prev = resp.headers_mut().insert(CONTENT_LENGTH, len.into());
+        }
+    }
+    resp
+}
+
+/// check query strings before dispatching
+///
+/// + Duplicate names are resolved by `OrderedQs`: the first value wins.
+/// + Conflicting sub-resource selectors are rejected with `InvalidArgument`,
+///   instead of routing to whichever handler happens to match first.
+fn check_query_strings(qs: &OrderedQs) -> S3Result<()> {
+    let mut iter = SUBRESOURCE_SELECTORS
+        .iter()
+        .copied()
+        .filter(|&name| qs.contains(name));
+
+    if let (Some(first), Some(second)) = (iter.next(), iter.next()) {
+        return Err(code_error!(
+            InvalidArgument,
+            format!("Conflicting query string parameters: {}, {}", first, second)
+        ));
+    }
+
+    Ok(())
+}
+
 /// extrace `Option<Mime>` from headers
-fn extract_mime(headers: &OrderedHeaders<'_>) -> S3Result<Option<Mime>> {
+pub(crate) fn extract_mime(headers: &OrderedHeaders<'_>) -> S3Result<Option<Mime>> {
     let content_type = try_some!(headers.get(CONTENT_TYPE));
     let err = try_err!(content_type.parse::<Mime>().map(Some));
     invalid_request!("Invalid header: Content-Type", err).apply(Err)
@@ -244,7 +1217,9 @@ fn extract_amz_date(headers: &'_ OrderedHeaders<'_>) -> S3Result<Option<AmzDate>
 }
 
 /// replace `body` with an empty body and transform it to IO stream
-fn take_io_body(body: &mut Body) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+pub(crate) fn take_io_body(
+    body: &mut Body,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
     mem::take(body).map(|try_chunk| {
         try_chunk.map_err(|e| {
             io::Error::new(
@@ -255,34 +1230,59 @@ fn take_io_body(body: &mut Body) -> impl Stream<Item = io::Result<Bytes>> + Send
     })
 }
 
-/// check signature (v4)
+/// check signature (v4 or v2), which returns what verifies the chunk signatures of a streaming upload
 async fn check_signature(
     ctx: &mut ReqContext<'_>,
     auth: Option<&(dyn S3Auth + Send + Sync)>,
-) -> S3Result<()> {
+    max_clock_skew: Duration,
+    region: Option<&str>,
+) -> S3Result<Option<ChunkSigning>> {
     // --- POST auth ---
-    if ctx.req.method() == Method::POST {
-        if let Some(mime) = ctx.mime.as_ref() {
-            if mime.type_() == mime::MULTIPART && mime.subtype() == mime::FORM_DATA {
-                return check_post_signature(ctx, auth).await;
-            }
-        }
+    if ctx.is_form_upload() {
+        return check_post_signature(ctx, auth).await.map(|()| None);
     }
 
     // --- query auth ---
-    if let Some(qs) = ctx.query_strings.as_ref() {
-        if qs.get("X-Amz-Signature").is_some() {
-            return check_presigned_url(ctx, auth).await;
-        }
+    if let Some(qs) = ctx.presigned_qs.take() {
+        let ret = if qs.contains("X-Amz-Signature") {
+            check_presigned_url(ctx, &qs, auth).await
+        } else {
+            check_presigned_url_v2(ctx, &qs, auth).await
+        };
+        return ret.map(|()| None);
     }
 
     // --- header auth ---
-    check_header_auth(ctx, auth).await
+    if auth.is_some() && ctx.headers.get(AUTHORIZATION).is_some() {
+        clock_skew::check(&ctx.headers, max_clock_skew, chrono::Utc::now())?;
+    }
+    let is_v2 = ctx
+        .headers
+        .get(AUTHORIZATION)
+        .map_or(false, signature_v2::is_authorization_v2);
+    if is_v2 {
+        return check_header_auth_v2(ctx, auth).await.map(|()| None);
+    }
+    check_header_auth(ctx, auth, region).await
 }
 
 /// fetch secret key from auth
 async fn fetch_secret_key(auth: &(dyn S3Auth + Send + Sync), access_key: &str) -> S3Result<String> {
     match try_err!(auth.get_secret_access_key(access_key).await) {
+        S3AuthError::Other(e) => Err(e),
+        S3AuthError::NotSignedUp => Err(code_error!(
+            InvalidAccessKeyId,
+            "The AWS Access Key Id you provided does not exist in our records."
+        )),
+    }
+}
+
+/// fetch the canonical user of an access key from auth
+async fn fetch_canonical_user(
+    auth: &(dyn S3Auth + Send + Sync),
+    access_key: &str,
+) -> S3Result<CanonicalUser> {
+    match try_err!(auth.get_canonical_user(access_key).await) {
         S3AuthError::Other(e) => Err(e),
         S3AuthError::NotSignedUp => Err(code_error!(NotSignedUp, "Your account is not signed up")),
     }
@@ -309,15 +1309,6 @@ async fn check_post_signature(
         ))
     }
 
-    let auth_provider = match auth {
-        Some(a) => a,
-        None => {
-            return Err(not_supported!(
-                "The service has no authentication provider."
-            ))
-        }
-    };
-
     let mime = ctx.mime.as_ref().unwrap_or_else(|| panic!("missing mime"));
 
     let boundary = mime
@@ -329,7 +1320,11 @@ async fn check_post_signature(
     let multipart = multipart::transform_multipart(body, boundary.as_str().as_bytes())
         .await
         .map_err(|err| invalid_request!("Invalid multipart/form-data body", err))?;
-    {
+
+    // an anonymous service accepts any form, and an unsigned form is answered as anonymous
+    let is_signed = multipart.find_field_value("policy").is_some()
+        || multipart.find_field_value("x-amz-signature").is_some();
+    if let Some(auth_provider) = auth.filter(|_| is_signed) {
         let (policy, x_amz_algorithm, x_amz_credential, x_amz_date, x_amz_signature) = {
             match find_info(&multipart) {
                 None => return Err(invalid_request!("Missing required fields")),
@@ -350,8 +1345,11 @@ async fn check_post_signature(
         }
 
         // check x_amz_credential
-        let (_, credential) = CredentialV4::parse_by_nom(x_amz_credential)
-            .map_err(|_err| invalid_request!("Invalid field: x-amz-credential"))?;
+        let credential = CredentialV4::parse_by_nom(x_amz_credential)
+            .ok()
+            .map(|(_, credential)| credential)
+            .filter(CredentialV4::is_aws4_request)
+            .ok_or_else(|| invalid_request!("Invalid field: x-amz-credential"))?;
 
         // check x_amz_date
         let amz_date = AmzDate::from_header_str(x_amz_date)
@@ -373,6 +1371,16 @@ async fn check_post_signature(
         if signature != x_amz_signature {
             return Err(signature_mismatch!());
         }
+
+        // check policy document
+        let bucket = match ctx.path {
+            S3Path::Root => "",
+            S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => bucket,
+        };
+        let policy = PostPolicy::from_base64(policy)?;
+        ctx.content_length_range = policy.check(bucket, &multipart, chrono::Utc::now())?;
+
+        ctx.access_key = Some(credential.access_key_id.to_owned());
     }
 
     // store ctx value
@@ -384,19 +1392,14 @@ async fn check_post_signature(
 /// check presigned url (v4)
 async fn check_presigned_url(
     ctx: &mut ReqContext<'_>,
+    qs: &OrderedQs,
     auth: Option<&(dyn S3Auth + Send + Sync)>,
 ) -> S3Result<()> {
-    let qs = ctx
-        .query_strings
-        .as_ref()
-        .unwrap_or_else(|| panic!("missing query string"));
-
     let presigned_url = signature_v4::PresignedUrl::from_query(qs)
         .map_err(|err| invalid_request!("Missing presigned fields", err))?;
 
-    let content_sha256: Option<AmzContentSha256<'_>> = extract_amz_content_sha256(&ctx.headers)?;
-
-    drop(content_sha256); // how to use it?
+    // a hex SHA256 of the body is not signed, but it is still verified while the body is streamed
+    let _content_sha256 = extract_amz_content_sha256(&ctx.headers)?;
 
     let auth_provider = match auth {
         Some(a) => a,
@@ -434,6 +1437,167 @@ async fn check_presigned_url(
         return Err(signature_mismatch!());
     }
 
+    let expires_at = presigned_url.amz_date.to_date_time().and_then(|date| {
+        date.checked_add_signed(chrono::Duration::seconds(presigned_url.expires.into()))
+    });
+    if expires_at.map_or(true, |expires_at| expires_at < chrono::Utc::now()) {
+        return Err(code_error!(AccessDenied, "Request has expired"));
+    }
+
+    ctx.access_key = Some(presigned_url.credential.access_key_id.to_owned());
+
+    Ok(())
+}
+
+/// the path of the canonicalized resource (v2), which starts with the bucket of a virtual-hosted-style request
+fn canonical_resource_path<'a>(ctx: &ReqContext<'a>) -> Cow<'a, str> {
+    let path = ctx.req.uri().path();
+    match ctx.path {
+        S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } if ctx.virtual_hosted => {
+            Cow::Owned(format!("/{}{}", bucket, path))
+        }
+        S3Path::Root | S3Path::Bucket { .. } | S3Path::Object { .. } => Cow::Borrowed(path),
+    }
+}
+
+/// check presigned url (v2)
+async fn check_presigned_url_v2(
+    ctx: &mut ReqContext<'_>,
+    qs: &OrderedQs,
+    auth: Option<&(dyn S3Auth + Send + Sync)>,
+) -> S3Result<()> {
+    let presigned_url = signature_v2::PresignedUrlV2::from_query(qs)
+        .ok_or_else(|| invalid_request!("Missing presigned fields"))?;
+
+    let auth_provider = match auth {
+        Some(a) => a,
+        None => {
+            return Err(not_supported!(
+                "The service has no authentication provider."
+            ))
+        }
+    };
+
+    let secret_key = fetch_secret_key(auth_provider, presigned_url.access_key_id).await?;
+
+    let string_to_sign = signature_v2::create_string_to_sign(
+        ctx.req.method(),
+        &ctx.headers,
+        presigned_url.expires_str,
+        &canonical_resource_path(ctx),
+        Some(qs),
+    );
+    let signature = signature_v2::calculate_signature(&string_to_sign, &secret_key);
+
+    if signature != presigned_url.signature {
+        return Err(signature_mismatch!());
+    }
+
+    if presigned_url.expires < chrono::Utc::now().timestamp() {
+        return Err(code_error!(AccessDenied, "Request has expired"));
+    }
+
+    ctx.access_key = Some(presigned_url.access_key_id.to_owned());
+
+    Ok(())
+}
+
+/// check header auth (v2)
+async fn check_header_auth_v2(
+    ctx: &mut ReqContext<'_>,
+    auth: Option<&(dyn S3Auth + Send + Sync)>,
+) -> S3Result<()> {
+    let auth_provider = match auth {
+        Some(a) => a,
+        None => {
+            return Err(not_supported!(
+                "The service has no authentication provider."
+            ))
+        }
+    };
+
+    let authorization = ctx.headers.get(AUTHORIZATION).unwrap_or("");
+    let auth = signature_v2::AuthorizationV2::from_header_str(authorization)
+        .ok_or_else(|| invalid_request!("Invalid header: Authorization"))?;
+
+    let secret_key = fetch_secret_key(auth_provider, auth.access_key_id).await?;
+
+    // `x-amz-date` takes the place of `Date`, which is signed as an empty line
+    let date = if ctx.headers.get(&*X_AMZ_DATE).is_some() {
+        ""
+    } else {
+        ctx.headers
+            .get(DATE)
+            .ok_or_else(|| invalid_request!("Missing header: Date"))?
+    };
+
+    let string_to_sign = signature_v2::create_string_to_sign(
+        ctx.req.method(),
+        &ctx.headers,
+        date,
+        &canonical_resource_path(ctx),
+        ctx.query_strings.as_ref(),
+    );
+    let signature = signature_v2::calculate_signature(&string_to_sign, &secret_key);
+
+    if signature != auth.signature {
+        return Err(signature_mismatch!());
+    }
+
+    ctx.access_key = Some(auth.access_key_id.to_owned());
+
+    Ok(())
+}
+
+/// check the credential scope of header auth (v4) against `x-amz-date` and the region of the service
+fn check_credential_scope(
+    credential: &CredentialV4<'_>,
+    amz_date: &AmzDate,
+    region: Option<&str>,
+) -> S3Result<()> {
+    /// the prefix of the messages
+    const MALFORMED: &str = "The authorization header is malformed;";
+
+    if credential.date != amz_date.to_date() {
+        return Err(code_error!(
+            AuthorizationHeaderMalformed,
+            format!(
+                "{} Invalid credential date. Date is not the same as X-Amz-Date.",
+                MALFORMED
+            )
+        ));
+    }
+    if credential.aws_service != "s3" {
+        return Err(code_error!(
+            AuthorizationHeaderMalformed,
+            format!(
+                "{} incorrect service '{}'. This endpoint belongs to 's3'.",
+                MALFORMED, credential.aws_service
+            )
+        ));
+    }
+    if !credential.is_aws4_request() {
+        return Err(code_error!(
+            AuthorizationHeaderMalformed,
+            format!(
+                "{} incorrect terminal '{}'. This endpoint uses 'aws4_request'.",
+                MALFORMED, credential.terminator
+            )
+        ));
+    }
+    if let Some(region) = region {
+        if credential.aws_region != region {
+            return Err(
+                S3Error::from_code(S3ErrorCode::AuthorizationHeaderMalformed)
+                    .message(format!(
+                        "{} the region '{}' is wrong; expecting '{}'",
+                        MALFORMED, credential.aws_region, region
+                    ))
+                    .region(region)
+                    .finish(),
+            );
+        }
+    }
     Ok(())
 }
 
@@ -441,18 +1605,33 @@ async fn check_presigned_url(
 async fn check_header_auth(
     ctx: &mut ReqContext<'_>,
     auth: Option<&(dyn S3Auth + Send + Sync)>,
-) -> S3Result<()> {
+    region: Option<&str>,
+) -> S3Result<Option<ChunkSigning>> {
     let amz_content_sha256 = match extract_amz_content_sha256(&ctx.headers)? {
         Some(h) => h,
-        None => return Ok(()),
+        // a signed request is never served as an unsigned one
+        None if auth.is_some() && ctx.headers.get(AUTHORIZATION).is_some() => {
+            return Err(invalid_request!("Missing header: x-amz-content-sha256"))
+        }
+        None => return Ok(None),
     };
 
     // --- header auth ---
-    let is_stream = match amz_content_sha256 {
-        AmzContentSha256::UnsignedPayload => return Ok(()),
-        AmzContentSha256::SingleChunk { .. } => false,
-        AmzContentSha256::MultipleChunks => true,
+    let payload = match amz_content_sha256 {
+        // an unsigned request may declare an unsigned payload
+        AmzContentSha256::UnsignedPayload => {
+            if auth.is_none() || ctx.headers.get(AUTHORIZATION).is_none() {
+                return Ok(None);
+            }
+            signature_v4::Payload::Unsigned
+        }
+        // the body is verified against the checksum while it is streamed
+        AmzContentSha256::SingleChunk { payload_checksum } => {
+            signature_v4::Payload::SingleChunkSha256(payload_checksum)
+        }
+        AmzContentSha256::MultipleChunks => signature_v4::Payload::MultipleChunks,
     };
+    let is_stream = matches!(payload, signature_v4::Payload::MultipleChunks);
 
     let auth_provider = match auth {
         Some(a) => a,
@@ -467,10 +1646,11 @@ async fn check_header_auth(
         .ok_or_else(|| invalid_request!("Missing header: Authorization"))?
         .also(|auth| auth.signed_headers.sort_unstable());
 
-    let secret_key = fetch_secret_key(auth_provider, auth.credential.access_key_id).await?;
-
     let amz_date = extract_amz_date(&ctx.headers)?
         .ok_or_else(|| invalid_request!("Missing header: x-amz-date"))?;
+    check_credential_scope(&auth.credential, &amz_date, region)?;
+
+    let secret_key = fetch_secret_key(auth_provider, auth.credential.access_key_id).await?;
 
     let signature = {
         let method = ctx.req.method();
@@ -481,37 +1661,13 @@ async fn check_header_auth(
         // here requires that `auth.signed_headers` is sorted
         let headers = ctx.headers.map_signed_headers(&auth.signed_headers);
 
-        let canonical_request = if is_stream {
-            signature_v4::create_canonical_request(
-                method,
-                uri_path,
-                query_strings,
-                &headers,
-                signature_v4::Payload::MultipleChunks,
-            )
-        } else {
-            let bytes = std::mem::take(&mut ctx.body)
-                .apply(hyper::body::to_bytes)
-                .await
-                .map_err(|err| invalid_request!("Can not obtain the whole request body.", err))?;
-
-            let payload = if bytes.is_empty() {
-                signature_v4::Payload::Empty
-            } else {
-                signature_v4::Payload::SingleChunk(&bytes)
-            };
-            let ans = signature_v4::create_canonical_request(
-                method,
-                uri_path,
-                query_strings,
-                &headers,
-                payload,
-            );
-
-            ctx.body = Body::from(bytes);
-
-            ans
-        };
+        let canonical_request = signature_v4::create_canonical_request(
+            method,
+            uri_path,
+            query_strings,
+            &headers,
+            payload,
+        );
 
         let region = auth.credential.aws_region;
         let string_to_sign =
@@ -524,19 +1680,120 @@ async fn check_header_auth(
         return Err(signature_mismatch!());
     }
 
-    if is_stream {
-        let body = take_io_body(&mut ctx.body);
+    ctx.access_key = Some(auth.credential.access_key_id.to_owned());
 
-        let chunked_stream = AwsChunkedStream::new(
-            body,
-            signature.into(),
-            amz_date,
-            auth.credential.aws_region.into(),
-            secret_key.into(),
-        );
+    if !is_stream {
+        return Ok(None);
+    }
+    Ok(Some(ChunkSigning {
+        seed_signature: signature.into(),
+        amz_date,
+        region: auth.credential.aws_region.into(),
+        secret_key: secret_key.into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::storages::memory::MemoryStorage;
+
+    /// a method, a URI and headers, with the operation or the code of the rejection
+    type Case = (
+        Method,
+        &'static str,
+        &'static [(&'static str, &'static str)],
+        &'static str,
+    );
 
-        ctx.body = Body::wrap_stream(chunked_stream);
+    const COPY_SOURCE: &[(&str, &str)] = &[("x-amz-copy-source", "/asd/src")];
+
+    fn request(method: Method, uri: &str, headers: &[(&str, &str)]) -> Request {
+        headers
+            .iter()
+            .fold(
+                hyper::Request::builder().method(method).uri(uri),
+                |builder, &(name, value)| builder.header(name, value),
+            )
+            .body(Body::empty())
+            .unwrap()
     }
 
-    Ok(())
+    /// routes a request, then returns the operation or the code of the rejection
+    fn route(service: &S3Service, req: &Request) -> &'static str {
+        let mut ctx = ReqContext::new(req, Body::empty()).unwrap();
+        service.route(&mut ctx).map_or_else(
+            || service.reject_unrouted(&ctx).code().as_static_str(),
+            |(_, &(name, _))| name,
+        )
+    }
+
+    #[test]
+    fn routing() {
+        let service = S3Service::new(MemoryStorage::new());
+        let cases: &[Case] = &[
+            (Method::PUT, "/asd/key", &[], "PutObject"),
+            (
+                Method::PUT,
+                "/asd/key?uploadId=x&partNumber=1",
+                &[],
+                "UploadPart",
+            ),
+            (Method::PUT, "/asd/key", COPY_SOURCE, "CopyObject"),
+            (
+                Method::PUT,
+                "/asd/key?uploadId=x&partNumber=1",
+                COPY_SOURCE,
+                "UploadPartCopy",
+            ),
+            (
+                Method::POST,
+                "/asd/key?uploads",
+                &[],
+                "CreateMultipartUpload",
+            ),
+            (
+                Method::POST,
+                "/asd/key?uploadId=x",
+                &[],
+                "CompleteMultipartUpload",
+            ),
+            (Method::GET, "/asd/key?uploadId=x", &[], "ListParts"),
+            (
+                Method::DELETE,
+                "/asd/key?uploadId=x",
+                &[],
+                "AbortMultipartUpload",
+            ),
+            (Method::GET, "/asd?uploads", &[], "ListMultipartUploads"),
+            (Method::GET, "/asd", &[], "ListObjects"),
+            (Method::GET, "/asd?list-type=2", &[], "ListObjectsV2"),
+            (Method::GET, "/asd/key?versionId=1", &[], "GetObject"),
+            (Method::GET, "/asd/key?acl", &[], "GetObjectAcl"),
+            (Method::HEAD, "/asd/key?acl", &[], "GetObjectAcl"),
+            (Method::HEAD, "/asd/key", &[], "HeadObject"),
+            (Method::PUT, "/asd?tagging", &[], "PutBucketTagging"),
+            (Method::PUT, "/asd", &[], "CreateBucket"),
+            (Method::POST, "/asd?delete", &[], "DeleteObjects"),
+            // sub-resources without a handler
+            (Method::GET, "/asd/key?torrent", &[], "NotImplemented"),
+            (Method::HEAD, "/asd/key?torrent", &[], "NotImplemented"),
+            (Method::PUT, "/asd?lifecycle", &[], "NotImplemented"),
+            (Method::GET, "/asd?website", &[], "NotImplemented"),
+            (Method::DELETE, "/asd?replication", &[], "NotImplemented"),
+            (Method::PUT, "/asd/key?select", &[], "NotImplemented"),
+            (Method::PATCH, "/asd/key", &[], "NotImplemented"),
+            (Method::POST, "/asd/key", &[], "NotImplemented"),
+        ];
+        for &(ref method, uri, headers, expected) in cases {
+            assert_eq!(
+                route(&service, &request(method.clone(), uri, headers)),
+                expected,
+                "{} {}",
+                method,
+                uri
+            );
+        }
+    }
 }
@@ -0,0 +1,105 @@
+//! Per-bucket serving policy
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Bucket-level defaults for `GetObject` and `HeadObject` responses
+///
+/// Precedence, from high to low:
+/// 1. `response-*` query strings of the request
+/// 2. headers stored with the object
+/// 3. the serving policy of the bucket
+///
+/// If `force_content_type` is set, the content type mapped from the key extension
+/// also replaces the stored one, but not a `response-content-type`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServingPolicy {
+    /// default `Cache-Control`
+    #[serde(default)]
+    pub cache_control: Option<String>,
+
+    /// default `Content-Type` by lowercase key extension (without the dot)
+    #[serde(default)]
+    pub content_types: HashMap<String, String>,
+
+    /// prefers `content_types` to the stored content type
+    #[serde(default)]
+    pub force_content_type: bool,
+}
+
+impl ServingPolicy {
+    /// Looks up the content type mapped from the extension of `key`
+    #[must_use]
+    pub fn content_type_for(&self, key: &str) -> Option<&str> {
+        let file_name = key.rsplit('/').next().unwrap_or(key);
+        let dot = file_name.rfind('.')?;
+        if dot == 0 {
+            return None;
+        }
+        let ext = file_name.get(dot.wrapping_add(1)..)?.to_ascii_lowercase();
+        self.content_types.get(&ext).map(String::as_str)
+    }
+
+    /// Fills the response fields of the object `key`
+    pub(crate) fn apply(
+        &self,
+        key: &str,
+        cache_control: &mut Option<String>,
+        content_type: &mut Option<String>,
+    ) {
+        if cache_control.is_none() {
+            cache_control.clone_from(&self.cache_control);
+        }
+        if let Some(mapped) = self.content_type_for(key) {
+            if content_type.is_none() || self.force_content_type {
+                *content_type = Some(mapped.to_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ServingPolicy {
+        let mut content_types = HashMap::new();
+        let _prev = content_types.insert("css".to_owned(), "text/css".to_owned());
+        ServingPolicy {
+            cache_control: Some("public, max-age=86400".into()),
+            content_types,
+            force_content_type: false,
+        }
+    }
+
+    #[test]
+    fn content_type_for() {
+        let policy = policy();
+        assert_eq!(policy.content_type_for("style.css"), Some("text/css"));
+        assert_eq!(policy.content_type_for("a.b/STYLE.CSS"), Some("text/css"));
+        assert_eq!(policy.content_type_for("a.css/style"), None);
+        assert_eq!(policy.content_type_for(".css"), None);
+        assert_eq!(policy.content_type_for("style.js"), None);
+    }
+
+    #[test]
+    fn apply() {
+        let mut policy = policy();
+
+        let (mut cache_control, mut content_type) = (None, None);
+        policy.apply("style.css", &mut cache_control, &mut content_type);
+        assert_eq!(cache_control.as_deref(), Some("public, max-age=86400"));
+        assert_eq!(content_type.as_deref(), Some("text/css"));
+
+        let mut cache_control = Some("no-cache".to_owned());
+        let mut content_type = Some("text/plain".to_owned());
+        policy.apply("style.css", &mut cache_control, &mut content_type);
+        assert_eq!(cache_control.as_deref(), Some("no-cache"));
+        assert_eq!(content_type.as_deref(), Some("text/plain"));
+
+        policy.force_content_type = true;
+        policy.apply("style.css", &mut cache_control, &mut content_type);
+        assert_eq!(content_type.as_deref(), Some("text/css"));
+    }
+}
@@ -77,7 +77,7 @@ impl<'a> PresignedUrl<'a> {
         let algorithm = info.x_amz_algorithm;
 
         let credential = match CredentialV4::parse_by_nom(info.x_amz_credential) {
-            Ok(("", c)) => c,
+            Ok(("", c)) if c.is_aws4_request() => c,
             Ok(_) | Err(_) => return Err(ParsePresignedUrlError { _priv: () }),
         };
 
@@ -112,7 +112,7 @@ impl<'a> PresignedUrl<'a> {
 }
 
 /// custom uri encode
-fn uri_encode(output: &mut String, input: &str, encode_slash: bool) {
+pub(crate) fn uri_encode(output: &mut String, input: &str, encode_slash: bool) {
     /// hex uppercase table
     const HEX_UPPERCASE_TABLE: [u8; 16] = *b"0123456789ABCDEF";
 
@@ -160,6 +160,22 @@ fn is_skipped_query_string(name: &str) -> bool {
     name == "X-Amz-Signature"
 }
 
+/// the query strings which carry the signature of a presigned url
+const PRESIGNED_QUERY_STRINGS: &[&str] = &[
+    "X-Amz-Algorithm",
+    "X-Amz-Credential",
+    "X-Amz-Date",
+    "X-Amz-Expires",
+    "X-Amz-SignedHeaders",
+    "X-Amz-Signature",
+    "X-Amz-Security-Token",
+];
+
+/// whether a query string is a field of a presigned url rather than a parameter of its operation
+pub(crate) fn is_presigned_query_string(name: &str) -> bool {
+    PRESIGNED_QUERY_STRINGS.contains(&name)
+}
+
 /// sha256 hash of an empty string
 const EMPTY_STRING_SHA256_HASH: &str =
     "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
@@ -170,8 +186,12 @@ pub enum Payload<'a> {
     Empty,
     /// single chunk
     SingleChunk(&'a [u8]),
+    /// single chunk, which is given by its hex SHA256
+    SingleChunkSha256(&'a str),
     /// multiple chunks
     MultipleChunks,
+    /// `UNSIGNED-PAYLOAD`
+    Unsigned,
 }
 
 /// create canonical request
@@ -262,7 +282,9 @@ pub fn create_canonical_request(
             match payload {
                 Payload::Empty => ans.push_str(EMPTY_STRING_SHA256_HASH),
                 Payload::SingleChunk(data) => ans.push_str(&crypto::hex_sha256(data)),
+                Payload::SingleChunkSha256(checksum) => ans.push_str(checksum),
                 Payload::MultipleChunks => ans.push_str("STREAMING-AWS4-HMAC-SHA256-PAYLOAD"),
+                Payload::Unsigned => ans.push_str("UNSIGNED-PAYLOAD"),
             }
             drop(payload);
         })
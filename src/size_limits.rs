@@ -0,0 +1,258 @@
+//! Size limits of uploads
+
+use crate::dto::{CompleteMultipartUploadRequest, ListPartsRequest};
+use crate::errors::{S3Error, S3ErrorBuilder, S3ErrorCode, S3Result, S3StorageError};
+use crate::headers::{CONTENT_LENGTH, X_AMZ_DECODED_CONTENT_LENGTH};
+use crate::ops::ReqContext;
+use crate::storage::S3Storage;
+use crate::{Body, Method};
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+
+/// 5 GiB, the max size of a single `PutObject` or `UploadPart` in S3
+const MAX_PUT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// 5 TiB, the max size of an object in S3
+const MAX_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024 * 1024;
+
+/// Size limits of uploads which are checked before the storage sees the data
+///
+/// An upload over a limit is rejected with `EntityTooLarge` (400),
+/// and the error carries `ProposedSize` and `MaxSizeAllowed`.
+///
+/// A `PUT` of `PutObject` or `UploadPart` is rejected by its declared length
+/// (`x-amz-decoded-content-length` of a chunked upload, or `Content-Length`) before the body is read.
+/// Its body is counted while it is streamed too, so a body without a declared length is cut after the limit,
+/// and its `ProposedSize` is the bytes received until then.
+/// A `CompleteMultipartUpload` is rejected if the parts in its list, as returned by `ListParts`, sum up over the object limit.
+/// It is not checked if the storage does not implement `ListParts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeLimits {
+    /// max bytes of a `PutObject`
+    pub max_put_size: u64,
+
+    /// max bytes of an `UploadPart`
+    pub max_part_size: u64,
+
+    /// max bytes of an object completed by `CompleteMultipartUpload`
+    pub max_object_size: u64,
+}
+
+impl Default for SizeLimits {
+    /// The limits of S3: 5 GB per `PutObject` and per `UploadPart`, and 5 TB per object
+    fn default() -> Self {
+        Self {
+            max_put_size: MAX_PUT_SIZE,
+            max_part_size: MAX_PUT_SIZE,
+            max_object_size: MAX_OBJECT_SIZE,
+        }
+    }
+}
+
+/// the error of an upload over `max`
+fn too_large(size: u64, max: u64) -> S3ErrorBuilder {
+    S3Error::from_code(S3ErrorCode::EntityTooLarge)
+        .message("Your proposed upload exceeds the maximum allowed size.")
+        .proposed_size(size)
+        .max_size_allowed(max)
+}
+
+/// Counts a request body which is cut after a limit
+#[derive(Debug)]
+pub(crate) struct BodyCounter {
+    /// the limit
+    max: u64,
+    /// the bytes received when the body is cut, or zero
+    exceeded_at: Arc<AtomicU64>,
+}
+
+impl BodyCounter {
+    /// replaces the error of a handler whose body is cut by `EntityTooLarge`
+    pub(crate) fn check<T>(&self, ret: S3Result<T>) -> S3Result<T> {
+        let size = self.exceeded_at.load(Ordering::Relaxed);
+        if size == 0 {
+            return ret;
+        }
+        ret.map_err(|err| {
+            let partial = err.partial_upload().cloned();
+            let builder = too_large(size, self.max);
+            let builder = match partial {
+                Some(partial) => builder.partial_upload(partial),
+                None => builder,
+            };
+            builder.source(err).finish()
+        })
+    }
+}
+
+impl SizeLimits {
+    /// checks the declared length of a `PutObject` or `UploadPart`
+    /// and counts its body while it is streamed
+    pub(crate) fn limit_body(
+        &self,
+        operation: &str,
+        ctx: &mut ReqContext<'_>,
+    ) -> S3Result<Option<BodyCounter>> {
+        let max = match operation {
+            "PutObject" => self.max_put_size,
+            "UploadPart" => self.max_part_size,
+            _ => return Ok(None),
+        };
+        // the fields of a form are not a part of the object
+        if ctx.method != Method::PUT {
+            return Ok(None);
+        }
+
+        // an invalid length is rejected by the handler
+        let declared = ctx
+            .headers
+            .get(&*X_AMZ_DECODED_CONTENT_LENGTH)
+            .or_else(|| ctx.headers.get(CONTENT_LENGTH))
+            .and_then(|value| value.parse::<u64>().ok());
+        if let Some(size) = declared {
+            if size > max {
+                return Err(too_large(size, max).finish());
+            }
+        }
+
+        let counter = BodyCounter {
+            max,
+            exceeded_at: Arc::default(),
+        };
+        let exceeded_at = Arc::clone(&counter.exceeded_at);
+        let mut received: u64 = 0;
+        let body = mem::take(&mut ctx.body).map(move |chunk| {
+            let chunk = chunk.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            received = received.saturating_add(u64::try_from(chunk.len()).unwrap_or(u64::MAX));
+            if received > max {
+                exceeded_at.store(received, Ordering::Relaxed);
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "The body exceeds the maximum allowed size",
+                ));
+            }
+            Ok(chunk)
+        });
+        ctx.body = Body::wrap_stream(body);
+        Ok(Some(counter))
+    }
+}
+
+/// checks the size of the object which `input` completes
+pub(crate) async fn check_completion(
+    ctx: &ReqContext<'_>,
+    storage: &(dyn S3Storage + Send + Sync),
+    input: &CompleteMultipartUploadRequest,
+) -> S3Result<()> {
+    let max = ctx.size_limits.max_object_size;
+    let part_numbers: BTreeSet<i64> = input
+        .multipart_upload
+        .iter()
+        .flat_map(|multipart_upload| multipart_upload.parts.iter().flatten())
+        .filter_map(|part| part.part_number)
+        .collect();
+
+    let mut total: u64 = 0;
+    let mut marker: Option<i64> = None;
+    loop {
+        let list = ListPartsRequest {
+            bucket: input.bucket.clone(),
+            key: input.key.clone(),
+            upload_id: input.upload_id.clone(),
+            part_number_marker: marker,
+            ..ListPartsRequest::default()
+        };
+        let output = match storage.list_parts(list).await {
+            Ok(output) => output,
+            // the errors of the upload are reported by the completion
+            Err(S3StorageError::Operation(_)) => return Ok(()),
+            Err(S3StorageError::Other(err))
+                if err.code() == S3ErrorCode::NoSuchUpload
+                    || err.unimplemented_operation().is_some() =>
+            {
+                return Ok(())
+            }
+            Err(S3StorageError::Other(err)) => return Err(err),
+        };
+
+        // a storage may return all parts or only the requested page
+        let floor = marker.unwrap_or(0);
+        for part in output.parts.iter().flatten() {
+            let part_number = part.part_number.unwrap_or(0);
+            if part_number > floor && part_numbers.contains(&part_number) {
+                let size = part.size.and_then(|n| u64::try_from(n).ok()).unwrap_or(0);
+                total = total.saturating_add(size);
+            }
+        }
+        if total > max {
+            return Err(too_large(total, max).finish());
+        }
+
+        match output.next_part_number_marker {
+            Some(next) if output.is_truncated == Some(true) && next > floor => marker = Some(next),
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Request;
+
+    fn put(headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder()
+            .method(Method::PUT)
+            .uri("http://localhost/bucket/key");
+        for &(name, value) in headers {
+            builder = builder.header(name, value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    /// whether a `PutObject` of `req` is rejected by its declared length
+    fn is_rejected(req: &Request) -> bool {
+        let limits = SizeLimits {
+            max_put_size: 10,
+            ..SizeLimits::default()
+        };
+        let mut ctx = ReqContext::new(req, Body::empty()).unwrap();
+        match limits.limit_body("PutObject", &mut ctx) {
+            Ok(counter) => {
+                assert!(counter.is_some());
+                false
+            }
+            Err(err) => {
+                assert_eq!(err.code(), S3ErrorCode::EntityTooLarge);
+                true
+            }
+        }
+    }
+
+    #[test]
+    fn declared_length() {
+        assert!(!is_rejected(&put(&[("content-length", "10")])));
+        assert!(is_rejected(&put(&[("content-length", "11")])));
+        assert!(!is_rejected(&put(&[])));
+
+        // the decoded length of a chunked upload is checked instead of the encoded length
+        assert!(!is_rejected(&put(&[
+            ("content-length", "200"),
+            ("x-amz-decoded-content-length", "10"),
+        ])));
+        assert!(is_rejected(&put(&[
+            ("content-length", "5"),
+            ("x-amz-decoded-content-length", "11"),
+        ])));
+    }
+}
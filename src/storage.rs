@@ -1,76 +1,149 @@
 //! Trait representing the capabilities of the Amazon S3 API at server side
 
-use crate::errors::S3StorageResult;
+pub mod migrate;
+
+use crate::bucket_freeze::BucketFreeze;
+use crate::consistency::ConsistencyToken;
+use crate::effective_config::StorageConfig;
+use crate::errors::{S3Result, S3StorageError, S3StorageResult};
+use crate::serving_policy::ServingPolicy;
 
 use crate::dto::{
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
     CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
     CopyObjectError, CopyObjectOutput, CopyObjectRequest, CreateBucketError, CreateBucketOutput,
     CreateBucketRequest, CreateMultipartUploadError, CreateMultipartUploadOutput,
-    CreateMultipartUploadRequest, DeleteBucketError, DeleteBucketOutput, DeleteBucketRequest,
+    CreateMultipartUploadRequest, DeleteBucketCorsError, DeleteBucketCorsOutput,
+    DeleteBucketCorsRequest, DeleteBucketError, DeleteBucketOutput, DeleteBucketPolicyError,
+    DeleteBucketPolicyOutput, DeleteBucketPolicyRequest, DeleteBucketRequest,
+    DeleteBucketTaggingError, DeleteBucketTaggingOutput, DeleteBucketTaggingRequest,
     DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError,
-    DeleteObjectsOutput, DeleteObjectsRequest, GetBucketLocationError, GetBucketLocationOutput,
-    GetBucketLocationRequest, GetObjectError, GetObjectOutput, GetObjectRequest, HeadBucketError,
-    HeadBucketOutput, HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest,
-    ListBucketsError, ListBucketsOutput, ListBucketsRequest, ListObjectsError, ListObjectsOutput,
-    ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request,
-    PutObjectError, PutObjectOutput, PutObjectRequest, UploadPartError, UploadPartOutput,
-    UploadPartRequest,
+    DeleteObjectsOutput, DeleteObjectsRequest, GetBucketAclError, GetBucketAclOutput,
+    GetBucketAclRequest, GetBucketCorsError, GetBucketCorsOutput, GetBucketCorsRequest,
+    GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest,
+    GetBucketPolicyError, GetBucketPolicyOutput, GetBucketPolicyRequest, GetBucketTaggingError,
+    GetBucketTaggingOutput, GetBucketTaggingRequest, GetBucketVersioningError,
+    GetBucketVersioningOutput, GetBucketVersioningRequest, GetObjectAclError, GetObjectAclOutput,
+    GetObjectAclRequest, GetObjectError, GetObjectLegalHoldError, GetObjectLegalHoldOutput,
+    GetObjectLegalHoldRequest, GetObjectLockConfigurationError, GetObjectLockConfigurationOutput,
+    GetObjectLockConfigurationRequest, GetObjectOutput, GetObjectRequest, GetObjectRetentionError,
+    GetObjectRetentionOutput, GetObjectRetentionRequest, HeadBucketError, HeadBucketOutput,
+    HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest, ListBucketsError,
+    ListBucketsOutput, ListBucketsRequest, ListMultipartUploadsError, ListMultipartUploadsOutput,
+    ListMultipartUploadsRequest, ListObjectVersionsError, ListObjectVersionsOutput,
+    ListObjectVersionsRequest, ListObjectsError, ListObjectsOutput, ListObjectsRequest,
+    ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, ListPartsError, ListPartsOutput,
+    ListPartsRequest, PutBucketAclError, PutBucketAclOutput, PutBucketAclRequest,
+    PutBucketCorsError, PutBucketCorsOutput, PutBucketCorsRequest, PutBucketPolicyError,
+    PutBucketPolicyOutput, PutBucketPolicyRequest, PutBucketTaggingError, PutBucketTaggingOutput,
+    PutBucketTaggingRequest, PutBucketVersioningError, PutBucketVersioningOutput,
+    PutBucketVersioningRequest, PutObjectAclError, PutObjectAclOutput, PutObjectAclRequest,
+    PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldOutput, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest, PutObjectOutput, PutObjectRequest, PutObjectRetentionError,
+    PutObjectRetentionOutput, PutObjectRetentionRequest, RestoreObjectError, RestoreObjectOutput,
+    RestoreObjectRequest, UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest,
+    UploadPartError, UploadPartOutput, UploadPartRequest,
 };
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 
 /// Trait representing the capabilities of the Amazon S3 API at server side.
 ///
+/// Only `get_object` and `put_object` are required.
+/// The other operations return `NotImplemented` (501) by default, unless documented otherwise.
+///
 /// See <https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations_Amazon_Simple_Storage_Service.html>
 #[async_trait]
 pub trait S3Storage {
     /// See [CompleteMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn complete_multipart_upload(
         &self,
-        input: CompleteMultipartUploadRequest,
-    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError>;
+        _input: CompleteMultipartUploadRequest,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "CompleteMultipartUpload"
+        )))
+    }
 
     /// See [CopyObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn copy_object(
         &self,
-        input: CopyObjectRequest,
-    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError>;
+        _input: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        Err(S3StorageError::Other(not_implemented!("CopyObject")))
+    }
 
     /// See [CreateMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn create_multipart_upload(
         &self,
-        input: CreateMultipartUploadRequest,
-    ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError>;
+        _input: CreateMultipartUploadRequest,
+    ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "CreateMultipartUpload"
+        )))
+    }
 
     /// See [CreateBucket](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateBucket.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn create_bucket(
         &self,
-        input: CreateBucketRequest,
-    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError>;
+        _input: CreateBucketRequest,
+    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
+        Err(S3StorageError::Other(not_implemented!("CreateBucket")))
+    }
 
     /// See [DeleteBucket](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucket.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn delete_bucket(
         &self,
-        input: DeleteBucketRequest,
-    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError>;
+        _input: DeleteBucketRequest,
+    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
+        Err(S3StorageError::Other(not_implemented!("DeleteBucket")))
+    }
 
     /// See [DeleteObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn delete_object(
         &self,
-        input: DeleteObjectRequest,
-    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError>;
+        _input: DeleteObjectRequest,
+    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
+        Err(S3StorageError::Other(not_implemented!("DeleteObject")))
+    }
 
     /// See [DeleteObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn delete_objects(
         &self,
-        input: DeleteObjectsRequest,
-    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError>;
+        _input: DeleteObjectsRequest,
+    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
+        Err(S3StorageError::Other(not_implemented!("DeleteObjects")))
+    }
 
     /// See [GetBucketLocation](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketLocation.html)
+    ///
+    /// A storage which does not record regions returns no location constraint,
+    /// and the bucket is in the default region of the service.
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn get_bucket_location(
         &self,
-        input: GetBucketLocationRequest,
-    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError>;
+        _input: GetBucketLocationRequest,
+    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
+        Err(S3StorageError::Other(not_implemented!("GetBucketLocation")))
+    }
 
     /// See [GetObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html)
     async fn get_object(
@@ -79,44 +152,462 @@ pub trait S3Storage {
     ) -> S3StorageResult<GetObjectOutput, GetObjectError>;
 
     /// See [HeadBucket](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadBucket.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn head_bucket(
         &self,
-        input: HeadBucketRequest,
-    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError>;
+        _input: HeadBucketRequest,
+    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
+        Err(S3StorageError::Other(not_implemented!("HeadBucket")))
+    }
 
     /// See [HeadObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadObject.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn head_object(
         &self,
-        input: HeadObjectRequest,
-    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError>;
+        _input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        Err(S3StorageError::Other(not_implemented!("HeadObject")))
+    }
 
     /// See [ListBuckets](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html)
+    ///
+    /// Every bucket must have a `name` and should have a `creation_date` (rfc3339).
+    /// A bucket without a name fails the request with `InternalError`.
+    ///
+    /// The buckets may be returned in any order: the handler sorts them by name unless they are sorted,
+    /// and then cuts the page by `max_buckets` and `continuation_token`.
+    /// A storage may ignore both fields and return all buckets.
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn list_buckets(
         &self,
-        input: ListBucketsRequest,
-    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError>;
+        _input: ListBucketsRequest,
+    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
+        Err(S3StorageError::Other(not_implemented!("ListBuckets")))
+    }
 
     /// See [ListObjects](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjects.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn list_objects(
         &self,
-        input: ListObjectsRequest,
-    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError>;
+        _input: ListObjectsRequest,
+    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
+        Err(S3StorageError::Other(not_implemented!("ListObjects")))
+    }
 
     /// See [ListObjectsV2](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn list_objects_v2(
         &self,
-        input: ListObjectsV2Request,
-    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error>;
+        _input: ListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        Err(S3StorageError::Other(not_implemented!("ListObjectsV2")))
+    }
 
     /// See [PutObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
+    ///
+    /// A storage may attach a [`PartialUpload`](crate::errors::PartialUpload) to the error of a body which fails midway,
+    /// with the bytes it has persisted until then.
     async fn put_object(
         &self,
         input: PutObjectRequest,
     ) -> S3StorageResult<PutObjectOutput, PutObjectError>;
 
     /// See [UploadPart](https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPart.html)
+    ///
+    /// The default implementation returns `NotImplemented`.
     async fn upload_part(
         &self,
-        input: UploadPartRequest,
-    ) -> S3StorageResult<UploadPartOutput, UploadPartError>;
+        _input: UploadPartRequest,
+    ) -> S3StorageResult<UploadPartOutput, UploadPartError> {
+        Err(S3StorageError::Other(not_implemented!("UploadPart")))
+    }
+
+    /// Returns the serving policy of a bucket, which fills `GetObject` and `HeadObject` responses.
+    ///
+    /// See [`ServingPolicy`] for the precedence. The default implementation returns `None`.
+    async fn get_bucket_serving_policy(&self, _bucket: &str) -> S3Result<Option<ServingPolicy>> {
+        Ok(None)
+    }
+
+    /// See [GetBucketVersioning](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketVersioning.html)
+    ///
+    /// The default implementation returns an empty configuration, like a bucket which has never been versioned.
+    async fn get_bucket_versioning(
+        &self,
+        _input: GetBucketVersioningRequest,
+    ) -> S3StorageResult<GetBucketVersioningOutput, GetBucketVersioningError> {
+        Ok(GetBucketVersioningOutput::default())
+    }
+
+    /// See [PutBucketVersioning](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketVersioning.html)
+    ///
+    /// The MFA of `MfaDelete` changes is verified before this method is called.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_bucket_versioning(
+        &self,
+        _input: PutBucketVersioningRequest,
+    ) -> S3StorageResult<PutBucketVersioningOutput, PutBucketVersioningError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "PutBucketVersioning"
+        )))
+    }
+
+    /// See [GetBucketTagging](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketTagging.html)
+    ///
+    /// A bucket without tags returns an empty tag set, which is answered with `NoSuchTagSet`.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_bucket_tagging(
+        &self,
+        _input: GetBucketTaggingRequest,
+    ) -> S3StorageResult<GetBucketTaggingOutput, GetBucketTaggingError> {
+        Err(S3StorageError::Other(not_implemented!("GetBucketTagging")))
+    }
+
+    /// See [PutBucketTagging](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketTagging.html)
+    ///
+    /// The tag set is validated before this method is called, and replaces the tags of the bucket.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_bucket_tagging(
+        &self,
+        _input: PutBucketTaggingRequest,
+    ) -> S3StorageResult<PutBucketTaggingOutput, PutBucketTaggingError> {
+        Err(S3StorageError::Other(not_implemented!("PutBucketTagging")))
+    }
+
+    /// See [DeleteBucketTagging](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucketTagging.html)
+    ///
+    /// Deleting the tags of a bucket without tags succeeds.
+    /// The default implementation returns `NotImplemented`.
+    async fn delete_bucket_tagging(
+        &self,
+        _input: DeleteBucketTaggingRequest,
+    ) -> S3StorageResult<DeleteBucketTaggingOutput, DeleteBucketTaggingError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "DeleteBucketTagging"
+        )))
+    }
+
+    /// See [GetBucketAcl](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketAcl.html)
+    ///
+    /// A bucket whose ACL was never put may return `None` grants,
+    /// which are answered as the default ACL granting `FULL_CONTROL` to the owner.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_bucket_acl(
+        &self,
+        _input: GetBucketAclRequest,
+    ) -> S3StorageResult<GetBucketAclOutput, GetBucketAclError> {
+        Err(S3StorageError::Other(not_implemented!("GetBucketAcl")))
+    }
+
+    /// See [PutBucketAcl](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAcl.html)
+    ///
+    /// A canned ACL or grant headers are resolved into `access_control_policy` before this method is called,
+    /// so a storage only keeps the policy.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_bucket_acl(
+        &self,
+        _input: PutBucketAclRequest,
+    ) -> S3StorageResult<PutBucketAclOutput, PutBucketAclError> {
+        Err(S3StorageError::Other(not_implemented!("PutBucketAcl")))
+    }
+
+    /// See [GetBucketCors](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketCors.html)
+    ///
+    /// A bucket without CORS rules returns `None` rules, which are answered with `NoSuchCORSConfiguration`.
+    /// The rules are also read to answer the preflight `OPTIONS` requests and to add the CORS headers of actual requests,
+    /// which ignore a `NotImplemented`.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_bucket_cors(
+        &self,
+        _input: GetBucketCorsRequest,
+    ) -> S3StorageResult<GetBucketCorsOutput, GetBucketCorsError> {
+        Err(S3StorageError::Other(not_implemented!("GetBucketCors")))
+    }
+
+    /// See [PutBucketCors](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketCors.html)
+    ///
+    /// The rules are validated before this method is called, and replace the CORS rules of the bucket.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_bucket_cors(
+        &self,
+        _input: PutBucketCorsRequest,
+    ) -> S3StorageResult<PutBucketCorsOutput, PutBucketCorsError> {
+        Err(S3StorageError::Other(not_implemented!("PutBucketCors")))
+    }
+
+    /// See [DeleteBucketCors](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucketCors.html)
+    ///
+    /// Deleting the CORS rules of a bucket without CORS rules succeeds.
+    /// The default implementation returns `NotImplemented`.
+    async fn delete_bucket_cors(
+        &self,
+        _input: DeleteBucketCorsRequest,
+    ) -> S3StorageResult<DeleteBucketCorsOutput, DeleteBucketCorsError> {
+        Err(S3StorageError::Other(not_implemented!("DeleteBucketCors")))
+    }
+
+    /// See [GetBucketPolicy](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketPolicy.html)
+    ///
+    /// A bucket without a policy returns `None`, which is answered with `NoSuchBucketPolicy`.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_bucket_policy(
+        &self,
+        _input: GetBucketPolicyRequest,
+    ) -> S3StorageResult<GetBucketPolicyOutput, GetBucketPolicyError> {
+        Err(S3StorageError::Other(not_implemented!("GetBucketPolicy")))
+    }
+
+    /// See [PutBucketPolicy](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketPolicy.html)
+    ///
+    /// The policy is checked to be a JSON document with a `Statement` array before this method is called,
+    /// and replaces the policy of the bucket. A storage keeps the document as it is.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_bucket_policy(
+        &self,
+        _input: PutBucketPolicyRequest,
+    ) -> S3StorageResult<PutBucketPolicyOutput, PutBucketPolicyError> {
+        Err(S3StorageError::Other(not_implemented!("PutBucketPolicy")))
+    }
+
+    /// See [DeleteBucketPolicy](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucketPolicy.html)
+    ///
+    /// Deleting the policy of a bucket without a policy succeeds.
+    /// The default implementation returns `NotImplemented`.
+    async fn delete_bucket_policy(
+        &self,
+        _input: DeleteBucketPolicyRequest,
+    ) -> S3StorageResult<DeleteBucketPolicyOutput, DeleteBucketPolicyError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "DeleteBucketPolicy"
+        )))
+    }
+
+    /// See [GetObjectLockConfiguration](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectLockConfiguration.html)
+    ///
+    /// A bucket without Object Lock returns `None`, which is answered with `ObjectLockConfigurationNotFoundError`.
+    /// A bucket created with Object Lock but without a default retention returns a configuration without a rule.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_object_lock_configuration(
+        &self,
+        _input: GetObjectLockConfigurationRequest,
+    ) -> S3StorageResult<GetObjectLockConfigurationOutput, GetObjectLockConfigurationError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "GetObjectLockConfiguration"
+        )))
+    }
+
+    /// See [PutObjectLockConfiguration](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectLockConfiguration.html)
+    ///
+    /// The configuration is checked to be `Enabled` with at most one valid default retention before this method is called,
+    /// and enables Object Lock for the bucket.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_object_lock_configuration(
+        &self,
+        _input: PutObjectLockConfigurationRequest,
+    ) -> S3StorageResult<PutObjectLockConfigurationOutput, PutObjectLockConfigurationError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "PutObjectLockConfiguration"
+        )))
+    }
+
+    /// See [GetObjectAcl](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectAcl.html)
+    ///
+    /// An object whose ACL was never put may return `None` grants,
+    /// which are answered as the default ACL granting `FULL_CONTROL` to the owner.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_object_acl(
+        &self,
+        _input: GetObjectAclRequest,
+    ) -> S3StorageResult<GetObjectAclOutput, GetObjectAclError> {
+        Err(S3StorageError::Other(not_implemented!("GetObjectAcl")))
+    }
+
+    /// See [PutObjectAcl](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectAcl.html)
+    ///
+    /// A canned ACL or grant headers are resolved into `access_control_policy` before this method is called,
+    /// so a storage only keeps the policy. A new write of the object resets its ACL.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_object_acl(
+        &self,
+        _input: PutObjectAclRequest,
+    ) -> S3StorageResult<PutObjectAclOutput, PutObjectAclError> {
+        Err(S3StorageError::Other(not_implemented!("PutObjectAcl")))
+    }
+
+    /// See [RestoreObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_RestoreObject.html)
+    ///
+    /// A storage with an archive tier starts to restore a temporary copy of the object for `Days`,
+    /// and sets `already_in_progress` if the object is already being restored.
+    /// An object which is not archived must be reported as `InvalidObjectState`.
+    /// The default implementation returns `NotImplemented`.
+    async fn restore_object(
+        &self,
+        _input: RestoreObjectRequest,
+    ) -> S3StorageResult<RestoreObjectOutput, RestoreObjectError> {
+        Err(S3StorageError::Other(not_implemented!("RestoreObject")))
+    }
+
+    /// See [GetObjectRetention](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectRetention.html)
+    ///
+    /// An object without a retention returns `None`, which is answered with `ObjectLockConfigurationNotFoundError`.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_object_retention(
+        &self,
+        _input: GetObjectRetentionRequest,
+    ) -> S3StorageResult<GetObjectRetentionOutput, GetObjectRetentionError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "GetObjectRetention"
+        )))
+    }
+
+    /// See [PutObjectRetention](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectRetention.html)
+    ///
+    /// The retention is checked to have a mode and a retain-until date in the future before this method is called.
+    /// A bucket without Object Lock must be reported as `InvalidRequest`.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_object_retention(
+        &self,
+        _input: PutObjectRetentionRequest,
+    ) -> S3StorageResult<PutObjectRetentionOutput, PutObjectRetentionError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "PutObjectRetention"
+        )))
+    }
+
+    /// See [GetObjectLegalHold](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectLegalHold.html)
+    ///
+    /// An object without a legal hold returns `None`, which is answered with `ObjectLockConfigurationNotFoundError`.
+    /// The default implementation returns `NotImplemented`.
+    async fn get_object_legal_hold(
+        &self,
+        _input: GetObjectLegalHoldRequest,
+    ) -> S3StorageResult<GetObjectLegalHoldOutput, GetObjectLegalHoldError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "GetObjectLegalHold"
+        )))
+    }
+
+    /// See [PutObjectLegalHold](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectLegalHold.html)
+    ///
+    /// The status is checked to be `ON` or `OFF` before this method is called.
+    /// A bucket without Object Lock must be reported as `InvalidRequest`.
+    /// The default implementation returns `NotImplemented`.
+    async fn put_object_legal_hold(
+        &self,
+        _input: PutObjectLegalHoldRequest,
+    ) -> S3StorageResult<PutObjectLegalHoldOutput, PutObjectLegalHoldError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "PutObjectLegalHold"
+        )))
+    }
+
+    /// See [ListObjectVersions](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectVersions.html)
+    ///
+    /// [`list_versions`](crate::storages::versions::list_versions) orders and paginates the versions of a storage.
+    /// The default implementation returns `NotImplemented`.
+    async fn list_object_versions(
+        &self,
+        _input: ListObjectVersionsRequest,
+    ) -> S3StorageResult<ListObjectVersionsOutput, ListObjectVersionsError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "ListObjectVersions"
+        )))
+    }
+
+    /// See [AbortMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_AbortMultipartUpload.html)
+    ///
+    /// An unknown upload must be reported as `NoSuchUpload`.
+    /// The default implementation returns `NotImplemented`.
+    async fn abort_multipart_upload(
+        &self,
+        _input: AbortMultipartUploadRequest,
+    ) -> S3StorageResult<AbortMultipartUploadOutput, AbortMultipartUploadError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "AbortMultipartUpload"
+        )))
+    }
+
+    /// See [ListParts](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListParts.html)
+    ///
+    /// A storage may return all parts of the upload or only the requested page,
+    /// which is sorted and cut by the handler.
+    /// A part which is still being uploaded must not be listed.
+    /// The default implementation returns `NotImplemented`.
+    async fn list_parts(
+        &self,
+        _input: ListPartsRequest,
+    ) -> S3StorageResult<ListPartsOutput, ListPartsError> {
+        Err(S3StorageError::Other(not_implemented!("ListParts")))
+    }
+
+    /// See [ListMultipartUploads](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListMultipartUploads.html)
+    ///
+    /// [`list_uploads`](crate::storages::uploads::list_uploads) orders and paginates the uploads of a storage.
+    /// An upload which has been completed or aborted must not be listed.
+    /// The default implementation returns `NotImplemented`.
+    async fn list_multipart_uploads(
+        &self,
+        _input: ListMultipartUploadsRequest,
+    ) -> S3StorageResult<ListMultipartUploadsOutput, ListMultipartUploadsError> {
+        Err(S3StorageError::Other(not_implemented!(
+            "ListMultipartUploads"
+        )))
+    }
+
+    /// See [UploadPartCopy](https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPartCopy.html)
+    ///
+    /// The copy source is `bucket/key`, followed by `?versionId=<version id>` if a version is given.
+    /// [`copy_source`](crate::storages::copy_source) checks the conditions and cuts the range of the source.
+    /// The default implementation returns `NotImplemented`.
+    async fn upload_part_copy(
+        &self,
+        _input: UploadPartCopyRequest,
+    ) -> S3StorageResult<UploadPartCopyOutput, UploadPartCopyError> {
+        Err(S3StorageError::Other(not_implemented!("UploadPartCopy")))
+    }
+
+    /// Returns the write freeze of a bucket, which is checked before each write to the bucket.
+    ///
+    /// It is called on every write, so implementations should cache it.
+    /// The default implementation returns `None`.
+    async fn get_bucket_freeze(&self, _bucket: &str) -> S3Result<Option<BucketFreeze>> {
+        Ok(None)
+    }
+
+    /// Returns a URL of an object which can be fetched by `GET` without credentials for `expiry`,
+    /// such as a presigned URL of an upstream S3 service.
+    ///
+    /// It is used by [`RedirectOffload`](crate::RedirectOffload) after the request is authenticated.
+    /// The default implementation returns `None`, which streams the object through the service.
+    async fn presign_get(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _expiry: Duration,
+    ) -> S3Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Waits until the write of `token` is readable by the following reads, for `timeout` at most,
+    /// and returns whether it is readable.
+    ///
+    /// It is called after each successful `PutObject`, `CopyObject` and `CompleteMultipartUpload`
+    /// when [`S3Service::set_read_after_write_barrier`](crate::S3Service::set_read_after_write_barrier) is set.
+    /// The default implementation returns `true` at once, which suits a storage whose writes are readable immediately.
+    async fn wait_for(&self, _token: &ConsistencyToken, _timeout: Duration) -> S3Result<bool> {
+        Ok(true)
+    }
+
+    /// Describes the storage for [`S3Service::effective_config`](crate::S3Service::effective_config).
+    ///
+    /// Secret options must be wrapped in [`Secret`](crate::Secret).
+    /// The default implementation describes a `custom` storage without options.
+    fn storage_config(&self) -> StorageConfig {
+        StorageConfig::new("custom")
+    }
 }
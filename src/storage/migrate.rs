@@ -0,0 +1,611 @@
+//! Data migration between two storages
+//!
+//! [`migrate`] copies the buckets and objects of a source storage to a destination storage
+//! through the [`S3Storage`] trait, so it moves data between any two backends,
+//! such as from a [`MemoryStorage`](crate::storages::memory::MemoryStorage) to a [`FileSystem`](crate::storages::fs::FileSystem),
+//! or between two filesystems with different key mappers.
+//!
+//! Buckets are enumerated by `ListBuckets` and objects by `ListObjectsV2`.
+//! Each object is read by `GetObject` and written by `PutObject` with its content type, metadata and
+//! content headers, while up to [`MigrateOptions::concurrency`] objects are copied at the same time.
+//! A copy is verified by the MD5 of the copied bytes, which must match the `ETag`s of both sides
+//! unless they are not plain MD5 digests, such as the `ETag` of a multipart upload.
+//!
+//! A failed copy is reported in [`MigrateReport::failures`] without stopping the migration.
+//! Each verified copy is appended to the [journal](MigrateOptions::journal),
+//! so a migration which is interrupted and run again skips the objects which are already copied,
+//! unless their `ETag` has changed since.
+
+use crate::dto::{
+    ByteStream, CreateBucketRequest, GetObjectRequest, HeadBucketRequest, ListBucketsRequest,
+    ListObjectsV2Request, Object, PutObjectRequest,
+};
+use crate::errors::{S3Error, S3Result, S3StorageError};
+use crate::storage::S3Storage;
+use crate::utils::crypto;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use async_fs::OpenOptions;
+use futures::io::AsyncWriteExt;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// default number of objects copied at the same time
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// A callback of [`MigrateOptions::on_progress`]
+type ProgressCallback = Arc<dyn Fn(&MigrateProgress) + Send + Sync + 'static>;
+
+/// Options of [`migrate`]
+#[derive(Clone)]
+pub struct MigrateOptions {
+    /// max objects copied at the same time
+    concurrency: usize,
+    /// prefixes of `bucket/key` to copy
+    include: Vec<String>,
+    /// prefixes of `bucket/key` to skip
+    exclude: Vec<String>,
+    /// the resume journal
+    journal: Option<PathBuf>,
+    /// the progress callback
+    on_progress: Option<ProgressCallback>,
+}
+
+impl Debug for MigrateOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MigrateOptions")
+            .field("concurrency", &self.concurrency)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("journal", &self.journal)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            journal: None,
+            on_progress: None,
+        }
+    }
+}
+
+impl MigrateOptions {
+    /// Copies every object of every bucket, 4 objects at a time, without a journal
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies up to `concurrency` objects at the same time, at least one
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Copies only the objects whose `bucket/key` starts with one of the included prefixes.
+    /// Every object is included by default.
+    #[must_use]
+    pub fn include(mut self, prefix: impl Into<String>) -> Self {
+        self.include.push(prefix.into());
+        self
+    }
+
+    /// Skips the objects whose `bucket/key` starts with `prefix`, even if they are included
+    #[must_use]
+    pub fn exclude(mut self, prefix: impl Into<String>) -> Self {
+        self.exclude.push(prefix.into());
+        self
+    }
+
+    /// Records the copied objects in a JSON lines file at `path`, and skips the ones it records
+    #[must_use]
+    pub fn journal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.journal = Some(path.into());
+        self
+    }
+
+    /// Calls `f` after each object is copied, skipped or failed
+    #[must_use]
+    pub fn on_progress(mut self, f: impl Fn(&MigrateProgress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
+
+    /// whether the objects of `bucket` may be copied
+    fn may_include_bucket(&self, bucket: &str) -> bool {
+        let path = format!("{}/", bucket);
+        let is_included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_str()) || prefix.starts_with(&path));
+        let is_excluded = self
+            .exclude
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()));
+        is_included && !is_excluded
+    }
+
+    /// whether `key` of `bucket` is copied
+    fn includes(&self, bucket: &str, key: &str) -> bool {
+        let path = format!("{}/{}", bucket, key);
+        let is_included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_str()));
+        let is_excluded = self
+            .exclude
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()));
+        is_included && !is_excluded
+    }
+}
+
+/// The progress of a migration, after an object is done
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrateProgress {
+    /// the bucket of the object
+    pub bucket: String,
+    /// the key of the object
+    pub key: String,
+    /// objects copied so far
+    pub copied: u64,
+    /// objects skipped so far, since the journal records them
+    pub skipped: u64,
+    /// objects failed so far
+    pub failed: u64,
+    /// bytes copied so far
+    pub bytes: u64,
+}
+
+/// An object which failed to be copied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct MigrateFailure {
+    /// the bucket
+    pub bucket: String,
+    /// the key
+    pub key: String,
+    /// what failed
+    pub error: String,
+}
+
+/// The result of a migration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct MigrateReport {
+    /// buckets created in the destination
+    pub buckets_created: u64,
+    /// objects copied and verified
+    pub copied: u64,
+    /// objects skipped, since the journal records them
+    pub skipped: u64,
+    /// bytes copied
+    pub bytes: u64,
+    /// objects which failed to be copied
+    pub failures: Vec<MigrateFailure>,
+}
+
+impl MigrateReport {
+    /// Returns whether every object was copied or skipped
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for MigrateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} objects copied ({} bytes), {} skipped, {} failed, {} buckets created",
+            self.copied,
+            self.bytes,
+            self.skipped,
+            self.failures.len(),
+            self.buckets_created
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n{}/{}: {}", failure.bucket, failure.key, failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+/// a line of the journal, written after an object is copied and verified
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    /// bucket
+    bucket: String,
+    /// key
+    key: String,
+    /// the `ETag` of the source object
+    e_tag: String,
+}
+
+/// the copied objects of previous runs and the file which records the new ones
+struct Journal {
+    /// the `ETag`s of the copied objects by bucket and key
+    copied: HashMap<(String, String), String>,
+    /// the journal file
+    file: Option<async_fs::File>,
+}
+
+impl Journal {
+    /// loads the journal at `path`, ignoring a torn last line
+    async fn open(path: Option<&PathBuf>) -> io::Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => {
+                return Ok(Self {
+                    copied: HashMap::new(),
+                    file: None,
+                })
+            }
+        };
+        let content = match async_fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let copied = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+            .map(|entry| ((entry.bucket, entry.key), entry.e_tag))
+            .collect();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        // a torn line is terminated, so that the next entry starts on its own line
+        if !content.is_empty() && !content.ends_with('\n') {
+            file.write_all(b"\n").await?;
+        }
+        Ok(Self {
+            copied,
+            file: Some(file),
+        })
+    }
+
+    /// checks whether `object` of `bucket` is already copied
+    fn contains(&self, bucket: &str, object: &Object) -> bool {
+        let key = (bucket.to_owned(), object.key.clone().unwrap_or_default());
+        self.copied.get(&key).map(String::as_str) == object.e_tag.as_deref()
+    }
+
+    /// records a copied object
+    async fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        if let Some(ref mut file) = self.file {
+            let mut line = serde_json::to_vec(entry)?;
+            line.push(b'\n');
+            file.write_all(&line).await?;
+            file.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// converts an error of a storage operation
+fn storage_error<E: Into<S3Error>>(err: S3StorageError<E>) -> S3Error {
+    match err {
+        S3StorageError::Operation(e) => e.into(),
+        S3StorageError::Other(e) => e,
+    }
+}
+
+/// the hex digest of a plain MD5 `ETag`, which is not the `ETag` of a multipart upload
+fn md5_e_tag(e_tag: &str) -> Option<&str> {
+    Some(e_tag.trim_matches('"'))
+        .filter(|digest| digest.len() == 32 && digest.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Copies the buckets and objects of `src` to `dst`, see the [module docs](self)
+///
+/// # Errors
+/// Returns an `Err` if a listing, the creation of a bucket or the journal fails.
+/// The failures of single objects are reported in [`MigrateReport::failures`].
+pub async fn migrate(
+    src: &(dyn S3Storage + Send + Sync),
+    dst: &(dyn S3Storage + Send + Sync),
+    opts: &MigrateOptions,
+) -> S3Result<MigrateReport> {
+    let mut journal = Journal::open(opts.journal.as_ref())
+        .await
+        .map_err(|e| internal_error!(e))?;
+    let mut report = MigrateReport::default();
+
+    let buckets = src
+        .list_buckets(ListBucketsRequest::default())
+        .await
+        .map_err(storage_error)?
+        .buckets
+        .unwrap_or_default();
+    for bucket in buckets.into_iter().filter_map(|bucket| bucket.name) {
+        if !opts.may_include_bucket(&bucket) {
+            continue;
+        }
+        if create_bucket(dst, &bucket).await? {
+            report.buckets_created = report.buckets_created.wrapping_add(1);
+        }
+        migrate_bucket(src, dst, opts, &bucket, &mut journal, &mut report).await?;
+    }
+    Ok(report)
+}
+
+/// creates `bucket` in `dst`, and returns whether it did not exist
+async fn create_bucket(dst: &(dyn S3Storage + Send + Sync), bucket: &str) -> S3Result<bool> {
+    let head = HeadBucketRequest {
+        bucket: bucket.to_owned(),
+        ..HeadBucketRequest::default()
+    };
+    if dst.head_bucket(head).await.is_ok() {
+        return Ok(false);
+    }
+    let create = CreateBucketRequest {
+        bucket: bucket.to_owned(),
+        ..CreateBucketRequest::default()
+    };
+    let _output = dst.create_bucket(create).await.map_err(storage_error)?;
+    Ok(true)
+}
+
+/// copies the included objects of `bucket`, page by page
+async fn migrate_bucket(
+    src: &(dyn S3Storage + Send + Sync),
+    dst: &(dyn S3Storage + Send + Sync),
+    opts: &MigrateOptions,
+    bucket: &str,
+    journal: &mut Journal,
+    report: &mut MigrateReport,
+) -> S3Result<()> {
+    let mut continuation_token = None;
+    loop {
+        let list = ListObjectsV2Request {
+            bucket: bucket.to_owned(),
+            continuation_token: continuation_token.take(),
+            ..ListObjectsV2Request::default()
+        };
+        let page = src.list_objects_v2(list).await.map_err(storage_error)?;
+
+        let mut objects = Vec::new();
+        for object in page.contents.unwrap_or_default() {
+            let key = object.key.clone().unwrap_or_default();
+            if !opts.includes(bucket, &key) {
+                continue;
+            }
+            if journal.contains(bucket, &object) {
+                report.skipped = report.skipped.wrapping_add(1);
+                progress(opts, report, bucket, key);
+                continue;
+            }
+            objects.push(object);
+        }
+
+        let mut copies = stream::iter(objects)
+            .map(|object| async move {
+                let key = object.key.unwrap_or_default();
+                let ret = copy_object(src, dst, bucket, &key).await;
+                (key, ret)
+            })
+            .buffer_unordered(opts.concurrency);
+        while let Some((key, ret)) = copies.next().await {
+            match ret {
+                Ok((e_tag, size)) => {
+                    report.copied = report.copied.wrapping_add(1);
+                    report.bytes = report.bytes.wrapping_add(size);
+                    let entry = JournalEntry {
+                        bucket: bucket.to_owned(),
+                        key: key.clone(),
+                        e_tag,
+                    };
+                    journal
+                        .append(&entry)
+                        .await
+                        .map_err(|e| internal_error!(e))?;
+                }
+                Err(error) => {
+                    warn!(%bucket, %key, %error, "failed to migrate an object");
+                    report.failures.push(MigrateFailure {
+                        bucket: bucket.to_owned(),
+                        key: key.clone(),
+                        error,
+                    });
+                }
+            }
+            progress(opts, report, bucket, key);
+        }
+
+        if page.is_truncated != Some(true) {
+            return Ok(());
+        }
+        match page.next_continuation_token {
+            Some(token) => continuation_token = Some(token),
+            None => {
+                return Err(internal_error!(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "a truncated listing has no continuation token"
+                )))
+            }
+        }
+    }
+}
+
+/// calls the progress callback
+fn progress(opts: &MigrateOptions, report: &MigrateReport, bucket: &str, key: String) {
+    if let Some(ref on_progress) = opts.on_progress {
+        on_progress(&MigrateProgress {
+            bucket: bucket.to_owned(),
+            key,
+            copied: report.copied,
+            skipped: report.skipped,
+            failed: u64::try_from(report.failures.len()).unwrap_or(u64::MAX),
+            bytes: report.bytes,
+        });
+    }
+}
+
+/// the MD5 and the size of the bytes read from a body
+type BodyDigest = Arc<Mutex<(Md5, u64)>>;
+
+/// copies an object, and returns the `ETag` of the source and the copied bytes
+async fn copy_object(
+    src: &(dyn S3Storage + Send + Sync),
+    dst: &(dyn S3Storage + Send + Sync),
+    bucket: &str,
+    key: &str,
+) -> Result<(String, u64), String> {
+    let get = GetObjectRequest {
+        bucket: bucket.to_owned(),
+        key: key.to_owned(),
+        ..GetObjectRequest::default()
+    };
+    let object = src
+        .get_object(get)
+        .await
+        .map_err(|err| format!("GetObject: {}", storage_error(err)))?;
+    let body = object
+        .body
+        .ok_or_else(|| "GetObject: the object has no body".to_owned())?;
+
+    let digest: BodyDigest = Arc::new(Mutex::new((Md5::new(), 0)));
+    let hashed = {
+        let digest = Arc::clone(&digest);
+        body.inspect_ok(move |bytes| {
+            let mut guard = lock(&digest);
+            guard.0.update(bytes);
+            guard.1 = guard
+                .1
+                .wrapping_add(u64::try_from(bytes.len()).unwrap_or(u64::MAX));
+        })
+    };
+    let put = PutObjectRequest {
+        bucket: bucket.to_owned(),
+        key: key.to_owned(),
+        body: Some(ByteStream::new(hashed)),
+        content_length: object.content_length,
+        content_type: object.content_type,
+        metadata: object.metadata,
+        cache_control: object.cache_control,
+        content_disposition: object.content_disposition,
+        content_encoding: object.content_encoding,
+        content_language: object.content_language,
+        expires: object.expires,
+        ..PutObjectRequest::default()
+    };
+    let output = dst
+        .put_object(put)
+        .await
+        .map_err(|err| format!("PutObject: {}", storage_error(err)))?;
+
+    let (md5_hash, size) = {
+        let mut guard = lock(&digest);
+        let md5_hash = mem::replace(&mut guard.0, Md5::new());
+        (crypto::to_hex_string(md5_hash.finalize()), guard.1)
+    };
+    let src_e_tag = object.e_tag.unwrap_or_default();
+    verify(
+        &md5_hash,
+        size,
+        object.content_length,
+        &src_e_tag,
+        output.e_tag.as_deref(),
+    )?;
+    debug!(%bucket, %key, size, "object migrated");
+    Ok((src_e_tag, size))
+}
+
+/// checks the copied bytes against the length and the `ETag`s of both sides
+fn verify(
+    md5_hash: &str,
+    size: u64,
+    content_length: Option<i64>,
+    src_e_tag: &str,
+    dst_e_tag: Option<&str>,
+) -> Result<(), String> {
+    if let Some(len) = content_length.and_then(|len| u64::try_from(len).ok()) {
+        if len != size {
+            return Err(format!("copied {} bytes of {}", size, len));
+        }
+    }
+    if let Some(expected) = md5_e_tag(src_e_tag) {
+        if !expected.eq_ignore_ascii_case(md5_hash) {
+            return Err(format!(
+                "the MD5 of the copied bytes is {}, but the source ETag is {}",
+                md5_hash, src_e_tag
+            ));
+        }
+    }
+    if let Some(actual) = dst_e_tag.and_then(md5_e_tag) {
+        if !actual.eq_ignore_ascii_case(md5_hash) {
+            return Err(format!(
+                "the MD5 of the copied bytes is {}, but the destination ETag is {}",
+                md5_hash, actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// locks a mutex, ignoring the poison of a panicked holder
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let opts = MigrateOptions::new()
+            .include("photos/2021/")
+            .include("docs/")
+            .exclude("docs/tmp/");
+        assert!(opts.may_include_bucket("photos"));
+        assert!(opts.may_include_bucket("docs"));
+        assert!(!opts.may_include_bucket("music"));
+        assert!(opts.includes("photos", "2021/a.jpg"));
+        assert!(!opts.includes("photos", "2020/a.jpg"));
+        assert!(opts.includes("docs", "a.txt"));
+        assert!(!opts.includes("docs", "tmp/a.txt"));
+
+        let opts = MigrateOptions::new().exclude("docs/");
+        assert!(opts.may_include_bucket("photos"));
+        assert!(!opts.may_include_bucket("docs"));
+    }
+
+    #[test]
+    fn verification() {
+        let md5_hash = "8b1a9953c4611296a827abf8c47804d7";
+        let e_tag = "\"8b1a9953c4611296a827abf8c47804d7\"";
+        assert_eq!(verify(md5_hash, 5, Some(5), e_tag, Some(e_tag)), Ok(()));
+        // the `ETag` of a multipart upload is not a digest of the content
+        assert_eq!(verify(md5_hash, 5, None, "\"abc-2\"", None), Ok(()));
+        assert!(verify(md5_hash, 4, Some(5), e_tag, Some(e_tag)).is_err());
+        let other = "\"00000000000000000000000000000000\"";
+        assert!(verify(md5_hash, 5, Some(5), other, Some(e_tag)).is_err());
+        assert!(verify(md5_hash, 5, Some(5), e_tag, Some(other)).is_err());
+    }
+}
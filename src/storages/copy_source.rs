@@ -0,0 +1,209 @@
+//! Conditions and ranges of the copy source of [`UploadPartCopy`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPartCopy.html)
+//!
+//! A storage checks the `x-amz-copy-source-if-*` conditions against its source before copying,
+//! which are evaluated like the conditions of a `GET`:
+//!
+//! + `if-match` fails unless the ETag matches. Without it, `if-unmodified-since` fails if the source was modified after the date.
+//! + `if-none-match` fails if the ETag matches. Without it, `if-modified-since` fails unless the source was modified after the date.
+//! + A failed condition is `PreconditionFailed`, even where a `GET` would be `304 Not Modified`.
+//!   A date which can not be parsed is ignored.
+//!
+//! A range is `bytes=first-last`, which spans at most 5 GiB.
+//! It is rejected with `InvalidRange` if its first byte is past the end of the source,
+//! and its last byte is cut at the end.
+
+use crate::conditions::{precondition_failed, Conditions, Evaluation};
+use crate::dto::UploadPartCopyRequest;
+use crate::errors::S3Result;
+use crate::headers::AmzCopySourceRange;
+
+use std::ops::Range;
+
+/// Max bytes of a copied part, 5 GiB
+pub const MAX_COPY_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// The conditions of a copy source
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopySourceConditions<'a> {
+    /// `x-amz-copy-source-if-match`
+    pub if_match: Option<&'a str>,
+    /// `x-amz-copy-source-if-none-match`
+    pub if_none_match: Option<&'a str>,
+    /// `x-amz-copy-source-if-modified-since`
+    pub if_modified_since: Option<&'a str>,
+    /// `x-amz-copy-source-if-unmodified-since`
+    pub if_unmodified_since: Option<&'a str>,
+}
+
+impl<'a> CopySourceConditions<'a> {
+    /// The conditions of an `UploadPartCopy`
+    #[must_use]
+    pub fn from_upload_part_copy(input: &'a UploadPartCopyRequest) -> Self {
+        Self {
+            if_match: input.copy_source_if_match.as_deref(),
+            if_none_match: input.copy_source_if_none_match.as_deref(),
+            if_modified_since: input.copy_source_if_modified_since.as_deref(),
+            if_unmodified_since: input.copy_source_if_unmodified_since.as_deref(),
+        }
+    }
+
+    /// Whether the ETag of the source is needed by [`CopySourceConditions::check`],
+    /// so that a storage only hashes its source if it must
+    #[must_use]
+    pub const fn needs_e_tag(&self) -> bool {
+        self.if_match.is_some() || self.if_none_match.is_some()
+    }
+
+    /// Checks the conditions against the quoted ETag and the rfc3339 modification time of the source
+    ///
+    /// # Errors
+    /// Returns `PreconditionFailed` if a condition does not hold
+    pub fn check(&self, e_tag: Option<&str>, last_modified: &str) -> S3Result<()> {
+        let conditions = Conditions {
+            if_match: self.if_match,
+            if_none_match: self.if_none_match,
+            if_modified_since: self.if_modified_since,
+            if_unmodified_since: self.if_unmodified_since,
+        };
+        match conditions.evaluate(e_tag, Some(last_modified)) {
+            Evaluation::Holds => Ok(()),
+            Evaluation::NotModified | Evaluation::Failed => Err(precondition_failed()),
+        }
+    }
+}
+
+/// Parses `x-amz-copy-source-range`, which does not depend on the source
+///
+/// # Errors
+/// Returns `InvalidArgument` if the range is malformed, or `InvalidRange` if it spans more than 5 GiB
+pub fn parse_range(range: &str) -> S3Result<AmzCopySourceRange> {
+    let range = AmzCopySourceRange::from_header_str(range).map_err(|err| {
+        code_error!(
+            InvalidArgument,
+            "The x-amz-copy-source-range value must be of the form bytes=first-last where first and last are the zero-based offsets of the first and last bytes to copy",
+            err
+        )
+    })?;
+    if range.size() > MAX_COPY_PART_SIZE {
+        return Err(code_error!(
+            InvalidRange,
+            "The specified copy source range is larger than the maximum allowable size for a part."
+        ));
+    }
+    Ok(range)
+}
+
+/// The bytes of a source of `size` bytes to copy, which are all of them without a range
+///
+/// # Errors
+/// Returns an `Err` if the range is invalid, see [`parse_range`],
+/// or `InvalidRange` if it starts past the end of the source
+pub fn byte_range(range: Option<&str>, size: u64) -> S3Result<Range<u64>> {
+    let range = match range {
+        Some(range) => parse_range(range)?,
+        None => return Ok(0..size),
+    };
+    if range.first >= size {
+        return Err(code_error!(
+            InvalidRange,
+            "The requested range is not satisfiable"
+        ));
+    }
+    Ok(range.first..range.last.saturating_add(1).min(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+
+    const E_TAG: &str = "\"a7d414b9133d6483d9a1c4e04e856e3b\"";
+    const LAST_MODIFIED: &str = "2021-06-01T10:00:00.500+00:00";
+
+    fn check(conditions: CopySourceConditions<'_>) -> Result<(), S3ErrorCode> {
+        let e_tag = Some(E_TAG).filter(|_| conditions.needs_e_tag());
+        conditions
+            .check(e_tag, LAST_MODIFIED)
+            .map_err(|err| err.code())
+    }
+
+    #[test]
+    fn conditions() {
+        const FAILED: Result<(), S3ErrorCode> = Err(S3ErrorCode::PreconditionFailed);
+        let before = "Tue, 01 Jun 2021 09:00:00 GMT";
+        let same = "Tue, 01 Jun 2021 10:00:00 GMT";
+        let after = "Tue, 01 Jun 2021 11:00:00 GMT";
+        let none = CopySourceConditions::default();
+
+        assert_eq!(check(none), Ok(()));
+
+        let if_match = |if_match| CopySourceConditions {
+            if_match: Some(if_match),
+            ..none
+        };
+        assert_eq!(check(if_match(E_TAG)), Ok(()));
+        assert_eq!(check(if_match("a7d414b9133d6483d9a1c4e04e856e3b")), Ok(()));
+        assert_eq!(check(if_match("\"other\", *")), Ok(()));
+        assert_eq!(check(if_match("\"other\"")), FAILED);
+
+        let if_none_match = |if_none_match| CopySourceConditions {
+            if_none_match: Some(if_none_match),
+            ..none
+        };
+        assert_eq!(check(if_none_match("\"other\"")), Ok(()));
+        assert_eq!(check(if_none_match(E_TAG)), FAILED);
+        assert_eq!(check(if_none_match("*")), FAILED);
+
+        let if_unmodified_since = |date| CopySourceConditions {
+            if_unmodified_since: Some(date),
+            ..none
+        };
+        assert_eq!(check(if_unmodified_since(after)), Ok(()));
+        assert_eq!(check(if_unmodified_since(same)), Ok(()));
+        assert_eq!(check(if_unmodified_since(before)), FAILED);
+        assert_eq!(check(if_unmodified_since("yesterday")), Ok(()));
+
+        let if_modified_since = |date| CopySourceConditions {
+            if_modified_since: Some(date),
+            ..none
+        };
+        assert_eq!(check(if_modified_since(before)), Ok(()));
+        assert_eq!(check(if_modified_since(same)), FAILED);
+        assert_eq!(check(if_modified_since(after)), FAILED);
+
+        // a matching ETag overrides the date
+        let conditions = CopySourceConditions {
+            if_match: Some(E_TAG),
+            if_unmodified_since: Some(before),
+            ..none
+        };
+        assert_eq!(check(conditions), Ok(()));
+        let conditions = CopySourceConditions {
+            if_none_match: Some(E_TAG),
+            if_modified_since: Some(before),
+            ..none
+        };
+        assert_eq!(check(conditions), FAILED);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(byte_range(None, 10).unwrap(), 0..10);
+        assert_eq!(byte_range(Some("bytes=2-5"), 10).unwrap(), 2..6);
+        // the last byte is cut at the end
+        assert_eq!(byte_range(Some("bytes=2-100"), 10).unwrap(), 2..10);
+
+        let code = |range: &str, size| byte_range(Some(range), size).unwrap_err().code();
+        assert_eq!(code("bytes=10-20", 10), S3ErrorCode::InvalidRange);
+        assert_eq!(code("bytes=0-0", 0), S3ErrorCode::InvalidRange);
+        assert_eq!(code("bytes=5-2", 10), S3ErrorCode::InvalidArgument);
+        assert_eq!(code("bytes=0-", 10), S3ErrorCode::InvalidArgument);
+
+        let max = MAX_COPY_PART_SIZE.saturating_sub(1);
+        let range = parse_range(&format!("bytes=0-{}", max)).unwrap();
+        assert_eq!(range.size(), MAX_COPY_PART_SIZE);
+        let err = parse_range(&format!("bytes=0-{}", MAX_COPY_PART_SIZE)).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::InvalidRange);
+    }
+}
@@ -0,0 +1,322 @@
+//! A storage wrapper which injects faults for tests
+//!
+//! Requires the feature `test-utils`.
+
+use crate::async_trait;
+use crate::bucket_freeze::BucketFreeze;
+use crate::consistency::ConsistencyToken;
+use crate::dto::{
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
+    CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
+    CopyObjectError, CopyObjectOutput, CopyObjectRequest, CreateBucketError, CreateBucketOutput,
+    CreateBucketRequest, CreateMultipartUploadError, CreateMultipartUploadOutput,
+    CreateMultipartUploadRequest, DeleteBucketCorsError, DeleteBucketCorsOutput,
+    DeleteBucketCorsRequest, DeleteBucketError, DeleteBucketOutput, DeleteBucketPolicyError,
+    DeleteBucketPolicyOutput, DeleteBucketPolicyRequest, DeleteBucketRequest,
+    DeleteBucketTaggingError, DeleteBucketTaggingOutput, DeleteBucketTaggingRequest,
+    DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError,
+    DeleteObjectsOutput, DeleteObjectsRequest, GetBucketAclError, GetBucketAclOutput,
+    GetBucketAclRequest, GetBucketCorsError, GetBucketCorsOutput, GetBucketCorsRequest,
+    GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest,
+    GetBucketPolicyError, GetBucketPolicyOutput, GetBucketPolicyRequest, GetBucketTaggingError,
+    GetBucketTaggingOutput, GetBucketTaggingRequest, GetBucketVersioningError,
+    GetBucketVersioningOutput, GetBucketVersioningRequest, GetObjectAclError, GetObjectAclOutput,
+    GetObjectAclRequest, GetObjectError, GetObjectLegalHoldError, GetObjectLegalHoldOutput,
+    GetObjectLegalHoldRequest, GetObjectLockConfigurationError, GetObjectLockConfigurationOutput,
+    GetObjectLockConfigurationRequest, GetObjectOutput, GetObjectRequest, GetObjectRetentionError,
+    GetObjectRetentionOutput, GetObjectRetentionRequest, HeadBucketError, HeadBucketOutput,
+    HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest, ListBucketsError,
+    ListBucketsOutput, ListBucketsRequest, ListMultipartUploadsError, ListMultipartUploadsOutput,
+    ListMultipartUploadsRequest, ListObjectVersionsError, ListObjectVersionsOutput,
+    ListObjectVersionsRequest, ListObjectsError, ListObjectsOutput, ListObjectsRequest,
+    ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, ListPartsError, ListPartsOutput,
+    ListPartsRequest, PutBucketAclError, PutBucketAclOutput, PutBucketAclRequest,
+    PutBucketCorsError, PutBucketCorsOutput, PutBucketCorsRequest, PutBucketPolicyError,
+    PutBucketPolicyOutput, PutBucketPolicyRequest, PutBucketTaggingError, PutBucketTaggingOutput,
+    PutBucketTaggingRequest, PutBucketVersioningError, PutBucketVersioningOutput,
+    PutBucketVersioningRequest, PutObjectAclError, PutObjectAclOutput, PutObjectAclRequest,
+    PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldOutput, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest, PutObjectOutput, PutObjectRequest, PutObjectRetentionError,
+    PutObjectRetentionOutput, PutObjectRetentionRequest, RestoreObjectError, RestoreObjectOutput,
+    RestoreObjectRequest, UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest,
+    UploadPartError, UploadPartOutput, UploadPartRequest,
+};
+use crate::effective_config::StorageConfig;
+use crate::errors::{S3Result, S3StorageResult};
+use crate::serving_policy::ServingPolicy;
+use crate::storage::S3Storage;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A storage wrapper which makes the inner storage misbehave like a remote store
+///
+/// No fault is injected by default.
+#[derive(Debug)]
+pub struct FaultInjector<S> {
+    /// inner storage
+    inner: S,
+    /// how long a written object stays invisible
+    read_after_write_lag: Option<Duration>,
+    /// when the recently written objects become visible, by bucket and key
+    pending: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl<S> FaultInjector<S> {
+    /// Wraps `inner`
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            read_after_write_lag: None,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the inner storage
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Simulates an eventually consistent store:
+    /// `GetObject` and `HeadObject` of an object fail with `NoSuchKey` for `lag` after it is written
+    /// by `PutObject`, `CopyObject` or `CompleteMultipartUpload`.
+    ///
+    /// [`S3Storage::wait_for`] sleeps until the object is visible, or for its timeout if the lag is longer.
+    pub fn set_read_after_write_lag(&mut self, lag: Duration) {
+        self.read_after_write_lag = Some(lag);
+    }
+
+    /// locks the pending writes, ignoring poisoning
+    fn lock(&self) -> MutexGuard<'_, HashMap<(String, String), Instant>> {
+        match self.pending.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// hides a written object for the lag
+    fn record_write<T, E>(&self, bucket: &str, key: &str, ret: &S3StorageResult<T, E>) {
+        if let (Some(lag), true) = (self.read_after_write_lag, ret.is_ok()) {
+            let visible_at = Instant::now() + lag;
+            let _prev = self
+                .lock()
+                .insert((bucket.to_owned(), key.to_owned()), visible_at);
+        }
+    }
+
+    /// returns how long an object stays invisible
+    fn remaining_lag(&self, bucket: &str, key: &str) -> Option<Duration> {
+        let entry = (bucket.to_owned(), key.to_owned());
+        let mut pending = self.lock();
+        let remaining = pending
+            .get(&entry)?
+            .saturating_duration_since(Instant::now());
+        if remaining > Duration::ZERO {
+            return Some(remaining);
+        }
+        let _prev = pending.remove(&entry);
+        None
+    }
+}
+
+/// Faults of the part reads of [`FileSystem`](crate::storages::fs::FileSystem),
+/// see [`FileSystem::set_part_read_faults`](crate::storages::fs::FileSystem::set_part_read_faults)
+///
+/// A failing read copies half of the part before it fails, like a read which breaks in the middle.
+/// Clones share the faults and the counts.
+#[derive(Debug, Clone, Default)]
+pub struct PartReadFaults {
+    /// remaining failures and reads by part number
+    state: Arc<Mutex<HashMap<i64, (u32, u32)>>>,
+}
+
+impl PartReadFaults {
+    /// Constructs an empty plan
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the next `times` reads of the parts numbered `part_number`
+    pub fn fail(&self, part_number: i64, times: u32) {
+        self.lock().entry(part_number).or_default().0 = times;
+    }
+
+    /// Returns how many times the parts numbered `part_number` have been read, including the failed reads
+    #[must_use]
+    pub fn reads(&self, part_number: i64) -> u32 {
+        self.lock().get(&part_number).map_or(0, |&(_, reads)| reads)
+    }
+
+    /// counts a read, returning whether it fails
+    pub(crate) fn on_read(&self, part_number: i64) -> bool {
+        let mut state = self.lock();
+        let &mut (ref mut failures, ref mut reads) = state.entry(part_number).or_default();
+        *reads = reads.wrapping_add(1);
+        let fails = *failures > 0;
+        *failures = failures.saturating_sub(1);
+        drop(state);
+        fails
+    }
+
+    /// locks the state, ignoring poisoning
+    fn lock(&self) -> MutexGuard<'_, HashMap<i64, (u32, u32)>> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// the error of a read which does not see a recent write
+fn no_such_key() -> crate::errors::S3Error {
+    code_error!(NoSuchKey, "The specified key does not exist.")
+}
+
+/// forwards the operations which are not affected by faults
+macro_rules! forward_operations {
+    ($($op:ident($input:ty) -> ($output:ty, $error:ty);)+) => {
+        #[async_trait]
+        impl<S: S3Storage + Send + Sync> S3Storage for FaultInjector<S> {
+            $(
+                async fn $op(&self, input: $input) -> S3StorageResult<$output, $error> {
+                    self.inner.$op(input).await
+                }
+            )+
+
+            async fn complete_multipart_upload(
+                &self,
+                input: CompleteMultipartUploadRequest,
+            ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+                let (bucket, key) = (input.bucket.clone(), input.key.clone());
+                let ret = self.inner.complete_multipart_upload(input).await;
+                self.record_write(&bucket, &key, &ret);
+                ret
+            }
+
+            async fn copy_object(
+                &self,
+                input: CopyObjectRequest,
+            ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+                let (bucket, key) = (input.bucket.clone(), input.key.clone());
+                let ret = self.inner.copy_object(input).await;
+                self.record_write(&bucket, &key, &ret);
+                ret
+            }
+
+            async fn put_object(
+                &self,
+                input: PutObjectRequest,
+            ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+                let (bucket, key) = (input.bucket.clone(), input.key.clone());
+                let ret = self.inner.put_object(input).await;
+                self.record_write(&bucket, &key, &ret);
+                ret
+            }
+
+            async fn get_object(
+                &self,
+                input: GetObjectRequest,
+            ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
+                if self.remaining_lag(&input.bucket, &input.key).is_some() {
+                    return Err(no_such_key().into());
+                }
+                self.inner.get_object(input).await
+            }
+
+            async fn head_object(
+                &self,
+                input: HeadObjectRequest,
+            ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+                if self.remaining_lag(&input.bucket, &input.key).is_some() {
+                    return Err(no_such_key().into());
+                }
+                self.inner.head_object(input).await
+            }
+
+            async fn get_bucket_serving_policy(
+                &self,
+                bucket: &str,
+            ) -> S3Result<Option<ServingPolicy>> {
+                self.inner.get_bucket_serving_policy(bucket).await
+            }
+
+            async fn get_bucket_freeze(&self, bucket: &str) -> S3Result<Option<BucketFreeze>> {
+                self.inner.get_bucket_freeze(bucket).await
+            }
+
+            async fn presign_get(
+                &self,
+                bucket: &str,
+                key: &str,
+                expiry: Duration,
+            ) -> S3Result<Option<String>> {
+                self.inner.presign_get(bucket, key, expiry).await
+            }
+
+            async fn wait_for(
+                &self,
+                token: &ConsistencyToken,
+                timeout: Duration,
+            ) -> S3Result<bool> {
+                let remaining = match self.remaining_lag(&token.bucket, &token.key) {
+                    Some(remaining) => remaining,
+                    None => return self.inner.wait_for(token, timeout).await,
+                };
+                if remaining > timeout {
+                    tokio::time::sleep(timeout).await;
+                    return Ok(false);
+                }
+                tokio::time::sleep(remaining).await;
+                self.inner.wait_for(token, timeout - remaining).await
+            }
+
+            fn storage_config(&self) -> StorageConfig {
+                self.inner.storage_config().wrapped("fault-injector")
+            }
+        }
+    };
+}
+
+forward_operations! {
+    abort_multipart_upload(AbortMultipartUploadRequest) -> (AbortMultipartUploadOutput, AbortMultipartUploadError);
+    create_multipart_upload(CreateMultipartUploadRequest) -> (CreateMultipartUploadOutput, CreateMultipartUploadError);
+    create_bucket(CreateBucketRequest) -> (CreateBucketOutput, CreateBucketError);
+    delete_bucket(DeleteBucketRequest) -> (DeleteBucketOutput, DeleteBucketError);
+    delete_bucket_cors(DeleteBucketCorsRequest) -> (DeleteBucketCorsOutput, DeleteBucketCorsError);
+    delete_bucket_policy(DeleteBucketPolicyRequest) -> (DeleteBucketPolicyOutput, DeleteBucketPolicyError);
+    delete_bucket_tagging(DeleteBucketTaggingRequest) -> (DeleteBucketTaggingOutput, DeleteBucketTaggingError);
+    delete_object(DeleteObjectRequest) -> (DeleteObjectOutput, DeleteObjectError);
+    delete_objects(DeleteObjectsRequest) -> (DeleteObjectsOutput, DeleteObjectsError);
+    get_bucket_acl(GetBucketAclRequest) -> (GetBucketAclOutput, GetBucketAclError);
+    get_bucket_cors(GetBucketCorsRequest) -> (GetBucketCorsOutput, GetBucketCorsError);
+    get_bucket_location(GetBucketLocationRequest) -> (GetBucketLocationOutput, GetBucketLocationError);
+    get_bucket_policy(GetBucketPolicyRequest) -> (GetBucketPolicyOutput, GetBucketPolicyError);
+    get_bucket_tagging(GetBucketTaggingRequest) -> (GetBucketTaggingOutput, GetBucketTaggingError);
+    get_bucket_versioning(GetBucketVersioningRequest) -> (GetBucketVersioningOutput, GetBucketVersioningError);
+    get_object_acl(GetObjectAclRequest) -> (GetObjectAclOutput, GetObjectAclError);
+    get_object_legal_hold(GetObjectLegalHoldRequest) -> (GetObjectLegalHoldOutput, GetObjectLegalHoldError);
+    get_object_lock_configuration(GetObjectLockConfigurationRequest) -> (GetObjectLockConfigurationOutput, GetObjectLockConfigurationError);
+    get_object_retention(GetObjectRetentionRequest) -> (GetObjectRetentionOutput, GetObjectRetentionError);
+    head_bucket(HeadBucketRequest) -> (HeadBucketOutput, HeadBucketError);
+    list_buckets(ListBucketsRequest) -> (ListBucketsOutput, ListBucketsError);
+    list_multipart_uploads(ListMultipartUploadsRequest) -> (ListMultipartUploadsOutput, ListMultipartUploadsError);
+    list_object_versions(ListObjectVersionsRequest) -> (ListObjectVersionsOutput, ListObjectVersionsError);
+    list_objects(ListObjectsRequest) -> (ListObjectsOutput, ListObjectsError);
+    list_objects_v2(ListObjectsV2Request) -> (ListObjectsV2Output, ListObjectsV2Error);
+    list_parts(ListPartsRequest) -> (ListPartsOutput, ListPartsError);
+    put_bucket_acl(PutBucketAclRequest) -> (PutBucketAclOutput, PutBucketAclError);
+    put_bucket_cors(PutBucketCorsRequest) -> (PutBucketCorsOutput, PutBucketCorsError);
+    put_bucket_policy(PutBucketPolicyRequest) -> (PutBucketPolicyOutput, PutBucketPolicyError);
+    put_bucket_tagging(PutBucketTaggingRequest) -> (PutBucketTaggingOutput, PutBucketTaggingError);
+    put_bucket_versioning(PutBucketVersioningRequest) -> (PutBucketVersioningOutput, PutBucketVersioningError);
+    put_object_acl(PutObjectAclRequest) -> (PutObjectAclOutput, PutObjectAclError);
+    put_object_legal_hold(PutObjectLegalHoldRequest) -> (PutObjectLegalHoldOutput, PutObjectLegalHoldError);
+    put_object_lock_configuration(PutObjectLockConfigurationRequest) -> (PutObjectLockConfigurationOutput, PutObjectLockConfigurationError);
+    put_object_retention(PutObjectRetentionRequest) -> (PutObjectRetentionOutput, PutObjectRetentionError);
+    restore_object(RestoreObjectRequest) -> (RestoreObjectOutput, RestoreObjectError);
+    upload_part(UploadPartRequest) -> (UploadPartOutput, UploadPartError);
+    upload_part_copy(UploadPartCopyRequest) -> (UploadPartCopyOutput, UploadPartCopyError);
+}
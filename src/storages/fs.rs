@@ -1,49 +1,278 @@
 //! fs implementation
 
+mod bucket_metadata;
+pub mod index;
+mod init;
+mod inline;
+pub mod key_mapper;
+mod stitch;
+mod stored_acl;
+mod stored_lock;
+mod verify;
+
+pub use self::bucket_metadata::{BucketMetadata, DEFAULT_REGION};
+pub use self::init::{FsCapabilities, FsInitError, InvalidBucketDir};
+pub use self::inline::{InlineMigration, DEFAULT_INLINE_THRESHOLD};
+pub use self::verify::CorruptionCounter;
+
+use self::index::{IndexEntry, IndexMismatch, IndexRecord, ObjectIndex};
+use self::key_mapper::{IdentityKeyMapper, KeyMapper};
+use self::stitch::Journal;
+use self::stored_acl::StoredAcl;
+use self::stored_lock::{StoredLockConfiguration, StoredObjectLock};
+use self::verify::VerifiedStream;
+
 use crate::async_trait;
+use crate::bucket_freeze::BucketFreeze;
 use crate::data_structures::BytesStream;
+#[cfg(feature = "mmap")]
+use crate::data_structures::MmapStream;
 use crate::dto::{
-    Bucket, CompleteMultipartUploadError, CompleteMultipartUploadOutput,
-    CompleteMultipartUploadRequest, CopyObjectError, CopyObjectOutput, CopyObjectRequest,
-    CopyObjectResult, CreateBucketError, CreateBucketOutput, CreateBucketRequest,
-    CreateMultipartUploadError, CreateMultipartUploadOutput, CreateMultipartUploadRequest,
-    DeleteBucketError, DeleteBucketOutput, DeleteBucketRequest, DeleteObjectError,
-    DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError, DeleteObjectsOutput,
-    DeleteObjectsRequest, DeletedObject, GetBucketLocationError, GetBucketLocationOutput,
-    GetBucketLocationRequest, GetObjectError, GetObjectOutput, GetObjectRequest, HeadBucketError,
-    HeadBucketOutput, HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest,
-    ListBucketsError, ListBucketsOutput, ListBucketsRequest, ListObjectsError, ListObjectsOutput,
-    ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, Object,
-    PutObjectError, PutObjectOutput, PutObjectRequest, UploadPartError, UploadPartOutput,
-    UploadPartRequest,
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
+    AccessControlPolicy, Bucket, CORSRule, CompleteMultipartUploadError,
+    CompleteMultipartUploadOutput, CompleteMultipartUploadRequest, CopyObjectError,
+    CopyObjectOutput, CopyObjectRequest, CopyObjectResult, CopyPartResult, CreateBucketError,
+    CreateBucketOutput, CreateBucketRequest, CreateMultipartUploadError,
+    CreateMultipartUploadOutput, CreateMultipartUploadRequest, DeleteBucketCorsError,
+    DeleteBucketCorsOutput, DeleteBucketCorsRequest, DeleteBucketError, DeleteBucketOutput,
+    DeleteBucketPolicyError, DeleteBucketPolicyOutput, DeleteBucketPolicyRequest,
+    DeleteBucketRequest, DeleteBucketTaggingError, DeleteBucketTaggingOutput,
+    DeleteBucketTaggingRequest, DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest,
+    DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, DeletedObject,
+    GetBucketAclError, GetBucketAclOutput, GetBucketAclRequest, GetBucketCorsError,
+    GetBucketCorsOutput, GetBucketCorsRequest, GetBucketLocationError, GetBucketLocationOutput,
+    GetBucketLocationRequest, GetBucketPolicyError, GetBucketPolicyOutput, GetBucketPolicyRequest,
+    GetBucketTaggingError, GetBucketTaggingOutput, GetBucketTaggingRequest,
+    GetBucketVersioningError, GetBucketVersioningOutput, GetBucketVersioningRequest,
+    GetObjectAclError, GetObjectAclOutput, GetObjectAclRequest, GetObjectError,
+    GetObjectLegalHoldError, GetObjectLegalHoldOutput, GetObjectLegalHoldRequest,
+    GetObjectLockConfigurationError, GetObjectLockConfigurationOutput,
+    GetObjectLockConfigurationRequest, GetObjectOutput, GetObjectRequest, GetObjectRetentionError,
+    GetObjectRetentionOutput, GetObjectRetentionRequest, HeadBucketError, HeadBucketOutput,
+    HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest, ListBucketsError,
+    ListBucketsOutput, ListBucketsRequest, ListMultipartUploadsError, ListMultipartUploadsOutput,
+    ListMultipartUploadsRequest, ListObjectVersionsError, ListObjectVersionsOutput,
+    ListObjectVersionsRequest, ListObjectsError, ListObjectsOutput, ListObjectsRequest,
+    ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, ListPartsError, ListPartsOutput,
+    ListPartsRequest, ListedVersion, MultipartUpload, Object, ObjectLockConfiguration,
+    ObjectVersion, Owner, Part, PutBucketAclError, PutBucketAclOutput, PutBucketAclRequest,
+    PutBucketCorsError, PutBucketCorsOutput, PutBucketCorsRequest, PutBucketPolicyError,
+    PutBucketPolicyOutput, PutBucketPolicyRequest, PutBucketTaggingError, PutBucketTaggingOutput,
+    PutBucketTaggingRequest, PutBucketVersioningError, PutBucketVersioningOutput,
+    PutBucketVersioningRequest, PutObjectAclError, PutObjectAclOutput, PutObjectAclRequest,
+    PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldOutput, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest, PutObjectOutput, PutObjectRequest, PutObjectRetentionError,
+    PutObjectRetentionOutput, PutObjectRetentionRequest, RestoreObjectError, RestoreObjectOutput,
+    RestoreObjectRequest, Tag, UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest,
+    UploadPartError, UploadPartOutput, UploadPartRequest,
+};
+use crate::effective_config::StorageConfig;
+use crate::errors::{
+    PartialUpload, S3Error, S3ErrorCode, S3Result, S3StorageError, S3StorageResult,
 };
-use crate::errors::{S3StorageError, S3StorageResult};
 use crate::headers::AmzCopySource;
 use crate::path::S3Path;
+use crate::serving_policy::ServingPolicy;
 use crate::storage::S3Storage;
+use crate::storages::copy_source::{self, CopySourceConditions};
+#[cfg(feature = "test-utils")]
+use crate::storages::fault_injector::PartReadFaults;
+use crate::storages::listing;
+use crate::storages::object_lock;
+use crate::storages::object_range::{self, ObjectRange};
+use crate::storages::uploads;
+use crate::storages::versions::{self, NULL_VERSION_ID};
 use crate::utils::{crypto, time, Apply};
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::convert::TryInto;
 use std::env;
-use std::io;
+use std::io::{self, SeekFrom};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockWriteGuard};
+use std::time::{Duration, SystemTime};
 
-use futures::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
+use chrono::Utc;
+use futures::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufWriter};
 use futures::stream::{Stream, StreamExt, TryStreamExt};
 use hyper::body::Bytes;
 use md5::{Digest, Md5};
 use path_absolutize::Absolutize;
-use tracing::{debug, error};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 
-use async_fs::File;
+use async_fs::{File, OpenOptions};
 
 /// A S3 storage implementation based on file system
+///
+/// Object files are never rewritten in place.
+/// New contents are written to a temporary file under the root and then renamed to the object path.
 #[derive(Debug)]
 pub struct FileSystem {
     /// root path
     root: PathBuf,
+
+    /// serve objects not larger than this size from memory maps
+    #[cfg(feature = "mmap")]
+    mmap_limit: Option<u64>,
+
+    /// loaded bucket freezes, updated by `set_bucket_freeze`
+    freezes: RwLock<HashMap<String, Option<BucketFreeze>>>,
+
+    /// serves `ListObjectsV2` instead of walking directories
+    index: Option<Box<dyn ObjectIndex>>,
+
+    /// objects smaller than this size are stored in the index
+    inline_threshold: Option<u64>,
+
+    /// verify object data against the stored checksums on read
+    verify_on_read: bool,
+
+    /// the layout of object files
+    key_mapper: Box<dyn KeyMapper>,
+
+    /// reads which found corrupted objects
+    corruptions: CorruptionCounter,
+
+    /// capabilities detected by `open`
+    capabilities: FsCapabilities,
+
+    /// the region of buckets created without a location constraint
+    default_region: String,
+
+    /// injected faults of part reads
+    #[cfg(feature = "test-utils")]
+    part_read_faults: Option<PartReadFaults>,
+}
+
+/// Attempts to read a part while completing a multipart upload
+const PART_READ_ATTEMPTS: u32 = 3;
+
+/// The wait before the second attempt, which doubles for each attempt
+const PART_READ_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Content headers stored with an object
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ObjectHeaders {
+    /// `Cache-Control`
+    cache_control: Option<String>,
+    /// `Content-Disposition`
+    content_disposition: Option<String>,
+    /// `Content-Encoding`
+    content_encoding: Option<String>,
+    /// `Content-Language`
+    content_language: Option<String>,
+    /// `Content-Type`
+    content_type: Option<String>,
+    /// `Expires`
+    expires: Option<String>,
+}
+
+/// The manifest of a multipart upload, saved when the upload is created
+///
+/// `ListParts` rejects an upload without a manifest.
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadManifest {
+    /// bucket
+    bucket: String,
+    /// key
+    key: String,
+    /// rfc3339 time of the creation
+    initiated: String,
+    /// `x-amz-meta-*` of the completed object
+    #[serde(default)]
+    metadata: Option<HashMap<String, String>>,
+    /// content headers of the completed object
+    #[serde(default)]
+    headers: ObjectHeaders,
+    /// retention and legal hold of the completed object
+    #[serde(default)]
+    lock: StoredObjectLock,
+}
+
+/// The manifest of an uploaded part, saved after the part file is renamed into place
+///
+/// A part file without a manifest, or whose size does not match, is hashed again when it is listed.
+#[derive(Debug, Serialize, Deserialize)]
+struct PartManifest {
+    /// quoted MD5 of the part
+    e_tag: String,
+    /// size of the part file
+    size: u64,
+    /// rfc3339 time of the upload
+    last_modified: String,
+}
+
+impl ObjectHeaders {
+    /// Checks whether no header is stored
+    const fn is_empty(&self) -> bool {
+        self.cache_control.is_none()
+            && self.content_disposition.is_none()
+            && self.content_encoding.is_none()
+            && self.content_language.is_none()
+            && self.content_type.is_none()
+            && self.expires.is_none()
+    }
+}
+
+/// Versioning configuration stored with a bucket
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BucketVersioning {
+    /// `Status`
+    status: Option<String>,
+    /// `MfaDelete`
+    mfa_delete: Option<String>,
+}
+
+/// A CORS rule stored with a bucket
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketCorsRule {
+    /// `AllowedHeader`
+    allowed_headers: Option<Vec<String>>,
+    /// `AllowedMethod`
+    allowed_methods: Vec<String>,
+    /// `AllowedOrigin`
+    allowed_origins: Vec<String>,
+    /// `ExposeHeader`
+    expose_headers: Option<Vec<String>>,
+    /// `MaxAgeSeconds`
+    max_age_seconds: Option<i64>,
+}
+
+impl From<CORSRule> for BucketCorsRule {
+    fn from(rule: CORSRule) -> Self {
+        Self {
+            allowed_headers: rule.allowed_headers,
+            allowed_methods: rule.allowed_methods,
+            allowed_origins: rule.allowed_origins,
+            expose_headers: rule.expose_headers,
+            max_age_seconds: rule.max_age_seconds,
+        }
+    }
+}
+
+impl From<BucketCorsRule> for CORSRule {
+    fn from(rule: BucketCorsRule) -> Self {
+        Self {
+            allowed_headers: rule.allowed_headers,
+            allowed_methods: rule.allowed_methods,
+            allowed_origins: rule.allowed_origins,
+            expose_headers: rule.expose_headers,
+            max_age_seconds: rule.max_age_seconds,
+        }
+    }
+}
+
+/// A tag stored with a bucket
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketTag {
+    /// `Key`
+    key: String,
+    /// `Value`
+    value: String,
 }
 
 impl FileSystem {
@@ -52,288 +281,2065 @@ impl FileSystem {
     /// Returns an `Err` if current working directory is invalid or `root` doesn't exist
     pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
         let root = env::current_dir()?.join(root).canonicalize()?;
-        Ok(Self { root })
+        Ok(Self::with_capabilities(root, FsCapabilities::default()))
     }
 
-    /// resolve object path under the virtual root
-    fn get_object_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
-        let dir = Path::new(&bucket);
-        let file_path = Path::new(&key);
-        let ans = dir
-            .join(&file_path)
-            .absolutize_virtually(&self.root)?
-            .into();
-        Ok(ans)
+    /// Constructs a file system storage located at `root`, validating it first.
+    ///
+    /// The root must be an existing writable directory,
+    /// on a filesystem where renaming a file over another replaces it.
+    /// The capabilities of the filesystem are detected once, see [`FileSystem::capabilities`].
+    ///
+    /// Bucket directories are not checked, see [`FileSystem::invalid_bucket_dirs`].
+    /// # Errors
+    /// Returns an `Err` if the root fails a check
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, FsInitError> {
+        let (root, capabilities) = init::probe(root.as_ref())?;
+        Ok(Self::with_capabilities(root, capabilities))
     }
 
-    /// resolve bucket path under the virtual root
-    fn get_bucket_path(&self, bucket: &str) -> io::Result<PathBuf> {
-        let dir = Path::new(&bucket);
-        let ans = dir.absolutize_virtually(&self.root)?.into();
-        Ok(ans)
+    /// constructs a storage at a canonical root
+    fn with_capabilities(root: PathBuf, capabilities: FsCapabilities) -> Self {
+        Self {
+            root,
+            #[cfg(feature = "mmap")]
+            mmap_limit: None,
+            freezes: RwLock::default(),
+            index: None,
+            inline_threshold: None,
+            verify_on_read: false,
+            key_mapper: Box::new(IdentityKeyMapper),
+            corruptions: CorruptionCounter::default(),
+            capabilities,
+            default_region: DEFAULT_REGION.to_owned(),
+            #[cfg(feature = "test-utils")]
+            part_read_faults: None,
+        }
     }
 
-    /// resolve metadata path under the virtual root (custom format)
-    fn get_metadata_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
-        let encode = |s: &str| base64::encode_config(s, base64::URL_SAFE_NO_PAD);
-
-        let file_path_str = format!(
-            ".bucket-{}.object-{}.metadata.json",
-            encode(bucket),
-            encode(key),
-        );
-        let file_path = Path::new(&file_path_str);
-        let ans = file_path.absolutize_virtually(&self.root)?.into();
-        Ok(ans)
+    /// Returns the capabilities detected by [`FileSystem::open`]
+    #[must_use]
+    pub const fn capabilities(&self) -> &FsCapabilities {
+        &self.capabilities
     }
 
-    /// load metadata from fs
-    async fn load_metadata(
-        &self,
-        bucket: &str,
-        key: &str,
-    ) -> io::Result<Option<HashMap<String, String>>> {
-        let path = self.get_metadata_path(bucket, key)?;
-        if path.exists() {
-            let content = async_fs::read(&path).await?;
-            let map = serde_json::from_slice(&content)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-            Ok(Some(map))
-        } else {
-            Ok(None)
-        }
+    /// Lists the directories under the root which are not valid bucket names.
+    ///
+    /// Such directories are ignored by the storage.
+    /// # Errors
+    /// Returns an `Err` if the root can not be read
+    pub fn invalid_bucket_dirs(&self) -> io::Result<Vec<InvalidBucketDir>> {
+        init::invalid_bucket_dirs(&self.root)
     }
 
-    /// save metadata
-    async fn save_metadata(
-        &self,
-        bucket: &str,
-        key: &str,
-        metadata: &HashMap<String, String>,
-    ) -> io::Result<()> {
-        let path = self.get_metadata_path(bucket, key)?;
-        let content = serde_json::to_vec(metadata)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        async_fs::write(&path, &content).await
+    /// Verifies the data of `GetObject` against the MD5 stored when the object was written.
+    ///
+    /// The `ETag` is served from the stored MD5 instead of hashing the file before the response.
+    /// If the data does not match at the end of the stream,
+    /// the body fails instead of ending, so the connection is aborted before the last chunk.
+    /// The corruption is logged and counted by [`FileSystem::corruption_counter`].
+    ///
+    /// Objects written without a stored MD5, such as files copied into the root, are not verified.
+    pub fn set_verify_on_read(&mut self, verify: bool) {
+        self.verify_on_read = verify;
     }
 
-    /// get md5 sum
-    async fn get_md5_sum(&self, bucket: &str, key: &str) -> io::Result<String> {
-        let object_path = self.get_object_path(bucket, key)?;
-        let mut file = File::open(&object_path).await?;
-        let mut buf = vec![0; 4_usize.wrapping_mul(1024).wrapping_mul(1024)];
-        let mut md5_hash = Md5::new();
-        loop {
-            let nread = file.read(&mut buf).await?;
-            if nread == 0 {
-                break;
-            }
-            md5_hash.update(buf.get(..nread).unwrap_or_else(|| {
-                panic!(
-                    "nread is larger than buffer size: nread = {}, size = {}",
-                    nread,
-                    buf.len()
-                )
-            }));
-        }
-        md5_hash.finalize().apply(crypto::to_hex_string).apply(Ok)
+    /// Returns the counter of reads which found corrupted objects
+    #[must_use]
+    pub fn corruption_counter(&self) -> CorruptionCounter {
+        self.corruptions.clone()
     }
-}
 
-/// copy bytes from a stream to a writer
-async fn copy_bytes<S, W>(mut stream: S, writer: &mut W) -> io::Result<usize>
-where
-    S: Stream<Item = io::Result<Bytes>> + Send + Unpin,
-    W: AsyncWrite + Send + Unpin,
-{
-    let mut nwrite: usize = 0;
-    while let Some(bytes) = stream.next().await {
-        let bytes = bytes?;
+    /// Serves `ListObjectsV2` from `index`, which is updated by object writes and deletes.
+    ///
+    /// The index must match the filesystem, so call [`FileSystem::reindex`] after setting a new index
+    /// or after objects are changed by other processes.
+    pub fn set_index(&mut self, index: impl ObjectIndex) {
+        self.index = Some(Box::new(index));
+    }
 
-        let amt_u64 = futures::io::copy_buf(bytes.as_ref(), writer).await?;
-        let amt: usize = amt_u64.try_into().unwrap_or_else(|err| {
-            panic!(
-                "number overflow: u64 to usize, n = {}, err = {}",
-                amt_u64, err
-            )
-        });
+    /// Sets the region of buckets created without a location constraint,
+    /// and of bucket directories without recorded metadata. The default is [`DEFAULT_REGION`].
+    ///
+    /// Existing buckets keep their recorded regions.
+    pub fn set_default_region(&mut self, region: impl Into<String>) {
+        self.default_region = region.into();
+    }
 
-        assert_eq!(
-            bytes.len(),
-            amt,
-            "amt mismatch: bytes.len() = {}, amt = {}, nwrite = {}",
-            bytes.len(),
-            amt,
-            nwrite
-        );
+    /// Returns the metadata of a bucket, or `None` if the bucket does not exist.
+    ///
+    /// The metadata of a bucket directory without a recorded one is synthesized from the directory
+    /// and recorded, so that it does not change later.
+    /// # Errors
+    /// Returns an `Err` if the metadata can not be loaded or recorded
+    pub async fn bucket_metadata(&self, bucket: &str) -> io::Result<Option<BucketMetadata>> {
+        let path = self.get_bucket_path(bucket)?;
+        if !path.is_dir() {
+            return Ok(None);
+        }
+        self.load_bucket_metadata(bucket, &path).await.map(Some)
+    }
 
-        nwrite = nwrite
-            .checked_add(amt)
-            .unwrap_or_else(|| panic!("nwrite overflow: amt = {}, nwrite = {}", amt, nwrite));
+    /// Stores object files at the paths given by `key_mapper` instead of their keys.
+    ///
+    /// Files which are not objects of the layout are hidden from listings.
+    /// Set the mapper before serving requests: objects written under another layout are not moved.
+    pub fn set_key_mapper(&mut self, key_mapper: impl KeyMapper) {
+        self.key_mapper = Box::new(key_mapper);
     }
-    writer.flush().await?;
-    Ok(nwrite)
-}
 
-/// wrap operation error
-const fn operation_error<E>(e: E) -> S3StorageError<E> {
-    S3StorageError::Operation(e)
-}
+    /// Fails the part reads of `CompleteMultipartUpload` as planned by `faults`.
+    ///
+    /// Requires the feature `test-utils`.
+    #[cfg(feature = "test-utils")]
+    pub fn set_part_read_faults(&mut self, faults: PartReadFaults) {
+        self.part_read_faults = Some(faults);
+    }
 
-#[async_trait]
-impl S3Storage for FileSystem {
-    #[tracing::instrument]
-    async fn create_bucket(
-        &self,
-        input: CreateBucketRequest,
-    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
+    /// Stores objects smaller than `threshold` bytes in the index instead of as files,
+    /// such as [`DEFAULT_INLINE_THRESHOLD`]. `None` (the default) stores all objects as files.
+    ///
+    /// Requires an index, see [`FileSystem::set_index`]: the threshold has no effect without one.
+    /// Inline objects are served like files by all operations, including copies, listings and
+    /// multipart completions, and keep their metadata and headers in the usual files.
+    /// Their data is persisted by the index, so replacing the index drops them.
+    /// They are not checked by [`FileSystem::set_verify_on_read`].
+    ///
+    /// Existing objects keep their layout until [`FileSystem::migrate_inline`] moves them.
+    pub fn set_inline_threshold(&mut self, threshold: Option<u64>) {
+        self.inline_threshold = threshold;
+    }
 
-        if path.exists() {
-            let err = CreateBucketError::BucketAlreadyExists(String::from(
-                "The requested bucket name is not available. \
-                    The bucket namespace is shared by all users of the system. \
-                    Please select a different name and try again.",
-            ));
-            return Err(operation_error(err));
+    /// Moves the objects whose layout does not match the inline threshold:
+    /// object files smaller than the threshold are stored inline,
+    /// and other inline objects are written back as files.
+    ///
+    /// The migration may run in the background while requests are served.
+    /// An object is checked again right before it is moved and skipped if it has changed,
+    /// but a write of the same object in between may still be overwritten by the moved data,
+    /// so run it when objects are not being rewritten, such as at startup.
+    /// # Errors
+    /// Returns an `Err` if there is no index, or the filesystem or the index fails
+    pub async fn migrate_inline(&self) -> io::Result<InlineMigration> {
+        let index = self.require_index()?;
+        let mut migration = InlineMigration::default();
+        for (bucket, key, entry) in index.snapshot()? {
+            let is_inline = self.is_inline_size(entry.size);
+            match (entry.inline.clone(), is_inline) {
+                (Some(data), false) => {
+                    let temp_path = self.get_temp_path()?;
+                    let ret = async_fs::write(&temp_path, &data).await;
+                    remove_temp_on_error(ret, &temp_path).await?;
+                    let current =
+                        remove_temp_on_error(index.get(&bucket, &key), &temp_path).await?;
+                    if current.and_then(|current| current.inline).as_ref() != Some(&data) {
+                        async_fs::remove_file(&temp_path).await?;
+                        migration.skipped = migration.skipped.wrapping_add(1);
+                        continue;
+                    }
+                    let object_path = self.get_object_path(&bucket, &key)?;
+                    let ret = self
+                        .commit_object(&bucket, &key, &temp_path, &object_path)
+                        .await;
+                    let _is_inline = remove_temp_on_error(ret, &temp_path).await?;
+                    self.save_checksum(&bucket, &key, &inline::md5_hex(&data))
+                        .await?;
+                    migration.exhumed = migration.exhumed.wrapping_add(1);
+                }
+                (None, true) => {
+                    let object_path = self.get_object_path(&bucket, &key)?;
+                    let data = async_fs::read(&object_path).await?;
+                    let metadata = async_fs::metadata(&object_path).await?;
+                    if index_entry(&metadata)? != entry {
+                        migration.skipped = migration.skipped.wrapping_add(1);
+                        continue;
+                    }
+                    let _last_modified = self.put_inline(&bucket, &key, data.into()).await?;
+                    migration.inlined = migration.inlined.wrapping_add(1);
+                }
+                _ => {}
+            }
         }
-
-        trace_try!(async_fs::create_dir(&path).await);
-
-        let output = CreateBucketOutput::default(); // TODO: handle other fields
-        Ok(output)
+        Ok(migration)
     }
 
-    #[tracing::instrument]
-    async fn copy_object(
-        &self,
-        input: CopyObjectRequest,
-    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
-        let copy_source = AmzCopySource::from_header_str(&input.copy_source)
-            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+    /// Rebuilds the index from the filesystem and returns the number of objects.
+    ///
+    /// Inline objects are kept, see [`FileSystem::set_inline_threshold`].
+    /// # Errors
+    /// Returns an `Err` if there is no index, or the filesystem or the index fails
+    pub async fn reindex(&self) -> io::Result<usize> {
+        let index = self.require_index()?;
+        let mut objects: BTreeMap<_, _> = self
+            .walk_objects()
+            .await?
+            .into_iter()
+            .map(|(bucket, key, entry)| ((bucket, key), entry))
+            .collect();
+        // an inline object shadows a file which is left by a crash
+        for (bucket, key, entry) in index.snapshot()? {
+            if entry.inline.is_some() {
+                let _prev = objects.insert((bucket, key), entry);
+            }
+        }
+        let records: Vec<IndexRecord> = objects
+            .into_iter()
+            .map(|((bucket, key), entry)| (bucket, key, entry))
+            .collect();
+        let len = records.len();
+        index.replace_all(records)?;
+        Ok(len)
+    }
 
-        let (bucket, key) = match copy_source {
-            AmzCopySource::AccessPoint { .. } => {
-                return Err(not_supported!("Access point is not supported yet.").into())
+    /// Compares the index with the filesystem.
+    ///
+    /// Inline objects are not compared, since the index is their only copy.
+    /// Objects changed during the check may be reported.
+    /// # Errors
+    /// Returns an `Err` if there is no index, or the filesystem or the index fails
+    pub async fn check_index(&self) -> io::Result<Vec<IndexMismatch>> {
+        let index = self.require_index()?;
+        let mut actual: BTreeMap<_, _> = self
+            .walk_objects()
+            .await?
+            .into_iter()
+            .map(|(bucket, key, entry)| ((bucket, key), entry))
+            .collect();
+
+        let mut mismatches = Vec::new();
+        for (bucket, key, entry) in index.snapshot()? {
+            let location = (bucket, key);
+            let actual_entry = actual.remove(&location);
+            let (bucket, key) = location;
+            if entry.inline.is_some() {
+                continue;
             }
-            AmzCopySource::Bucket { bucket, key } => (bucket, key),
-        };
+            match actual_entry {
+                Some(ref actual_entry) if *actual_entry == entry => {}
+                Some(_) => mismatches.push(IndexMismatch::Changed { bucket, key }),
+                None => mismatches.push(IndexMismatch::Stale { bucket, key }),
+            }
+        }
+        mismatches.extend(
+            actual
+                .into_iter()
+                .map(|((bucket, key), _)| IndexMismatch::Missing { bucket, key }),
+        );
+        Ok(mismatches)
+    }
 
-        let src_path = trace_try!(self.get_object_path(bucket, key));
-        let dst_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+    /// the index, or an error for the maintenance methods
+    fn require_index(&self) -> io::Result<&dyn ObjectIndex> {
+        self.index
+            .as_deref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The storage has no index"))
+    }
 
-        let file_metadata = trace_try!(async_fs::metadata(&src_path).await);
-        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
+    /// walk all object files of all buckets
+    async fn walk_objects(&self) -> io::Result<Vec<IndexRecord>> {
+        let mut records = Vec::new();
+        let mut buckets = async_fs::read_dir(&self.root).await?;
+        while let Some(bucket) = buckets.next().await {
+            let bucket = bucket?;
+            let name = bucket.file_name().to_string_lossy().into_owned();
+            if !bucket.file_type().await?.is_dir() || !S3Path::check_bucket_name(&name) {
+                continue;
+            }
+            for (key, metadata) in self.walk_bucket(&bucket.path()).await? {
+                records.push((name.clone(), key, index_entry(&metadata)?));
+            }
+        }
+        records.sort_by(|lhs, rhs| (&lhs.0, &lhs.1).cmp(&(&rhs.0, &rhs.1)));
+        Ok(records)
+    }
 
-        let _ = trace_try!(async_fs::copy(&src_path, &dst_path).await);
+    /// walk all object files of a bucket, returning their keys in ascending order
+    ///
+    /// Files which are not objects of the key layout are skipped.
+    async fn walk_bucket(
+        &self,
+        bucket_path: &Path,
+    ) -> io::Result<Vec<(String, std::fs::Metadata)>> {
+        let mut files = Vec::new();
+        let mut dir_queue = VecDeque::new();
+        dir_queue.push_back(bucket_path.to_owned());
+        while let Some(dir) = dir_queue.pop_front() {
+            let mut entries = async_fs::read_dir(dir).await?;
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                if entry.file_type().await?.is_dir() {
+                    dir_queue.push_back(entry.path());
+                    continue;
+                }
+                let file_path = entry.path();
+                let backend_key = file_path
+                    .strip_prefix(bucket_path)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+                    .to_string_lossy()
+                    .into_owned();
+                files.push((backend_key, entry));
+            }
+        }
+        files.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
 
-        debug!(
-            from = %src_path.display(),
-            to = %dst_path.display(),
-            "CopyObject: copy file",
-        );
+        let mut objects = Vec::with_capacity(files.len());
+        for (backend_key, entry) in files {
+            if let Some(key) = self.key_mapper.to_s3(&backend_key) {
+                objects.push((key, entry.metadata().await?));
+            }
+        }
+        if !self.key_mapper.is_order_preserving() {
+            objects.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        }
+        Ok(objects)
+    }
 
-        let src_metadata_path = trace_try!(self.get_metadata_path(bucket, key));
-        if src_metadata_path.exists() {
-            let dst_metadata_path = trace_try!(self.get_metadata_path(&input.bucket, &input.key));
-            let _ = trace_try!(async_fs::copy(src_metadata_path, dst_metadata_path).await);
+    /// update the index after an object file is written
+    async fn index_object(&self, bucket: &str, key: &str, path: &Path) -> io::Result<()> {
+        if let Some(ref index) = self.index {
+            let entry = index_entry(&async_fs::metadata(path).await?)?;
+            index.insert(bucket, key, entry)?;
         }
+        Ok(())
+    }
 
-        let md5_sum = trace_try!(self.get_md5_sum(bucket, key).await);
+    /// update the index after an object file is removed
+    fn unindex_object(&self, bucket: &str, key: &str) -> io::Result<()> {
+        self.index
+            .as_ref()
+            .map_or(Ok(()), |index| index.remove(bucket, key))
+    }
 
-        let output = CopyObjectOutput {
-            copy_object_result: CopyObjectResult {
-                e_tag: Some(format!("\"{}\"", md5_sum)),
-                last_modified: Some(last_modified),
-            }
-            .apply(Some),
-            ..CopyObjectOutput::default()
+    /// whether an object of `size` bytes is stored inline
+    fn is_inline_size(&self, size: u64) -> bool {
+        self.index.is_some()
+            && self
+                .inline_threshold
+                .map_or(false, |threshold| size < threshold)
+    }
+
+    /// the data and the modification time of an inline object
+    fn get_inline(&self, bucket: &str, key: &str) -> io::Result<Option<(Bytes, String)>> {
+        let index = match self.index {
+            Some(ref index) => index,
+            None => return Ok(None),
         };
+        let entry = index.get(bucket, key)?;
+        Ok(entry.and_then(|entry| Some((entry.inline?, entry.last_modified))))
+    }
 
-        Ok(output)
+    /// store an object inline, replacing its file, and return its modification time
+    async fn put_inline(&self, bucket: &str, key: &str, data: Bytes) -> io::Result<String> {
+        let index = self.require_index()?;
+        let last_modified = time::to_rfc3339(SystemTime::now());
+        let entry = IndexEntry {
+            size: data.len().try_into().unwrap_or(u64::MAX),
+            last_modified: last_modified.clone(),
+            inline: Some(data),
+        };
+        self.remove_checksum(bucket, key).await?;
+        index.insert(bucket, key, entry)?;
+        // a file left by a crash here is shadowed by the inline object
+        let object_path = self.get_object_path(bucket, key)?;
+        remove_file_if_exists(&object_path).await?;
+        Ok(last_modified)
     }
 
-    #[tracing::instrument]
-    async fn delete_bucket(
+    /// Moves a written temporary file to the object, or into the index if the object is small.
+    ///
+    /// Returns whether the object is stored inline.
+    /// The temporary file is left on an error, for the caller to remove or to resume.
+    async fn commit_object(
         &self,
-        input: DeleteBucketRequest,
-    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
-        trace_try!(async_fs::remove_dir_all(path).await);
-        Ok(DeleteBucketOutput)
+        bucket: &str,
+        key: &str,
+        temp_path: &Path,
+        object_path: &Path,
+    ) -> io::Result<bool> {
+        let size = async_fs::metadata(temp_path).await?.len();
+        if self.is_inline_size(size) {
+            let data = async_fs::read(temp_path).await?;
+            let _last_modified = self.put_inline(bucket, key, data.into()).await?;
+            async_fs::remove_file(temp_path).await?;
+            return Ok(true);
+        }
+        if let Some(dir_path) = object_path.parent() {
+            async_fs::create_dir_all(&dir_path).await?;
+        }
+        self.remove_checksum(bucket, key).await?;
+        async_fs::rename(temp_path, object_path).await?;
+        self.index_object(bucket, key, object_path).await?;
+        Ok(false)
     }
 
-    #[tracing::instrument]
-    async fn delete_object(
-        &self,
-        input: DeleteObjectRequest,
-    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
-        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
-        if input.key.ends_with('/') {
-            let mut dir = trace_try!(async_fs::read_dir(&path).await);
-            let is_empty = dir.next().await.is_none();
-            if is_empty {
-                trace_try!(async_fs::remove_dir(&path).await);
-            }
+    /// whether an object file or an inline object exists
+    fn object_exists(&self, bucket: &str, key: &str) -> io::Result<bool> {
+        Ok(self.get_inline(bucket, key)?.is_some() || self.get_object_path(bucket, key)?.is_file())
+    }
+
+    /// remove an object file or an inline object, with its metadata, headers, part sizes, ACL and lock
+    async fn remove_object(&self, bucket: &str, key: &str) -> io::Result<()> {
+        let path = self.get_object_path(bucket, key)?;
+        self.remove_checksum(bucket, key).await?;
+        remove_file_if_exists(&self.get_metadata_path(bucket, key)?).await?;
+        remove_file_if_exists(&self.get_object_headers_path(bucket, key)?).await?;
+        remove_file_if_exists(&self.get_part_sizes_path(bucket, key)?).await?;
+        self.remove_acl(bucket, key).await?;
+        self.remove_object_lock(bucket, key).await?;
+        if self.get_inline(bucket, key)?.is_some() {
+            remove_file_if_exists(&path).await?;
         } else {
-            trace_try!(async_fs::remove_file(path).await);
+            async_fs::remove_file(&path).await?;
         }
-        let output = DeleteObjectOutput::default(); // TODO: handle other fields
-        Ok(output)
+        self.unindex_object(bucket, key)
     }
 
-    #[tracing::instrument]
-    async fn delete_objects(
+    /// the objects of a bucket in key order, including the inline objects
+    async fn list_bucket(
         &self,
-        input: DeleteObjectsRequest,
-    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
-        let mut objects: Vec<(PathBuf, String)> = Vec::new();
-        for object in input.delete.objects {
-            let path = trace_try!(self.get_object_path(&input.bucket, &object.key));
-            if path.exists() {
-                objects.push((path, object.key));
+        bucket: &str,
+        bucket_path: &Path,
+    ) -> io::Result<Vec<(String, IndexEntry)>> {
+        let mut objects = BTreeMap::new();
+        for (key, metadata) in self.walk_bucket(bucket_path).await? {
+            let _prev = objects.insert(key, index_entry(&metadata)?);
+        }
+        if let Some(ref index) = self.index {
+            for (key, entry) in index.scan(bucket, "", None, usize::MAX)? {
+                if entry.inline.is_some() {
+                    let _prev = objects.insert(key, entry);
+                }
             }
         }
+        Ok(objects.into_iter().collect())
+    }
 
+    /// Serves `GetObject` from memory maps for objects not larger than `limit` bytes.
+    ///
+    /// `None` (the default) disables memory maps.
+    /// Empty objects and larger objects are always served by the buffered reader.
+    ///
+    /// A response body keeps its map alive.
+    /// If the object is replaced by a new PUT meanwhile,
+    /// the body keeps serving the old inode, which is a consistent snapshot of the object.
+    #[cfg(feature = "mmap")]
+    pub fn set_mmap_limit(&mut self, limit: Option<u64>) {
+        self.mmap_limit = limit;
+    }
+
+    /// Sets the serving policy of a bucket. `None` removes it.
+    ///
+    /// See [`ServingPolicy`] for how it fills `GetObject` and `HeadObject` responses.
+    /// # Errors
+    /// Returns an `Err` if the policy can not be saved
+    pub async fn set_bucket_serving_policy(
+        &self,
+        bucket: &str,
+        policy: Option<&ServingPolicy>,
+    ) -> io::Result<()> {
+        let path = self.get_serving_policy_path(bucket)?;
+        match policy {
+            Some(policy) => {
+                let content = serde_json::to_vec(policy)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                async_fs::write(&path, &content).await
+            }
+            None if path.exists() => async_fs::remove_file(&path).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Freezes writes to a bucket. `None` unfreezes it.
+    ///
+    /// The freeze is saved under the root so that it survives restarts.
+    /// See [`BucketFreeze`] for which operations are rejected.
+    /// # Errors
+    /// Returns an `Err` if the freeze can not be saved
+    pub async fn set_bucket_freeze(
+        &self,
+        bucket: &str,
+        freeze: Option<&BucketFreeze>,
+    ) -> io::Result<()> {
+        let path = self.get_freeze_path(bucket)?;
+        let ret = match freeze {
+            Some(freeze) => {
+                let content = serde_json::to_vec(freeze)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                async_fs::write(&path, &content).await
+            }
+            None if path.exists() => async_fs::remove_file(&path).await,
+            None => Ok(()),
+        };
+        if ret.is_ok() {
+            let _prev = self
+                .lock_freezes()
+                .insert(bucket.to_owned(), freeze.copied());
+        } else {
+            // the file may be partially written, so it is loaded again
+            let _prev = self.lock_freezes().remove(bucket);
+        }
+        ret
+    }
+
+    /// lock the loaded bucket freezes
+    fn lock_freezes(&self) -> RwLockWriteGuard<'_, HashMap<String, Option<BucketFreeze>>> {
+        match self.freezes.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// resolve a new temporary file path under the virtual root
+    fn get_temp_path(&self) -> io::Result<PathBuf> {
+        let file_path_str = format!(".tmp-{}", Uuid::new_v4());
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve object path under the virtual root
+    fn get_object_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        let dir = Path::new(&bucket);
+        let backend_key = self.key_mapper.to_backend(key);
+        let file_path = Path::new(&backend_key);
+        let ans = dir
+            .join(&file_path)
+            .absolutize_virtually(&self.root)?
+            .into();
+        Ok(ans)
+    }
+
+    /// resolve bucket path under the virtual root
+    fn get_bucket_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        let dir = Path::new(&bucket);
+        let ans = dir.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve the path of a file which belongs to a bucket or to an object under the virtual root
+    fn sidecar_path(&self, bucket: &str, key: Option<&str>, suffix: &str) -> io::Result<PathBuf> {
+        let encode = |s: &str| base64::encode_config(s, base64::URL_SAFE_NO_PAD);
+
+        let object = key.map_or_else(String::new, |key| format!("object-{}.", encode(key)));
+        let file_path_str = format!(".bucket-{}.{}{}", encode(bucket), object, suffix);
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve metadata path under the virtual root (custom format)
+    fn get_metadata_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, Some(key), "metadata.json")
+    }
+
+    /// resolve object headers path under the virtual root (custom format)
+    fn get_object_headers_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, Some(key), "headers.json")
+    }
+
+    /// resolve the path to the part sizes of a multipart object under the virtual root (custom format)
+    fn get_part_sizes_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, Some(key), "parts.json")
+    }
+
+    /// resolve object ACL path under the virtual root (custom format)
+    fn get_acl_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, Some(key), "acl.json")
+    }
+
+    /// resolve object lock path under the virtual root (custom format)
+    fn get_object_lock_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, Some(key), "lock.json")
+    }
+
+    /// resolve object checksum path under the virtual root (custom format)
+    fn get_checksum_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, Some(key), "md5")
+    }
+
+    /// resolve serving policy path under the virtual root (custom format)
+    fn get_serving_policy_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "serving-policy.json")
+    }
+
+    /// resolve bucket freeze path under the virtual root (custom format)
+    fn get_freeze_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "freeze.json")
+    }
+
+    /// resolve bucket metadata path under the virtual root (custom format)
+    fn get_bucket_metadata_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "bucket.json")
+    }
+
+    /// resolve bucket versioning path under the virtual root (custom format)
+    fn get_versioning_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "versioning.json")
+    }
+
+    /// resolve bucket ACL path under the virtual root (custom format)
+    fn get_bucket_acl_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "acl.json")
+    }
+
+    /// resolve bucket CORS rules path under the virtual root (custom format)
+    fn get_cors_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "cors.json")
+    }
+
+    /// resolve bucket policy path under the virtual root (custom format)
+    fn get_policy_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "policy.json")
+    }
+
+    /// resolve bucket Object Lock configuration path under the virtual root (custom format)
+    fn get_lock_configuration_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "object-lock.json")
+    }
+
+    /// resolve bucket tagging path under the virtual root (custom format)
+    fn get_tagging_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        self.sidecar_path(bucket, None, "tagging.json")
+    }
+
+    /// resolve multipart upload manifest path under the virtual root (custom format)
+    fn get_upload_path(&self, upload_id: &str) -> io::Result<PathBuf> {
+        let file_path_str = format!(".upload_id-{}.json", upload_id);
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve part path under the virtual root
+    fn get_part_path(&self, upload_id: &str, part_number: i64) -> io::Result<PathBuf> {
+        let file_path_str = format!("{}{}", part_prefix(upload_id), part_number);
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve the stitch file of a multipart upload under the virtual root
+    fn get_stitch_path(&self, upload_id: &str) -> io::Result<PathBuf> {
+        let file_path_str = format!(".upload_id-{}.stitch", upload_id);
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve the stitch journal of a multipart upload under the virtual root (custom format)
+    fn get_stitch_journal_path(&self, upload_id: &str) -> io::Result<PathBuf> {
+        let file_path_str = format!(".upload_id-{}.stitch.jsonl", upload_id);
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// appends a part to the stitch file at `offset`, retrying a failed read after a backoff
+    ///
+    /// The bytes of a failed attempt are truncated before the next one.
+    async fn append_part(
+        &self,
+        writer: &mut BufWriter<File>,
+        part_path: &Path,
+        part_number: i64,
+        offset: u64,
+    ) -> io::Result<(u64, Vec<u8>)> {
+        let mut backoff = PART_READ_BACKOFF;
+        let mut attempt: u32 = 1;
+        loop {
+            let err = match self.copy_part(writer, part_path, part_number).await {
+                Ok(ans) => return Ok(ans),
+                Err(err) => err,
+            };
+            if attempt >= PART_READ_ATTEMPTS || err.kind() == io::ErrorKind::NotFound {
+                return Err(err);
+            }
+            warn!(
+                part = %part_path.display(),
+                attempt,
+                %err,
+                ?backoff,
+                "CompleteMultipartUpload: retry a part",
+            );
+            writer.flush().await?;
+            writer.get_mut().set_len(offset).await?;
+            let _pos = writer.seek(SeekFrom::Start(offset)).await?;
+            time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+            attempt = attempt.wrapping_add(1);
+        }
+    }
+
+    /// copies a part to the stitch file, returning its size and MD5
+    #[cfg_attr(not(feature = "test-utils"), allow(unused_variables))]
+    async fn copy_part(
+        &self,
+        writer: &mut BufWriter<File>,
+        part_path: &Path,
+        part_number: i64,
+    ) -> io::Result<(u64, Vec<u8>)> {
+        let mut reader = File::open(part_path).await?;
+        let mut md5_hash = Md5::new();
+        #[cfg(feature = "test-utils")]
+        if let Some(ref faults) = self.part_read_faults {
+            if faults.on_read(part_number) {
+                let half = reader.metadata().await?.len().wrapping_div(2);
+                let _size =
+                    copy_hashed(&mut (&mut reader).take(half), writer, &mut md5_hash).await?;
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "injected part read fault",
+                ));
+            }
+        }
+        let size = copy_hashed(&mut reader, writer, &mut md5_hash).await?;
+        Ok((size, md5_hash.finalize().to_vec()))
+    }
+
+    /// resolve part manifest path under the virtual root (custom format)
+    fn get_part_manifest_path(&self, upload_id: &str, part_number: i64) -> io::Result<PathBuf> {
+        let file_path_str = format!("{}{}.json", part_prefix(upload_id), part_number);
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// write a file by renaming a temporary file over it
+    async fn write_atomically(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        let temp_path = self.get_temp_path()?;
+        async_fs::write(&temp_path, content).await?;
+        async_fs::rename(&temp_path, path).await
+    }
+
+    /// load the manifest of a multipart upload
+    async fn load_upload(&self, upload_id: &str) -> io::Result<Option<UploadManifest>> {
+        let path = self.get_upload_path(upload_id)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            let manifest = serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(Some(manifest))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// list the multipart uploads of a bucket by their manifests
+    async fn load_uploads(&self, bucket: &str) -> io::Result<Vec<MultipartUpload>> {
+        let mut uploads = Vec::new();
+        let mut entries = async_fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next().await {
+            let name = entry?.file_name();
+            // the manifests of parts start with the same prefix, but an upload id has no dot
+            let upload_id = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(".upload_id-"))
+                .and_then(|rest| rest.strip_suffix(".json"))
+                .filter(|upload_id| !upload_id.contains('.'));
+            let upload_id = match upload_id {
+                Some(upload_id) => upload_id,
+                None => continue,
+            };
+            let manifest = match self.load_upload(upload_id).await? {
+                Some(manifest) if manifest.bucket == bucket => manifest,
+                _ => continue,
+            };
+            uploads.push(MultipartUpload {
+                initiated: Some(manifest.initiated),
+                key: Some(manifest.key),
+                storage_class: Some("STANDARD".into()),
+                upload_id: Some(upload_id.to_owned()),
+                ..MultipartUpload::default()
+            });
+        }
+        Ok(uploads)
+    }
+
+    /// save the manifest of an uploaded part
+    async fn save_part_manifest(
+        &self,
+        upload_id: &str,
+        part_number: i64,
+        manifest: &PartManifest,
+    ) -> io::Result<()> {
+        let path = self.get_part_manifest_path(upload_id, part_number)?;
+        let content = serde_json::to_vec(manifest)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.write_atomically(&path, &content).await
+    }
+
+    /// remove the manifest of a part before the part file is replaced or removed,
+    /// so that a crash in between leaves the part unhashed instead of mislabeled
+    async fn remove_part_manifest(&self, upload_id: &str, part_number: i64) -> io::Result<()> {
+        let path = self.get_part_manifest_path(upload_id, part_number)?;
+        if path.exists() {
+            async_fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// list the uploaded parts of a multipart upload by part number
+    ///
+    /// Parts which are still being uploaded are temporary files, which are not listed.
+    async fn load_parts(&self, upload_id: &str) -> io::Result<Vec<Part>> {
+        let prefix = part_prefix(upload_id);
+        let mut parts = Vec::new();
+        let mut entries = async_fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let name = entry.file_name();
+            // the suffix of a part manifest is not a number
+            let part_number = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(prefix.as_str()))
+                .and_then(|suffix| suffix.parse::<i64>().ok());
+            let part_number = match part_number {
+                Some(part_number) => part_number,
+                None => continue,
+            };
+
+            let part_path = entry.path();
+            let metadata = entry.metadata().await?;
+            let manifest_path = self.get_part_manifest_path(upload_id, part_number)?;
+            let manifest: Option<PartManifest> = if manifest_path.exists() {
+                let content = async_fs::read(&manifest_path).await?;
+                serde_json::from_slice(&content).ok()
+            } else {
+                None
+            };
+            let (e_tag, last_modified) = match manifest {
+                Some(manifest) if manifest.size == metadata.len() => {
+                    (manifest.e_tag, manifest.last_modified)
+                }
+                _ => {
+                    let md5_sum = md5_file(&part_path).await?;
+                    let last_modified = time::to_rfc3339(metadata.modified()?);
+                    (format!("\"{}\"", md5_sum), last_modified)
+                }
+            };
+            parts.push(Part {
+                e_tag: Some(e_tag),
+                last_modified: Some(last_modified),
+                part_number: Some(part_number),
+                size: metadata.len().try_into().ok(),
+            });
+        }
+        parts.sort_by_key(|part| part.part_number);
+        Ok(parts)
+    }
+
+    /// load the metadata of the bucket at `dir`, synthesizing it from the directory if it is not recorded
+    async fn load_bucket_metadata(&self, bucket: &str, dir: &Path) -> io::Result<BucketMetadata> {
+        let path = self.get_bucket_metadata_path(bucket)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            return serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+
+        // the birth time of the directory, where the filesystem records it
+        let dir_metadata = async_fs::metadata(dir).await?;
+        let created = dir_metadata
+            .created()
+            .or_else(|_| dir_metadata.modified())?;
+        let metadata = BucketMetadata {
+            creation_date: time::to_rfc3339(created),
+            region: self.default_region.clone(),
+            object_lock_enabled: false,
+            owner_id: None,
+        };
+        self.save_bucket_metadata(bucket, &metadata).await?;
+        debug!(
+            bucket,
+            ?metadata,
+            "recorded the metadata of a bucket directory"
+        );
+        Ok(metadata)
+    }
+
+    /// record the metadata of a bucket
+    async fn save_bucket_metadata(
+        &self,
+        bucket: &str,
+        metadata: &BucketMetadata,
+    ) -> io::Result<()> {
+        let path = self.get_bucket_metadata_path(bucket)?;
+        let content = serde_json::to_vec(metadata)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.write_atomically(&path, &content).await
+    }
+
+    /// load bucket versioning from fs
+    async fn load_versioning(&self, bucket: &str) -> io::Result<BucketVersioning> {
+        let path = self.get_versioning_path(bucket)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(BucketVersioning::default())
+        }
+    }
+
+    /// load bucket CORS rules from fs
+    async fn load_cors(&self, bucket: &str) -> io::Result<Vec<BucketCorsRule>> {
+        let path = self.get_cors_path(bucket)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// load the policy document of a bucket from fs
+    async fn load_policy(&self, bucket: &str) -> io::Result<Option<String>> {
+        let path = self.get_policy_path(bucket)?;
+        if path.exists() {
+            async_fs::read_to_string(&path).await.map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// load bucket tags from fs
+    async fn load_tagging(&self, bucket: &str) -> io::Result<Vec<BucketTag>> {
+        let path = self.get_tagging_path(bucket)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// load object headers from fs
+    async fn load_object_headers(&self, bucket: &str, key: &str) -> io::Result<ObjectHeaders> {
+        let path = self.get_object_headers_path(bucket, key)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(ObjectHeaders::default())
+        }
+    }
+
+    /// save object headers, removing the stale ones if there is no header
+    async fn save_object_headers(
+        &self,
+        bucket: &str,
+        key: &str,
+        headers: &ObjectHeaders,
+    ) -> io::Result<()> {
+        let path = self.get_object_headers_path(bucket, key)?;
+        if headers.is_empty() {
+            if path.exists() {
+                async_fs::remove_file(&path).await?;
+            }
+            return Ok(());
+        }
+        let content = serde_json::to_vec(headers)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        async_fs::write(&path, &content).await
+    }
+
+    /// load the part sizes of an object, which are empty unless it is uploaded in parts
+    async fn load_part_sizes(&self, bucket: &str, key: &str) -> io::Result<Vec<u64>> {
+        let path = self.get_part_sizes_path(bucket, key)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// save the part sizes of an object, removing the stale ones if it is not uploaded in parts
+    async fn save_part_sizes(&self, bucket: &str, key: &str, part_sizes: &[u64]) -> io::Result<()> {
+        let path = self.get_part_sizes_path(bucket, key)?;
+        if part_sizes.is_empty() {
+            return remove_file_if_exists(&path).await;
+        }
+        let content = serde_json::to_vec(part_sizes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        async_fs::write(&path, &content).await
+    }
+
+    /// the bytes of an object of `size` bytes to read, which are a part or a `Range`,
+    /// with the number of parts of an object uploaded in parts
+    async fn read_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        part_number: Option<i64>,
+        range: Option<&str>,
+        size: u64,
+    ) -> S3Result<(ObjectRange, Option<i64>)> {
+        let part_number = match part_number {
+            Some(part_number) => part_number,
+            None => return Ok((object_range::byte_range(range, size)?, None)),
+        };
+        let part_sizes = trace_try!(self.load_part_sizes(bucket, key).await);
+        let range = object_range::part_range(part_number, &part_sizes, size)?;
+        let parts_count = Some(part_sizes.len())
+            .filter(|&count| count > 0)
+            .and_then(|count| count.try_into().ok());
+        Ok((range, parts_count))
+    }
+
+    /// load the ACL put on an object
+    async fn load_acl(&self, bucket: &str, key: &str) -> io::Result<Option<StoredAcl>> {
+        let path = self.get_acl_path(bucket, key)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// load the ACL put on a bucket
+    async fn load_bucket_acl(&self, bucket: &str) -> io::Result<Option<StoredAcl>> {
+        let path = self.get_bucket_acl_path(bucket)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// remove the ACL put on an object, which gets the default ACL
+    async fn remove_acl(&self, bucket: &str, key: &str) -> io::Result<()> {
+        remove_file_if_exists(&self.get_acl_path(bucket, key)?).await
+    }
+
+    /// load the retention and the legal hold put on an object
+    async fn load_object_lock(&self, bucket: &str, key: &str) -> io::Result<StoredObjectLock> {
+        let path = self.get_object_lock_path(bucket, key)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(StoredObjectLock::default())
+        }
+    }
+
+    /// save the retention and the legal hold put on an object
+    async fn save_object_lock(
+        &self,
+        bucket: &str,
+        key: &str,
+        lock: &StoredObjectLock,
+    ) -> io::Result<()> {
+        let path = self.get_object_lock_path(bucket, key)?;
+        let content =
+            serde_json::to_vec(lock).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        async_fs::write(&path, &content).await
+    }
+
+    /// remove the retention and the legal hold put on an object
+    async fn remove_object_lock(&self, bucket: &str, key: &str) -> io::Result<()> {
+        remove_file_if_exists(&self.get_object_lock_path(bucket, key)?).await
+    }
+
+    /// replace the retention and the legal hold of a written object
+    async fn replace_object_lock(
+        &self,
+        bucket: &str,
+        key: &str,
+        lock: &StoredObjectLock,
+    ) -> io::Result<()> {
+        if lock.is_empty() {
+            return self.remove_object_lock(bucket, key).await;
+        }
+        self.save_object_lock(bucket, key, lock).await
+    }
+
+    /// check that an object is not protected by its retention or its legal hold
+    async fn check_unprotected(
+        &self,
+        bucket: &str,
+        key: &str,
+        bypass_governance: bool,
+    ) -> S3Result<()> {
+        let lock = trace_try!(self.load_object_lock(bucket, key).await);
+        object_lock::check_unprotected(
+            lock.retention().as_ref(),
+            lock.legal_hold.as_deref(),
+            bypass_governance,
+            Utc::now(),
+        )
+    }
+
+    /// resolve the lock of an object written with `x-amz-object-lock-*` headers
+    async fn resolve_object_lock(
+        &self,
+        bucket: &str,
+        mode: Option<&str>,
+        retain_until_date: Option<&str>,
+        legal_hold: Option<&str>,
+    ) -> S3Result<StoredObjectLock> {
+        let config = trace_try!(self.load_lock_configuration(bucket).await);
+        let lock = object_lock::resolve_object_lock(
+            mode,
+            retain_until_date,
+            legal_hold,
+            config.as_ref(),
+            Utc::now(),
+        )?;
+        Ok(lock.into())
+    }
+
+    /// load the Object Lock configuration of a bucket, which is `None` if Object Lock is not enabled
+    async fn load_lock_configuration(
+        &self,
+        bucket: &str,
+    ) -> io::Result<Option<ObjectLockConfiguration>> {
+        let path = self.get_lock_configuration_path(bucket)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            let config: StoredLockConfiguration = serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            return Ok(Some(config.into()));
+        }
+        let metadata = self.bucket_metadata(bucket).await?;
+        let is_enabled = metadata.map_or(false, |metadata| metadata.object_lock_enabled);
+        Ok(is_enabled.then(|| ObjectLockConfiguration {
+            object_lock_enabled: Some("Enabled".to_owned()),
+            rule: None,
+        }))
+    }
+
+    /// check that an object exists in a bucket which has Object Lock enabled
+    async fn check_lockable_object(&self, bucket: &str, key: &str) -> S3Result<()> {
+        let path = trace_try!(self.get_bucket_path(bucket));
+        if !path.exists() {
+            return Err(code_error!(
+                NoSuchBucket,
+                "The specified bucket does not exist."
+            ));
+        }
+        if trace_try!(self.load_lock_configuration(bucket).await).is_none() {
+            return Err(invalid_request!(
+                "Bucket is missing Object Lock Configuration"
+            ));
+        }
+        if !trace_try!(self.object_exists(bucket, key)) {
+            return Err(code_error!(NoSuchKey, "The specified key does not exist."));
+        }
+        Ok(())
+    }
+
+    /// load the MD5 stored when the object was written
+    async fn load_checksum(&self, bucket: &str, key: &str) -> io::Result<Option<String>> {
+        let path = self.get_checksum_path(bucket, key)?;
+        if path.exists() {
+            Ok(Some(async_fs::read_to_string(&path).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// save the MD5 of a written object
+    async fn save_checksum(&self, bucket: &str, key: &str, md5_sum: &str) -> io::Result<()> {
+        let path = self.get_checksum_path(bucket, key)?;
+        async_fs::write(&path, md5_sum).await
+    }
+
+    /// remove the stored MD5 before the object file is replaced or removed,
+    /// so that a crash in between leaves the object unverified instead of corrupted
+    async fn remove_checksum(&self, bucket: &str, key: &str) -> io::Result<()> {
+        let path = self.get_checksum_path(bucket, key)?;
+        if path.exists() {
+            async_fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// load metadata from fs
+    async fn load_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> io::Result<Option<HashMap<String, String>>> {
+        let path = self.get_metadata_path(bucket, key)?;
+        if path.exists() {
+            let content = async_fs::read(&path).await?;
+            let map = serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(Some(map))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// save metadata
+    async fn save_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: &HashMap<String, String>,
+    ) -> io::Result<()> {
+        let path = self.get_metadata_path(bucket, key)?;
+        let content = serde_json::to_vec(metadata)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        async_fs::write(&path, &content).await
+    }
+
+    /// save metadata, removing the stale metadata if there is none
+    async fn replace_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: Option<&HashMap<String, String>>,
+    ) -> io::Result<()> {
+        if let Some(metadata) = metadata {
+            return self.save_metadata(bucket, key, metadata).await;
+        }
+        let path = self.get_metadata_path(bucket, key)?;
+        if path.exists() {
+            async_fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// serve `GetObject` of an inline object
+    async fn get_inline_object(
+        &self,
+        input: GetObjectRequest,
+        data: Bytes,
+        last_modified: String,
+    ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
+        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+        let object_headers = trace_try!(self.load_object_headers(&input.bucket, &input.key).await);
+
+        let size: u64 = trace_try!(data.len().try_into());
+        let (range, parts_count) = self
+            .read_range(
+                &input.bucket,
+                &input.key,
+                input.part_number,
+                input.range.as_deref(),
+                size,
+            )
+            .await?;
+        let md5_sum = inline::md5_hex(&data);
+        let start: usize = trace_try!(range.bytes.start.try_into());
+        let end: usize = trace_try!(range.bytes.end.try_into());
+        let data = data.slice(start..end);
+        let body = futures::stream::once(async move { Ok(data) });
+
+        let output: GetObjectOutput = GetObjectOutput {
+            body: Some(crate::dto::ByteStream::new(body)),
+            content_length: Some(trace_try!(range.len().try_into())),
+            content_range: range.content_range,
+            accept_ranges: Some("bytes".to_owned()),
+            parts_count,
+            last_modified: Some(last_modified),
+            metadata: object_metadata,
+            e_tag: Some(format!("\"{}\"", md5_sum)),
+            cache_control: object_headers.cache_control,
+            content_disposition: object_headers.content_disposition,
+            content_encoding: object_headers.content_encoding,
+            content_language: object_headers.content_language,
+            content_type: object_headers.content_type,
+            expires: object_headers.expires,
+            version_id: input.version_id,
+            ..GetObjectOutput::default()
+        };
+        Ok(output)
+    }
+
+    /// get md5 sum
+    async fn get_md5_sum(&self, bucket: &str, key: &str) -> io::Result<String> {
+        if let Some((data, _)) = self.get_inline(bucket, key)? {
+            return Ok(inline::md5_hex(&data));
+        }
+        let object_path = self.get_object_path(bucket, key)?;
+        md5_file(&object_path).await
+    }
+}
+
+/// the prefix of the part files of a multipart upload, which is followed by the part number
+fn part_prefix(upload_id: &str) -> String {
+    format!(".upload_id-{}.part-", upload_id)
+}
+
+/// hashes a file with MD5
+async fn md5_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut md5_hash = Md5::new();
+    let _size = copy_hashed(&mut file, &mut futures::io::sink(), &mut md5_hash).await?;
+    md5_hash.finalize().apply(crypto::to_hex_string).apply(Ok)
+}
+
+/// copies a reader to a writer, feeding the bytes to `md5_hash`
+async fn copy_hashed<R, W>(reader: &mut R, writer: &mut W, md5_hash: &mut Md5) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0; 4_usize.wrapping_mul(1024).wrapping_mul(1024)];
+    let mut size: u64 = 0;
+    loop {
+        let nread = reader.read(&mut buf).await?;
+        if nread == 0 {
+            break;
+        }
+        let bytes = buf.get(..nread).unwrap_or_else(|| {
+            panic!(
+                "nread is larger than buffer size: nread = {}, size = {}",
+                nread,
+                buf.len()
+            )
+        });
+        md5_hash.update(bytes);
+        writer.write_all(bytes).await?;
+        size = size.saturating_add(nread.try_into().unwrap_or(u64::MAX));
+    }
+    Ok(size)
+}
+
+/// copies a range of a source, which is inline or a file, to a new file, and returns its MD5
+async fn copy_range(
+    inline: Option<&[u8]>,
+    src_path: &Path,
+    range: Range<u64>,
+    dst_path: &Path,
+) -> io::Result<String> {
+    let mut md5_hash = Md5::new();
+    let mut writer = BufWriter::new(File::create(dst_path).await?);
+    let len = range.end.saturating_sub(range.start);
+    let _size = if let Some(data) = inline {
+        let start: usize = range.start.try_into().unwrap_or(usize::MAX);
+        let end: usize = range.end.try_into().unwrap_or(usize::MAX);
+        let mut bytes = data.get(start..end).unwrap_or(&[]);
+        copy_hashed(&mut bytes, &mut writer, &mut md5_hash).await?
+    } else {
+        let mut file = File::open(src_path).await?;
+        let _pos = file.seek(SeekFrom::Start(range.start)).await?;
+        copy_hashed(&mut file.take(len), &mut writer, &mut md5_hash).await?
+    };
+    writer.flush().await?;
+    md5_hash.finalize().apply(crypto::to_hex_string).apply(Ok)
+}
+
+/// streams the bytes of a range of a file
+async fn range_stream(mut file: File, range: &ObjectRange) -> io::Result<crate::dto::ByteStream> {
+    if !range.is_partial() {
+        return Ok(crate::dto::ByteStream::new(BytesStream::new(file, 4096)));
+    }
+    let _pos = file.seek(SeekFrom::Start(range.bytes.start)).await?;
+    let reader = file.take(range.len());
+    Ok(crate::dto::ByteStream::new(BytesStream::new(reader, 4096)))
+}
+
+/// default and max `max-keys` of indexed listings
+const MAX_KEYS: i64 = 1000;
+
+/// a listed object
+fn to_object(key: &str, entry: IndexEntry) -> Object {
+    Object {
+        e_tag: None,
+        key: Some(key.to_owned()),
+        last_modified: Some(entry.last_modified),
+        owner: None,
+        size: entry.size.try_into().ok(),
+        storage_class: None,
+    }
+}
+
+/// the keys to scan for a listing page
+///
+/// Without a delimiter, one more key than a page tells whether it is truncated,
+/// and with a delimiter the rolled up keys are scanned too.
+fn scan_limit(delimiter: Option<&str>, max_keys: Option<i64>) -> S3Result<usize> {
+    if delimiter.map_or(false, |d| !d.is_empty()) {
+        return Ok(usize::MAX);
+    }
+    let max_keys = max_keys.unwrap_or(MAX_KEYS).clamp(0, MAX_KEYS);
+    let limit: usize = trace_try!(max_keys.try_into());
+    Ok(limit.saturating_add(1))
+}
+
+/// list a page of objects by range scanning the index
+fn list_indexed(
+    index: &dyn ObjectIndex,
+    input: ListObjectsV2Request,
+) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+    let start_after = listing::start_after_v2(&input)?;
+    let limit = scan_limit(input.delimiter.as_deref(), input.max_keys)?;
+    let prefix = input.prefix.as_deref().unwrap_or("");
+    let entries = trace_try!(index.scan(&input.bucket, prefix, start_after.as_deref(), limit));
+    Ok(listing::list_objects_v2(input, entries, to_object)?)
+}
+
+/// the index entry of an object file
+fn index_entry(metadata: &std::fs::Metadata) -> io::Result<IndexEntry> {
+    Ok(IndexEntry {
+        size: metadata.len(),
+        last_modified: time::to_rfc3339(metadata.modified()?),
+        inline: None,
+    })
+}
+
+/// removes a file which may not exist
+async fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    match async_fs::remove_file(path).await {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// whether a directory has a file at any depth
+async fn has_files(dir: &Path) -> io::Result<bool> {
+    let mut dir_queue = VecDeque::new();
+    dir_queue.push_back(dir.to_owned());
+    while let Some(dir) = dir_queue.pop_front() {
+        let mut entries = async_fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if !entry.file_type().await?.is_dir() {
+                return Ok(true);
+            }
+            dir_queue.push_back(entry.path());
+        }
+    }
+    Ok(false)
+}
+
+/// removes the temporary file of a failed write, such as an aborted upload
+async fn remove_temp_on_error<T>(ret: io::Result<T>, temp_path: &Path) -> io::Result<T> {
+    if ret.is_err() {
+        remove_temp(temp_path).await;
+    }
+    ret
+}
+
+/// removes a temporary file if it has not been moved yet, logging a failure
+async fn remove_temp(temp_path: &Path) {
+    if let Err(err) = remove_file_if_exists(temp_path).await {
+        error!(%err, path = %temp_path.display(), "failed to remove a temporary file");
+    }
+}
+
+/// the internal error of a `PutObject` whose body fails after `bytes_persisted` bytes are written
+fn partial_upload_error(err: io::Error, bytes_persisted: u64) -> S3Error {
+    // the fs backend removes the written bytes, so there is no resume token
+    let partial = PartialUpload {
+        bytes_persisted,
+        resume_token: None,
+    };
+    let err = S3Error::from_code(S3ErrorCode::InternalError)
+        .message("We encountered an internal error. Please try again.")
+        .source(err)
+        .partial_upload(partial)
+        .capture_backtrace()
+        .capture_span_trace()
+        .finish();
+    error!("generated internal error: {}", err);
+    err
+}
+
+/// copy bytes from a stream to a writer
+async fn copy_bytes<S, W>(mut stream: S, writer: &mut W) -> io::Result<usize>
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + Unpin,
+    W: AsyncWrite + Send + Unpin,
+{
+    let mut nwrite: usize = 0;
+    while let Some(bytes) = stream.next().await {
+        let bytes = bytes?;
+
+        let amt_u64 = futures::io::copy_buf(bytes.as_ref(), writer).await?;
+        let amt: usize = amt_u64.try_into().unwrap_or_else(|err| {
+            panic!(
+                "number overflow: u64 to usize, n = {}, err = {}",
+                amt_u64, err
+            )
+        });
+
+        assert_eq!(
+            bytes.len(),
+            amt,
+            "amt mismatch: bytes.len() = {}, amt = {}, nwrite = {}",
+            bytes.len(),
+            amt,
+            nwrite
+        );
+
+        nwrite = nwrite
+            .checked_add(amt)
+            .unwrap_or_else(|| panic!("nwrite overflow: amt = {}, nwrite = {}", amt, nwrite));
+    }
+    writer.flush().await?;
+    Ok(nwrite)
+}
+
+/// wrap operation error
+const fn operation_error<E>(e: E) -> S3StorageError<E> {
+    S3StorageError::Operation(e)
+}
+
+#[async_trait]
+impl S3Storage for FileSystem {
+    #[tracing::instrument]
+    async fn create_bucket(
+        &self,
+        input: CreateBucketRequest,
+    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if path.exists() {
+            let err = CreateBucketError::BucketAlreadyExists(String::from(
+                "The requested bucket name is not available. \
+                    The bucket namespace is shared by all users of the system. \
+                    Please select a different name and try again.",
+            ));
+            return Err(operation_error(err));
+        }
+
+        trace_try!(async_fs::create_dir(&path).await);
+
+        let region = input
+            .create_bucket_configuration
+            .and_then(|config| config.location_constraint)
+            .unwrap_or_else(|| self.default_region.clone());
+        let metadata = BucketMetadata {
+            creation_date: time::to_rfc3339(SystemTime::now()),
+            region,
+            object_lock_enabled: input.object_lock_enabled_for_bucket == Some(true),
+            owner_id: bucket_metadata::owner_id(input.grant_full_control.as_deref()),
+        };
+        if let Err(err) = self.save_bucket_metadata(&input.bucket, &metadata).await {
+            // a bucket is not created without its metadata
+            if let Err(err) = async_fs::remove_dir(&path).await {
+                error!(%err, path = %path.display(), "failed to remove a bucket directory");
+            }
+            return Err(internal_error!(err).into());
+        }
+
+        let output = CreateBucketOutput::default(); // TODO: handle other fields
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn copy_object(
+        &self,
+        input: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        let copy_source = AmzCopySource::from_header_str(&input.copy_source)
+            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+
+        let (bucket, key) = match copy_source {
+            AmzCopySource::AccessPoint { .. } => {
+                return Err(
+                    code_error!(NotImplemented, "Access point is not supported yet.").into(),
+                )
+            }
+            AmzCopySource::Bucket {
+                bucket,
+                key,
+                version_id,
+            } => {
+                versions::check_null_version(version_id)?;
+                (bucket, key)
+            }
+        };
+
+        let src_path = trace_try!(self.get_object_path(bucket, key));
+        let dst_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+
+        self.check_unprotected(&input.bucket, &input.key, false)
+            .await?;
+        let lock = self
+            .resolve_object_lock(
+                &input.bucket,
+                input.object_lock_mode.as_deref(),
+                input.object_lock_retain_until_date.as_deref(),
+                input.object_lock_legal_hold_status.as_deref(),
+            )
+            .await?;
+
+        // the metadata is read before the copy, whose destination may be its source
+        let (metadata, object_headers) = if input.metadata_directive.as_deref() == Some("REPLACE") {
+            let object_headers = ObjectHeaders {
+                cache_control: input.cache_control,
+                content_disposition: input.content_disposition,
+                content_encoding: input.content_encoding,
+                content_language: input.content_language,
+                content_type: input.content_type,
+                expires: input.expires,
+            };
+            (input.metadata, object_headers)
+        } else {
+            let metadata = trace_try!(self.load_metadata(bucket, key).await);
+            let object_headers = trace_try!(self.load_object_headers(bucket, key).await);
+            (metadata, object_headers)
+        };
+
+        let temp_path = trace_try!(self.get_temp_path());
+        let last_modified =
+            if let Some((data, last_modified)) = trace_try!(self.get_inline(bucket, key)) {
+                let ret = async_fs::write(&temp_path, &data).await;
+                trace_try!(remove_temp_on_error(ret, &temp_path).await);
+                last_modified
+            } else {
+                let file_metadata = trace_try!(async_fs::metadata(&src_path).await);
+                let modified = trace_try!(file_metadata.modified());
+                let ret = async_fs::copy(&src_path, &temp_path).await;
+                let _ = trace_try!(remove_temp_on_error(ret, &temp_path).await);
+                time::to_rfc3339(modified)
+            };
+        let ret = self
+            .commit_object(&input.bucket, &input.key, &temp_path, &dst_path)
+            .await;
+        let is_inline = trace_try!(remove_temp_on_error(ret, &temp_path).await);
+
+        debug!(
+            from = %src_path.display(),
+            to = %dst_path.display(),
+            "CopyObject: copy file",
+        );
+
+        trace_try!(
+            self.replace_metadata(&input.bucket, &input.key, metadata.as_ref())
+                .await
+        );
+        trace_try!(
+            self.save_object_headers(&input.bucket, &input.key, &object_headers)
+                .await
+        );
+        trace_try!(self.save_part_sizes(&input.bucket, &input.key, &[]).await);
+        trace_try!(self.remove_acl(&input.bucket, &input.key).await);
+        trace_try!(
+            self.replace_object_lock(&input.bucket, &input.key, &lock)
+                .await
+        );
+
+        let md5_sum = trace_try!(self.get_md5_sum(&input.bucket, &input.key).await);
+        if !is_inline {
+            trace_try!(
+                self.save_checksum(&input.bucket, &input.key, &md5_sum)
+                    .await
+            );
+        }
+
+        let output = CopyObjectOutput {
+            copy_object_result: CopyObjectResult {
+                e_tag: Some(format!("\"{}\"", md5_sum)),
+                last_modified: Some(last_modified),
+            }
+            .apply(Some),
+            ..CopyObjectOutput::default()
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket(
+        &self,
+        input: DeleteBucketRequest,
+    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        // the directories of deleted objects are left empty, and inline objects are only in the index
+        let mut is_empty = !trace_try!(has_files(&path).await);
+        if let Some(ref index) = self.index {
+            is_empty = is_empty && trace_try!(index.scan(&input.bucket, "", None, 1)).is_empty();
+        }
+        if !is_empty {
+            let err = code_error!(
+                BucketNotEmpty,
+                "The bucket you tried to delete is not empty."
+            );
+            return Err(err.into());
+        }
+
+        trace_try!(async_fs::remove_dir_all(&path).await);
+        let metadata_path = trace_try!(self.get_bucket_metadata_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&metadata_path).await);
+        let tagging_path = trace_try!(self.get_tagging_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&tagging_path).await);
+        let acl_path = trace_try!(self.get_bucket_acl_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&acl_path).await);
+        let cors_path = trace_try!(self.get_cors_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&cors_path).await);
+        let policy_path = trace_try!(self.get_policy_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&policy_path).await);
+        let lock_path = trace_try!(self.get_lock_configuration_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&lock_path).await);
+        let serving_policy_path = trace_try!(self.get_serving_policy_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&serving_policy_path).await);
+        let versioning_path = trace_try!(self.get_versioning_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&versioning_path).await);
+        if let Some(ref index) = self.index {
+            trace_try!(index.remove_bucket(&input.bucket));
+        }
+        Ok(DeleteBucketOutput)
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket_cors(
+        &self,
+        input: DeleteBucketCorsRequest,
+    ) -> S3StorageResult<DeleteBucketCorsOutput, DeleteBucketCorsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let cors_path = trace_try!(self.get_cors_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&cors_path).await);
+
+        Ok(DeleteBucketCorsOutput)
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket_policy(
+        &self,
+        input: DeleteBucketPolicyRequest,
+    ) -> S3StorageResult<DeleteBucketPolicyOutput, DeleteBucketPolicyError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let policy_path = trace_try!(self.get_policy_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&policy_path).await);
+
+        Ok(DeleteBucketPolicyOutput)
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket_tagging(
+        &self,
+        input: DeleteBucketTaggingRequest,
+    ) -> S3StorageResult<DeleteBucketTaggingOutput, DeleteBucketTaggingError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let tagging_path = trace_try!(self.get_tagging_path(&input.bucket));
+        trace_try!(remove_file_if_exists(&tagging_path).await);
+
+        Ok(DeleteBucketTaggingOutput)
+    }
+
+    #[tracing::instrument]
+    async fn delete_object(
+        &self,
+        input: DeleteObjectRequest,
+    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+        if input.key.ends_with('/') {
+            let mut dir = trace_try!(async_fs::read_dir(&path).await);
+            let is_empty = dir.next().await.is_none();
+            if is_empty {
+                trace_try!(async_fs::remove_dir(&path).await);
+            }
+        } else {
+            let bypass_governance = input.bypass_governance_retention == Some(true);
+            self.check_unprotected(&input.bucket, &input.key, bypass_governance)
+                .await?;
+            trace_try!(self.remove_object(&input.bucket, &input.key).await);
+        }
+        let output = DeleteObjectOutput {
+            version_id: input.version_id,
+            ..DeleteObjectOutput::default() // TODO: handle other fields
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn delete_objects(
+        &self,
+        input: DeleteObjectsRequest,
+    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
+        // like S3, a missing key is reported as deleted
+        let bypass_governance = input.bypass_governance_retention == Some(true);
+        let now = Utc::now();
         let mut deleted: Vec<DeletedObject> = Vec::new();
-        for (path, key) in objects {
-            trace_try!(async_fs::remove_file(path).await);
+        let mut errors = Vec::new();
+        for object in input.delete.objects {
+            let path = trace_try!(self.get_object_path(&input.bucket, &object.key));
+            if path.exists() || trace_try!(self.get_inline(&input.bucket, &object.key)).is_some() {
+                let lock = trace_try!(self.load_object_lock(&input.bucket, &object.key).await);
+                let retention = lock.retention();
+                let legal_hold = lock.legal_hold.as_deref();
+                if object_lock::is_protected(retention.as_ref(), legal_hold, bypass_governance, now)
+                {
+                    errors.push(object_lock::protected_entry(object.key));
+                    continue;
+                }
+                trace_try!(self.remove_object(&input.bucket, &object.key).await);
+            }
             deleted.push(DeletedObject {
-                key: Some(key),
+                key: Some(object.key),
                 ..DeletedObject::default()
             });
         }
-        let output = DeleteObjectsOutput {
-            deleted: Some(deleted),
-            ..DeleteObjectsOutput::default()
+        let output = DeleteObjectsOutput {
+            deleted: Some(deleted),
+            errors: Some(errors),
+            ..DeleteObjectsOutput::default()
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_acl(
+        &self,
+        input: GetBucketAclRequest,
+    ) -> S3StorageResult<GetBucketAclOutput, GetBucketAclError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let acl = trace_try!(self.load_bucket_acl(&input.bucket).await);
+        if let Some(acl) = acl {
+            let policy = AccessControlPolicy::from(acl);
+            return Ok(GetBucketAclOutput {
+                grants: policy.grants,
+                owner: policy.owner,
+            });
+        }
+
+        // the default ACL of the recorded owner
+        let metadata = trace_try!(self.load_bucket_metadata(&input.bucket, &path).await);
+        let output = GetBucketAclOutput {
+            grants: None,
+            owner: metadata.owner_id.map(|id| Owner {
+                id: Some(id),
+                display_name: None,
+            }),
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_location(
+        &self,
+        input: GetBucketLocationRequest,
+    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "NotFound");
+            return Err(err.into());
+        }
+
+        let metadata = trace_try!(self.load_bucket_metadata(&input.bucket, &path).await);
+
+        // the handler serves `us-east-1` as an empty constraint
+        let output = GetBucketLocationOutput {
+            location_constraint: Some(metadata.region),
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_cors(
+        &self,
+        input: GetBucketCorsRequest,
+    ) -> S3StorageResult<GetBucketCorsOutput, GetBucketCorsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let rules = trace_try!(self.load_cors(&input.bucket).await);
+
+        let output = GetBucketCorsOutput {
+            cors_rules: Some(rules)
+                .filter(|rules| !rules.is_empty())
+                .map(|rules| rules.into_iter().map(Into::into).collect()),
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_policy(
+        &self,
+        input: GetBucketPolicyRequest,
+    ) -> S3StorageResult<GetBucketPolicyOutput, GetBucketPolicyError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let policy = trace_try!(self.load_policy(&input.bucket).await);
+
+        Ok(GetBucketPolicyOutput { policy })
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_tagging(
+        &self,
+        input: GetBucketTaggingRequest,
+    ) -> S3StorageResult<GetBucketTaggingOutput, GetBucketTaggingError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let tags = trace_try!(self.load_tagging(&input.bucket).await);
+
+        let output = GetBucketTaggingOutput {
+            tag_set: tags
+                .into_iter()
+                .map(|BucketTag { key, value }| Tag { key, value })
+                .collect(),
         };
+
         Ok(output)
     }
 
     #[tracing::instrument]
-    async fn get_bucket_location(
+    async fn get_bucket_versioning(
         &self,
-        input: GetBucketLocationRequest,
-    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
+        input: GetBucketVersioningRequest,
+    ) -> S3StorageResult<GetBucketVersioningOutput, GetBucketVersioningError> {
         let path = trace_try!(self.get_bucket_path(&input.bucket));
 
         if !path.exists() {
-            let err = code_error!(NoSuchBucket, "NotFound");
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
             return Err(err.into());
         }
 
-        let output = GetBucketLocationOutput {
-            location_constraint: None, // TODO: handle region
+        let versioning = trace_try!(self.load_versioning(&input.bucket).await);
+
+        let output = GetBucketVersioningOutput {
+            status: versioning.status,
+            mfa_delete: versioning.mfa_delete,
         };
 
         Ok(output)
     }
 
+    #[tracing::instrument]
+    async fn get_object_acl(
+        &self,
+        input: GetObjectAclRequest,
+    ) -> S3StorageResult<GetObjectAclOutput, GetObjectAclError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        if !trace_try!(self.object_exists(&input.bucket, &input.key)) {
+            let err = code_error!(NoSuchKey, "The specified key does not exist.");
+            return Err(err.into());
+        }
+
+        let acl = trace_try!(self.load_acl(&input.bucket, &input.key).await);
+        let (owner, grants) = acl
+            .map(AccessControlPolicy::from)
+            .map_or((None, None), |policy| (policy.owner, policy.grants));
+
+        Ok(GetObjectAclOutput {
+            grants,
+            owner,
+            ..GetObjectAclOutput::default()
+        })
+    }
+
+    #[tracing::instrument]
+    async fn get_object_legal_hold(
+        &self,
+        input: GetObjectLegalHoldRequest,
+    ) -> S3StorageResult<GetObjectLegalHoldOutput, GetObjectLegalHoldError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        if !trace_try!(self.object_exists(&input.bucket, &input.key)) {
+            let err = code_error!(NoSuchKey, "The specified key does not exist.");
+            return Err(err.into());
+        }
+
+        let lock = trace_try!(self.load_object_lock(&input.bucket, &input.key).await);
+
+        Ok(GetObjectLegalHoldOutput {
+            legal_hold: lock.legal_hold(),
+        })
+    }
+
+    #[tracing::instrument]
+    async fn get_object_lock_configuration(
+        &self,
+        input: GetObjectLockConfigurationRequest,
+    ) -> S3StorageResult<GetObjectLockConfigurationOutput, GetObjectLockConfigurationError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let config = trace_try!(self.load_lock_configuration(&input.bucket).await);
+
+        Ok(GetObjectLockConfigurationOutput {
+            object_lock_configuration: config,
+        })
+    }
+
+    #[tracing::instrument]
+    async fn get_object_retention(
+        &self,
+        input: GetObjectRetentionRequest,
+    ) -> S3StorageResult<GetObjectRetentionOutput, GetObjectRetentionError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        if !trace_try!(self.object_exists(&input.bucket, &input.key)) {
+            let err = code_error!(NoSuchKey, "The specified key does not exist.");
+            return Err(err.into());
+        }
+
+        let lock = trace_try!(self.load_object_lock(&input.bucket, &input.key).await);
+
+        Ok(GetObjectRetentionOutput {
+            retention: lock.retention.map(Into::into),
+        })
+    }
+
     #[tracing::instrument]
     async fn get_object(
         &self,
         input: GetObjectRequest,
     ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        if let Some((data, last_modified)) = trace_try!(self.get_inline(&input.bucket, &input.key))
+        {
+            return self.get_inline_object(input, data, last_modified).await;
+        }
+
         let object_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
 
         let file = match File::open(&object_path).await {
@@ -348,42 +2354,397 @@ impl S3Storage for FileSystem {
         let file_metadata = trace_try!(file.metadata().await);
         let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
         let content_length = file_metadata.len();
-        let stream = BytesStream::new(file, 4096);
+        let (range, parts_count) = self
+            .read_range(
+                &input.bucket,
+                &input.key,
+                input.part_number,
+                input.range.as_deref(),
+                content_length,
+            )
+            .await?;
+
+        #[cfg(feature = "mmap")]
+        let (body, content_length) = match self.mmap_limit {
+            Some(limit) if !range.is_partial() && (1..=limit).contains(&content_length) => {
+                let stream = trace_try!(MmapStream::open(&object_path, 64 * 1024));
+                // the map may refer to a newer inode than `file_metadata`
+                let content_length: u64 = trace_try!(stream.len().try_into());
+                debug!(
+                    path = %object_path.display(),
+                    size = ?content_length,
+                    "GetObject: serve memory map",
+                );
+                (crate::dto::ByteStream::new(stream), content_length)
+            }
+            _ => (trace_try!(range_stream(file, &range).await), range.len()),
+        };
+
+        #[cfg(not(feature = "mmap"))]
+        let (body, content_length) = (trace_try!(range_stream(file, &range).await), range.len());
 
         let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+        let object_headers = trace_try!(self.load_object_headers(&input.bucket, &input.key).await);
 
-        let (md5_sum, duration) = {
+        let stored_md5 = if self.verify_on_read {
+            trace_try!(self.load_checksum(&input.bucket, &input.key).await)
+        } else {
+            None
+        };
+
+        // a partial read can not be verified, since the checksum is of the whole object
+        let (body, md5_sum) = if let Some(md5_sum) = stored_md5.filter(|_| !range.is_partial()) {
+            let stream = VerifiedStream::new(
+                body,
+                md5_sum.clone(),
+                input.bucket.clone(),
+                input.key.clone(),
+                self.corruptions.clone(),
+            );
+            (crate::dto::ByteStream::new(stream), md5_sum)
+        } else {
             let (ret, duration) =
                 time::count_duration(self.get_md5_sum(&input.bucket, &input.key)).await;
             let md5_sum = trace_try!(ret);
-            (md5_sum, duration)
-        };
 
-        debug!(
-            sum = ?md5_sum,
-            path = %object_path.display(),
-            size = ?content_length,
-            ?duration,
-            "GetObject: calculate md5 sum",
-        );
+            debug!(
+                sum = ?md5_sum,
+                path = %object_path.display(),
+                size = ?content_length,
+                ?duration,
+                "GetObject: calculate md5 sum",
+            );
+            (body, md5_sum)
+        };
 
         let output: GetObjectOutput = GetObjectOutput {
-            body: Some(crate::dto::ByteStream::new(stream)),
+            body: Some(body),
             content_length: Some(trace_try!(content_length.try_into())),
+            content_range: range.content_range,
+            accept_ranges: Some("bytes".to_owned()),
+            parts_count,
             last_modified: Some(last_modified),
             metadata: object_metadata,
             e_tag: Some(format!("\"{}\"", md5_sum)),
+            cache_control: object_headers.cache_control,
+            content_disposition: object_headers.content_disposition,
+            content_encoding: object_headers.content_encoding,
+            content_language: object_headers.content_language,
+            content_type: object_headers.content_type,
+            expires: object_headers.expires,
+            version_id: input.version_id,
             ..GetObjectOutput::default() // TODO: handle other fields
         };
 
         Ok(output)
     }
 
+    #[tracing::instrument]
+    async fn get_bucket_serving_policy(&self, bucket: &str) -> S3Result<Option<ServingPolicy>> {
+        let path = trace_try!(self.get_serving_policy_path(bucket));
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = trace_try!(async_fs::read(&path).await);
+        let policy = trace_try!(serde_json::from_slice(&content));
+        Ok(Some(policy))
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_freeze(&self, bucket: &str) -> S3Result<Option<BucketFreeze>> {
+        let cached = match self.freezes.read() {
+            Ok(guard) => guard.get(bucket).copied(),
+            Err(poisoned) => poisoned.into_inner().get(bucket).copied(),
+        };
+        if let Some(freeze) = cached {
+            return Ok(freeze);
+        }
+
+        let path = trace_try!(self.get_freeze_path(bucket));
+        let freeze: Option<BucketFreeze> = if path.exists() {
+            let content = trace_try!(async_fs::read(&path).await);
+            Some(trace_try!(serde_json::from_slice(&content)))
+        } else {
+            None
+        };
+
+        let _prev = self
+            .lock_freezes()
+            .entry(bucket.to_owned())
+            .or_insert(freeze);
+        Ok(freeze)
+    }
+
     #[tracing::instrument]
     async fn head_bucket(
         &self,
-        input: HeadBucketRequest,
-    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
+        input: HeadBucketRequest,
+    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let metadata = trace_try!(self.load_bucket_metadata(&input.bucket, &path).await);
+        Ok(HeadBucketOutput {
+            bucket_region: Some(metadata.region),
+        })
+    }
+
+    #[tracing::instrument]
+    async fn head_object(
+        &self,
+        input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+
+        let (size, last_modified) = if let Some((data, last_modified)) =
+            trace_try!(self.get_inline(&input.bucket, &input.key))
+        {
+            (data.len(), last_modified)
+        } else {
+            if !path.exists() {
+                let err = code_error!(NoSuchKey, "The specified key does not exist.");
+                return Err(err.into());
+            }
+
+            let file_metadata = trace_try!(async_fs::metadata(path).await);
+            let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
+            (trace_try!(file_metadata.len().try_into()), last_modified)
+        };
+        let size: u64 = trace_try!(size.try_into());
+        let (range, parts_count) = self
+            .read_range(&input.bucket, &input.key, input.part_number, None, size)
+            .await?;
+
+        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+        let object_headers = trace_try!(self.load_object_headers(&input.bucket, &input.key).await);
+
+        // the object is only hashed for the conditions which compare its ETag
+        let e_tag = if input.if_match.is_some() || input.if_none_match.is_some() {
+            let md5_sum = trace_try!(self.get_md5_sum(&input.bucket, &input.key).await);
+            Some(format!("\"{}\"", md5_sum))
+        } else {
+            None
+        };
+
+        let output: HeadObjectOutput = HeadObjectOutput {
+            content_length: Some(trace_try!(range.len().try_into())),
+            parts_count,
+            last_modified: Some(last_modified),
+            e_tag,
+            metadata: object_metadata,
+            cache_control: object_headers.cache_control,
+            content_disposition: object_headers.content_disposition,
+            content_encoding: object_headers.content_encoding,
+            content_language: object_headers.content_language,
+            content_type: object_headers.content_type,
+            expires: object_headers.expires,
+            version_id: input.version_id,
+            ..HeadObjectOutput::default()
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn list_buckets(
+        &self,
+        _: ListBucketsRequest,
+    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
+        let mut buckets = Vec::new();
+
+        let mut iter = trace_try!(async_fs::read_dir(&self.root).await);
+        while let Some(entry) = iter.next().await {
+            let entry = trace_try!(entry);
+            let file_type = trace_try!(entry.file_type().await);
+            if file_type.is_dir() {
+                let file_name = entry.file_name();
+                let name = file_name.to_string_lossy();
+                if S3Path::check_bucket_name(&*name) {
+                    let metadata =
+                        trace_try!(self.load_bucket_metadata(&name, &entry.path()).await);
+                    buckets.push(Bucket {
+                        creation_date: Some(metadata.creation_date),
+                        name: Some(name.into()),
+                    });
+                }
+            }
+        }
+
+        let output = ListBucketsOutput {
+            buckets: Some(buckets),
+            owner: None, // TODO: handle owner
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn list_object_versions(
+        &self,
+        input: ListObjectVersionsRequest,
+    ) -> S3StorageResult<ListObjectVersionsOutput, ListObjectVersionsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        // objects are not versioned, so each key has only a null version
+        let mut keys = Vec::new();
+        for (key, entry) in trace_try!(self.list_bucket(&input.bucket, &path).await) {
+            let version = ObjectVersion {
+                last_modified: Some(entry.last_modified),
+                size: Some(trace_try!(entry.size.try_into())),
+                version_id: Some(NULL_VERSION_ID.to_owned()),
+                ..ObjectVersion::default()
+            };
+            keys.push((key, Some(ListedVersion::Version(version))));
+        }
+
+        versions::list_versions(input, keys).map_err(S3StorageError::Other)
+    }
+
+    #[tracing::instrument]
+    async fn list_objects(
+        &self,
+        input: ListObjectsRequest,
+    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let objects = match self.index {
+            Some(ref index) => {
+                let prefix = input.prefix.as_deref().unwrap_or("");
+                let marker = input.marker.as_deref();
+                let limit = scan_limit(input.delimiter.as_deref(), input.max_keys)?;
+                trace_try!(index.scan(&input.bucket, prefix, marker, limit))
+            }
+            None => trace_try!(self.list_bucket(&input.bucket, &path).await),
+        };
+        Ok(listing::list_objects(input, objects, to_object))
+    }
+
+    #[tracing::instrument]
+    async fn list_objects_v2(
+        &self,
+        input: ListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+        if let Some(ref index) = self.index {
+            return list_indexed(index.as_ref(), input);
+        }
+
+        let objects = trace_try!(self.list_bucket(&input.bucket, &path).await);
+        Ok(listing::list_objects_v2(input, objects, to_object)?)
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_acl(
+        &self,
+        input: PutBucketAclRequest,
+    ) -> S3StorageResult<PutBucketAclOutput, PutBucketAclError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let acl: StoredAcl = input.access_control_policy.unwrap_or_default().into();
+        let acl_path = trace_try!(self.get_bucket_acl_path(&input.bucket));
+        let content = trace_try!(serde_json::to_vec(&acl));
+        trace_try!(async_fs::write(&acl_path, &content).await);
+
+        Ok(PutBucketAclOutput)
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_cors(
+        &self,
+        input: PutBucketCorsRequest,
+    ) -> S3StorageResult<PutBucketCorsOutput, PutBucketCorsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let rules: Vec<BucketCorsRule> = input
+            .cors_configuration
+            .cors_rules
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        let cors_path = trace_try!(self.get_cors_path(&input.bucket));
+        let content = trace_try!(serde_json::to_vec(&rules));
+        trace_try!(async_fs::write(&cors_path, &content).await);
+
+        Ok(PutBucketCorsOutput)
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_policy(
+        &self,
+        input: PutBucketPolicyRequest,
+    ) -> S3StorageResult<PutBucketPolicyOutput, PutBucketPolicyError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let policy_path = trace_try!(self.get_policy_path(&input.bucket));
+        trace_try!(async_fs::write(&policy_path, &input.policy).await);
+
+        Ok(PutBucketPolicyOutput)
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_tagging(
+        &self,
+        input: PutBucketTaggingRequest,
+    ) -> S3StorageResult<PutBucketTaggingOutput, PutBucketTaggingError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let tags: Vec<BucketTag> = input
+            .tagging
+            .tag_set
+            .into_iter()
+            .map(|Tag { key, value }| BucketTag { key, value })
+            .collect();
+
+        let tagging_path = trace_try!(self.get_tagging_path(&input.bucket));
+        let content = trace_try!(serde_json::to_vec(&tags));
+        trace_try!(async_fs::write(&tagging_path, &content).await);
+
+        Ok(PutBucketTaggingOutput)
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_versioning(
+        &self,
+        input: PutBucketVersioningRequest,
+    ) -> S3StorageResult<PutBucketVersioningOutput, PutBucketVersioningError> {
         let path = trace_try!(self.get_bucket_path(&input.bucket));
 
         if !path.exists() {
@@ -391,199 +2752,114 @@ impl S3Storage for FileSystem {
             return Err(err.into());
         }
 
-        Ok(HeadBucketOutput)
+        let config = input.versioning_configuration;
+        if config.status.as_deref() == Some("Enabled") {
+            // objects have no versions in this storage
+            let err = code_error!(
+                NotImplemented,
+                "The storage does not support enabling bucket versioning."
+            );
+            return Err(err.into());
+        }
+
+        let mut versioning = trace_try!(self.load_versioning(&input.bucket).await);
+        if config.status.is_some() {
+            versioning.status = config.status;
+        }
+        if config.mfa_delete.is_some() {
+            versioning.mfa_delete = config.mfa_delete;
+        }
+
+        let versioning_path = trace_try!(self.get_versioning_path(&input.bucket));
+        let content = trace_try!(serde_json::to_vec(&versioning));
+        trace_try!(async_fs::write(&versioning_path, &content).await);
+
+        Ok(PutBucketVersioningOutput)
     }
 
     #[tracing::instrument]
-    async fn head_object(
+    async fn put_object_acl(
         &self,
-        input: HeadObjectRequest,
-    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
-        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
-
-        if !path.exists() {
+        input: PutObjectAclRequest,
+    ) -> S3StorageResult<PutObjectAclOutput, PutObjectAclError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        if !trace_try!(self.object_exists(&input.bucket, &input.key)) {
             let err = code_error!(NoSuchKey, "The specified key does not exist.");
             return Err(err.into());
         }
 
-        let file_metadata = trace_try!(async_fs::metadata(path).await);
-        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
-        let size = file_metadata.len();
-
-        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+        let acl: StoredAcl = input.access_control_policy.unwrap_or_default().into();
+        let acl_path = trace_try!(self.get_acl_path(&input.bucket, &input.key));
+        let content = trace_try!(serde_json::to_vec(&acl));
+        trace_try!(async_fs::write(&acl_path, &content).await);
 
-        let output: HeadObjectOutput = HeadObjectOutput {
-            content_length: Some(trace_try!(size.try_into())),
-            content_type: Some(mime::APPLICATION_OCTET_STREAM.as_ref().to_owned()), // TODO: handle content type
-            last_modified: Some(last_modified),
-            metadata: object_metadata,
-            ..HeadObjectOutput::default()
-        };
-        Ok(output)
+        Ok(PutObjectAclOutput::default())
     }
 
     #[tracing::instrument]
-    async fn list_buckets(
+    async fn put_object_legal_hold(
         &self,
-        _: ListBucketsRequest,
-    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
-        let mut buckets = Vec::new();
-
-        let mut iter = trace_try!(async_fs::read_dir(&self.root).await);
-        while let Some(entry) = iter.next().await {
-            let entry = trace_try!(entry);
-            let file_type = trace_try!(entry.file_type().await);
-            if file_type.is_dir() {
-                let file_name = entry.file_name();
-                let name = file_name.to_string_lossy();
-                if S3Path::check_bucket_name(&*name) {
-                    buckets.push(Bucket {
-                        creation_date: None,
-                        name: Some(name.into()),
-                    });
-                }
-            }
-        }
+        input: PutObjectLegalHoldRequest,
+    ) -> S3StorageResult<PutObjectLegalHoldOutput, PutObjectLegalHoldError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        self.check_lockable_object(&input.bucket, &input.key)
+            .await?;
+
+        let mut lock = trace_try!(self.load_object_lock(&input.bucket, &input.key).await);
+        lock.legal_hold = input.legal_hold.and_then(|legal_hold| legal_hold.status);
+        trace_try!(
+            self.save_object_lock(&input.bucket, &input.key, &lock)
+                .await
+        );
 
-        let output = ListBucketsOutput {
-            buckets: Some(buckets),
-            owner: None, // TODO: handle owner
-        };
-        Ok(output)
+        Ok(PutObjectLegalHoldOutput::default())
     }
 
     #[tracing::instrument]
-    async fn list_objects(
+    async fn put_object_lock_configuration(
         &self,
-        input: ListObjectsRequest,
-    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
+        input: PutObjectLockConfigurationRequest,
+    ) -> S3StorageResult<PutObjectLockConfigurationOutput, PutObjectLockConfigurationError> {
         let path = trace_try!(self.get_bucket_path(&input.bucket));
 
-        let mut objects = Vec::new();
-        let mut dir_queue = VecDeque::new();
-        dir_queue.push_back(path.clone());
-
-        while let Some(dir) = dir_queue.pop_front() {
-            let mut entries = trace_try!(async_fs::read_dir(dir).await);
-            while let Some(entry) = entries.next().await {
-                let entry = trace_try!(entry);
-                let file_type = trace_try!(entry.file_type().await);
-                if file_type.is_dir() {
-                    dir_queue.push_back(entry.path());
-                } else {
-                    let file_path = entry.path();
-                    let key = trace_try!(file_path.strip_prefix(&path));
-                    if let Some(ref prefix) = input.prefix {
-                        if !key.to_string_lossy().as_ref().starts_with(prefix) {
-                            continue;
-                        }
-                    }
-
-                    let metadata = trace_try!(entry.metadata().await);
-                    let last_modified = time::to_rfc3339(trace_try!(metadata.modified()));
-                    let size = metadata.len();
-
-                    objects.push(Object {
-                        e_tag: None,
-                        key: Some(key.to_string_lossy().into()),
-                        last_modified: Some(last_modified),
-                        owner: None,
-                        size: Some(trace_try!(size.try_into())),
-                        storage_class: None,
-                    });
-                }
-            }
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
         }
 
-        objects.sort_by(|lhs, rhs| {
-            let lhs_key = lhs.key.as_deref().unwrap_or("");
-            let rhs_key = rhs.key.as_deref().unwrap_or("");
-            lhs_key.cmp(rhs_key)
-        });
-
-        // TODO: handle other fields
-        let output = ListObjectsOutput {
-            contents: Some(objects),
-            delimiter: input.delimiter,
-            encoding_type: input.encoding_type,
-            name: Some(input.bucket),
-            common_prefixes: None,
-            is_truncated: None,
-            marker: None,
-            max_keys: None,
-            next_marker: None,
-            prefix: None,
-        };
+        if let Some(config) = input.object_lock_configuration {
+            let config = StoredLockConfiguration::from(config);
+            let lock_path = trace_try!(self.get_lock_configuration_path(&input.bucket));
+            let content = trace_try!(serde_json::to_vec(&config));
+            trace_try!(async_fs::write(&lock_path, &content).await);
+        }
 
-        Ok(output)
+        Ok(PutObjectLockConfigurationOutput::default())
     }
 
     #[tracing::instrument]
-    async fn list_objects_v2(
+    async fn put_object_retention(
         &self,
-        input: ListObjectsV2Request,
-    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
-
-        let mut objects = Vec::new();
-        let mut dir_queue = VecDeque::new();
-        dir_queue.push_back(path.clone());
-
-        while let Some(dir) = dir_queue.pop_front() {
-            let mut entries = trace_try!(async_fs::read_dir(dir).await);
-            while let Some(entry) = entries.next().await {
-                let entry = trace_try!(entry);
-                let file_type = trace_try!(entry.file_type().await);
-                if file_type.is_dir() {
-                    dir_queue.push_back(entry.path());
-                } else {
-                    let file_path = entry.path();
-                    let key = trace_try!(file_path.strip_prefix(&path));
-                    if let Some(ref prefix) = input.prefix {
-                        if !key.to_string_lossy().as_ref().starts_with(prefix) {
-                            continue;
-                        }
-                    }
-
-                    let metadata = trace_try!(entry.metadata().await);
-                    let last_modified = time::to_rfc3339(trace_try!(metadata.modified()));
-                    let size = metadata.len();
-
-                    objects.push(Object {
-                        e_tag: None,
-                        key: Some(key.to_string_lossy().into()),
-                        last_modified: Some(last_modified),
-                        owner: None,
-                        size: Some(trace_try!(size.try_into())),
-                        storage_class: None,
-                    });
-                }
-            }
-        }
-
-        objects.sort_by(|lhs, rhs| {
-            let lhs_key = lhs.key.as_deref().unwrap_or("");
-            let rhs_key = rhs.key.as_deref().unwrap_or("");
-            lhs_key.cmp(rhs_key)
-        });
-
-        // TODO: handle other fields
-        let output = ListObjectsV2Output {
-            key_count: Some(trace_try!(objects.len().try_into())),
-            contents: Some(objects),
-            delimiter: input.delimiter,
-            encoding_type: input.encoding_type,
-            name: Some(input.bucket),
-            common_prefixes: None,
-            is_truncated: None,
-            max_keys: None,
-            prefix: None,
-            continuation_token: None,
-            next_continuation_token: None,
-            start_after: None,
-        };
+        input: PutObjectRetentionRequest,
+    ) -> S3StorageResult<PutObjectRetentionOutput, PutObjectRetentionError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        self.check_lockable_object(&input.bucket, &input.key)
+            .await?;
+
+        let mut lock = trace_try!(self.load_object_lock(&input.bucket, &input.key).await);
+        object_lock::check_retention_change(
+            lock.retention().as_ref(),
+            input.retention.as_ref(),
+            input.bypass_governance_retention == Some(true),
+            Utc::now(),
+        )?;
+        lock.retention = input.retention.map(Into::into);
+        trace_try!(
+            self.save_object_lock(&input.bucket, &input.key, &lock)
+                .await
+        );
 
-        Ok(output)
+        Ok(PutObjectRetentionOutput::default())
     }
 
     #[tracing::instrument]
@@ -608,9 +2884,27 @@ impl S3Storage for FileSystem {
             key,
             metadata,
             content_length,
+            cache_control,
+            content_disposition,
+            content_encoding,
+            content_language,
+            content_type,
+            expires,
+            object_lock_mode,
+            object_lock_retain_until_date,
+            object_lock_legal_hold_status,
             ..
         } = input;
 
+        let object_headers = ObjectHeaders {
+            cache_control,
+            content_disposition,
+            content_encoding,
+            content_language,
+            content_type,
+            expires,
+        };
+
         let body = body.ok_or_else(||{
             code_error!(IncompleteBody,"You did not provide the number of bytes specified by the Content-Length HTTP header.")
         })?;
@@ -629,32 +2923,102 @@ impl S3Storage for FileSystem {
             return Err(err.into());
         }
 
-        let object_path = trace_try!(self.get_object_path(&bucket, &key));
-        if let Some(dir_path) = object_path.parent() {
-            trace_try!(async_fs::create_dir_all(&dir_path).await);
-        }
+        self.check_unprotected(&bucket, &key, false).await?;
+        let lock = self
+            .resolve_object_lock(
+                &bucket,
+                object_lock_mode.as_deref(),
+                object_lock_retain_until_date.as_deref(),
+                object_lock_legal_hold_status.as_deref(),
+            )
+            .await?;
 
-        let mut md5_hash = Md5::new();
-        let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
+        let object_path = trace_try!(self.get_object_path(&bucket, &key));
+        let declared_size: Option<u64> = content_length.and_then(|size| size.try_into().ok());
+
+        let (md5_sum, is_inline) = if declared_size.map_or(false, |size| self.is_inline_size(size))
+        {
+            // a small object is collected instead of being written to a temporary file
+            let collect = body.try_fold(Vec::new(), |mut data, bytes| {
+                data.extend_from_slice(&bytes);
+                futures::future::ready(Ok(data))
+            });
+            let (ret, duration) = time::count_duration(collect).await;
+            let data = ret.map_err(|err| partial_upload_error(err, 0))?;
+            let md5_sum = inline::md5_hex(&data);
+            let size = data.len();
+
+            let is_inline = if self.is_inline_size(size.try_into().unwrap_or(u64::MAX)) {
+                let _last_modified = trace_try!(self.put_inline(&bucket, &key, data.into()).await);
+                true
+            } else {
+                // the body is longer than declared
+                let temp_path = trace_try!(self.get_temp_path());
+                let ret = async_fs::write(&temp_path, &data).await;
+                trace_try!(remove_temp_on_error(ret, &temp_path).await);
+                let ret = self
+                    .commit_object(&bucket, &key, &temp_path, &object_path)
+                    .await;
+                trace_try!(remove_temp_on_error(ret, &temp_path).await)
+            };
+
+            debug!(?size, ?duration, %md5_sum, is_inline, "PutObject: collect body");
+            (md5_sum, is_inline)
+        } else {
+            let mut md5_hash = Md5::new();
+            let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
+
+            let temp_path = trace_try!(self.get_temp_path());
+            let file = trace_try!(File::create(&temp_path).await);
+            let mut writer = BufWriter::new(file);
+
+            let (ret, duration) = time::count_duration(copy_bytes(stream, &mut writer)).await;
+            let size = match ret {
+                Ok(size) => size,
+                Err(err) => {
+                    // the buffered bytes are flushed, so the file length is the persisted offset
+                    let _flushed = writer.flush().await;
+                    drop(writer);
+                    let persisted = async_fs::metadata(&temp_path)
+                        .await
+                        .map_or(0, |metadata| metadata.len());
+                    remove_temp(&temp_path).await;
+                    return Err(partial_upload_error(err, persisted).into());
+                }
+            };
+            drop(writer);
+            let md5_sum = md5_hash.finalize().apply(crypto::to_hex_string);
 
-        let file = trace_try!(File::create(&object_path).await);
-        let mut writer = BufWriter::new(file);
+            let ret = self
+                .commit_object(&bucket, &key, &temp_path, &object_path)
+                .await;
+            let is_inline = trace_try!(remove_temp_on_error(ret, &temp_path).await);
 
-        let (ret, duration) = time::count_duration(copy_bytes(stream, &mut writer)).await;
-        let size = trace_try!(ret);
-        let md5_sum = md5_hash.finalize().apply(crypto::to_hex_string);
+            debug!(
+                path = %object_path.display(),
+                ?size,
+                ?duration,
+                %md5_sum,
+                is_inline,
+                "PutObject: write file",
+            );
+            (md5_sum, is_inline)
+        };
+        if !is_inline {
+            trace_try!(self.save_checksum(&bucket, &key, &md5_sum).await);
+        }
 
-        debug!(
-            path = %object_path.display(),
-            ?size,
-            ?duration,
-            %md5_sum,
-            "PutObject: write file",
+        trace_try!(
+            self.replace_metadata(&bucket, &key, metadata.as_ref())
+                .await
         );
-
-        if let Some(ref metadata) = metadata {
-            trace_try!(self.save_metadata(&bucket, &key, metadata).await);
-        }
+        trace_try!(
+            self.save_object_headers(&bucket, &key, &object_headers)
+                .await
+        );
+        trace_try!(self.save_part_sizes(&bucket, &key, &[]).await);
+        trace_try!(self.remove_acl(&bucket, &key).await);
+        trace_try!(self.replace_object_lock(&bucket, &key, &lock).await);
 
         let output = PutObjectOutput {
             e_tag: Some(format!("\"{}\"", md5_sum)),
@@ -664,12 +3028,58 @@ impl S3Storage for FileSystem {
         Ok(output)
     }
 
+    #[tracing::instrument]
+    async fn restore_object(
+        &self,
+        input: RestoreObjectRequest,
+    ) -> S3StorageResult<RestoreObjectOutput, RestoreObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        if !trace_try!(self.object_exists(&input.bucket, &input.key)) {
+            let err = code_error!(NoSuchKey, "The specified key does not exist.");
+            return Err(err.into());
+        }
+
+        // objects are never archived, so there is nothing to restore
+        let err = code_error!(
+            InvalidObjectState,
+            "Restore is not allowed for the object's current storage class"
+        );
+        Err(err.into())
+    }
+
     #[tracing::instrument]
     async fn create_multipart_upload(
         &self,
         input: CreateMultipartUploadRequest,
     ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError> {
         let upload_id = Uuid::new_v4().to_string();
+        let lock = self
+            .resolve_object_lock(
+                &input.bucket,
+                input.object_lock_mode.as_deref(),
+                input.object_lock_retain_until_date.as_deref(),
+                input.object_lock_legal_hold_status.as_deref(),
+            )
+            .await?;
+
+        let manifest = UploadManifest {
+            bucket: input.bucket.clone(),
+            key: input.key.clone(),
+            initiated: time::to_rfc3339(SystemTime::now()),
+            metadata: input.metadata,
+            headers: ObjectHeaders {
+                cache_control: input.cache_control,
+                content_disposition: input.content_disposition,
+                content_encoding: input.content_encoding,
+                content_language: input.content_language,
+                content_type: input.content_type,
+                expires: input.expires,
+            },
+            lock,
+        };
+        let content = trace_try!(serde_json::to_vec(&manifest));
+        let upload_path = trace_try!(self.get_upload_path(&upload_id));
+        trace_try!(self.write_atomically(&upload_path, &content).await);
 
         let output = CreateMultipartUploadOutput {
             bucket: Some(input.bucket),
@@ -697,21 +3107,26 @@ impl S3Storage for FileSystem {
             code_error!(IncompleteBody, "You did not provide the number of bytes specified by the Content-Length HTTP header.")
         })?;
 
-        let file_path_str = format!(".upload_id-{}.part-{}", upload_id, part_number);
-        let file_path = trace_try!(Path::new(&file_path_str).absolutize_virtually(&self.root));
+        let part_path = trace_try!(self.get_part_path(&upload_id, part_number));
 
         let mut md5_hash = Md5::new();
         let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
 
-        let file = trace_try!(File::create(&file_path).await);
+        // the part is written aside, so that it is never listed half-written
+        let temp_path = trace_try!(self.get_temp_path());
+        let file = trace_try!(File::create(&temp_path).await);
         let mut writer = BufWriter::new(file);
 
         let (ret, duration) = time::count_duration(copy_bytes(stream, &mut writer)).await;
-        let size = trace_try!(ret);
+        drop(writer);
+        let size = trace_try!(remove_temp_on_error(ret, &temp_path).await);
         let md5_sum = md5_hash.finalize().apply(crypto::to_hex_string);
 
+        trace_try!(self.remove_part_manifest(&upload_id, part_number).await);
+        trace_try!(async_fs::rename(&temp_path, &part_path).await);
+
         debug!(
-            path = %file_path.display(),
+            path = %part_path.display(),
             ?size,
             ?duration,
             %md5_sum,
@@ -719,6 +3134,15 @@ impl S3Storage for FileSystem {
         );
 
         let e_tag = format!("\"{}\"", md5_sum);
+        let manifest = PartManifest {
+            e_tag: e_tag.clone(),
+            size: trace_try!(size.try_into()),
+            last_modified: time::to_rfc3339(SystemTime::now()),
+        };
+        trace_try!(
+            self.save_part_manifest(&upload_id, part_number, &manifest)
+                .await
+        );
 
         let output = UploadPartOutput {
             e_tag: Some(e_tag),
@@ -728,6 +3152,111 @@ impl S3Storage for FileSystem {
         Ok(output)
     }
 
+    #[tracing::instrument]
+    async fn upload_part_copy(
+        &self,
+        input: UploadPartCopyRequest,
+    ) -> S3StorageResult<UploadPartCopyOutput, UploadPartCopyError> {
+        let copy_source = AmzCopySource::from_header_str(&input.copy_source)
+            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+
+        let (bucket, key) = match copy_source {
+            AmzCopySource::AccessPoint { .. } => {
+                return Err(
+                    code_error!(NotImplemented, "Access point is not supported yet.").into(),
+                )
+            }
+            AmzCopySource::Bucket {
+                bucket,
+                key,
+                version_id,
+            } => {
+                versions::check_null_version(version_id)?;
+                (bucket, key)
+            }
+        };
+
+        let manifest = trace_try!(self.load_upload(&input.upload_id).await);
+        if !manifest.map_or(false, |manifest| {
+            manifest.bucket == input.bucket && manifest.key == input.key
+        }) {
+            let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+            return Err(err.into());
+        }
+
+        let src_path = trace_try!(self.get_object_path(bucket, key));
+        let inline = trace_try!(self.get_inline(bucket, key));
+        let (size, last_modified) = match inline {
+            Some((ref data, ref last_modified)) => {
+                (trace_try!(data.len().try_into()), last_modified.clone())
+            }
+            None => match async_fs::metadata(&src_path).await {
+                Ok(file_metadata) if file_metadata.is_file() => {
+                    let modified = trace_try!(file_metadata.modified());
+                    (file_metadata.len(), time::to_rfc3339(modified))
+                }
+                _ => {
+                    let err = code_error!(NoSuchKey, "The specified key does not exist.");
+                    return Err(err.into());
+                }
+            },
+        };
+
+        let conditions = CopySourceConditions::from_upload_part_copy(&input);
+        let e_tag = if conditions.needs_e_tag() {
+            let md5_sum = trace_try!(self.get_md5_sum(bucket, key).await);
+            Some(format!("\"{}\"", md5_sum))
+        } else {
+            None
+        };
+        conditions.check(e_tag.as_deref(), &last_modified)?;
+        let range = copy_source::byte_range(input.copy_source_range.as_deref(), size)?;
+
+        // the part is written aside, so that it is never listed half-written
+        let part_path = trace_try!(self.get_part_path(&input.upload_id, input.part_number));
+        let temp_path = trace_try!(self.get_temp_path());
+        let data = inline.as_ref().map(|&(ref data, _)| data.as_ref());
+        let ret = copy_range(data, &src_path, range.clone(), &temp_path).await;
+        let md5_sum = trace_try!(remove_temp_on_error(ret, &temp_path).await);
+
+        trace_try!(
+            self.remove_part_manifest(&input.upload_id, input.part_number)
+                .await
+        );
+        trace_try!(async_fs::rename(&temp_path, &part_path).await);
+
+        debug!(
+            from = %src_path.display(),
+            to = %part_path.display(),
+            ?range,
+            %md5_sum,
+            "UploadPartCopy: copy file",
+        );
+
+        let e_tag = format!("\"{}\"", md5_sum);
+        let last_modified = time::to_rfc3339(SystemTime::now());
+        let manifest = PartManifest {
+            e_tag: e_tag.clone(),
+            size: range.end.saturating_sub(range.start),
+            last_modified: last_modified.clone(),
+        };
+        trace_try!(
+            self.save_part_manifest(&input.upload_id, input.part_number, &manifest)
+                .await
+        );
+
+        let output = UploadPartCopyOutput {
+            copy_part_result: CopyPartResult {
+                e_tag: Some(e_tag),
+                last_modified: Some(last_modified),
+            }
+            .apply(Some),
+            ..UploadPartCopyOutput::default()
+        };
+
+        Ok(output)
+    }
+
     #[tracing::instrument]
     async fn complete_multipart_upload(
         &self,
@@ -741,19 +3270,20 @@ impl S3Storage for FileSystem {
             ..
         } = input;
 
-        let multipart_upload = if let Some(multipart_upload) = multipart_upload {
-            multipart_upload
-        } else {
-            let err = code_error!(InvalidPart, "Missing multipart_upload");
+        let parts = multipart_upload
+            .and_then(|multipart_upload| multipart_upload.parts)
+            .unwrap_or_default();
+        if parts.is_empty() {
+            let err = code_error!(
+                MalformedXML,
+                "The XML you provided was not well-formed or did not validate against our published schema."
+            );
             return Err(err.into());
-        };
-
-        let object_path = trace_try!(self.get_object_path(&bucket, &key));
-        let file = trace_try!(File::create(&object_path).await);
-        let mut writer = BufWriter::new(file);
+        }
 
         let mut cnt: i64 = 0;
-        for part in multipart_upload.parts.into_iter().flatten() {
+        let mut part_numbers = Vec::with_capacity(parts.len());
+        for part in &parts {
             let part_number = trace_try!(part
                 .part_number
                 .ok_or_else(|| { io::Error::new(io::ErrorKind::NotFound, "Missing part_number") }));
@@ -764,26 +3294,79 @@ impl S3Storage for FileSystem {
                     "InvalidPartOrder"
                 )));
             }
-            let part_path_str = format!(".upload_id-{}.part-{}", upload_id, part_number);
-            let part_path = trace_try!(Path::new(&part_path_str).absolutize_virtually(&self.root));
+            part_numbers.push(part_number);
+        }
 
-            let mut reader = trace_try!(File::open(&part_path).await);
-            let (ret, duration) =
-                time::count_duration(futures::io::copy(&mut reader, &mut writer)).await;
-            let size = trace_try!(ret);
+        let object_path = trace_try!(self.get_object_path(&bucket, &key));
+        // the metadata given when the upload was created
+        let manifest = trace_try!(self.load_upload(&upload_id).await)
+            .filter(|manifest| manifest.bucket == bucket && manifest.key == key);
+        self.check_unprotected(&bucket, &key, false).await?;
+
+        // the parts are kept until the object is in place, so that a failed call can be repeated
+        let stitch_path = trace_try!(self.get_stitch_path(&upload_id));
+        let journal_path = trace_try!(self.get_stitch_journal_path(&upload_id));
+        let fingerprint = stitch::fingerprint(&parts);
+        let mut journal = trace_try!(Journal::open(journal_path, fingerprint, &part_numbers).await);
+        let file = trace_try!(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&stitch_path)
+                .await
+        );
+        if trace_try!(file.metadata().await).len() < journal.bytes() {
+            trace_try!(journal.reset().await);
+        }
+        trace_try!(file.set_len(journal.bytes()).await);
+        let mut writer = BufWriter::new(file);
+        let _pos = trace_try!(writer.seek(SeekFrom::Start(journal.bytes())).await);
+        if journal.consumed() > 0 {
+            debug!(
+                consumed = journal.consumed(),
+                bytes = journal.bytes(),
+                "CompleteMultipartUpload: resume",
+            );
+        }
+
+        for &part_number in part_numbers.iter().skip(journal.consumed()) {
+            let part_path = trace_try!(self.get_part_path(&upload_id, part_number));
+
+            let offset = journal.bytes();
+            let (ret, duration) = time::count_duration(self.append_part(
+                &mut writer,
+                &part_path,
+                part_number,
+                offset,
+            ))
+            .await;
+            let (size, md5) = trace_try!(ret);
+            trace_try!(writer.flush().await);
+            trace_try!(journal.append(part_number, size, &md5).await);
 
             debug!(
                 from = %part_path.display(),
-                to = %object_path.display(),
+                to = %stitch_path.display(),
                 ?size,
                 ?duration,
                 "CompleteMultipartUpload: write file",
             );
-            trace_try!(async_fs::remove_file(&part_path).await);
         }
         drop(writer);
+        let part_md5_sums = trace_try!(journal.md5_sums());
+        let part_sizes = journal.part_sizes();
+        let file_size = journal.bytes();
+        let is_inline = trace_try!(
+            self.commit_object(&bucket, &key, &stitch_path, &object_path)
+                .await
+        );
+        trace_try!(journal.remove().await);
 
-        let file_size = trace_try!(async_fs::metadata(&object_path).await).len();
+        for &part_number in &part_numbers {
+            trace_try!(self.remove_part_manifest(&upload_id, part_number).await);
+            let part_path = trace_try!(self.get_part_path(&upload_id, part_number));
+            trace_try!(async_fs::remove_file(&part_path).await);
+        }
 
         let (md5_sum, duration) = {
             let (ret, duration) = time::count_duration(self.get_md5_sum(&bucket, &key)).await;
@@ -799,13 +3382,140 @@ impl S3Storage for FileSystem {
             "CompleteMultipartUpload: calculate md5 sum",
         );
 
-        let e_tag = format!("\"{}\"", md5_sum);
+        // the checksum verifies the content, while the `ETag` is derived from the parts
+        if !is_inline {
+            trace_try!(self.save_checksum(&bucket, &key, &md5_sum).await);
+        }
+        let (metadata, object_headers, lock) = manifest
+            .map(|manifest| (manifest.metadata, manifest.headers, manifest.lock))
+            .unwrap_or_default();
+        trace_try!(
+            self.replace_metadata(&bucket, &key, metadata.as_ref())
+                .await
+        );
+        trace_try!(
+            self.save_object_headers(&bucket, &key, &object_headers)
+                .await
+        );
+        trace_try!(self.save_part_sizes(&bucket, &key, &part_sizes).await);
+        trace_try!(self.remove_acl(&bucket, &key).await);
+        trace_try!(self.replace_object_lock(&bucket, &key, &lock).await);
+
+        let upload_path = trace_try!(self.get_upload_path(&upload_id));
+        if upload_path.exists() {
+            trace_try!(async_fs::remove_file(&upload_path).await);
+        }
+
         let output = CompleteMultipartUploadOutput {
             bucket: Some(bucket),
             key: Some(key),
-            e_tag: Some(e_tag),
+            e_tag: Some(crypto::multipart_e_tag(&part_md5_sums)),
             ..CompleteMultipartUploadOutput::default()
         };
         Ok(output)
     }
+
+    #[tracing::instrument]
+    async fn list_parts(
+        &self,
+        input: ListPartsRequest,
+    ) -> S3StorageResult<ListPartsOutput, ListPartsError> {
+        let manifest = trace_try!(self.load_upload(&input.upload_id).await);
+        let manifest = match manifest {
+            Some(manifest) if manifest.bucket == input.bucket && manifest.key == input.key => {
+                manifest
+            }
+            _ => {
+                let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+                return Err(err.into());
+            }
+        };
+        debug!(initiated = %manifest.initiated, "ListParts: load upload");
+
+        let parts = trace_try!(self.load_parts(&input.upload_id).await);
+
+        let output = ListPartsOutput {
+            bucket: Some(input.bucket),
+            key: Some(input.key),
+            upload_id: Some(input.upload_id),
+            parts: Some(parts),
+            storage_class: Some("STANDARD".into()),
+            ..ListPartsOutput::default()
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn list_multipart_uploads(
+        &self,
+        input: ListMultipartUploadsRequest,
+    ) -> S3StorageResult<ListMultipartUploadsOutput, ListMultipartUploadsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let uploads = trace_try!(self.load_uploads(&input.bucket).await);
+        debug!(
+            uploads = uploads.len(),
+            "ListMultipartUploads: load uploads"
+        );
+        Ok(uploads::list_uploads(input, uploads))
+    }
+
+    #[tracing::instrument]
+    async fn abort_multipart_upload(
+        &self,
+        input: AbortMultipartUploadRequest,
+    ) -> S3StorageResult<AbortMultipartUploadOutput, AbortMultipartUploadError> {
+        let AbortMultipartUploadRequest {
+            bucket,
+            key,
+            upload_id,
+            ..
+        } = input;
+
+        let manifest = trace_try!(self.load_upload(&upload_id).await);
+        if !manifest.map_or(false, |manifest| {
+            manifest.bucket == bucket && manifest.key == key
+        }) {
+            let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+            return Err(err.into());
+        }
+
+        let parts = trace_try!(self.load_parts(&upload_id).await);
+        for part_number in parts.iter().filter_map(|part| part.part_number) {
+            trace_try!(self.remove_part_manifest(&upload_id, part_number).await);
+            let part_path = trace_try!(self.get_part_path(&upload_id, part_number));
+            trace_try!(remove_file_if_exists(&part_path).await);
+        }
+
+        // the leftovers of a failed completion
+        let stitch_path = trace_try!(self.get_stitch_path(&upload_id));
+        trace_try!(remove_file_if_exists(&stitch_path).await);
+        let journal_path = trace_try!(self.get_stitch_journal_path(&upload_id));
+        trace_try!(remove_file_if_exists(&journal_path).await);
+
+        // the upload is removed last, so that a failed call can be repeated
+        let upload_path = trace_try!(self.get_upload_path(&upload_id));
+        trace_try!(remove_file_if_exists(&upload_path).await);
+        debug!(parts = parts.len(), "AbortMultipartUpload: remove upload");
+
+        Ok(AbortMultipartUploadOutput::default())
+    }
+
+    fn storage_config(&self) -> StorageConfig {
+        let config = StorageConfig::new("fs")
+            .option("root", &self.root)
+            .option("index", self.index.is_some())
+            .option("verify_on_read", self.verify_on_read)
+            .option("key_mapper", self.key_mapper.name())
+            .option("default_region", &self.default_region)
+            .option("capabilities", &self.capabilities);
+        #[cfg(feature = "mmap")]
+        let config = config.option("mmap_limit", self.mmap_limit);
+        config
+    }
 }
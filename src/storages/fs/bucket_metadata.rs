@@ -0,0 +1,40 @@
+//! Metadata recorded when a bucket is created
+//!
+//! The timestamps of a bucket directory change when the root is restored from an archive or copied,
+//! so the creation date, region, object lock flag and owner of a bucket are recorded in a file under the root.
+//! A bucket directory without the file, such as one created by an older version or by another process,
+//! gets a file synthesized from the directory when it is first used.
+
+use crate::headers::{AmzGrant, AmzGrantee};
+
+use serde::{Deserialize, Serialize};
+
+/// `us-east-1`, the default region of buckets
+pub const DEFAULT_REGION: &str = "us-east-1";
+
+/// The metadata of a bucket, see [`FileSystem::bucket_metadata`](super::FileSystem::bucket_metadata)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketMetadata {
+    /// rfc3339 time of the creation
+    pub creation_date: String,
+    /// the region of the bucket
+    pub region: String,
+    /// whether object lock is enabled for the bucket
+    #[serde(default)]
+    pub object_lock_enabled: bool,
+    /// the canonical user id of the owner, if it is known
+    #[serde(default)]
+    pub owner_id: Option<String>,
+}
+
+/// the owner of a new bucket, who gets `FULL_CONTROL` by its `x-amz-grant-full-control`
+pub(super) fn owner_id(grant_full_control: Option<&str>) -> Option<String> {
+    let grant = AmzGrant::from_header_str(grant_full_control?).ok()?;
+    grant
+        .grantees
+        .into_iter()
+        .find_map(|grantee| match grantee {
+            AmzGrantee::Id(id) => Some(id.to_owned()),
+            AmzGrantee::Uri(_) | AmzGrantee::EmailAddress(_) => None,
+        })
+}
@@ -0,0 +1,543 @@
+//! Ordered object index of the fs storage
+//!
+//! Listings walk the bucket directories unless an index is set by [`FileSystem::set_index`](super::FileSystem::set_index).
+//! An index serves `ListObjectsV2` by range scans, so a page costs the same in a bucket of any size.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use hyper::body::Bytes;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// An indexed object
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// object size
+    pub size: u64,
+    /// rfc3339 modification time of the object file, or of the write of an inline object
+    pub last_modified: String,
+    /// the data of an object which is stored in the index instead of a file,
+    /// see [`FileSystem::set_inline_threshold`](super::FileSystem::set_inline_threshold)
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "inline_data")]
+    pub inline: Option<Bytes>,
+}
+
+/// serializes inline data as base64
+mod inline_data {
+    use hyper::body::Bytes;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// serialize the data
+    #[allow(clippy::ref_option)] // the signature of `serialize_with`
+    pub(super) fn serialize<S: Serializer>(
+        data: &Option<Bytes>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match *data {
+            Some(ref data) => serializer.serialize_some(&base64::encode(data)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// deserialize the data
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Bytes>, D::Error> {
+        let encoded: Option<String> = Option::deserialize(deserializer)?;
+        encoded
+            .map(|encoded| {
+                base64::decode(encoded)
+                    .map(Bytes::from)
+                    .map_err(D::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+/// An indexed object with its location
+pub type IndexRecord = (String, String, IndexEntry);
+
+/// An ordered index of object keys
+///
+/// The fs storage updates it after each object write and delete.
+/// The object file is changed first, so a failure in between leaves the index behind,
+/// which is reported by [`FileSystem::check_index`](super::FileSystem::check_index)
+/// and repaired by [`FileSystem::reindex`](super::FileSystem::reindex).
+pub trait ObjectIndex: Debug + Send + Sync + 'static {
+    /// Inserts or replaces an object
+    /// # Errors
+    /// Returns an `Err` if the index can not be updated
+    fn insert(&self, bucket: &str, key: &str, entry: IndexEntry) -> io::Result<()>;
+
+    /// Removes an object
+    /// # Errors
+    /// Returns an `Err` if the index can not be updated
+    fn remove(&self, bucket: &str, key: &str) -> io::Result<()>;
+
+    /// Removes all objects of a bucket
+    /// # Errors
+    /// Returns an `Err` if the index can not be updated
+    fn remove_bucket(&self, bucket: &str) -> io::Result<()>;
+
+    /// Returns an object
+    ///
+    /// The default implementation scans from `key`.
+    /// # Errors
+    /// Returns an `Err` if the index can not be read
+    fn get(&self, bucket: &str, key: &str) -> io::Result<Option<IndexEntry>> {
+        let mut objects = self.scan(bucket, key, None, 1)?;
+        Ok(objects
+            .pop()
+            .filter(|&(ref found, _)| found == key)
+            .map(|(_, entry)| entry))
+    }
+
+    /// Returns at most `limit` objects of `bucket` in key order,
+    /// whose keys start with `prefix` and are greater than `start_after`
+    /// # Errors
+    /// Returns an `Err` if the index can not be read
+    fn scan(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> io::Result<Vec<(String, IndexEntry)>>;
+
+    /// Returns all objects, ordered by bucket and key
+    /// # Errors
+    /// Returns an `Err` if the index can not be read
+    fn snapshot(&self) -> io::Result<Vec<IndexRecord>>;
+
+    /// Replaces all objects
+    /// # Errors
+    /// Returns an `Err` if the index can not be updated
+    fn replace_all(&self, records: Vec<IndexRecord>) -> io::Result<()>;
+}
+
+/// A difference between the index and the filesystem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexMismatch {
+    /// the object file is not indexed
+    Missing {
+        /// bucket name
+        bucket: String,
+        /// object key
+        key: String,
+    },
+    /// the indexed object has no file
+    Stale {
+        /// bucket name
+        bucket: String,
+        /// object key
+        key: String,
+    },
+    /// the size or the modification time differs
+    Changed {
+        /// bucket name
+        bucket: String,
+        /// object key
+        key: String,
+    },
+}
+
+impl Display for IndexMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Missing {
+                ref bucket,
+                ref key,
+            } => write!(f, "missing: {}/{}", bucket, key),
+            Self::Stale {
+                ref bucket,
+                ref key,
+            } => write!(f, "stale: {}/{}", bucket, key),
+            Self::Changed {
+                ref bucket,
+                ref key,
+            } => write!(f, "changed: {}/{}", bucket, key),
+        }
+    }
+}
+
+/// A record of the log file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogRecord {
+    /// insert or replace an object
+    Put {
+        /// bucket name
+        bucket: String,
+        /// object key
+        key: String,
+        /// indexed object
+        #[serde(flatten)]
+        entry: IndexEntry,
+    },
+    /// remove an object
+    Delete {
+        /// bucket name
+        bucket: String,
+        /// object key
+        key: String,
+    },
+    /// remove a bucket
+    DeleteBucket {
+        /// bucket name
+        bucket: String,
+    },
+}
+
+/// objects by bucket and key
+type Buckets = BTreeMap<String, BTreeMap<String, IndexEntry>>;
+
+/// An [`ObjectIndex`] kept in memory and persisted as an append-only log
+///
+/// Each update appends one JSON line to the log file, which is flushed to the OS but not synced.
+/// Opening the log replays it, and rewrites it as a sorted snapshot once most records are obsolete.
+pub struct LogIndex {
+    /// log file path
+    path: PathBuf,
+    /// objects and the log file
+    state: RwLock<LogState>,
+}
+
+/// state of a [`LogIndex`]
+struct LogState {
+    /// indexed objects
+    buckets: Buckets,
+    /// log file opened for appending
+    file: File,
+}
+
+impl Debug for LogIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LogIndex {{ path: {:?} }}", self.path)
+    }
+}
+
+/// log files with fewer records are never compacted
+const MIN_COMPACTION: usize = 1024;
+
+impl LogIndex {
+    /// Opens the log at `path`, creating an empty index if it does not exist
+    ///
+    /// A torn last line, which is left by a crash during an append, is dropped.
+    /// # Errors
+    /// Returns an `Err` if the log can not be read or is corrupted
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let mut buckets = Buckets::new();
+        let mut records = 0_usize;
+        let mut is_torn = false;
+
+        if path.exists() {
+            let mut lines = BufReader::new(File::open(&path)?).lines().peekable();
+            while let Some(line) = lines.next() {
+                let line = line?;
+                match serde_json::from_str(&line) {
+                    Ok(record) => apply(&mut buckets, record),
+                    Err(err) if lines.peek().is_none() => {
+                        warn!(%err, path = %path.display(), "dropped a torn record of the index log");
+                        is_torn = true;
+                    }
+                    Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+                }
+                records = records.wrapping_add(1);
+            }
+        }
+
+        let live = count(&buckets);
+        // appending after a torn line would corrupt the next record
+        if is_torn || records > live.saturating_mul(2).max(MIN_COMPACTION) {
+            write_snapshot(&path, &buckets)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let state = LogState { buckets, file };
+        Ok(Self {
+            path,
+            state: RwLock::new(state),
+        })
+    }
+
+    /// Rewrites the log as a sorted snapshot of the objects
+    /// # Errors
+    /// Returns an `Err` if the log can not be written
+    pub fn compact(&self) -> io::Result<()> {
+        self.rewrite(&mut self.write_state())
+    }
+
+    /// write the snapshot and reopen the log
+    fn rewrite(&self, state: &mut LogState) -> io::Result<()> {
+        write_snapshot(&self.path, &state.buckets)?;
+        state.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+
+    /// lock the state for reading
+    fn read_state(&self) -> RwLockReadGuard<'_, LogState> {
+        match self.state.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// lock the state for writing
+    fn write_state(&self) -> RwLockWriteGuard<'_, LogState> {
+        match self.state.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// append a record, then apply it
+    fn append(&self, record: LogRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let mut state = self.write_state();
+        state.file.write_all(&line)?;
+        apply(&mut state.buckets, record);
+        drop(state);
+        Ok(())
+    }
+}
+
+/// apply a record to the objects
+fn apply(buckets: &mut Buckets, record: LogRecord) {
+    match record {
+        LogRecord::Put { bucket, key, entry } => {
+            let _prev = buckets.entry(bucket).or_default().insert(key, entry);
+        }
+        LogRecord::Delete { bucket, key } => {
+            if let Some(objects) = buckets.get_mut(&bucket) {
+                let _prev = objects.remove(&key);
+                if objects.is_empty() {
+                    let _prev = buckets.remove(&bucket);
+                }
+            }
+        }
+        LogRecord::DeleteBucket { bucket } => {
+            let _prev = buckets.remove(&bucket);
+        }
+    }
+}
+
+/// count the objects
+fn count(buckets: &Buckets) -> usize {
+    buckets.values().map(BTreeMap::len).sum()
+}
+
+/// replace the log at `path` by sorted `Put` records
+fn write_snapshot(path: &Path, buckets: &Buckets) -> io::Result<()> {
+    let temp_path = path.with_extension("compacting");
+    let mut writer = io::BufWriter::new(File::create(&temp_path)?);
+    for (bucket, objects) in buckets {
+        for (key, entry) in objects {
+            let record = LogRecord::Put {
+                bucket: bucket.clone(),
+                key: key.clone(),
+                entry: entry.clone(),
+            };
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    let file = writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?;
+    file.sync_data()?;
+    fs::rename(&temp_path, path)
+}
+
+impl ObjectIndex for LogIndex {
+    fn insert(&self, bucket: &str, key: &str, entry: IndexEntry) -> io::Result<()> {
+        self.append(LogRecord::Put {
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            entry,
+        })
+    }
+
+    fn remove(&self, bucket: &str, key: &str) -> io::Result<()> {
+        self.append(LogRecord::Delete {
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+        })
+    }
+
+    fn remove_bucket(&self, bucket: &str) -> io::Result<()> {
+        self.append(LogRecord::DeleteBucket {
+            bucket: bucket.to_owned(),
+        })
+    }
+
+    fn get(&self, bucket: &str, key: &str) -> io::Result<Option<IndexEntry>> {
+        let entry = self
+            .read_state()
+            .buckets
+            .get(bucket)
+            .and_then(|objects| objects.get(key))
+            .cloned();
+        Ok(entry)
+    }
+
+    fn scan(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> io::Result<Vec<(String, IndexEntry)>> {
+        let lower = match start_after {
+            Some(start_after) if start_after >= prefix => Bound::Excluded(start_after),
+            _ => Bound::Included(prefix),
+        };
+        let objects = self
+            .read_state()
+            .buckets
+            .get(bucket)
+            .map_or_else(Vec::new, |objects| {
+                objects
+                    .range::<str, _>((lower, Bound::Unbounded))
+                    .take_while(|&(key, _)| key.starts_with(prefix))
+                    .take(limit)
+                    .map(|(key, entry)| (key.clone(), entry.clone()))
+                    .collect()
+            });
+        Ok(objects)
+    }
+
+    fn snapshot(&self) -> io::Result<Vec<IndexRecord>> {
+        let records = self
+            .read_state()
+            .buckets
+            .iter()
+            .flat_map(|(bucket, objects)| {
+                objects
+                    .iter()
+                    .map(move |(key, entry)| (bucket.clone(), key.clone(), entry.clone()))
+            })
+            .collect();
+        Ok(records)
+    }
+
+    fn replace_all(&self, records: Vec<IndexRecord>) -> io::Result<()> {
+        let mut buckets = Buckets::new();
+        for (bucket, key, entry) in records {
+            let _prev = buckets.entry(bucket).or_default().insert(key, entry);
+        }
+        let mut state = self.write_state();
+        state.buckets = buckets;
+        let ret = self.rewrite(&mut state);
+        drop(state);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64) -> IndexEntry {
+        IndexEntry {
+            size,
+            last_modified: "2021-05-01T00:00:00+00:00".into(),
+            inline: None,
+        }
+    }
+
+    fn keys(objects: &[(String, IndexEntry)]) -> Vec<&str> {
+        objects.iter().map(|(key, _)| key.as_str()).collect()
+    }
+
+    #[test]
+    fn log_index() {
+        let path = std::env::temp_dir().join(format!("s3-index-{}.log", std::process::id()));
+        let _err = fs::remove_file(&path);
+
+        let index = LogIndex::open(&path).unwrap();
+        for key in &["b/2", "a", "b/1", "c", "b/3"] {
+            index.insert("asd", key, entry(1)).unwrap();
+        }
+        index.insert("qwe", "a", entry(2)).unwrap();
+        index.insert("asd", "c", entry(3)).unwrap();
+        index.remove("asd", "b/3").unwrap();
+
+        let page = index.scan("asd", "", None, 2).unwrap();
+        assert_eq!(keys(&page), ["a", "b/1"]);
+        let page = index.scan("asd", "", Some("b/1"), 10).unwrap();
+        assert_eq!(keys(&page), ["b/2", "c"]);
+        assert_eq!(page[1].1, entry(3));
+        let page = index.scan("asd", "b/", None, 10).unwrap();
+        assert_eq!(keys(&page), ["b/1", "b/2"]);
+        let page = index.scan("asd", "b/", Some("a"), 10).unwrap();
+        assert_eq!(keys(&page), ["b/1", "b/2"]);
+        assert!(index.scan("zxc", "", None, 10).unwrap().is_empty());
+
+        // the log is replayed, and a torn line is dropped
+        drop(index);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"op\":\"put\",\"buck").unwrap();
+        drop(file);
+        let index = LogIndex::open(&path).unwrap();
+        index.insert("asd", "d", entry(1)).unwrap();
+        index.remove("asd", "d").unwrap();
+        drop(index);
+        let index = LogIndex::open(&path).unwrap();
+        let snapshot = index.snapshot().unwrap();
+        assert_eq!(snapshot.len(), 5);
+        assert_eq!(snapshot[4], ("qwe".into(), "a".into(), entry(2)));
+
+        index.remove_bucket("qwe").unwrap();
+        index.compact().unwrap();
+        drop(index);
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 4);
+
+        let index = LogIndex::open(&path).unwrap();
+        index
+            .replace_all(vec![("zxc".into(), "k".into(), entry(4))])
+            .unwrap();
+        drop(index);
+        let index = LogIndex::open(&path).unwrap();
+        assert_eq!(
+            index.snapshot().unwrap(),
+            [("zxc".into(), "k".into(), entry(4))]
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn inline_entry() {
+        let path = std::env::temp_dir().join(format!("s3-index-inline-{}.log", std::process::id()));
+        let _err = fs::remove_file(&path);
+
+        let index = LogIndex::open(&path).unwrap();
+        let inline = IndexEntry {
+            inline: Some(Bytes::from_static(b"\x00inline\xff")),
+            ..entry(8)
+        };
+        index.insert("asd", "a", inline.clone()).unwrap();
+        index.insert("asd", "ab", entry(1)).unwrap();
+        drop(index);
+
+        // the data survives the log, and files have no data field
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.lines().nth(1).unwrap().contains("inline"));
+        let index = LogIndex::open(&path).unwrap();
+        assert_eq!(index.get("asd", "a").unwrap(), Some(inline));
+        assert_eq!(index.get("asd", "b").unwrap(), None);
+        assert_eq!(index.get("qwe", "a").unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
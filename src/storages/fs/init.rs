@@ -0,0 +1,304 @@
+//! Startup validation of the root
+
+use crate::path::S3Path;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+/// An error which can be returned by [`FileSystem::open`](super::FileSystem::open)
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FsInitError {
+    /// The root does not exist
+    #[error("The root {} does not exist", .root.display())]
+    NotFound {
+        /// root path
+        root: PathBuf,
+    },
+    /// The root is not a directory
+    #[error("The root {} is not a directory", .root.display())]
+    NotADirectory {
+        /// root path
+        root: PathBuf,
+    },
+    /// A probe file can not be created or removed under the root
+    #[error("The root {} is not writable: {}", .root.display(), .source)]
+    NotWritable {
+        /// root path
+        root: PathBuf,
+        /// the error of the probe
+        source: io::Error,
+    },
+    /// Renaming a file over another does not replace it
+    #[error(
+        "The filesystem of the root {} ({}) does not support atomic renames: {}",
+        .root.display(),
+        .filesystem.as_deref().unwrap_or("unknown"),
+        .source
+    )]
+    UnsupportedFilesystem {
+        /// root path
+        root: PathBuf,
+        /// the filesystem type, if it is detected
+        filesystem: Option<String>,
+        /// the error of the probe
+        source: io::Error,
+    },
+    /// The root can not be resolved
+    #[error("The root {} can not be resolved: {}", .root.display(), .source)]
+    Io {
+        /// root path
+        root: PathBuf,
+        /// the error
+        source: io::Error,
+    },
+}
+
+/// Capabilities of the filesystem of the root, detected once by [`FileSystem::open`](super::FileSystem::open)
+///
+/// A storage constructed by [`FileSystem::new`](super::FileSystem::new) has none of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct FsCapabilities {
+    /// the filesystem type from `/proc/self/mountinfo` (Linux only), such as `ext4`
+    pub filesystem: Option<String>,
+    /// whether the filesystem type supports reflinks (btrfs, xfs, bcachefs or ocfs2)
+    ///
+    /// It is derived from the type: an xfs formatted without reflinks is reported too.
+    pub reflink: bool,
+    /// whether unnamed temporary files can be created under the root with `O_TMPFILE` (Linux only)
+    pub o_tmpfile: bool,
+}
+
+/// A directory under the root which is not a valid bucket name
+///
+/// It is not listed by `ListBuckets` and its objects are not served.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBucketDir {
+    /// the directory name
+    pub name: String,
+}
+
+/// Validates `root` and detects its capabilities
+pub(super) fn probe(root: &Path) -> Result<(PathBuf, FsCapabilities), FsInitError> {
+    let root = env::current_dir()
+        .map(|cwd| cwd.join(root))
+        .map_err(|source| FsInitError::Io {
+            root: root.to_owned(),
+            source,
+        })?;
+
+    match fs::metadata(&root) {
+        Ok(meta) if meta.is_dir() => {}
+        Ok(_) => return Err(FsInitError::NotADirectory { root }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(FsInitError::NotFound { root })
+        }
+        Err(source) => return Err(FsInitError::Io { root, source }),
+    }
+    let root = match root.canonicalize() {
+        Ok(root) => root,
+        Err(source) => return Err(FsInitError::Io { root, source }),
+    };
+
+    let filesystem = filesystem_type(&root);
+    let src = root.join(format!(".tmp-probe-{}", Uuid::new_v4()));
+    let dst = root.join(format!(".tmp-probe-{}", Uuid::new_v4()));
+
+    let written = fs::write(&src, b"src").and_then(|()| fs::write(&dst, b"dst"));
+    if let Err(source) = written {
+        let _err = fs::remove_file(&src);
+        return Err(FsInitError::NotWritable { root, source });
+    }
+
+    // objects are replaced by renaming temporary files over them
+    let replaced = fs::rename(&src, &dst).and_then(|()| match fs::read(&dst)? {
+        ref content if content == b"src" => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the renamed file does not replace the destination",
+        )),
+    });
+    if let Err(source) = replaced {
+        let _err = fs::remove_file(&src);
+        let _err = fs::remove_file(&dst);
+        return Err(FsInitError::UnsupportedFilesystem {
+            root,
+            filesystem,
+            source,
+        });
+    }
+    if let Err(source) = fs::remove_file(&dst) {
+        return Err(FsInitError::NotWritable { root, source });
+    }
+
+    let capabilities = FsCapabilities {
+        reflink: filesystem.as_deref().map_or(false, |fs_type| {
+            matches!(fs_type, "btrfs" | "xfs" | "bcachefs" | "ocfs2")
+        }),
+        o_tmpfile: probe_o_tmpfile(&root),
+        filesystem,
+    };
+    Ok((root, capabilities))
+}
+
+/// Lists the directories under `root` which are not valid bucket names
+pub(super) fn invalid_bucket_dirs(root: &Path) -> io::Result<Vec<InvalidBucketDir>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !S3Path::check_bucket_name(&name) {
+            dirs.push(InvalidBucketDir { name });
+        }
+    }
+    dirs.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+    Ok(dirs)
+}
+
+/// finds the filesystem type of the mount which contains `path`
+fn filesystem_type(path: &Path) -> Option<String> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    parse_mountinfo(&mountinfo, path)
+}
+
+/// finds the filesystem type of the longest mount point which contains `path`
+///
+/// A line looks like `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw`,
+/// where the fifth field is the mount point and the type follows the separator.
+/// Later mounts on the same point shadow earlier ones.
+fn parse_mountinfo(mountinfo: &str, path: &Path) -> Option<String> {
+    let mut found: Option<(usize, &str)> = None;
+    for line in mountinfo.lines() {
+        let mut fields = line.split(' ');
+        let mount_point = match fields.nth(4) {
+            Some(mount_point) => unescape_mount_point(mount_point),
+            None => continue,
+        };
+        let fs_type = match fields.skip_while(|&field| field != "-").nth(1) {
+            Some(fs_type) => fs_type,
+            None => continue,
+        };
+        if !path.starts_with(&mount_point) {
+            continue;
+        }
+        let depth = Path::new(&mount_point).components().count();
+        if found.map_or(true, |(max_depth, _)| depth >= max_depth) {
+            found = Some((depth, fs_type));
+        }
+    }
+    found.map(|(_, fs_type)| fs_type.to_owned())
+}
+
+/// decodes the octal escapes (`\040`) of a mount point
+fn unescape_mount_point(field: &str) -> String {
+    let mut ans = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        ans.push_str(rest.get(..pos).unwrap_or(""));
+        let escape = rest.get(pos.saturating_add(1)..pos.saturating_add(4));
+        if let Some(byte) = escape.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            ans.push(char::from(byte));
+            rest = rest.get(pos.saturating_add(4)..).unwrap_or("");
+        } else {
+            ans.push('\\');
+            rest = rest.get(pos.saturating_add(1)..).unwrap_or("");
+        }
+    }
+    ans.push_str(rest);
+    ans
+}
+
+/// checks whether an unnamed temporary file can be created under `root`
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86", target_arch = "x86_64", target_arch = "riscv64")
+))]
+fn probe_o_tmpfile(root: &Path) -> bool {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    /// `__O_TMPFILE | O_DIRECTORY` of the generic Linux ABI, which arm does not follow
+    const O_TMPFILE: i32 = 0o2020_0000;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(O_TMPFILE)
+        .open(root)
+        .is_ok()
+}
+
+/// checks whether an unnamed temporary file can be created under `root`
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86", target_arch = "x86_64", target_arch = "riscv64")
+)))]
+fn probe_o_tmpfile(_: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        let root = env::temp_dir().join(format!("s3-fs-init-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn validation() {
+        let root = temp_root();
+
+        let (canonical, _) = probe(&root).unwrap();
+        assert_eq!(canonical, root.canonicalize().unwrap());
+        // the probe files are removed
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+
+        match probe(&root.join("missing")) {
+            Err(FsInitError::NotFound { .. }) => {}
+            ret => panic!("expected NotFound: {:?}", ret),
+        }
+
+        let file = root.join("file");
+        fs::write(&file, b"").unwrap();
+        match probe(&file) {
+            Err(FsInitError::NotADirectory { .. }) => {}
+            ret => panic!("expected NotADirectory: {:?}", ret),
+        }
+
+        for name in &["asd", "Capital", ".hidden", "qwe.zxc"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+        }
+        let dirs = invalid_bucket_dirs(&root).unwrap();
+        let names: Vec<&str> = dirs.iter().map(|dir| dir.name.as_str()).collect();
+        assert_eq!(names, [".hidden", "Capital"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn mountinfo() {
+        let mountinfo = "\
+            22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n\
+            23 22 0:5 / /data rw shared:2 - xfs /dev/sdb1 rw\n\
+            24 22 0:6 / /data\\040dir rw - btrfs /dev/sdc1 rw\n\
+            25 23 0:7 / /data/tmp rw - tmpfs tmpfs rw\n\
+            26 23 0:8 / /data/tmp rw - overlay overlay rw\n";
+        let fs_type = |path: &str| parse_mountinfo(mountinfo, Path::new(path));
+        assert_eq!(fs_type("/srv").as_deref(), Some("ext4"));
+        assert_eq!(fs_type("/data/s3").as_deref(), Some("xfs"));
+        assert_eq!(fs_type("/data dir/s3").as_deref(), Some("btrfs"));
+        assert_eq!(fs_type("/database").as_deref(), Some("ext4"));
+        assert_eq!(fs_type("/data/tmp/s3").as_deref(), Some("overlay"));
+        assert_eq!(parse_mountinfo("", Path::new("/")), None);
+    }
+}
@@ -0,0 +1,29 @@
+//! Small objects stored in the object index
+//!
+//! An object smaller than the inline threshold has no object file:
+//! its data is kept in its [`IndexEntry`](super::index::IndexEntry),
+//! which saves the file creation, rename and open of each write and read.
+//! Its metadata, headers and multipart parts are stored as usual.
+
+use crate::utils::crypto;
+
+use md5::{Digest, Md5};
+
+/// 16 KiB, a threshold which keeps the index small while covering most small objects
+pub const DEFAULT_INLINE_THRESHOLD: u64 = 16 * 1024;
+
+/// The objects moved by [`FileSystem::migrate_inline`](super::FileSystem::migrate_inline)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InlineMigration {
+    /// object files which are stored inline now
+    pub inlined: usize,
+    /// inline objects which are written as files now
+    pub exhumed: usize,
+    /// objects skipped because they changed during the migration
+    pub skipped: usize,
+}
+
+/// hex MD5 of inline data
+pub(super) fn md5_hex(data: &[u8]) -> String {
+    crypto::to_hex_string(Md5::digest(data))
+}
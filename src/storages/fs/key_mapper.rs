@@ -0,0 +1,115 @@
+//! Object key layouts of the fs storage
+//!
+//! An object is stored at `<bucket>/<key>` unless a [`KeyMapper`] is set by
+//! [`FileSystem::set_key_mapper`](super::FileSystem::set_key_mapper),
+//! which lets the storage serve a directory tree written by another program.
+//! Only object files are mapped: metadata, headers and checksums stay named by the S3 key.
+
+use crate::utils::crypto;
+
+use std::fmt::Debug;
+
+use md5::{Digest, Md5};
+
+/// A bidirectional mapping between S3 keys and object paths relative to the bucket directory
+///
+/// `to_s3(&to_backend(key))` must return `Some(key)` for every key.
+pub trait KeyMapper: Debug + Send + Sync + 'static {
+    /// Returns the path of an object, relative to its bucket directory
+    fn to_backend(&self, key: &str) -> String;
+
+    /// Returns the S3 key of an object file,
+    /// or `None` if the file is not an object of this layout, which hides it from listings
+    fn to_s3(&self, backend_key: &str) -> Option<String>;
+
+    /// Whether the mapping keeps the order of keys.
+    ///
+    /// Listings sort the files by path, and sort them again by S3 key unless the order is kept.
+    fn is_order_preserving(&self) -> bool {
+        false
+    }
+
+    /// The name shown by the storage config
+    fn name(&self) -> &'static str;
+}
+
+/// The default layout, which stores an object at its key
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityKeyMapper;
+
+impl KeyMapper for IdentityKeyMapper {
+    fn to_backend(&self, key: &str) -> String {
+        key.to_owned()
+    }
+
+    fn to_s3(&self, backend_key: &str) -> Option<String> {
+        Some(backend_key.to_owned())
+    }
+
+    fn is_order_preserving(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &'static str {
+        "identity"
+    }
+}
+
+/// A layout which spreads objects over two levels of directories
+///
+/// An object is stored at `<h[0..2]>/<h[2..4]>/<key>`, where `h` is the hex MD5 of the key,
+/// so that no directory holds too many entries.
+/// A file whose directories do not match the hash of the rest of its path is not an object.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShardedKeyMapper;
+
+impl ShardedKeyMapper {
+    /// the two directory names of a key
+    fn shards(key: &str) -> (String, String) {
+        let hash = crypto::to_hex_string(Md5::digest(key.as_bytes()));
+        let first = hash.get(..2).unwrap_or_default().to_owned();
+        let second = hash.get(2..4).unwrap_or_default().to_owned();
+        (first, second)
+    }
+}
+
+impl KeyMapper for ShardedKeyMapper {
+    fn to_backend(&self, key: &str) -> String {
+        let (first, second) = Self::shards(key);
+        format!("{}/{}/{}", first, second, key)
+    }
+
+    fn to_s3(&self, backend_key: &str) -> Option<String> {
+        let mut iter = backend_key.splitn(3, '/');
+        let (first, second, key) = (iter.next()?, iter.next()?, iter.next()?);
+        let (expected_first, expected_second) = Self::shards(key);
+        (first == expected_first && second == expected_second).then(|| key.to_owned())
+    }
+
+    fn name(&self) -> &'static str {
+        "sharded"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sharded() {
+        let mapper = ShardedKeyMapper;
+        for key in &["a", "photos/2021/1.jpg", "dir/", "\u{4e2d}\u{6587}.txt"] {
+            let backend_key = mapper.to_backend(key);
+            assert_eq!(backend_key.len(), key.len() + 6, "{}", backend_key);
+            assert!(backend_key.ends_with(key), "{}", backend_key);
+            assert_eq!(mapper.to_s3(&backend_key).as_deref(), Some(*key));
+        }
+
+        // md5("a") = 0cc175b9c0f1b6a831c399e269772661
+        assert_eq!(mapper.to_backend("a"), "0c/c1/a");
+        assert_eq!(mapper.to_s3("a"), None);
+        assert_eq!(mapper.to_s3("0c/c1"), None);
+        assert_eq!(mapper.to_s3("0c/c2/a"), None);
+        assert_eq!(mapper.to_s3("00/00/a"), None);
+    }
+}
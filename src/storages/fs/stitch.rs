@@ -0,0 +1,167 @@
+//! Progress journal of `CompleteMultipartUpload`
+//!
+//! The parts are appended to a stitch file which is kept between calls.
+//! After each part is flushed, a line is appended to the journal,
+//! so a repeated call with the same part list continues after the last recorded part.
+
+use crate::dto::CompletedPart;
+use crate::utils::crypto;
+
+use std::io;
+use std::path::PathBuf;
+
+use async_fs::OpenOptions;
+use futures::io::AsyncWriteExt;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+/// the first line of a journal
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalHeader {
+    /// fingerprint of the part list
+    parts: String,
+}
+
+/// a line of a journal, written after a part is flushed to the stitch file
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    /// part number
+    part_number: i64,
+    /// bytes of the part
+    size: u64,
+    /// hex MD5 of the part
+    md5: String,
+}
+
+/// The progress of stitching an upload
+#[derive(Debug)]
+pub(super) struct Journal {
+    /// journal path
+    path: PathBuf,
+    /// fingerprint of the part list
+    fingerprint: String,
+    /// appended parts
+    entries: Vec<JournalEntry>,
+}
+
+/// identifies a part list by the numbers and `ETag`s of its parts
+pub(super) fn fingerprint(parts: &[CompletedPart]) -> String {
+    let mut md5_hash = Md5::new();
+    for part in parts {
+        let part_number = part.part_number.unwrap_or_default();
+        let e_tag = part.e_tag.as_deref().unwrap_or_default();
+        md5_hash.update(format!("{}:{}\n", part_number, e_tag));
+    }
+    crypto::to_hex_string(md5_hash.finalize())
+}
+
+impl Journal {
+    /// Loads the journal at `path` if it records a prefix of `part_numbers` with `fingerprint`,
+    /// or starts a new one.
+    ///
+    /// A torn last line, written by a call which was killed, is ignored.
+    pub(super) async fn open(
+        path: PathBuf,
+        fingerprint: String,
+        part_numbers: &[i64],
+    ) -> io::Result<Self> {
+        let mut journal = Self {
+            path,
+            fingerprint,
+            entries: Vec::new(),
+        };
+        let content = match async_fs::read_to_string(&journal.path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut lines = content.lines();
+        let is_same_list = lines
+            .next()
+            .and_then(|line| serde_json::from_str::<JournalHeader>(line).ok())
+            .map_or(false, |header| header.parts == journal.fingerprint);
+        if is_same_list {
+            for (line, &part_number) in lines.zip(part_numbers) {
+                match serde_json::from_str::<JournalEntry>(line) {
+                    Ok(entry) if entry.part_number == part_number => journal.entries.push(entry),
+                    _ => break,
+                }
+            }
+        }
+
+        if journal.entries.is_empty() {
+            journal.reset().await?;
+        }
+        Ok(journal)
+    }
+
+    /// forgets the appended parts
+    pub(super) async fn reset(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        let header = JournalHeader {
+            parts: self.fingerprint.clone(),
+        };
+        let mut line = serde_json::to_string(&header)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        line.push('\n');
+        async_fs::write(&self.path, line).await
+    }
+
+    /// records a part which is flushed to the stitch file
+    pub(super) async fn append(
+        &mut self,
+        part_number: i64,
+        size: u64,
+        md5: &[u8],
+    ) -> io::Result<()> {
+        let entry = JournalEntry {
+            part_number,
+            size,
+            md5: crypto::to_hex_string(md5),
+        };
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        line.push('\n');
+        let mut file = OpenOptions::new().append(true).open(&self.path).await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// the number of appended parts
+    pub(super) fn consumed(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// the bytes of the appended parts
+    pub(super) fn bytes(&self) -> u64 {
+        self.entries
+            .iter()
+            .fold(0, |bytes, entry| bytes.saturating_add(entry.size))
+    }
+
+    /// the bytes of each appended part
+    pub(super) fn part_sizes(&self) -> Vec<u64> {
+        self.entries.iter().map(|entry| entry.size).collect()
+    }
+
+    /// the MD5 of the appended parts
+    pub(super) fn md5_sums(&self) -> io::Result<Vec<Vec<u8>>> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let mut md5 = vec![0; entry.md5.len().wrapping_div(2)];
+                faster_hex::hex_decode(entry.md5.as_bytes(), &mut md5)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                Ok(md5)
+            })
+            .collect()
+    }
+
+    /// removes the journal
+    pub(super) async fn remove(self) -> io::Result<()> {
+        async_fs::remove_file(&self.path).await
+    }
+}
@@ -0,0 +1,94 @@
+//! ACLs put on objects and buckets
+//!
+//! The policy of `PutStoredAcl` is stored in a file next to the headers of the object,
+//! which is removed when the object is written again or deleted, so that a new object has the default ACL.
+//! The policy of `PutBucketAcl` is stored next to the metadata of the bucket, and removed with the bucket.
+
+use crate::dto::{AccessControlPolicy, Grant, Grantee, Owner};
+
+use serde::{Deserialize, Serialize};
+
+/// The ACL stored with an object or a bucket
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct StoredAcl {
+    /// `Owner`
+    owner: Option<StoredOwner>,
+    /// `Grant`
+    grants: Vec<StoredGrant>,
+}
+
+/// `Owner`
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredOwner {
+    /// `ID`
+    id: Option<String>,
+    /// `DisplayName`
+    display_name: Option<String>,
+}
+
+/// `Grant`, with its grantee
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredGrant {
+    /// `xsi:type` of the grantee
+    grantee_type: String,
+    /// `ID`
+    id: Option<String>,
+    /// `DisplayName`
+    display_name: Option<String>,
+    /// `EmailAddress`
+    email_address: Option<String>,
+    /// `URI`
+    uri: Option<String>,
+    /// `Permission`
+    permission: Option<String>,
+}
+
+impl From<AccessControlPolicy> for StoredAcl {
+    fn from(policy: AccessControlPolicy) -> Self {
+        let grants = policy.grants.unwrap_or_default().into_iter();
+        Self {
+            owner: policy
+                .owner
+                .map(|Owner { id, display_name }| StoredOwner { id, display_name }),
+            grants: grants
+                .filter_map(
+                    |Grant {
+                         grantee,
+                         permission,
+                     }| {
+                        let grantee = grantee?;
+                        Some(StoredGrant {
+                            grantee_type: grantee.type_,
+                            id: grantee.id,
+                            display_name: grantee.display_name,
+                            email_address: grantee.email_address,
+                            uri: grantee.uri,
+                            permission,
+                        })
+                    },
+                )
+                .collect(),
+        }
+    }
+}
+
+impl From<StoredAcl> for AccessControlPolicy {
+    fn from(acl: StoredAcl) -> Self {
+        let grants = acl.grants.into_iter().map(|grant| Grant {
+            grantee: Some(Grantee {
+                display_name: grant.display_name,
+                email_address: grant.email_address,
+                id: grant.id,
+                type_: grant.grantee_type,
+                uri: grant.uri,
+            }),
+            permission: grant.permission,
+        });
+        Self {
+            grants: Some(grants.collect()),
+            owner: acl
+                .owner
+                .map(|StoredOwner { id, display_name }| Owner { id, display_name }),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Object Lock configurations of buckets, and retentions and legal holds of objects
+//!
+//! The retention and the legal hold of an object are stored in a file next to the headers of the object,
+//! which is replaced by the lock of the object written again, and removed with the object.
+//! The configuration of `PutObjectLockConfiguration` is stored next to the metadata of the bucket,
+//! and removed with the bucket. A bucket created with Object Lock and without a stored configuration
+//! has Object Lock enabled without a default retention.
+
+use crate::dto::{
+    DefaultRetention, ObjectLockConfiguration, ObjectLockLegalHold, ObjectLockRetention,
+    ObjectLockRule,
+};
+
+use crate::storages::object_lock::ObjectLock;
+
+use serde::{Deserialize, Serialize};
+
+/// The Object Lock configuration stored with a bucket
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct StoredLockConfiguration {
+    /// `DefaultRetention` of the rule
+    default_retention: Option<StoredDefaultRetention>,
+}
+
+/// `DefaultRetention`
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredDefaultRetention {
+    /// `Mode`
+    mode: Option<String>,
+    /// `Days`
+    days: Option<i64>,
+    /// `Years`
+    years: Option<i64>,
+}
+
+/// The retention and the legal hold stored with an object
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct StoredObjectLock {
+    /// `Retention`
+    pub(super) retention: Option<StoredRetention>,
+    /// `Status` of `LegalHold`
+    pub(super) legal_hold: Option<String>,
+}
+
+/// `Retention`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct StoredRetention {
+    /// `Mode`
+    mode: Option<String>,
+    /// rfc3339 `RetainUntilDate`
+    retain_until_date: Option<String>,
+}
+
+impl From<ObjectLockConfiguration> for StoredLockConfiguration {
+    fn from(config: ObjectLockConfiguration) -> Self {
+        let default_retention = config.rule.and_then(|rule| rule.default_retention);
+        Self {
+            default_retention: default_retention.map(|retention| StoredDefaultRetention {
+                mode: retention.mode,
+                days: retention.days,
+                years: retention.years,
+            }),
+        }
+    }
+}
+
+impl From<StoredLockConfiguration> for ObjectLockConfiguration {
+    fn from(config: StoredLockConfiguration) -> Self {
+        Self {
+            object_lock_enabled: Some("Enabled".to_owned()),
+            rule: config.default_retention.map(|retention| ObjectLockRule {
+                default_retention: Some(DefaultRetention {
+                    mode: retention.mode,
+                    days: retention.days,
+                    years: retention.years,
+                }),
+            }),
+        }
+    }
+}
+
+impl From<ObjectLockRetention> for StoredRetention {
+    fn from(retention: ObjectLockRetention) -> Self {
+        Self {
+            mode: retention.mode,
+            retain_until_date: retention.retain_until_date,
+        }
+    }
+}
+
+impl From<StoredRetention> for ObjectLockRetention {
+    fn from(retention: StoredRetention) -> Self {
+        Self {
+            mode: retention.mode,
+            retain_until_date: retention.retain_until_date,
+        }
+    }
+}
+
+impl From<ObjectLock> for StoredObjectLock {
+    fn from(lock: ObjectLock) -> Self {
+        Self {
+            retention: lock.retention.map(Into::into),
+            legal_hold: lock.legal_hold.and_then(|legal_hold| legal_hold.status),
+        }
+    }
+}
+
+impl StoredObjectLock {
+    /// whether there is neither a retention nor a legal hold
+    pub(super) const fn is_empty(&self) -> bool {
+        self.retention.is_none() && self.legal_hold.is_none()
+    }
+
+    /// the retention to answer or to check
+    pub(super) fn retention(&self) -> Option<ObjectLockRetention> {
+        self.retention.clone().map(Into::into)
+    }
+
+    /// the legal hold to answer
+    pub(super) fn legal_hold(&self) -> Option<ObjectLockLegalHold> {
+        self.legal_hold.clone().map(|status| ObjectLockLegalHold {
+            status: Some(status),
+        })
+    }
+}
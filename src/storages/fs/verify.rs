@@ -0,0 +1,167 @@
+//! Verification of object data on read
+
+use crate::utils::{crypto, Apply};
+
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use md5::{Digest, Md5};
+use pin_project_lite::pin_project;
+use tracing::error;
+
+/// Counts the reads which found corrupted objects
+///
+/// Clones share the count.
+#[derive(Debug, Clone, Default)]
+pub struct CorruptionCounter(Arc<AtomicU64>);
+
+impl CorruptionCounter {
+    /// Returns the current count
+    #[must_use]
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// records a corruption
+    fn increment(&self) {
+        let _prev = self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pin_project! {
+    /// Hashes an object stream and fails it if the MD5 differs from the stored one.
+    ///
+    /// The last chunk is held back until the stream ends,
+    /// so a client never receives the whole body of a corrupted object.
+    pub(super) struct VerifiedStream<S> {
+        #[pin]
+        inner: S,
+        hasher: Option<Md5>,
+        held: Option<Bytes>,
+        expected_md5: String,
+        bucket: String,
+        key: String,
+        counter: CorruptionCounter,
+    }
+}
+
+impl<S> VerifiedStream<S> {
+    /// Constructs a `VerifiedStream`
+    pub(super) fn new(
+        inner: S,
+        expected_md5: String,
+        bucket: String,
+        key: String,
+        counter: CorruptionCounter,
+    ) -> Self {
+        Self {
+            inner,
+            hasher: Some(Md5::new()),
+            held: None,
+            expected_md5,
+            bucket,
+            key,
+            counter,
+        }
+    }
+}
+
+impl<S> Stream for VerifiedStream<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let hasher = match *this.hasher {
+                Some(ref mut hasher) => hasher,
+                None => return Poll::Ready(this.held.take().map(Ok)),
+            };
+            match futures::ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(bytes)) => {
+                    hasher.update(&bytes);
+                    if let Some(prev) = this.held.replace(bytes) {
+                        return Poll::Ready(Some(Ok(prev)));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    let md5_sum = this
+                        .hasher
+                        .take()
+                        .map(|hasher| hasher.finalize().apply(crypto::to_hex_string));
+                    if md5_sum.as_deref() != Some(this.expected_md5.as_str()) {
+                        this.counter.increment();
+                        error!(
+                            bucket = %this.bucket,
+                            key = %this.key,
+                            expected = %this.expected_md5,
+                            actual = ?md5_sum,
+                            "GetObject: object data is corrupted",
+                        );
+                        *this.held = None;
+                        return Poll::Ready(Some(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "The object data does not match its checksum",
+                        ))));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::{self, StreamExt};
+
+    async fn collect(data: &[&'static [u8]], expected_md5: &str) -> (Vec<u8>, bool, u64) {
+        let counter = CorruptionCounter::default();
+        let inner = stream::iter(data.iter().map(|&chunk| Ok(Bytes::from(chunk))));
+        let mut stream = VerifiedStream::new(
+            inner,
+            expected_md5.into(),
+            "asd".into(),
+            "qwe".into(),
+            counter.clone(),
+        );
+        let mut body = Vec::new();
+        let mut failed = false;
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(bytes) => body.extend_from_slice(&bytes),
+                Err(_) => failed = true,
+            }
+        }
+        (body, failed, counter.get())
+    }
+
+    #[tokio::test]
+    async fn verified_stream() {
+        let md5_sum = Md5::digest(b"Hello World!").apply(crypto::to_hex_string);
+
+        let (body, failed, count) = collect(&[b"Hello", b" World", b"!"], &md5_sum).await;
+        assert_eq!(body, b"Hello World!");
+        assert!(!failed);
+        assert_eq!(count, 0);
+
+        let (body, failed, count) = collect(&[b"Hello", b" Wxrld", b"!"], &md5_sum).await;
+        assert_eq!(body, b"Hello Wxrld");
+        assert!(failed);
+        assert_eq!(count, 1);
+
+        let empty_md5 = Md5::digest(b"").apply(crypto::to_hex_string);
+        let (body, failed, _) = collect(&[], &empty_md5).await;
+        assert!(body.is_empty());
+        assert!(!failed);
+    }
+}
@@ -0,0 +1,307 @@
+//! Pagination of [`ListObjectsV2`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
+//! and [`ListObjects`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjects.html)
+//!
+//! A storage supplies its objects in ascending key order, and [`list_objects_v2`] or [`list_objects`] cuts a page like S3 does:
+//!
+//! + The keys are filtered by the prefix, and start after `start-after`, the continuation token or the marker.
+//! + With a delimiter, the keys which contain it after the prefix are rolled up into their common prefix,
+//!   which counts as one key.
+//! + A continuation token is the url-safe base64 of the last key or common prefix of its page.
+//!   A page which starts after a common prefix skips the keys under it.
+//! + `NextMarker` of `ListObjects` is only returned with a delimiter, as S3 does.
+
+use crate::dto::{
+    CommonPrefix, ListObjectsOutput, ListObjectsRequest, ListObjectsV2Output, ListObjectsV2Request,
+    Object,
+};
+use crate::errors::S3Result;
+
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+/// Max keys of a listing page
+const MAX_KEYS: i64 = 1000;
+
+/// a listing page
+#[derive(Debug, Default)]
+struct Page {
+    /// objects
+    contents: Vec<Object>,
+    /// prefixes rolled up by the delimiter
+    common_prefixes: Vec<CommonPrefix>,
+    /// whether there are more keys
+    is_truncated: bool,
+    /// the last key or prefix of the page
+    last: Option<String>,
+}
+
+/// the parameters of a page
+#[derive(Debug, Clone, Copy)]
+struct PageRange<'a> {
+    /// key prefix
+    prefix: &'a str,
+    /// the delimiter which rolls up keys
+    delimiter: Option<&'a str>,
+    /// the key or common prefix before the page
+    start_after: Option<&'a str>,
+    /// max keys and common prefixes
+    max_keys: i64,
+}
+
+/// omits an empty list
+fn non_empty<T>(list: Vec<T>) -> Option<Vec<T>> {
+    if list.is_empty() {
+        None
+    } else {
+        Some(list)
+    }
+}
+
+/// cuts a page of `objects`, which must be in ascending key order
+fn cut_page<I, K, V, F>(range: PageRange<'_>, objects: I, mut to_object: F) -> Page
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    F: FnMut(&str, V) -> Object,
+{
+    let PageRange {
+        prefix,
+        start_after,
+        ..
+    } = range;
+    let delimiter = range.delimiter.filter(|d| !d.is_empty());
+    let limit: usize = range.max_keys.clamp(0, MAX_KEYS).try_into().unwrap_or(0);
+
+    // the previous page may end with a common prefix
+    let skipped_prefix = match (start_after, delimiter) {
+        (Some(start), Some(d)) if start.ends_with(d) => Some(start),
+        _ => None,
+    };
+
+    let mut page = Page::default();
+    let mut count: usize = 0;
+    let mut rolled_up = BTreeSet::new();
+    for (key, value) in objects {
+        let key = key.as_ref();
+        if !key.starts_with(prefix) || start_after.map_or(false, |start| key <= start) {
+            continue;
+        }
+        if skipped_prefix.map_or(false, |skipped| key.starts_with(skipped)) {
+            continue;
+        }
+
+        let common_prefix = delimiter.and_then(|d| {
+            let rest = key.get(prefix.len()..).unwrap_or("");
+            let end = rest.find(d)?.saturating_add(d.len());
+            key.get(..prefix.len().saturating_add(end))
+        });
+        if let Some(common_prefix) = common_prefix {
+            if rolled_up.contains(common_prefix) {
+                continue;
+            }
+        }
+        if count == limit {
+            page.is_truncated = true;
+            break;
+        }
+        count = count.saturating_add(1);
+        if let Some(common_prefix) = common_prefix {
+            let _ = rolled_up.insert(common_prefix.to_owned());
+            page.common_prefixes.push(CommonPrefix {
+                prefix: Some(common_prefix.to_owned()),
+            });
+            page.last = Some(common_prefix.to_owned());
+        } else {
+            page.contents.push(to_object(key, value));
+            page.last = Some(key.to_owned());
+        }
+    }
+    page
+}
+
+/// decodes a continuation token into the last key or common prefix of its page
+fn decode_token(token: &str) -> S3Result<String> {
+    base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| {
+            code_error!(
+                InvalidArgument,
+                "The continuation token provided is incorrect"
+            )
+        })
+}
+
+/// The key which a `ListObjectsV2` page starts after,
+/// by its continuation token or `start-after`
+///
+/// A storage which scans its keys from there may pass the scanned keys to [`list_objects_v2`].
+///
+/// # Errors
+/// Returns `InvalidArgument` if the continuation token is not a token of a page
+pub fn start_after_v2(input: &ListObjectsV2Request) -> S3Result<Option<String>> {
+    input.continuation_token.as_deref().map_or_else(
+        || Ok(input.start_after.clone()),
+        |token| decode_token(token).map(Some),
+    )
+}
+
+/// Lists a page of objects for `ListObjectsV2`
+///
+/// `objects` must be in ascending key order, and `to_object` converts an object with its key.
+/// Their prefix is not required to match: the keys are filtered here.
+///
+/// # Errors
+/// Returns `InvalidArgument` if the continuation token is not a token of a page
+pub fn list_objects_v2<I, K, V, F>(
+    input: ListObjectsV2Request,
+    objects: I,
+    to_object: F,
+) -> S3Result<ListObjectsV2Output>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    F: FnMut(&str, V) -> Object,
+{
+    let start_after = start_after_v2(&input)?;
+    let max_keys = input.max_keys.unwrap_or(MAX_KEYS);
+    let range = PageRange {
+        prefix: input.prefix.as_deref().unwrap_or(""),
+        delimiter: input.delimiter.as_deref(),
+        start_after: start_after.as_deref(),
+        max_keys,
+    };
+    let page = cut_page(range, objects, to_object);
+
+    let next_continuation_token = if page.is_truncated {
+        page.last
+            .map(|last| base64::encode_config(last, base64::URL_SAFE_NO_PAD))
+    } else {
+        None
+    };
+    let key_count = page
+        .contents
+        .len()
+        .saturating_add(page.common_prefixes.len());
+    Ok(ListObjectsV2Output {
+        key_count: key_count.try_into().ok(),
+        contents: Some(page.contents),
+        common_prefixes: non_empty(page.common_prefixes),
+        delimiter: input.delimiter,
+        encoding_type: input.encoding_type,
+        name: Some(input.bucket),
+        is_truncated: Some(page.is_truncated),
+        max_keys: Some(max_keys),
+        prefix: input.prefix,
+        continuation_token: input.continuation_token,
+        next_continuation_token,
+        start_after: input.start_after,
+    })
+}
+
+/// Lists a page of objects for `ListObjects`
+///
+/// `objects` must be in ascending key order, and `to_object` converts an object with its key.
+/// Their prefix is not required to match: the keys are filtered here.
+#[must_use]
+pub fn list_objects<I, K, V, F>(
+    input: ListObjectsRequest,
+    objects: I,
+    to_object: F,
+) -> ListObjectsOutput
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    F: FnMut(&str, V) -> Object,
+{
+    let max_keys = input.max_keys.unwrap_or(MAX_KEYS);
+    let range = PageRange {
+        prefix: input.prefix.as_deref().unwrap_or(""),
+        delimiter: input.delimiter.as_deref(),
+        start_after: input.marker.as_deref(),
+        max_keys,
+    };
+    let page = cut_page(range, objects, to_object);
+
+    // `NextMarker` is only returned with a delimiter
+    let next_marker = if page.is_truncated && input.delimiter.is_some() {
+        page.last
+    } else {
+        None
+    };
+    ListObjectsOutput {
+        contents: Some(page.contents),
+        common_prefixes: non_empty(page.common_prefixes),
+        delimiter: input.delimiter,
+        encoding_type: input.encoding_type,
+        name: Some(input.bucket),
+        is_truncated: Some(page.is_truncated),
+        marker: input.marker,
+        max_keys: Some(max_keys),
+        next_marker,
+        prefix: input.prefix,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+
+    fn list(input: ListObjectsV2Request, keys: &[&str]) -> ListObjectsV2Output {
+        let objects = keys.iter().map(|&key| (key, ()));
+        let to_object = |key: &str, ()| Object {
+            key: Some(key.to_owned()),
+            ..Object::default()
+        };
+        list_objects_v2(input, objects, to_object).unwrap()
+    }
+
+    /// the keys and common prefixes of a page
+    fn entries(output: &ListObjectsV2Output) -> Vec<String> {
+        let keys = output.contents.iter().flatten().map(|o| o.key.clone());
+        let prefixes = output
+            .common_prefixes
+            .iter()
+            .flatten()
+            .map(|p| p.prefix.clone());
+        keys.chain(prefixes).flatten().collect()
+    }
+
+    #[test]
+    fn pages() {
+        let keys = ["a", "b/1", "b/2", "b/c/3", "c", "d/4"];
+        let mut input = ListObjectsV2Request {
+            bucket: "bucket".into(),
+            delimiter: Some("/".into()),
+            max_keys: Some(2),
+            ..ListObjectsV2Request::default()
+        };
+
+        let mut pages = Vec::new();
+        loop {
+            let output = list(input.clone(), &keys);
+            assert_eq!(output.key_count, Some(2));
+            pages.push(entries(&output));
+            match output.next_continuation_token {
+                Some(token) => input.continuation_token = Some(token),
+                None => break,
+            }
+        }
+        assert_eq!(pages, [vec!["a", "b/"], vec!["c", "d/"]]);
+
+        // the keys under a prefix
+        input.continuation_token = None;
+        input.prefix = Some("b/".into());
+        input.max_keys = None;
+        let output = list(input.clone(), &keys);
+        assert_eq!(entries(&output), ["b/1", "b/2", "b/c/"]);
+        assert_eq!(output.is_truncated, Some(false));
+
+        input.continuation_token = Some("!".into());
+        let objects = keys.iter().map(|&key| (key, ()));
+        let err = list_objects_v2(input, objects, |_, ()| Object::default()).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::InvalidArgument);
+    }
+}
@@ -0,0 +1,1928 @@
+//! In-memory implementation for tests and examples
+
+mod snapshot;
+
+use self::snapshot::{Snapshot, SnapshotFile, SnapshotTimer};
+
+use crate::async_trait;
+use crate::dto::{
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
+    AccessControlPolicy, Bucket, ByteStream, CORSRule, CompleteMultipartUploadError,
+    CompleteMultipartUploadOutput, CompleteMultipartUploadRequest, CopyObjectError,
+    CopyObjectOutput, CopyObjectRequest, CopyObjectResult, CopyPartResult, CreateBucketError,
+    CreateBucketOutput, CreateBucketRequest, CreateMultipartUploadError,
+    CreateMultipartUploadOutput, CreateMultipartUploadRequest, DeleteBucketCorsError,
+    DeleteBucketCorsOutput, DeleteBucketCorsRequest, DeleteBucketError, DeleteBucketOutput,
+    DeleteBucketPolicyError, DeleteBucketPolicyOutput, DeleteBucketPolicyRequest,
+    DeleteBucketRequest, DeleteBucketTaggingError, DeleteBucketTaggingOutput,
+    DeleteBucketTaggingRequest, DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest,
+    DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, DeletedObject,
+    GetBucketAclError, GetBucketAclOutput, GetBucketAclRequest, GetBucketCorsError,
+    GetBucketCorsOutput, GetBucketCorsRequest, GetBucketLocationError, GetBucketLocationOutput,
+    GetBucketLocationRequest, GetBucketPolicyError, GetBucketPolicyOutput, GetBucketPolicyRequest,
+    GetBucketTaggingError, GetBucketTaggingOutput, GetBucketTaggingRequest, GetObjectAclError,
+    GetObjectAclOutput, GetObjectAclRequest, GetObjectError, GetObjectLegalHoldError,
+    GetObjectLegalHoldOutput, GetObjectLegalHoldRequest, GetObjectLockConfigurationError,
+    GetObjectLockConfigurationOutput, GetObjectLockConfigurationRequest, GetObjectOutput,
+    GetObjectRequest, GetObjectRetentionError, GetObjectRetentionOutput, GetObjectRetentionRequest,
+    HeadBucketError, HeadBucketOutput, HeadBucketRequest, HeadObjectError, HeadObjectOutput,
+    HeadObjectRequest, ListBucketsError, ListBucketsOutput, ListBucketsRequest,
+    ListMultipartUploadsError, ListMultipartUploadsOutput, ListMultipartUploadsRequest,
+    ListObjectVersionsError, ListObjectVersionsOutput, ListObjectVersionsRequest, ListObjectsError,
+    ListObjectsOutput, ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output,
+    ListObjectsV2Request, ListPartsError, ListPartsOutput, ListPartsRequest, ListedVersion,
+    MultipartUpload, Object, ObjectLockConfiguration, ObjectLockLegalHold, ObjectLockRetention,
+    ObjectVersion, Part, PutBucketAclError, PutBucketAclOutput, PutBucketAclRequest,
+    PutBucketCorsError, PutBucketCorsOutput, PutBucketCorsRequest, PutBucketPolicyError,
+    PutBucketPolicyOutput, PutBucketPolicyRequest, PutBucketTaggingError, PutBucketTaggingOutput,
+    PutBucketTaggingRequest, PutObjectAclError, PutObjectAclOutput, PutObjectAclRequest,
+    PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldOutput, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest, PutObjectOutput, PutObjectRequest, PutObjectRetentionError,
+    PutObjectRetentionOutput, PutObjectRetentionRequest, RestoreObjectError, RestoreObjectOutput,
+    RestoreObjectRequest, Tag, UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest,
+    UploadPartError, UploadPartOutput, UploadPartRequest,
+};
+use crate::effective_config::StorageConfig;
+use crate::errors::{S3Error, S3ErrorCode, S3Result, S3StorageError, S3StorageResult};
+use crate::headers::AmzCopySource;
+use crate::signature_v4;
+use crate::storage::S3Storage;
+use crate::storages::copy_source::{self, CopySourceConditions};
+use crate::storages::listing;
+use crate::storages::object_lock::{self, ObjectLock};
+use crate::storages::object_range::{self, ObjectRange};
+use crate::storages::uploads;
+use crate::storages::versions::{self, NULL_VERSION_ID};
+use crate::utils::{crypto, time, Apply};
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fmt::{self, Debug};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use hyper::body::Bytes;
+use md5::{Digest, Md5};
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+/// A S3 storage implementation which keeps everything in memory
+///
+/// It is meant for tests, examples and ephemeral caches:
+/// the data is lost when the storage is dropped unless it has a snapshot, see [`MemoryStorage::with_snapshot`],
+/// and objects keep only their `Content-Type` and user metadata.
+///
+/// The memory is unbounded unless a [`MemoryCapacity`] is given.
+///
+/// Requires the feature `test-utils`.
+#[derive(Default)]
+pub struct MemoryStorage {
+    /// buckets and multipart uploads
+    state: Arc<Mutex<State>>,
+    /// limits
+    capacity: MemoryCapacity,
+    /// called with the evicted objects
+    eviction_hook: Option<EvictionHook>,
+    /// base URL of the presigned URLs
+    presign_endpoint: Option<String>,
+    /// the file which the state is saved to
+    snapshot: Option<Arc<SnapshotFile>>,
+    /// writes snapshots periodically
+    snapshot_timer: Option<SnapshotTimer>,
+}
+
+/// a hook of evicted objects
+type EvictionHook = Box<dyn Fn(&Eviction) + Send + Sync>;
+
+/// Limits of a [`MemoryStorage`]
+///
+/// Object data and uploaded parts are counted, while metadata is not.
+/// A write which exceeds a limit fails with `AccessDenied` (403).
+///
+/// `CopyObject` and `CompleteMultipartUpload` need room for the new object
+/// before the replaced object or the parts are freed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryCapacity {
+    /// max bytes of all objects and parts
+    pub max_total_bytes: Option<usize>,
+    /// max bytes of an object or a part
+    pub max_object_size: Option<usize>,
+    /// whether to evict the least recently used objects to make room instead of failing
+    ///
+    /// Objects are used by writes, `GetObject`, `HeadObject` and as the source of `CopyObject`.
+    /// Parts of unfinished uploads are never evicted.
+    pub evict: bool,
+}
+
+/// An object evicted by a [`MemoryStorage`], see [`MemoryStorage::set_eviction_hook`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    /// bucket
+    pub bucket: String,
+    /// key
+    pub key: String,
+    /// bytes of the object
+    pub size: usize,
+}
+
+/// Memory usage of a [`MemoryStorage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// bytes of the objects and parts
+    pub used_bytes: usize,
+    /// max of `used_bytes` since the storage is constructed, including transient copies
+    pub peak_bytes: usize,
+    /// evicted objects since the storage is constructed
+    pub evictions: u64,
+}
+
+/// internal state
+#[derive(Debug, Default)]
+struct State {
+    /// objects by bucket and key
+    buckets: BTreeMap<String, BTreeMap<String, MemoryObject>>,
+    /// rfc3339 creation time of the buckets
+    bucket_dates: HashMap<String, String>,
+    /// tags of the buckets which have any
+    bucket_tags: HashMap<String, Vec<Tag>>,
+    /// ACLs put on the buckets
+    bucket_acls: HashMap<String, AccessControlPolicy>,
+    /// CORS rules put on the buckets
+    bucket_cors: HashMap<String, Vec<CORSRule>>,
+    /// policy documents put on the buckets
+    bucket_policies: HashMap<String, String>,
+    /// Object Lock configurations of the buckets which have Object Lock enabled
+    bucket_object_locks: HashMap<String, ObjectLockConfiguration>,
+    /// multipart uploads by upload id
+    uploads: HashMap<String, Upload>,
+    /// bytes of the objects and parts
+    used: usize,
+    /// max of `used`
+    peak: usize,
+    /// evicted objects
+    evictions: u64,
+    /// the last access time
+    clock: u64,
+    /// bucket and key of the objects by access time
+    lru: BTreeMap<u64, (String, String)>,
+}
+
+/// an object
+#[derive(Debug, Clone)]
+struct MemoryObject {
+    /// content
+    data: Bytes,
+    /// quoted MD5 of the content
+    e_tag: String,
+    /// rfc3339 time of the write
+    last_modified: String,
+    /// `Content-Type`
+    content_type: Option<String>,
+    /// user metadata
+    metadata: Option<HashMap<String, String>>,
+    /// the ACL put by `PutObjectAcl`
+    acl: Option<AccessControlPolicy>,
+    /// the retention put by `PutObjectRetention`
+    retention: Option<ObjectLockRetention>,
+    /// the legal hold put by `PutObjectLegalHold`
+    legal_hold: Option<ObjectLockLegalHold>,
+    /// the bytes of each part, which are empty unless the object is uploaded in parts
+    part_sizes: Vec<u64>,
+    /// the access time, a key of `State::lru`
+    last_access: u64,
+}
+
+/// a multipart upload
+#[derive(Debug, Clone)]
+struct Upload {
+    /// bucket
+    bucket: String,
+    /// key
+    key: String,
+    /// `Content-Type`
+    content_type: Option<String>,
+    /// user metadata
+    metadata: Option<HashMap<String, String>>,
+    /// retention and legal hold of the completed object
+    lock: ObjectLock,
+    /// uploaded parts by part number
+    parts: BTreeMap<i64, Bytes>,
+}
+
+impl MemoryObject {
+    /// Constructs an object written now
+    fn new(
+        data: Bytes,
+        content_type: Option<String>,
+        metadata: Option<HashMap<String, String>>,
+    ) -> Self {
+        let e_tag = format!("\"{}\"", Md5::digest(&data).apply(crypto::to_hex_string));
+        Self {
+            data,
+            e_tag,
+            last_modified: time::to_rfc3339(SystemTime::now()),
+            content_type,
+            metadata,
+            acl: None,
+            retention: None,
+            legal_hold: None,
+            part_sizes: Vec::new(),
+            last_access: 0,
+        }
+    }
+
+    /// sets the retention and the legal hold of a written object
+    fn set_lock(&mut self, lock: ObjectLock) {
+        self.retention = lock.retention;
+        self.legal_hold = lock.legal_hold;
+    }
+
+    /// the status of the legal hold
+    fn legal_hold_status(&self) -> Option<&str> {
+        self.legal_hold.as_ref()?.status.as_deref()
+    }
+
+    /// whether the object is protected by its retention or its legal hold
+    fn is_protected(&self, bypass_governance: bool, now: DateTime<Utc>) -> bool {
+        let legal_hold = self.legal_hold_status();
+        object_lock::is_protected(self.retention.as_ref(), legal_hold, bypass_governance, now)
+    }
+}
+
+impl Debug for MemoryStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MemoryStorage {{ capacity: {:?}, usage: {:?} }}",
+            self.capacity,
+            self.usage()
+        )
+    }
+}
+
+impl MemoryStorage {
+    /// Constructs an empty storage
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an empty storage with limits
+    #[must_use]
+    pub fn with_capacity(capacity: MemoryCapacity) -> Self {
+        let mut storage = Self::default();
+        storage.capacity = capacity;
+        storage
+    }
+
+    /// Constructs a storage which is saved to a snapshot at `path`,
+    /// restoring the buckets, objects and multipart uploads of the snapshot if it exists.
+    ///
+    /// A snapshot is written when the storage is dropped, such as when a server shuts down gracefully,
+    /// by [`MemoryStorage::snapshot`] and periodically if [`MemoryStorage::set_snapshot_interval`] is set.
+    /// The objects are written one by one, so a snapshot does not copy all data into one buffer.
+    /// # Errors
+    /// Returns an `Err` if the snapshot exists and can not be read
+    pub fn with_snapshot(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut storage = Self::default();
+        if path.exists() {
+            storage.state = Arc::new(Mutex::new(Snapshot::load(&path)?.restore()));
+        }
+        storage.snapshot = Some(Arc::new(SnapshotFile::new(path)));
+        Ok(storage)
+    }
+
+    /// Writes a snapshot every `interval`, or stops writing periodically if it is `None`.
+    ///
+    /// It has no effect on a storage without a snapshot, see [`MemoryStorage::with_snapshot`].
+    /// The state is locked only while the objects are collected, not while they are written.
+    pub fn set_snapshot_interval(&mut self, interval: Option<Duration>) {
+        self.snapshot_timer = match (&self.snapshot, interval) {
+            (Some(file), Some(interval)) => Some(SnapshotTimer::spawn(
+                Arc::clone(file),
+                Arc::downgrade(&self.state),
+                interval,
+            )),
+            _ => None,
+        };
+    }
+
+    /// Writes a snapshot to the path given by [`MemoryStorage::with_snapshot`],
+    /// renaming it over the previous snapshot when it is complete.
+    /// # Errors
+    /// Returns an `Err` if the storage has no snapshot, or the snapshot can not be written
+    pub fn snapshot(&self) -> io::Result<()> {
+        self.snapshot.as_ref().map_or_else(
+            || {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the storage has no snapshot",
+                ))
+            },
+            |file| file.save(&self.state),
+        )
+    }
+
+    /// Sets a hook which is called with each evicted object, after the write which evicts it
+    pub fn set_eviction_hook(&mut self, hook: impl Fn(&Eviction) + Send + Sync + 'static) {
+        self.eviction_hook = Some(Box::new(hook));
+    }
+
+    /// Lets [`S3Storage::presign_get`] return URLs under `endpoint`, which must serve the same objects,
+    /// such as a mirror of the storage.
+    ///
+    /// A URL is `<endpoint>/<bucket>/<key>?X-Amz-Expires=<secs>` and carries no signature.
+    pub fn set_presign_endpoint(&mut self, endpoint: impl Into<String>) {
+        self.presign_endpoint = Some(endpoint.into());
+    }
+
+    /// Returns the current memory usage
+    #[must_use]
+    pub fn usage(&self) -> MemoryUsage {
+        let state = self.lock();
+        MemoryUsage {
+            used_bytes: state.used,
+            peak_bytes: state.peak,
+            evictions: state.evictions,
+        }
+    }
+
+    /// locks the state
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    /// checks the size of an object or a part
+    fn check_size(&self, len: usize) -> Result<(), S3Error> {
+        match self.capacity.max_object_size {
+            Some(max) if len > max => Err(object_too_large(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// reports evicted objects to the hook
+    fn report(&self, evictions: &[Eviction]) {
+        if let Some(ref hook) = self.eviction_hook {
+            for eviction in evictions {
+                hook(eviction);
+            }
+        }
+    }
+}
+
+impl Drop for MemoryStorage {
+    fn drop(&mut self) {
+        self.snapshot_timer = None;
+        if let Some(ref file) = self.snapshot {
+            if let Err(err) = file.save(&self.state) {
+                error!(%err, path = %file.path.display(), "failed to save a snapshot");
+            }
+        }
+    }
+}
+
+/// locks a mutex, ignoring poisoning
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl State {
+    /// gets a bucket
+    fn bucket(&self, bucket: &str) -> Result<&BTreeMap<String, MemoryObject>, S3Error> {
+        self.buckets.get(bucket).ok_or_else(no_such_bucket)
+    }
+
+    /// gets an object
+    fn object(&self, bucket: &str, key: &str) -> Result<&MemoryObject, S3Error> {
+        self.bucket(bucket)?.get(key).ok_or_else(no_such_key)
+    }
+
+    /// gets an object to modify
+    fn object_mut(&mut self, bucket: &str, key: &str) -> Result<&mut MemoryObject, S3Error> {
+        self.buckets
+            .get_mut(bucket)
+            .ok_or_else(no_such_bucket)?
+            .get_mut(key)
+            .ok_or_else(no_such_key)
+    }
+
+    /// gets an object to lock, whose bucket must have Object Lock enabled
+    fn locked_object_mut(&mut self, bucket: &str, key: &str) -> Result<&mut MemoryObject, S3Error> {
+        let _bucket = self.bucket(bucket)?;
+        if !self.bucket_object_locks.contains_key(bucket) {
+            return Err(invalid_request!(
+                "Bucket is missing Object Lock Configuration"
+            ));
+        }
+        self.object_mut(bucket, key)
+    }
+
+    /// checks that an object, if any, is not protected by its retention or its legal hold
+    fn check_unprotected(
+        &self,
+        bucket: &str,
+        key: &str,
+        bypass_governance: bool,
+    ) -> Result<(), S3Error> {
+        let object = self
+            .buckets
+            .get(bucket)
+            .and_then(|objects| objects.get(key));
+        object.map_or(Ok(()), |object| {
+            object_lock::check_unprotected(
+                object.retention.as_ref(),
+                object.legal_hold_status(),
+                bypass_governance,
+                Utc::now(),
+            )
+        })
+    }
+
+    /// resolves the lock of an object written with `x-amz-object-lock-*` headers
+    fn resolve_object_lock(
+        &self,
+        bucket: &str,
+        mode: Option<&str>,
+        retain_until_date: Option<&str>,
+        legal_hold: Option<&str>,
+    ) -> Result<ObjectLock, S3Error> {
+        let config = self.bucket_object_locks.get(bucket);
+        object_lock::resolve_object_lock(mode, retain_until_date, legal_hold, config, Utc::now())
+    }
+
+    /// gets an object and marks it as used
+    fn touch(&mut self, bucket: &str, key: &str) -> Result<&MemoryObject, S3Error> {
+        self.clock = self.clock.wrapping_add(1);
+        let object = self
+            .buckets
+            .get_mut(bucket)
+            .ok_or_else(no_such_bucket)?
+            .get_mut(key)
+            .ok_or_else(no_such_key)?;
+        let _prev = self.lru.remove(&object.last_access);
+        object.last_access = self.clock;
+        let _prev = self
+            .lru
+            .insert(self.clock, (bucket.to_owned(), key.to_owned()));
+        Ok(object)
+    }
+
+    /// inserts an object whose bytes are reserved, freeing the replaced object
+    fn insert_object(&mut self, bucket: &str, key: String, mut object: MemoryObject) {
+        let _prev = self.remove_object(bucket, &key);
+        self.clock = self.clock.wrapping_add(1);
+        object.last_access = self.clock;
+        let _prev = self
+            .lru
+            .insert(self.clock, (bucket.to_owned(), key.clone()));
+        if let Some(objects) = self.buckets.get_mut(bucket) {
+            let _prev = objects.insert(key, object);
+        }
+    }
+
+    /// removes an object and frees its bytes
+    fn remove_object(&mut self, bucket: &str, key: &str) -> Option<MemoryObject> {
+        let object = self.buckets.get_mut(bucket)?.remove(key)?;
+        let _prev = self.lru.remove(&object.last_access);
+        self.used = self.used.saturating_sub(object.data.len());
+        Some(object)
+    }
+
+    /// reserves `len` bytes, evicting the least recently used objects except `keep` if enabled
+    fn reserve(
+        &mut self,
+        capacity: &MemoryCapacity,
+        len: usize,
+        keep: Option<(&str, &str)>,
+    ) -> Result<Vec<Eviction>, S3Error> {
+        let mut evictions = Vec::new();
+        if let Some(max) = capacity.max_total_bytes {
+            if self.used.saturating_add(len) > max {
+                if !capacity.evict {
+                    return Err(quota_exceeded(max));
+                }
+                let mut needed = self.used.saturating_add(len).saturating_sub(max);
+                let mut victims = Vec::new();
+                for (bucket, key) in self.lru.values() {
+                    if needed == 0 {
+                        break;
+                    }
+                    if keep == Some((bucket.as_str(), key.as_str())) {
+                        continue;
+                    }
+                    let size = self.object(bucket, key).map_or(0, |o| o.data.len());
+                    needed = needed.saturating_sub(size);
+                    victims.push((bucket.clone(), key.clone()));
+                }
+                // nothing is evicted for a write which can not fit anyway
+                if needed > 0 {
+                    return Err(quota_exceeded(max));
+                }
+                for (bucket, key) in victims {
+                    if let Some(object) = self.remove_object(&bucket, &key) {
+                        self.evictions = self.evictions.wrapping_add(1);
+                        let size = object.data.len();
+                        evictions.push(Eviction { bucket, key, size });
+                    }
+                }
+            }
+        }
+        self.used = self.used.saturating_add(len);
+        self.peak = self.peak.max(self.used);
+        Ok(evictions)
+    }
+}
+
+/// the error of a missing bucket
+fn no_such_bucket() -> S3Error {
+    code_error!(NoSuchBucket, "The specified bucket does not exist.")
+}
+
+/// the error of a missing key
+fn no_such_key() -> S3Error {
+    code_error!(NoSuchKey, "The specified key does not exist.")
+}
+
+/// the error of a write which exceeds `MemoryCapacity::max_total_bytes`
+fn quota_exceeded(max: usize) -> S3Error {
+    S3Error::from_code(S3ErrorCode::AccessDenied)
+        .message(format!("The storage quota of {} bytes is exceeded.", max))
+        .finish()
+}
+
+/// the error of an object which exceeds `MemoryCapacity::max_object_size`
+fn object_too_large(max: usize) -> S3Error {
+    S3Error::from_code(S3ErrorCode::AccessDenied)
+        .message(format!(
+            "The object exceeds the storage quota of {} bytes per object.",
+            max
+        ))
+        .finish()
+}
+
+/// collects a request body, failing as soon as it exceeds `limit`
+async fn collect_body(body: Option<ByteStream>, limit: Option<usize>) -> Result<Bytes, S3Error> {
+    let mut body = body.ok_or_else(|| {
+        code_error!(
+            IncompleteBody,
+            "You did not provide the number of bytes specified by the Content-Length HTTP header."
+        )
+    })?;
+    let mut data = Vec::new();
+    while let Some(bytes) = body.try_next().await.map_err(|e| internal_error!(e))? {
+        let len = data.len().saturating_add(bytes.len());
+        if let Some(max) = limit.filter(|&max| len > max) {
+            return Err(object_too_large(max));
+        }
+        data.extend_from_slice(&bytes);
+    }
+    Ok(data.into())
+}
+
+/// a listed object
+fn to_object(key: &str, object: &MemoryObject) -> Object {
+    Object {
+        e_tag: Some(object.e_tag.clone()),
+        key: Some(key.to_owned()),
+        last_modified: Some(object.last_modified.clone()),
+        owner: None,
+        size: to_i64(object.data.len()),
+        storage_class: Some("STANDARD".into()),
+    }
+}
+
+/// a listing size
+fn to_i64(len: usize) -> Option<i64> {
+    len.try_into().ok()
+}
+
+/// the bytes of an object of `size` bytes to read, which are a part or a `Range`,
+/// with the number of parts of an object uploaded in parts
+fn read_range(
+    object: &MemoryObject,
+    part_number: Option<i64>,
+    range: Option<&str>,
+    size: u64,
+) -> S3Result<(ObjectRange, Option<i64>)> {
+    let part_number = match part_number {
+        Some(part_number) => part_number,
+        None => return Ok((object_range::byte_range(range, size)?, None)),
+    };
+    let range = object_range::part_range(part_number, &object.part_sizes, size)?;
+    let parts_count = Some(object.part_sizes.len())
+        .filter(|&count| count > 0)
+        .and_then(to_i64);
+    Ok((range, parts_count))
+}
+
+#[async_trait]
+impl S3Storage for MemoryStorage {
+    async fn abort_multipart_upload(
+        &self,
+        input: AbortMultipartUploadRequest,
+    ) -> S3StorageResult<AbortMultipartUploadOutput, AbortMultipartUploadError> {
+        let mut state = self.lock();
+        match state.uploads.get(&input.upload_id) {
+            Some(upload) if upload.bucket == input.bucket && upload.key == input.key => {}
+            _ => {
+                let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+                return Err(err.into());
+            }
+        }
+        if let Some(upload) = state.uploads.remove(&input.upload_id) {
+            let parts_len = upload.parts.values().map(Bytes::len).sum();
+            state.used = state.used.saturating_sub(parts_len);
+        }
+        drop(state);
+        Ok(AbortMultipartUploadOutput::default())
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        input: CompleteMultipartUploadRequest,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        let mut state = self.lock();
+        let upload = match state.uploads.get(&input.upload_id) {
+            Some(upload) if upload.bucket == input.bucket && upload.key == input.key => upload,
+            _ => {
+                let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+                return Err(err.into());
+            }
+        };
+
+        let parts = input
+            .multipart_upload
+            .and_then(|multipart_upload| multipart_upload.parts)
+            .unwrap_or_default();
+        if parts.is_empty() {
+            let err = code_error!(
+                MalformedXML,
+                "The XML you provided was not well-formed or did not validate against our published schema."
+            );
+            return Err(err.into());
+        }
+        let mut data = Vec::new();
+        let mut part_md5_sums = Vec::with_capacity(parts.len());
+        let mut part_sizes = Vec::with_capacity(parts.len());
+        let mut prev: i64 = 0;
+        for part in parts {
+            let part_number = part.part_number.unwrap_or(0);
+            if part_number <= prev {
+                let err = code_error!(InvalidPartOrder, "The list of parts was not in ascending order. The parts list must be specified in order by part number.");
+                return Err(err.into());
+            }
+            prev = part_number;
+            let bytes = upload.parts.get(&part_number).ok_or_else(|| {
+                code_error!(
+                    InvalidPart,
+                    "One or more of the specified parts could not be found."
+                )
+            })?;
+            data.extend_from_slice(bytes);
+            part_md5_sums.push(Md5::digest(bytes));
+            part_sizes.push(trace_try!(bytes.len().try_into()));
+        }
+
+        self.check_size(data.len())?;
+        let mut object = MemoryObject::new(
+            data.into(),
+            upload.content_type.clone(),
+            upload.metadata.clone(),
+        );
+        object.e_tag = crypto::multipart_e_tag(&part_md5_sums);
+        object.part_sizes = part_sizes;
+        object.set_lock(upload.lock.clone());
+        let e_tag = object.e_tag.clone();
+        let _bucket = state.bucket(&input.bucket)?;
+        state.check_unprotected(&input.bucket, &input.key, false)?;
+
+        // the parts are freed after the object is stored
+        let evictions = state.reserve(&self.capacity, object.data.len(), None)?;
+        state.insert_object(&input.bucket, input.key.clone(), object);
+        if let Some(upload) = state.uploads.remove(&input.upload_id) {
+            let parts_len = upload.parts.values().map(Bytes::len).sum();
+            state.used = state.used.saturating_sub(parts_len);
+        }
+        drop(state);
+        self.report(&evictions);
+
+        Ok(CompleteMultipartUploadOutput {
+            bucket: Some(input.bucket),
+            key: Some(input.key),
+            e_tag: Some(e_tag),
+            ..CompleteMultipartUploadOutput::default()
+        })
+    }
+
+    async fn copy_object(
+        &self,
+        input: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        let copy_source = AmzCopySource::from_header_str(&input.copy_source)
+            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+        let (bucket, key) = match copy_source {
+            AmzCopySource::AccessPoint { .. } => {
+                return Err(
+                    code_error!(NotImplemented, "Access point is not supported yet.").into(),
+                )
+            }
+            AmzCopySource::Bucket {
+                bucket,
+                key,
+                version_id,
+            } => {
+                versions::check_null_version(version_id)?;
+                (bucket, key)
+            }
+        };
+
+        let mut state = self.lock();
+        let src = state.touch(bucket, key)?;
+        let (content_type, metadata) = if input.metadata_directive.as_deref() == Some("REPLACE") {
+            (input.content_type, input.metadata)
+        } else {
+            (src.content_type.clone(), src.metadata.clone())
+        };
+        let mut object = MemoryObject::new(src.data.clone(), content_type, metadata);
+        let result = CopyObjectResult {
+            e_tag: Some(object.e_tag.clone()),
+            last_modified: Some(object.last_modified.clone()),
+        };
+        let _bucket = state.bucket(&input.bucket)?;
+        state.check_unprotected(&input.bucket, &input.key, false)?;
+        object.set_lock(state.resolve_object_lock(
+            &input.bucket,
+            input.object_lock_mode.as_deref(),
+            input.object_lock_retain_until_date.as_deref(),
+            input.object_lock_legal_hold_status.as_deref(),
+        )?);
+
+        // the copy is counted apart from its source, which is not evicted for it
+        let keep = Some((bucket, key));
+        let evictions = state.reserve(&self.capacity, object.data.len(), keep)?;
+        state.insert_object(&input.bucket, input.key, object);
+        drop(state);
+        self.report(&evictions);
+
+        Ok(CopyObjectOutput {
+            copy_object_result: Some(result),
+            ..CopyObjectOutput::default()
+        })
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        input: CreateMultipartUploadRequest,
+    ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError> {
+        let upload_id = Uuid::new_v4().to_string();
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let lock = state.resolve_object_lock(
+            &input.bucket,
+            input.object_lock_mode.as_deref(),
+            input.object_lock_retain_until_date.as_deref(),
+            input.object_lock_legal_hold_status.as_deref(),
+        )?;
+        let upload = Upload {
+            bucket: input.bucket.clone(),
+            key: input.key.clone(),
+            content_type: input.content_type,
+            metadata: input.metadata,
+            lock,
+            parts: BTreeMap::new(),
+        };
+        let _prev = state.uploads.insert(upload_id.clone(), upload);
+        drop(state);
+
+        Ok(CreateMultipartUploadOutput {
+            bucket: Some(input.bucket),
+            key: Some(input.key),
+            upload_id: Some(upload_id),
+            ..CreateMultipartUploadOutput::default()
+        })
+    }
+
+    async fn create_bucket(
+        &self,
+        input: CreateBucketRequest,
+    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
+        let mut state = self.lock();
+        if state.buckets.contains_key(&input.bucket) {
+            let err = CreateBucketError::BucketAlreadyExists(String::from(
+                "The requested bucket name is not available. \
+                    The bucket namespace is shared by all users of the system. \
+                    Please select a different name and try again.",
+            ));
+            return Err(S3StorageError::Operation(err));
+        }
+        let now = time::to_rfc3339(SystemTime::now());
+        let _prev = state.bucket_dates.insert(input.bucket.clone(), now);
+        if input.object_lock_enabled_for_bucket == Some(true) {
+            let config = ObjectLockConfiguration {
+                object_lock_enabled: Some("Enabled".to_owned()),
+                rule: None,
+            };
+            let _prev = state
+                .bucket_object_locks
+                .insert(input.bucket.clone(), config);
+        }
+        let _prev = state.buckets.insert(input.bucket, BTreeMap::new());
+        drop(state);
+        Ok(CreateBucketOutput::default())
+    }
+
+    async fn delete_bucket(
+        &self,
+        input: DeleteBucketRequest,
+    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
+        let mut state = self.lock();
+        if !state.bucket(&input.bucket)?.is_empty() {
+            let err = code_error!(
+                BucketNotEmpty,
+                "The bucket you tried to delete is not empty."
+            );
+            return Err(err.into());
+        }
+        let _bucket = state.buckets.remove(&input.bucket);
+        let _date = state.bucket_dates.remove(&input.bucket);
+        let _tags = state.bucket_tags.remove(&input.bucket);
+        let _acl = state.bucket_acls.remove(&input.bucket);
+        let _cors = state.bucket_cors.remove(&input.bucket);
+        let _policy = state.bucket_policies.remove(&input.bucket);
+        let _object_lock = state.bucket_object_locks.remove(&input.bucket);
+        drop(state);
+        Ok(DeleteBucketOutput)
+    }
+
+    async fn delete_bucket_cors(
+        &self,
+        input: DeleteBucketCorsRequest,
+    ) -> S3StorageResult<DeleteBucketCorsOutput, DeleteBucketCorsError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let _cors = state.bucket_cors.remove(&input.bucket);
+        drop(state);
+        Ok(DeleteBucketCorsOutput)
+    }
+
+    async fn delete_bucket_policy(
+        &self,
+        input: DeleteBucketPolicyRequest,
+    ) -> S3StorageResult<DeleteBucketPolicyOutput, DeleteBucketPolicyError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let _policy = state.bucket_policies.remove(&input.bucket);
+        drop(state);
+        Ok(DeleteBucketPolicyOutput)
+    }
+
+    async fn delete_bucket_tagging(
+        &self,
+        input: DeleteBucketTaggingRequest,
+    ) -> S3StorageResult<DeleteBucketTaggingOutput, DeleteBucketTaggingError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let _tags = state.bucket_tags.remove(&input.bucket);
+        drop(state);
+        Ok(DeleteBucketTaggingOutput)
+    }
+
+    async fn delete_object(
+        &self,
+        input: DeleteObjectRequest,
+    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let bypass_governance = input.bypass_governance_retention == Some(true);
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        state.check_unprotected(&input.bucket, &input.key, bypass_governance)?;
+        let _object = state.remove_object(&input.bucket, &input.key);
+        drop(state);
+        Ok(DeleteObjectOutput {
+            version_id: input.version_id,
+            ..DeleteObjectOutput::default()
+        })
+    }
+
+    async fn delete_objects(
+        &self,
+        input: DeleteObjectsRequest,
+    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
+        let bypass_governance = input.bypass_governance_retention == Some(true);
+        let now = Utc::now();
+        let mut state = self.lock();
+        let objects = state.bucket(&input.bucket)?;
+        let (protected, unprotected): (Vec<_>, Vec<_>) =
+            input.delete.objects.into_iter().partition(|object| {
+                objects
+                    .get(&object.key)
+                    .map_or(false, |object| object.is_protected(bypass_governance, now))
+            });
+        let errors: Vec<_> = protected
+            .into_iter()
+            .map(|object| object_lock::protected_entry(object.key))
+            .collect();
+        let mut deleted = Vec::new();
+        for object in unprotected {
+            let _object = state.remove_object(&input.bucket, &object.key);
+            deleted.push(DeletedObject {
+                key: Some(object.key),
+                ..DeletedObject::default()
+            });
+        }
+        drop(state);
+
+        Ok(DeleteObjectsOutput {
+            deleted: Some(deleted),
+            errors: Some(errors),
+            ..DeleteObjectsOutput::default()
+        })
+    }
+
+    async fn get_bucket_acl(
+        &self,
+        input: GetBucketAclRequest,
+    ) -> S3StorageResult<GetBucketAclOutput, GetBucketAclError> {
+        let state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let acl = state.bucket_acls.get(&input.bucket).cloned();
+        drop(state);
+        let (owner, grants) = acl.map_or((None, None), |acl| (acl.owner, acl.grants));
+        Ok(GetBucketAclOutput { grants, owner })
+    }
+
+    async fn get_bucket_cors(
+        &self,
+        input: GetBucketCorsRequest,
+    ) -> S3StorageResult<GetBucketCorsOutput, GetBucketCorsError> {
+        let state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let cors_rules = state.bucket_cors.get(&input.bucket).cloned();
+        drop(state);
+        Ok(GetBucketCorsOutput { cors_rules })
+    }
+
+    async fn get_bucket_location(
+        &self,
+        input: GetBucketLocationRequest,
+    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
+        let _bucket = self.lock().bucket(&input.bucket)?;
+        Ok(GetBucketLocationOutput {
+            location_constraint: None,
+        })
+    }
+
+    async fn get_bucket_policy(
+        &self,
+        input: GetBucketPolicyRequest,
+    ) -> S3StorageResult<GetBucketPolicyOutput, GetBucketPolicyError> {
+        let state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let policy = state.bucket_policies.get(&input.bucket).cloned();
+        drop(state);
+        Ok(GetBucketPolicyOutput { policy })
+    }
+
+    async fn get_bucket_tagging(
+        &self,
+        input: GetBucketTaggingRequest,
+    ) -> S3StorageResult<GetBucketTaggingOutput, GetBucketTaggingError> {
+        let state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let tag_set = state
+            .bucket_tags
+            .get(&input.bucket)
+            .cloned()
+            .unwrap_or_default();
+        drop(state);
+        Ok(GetBucketTaggingOutput { tag_set })
+    }
+
+    async fn get_object_acl(
+        &self,
+        input: GetObjectAclRequest,
+    ) -> S3StorageResult<GetObjectAclOutput, GetObjectAclError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let acl = self.lock().object(&input.bucket, &input.key)?.acl.clone();
+        let (owner, grants) = acl.map_or((None, None), |acl| (acl.owner, acl.grants));
+        Ok(GetObjectAclOutput {
+            grants,
+            owner,
+            ..GetObjectAclOutput::default()
+        })
+    }
+
+    async fn get_object_legal_hold(
+        &self,
+        input: GetObjectLegalHoldRequest,
+    ) -> S3StorageResult<GetObjectLegalHoldOutput, GetObjectLegalHoldError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let state = self.lock();
+        let legal_hold = state.object(&input.bucket, &input.key)?.legal_hold.clone();
+        drop(state);
+        Ok(GetObjectLegalHoldOutput { legal_hold })
+    }
+
+    async fn get_object_lock_configuration(
+        &self,
+        input: GetObjectLockConfigurationRequest,
+    ) -> S3StorageResult<GetObjectLockConfigurationOutput, GetObjectLockConfigurationError> {
+        let state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let object_lock_configuration = state.bucket_object_locks.get(&input.bucket).cloned();
+        drop(state);
+        Ok(GetObjectLockConfigurationOutput {
+            object_lock_configuration,
+        })
+    }
+
+    async fn get_object_retention(
+        &self,
+        input: GetObjectRetentionRequest,
+    ) -> S3StorageResult<GetObjectRetentionOutput, GetObjectRetentionError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let state = self.lock();
+        let retention = state.object(&input.bucket, &input.key)?.retention.clone();
+        drop(state);
+        Ok(GetObjectRetentionOutput { retention })
+    }
+
+    async fn get_object(
+        &self,
+        input: GetObjectRequest,
+    ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let object = self.lock().touch(&input.bucket, &input.key)?.clone();
+        let size: u64 = trace_try!(object.data.len().try_into());
+        let (range, parts_count) =
+            read_range(&object, input.part_number, input.range.as_deref(), size)?;
+        let start: usize = trace_try!(range.bytes.start.try_into());
+        let end: usize = trace_try!(range.bytes.end.try_into());
+        let data = object.data.slice(start..end);
+        Ok(GetObjectOutput {
+            content_length: to_i64(data.len()),
+            content_range: range.content_range,
+            accept_ranges: Some("bytes".to_owned()),
+            parts_count,
+            body: Some(ByteStream::from(data.to_vec())),
+            e_tag: Some(object.e_tag),
+            last_modified: Some(object.last_modified),
+            content_type: object.content_type,
+            metadata: object.metadata,
+            version_id: input.version_id,
+            ..GetObjectOutput::default()
+        })
+    }
+
+    async fn head_bucket(
+        &self,
+        input: HeadBucketRequest,
+    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
+        let _bucket = self.lock().bucket(&input.bucket)?;
+        Ok(HeadBucketOutput::default())
+    }
+
+    async fn head_object(
+        &self,
+        input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let object = self.lock().touch(&input.bucket, &input.key)?.clone();
+        let size: u64 = trace_try!(object.data.len().try_into());
+        let (range, parts_count) = read_range(&object, input.part_number, None, size)?;
+        Ok(HeadObjectOutput {
+            content_length: range.len().try_into().ok(),
+            parts_count,
+            e_tag: Some(object.e_tag),
+            last_modified: Some(object.last_modified),
+            content_type: object.content_type,
+            metadata: object.metadata,
+            version_id: input.version_id,
+            ..HeadObjectOutput::default()
+        })
+    }
+
+    async fn list_buckets(
+        &self,
+        _: ListBucketsRequest,
+    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
+        let state = self.lock();
+        let buckets = state
+            .buckets
+            .keys()
+            .map(|name| Bucket {
+                creation_date: state.bucket_dates.get(name).cloned(),
+                name: Some(name.clone()),
+            })
+            .collect();
+        drop(state);
+        Ok(ListBucketsOutput {
+            buckets: Some(buckets),
+            owner: None,
+        })
+    }
+
+    async fn list_object_versions(
+        &self,
+        input: ListObjectVersionsRequest,
+    ) -> S3StorageResult<ListObjectVersionsOutput, ListObjectVersionsError> {
+        let state = self.lock();
+        // objects are not versioned, so each key has only a null version
+        let keys = state.bucket(&input.bucket)?.iter().map(|(key, object)| {
+            let version = ObjectVersion {
+                e_tag: Some(object.e_tag.clone()),
+                last_modified: Some(object.last_modified.clone()),
+                size: object.data.len().try_into().ok(),
+                storage_class: Some("STANDARD".into()),
+                version_id: Some(NULL_VERSION_ID.to_owned()),
+                ..ObjectVersion::default()
+            };
+            (key.clone(), Some(ListedVersion::Version(version)))
+        });
+        let output = versions::list_versions(input, keys);
+        drop(state);
+        output.map_err(S3StorageError::Other)
+    }
+
+    async fn list_objects(
+        &self,
+        input: ListObjectsRequest,
+    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
+        let state = self.lock();
+        let objects = state.bucket(&input.bucket)?;
+        let output = listing::list_objects(input, objects, to_object);
+        drop(state);
+        Ok(output)
+    }
+
+    async fn list_objects_v2(
+        &self,
+        input: ListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        let state = self.lock();
+        let objects = state.bucket(&input.bucket)?;
+        let output = listing::list_objects_v2(input, objects, to_object);
+        drop(state);
+        output.map_err(S3StorageError::Other)
+    }
+
+    async fn list_parts(
+        &self,
+        input: ListPartsRequest,
+    ) -> S3StorageResult<ListPartsOutput, ListPartsError> {
+        let state = self.lock();
+        let upload = match state.uploads.get(&input.upload_id) {
+            Some(upload) if upload.bucket == input.bucket && upload.key == input.key => upload,
+            _ => {
+                let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+                return Err(err.into());
+            }
+        };
+        let parts = upload
+            .parts
+            .iter()
+            .map(|(&part_number, data)| Part {
+                e_tag: Some(format!(
+                    "\"{}\"",
+                    Md5::digest(data).apply(crypto::to_hex_string)
+                )),
+                last_modified: None,
+                part_number: Some(part_number),
+                size: to_i64(data.len()),
+            })
+            .collect();
+        drop(state);
+
+        Ok(ListPartsOutput {
+            bucket: Some(input.bucket),
+            key: Some(input.key),
+            upload_id: Some(input.upload_id),
+            parts: Some(parts),
+            storage_class: Some("STANDARD".into()),
+            ..ListPartsOutput::default()
+        })
+    }
+
+    async fn list_multipart_uploads(
+        &self,
+        input: ListMultipartUploadsRequest,
+    ) -> S3StorageResult<ListMultipartUploadsOutput, ListMultipartUploadsError> {
+        let state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        // the initiation time is not recorded, so the uploads of a key are ordered by id
+        let uploads: Vec<_> = state
+            .uploads
+            .iter()
+            .filter(|&(_, upload)| upload.bucket == input.bucket)
+            .map(|(upload_id, upload)| MultipartUpload {
+                key: Some(upload.key.clone()),
+                storage_class: Some("STANDARD".into()),
+                upload_id: Some(upload_id.clone()),
+                ..MultipartUpload::default()
+            })
+            .collect();
+        drop(state);
+        Ok(uploads::list_uploads(input, uploads))
+    }
+
+    async fn put_bucket_acl(
+        &self,
+        input: PutBucketAclRequest,
+    ) -> S3StorageResult<PutBucketAclOutput, PutBucketAclError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let policy = input.access_control_policy.unwrap_or_default();
+        let _prev = state.bucket_acls.insert(input.bucket, policy);
+        drop(state);
+        Ok(PutBucketAclOutput)
+    }
+
+    async fn put_bucket_cors(
+        &self,
+        input: PutBucketCorsRequest,
+    ) -> S3StorageResult<PutBucketCorsOutput, PutBucketCorsError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let _prev = state
+            .bucket_cors
+            .insert(input.bucket, input.cors_configuration.cors_rules);
+        drop(state);
+        Ok(PutBucketCorsOutput)
+    }
+
+    async fn put_bucket_policy(
+        &self,
+        input: PutBucketPolicyRequest,
+    ) -> S3StorageResult<PutBucketPolicyOutput, PutBucketPolicyError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let _prev = state.bucket_policies.insert(input.bucket, input.policy);
+        drop(state);
+        Ok(PutBucketPolicyOutput)
+    }
+
+    async fn put_bucket_tagging(
+        &self,
+        input: PutBucketTaggingRequest,
+    ) -> S3StorageResult<PutBucketTaggingOutput, PutBucketTaggingError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        let _prev = state
+            .bucket_tags
+            .insert(input.bucket, input.tagging.tag_set);
+        drop(state);
+        Ok(PutBucketTaggingOutput)
+    }
+
+    async fn put_object_acl(
+        &self,
+        input: PutObjectAclRequest,
+    ) -> S3StorageResult<PutObjectAclOutput, PutObjectAclError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let mut state = self.lock();
+        state.object_mut(&input.bucket, &input.key)?.acl = input.access_control_policy;
+        drop(state);
+        Ok(PutObjectAclOutput::default())
+    }
+
+    async fn put_object_legal_hold(
+        &self,
+        input: PutObjectLegalHoldRequest,
+    ) -> S3StorageResult<PutObjectLegalHoldOutput, PutObjectLegalHoldError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let mut state = self.lock();
+        state
+            .locked_object_mut(&input.bucket, &input.key)?
+            .legal_hold = input.legal_hold;
+        drop(state);
+        Ok(PutObjectLegalHoldOutput::default())
+    }
+
+    async fn put_object_lock_configuration(
+        &self,
+        input: PutObjectLockConfigurationRequest,
+    ) -> S3StorageResult<PutObjectLockConfigurationOutput, PutObjectLockConfigurationError> {
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        if let Some(config) = input.object_lock_configuration {
+            let _prev = state.bucket_object_locks.insert(input.bucket, config);
+        }
+        drop(state);
+        Ok(PutObjectLockConfigurationOutput::default())
+    }
+
+    async fn put_object_retention(
+        &self,
+        input: PutObjectRetentionRequest,
+    ) -> S3StorageResult<PutObjectRetentionOutput, PutObjectRetentionError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let mut state = self.lock();
+        let object = state.locked_object_mut(&input.bucket, &input.key)?;
+        object_lock::check_retention_change(
+            object.retention.as_ref(),
+            input.retention.as_ref(),
+            input.bypass_governance_retention == Some(true),
+            Utc::now(),
+        )?;
+        object.retention = input.retention;
+        drop(state);
+        Ok(PutObjectRetentionOutput::default())
+    }
+
+    async fn put_object(
+        &self,
+        input: PutObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        let _bucket = self.lock().bucket(&input.bucket)?;
+        let data = collect_body(input.body, self.capacity.max_object_size).await?;
+        let mut object = MemoryObject::new(data, input.content_type, input.metadata);
+        let e_tag = object.e_tag.clone();
+
+        // the replaced object is freed after the new one is stored
+        let mut state = self.lock();
+        let _bucket = state.bucket(&input.bucket)?;
+        state.check_unprotected(&input.bucket, &input.key, false)?;
+        object.set_lock(state.resolve_object_lock(
+            &input.bucket,
+            input.object_lock_mode.as_deref(),
+            input.object_lock_retain_until_date.as_deref(),
+            input.object_lock_legal_hold_status.as_deref(),
+        )?);
+        let evictions = state.reserve(&self.capacity, object.data.len(), None)?;
+        state.insert_object(&input.bucket, input.key, object);
+        drop(state);
+        self.report(&evictions);
+
+        Ok(PutObjectOutput {
+            e_tag: Some(e_tag),
+            ..PutObjectOutput::default()
+        })
+    }
+
+    async fn restore_object(
+        &self,
+        input: RestoreObjectRequest,
+    ) -> S3StorageResult<RestoreObjectOutput, RestoreObjectError> {
+        versions::check_null_version(input.version_id.as_deref())?;
+        let _object = self.lock().object(&input.bucket, &input.key)?;
+        let err = code_error!(
+            InvalidObjectState,
+            "Restore is not allowed for the object's current storage class"
+        );
+        Err(err.into())
+    }
+
+    async fn upload_part(
+        &self,
+        input: UploadPartRequest,
+    ) -> S3StorageResult<UploadPartOutput, UploadPartError> {
+        let data = collect_body(input.body, self.capacity.max_object_size).await?;
+        let e_tag = format!("\"{}\"", Md5::digest(&data).apply(crypto::to_hex_string));
+        let mut state = self.lock();
+        match state.uploads.get(&input.upload_id) {
+            Some(upload) if upload.bucket == input.bucket && upload.key == input.key => {}
+            _ => {
+                let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+                return Err(err.into());
+            }
+        }
+        let evictions = state.reserve(&self.capacity, data.len(), None)?;
+        let part_number = input.part_number;
+        let replaced = state
+            .uploads
+            .get_mut(&input.upload_id)
+            .and_then(|upload| upload.parts.insert(part_number, data));
+        if let Some(replaced) = replaced {
+            state.used = state.used.saturating_sub(replaced.len());
+        }
+        drop(state);
+        self.report(&evictions);
+        Ok(UploadPartOutput {
+            e_tag: Some(e_tag),
+            ..UploadPartOutput::default()
+        })
+    }
+
+    async fn upload_part_copy(
+        &self,
+        input: UploadPartCopyRequest,
+    ) -> S3StorageResult<UploadPartCopyOutput, UploadPartCopyError> {
+        let copy_source = AmzCopySource::from_header_str(&input.copy_source)
+            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+        let (bucket, key) = match copy_source {
+            AmzCopySource::AccessPoint { .. } => {
+                return Err(
+                    code_error!(NotImplemented, "Access point is not supported yet.").into(),
+                )
+            }
+            AmzCopySource::Bucket {
+                bucket,
+                key,
+                version_id,
+            } => {
+                versions::check_null_version(version_id)?;
+                (bucket, key)
+            }
+        };
+
+        let mut state = self.lock();
+        match state.uploads.get(&input.upload_id) {
+            Some(upload) if upload.bucket == input.bucket && upload.key == input.key => {}
+            _ => {
+                let err = code_error!(NoSuchUpload, "The specified upload does not exist.");
+                return Err(err.into());
+            }
+        }
+        let src = state.touch(bucket, key)?;
+        CopySourceConditions::from_upload_part_copy(&input)
+            .check(Some(&src.e_tag), &src.last_modified)?;
+        let size = trace_try!(src.data.len().try_into());
+        let range = copy_source::byte_range(input.copy_source_range.as_deref(), size)?;
+        let start: usize = trace_try!(range.start.try_into());
+        let end: usize = trace_try!(range.end.try_into());
+        let data = src.data.slice(start..end);
+        if let Some(max) = self.capacity.max_object_size {
+            if data.len() > max {
+                return Err(object_too_large(max).into());
+            }
+        }
+
+        let e_tag = format!("\"{}\"", Md5::digest(&data).apply(crypto::to_hex_string));
+        let evictions = state.reserve(&self.capacity, data.len(), Some((bucket, key)))?;
+        let part_number = input.part_number;
+        let replaced = state
+            .uploads
+            .get_mut(&input.upload_id)
+            .and_then(|upload| upload.parts.insert(part_number, data));
+        if let Some(replaced) = replaced {
+            state.used = state.used.saturating_sub(replaced.len());
+        }
+        drop(state);
+        self.report(&evictions);
+        Ok(UploadPartCopyOutput {
+            copy_part_result: Some(CopyPartResult {
+                e_tag: Some(e_tag),
+                last_modified: Some(time::to_rfc3339(SystemTime::now())),
+            }),
+            ..UploadPartCopyOutput::default()
+        })
+    }
+
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expiry: Duration,
+    ) -> S3Result<Option<String>> {
+        let endpoint = match self.presign_endpoint {
+            Some(ref endpoint) => endpoint,
+            None => return Ok(None),
+        };
+        let exists = self
+            .lock()
+            .buckets
+            .get(bucket)
+            .map_or(false, |objects| objects.contains_key(key));
+        if !exists {
+            return Ok(None);
+        }
+        let mut url = format!("{}/{}/", endpoint.trim_end_matches('/'), bucket);
+        signature_v4::uri_encode(&mut url, key, false);
+        url.push_str("?X-Amz-Expires=");
+        url.push_str(&expiry.as_secs().to_string());
+        Ok(Some(url))
+    }
+
+    fn storage_config(&self) -> StorageConfig {
+        StorageConfig::new("memory")
+            .option("capacity", self.capacity)
+            .option("presign_endpoint", &self.presign_endpoint)
+            .option("snapshot", self.snapshot.as_ref().map(|file| &file.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::{CompletedMultipartUpload, CompletedPart};
+
+    use futures::executor::block_on;
+
+    fn try_put(
+        storage: &MemoryStorage,
+        key: &str,
+        content: &'static str,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            body: Some(content.as_bytes().to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        block_on(storage.put_object(input))
+    }
+
+    fn put(storage: &MemoryStorage, key: &str, content: &'static str) {
+        let _output = try_put(storage, key, content).unwrap();
+    }
+
+    fn create_bucket(storage: &MemoryStorage) {
+        let input = CreateBucketRequest {
+            bucket: "asd".into(),
+            ..CreateBucketRequest::default()
+        };
+        let _output = block_on(storage.create_bucket(input)).unwrap();
+    }
+
+    fn assert_denied<T: Debug, E: Debug>(ret: S3StorageResult<T, E>) {
+        match ret {
+            Err(S3StorageError::Other(e)) => {
+                assert_eq!(e.code(), S3ErrorCode::AccessDenied);
+            }
+            ret => panic!("expected AccessDenied: {:?}", ret),
+        }
+    }
+
+    fn head(
+        storage: &MemoryStorage,
+        key: &str,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        let input = HeadObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            ..HeadObjectRequest::default()
+        };
+        block_on(storage.head_object(input))
+    }
+
+    fn list(
+        storage: &MemoryStorage,
+        delimiter: Option<&str>,
+        max_keys: i64,
+        token: Option<String>,
+    ) -> ListObjectsV2Output {
+        let input = ListObjectsV2Request {
+            bucket: "asd".into(),
+            delimiter: delimiter.map(Into::into),
+            continuation_token: token,
+            max_keys: Some(max_keys),
+            ..ListObjectsV2Request::default()
+        };
+        block_on(storage.list_objects_v2(input)).unwrap()
+    }
+
+    fn keys(output: &ListObjectsV2Output) -> Vec<&str> {
+        let keys = output
+            .contents
+            .iter()
+            .flatten()
+            .filter_map(|o| o.key.as_deref());
+        let prefixes = output
+            .common_prefixes
+            .iter()
+            .flatten()
+            .filter_map(|p| p.prefix.as_deref());
+        keys.chain(prefixes).collect()
+    }
+
+    #[test]
+    fn memory_storage() {
+        let storage = MemoryStorage::new();
+        create_bucket(&storage);
+
+        for &key in &["a/1", "a/2", "b", "c/1", "d"] {
+            put(&storage, key, "Hello World!");
+        }
+
+        let page = list(&storage, None, 2, None);
+        assert_eq!(keys(&page), ["a/1", "a/2"]);
+        let page = list(&storage, None, 2, page.next_continuation_token);
+        assert_eq!(keys(&page), ["b", "c/1"]);
+        let page = list(&storage, None, 2, page.next_continuation_token);
+        assert_eq!(keys(&page), ["d"]);
+        assert_eq!(page.is_truncated, Some(false));
+
+        let page = list(&storage, Some("/"), 2, None);
+        assert_eq!(keys(&page), ["b", "a/"]);
+        let page = list(&storage, Some("/"), 2, page.next_continuation_token);
+        assert_eq!(keys(&page), ["d", "c/"]);
+
+        // a page which ends with a common prefix
+        let page = list(&storage, Some("/"), 1, None);
+        assert_eq!(keys(&page), ["a/"]);
+        let page = list(&storage, Some("/"), 1, page.next_continuation_token);
+        assert_eq!(keys(&page), ["b"]);
+
+        // multipart uploads
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "e".into(),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = block_on(storage.create_multipart_upload(input))
+            .unwrap()
+            .upload_id
+            .unwrap();
+        for (part_number, content) in [(1, "Hello"), (2, " World!")].iter().copied() {
+            let input = UploadPartRequest {
+                bucket: "asd".into(),
+                key: "e".into(),
+                upload_id: upload_id.clone(),
+                part_number,
+                body: Some(content.as_bytes().to_vec().into()),
+                ..UploadPartRequest::default()
+            };
+            let _output = block_on(storage.upload_part(input)).unwrap();
+        }
+        let input = ListPartsRequest {
+            bucket: "asd".into(),
+            key: "e".into(),
+            upload_id: upload_id.clone(),
+            ..ListPartsRequest::default()
+        };
+        let listed = block_on(storage.list_parts(input)).unwrap();
+        let sizes: Vec<_> = listed
+            .parts
+            .iter()
+            .flatten()
+            .map(|part| (part.part_number, part.size))
+            .collect();
+        assert_eq!(sizes, [(Some(1), Some(5)), (Some(2), Some(7))]);
+        let input = CompleteMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "e".into(),
+            upload_id: upload_id.clone(),
+            multipart_upload: Some(CompletedMultipartUpload {
+                parts: Some(vec![]),
+            }),
+            ..CompleteMultipartUploadRequest::default()
+        };
+        match block_on(storage.complete_multipart_upload(input)) {
+            Err(S3StorageError::Other(e)) => assert_eq!(e.code(), S3ErrorCode::MalformedXML),
+            ret => panic!("expected MalformedXML: {:?}", ret),
+        }
+        let parts = (1..=2)
+            .map(|part_number| CompletedPart {
+                e_tag: None,
+                part_number: Some(part_number),
+            })
+            .collect();
+        let input = CompleteMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "e".into(),
+            upload_id,
+            multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+            ..CompleteMultipartUploadRequest::default()
+        };
+        let output = block_on(storage.complete_multipart_upload(input)).unwrap();
+        assert_eq!(
+            output.e_tag.as_deref(),
+            Some("\"1b9cfd061df511915f089ff8ba327fd2-2\"")
+        );
+        let input = HeadObjectRequest {
+            bucket: "asd".into(),
+            key: "e".into(),
+            ..HeadObjectRequest::default()
+        };
+        let head = block_on(storage.head_object(input)).unwrap();
+        assert_eq!(head.e_tag, output.e_tag);
+        assert_eq!(head.content_length, Some(12));
+
+        // a bucket with objects can not be deleted
+        let input = DeleteBucketRequest {
+            bucket: "asd".into(),
+            expected_bucket_owner: None,
+        };
+        match block_on(storage.delete_bucket(input)) {
+            Err(S3StorageError::Other(e)) => {
+                assert_eq!(e.code(), crate::errors::S3ErrorCode::BucketNotEmpty);
+            }
+            _ => panic!("expected BucketNotEmpty"),
+        }
+    }
+
+    #[test]
+    fn capacity() {
+        let storage = MemoryStorage::with_capacity(MemoryCapacity {
+            max_total_bytes: Some(24),
+            max_object_size: Some(12),
+            evict: false,
+        });
+        create_bucket(&storage);
+
+        put(&storage, "a", "Hello World!");
+        assert_denied(try_put(&storage, "b", "Hello World!!"));
+        put(&storage, "b", "Hello World!");
+        assert_eq!(storage.usage().used_bytes, 24);
+        assert_denied(try_put(&storage, "c", "!"));
+
+        // the replaced object is still stored while the new one is written
+        assert_denied(try_put(&storage, "a", "Hello"));
+        let input = DeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            ..DeleteObjectRequest::default()
+        };
+        let _output = block_on(storage.delete_object(input)).unwrap();
+        put(&storage, "a", "Hello");
+        assert_eq!(storage.usage().used_bytes, 5);
+        assert_eq!(storage.usage().peak_bytes, 24);
+
+        // a copy is counted apart from its source
+        let copy = |key: &str| {
+            let input = CopyObjectRequest {
+                bucket: "asd".into(),
+                key: key.into(),
+                copy_source: "asd/a".into(),
+                ..CopyObjectRequest::default()
+            };
+            block_on(storage.copy_object(input))
+        };
+        let _output = copy("b").unwrap();
+        let _output = copy("c").unwrap();
+        assert_eq!(storage.usage().used_bytes, 15);
+
+        // the parts are counted until the object is completed
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "d".into(),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = block_on(storage.create_multipart_upload(input))
+            .unwrap()
+            .upload_id
+            .unwrap();
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "d".into(),
+            upload_id: upload_id.clone(),
+            part_number: 1,
+            body: Some(b"Hello".to_vec().into()),
+            ..UploadPartRequest::default()
+        };
+        let _output = block_on(storage.upload_part(input)).unwrap();
+        assert_eq!(storage.usage().used_bytes, 20);
+        let complete = || {
+            let parts = vec![CompletedPart {
+                e_tag: None,
+                part_number: Some(1),
+            }];
+            let input = CompleteMultipartUploadRequest {
+                bucket: "asd".into(),
+                key: "d".into(),
+                upload_id: upload_id.clone(),
+                multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+                ..CompleteMultipartUploadRequest::default()
+            };
+            block_on(storage.complete_multipart_upload(input))
+        };
+        assert_denied(complete());
+        let input = DeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "c".into(),
+            ..DeleteObjectRequest::default()
+        };
+        let _output = block_on(storage.delete_object(input)).unwrap();
+        let output = complete().unwrap();
+        assert_eq!(
+            output.e_tag.as_deref(),
+            Some("\"49c24cf3c5af9ba03cec39ee4aac4f77-1\"")
+        );
+        assert_eq!(storage.usage().used_bytes, 15);
+        assert!(storage.usage().peak_bytes <= 24);
+    }
+
+    #[test]
+    fn eviction() {
+        use std::sync::Arc;
+
+        let mut storage = MemoryStorage::with_capacity(MemoryCapacity {
+            max_total_bytes: Some(12),
+            max_object_size: None,
+            evict: true,
+        });
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let hook_evicted = Arc::clone(&evicted);
+        storage.set_eviction_hook(move |eviction| {
+            hook_evicted.lock().unwrap().push(eviction.key.clone());
+        });
+        create_bucket(&storage);
+
+        put(&storage, "a", "1234");
+        put(&storage, "b", "1234");
+        put(&storage, "c", "1234");
+        // a read makes "a" the most recently used
+        let _head = head(&storage, "a").unwrap();
+        put(&storage, "d", "12345678");
+        assert_eq!(*evicted.lock().unwrap(), ["b", "c"]);
+        let _head = head(&storage, "a").unwrap();
+        let _err = head(&storage, "b").unwrap_err();
+
+        // nothing is evicted for an object which can not fit anyway
+        assert_denied(try_put(&storage, "e", "1234567890123"));
+        assert_eq!(evicted.lock().unwrap().len(), 2);
+
+        let usage = storage.usage();
+        assert_eq!(usage.used_bytes, 12);
+        assert_eq!(usage.peak_bytes, 12);
+        assert_eq!(usage.evictions, 2);
+    }
+
+    #[test]
+    fn snapshot() {
+        let dir = std::env::temp_dir().join(format!("s3-memory-storage-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot");
+        let _ignored = std::fs::remove_file(&path);
+
+        let storage = MemoryStorage::with_snapshot(&path).unwrap();
+        create_bucket(&storage);
+        put(&storage, "a", "Hello");
+        let mut metadata = HashMap::new();
+        let _prev = metadata.insert("color".to_owned(), "blue".to_owned());
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "b/c".into(),
+            body: Some(b"Hello World!".to_vec().into()),
+            content_type: Some("text/plain".into()),
+            metadata: Some(metadata.clone()),
+            ..PutObjectRequest::default()
+        };
+        let e_tag = block_on(storage.put_object(input)).unwrap().e_tag;
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "d".into(),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = block_on(storage.create_multipart_upload(input))
+            .unwrap()
+            .upload_id
+            .unwrap();
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "d".into(),
+            upload_id: upload_id.clone(),
+            part_number: 1,
+            body: Some(b"Hello".to_vec().into()),
+            ..UploadPartRequest::default()
+        };
+        let _output = block_on(storage.upload_part(input)).unwrap();
+        // the storage is saved when it is dropped
+        drop(storage);
+        assert!(path.exists());
+
+        let storage = MemoryStorage::with_snapshot(&path).unwrap();
+        let head = head(&storage, "b/c").unwrap();
+        assert_eq!(head.e_tag, e_tag);
+        assert_eq!(head.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(head.metadata, Some(metadata));
+        assert_eq!(head.content_length, Some(12));
+        assert_eq!(keys(&list(&storage, None, 10, None)), ["a", "b/c"]);
+        assert_eq!(storage.usage().used_bytes, 22);
+
+        // the upload goes on
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "d".into(),
+            upload_id: upload_id.clone(),
+            part_number: 2,
+            body: Some(b" World!".to_vec().into()),
+            ..UploadPartRequest::default()
+        };
+        let _output = block_on(storage.upload_part(input)).unwrap();
+        let parts = (1..=2)
+            .map(|part_number| CompletedPart {
+                e_tag: None,
+                part_number: Some(part_number),
+            })
+            .collect();
+        let input = CompleteMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "d".into(),
+            upload_id,
+            multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+            ..CompleteMultipartUploadRequest::default()
+        };
+        let output = block_on(storage.complete_multipart_upload(input)).unwrap();
+        assert_eq!(
+            output.e_tag.as_deref(),
+            Some("\"1b9cfd061df511915f089ff8ba327fd2-2\"")
+        );
+        storage.snapshot().unwrap();
+        assert!(MemoryStorage::new().snapshot().is_err());
+        drop(storage);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,461 @@
+//! Snapshots of a [`MemoryStorage`](super::MemoryStorage)
+//!
+//! ## Format
+//!
+//! A snapshot starts with the magic `S3MEM\x01`, whose last byte is the version of the format,
+//! followed by the buckets and the multipart uploads. All integers are big-endian.
+//! A field is a `u32` length followed by the bytes,
+//! and an optional field is a `u8` flag followed by the field if the flag is 1.
+//!
+//! + buckets: `u32` count, then for each bucket its name field, creation date field and objects
+//! + objects: `u32` count, then for each object its key field, `ETag` field, last modified field,
+//!   optional `Content-Type` field, metadata and data
+//! + metadata: `u8` flag, then if the flag is 1, `u32` count and a name field and a value field for each entry
+//! + data: `u64` length followed by the bytes
+//! + uploads: `u32` count, then for each upload its id field, bucket field, key field,
+//!   optional `Content-Type` field, metadata and parts
+//! + parts: `u32` count, then for each part its number as `i64` and its data
+//!
+//! The tags, the ACLs, the CORS rules, the policies and the Object Lock configurations of the buckets,
+//! the ACLs, the part sizes, the retentions and the legal holds of the objects, and the locks of the uploads are not kept.
+//!
+//! A snapshot is written to a temporary file next to it, which is renamed over it when it is complete,
+//! so a crash leaves the previous snapshot.
+
+use super::{lock, MemoryObject, State, Upload};
+
+use crate::storages::object_lock::ObjectLock;
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+use hyper::body::Bytes;
+use tracing::{debug, error};
+
+/// magic of a snapshot file, ending with the version of the format
+const MAGIC: &[u8] = b"S3MEM\x01";
+
+/// a bucket with its creation date and objects in key order
+type SnapshotBucket = (String, String, Vec<(String, MemoryObject)>);
+
+/// the objects and uploads of a snapshot
+///
+/// The data is shared with the storage, so taking a snapshot does not copy it.
+#[derive(Debug, Default)]
+pub(super) struct Snapshot {
+    /// buckets with their creation dates and objects
+    pub(super) buckets: Vec<SnapshotBucket>,
+    /// multipart uploads by upload id
+    pub(super) uploads: Vec<(String, Upload)>,
+}
+
+impl Snapshot {
+    /// takes a snapshot of `state`
+    pub(super) fn take(state: &State) -> Self {
+        let buckets = state
+            .buckets
+            .iter()
+            .map(|(bucket, objects)| {
+                let date = state.bucket_dates.get(bucket).cloned().unwrap_or_default();
+                let objects = objects
+                    .iter()
+                    .map(|(key, object)| (key.clone(), object.clone()))
+                    .collect();
+                (bucket.clone(), date, objects)
+            })
+            .collect();
+        let mut uploads: Vec<_> = state
+            .uploads
+            .iter()
+            .map(|(upload_id, upload)| (upload_id.clone(), upload.clone()))
+            .collect();
+        uploads.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        Self { buckets, uploads }
+    }
+
+    /// restores a state, whose objects are used in key order
+    pub(super) fn restore(self) -> State {
+        let mut state = State::default();
+        for (bucket, date, objects) in self.buckets {
+            let _prev = state.buckets.insert(bucket.clone(), BTreeMap::new());
+            let _prev = state.bucket_dates.insert(bucket.clone(), date);
+            for (key, object) in objects {
+                state.used = state.used.saturating_add(object.data.len());
+                state.insert_object(&bucket, key, object);
+            }
+        }
+        for (upload_id, upload) in self.uploads {
+            let parts_len: usize = upload.parts.values().map(Bytes::len).sum();
+            state.used = state.used.saturating_add(parts_len);
+            let _prev = state.uploads.insert(upload_id, upload);
+        }
+        state.peak = state.used;
+        state
+    }
+
+    /// writes the snapshot to `path` atomically
+    pub(super) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let ret = File::create(&temp_path).and_then(|file| {
+            let mut writer = Writer(BufWriter::new(file));
+            self.write(&mut writer)?;
+            let file = writer
+                .0
+                .into_inner()
+                .map_err(io::IntoInnerError::into_error)?;
+            file.sync_all()
+        });
+        if let Err(err) = ret {
+            let _ignored = fs::remove_file(&temp_path);
+            return Err(err);
+        }
+        fs::rename(&temp_path, path)
+    }
+
+    /// reads a snapshot from `path`
+    pub(super) fn load(path: &Path) -> io::Result<Self> {
+        let mut reader = Reader(BufReader::new(File::open(path)?));
+        let mut magic = [0; MAGIC.len()];
+        reader.exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid_data("not a snapshot of a supported version"));
+        }
+
+        let mut snapshot = Self::default();
+        for _ in 0..reader.u32()? {
+            let bucket = reader.string()?;
+            let creation_date = reader.string()?;
+            let mut objects = Vec::new();
+            for _ in 0..reader.u32()? {
+                let key = reader.string()?;
+                let e_tag = reader.string()?;
+                let last_modified = reader.string()?;
+                let content_type = reader.optional_string()?;
+                let metadata = reader.metadata()?;
+                let data = reader.data()?;
+                let object = MemoryObject {
+                    data,
+                    e_tag,
+                    last_modified,
+                    content_type,
+                    metadata,
+                    acl: None,
+                    retention: None,
+                    legal_hold: None,
+                    part_sizes: Vec::new(),
+                    last_access: 0,
+                };
+                objects.push((key, object));
+            }
+            snapshot.buckets.push((bucket, creation_date, objects));
+        }
+        for _ in 0..reader.u32()? {
+            let upload_id = reader.string()?;
+            let bucket = reader.string()?;
+            let key = reader.string()?;
+            let content_type = reader.optional_string()?;
+            let metadata = reader.metadata()?;
+            let mut parts = BTreeMap::new();
+            for _ in 0..reader.u32()? {
+                let part_number = reader.i64()?;
+                let _prev = parts.insert(part_number, reader.data()?);
+            }
+            let upload = Upload {
+                bucket,
+                key,
+                content_type,
+                metadata,
+                lock: ObjectLock::default(),
+                parts,
+            };
+            snapshot.uploads.push((upload_id, upload));
+        }
+        Ok(snapshot)
+    }
+
+    /// writes the snapshot format
+    fn write<W: Write>(&self, w: &mut Writer<W>) -> io::Result<()> {
+        w.0.write_all(MAGIC)?;
+        w.count(self.buckets.len())?;
+        for &(ref bucket, ref date, ref objects) in &self.buckets {
+            w.field(bucket.as_bytes())?;
+            w.field(date.as_bytes())?;
+            w.count(objects.len())?;
+            for &(ref key, ref object) in objects {
+                w.field(key.as_bytes())?;
+                w.field(object.e_tag.as_bytes())?;
+                w.field(object.last_modified.as_bytes())?;
+                w.optional_field(object.content_type.as_deref())?;
+                w.metadata(object.metadata.as_ref())?;
+                w.data(&object.data)?;
+            }
+        }
+        w.count(self.uploads.len())?;
+        for &(ref upload_id, ref upload) in &self.uploads {
+            w.field(upload_id.as_bytes())?;
+            w.field(upload.bucket.as_bytes())?;
+            w.field(upload.key.as_bytes())?;
+            w.optional_field(upload.content_type.as_deref())?;
+            w.metadata(upload.metadata.as_ref())?;
+            w.count(upload.parts.len())?;
+            for (part_number, data) in &upload.parts {
+                w.0.write_all(&part_number.to_be_bytes())?;
+                w.data(data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The snapshot file of a storage
+#[derive(Debug)]
+pub(super) struct SnapshotFile {
+    /// the path of the snapshot
+    pub(super) path: PathBuf,
+    /// held while a snapshot is written, since the writes share the temporary file
+    saving: Mutex<()>,
+}
+
+impl SnapshotFile {
+    /// a snapshot file at `path`
+    pub(super) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            saving: Mutex::new(()),
+        }
+    }
+
+    /// writes a snapshot of `state`, which is locked only while the snapshot is taken
+    pub(super) fn save(&self, state: &Mutex<State>) -> io::Result<()> {
+        let _saving = lock(&self.saving);
+        let snapshot = Snapshot::take(&lock(state));
+        snapshot.save(&self.path)?;
+        debug!(path = %self.path.display(), "saved a snapshot");
+        Ok(())
+    }
+}
+
+/// Writes snapshots periodically until it is dropped
+#[derive(Debug)]
+pub(super) struct SnapshotTimer {
+    /// wakes the thread up when it is dropped
+    _stop: mpsc::Sender<()>,
+}
+
+impl SnapshotTimer {
+    /// Spawns a thread which writes a snapshot of `state` every `interval` while the state is alive
+    ///
+    /// The storages do not depend on a runtime, so the timer is a thread, like [`time::sleep`](crate::utils::time::sleep).
+    pub(super) fn spawn(
+        file: Arc<SnapshotFile>,
+        state: Weak<Mutex<State>>,
+        interval: Duration,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let _handle = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let state = match state.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+            if let Err(err) = file.save(&state) {
+                error!(%err, path = %file.path.display(), "failed to save a snapshot");
+            }
+        });
+        Self { _stop: stop }
+    }
+}
+
+/// an `InvalidData` error
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// writes the snapshot format
+struct Writer<W>(W);
+
+impl<W: Write> Writer<W> {
+    /// writes a `u32` count
+    fn count(&mut self, n: usize) -> io::Result<()> {
+        let n = u32::try_from(n).map_err(|_err| invalid_data("too many entries"))?;
+        self.0.write_all(&n.to_be_bytes())
+    }
+
+    /// writes a length-prefixed field
+    fn field(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(bytes.len()).map_err(|_err| invalid_data("field too long"))?;
+        self.0.write_all(&len.to_be_bytes())?;
+        self.0.write_all(bytes)
+    }
+
+    /// writes an optional field
+    fn optional_field(&mut self, field: Option<&str>) -> io::Result<()> {
+        match field {
+            Some(field) => {
+                self.0.write_all(&[1])?;
+                self.field(field.as_bytes())
+            }
+            None => self.0.write_all(&[0]),
+        }
+    }
+
+    /// writes user metadata
+    fn metadata(&mut self, metadata: Option<&HashMap<String, String>>) -> io::Result<()> {
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => return self.0.write_all(&[0]),
+        };
+        self.0.write_all(&[1])?;
+        self.count(metadata.len())?;
+        for (name, value) in metadata {
+            self.field(name.as_bytes())?;
+            self.field(value.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// writes data with a `u64` length
+    fn data(&mut self, data: &[u8]) -> io::Result<()> {
+        let len = u64::try_from(data.len()).map_err(|_err| invalid_data("data too long"))?;
+        self.0.write_all(&len.to_be_bytes())?;
+        self.0.write_all(data)
+    }
+}
+
+/// reads the snapshot format
+struct Reader<R>(R);
+
+impl<R: Read> Reader<R> {
+    /// fills `buf`, treating the end of the file as a truncated snapshot
+    fn exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.0.read_exact(buf).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                invalid_data("unexpected end of snapshot")
+            } else {
+                err
+            }
+        })
+    }
+
+    /// reads `n` bytes without trusting `n` for the allocation
+    fn bytes(&mut self, n: u64) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let _len = self.0.by_ref().take(n).read_to_end(&mut buf)?;
+        if u64::try_from(buf.len()).ok() != Some(n) {
+            return Err(invalid_data("unexpected end of snapshot"));
+        }
+        Ok(buf)
+    }
+
+    /// reads a `u8` flag
+    fn flag(&mut self) -> io::Result<bool> {
+        let mut byte = [0; 1];
+        self.exact(&mut byte)?;
+        match byte {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(invalid_data("invalid flag")),
+        }
+    }
+
+    /// reads a big-endian `u32`
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.exact(&mut bytes)?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    /// reads a big-endian `i64`
+    fn i64(&mut self) -> io::Result<i64> {
+        let mut bytes = [0; 8];
+        self.exact(&mut bytes)?;
+        Ok(i64::from_be_bytes(bytes))
+    }
+
+    /// reads a utf-8 field
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()?;
+        let bytes = self.bytes(len.into())?;
+        String::from_utf8(bytes).map_err(|_err| invalid_data("invalid utf-8"))
+    }
+
+    /// reads an optional utf-8 field
+    fn optional_string(&mut self) -> io::Result<Option<String>> {
+        if self.flag()? {
+            self.string().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// reads user metadata
+    fn metadata(&mut self) -> io::Result<Option<HashMap<String, String>>> {
+        if !self.flag()? {
+            return Ok(None);
+        }
+        let mut metadata = HashMap::new();
+        for _ in 0..self.u32()? {
+            let name = self.string()?;
+            let value = self.string()?;
+            let _prev = metadata.insert(name, value);
+        }
+        Ok(Some(metadata))
+    }
+
+    /// reads data with a `u64` length
+    fn data(&mut self) -> io::Result<Bytes> {
+        let mut len = [0; 8];
+        self.exact(&mut len)?;
+        self.bytes(u64::from_be_bytes(len)).map(Bytes::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated() {
+        let dir = std::env::temp_dir().join(format!("s3-memory-snapshot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot");
+
+        let mut state = State::default();
+        let _prev = state.buckets.insert("asd".into(), BTreeMap::new());
+        let object = MemoryObject::new(Bytes::from_static(b"Hello World!"), None, None);
+        state.insert_object("asd", "qwe".into(), object);
+        Snapshot::take(&state).save(&path).unwrap();
+
+        let restored = Snapshot::load(&path).unwrap().restore();
+        assert_eq!(restored.used, 12);
+        assert!(!dir.join("snapshot.tmp").exists());
+
+        // a snapshot which is cut in a count or in the data is rejected
+        let bytes = fs::read(&path).unwrap();
+        for &cut in &[3, 7] {
+            let mut truncated = bytes.clone();
+            truncated.truncate(bytes.len().saturating_sub(cut));
+            fs::write(&path, &truncated).unwrap();
+            let err = Snapshot::load(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        fs::write(&path, b"S3MEM\x02").unwrap();
+        let err = Snapshot::load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
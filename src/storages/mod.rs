@@ -1,3 +1,14 @@
 //! S3 storages
 
+pub mod copy_source;
+#[cfg(feature = "test-utils")]
+pub mod fault_injector;
 pub mod fs;
+pub mod listing;
+#[cfg(feature = "test-utils")]
+pub mod memory;
+pub mod object_lock;
+pub mod object_range;
+pub mod uploads;
+pub mod versions;
+pub mod wrappers;
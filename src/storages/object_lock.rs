@@ -0,0 +1,371 @@
+//! Enforcement of [Object Lock](https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-lock.html) by a storage
+//!
+//! + An object is protected while its retain-until date is in the future, or while its legal hold is `ON`.
+//!   A protected object can be neither deleted nor overwritten, which is `AccessDenied`.
+//! + A `GOVERNANCE` retention does not protect an object deleted with `x-amz-bypass-governance-retention: true`.
+//!   A `COMPLIANCE` retention and a legal hold always do.
+//! + A `COMPLIANCE` retention in effect can not be removed, shortened or changed to `GOVERNANCE`.
+//!   A `GOVERNANCE` retention in effect can only be removed or shortened with the bypass.
+//! + A written object gets the retention and the legal hold of its `x-amz-object-lock-*` headers,
+//!   or else the default retention of its bucket.
+
+use crate::dto::{ObjectLockConfiguration, ObjectLockLegalHold, ObjectLockRetention};
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+
+use std::convert::TryInto;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusoto_s3::S3Error as ErrorEntry;
+
+/// the retention mode which can be bypassed
+const GOVERNANCE: &str = "GOVERNANCE";
+
+/// the retention mode which can not be bypassed
+const COMPLIANCE: &str = "COMPLIANCE";
+
+/// the legal hold status which protects an object
+const ON: &str = "ON";
+
+/// the legal hold statuses
+const STATUSES: &[&str] = &[ON, "OFF"];
+
+/// seconds of a day of a default retention
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// days of a year of a default retention
+const DAYS_PER_YEAR: i64 = 365;
+
+/// the message of a protected object
+const PROTECTED: &str = "Access Denied because object protected by object lock.";
+
+/// The retention and the legal hold of a written object
+#[derive(Debug, Clone, Default)]
+pub struct ObjectLock {
+    /// `Retention`
+    pub retention: Option<ObjectLockRetention>,
+    /// `LegalHold`
+    pub legal_hold: Option<ObjectLockLegalHold>,
+}
+
+/// the error of a protected object
+fn protected() -> S3Error {
+    code_error!(AccessDenied, PROTECTED)
+}
+
+/// the retain-until date of a retention, which is `None` if it is missing or invalid
+fn retain_until(retention: &ObjectLockRetention) -> Option<DateTime<Utc>> {
+    let date = retention.retain_until_date.as_deref()?;
+    let date = DateTime::parse_from_rfc3339(date).ok()?;
+    Some(date.with_timezone(&Utc))
+}
+
+/// whether a retention is in effect at `now`, which it is if its date is invalid
+fn is_retained(retention: &ObjectLockRetention, now: DateTime<Utc>) -> bool {
+    retention.retain_until_date.is_some() && retain_until(retention).map_or(true, |date| date > now)
+}
+
+/// Returns whether an object with a retention and a legal hold status is protected at `now`
+#[must_use]
+pub fn is_protected(
+    retention: Option<&ObjectLockRetention>,
+    legal_hold: Option<&str>,
+    bypass_governance: bool,
+    now: DateTime<Utc>,
+) -> bool {
+    let is_bypassed = |retention: &ObjectLockRetention| {
+        bypass_governance && retention.mode.as_deref() == Some(GOVERNANCE)
+    };
+    let is_retained = retention.map_or(false, |retention| {
+        is_retained(retention, now) && !is_bypassed(retention)
+    });
+    is_retained || legal_hold == Some(ON)
+}
+
+/// Checks that an object with a retention and a legal hold status can be deleted or overwritten at `now`
+///
+/// # Errors
+/// Returns `AccessDenied` if the object is protected
+pub fn check_unprotected(
+    retention: Option<&ObjectLockRetention>,
+    legal_hold: Option<&str>,
+    bypass_governance: bool,
+    now: DateTime<Utc>,
+) -> S3Result<()> {
+    if is_protected(retention, legal_hold, bypass_governance, now) {
+        return Err(protected());
+    }
+    Ok(())
+}
+
+/// Returns the `DeleteObjects` error of a protected object
+#[must_use]
+pub fn protected_entry(key: String) -> ErrorEntry {
+    ErrorEntry {
+        key: Some(key),
+        code: Some(S3ErrorCode::AccessDenied.as_static_str().to_owned()),
+        message: Some(PROTECTED.to_owned()),
+        ..ErrorEntry::default()
+    }
+}
+
+/// Checks that the retention of an object can be replaced at `now`
+///
+/// # Errors
+/// Returns `AccessDenied` if the current retention is in effect and the new one removes or shortens it,
+/// or changes `COMPLIANCE` to `GOVERNANCE`
+pub fn check_retention_change(
+    current: Option<&ObjectLockRetention>,
+    new: Option<&ObjectLockRetention>,
+    bypass_governance: bool,
+    now: DateTime<Utc>,
+) -> S3Result<()> {
+    let current = match current {
+        Some(current) if is_retained(current, now) => current,
+        _ => return Ok(()),
+    };
+    let is_compliance = current.mode.as_deref() == Some(COMPLIANCE);
+    if !is_compliance && bypass_governance {
+        return Ok(());
+    }
+    let new = new.ok_or_else(protected)?;
+    if is_compliance && new.mode.as_deref() != Some(COMPLIANCE) {
+        return Err(protected());
+    }
+    match (retain_until(current), retain_until(new)) {
+        (Some(current), Some(new)) if new >= current => Ok(()),
+        _ => Err(protected()),
+    }
+}
+
+/// the default retention of a bucket, which starts at `now`
+fn default_retention(
+    config: &ObjectLockConfiguration,
+    now: DateTime<Utc>,
+) -> S3Result<Option<ObjectLockRetention>> {
+    let retention = match config
+        .rule
+        .as_ref()
+        .and_then(|rule| rule.default_retention.as_ref())
+    {
+        Some(retention) => retention,
+        None => return Ok(None),
+    };
+    let days = match (retention.days, retention.years) {
+        (Some(days), _) => Some(days),
+        (None, Some(years)) => years.checked_mul(DAYS_PER_YEAR),
+        (None, None) => return Ok(None),
+    };
+    let date = days
+        .and_then(|days| days.try_into().ok())
+        .and_then(|days: u64| days.checked_mul(SECS_PER_DAY))
+        .and_then(|secs| chrono::Duration::from_std(Duration::from_secs(secs)).ok())
+        .and_then(|period| now.checked_add_signed(period))
+        .ok_or_else(|| code_error!(InvalidArgument, "Default retention period is too large"))?;
+    Ok(Some(ObjectLockRetention {
+        mode: retention.mode.clone(),
+        retain_until_date: Some(date.to_rfc3339_opts(SecondsFormat::Millis, true)),
+    }))
+}
+
+/// Resolves the lock of an object written at `now` from its `x-amz-object-lock-*` headers
+/// and the Object Lock configuration of its bucket
+///
+/// # Errors
+/// + Returns `InvalidRequest` if the headers are given and the bucket does not have Object Lock enabled
+/// + Returns `InvalidArgument` if only one of the mode and the retain-until date is given,
+///   if the mode or the legal hold status is unknown, or if the retain-until date is not in the future
+pub fn resolve_object_lock(
+    mode: Option<&str>,
+    retain_until_date: Option<&str>,
+    legal_hold: Option<&str>,
+    config: Option<&ObjectLockConfiguration>,
+    now: DateTime<Utc>,
+) -> S3Result<ObjectLock> {
+    let config = match config {
+        Some(config) => config,
+        None if mode.is_none() && retain_until_date.is_none() && legal_hold.is_none() => {
+            return Ok(ObjectLock::default())
+        }
+        None => {
+            return Err(invalid_request!(
+                "Bucket is missing Object Lock Configuration"
+            ))
+        }
+    };
+
+    let retention = match (mode, retain_until_date) {
+        (None, None) => default_retention(config, now)?,
+        (Some(mode), Some(retain_until_date)) => {
+            if ![GOVERNANCE, COMPLIANCE].contains(&mode) {
+                return Err(code_error!(InvalidArgument, "Unknown wormMode directive."));
+            }
+            let retention = ObjectLockRetention {
+                mode: Some(mode.to_owned()),
+                retain_until_date: Some(retain_until_date.to_owned()),
+            };
+            if retain_until(&retention).map_or(true, |date| date <= now) {
+                return Err(code_error!(
+                    InvalidArgument,
+                    "The retain until date must be in the future!"
+                ));
+            }
+            Some(retention)
+        }
+        _ => return Err(code_error!(
+            InvalidArgument,
+            "x-amz-object-lock-retain-until-date and x-amz-object-lock-mode must both be supplied"
+        )),
+    };
+
+    if let Some(status) = legal_hold {
+        if !STATUSES.contains(&status) {
+            return Err(code_error!(
+                InvalidArgument,
+                "Legal Hold must be either of 'ON' or 'OFF'"
+            ));
+        }
+    }
+
+    Ok(ObjectLock {
+        retention,
+        legal_hold: legal_hold.map(|status| ObjectLockLegalHold {
+            status: Some(status.to_owned()),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::{DefaultRetention, ObjectLockRule};
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2021-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn retention(mode: &str, retain_until_date: &str) -> ObjectLockRetention {
+        ObjectLockRetention {
+            mode: Some(mode.to_owned()),
+            retain_until_date: Some(retain_until_date.to_owned()),
+        }
+    }
+
+    fn config(days: Option<i64>, years: Option<i64>) -> ObjectLockConfiguration {
+        ObjectLockConfiguration {
+            object_lock_enabled: Some("Enabled".to_owned()),
+            rule: Some(ObjectLockRule {
+                default_retention: Some(DefaultRetention {
+                    days,
+                    mode: Some(GOVERNANCE.to_owned()),
+                    years,
+                }),
+            }),
+        }
+    }
+
+    #[test]
+    fn protection() {
+        let future = "2021-06-02T00:00:00Z";
+        let past = "2021-05-31T00:00:00Z";
+        let compliance = retention(COMPLIANCE, future);
+        let governance = retention(GOVERNANCE, future);
+
+        assert!(is_protected(Some(&compliance), None, true, now()));
+        assert!(is_protected(Some(&governance), None, false, now()));
+        assert!(!is_protected(Some(&governance), None, true, now()));
+        assert!(!is_protected(
+            Some(&retention(COMPLIANCE, past)),
+            None,
+            false,
+            now()
+        ));
+        assert!(is_protected(
+            Some(&retention(COMPLIANCE, "tomorrow")),
+            None,
+            false,
+            now()
+        ));
+        assert!(is_protected(None, Some(ON), true, now()));
+        assert!(!is_protected(None, Some("OFF"), false, now()));
+
+        let err = check_unprotected(None, Some(ON), false, now()).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::AccessDenied);
+        let entry = protected_entry("a".to_owned());
+        assert_eq!(entry.code.as_deref(), Some("AccessDenied"));
+    }
+
+    #[test]
+    fn retention_changes() {
+        let change = |current: &ObjectLockRetention, new: Option<&ObjectLockRetention>, bypass| {
+            check_retention_change(Some(current), new, bypass, now()).map_err(|err| err.code())
+        };
+        let denied = Err(S3ErrorCode::AccessDenied);
+        let compliance = retention(COMPLIANCE, "2021-06-02T00:00:00Z");
+        let longer = retention(COMPLIANCE, "2021-06-03T00:00:00Z");
+        let shorter = retention(COMPLIANCE, "2021-06-01T12:00:00Z");
+        let governance = retention(GOVERNANCE, "2021-06-03T00:00:00Z");
+
+        assert_eq!(change(&compliance, Some(&longer), false), Ok(()));
+        assert_eq!(change(&compliance, Some(&shorter), true), denied);
+        assert_eq!(change(&compliance, Some(&governance), true), denied);
+        assert_eq!(change(&compliance, None, true), denied);
+
+        let governance = retention(GOVERNANCE, "2021-06-02T00:00:00Z");
+        assert_eq!(change(&governance, Some(&shorter), false), denied);
+        assert_eq!(change(&governance, Some(&shorter), true), Ok(()));
+        assert_eq!(change(&governance, Some(&longer), false), Ok(()));
+        assert_eq!(change(&governance, None, true), Ok(()));
+
+        let expired = retention(COMPLIANCE, "2021-05-31T00:00:00Z");
+        assert_eq!(change(&expired, None, false), Ok(()));
+    }
+
+    #[test]
+    fn resolution() {
+        let resolve = |mode, date, legal_hold, config: Option<&ObjectLockConfiguration>| {
+            resolve_object_lock(mode, date, legal_hold, config, now()).map_err(|err| err.code())
+        };
+
+        let lock = resolve(None, None, None, None).unwrap();
+        assert!(lock.retention.is_none() && lock.legal_hold.is_none());
+        assert_eq!(
+            resolve(None, None, Some(ON), None).unwrap_err(),
+            S3ErrorCode::InvalidRequest
+        );
+
+        let days = config(Some(2), None);
+        let lock = resolve(None, None, Some(ON), Some(&days)).unwrap();
+        let expected = retention(GOVERNANCE, "2021-06-03T00:00:00.000Z");
+        assert_eq!(lock.retention, Some(expected));
+        assert_eq!(lock.legal_hold.and_then(|h| h.status).as_deref(), Some(ON));
+
+        let years = config(None, Some(1));
+        let lock = resolve(None, None, None, Some(&years)).unwrap();
+        let expected = retention(GOVERNANCE, "2022-06-01T00:00:00.000Z");
+        assert_eq!(lock.retention, Some(expected));
+
+        let date = "2021-06-05T00:00:00Z";
+        let lock = resolve(Some(COMPLIANCE), Some(date), None, Some(&days)).unwrap();
+        assert_eq!(lock.retention, Some(retention(COMPLIANCE, date)));
+
+        let huge = config(Some(i64::MAX), None);
+        for (mode, date, legal_hold, config) in &[
+            (None, None, None, &huge),
+            (Some(COMPLIANCE), None, None, &days),
+            (Some("LOCKED"), Some(date), None, &days),
+            (Some(COMPLIANCE), Some("2021-05-31T00:00:00Z"), None, &days),
+            (None, None, Some("on"), &days),
+        ] {
+            assert_eq!(
+                resolve(*mode, *date, *legal_hold, Some(config)).unwrap_err(),
+                S3ErrorCode::InvalidArgument,
+                "{:?}",
+                (mode, date, legal_hold)
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Byte ranges of [`GetObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html)
+//!
+//! A storage reads the bytes of the `Range` of a request, which are all of them without one.
+//! A range is `bytes=first-last`, `bytes=first-` or `bytes=-length`, and its last byte is cut at the end.
+//! It is rejected with `InvalidRange` if none of its bytes is in the object,
+//! and with `NotImplemented` if the header has several ranges.
+//!
+//! A `partNumber` selects the bytes of a part instead, where an object which is not uploaded in parts has one part.
+//! It is rejected with `InvalidPartNumber` if it is beyond the parts of the object.
+//!
+//! The response of a partial read has `Content-Range: bytes first-last/size`.
+
+use crate::errors::{S3Error, S3ErrorCode, S3Result};
+use crate::headers::Range;
+
+use std::convert::TryFrom;
+
+/// The bytes of an object to read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectRange {
+    /// the offsets of the bytes
+    pub bytes: std::ops::Range<u64>,
+    /// `Content-Range`, which is set if the read is partial
+    pub content_range: Option<String>,
+}
+
+impl ObjectRange {
+    /// the number of bytes to read
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.bytes.end.saturating_sub(self.bytes.start)
+    }
+
+    /// whether there is no byte to read
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// whether only some bytes of the object are read
+    #[must_use]
+    pub const fn is_partial(&self) -> bool {
+        self.content_range.is_some()
+    }
+}
+
+/// Parses `Range`, which does not depend on the object
+///
+/// # Errors
+/// Returns `NotImplemented` if there are several ranges, or `InvalidArgument` if the range is malformed
+pub fn parse_range(range: &str) -> S3Result<Range> {
+    Range::from_header_str(range).map_err(|err| {
+        if err.is_multiple() {
+            return code_error!(
+                NotImplemented,
+                "A header you provided implies functionality that is not implemented: multiple ranges"
+            );
+        }
+        code_error!(
+            InvalidArgument,
+            "The Range value must be of the form bytes=first-last, bytes=first- or bytes=-length",
+            err
+        )
+    })
+}
+
+/// The bytes of an object of `size` bytes to read, which are all of them without a range
+///
+/// # Errors
+/// Returns an `Err` if the range is invalid, see [`parse_range`],
+/// or `InvalidRange` with the size of the object if the range is not satisfiable
+pub fn byte_range(range: Option<&str>, size: u64) -> S3Result<ObjectRange> {
+    let header = match range {
+        Some(header) => header,
+        None => {
+            return Ok(ObjectRange {
+                bytes: 0..size,
+                content_range: None,
+            })
+        }
+    };
+    let bytes = parse_range(header)?.satisfy(size).ok_or_else(|| {
+        S3Error::from_code(S3ErrorCode::InvalidRange)
+            .message("The requested range is not satisfiable")
+            .range_requested(header)
+            .actual_object_size(size)
+            .finish()
+    })?;
+    let content_range = format!(
+        "bytes {}-{}/{}",
+        bytes.start,
+        bytes.end.saturating_sub(1),
+        size
+    );
+    Ok(ObjectRange {
+        bytes,
+        content_range: Some(content_range),
+    })
+}
+
+/// The bytes of the part `part_number` of an object of `size` bytes,
+/// whose parts have `part_sizes` bytes, or which is one part without them
+///
+/// # Errors
+/// Returns `InvalidPartNumber` if the object has fewer parts
+pub fn part_range(part_number: i64, part_sizes: &[u64], size: u64) -> S3Result<ObjectRange> {
+    let whole = [size];
+    let part_sizes = if part_sizes.is_empty() {
+        &whole
+    } else {
+        part_sizes
+    };
+    let index = usize::try_from(part_number.saturating_sub(1)).ok();
+    let part_size = match index.and_then(|index| part_sizes.get(index)) {
+        Some(&part_size) => part_size,
+        None => {
+            return Err(code_error!(
+                InvalidPartNumber,
+                "The requested partnumber is not satisfiable"
+            ))
+        }
+    };
+    let start = part_sizes
+        .iter()
+        .take(index.unwrap_or_default())
+        .fold(0, |start: u64, &size| start.saturating_add(size));
+    let end = start.saturating_add(part_size);
+    // an empty part is only the whole of an empty object
+    let content_range = Some(end)
+        .filter(|&end| end > start)
+        .map(|end| format!("bytes {}-{}/{}", start, end.saturating_sub(1), size));
+    Ok(ObjectRange {
+        bytes: start..end,
+        content_range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        let full = byte_range(None, 10).unwrap();
+        assert_eq!(full.bytes, 0..10);
+        assert!(!full.is_partial());
+
+        let read = |range, size| {
+            let range = byte_range(Some(range), size).unwrap();
+            (range.bytes, range.content_range.unwrap())
+        };
+        assert_eq!(read("bytes=0-3", 10), (0..4, "bytes 0-3/10".to_owned()));
+        assert_eq!(read("bytes=2-100", 10), (2..10, "bytes 2-9/10".to_owned()));
+        assert_eq!(read("bytes=4-", 10), (4..10, "bytes 4-9/10".to_owned()));
+        assert_eq!(read("bytes=-3", 10), (7..10, "bytes 7-9/10".to_owned()));
+        assert_eq!(read("bytes=0-9", 10), (0..10, "bytes 0-9/10".to_owned()));
+
+        let code = |range: &str, size| byte_range(Some(range), size).unwrap_err().code();
+        assert_eq!(code("bytes=10-20", 10), S3ErrorCode::InvalidRange);
+        assert_eq!(code("bytes=-0", 10), S3ErrorCode::InvalidRange);
+        assert_eq!(code("bytes=0-0", 0), S3ErrorCode::InvalidRange);
+        assert_eq!(code("bytes=5-2", 10), S3ErrorCode::InvalidArgument);
+        assert_eq!(code("bytes=0-1,4-5", 10), S3ErrorCode::NotImplemented);
+    }
+
+    #[test]
+    fn parts() {
+        let read = |part_number, part_sizes: &[u64], size| {
+            let range = part_range(part_number, part_sizes, size).unwrap();
+            (range.bytes, range.content_range)
+        };
+        let sizes = [5, 5, 2];
+        assert_eq!(read(1, &sizes, 12), (0..5, Some("bytes 0-4/12".to_owned())));
+        assert_eq!(
+            read(2, &sizes, 12),
+            (5..10, Some("bytes 5-9/12".to_owned()))
+        );
+        assert_eq!(
+            read(3, &sizes, 12),
+            (10..12, Some("bytes 10-11/12".to_owned()))
+        );
+        assert_eq!(read(1, &[], 12), (0..12, Some("bytes 0-11/12".to_owned())));
+        assert_eq!(read(1, &[], 0), (0..0, None));
+
+        let code = |part_number, part_sizes: &[u64]| {
+            part_range(part_number, part_sizes, 12).unwrap_err().code()
+        };
+        assert_eq!(code(4, &sizes), S3ErrorCode::InvalidPartNumber);
+        assert_eq!(code(2, &[]), S3ErrorCode::InvalidPartNumber);
+        assert_eq!(code(0, &sizes), S3ErrorCode::InvalidPartNumber);
+    }
+}
@@ -0,0 +1,246 @@
+//! Ordering and pagination of [`ListMultipartUploads`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListMultipartUploads.html)
+//!
+//! A storage supplies the uploads of a bucket in any order, and [`list_uploads`] cuts a page like S3 does:
+//!
+//! + Uploads are ordered by key, then by initiation time, oldest first.
+//! + `key-marker` alone resumes after all the uploads of that key.
+//!   With `upload-id-marker`, it resumes after that upload, in the middle of the key.
+//!   If the upload is gone, such as aborted, it resumes with the uploads of the key whose ids are greater.
+//! + `upload-id-marker` is ignored without `key-marker`.
+//! + A truncated page returns the key and the upload id of its last entry as the next markers.
+//! + With a delimiter, the keys under a common prefix are rolled up into it, which counts as one entry.
+
+use crate::dto::{
+    CommonPrefix, ListMultipartUploadsOutput, ListMultipartUploadsRequest, MultipartUpload,
+};
+
+use std::convert::TryInto;
+
+/// Max uploads of a listing page
+const MAX_UPLOADS: i64 = 1000;
+
+/// the order of uploads: key, initiation time and upload id
+fn order(upload: &MultipartUpload) -> (&str, &str, &str) {
+    (
+        upload.key.as_deref().unwrap_or(""),
+        upload.initiated.as_deref().unwrap_or(""),
+        upload.upload_id.as_deref().unwrap_or(""),
+    )
+}
+
+/// Lists a page of multipart uploads
+///
+/// `uploads` may be in any order, and their prefix is not required to match:
+/// they are sorted and filtered here.
+#[must_use]
+pub fn list_uploads<I>(input: ListMultipartUploadsRequest, uploads: I) -> ListMultipartUploadsOutput
+where
+    I: IntoIterator<Item = MultipartUpload>,
+{
+    let prefix = input.prefix.as_deref().unwrap_or("");
+    let delimiter = input.delimiter.as_deref().filter(|d| !d.is_empty());
+    let key_marker = input.key_marker.as_deref();
+    let upload_id_marker = key_marker.and(input.upload_id_marker.as_deref());
+    let max_uploads = input
+        .max_uploads
+        .unwrap_or(MAX_UPLOADS)
+        .clamp(0, MAX_UPLOADS);
+    let limit: usize = max_uploads.try_into().unwrap_or(0);
+
+    let mut sorted: Vec<MultipartUpload> = uploads
+        .into_iter()
+        .filter(|upload| {
+            upload
+                .key
+                .as_deref()
+                .map_or(false, |k| k.starts_with(prefix))
+        })
+        .collect();
+    sorted.sort_by(|lhs, rhs| order(lhs).cmp(&order(rhs)));
+
+    // the position of the upload of the markers, if it is still listed
+    let resumed = upload_id_marker.and_then(|marker| {
+        sorted.iter().position(|upload| {
+            upload.key.as_deref() == key_marker && upload.upload_id.as_deref() == Some(marker)
+        })
+    });
+
+    let mut page = Vec::new();
+    let mut common_prefixes: Vec<CommonPrefix> = Vec::new();
+    let mut count: usize = 0;
+    let mut is_truncated = false;
+    // the key (or prefix) and the upload id of the last entry
+    let mut last: Option<(String, Option<String>)> = None;
+
+    for (i, upload) in sorted.into_iter().enumerate() {
+        let key = upload.key.clone().unwrap_or_default();
+        let skipped = match key_marker {
+            Some(marker) if key.as_str() < marker => true,
+            Some(marker) if key == marker => upload_id_marker.map_or(true, |id_marker| {
+                resumed.map_or_else(
+                    || upload.upload_id.as_deref().unwrap_or("") <= id_marker,
+                    |pos| i <= pos,
+                )
+            }),
+            // the last page may end with a common prefix
+            Some(marker) => {
+                delimiter.map_or(false, |d| marker.ends_with(d) && key.starts_with(marker))
+            }
+            None => false,
+        };
+        if skipped {
+            continue;
+        }
+
+        let common_prefix = delimiter.and_then(|d| {
+            let rest = key.get(prefix.len()..).unwrap_or("");
+            let end = rest.find(d)?.saturating_add(d.len());
+            key.get(..prefix.len().saturating_add(end))
+        });
+        if let Some(common_prefix) = common_prefix {
+            let rolled_up = common_prefixes
+                .last()
+                .and_then(|last| last.prefix.as_deref())
+                == Some(common_prefix);
+            if rolled_up {
+                continue;
+            }
+        }
+        if count == limit {
+            is_truncated = true;
+            break;
+        }
+        count = count.saturating_add(1);
+        if let Some(common_prefix) = common_prefix {
+            common_prefixes.push(CommonPrefix {
+                prefix: Some(common_prefix.to_owned()),
+            });
+            last = Some((common_prefix.to_owned(), None));
+        } else {
+            last = Some((key, upload.upload_id.clone()));
+            page.push(upload);
+        }
+    }
+
+    let (next_key_marker, next_upload_id_marker) = match last {
+        Some((key, upload_id)) if is_truncated => (Some(key), upload_id),
+        _ => (None, None),
+    };
+
+    ListMultipartUploadsOutput {
+        bucket: Some(input.bucket),
+        common_prefixes: if common_prefixes.is_empty() {
+            None
+        } else {
+            Some(common_prefixes)
+        },
+        delimiter: input.delimiter,
+        encoding_type: input.encoding_type,
+        is_truncated: Some(is_truncated),
+        key_marker: input.key_marker,
+        max_uploads: Some(max_uploads),
+        next_key_marker,
+        next_upload_id_marker,
+        prefix: input.prefix,
+        upload_id_marker: input.upload_id_marker,
+        uploads: Some(page),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(key: &str, upload_id: &str, initiated: &str) -> MultipartUpload {
+        MultipartUpload {
+            initiated: Some(format!("2021-06-0{}T10:00:00.000Z", initiated)),
+            key: Some(key.to_owned()),
+            upload_id: Some(upload_id.to_owned()),
+            ..MultipartUpload::default()
+        }
+    }
+
+    /// uploads in no particular order, two of them for `b`
+    fn uploads() -> Vec<MultipartUpload> {
+        vec![
+            upload("photos/2.jpg", "p2", "1"),
+            upload("b", "b-new", "3"),
+            upload("a", "a1", "1"),
+            upload("photos/1.jpg", "p1", "4"),
+            upload("b", "b-old", "2"),
+            upload("z", "z1", "5"),
+        ]
+    }
+
+    fn list(
+        delimiter: Option<&str>,
+        key_marker: Option<&str>,
+        upload_id_marker: Option<&str>,
+        max_uploads: Option<i64>,
+    ) -> ListMultipartUploadsOutput {
+        let input = ListMultipartUploadsRequest {
+            bucket: "asd".to_owned(),
+            delimiter: delimiter.map(ToOwned::to_owned),
+            key_marker: key_marker.map(ToOwned::to_owned),
+            upload_id_marker: upload_id_marker.map(ToOwned::to_owned),
+            max_uploads,
+            ..ListMultipartUploadsRequest::default()
+        };
+        list_uploads(input, uploads())
+    }
+
+    /// the upload ids of a page, then its common prefixes
+    fn summary(output: &ListMultipartUploadsOutput) -> Vec<&str> {
+        let uploads = output.uploads.iter().flatten();
+        let prefixes = output.common_prefixes.iter().flatten();
+        uploads
+            .filter_map(|upload| upload.upload_id.as_deref())
+            .chain(prefixes.filter_map(|common_prefix| common_prefix.prefix.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn ordering() {
+        let output = list(None, None, None, None);
+        assert_eq!(summary(&output), ["a1", "b-old", "b-new", "p1", "p2", "z1"]);
+        assert_eq!(output.is_truncated, Some(false));
+        assert_eq!(output.max_uploads, Some(1000));
+
+        let output = list(None, None, None, Some(2));
+        assert_eq!(summary(&output), ["a1", "b-old"]);
+        assert_eq!(output.is_truncated, Some(true));
+        assert_eq!(output.next_key_marker.as_deref(), Some("b"));
+        assert_eq!(output.next_upload_id_marker.as_deref(), Some("b-old"));
+    }
+
+    #[test]
+    fn markers() {
+        // resumes in the middle of a key
+        let output = list(None, Some("b"), Some("b-old"), Some(2));
+        assert_eq!(summary(&output), ["b-new", "p1"]);
+
+        // `key-marker` alone skips all the uploads of the key
+        let output = list(None, Some("b"), None, None);
+        assert_eq!(summary(&output), ["p1", "p2", "z1"]);
+
+        // an aborted upload resumes by the order of ids
+        let output = list(None, Some("b"), Some("b-nz"), None);
+        assert_eq!(summary(&output), ["b-old", "p1", "p2", "z1"]);
+
+        // `upload-id-marker` without `key-marker` is ignored
+        let output = list(None, None, Some("b-old"), None);
+        assert_eq!(summary(&output).len(), 6);
+    }
+
+    #[test]
+    fn delimiter() {
+        let output = list(Some("/"), None, None, Some(4));
+        assert_eq!(summary(&output), ["a1", "b-old", "b-new", "photos/"]);
+        assert_eq!(output.next_key_marker.as_deref(), Some("photos/"));
+        assert_eq!(output.next_upload_id_marker, None);
+
+        let output = list(Some("/"), Some("photos/"), None, None);
+        assert_eq!(summary(&output), ["z1"]);
+        assert_eq!(output.is_truncated, Some(false));
+    }
+}
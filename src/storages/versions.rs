@@ -0,0 +1,307 @@
+//! Ordering and pagination of [`ListObjectVersions`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectVersions.html)
+//!
+//! A storage supplies its keys in ascending order, each with its versions and delete markers newest first,
+//! and [`list_versions`] cuts a page like S3 does:
+//!
+//! + Versions and delete markers are interleaved, ordered by key, then newest first.
+//! + `IsLatest` is set on the first entry of each key.
+//! + `key-marker` alone resumes after all the entries of that key.
+//!   With `version-id-marker`, it resumes after that entry, in the middle of the key.
+//! + A truncated page returns the key and the version id of its last entry as the next markers.
+//! + With a delimiter, the keys under a common prefix are rolled up into it, which counts as one entry.
+//!
+//! A storage without versioning supplies one version per key, whose version id is `null`.
+
+use crate::dto::{
+    CommonPrefix, ListObjectVersionsOutput, ListObjectVersionsRequest, ListedVersion,
+};
+use crate::errors::S3Result;
+
+use std::convert::TryInto;
+
+/// Max keys of a listing page
+const MAX_KEYS: i64 = 1000;
+
+/// The version id of an object which is written while versioning is not enabled
+pub const NULL_VERSION_ID: &str = "null";
+
+/// Checks a version id given to a storage without versioning, whose objects only have the `null` version
+///
+/// # Errors
+/// Returns `NoSuchVersion` if the version id is not `null`
+pub fn check_null_version(version_id: Option<&str>) -> S3Result<()> {
+    match version_id {
+        Some(version_id) if version_id != NULL_VERSION_ID => Err(code_error!(
+            NoSuchVersion,
+            "The specified version does not exist."
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// the version id of an entry
+fn version_id(entry: &ListedVersion) -> Option<&str> {
+    match *entry {
+        ListedVersion::Version(ref version) => version.version_id.as_deref(),
+        ListedVersion::DeleteMarker(ref marker) => marker.version_id.as_deref(),
+    }
+}
+
+/// fills the key and `IsLatest` of an entry
+fn fill(entry: &mut ListedVersion, key: &str, is_latest: bool) {
+    let (entry_key, entry_is_latest) = match *entry {
+        ListedVersion::Version(ref mut version) => (&mut version.key, &mut version.is_latest),
+        ListedVersion::DeleteMarker(ref mut marker) => (&mut marker.key, &mut marker.is_latest),
+    };
+    *entry_key = Some(key.to_owned());
+    *entry_is_latest = Some(is_latest);
+}
+
+/// Lists a page of versions
+///
+/// `keys` must be in ascending order, and the entries of each key newest first.
+/// Their prefix is not required to match: the keys are filtered here.
+/// The keys and `IsLatest` of the entries are filled here too.
+///
+/// # Errors
+/// Returns `InvalidArgument` if `version-id-marker` is not an entry of the key of `key-marker`
+pub fn list_versions<K, V>(
+    input: ListObjectVersionsRequest,
+    keys: K,
+) -> S3Result<ListObjectVersionsOutput>
+where
+    K: IntoIterator<Item = (String, V)>,
+    V: IntoIterator<Item = ListedVersion>,
+{
+    let prefix = input.prefix.as_deref().unwrap_or("");
+    let delimiter = input.delimiter.as_deref().filter(|d| !d.is_empty());
+    let key_marker = input.key_marker.as_deref();
+    let version_id_marker = input.version_id_marker.as_deref();
+    let max_keys = input.max_keys.unwrap_or(MAX_KEYS).clamp(0, MAX_KEYS);
+    let limit: usize = max_keys.try_into().unwrap_or(0);
+
+    let mut versions = Vec::new();
+    let mut common_prefixes: Vec<CommonPrefix> = Vec::new();
+    let mut count: usize = 0;
+    let mut is_truncated = false;
+    // the key (or prefix) and the version id of the last entry
+    let mut last: Option<(String, Option<String>)> = None;
+
+    'keys: for (key, entries) in keys {
+        if !key.starts_with(prefix) {
+            continue;
+        }
+        let resumes_key = match key_marker {
+            Some(marker) if key.as_str() < marker => continue,
+            Some(marker) if key == marker => match version_id_marker {
+                Some(_) => true,
+                None => continue,
+            },
+            // the last page may end with a common prefix
+            Some(marker) if delimiter.map_or(false, |d| marker.ends_with(d)) => {
+                if key.starts_with(marker) {
+                    continue;
+                }
+                false
+            }
+            _ => false,
+        };
+
+        let common_prefix = delimiter.and_then(|d| {
+            let rest = key.get(prefix.len()..).unwrap_or("");
+            let end = rest.find(d)?.saturating_add(d.len());
+            key.get(..prefix.len().saturating_add(end))
+        });
+        if let Some(common_prefix) = common_prefix {
+            let rolled_up = common_prefixes
+                .last()
+                .and_then(|last| last.prefix.as_deref())
+                == Some(common_prefix);
+            if rolled_up {
+                continue;
+            }
+            if count == limit {
+                is_truncated = true;
+                break;
+            }
+            count = count.saturating_add(1);
+            common_prefixes.push(CommonPrefix {
+                prefix: Some(common_prefix.to_owned()),
+            });
+            last = Some((common_prefix.to_owned(), None));
+            continue;
+        }
+
+        let mut entries = entries.into_iter();
+        let mut is_latest = !resumes_key;
+        if resumes_key {
+            let marker = version_id_marker.unwrap_or("");
+            if !entries
+                .by_ref()
+                .any(|entry| version_id(&entry) == Some(marker))
+            {
+                return Err(code_error!(InvalidArgument, "Invalid version id specified"));
+            }
+        }
+        for mut entry in entries {
+            if count == limit {
+                is_truncated = true;
+                break 'keys;
+            }
+            count = count.saturating_add(1);
+            fill(&mut entry, &key, is_latest);
+            is_latest = false;
+            last = Some((key.clone(), version_id(&entry).map(ToOwned::to_owned)));
+            versions.push(entry);
+        }
+    }
+
+    let (next_key_marker, next_version_id_marker) = match last {
+        Some((key, version_id)) if is_truncated => (Some(key), version_id),
+        _ => (None, None),
+    };
+
+    Ok(ListObjectVersionsOutput {
+        common_prefixes: if common_prefixes.is_empty() {
+            None
+        } else {
+            Some(common_prefixes)
+        },
+        delimiter: input.delimiter,
+        encoding_type: input.encoding_type,
+        is_truncated: Some(is_truncated),
+        key_marker: input.key_marker,
+        max_keys: Some(max_keys),
+        name: Some(input.bucket),
+        next_key_marker,
+        next_version_id_marker,
+        prefix: input.prefix,
+        version_id_marker: input.version_id_marker,
+        versions: Some(versions),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::{DeleteMarkerEntry, ObjectVersion};
+    use crate::errors::S3ErrorCode;
+
+    fn version(version_id: &str) -> ListedVersion {
+        ListedVersion::Version(ObjectVersion {
+            version_id: Some(version_id.to_owned()),
+            ..ObjectVersion::default()
+        })
+    }
+
+    fn delete_marker(version_id: &str) -> ListedVersion {
+        ListedVersion::DeleteMarker(DeleteMarkerEntry {
+            version_id: Some(version_id.to_owned()),
+            ..DeleteMarkerEntry::default()
+        })
+    }
+
+    fn keys() -> Vec<(String, Vec<ListedVersion>)> {
+        vec![
+            ("a".to_owned(), vec![delete_marker("a2"), version("a1")]),
+            ("photos/1.jpg".to_owned(), vec![version("p1")]),
+            ("photos/2.jpg".to_owned(), vec![version("p2")]),
+            ("z".to_owned(), vec![version("z2"), version("z1")]),
+        ]
+    }
+
+    /// (key, version id or `None` for a common prefix, is latest)
+    fn summary(output: &ListObjectVersionsOutput) -> Vec<(String, Option<String>, bool)> {
+        let entries = output.versions.iter().flatten().map(|entry| {
+            let (key, version_id, is_latest) = match *entry {
+                ListedVersion::Version(ref v) => (&v.key, &v.version_id, v.is_latest),
+                ListedVersion::DeleteMarker(ref m) => (&m.key, &m.version_id, m.is_latest),
+            };
+            let key = key.clone().unwrap_or_default();
+            (key, version_id.clone(), is_latest == Some(true))
+        });
+        let prefixes = output
+            .common_prefixes
+            .iter()
+            .flatten()
+            .map(|common_prefix| {
+                let prefix = common_prefix.prefix.clone().unwrap_or_default();
+                (prefix, None, false)
+            });
+        entries.chain(prefixes).collect()
+    }
+
+    fn list(
+        delimiter: Option<&str>,
+        key_marker: Option<&str>,
+        version_id_marker: Option<&str>,
+        max_keys: Option<i64>,
+    ) -> S3Result<ListObjectVersionsOutput> {
+        let input = ListObjectVersionsRequest {
+            bucket: "asd".to_owned(),
+            delimiter: delimiter.map(ToOwned::to_owned),
+            key_marker: key_marker.map(ToOwned::to_owned),
+            version_id_marker: version_id_marker.map(ToOwned::to_owned),
+            max_keys,
+            ..ListObjectVersionsRequest::default()
+        };
+        list_versions(input, keys())
+    }
+
+    fn entry(key: &str, version_id: &str, is_latest: bool) -> (String, Option<String>, bool) {
+        (key.to_owned(), Some(version_id.to_owned()), is_latest)
+    }
+
+    #[test]
+    fn order() {
+        let output = list(None, None, None, None).unwrap();
+        assert_eq!(
+            summary(&output),
+            [
+                entry("a", "a2", true),
+                entry("a", "a1", false),
+                entry("photos/1.jpg", "p1", true),
+                entry("photos/2.jpg", "p2", true),
+                entry("z", "z2", true),
+                entry("z", "z1", false),
+            ]
+        );
+        assert_eq!(output.is_truncated, Some(false));
+        assert_eq!(output.next_key_marker, None);
+
+        // resumes in the middle of a key
+        let output = list(None, Some("z"), Some("z2"), None).unwrap();
+        assert_eq!(summary(&output), [entry("z", "z1", false)]);
+
+        let output = list(None, Some("a"), Some("a2"), Some(1)).unwrap();
+        assert_eq!(summary(&output), [entry("a", "a1", false)]);
+        assert_eq!(output.next_key_marker.as_deref(), Some("a"));
+        assert_eq!(output.next_version_id_marker.as_deref(), Some("a1"));
+
+        let err = list(None, Some("a"), Some("z1"), None).unwrap_err();
+        assert_eq!(err.code(), S3ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn delimiter() {
+        let output = list(Some("/"), None, None, Some(3)).unwrap();
+        assert_eq!(
+            summary(&output),
+            [
+                entry("a", "a2", true),
+                entry("a", "a1", false),
+                ("photos/".to_owned(), None, false),
+            ]
+        );
+        assert_eq!(output.next_key_marker.as_deref(), Some("photos/"));
+        assert_eq!(output.next_version_id_marker, None);
+
+        let output = list(Some("/"), Some("photos/"), None, Some(3)).unwrap();
+        assert_eq!(
+            summary(&output),
+            [entry("z", "z2", true), entry("z", "z1", false)]
+        );
+        assert_eq!(output.is_truncated, Some(false));
+    }
+}
@@ -0,0 +1,696 @@
+//! Storage wrappers
+
+use crate::async_trait;
+use crate::bucket_freeze::BucketFreeze;
+use crate::consistency::ConsistencyToken;
+use crate::dto::{
+    AbortMultipartUploadError, AbortMultipartUploadOutput, AbortMultipartUploadRequest,
+    CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
+    CopyObjectError, CopyObjectOutput, CopyObjectRequest, CreateBucketError, CreateBucketOutput,
+    CreateBucketRequest, CreateMultipartUploadError, CreateMultipartUploadOutput,
+    CreateMultipartUploadRequest, DeleteBucketCorsError, DeleteBucketCorsOutput,
+    DeleteBucketCorsRequest, DeleteBucketError, DeleteBucketOutput, DeleteBucketPolicyError,
+    DeleteBucketPolicyOutput, DeleteBucketPolicyRequest, DeleteBucketRequest,
+    DeleteBucketTaggingError, DeleteBucketTaggingOutput, DeleteBucketTaggingRequest,
+    DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError,
+    DeleteObjectsOutput, DeleteObjectsRequest, GetBucketAclError, GetBucketAclOutput,
+    GetBucketAclRequest, GetBucketCorsError, GetBucketCorsOutput, GetBucketCorsRequest,
+    GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest,
+    GetBucketPolicyError, GetBucketPolicyOutput, GetBucketPolicyRequest, GetBucketTaggingError,
+    GetBucketTaggingOutput, GetBucketTaggingRequest, GetBucketVersioningError,
+    GetBucketVersioningOutput, GetBucketVersioningRequest, GetObjectAclError, GetObjectAclOutput,
+    GetObjectAclRequest, GetObjectError, GetObjectLegalHoldError, GetObjectLegalHoldOutput,
+    GetObjectLegalHoldRequest, GetObjectLockConfigurationError, GetObjectLockConfigurationOutput,
+    GetObjectLockConfigurationRequest, GetObjectOutput, GetObjectRequest, GetObjectRetentionError,
+    GetObjectRetentionOutput, GetObjectRetentionRequest, HeadBucketError, HeadBucketOutput,
+    HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest, ListBucketsError,
+    ListBucketsOutput, ListBucketsRequest, ListMultipartUploadsError, ListMultipartUploadsOutput,
+    ListMultipartUploadsRequest, ListObjectVersionsError, ListObjectVersionsOutput,
+    ListObjectVersionsRequest, ListObjectsError, ListObjectsOutput, ListObjectsRequest,
+    ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, ListPartsError, ListPartsOutput,
+    ListPartsRequest, PutBucketAclError, PutBucketAclOutput, PutBucketAclRequest,
+    PutBucketCorsError, PutBucketCorsOutput, PutBucketCorsRequest, PutBucketPolicyError,
+    PutBucketPolicyOutput, PutBucketPolicyRequest, PutBucketTaggingError, PutBucketTaggingOutput,
+    PutBucketTaggingRequest, PutBucketVersioningError, PutBucketVersioningOutput,
+    PutBucketVersioningRequest, PutObjectAclError, PutObjectAclOutput, PutObjectAclRequest,
+    PutObjectError, PutObjectLegalHoldError, PutObjectLegalHoldOutput, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationError, PutObjectLockConfigurationOutput,
+    PutObjectLockConfigurationRequest, PutObjectOutput, PutObjectRequest, PutObjectRetentionError,
+    PutObjectRetentionOutput, PutObjectRetentionRequest, RestoreObjectError, RestoreObjectOutput,
+    RestoreObjectRequest, UploadPartCopyError, UploadPartCopyOutput, UploadPartCopyRequest,
+    UploadPartError, UploadPartOutput, UploadPartRequest,
+};
+use crate::effective_config::StorageConfig;
+use crate::errors::{S3Error, S3ErrorCode, S3Result, S3StorageError, S3StorageResult};
+use crate::serving_policy::ServingPolicy;
+use crate::storage::S3Storage;
+
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+/// Options of [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// consecutive failures which trip the breaker
+    pub failure_threshold: u32,
+    /// how long to fast-fail before probing the storage again
+    pub cool_down: Duration,
+    /// successful probes which close the breaker
+    pub probes: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(10),
+            probes: 1,
+        }
+    }
+}
+
+/// The state of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CircuitState {
+    /// requests go to the storage
+    Closed,
+    /// requests fail fast with `ServiceUnavailable`
+    Open,
+    /// a limited number of probe requests go to the storage
+    HalfOpen,
+}
+
+/// internal state
+#[derive(Debug)]
+enum State {
+    /// closed
+    Closed {
+        /// consecutive failures
+        failures: u32,
+    },
+    /// open
+    Open {
+        /// end of the cool-down
+        until: Instant,
+    },
+    /// half-open
+    HalfOpen {
+        /// probes which have not finished
+        in_flight: u32,
+        /// successful probes
+        successes: u32,
+    },
+}
+
+/// A storage wrapper which fails fast while the storage keeps failing
+///
+/// After `failure_threshold` consecutive `InternalError`s the breaker opens,
+/// and requests fail with `503 ServiceUnavailable` and `Retry-After` for `cool_down`.
+/// Then the breaker half-opens: up to `probes` requests go to the storage at a time.
+/// A failed probe opens the breaker again, and `probes` successful probes close it.
+///
+/// Operation errors such as `NoSuchKey` are successes for the breaker.
+/// This tree has no storage timeouts, so only `InternalError` counts as a failure.
+pub struct CircuitBreaker<S> {
+    /// inner storage
+    inner: S,
+    /// options
+    config: CircuitBreakerConfig,
+    /// state
+    state: Mutex<State>,
+    /// clock
+    clock: Box<dyn Fn() -> Instant + Send + Sync>,
+}
+
+impl<S> Debug for CircuitBreaker<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CircuitBreaker {{ state: {:?} }}", self.state())
+    }
+}
+
+/// Marks a request admitted by the breaker
+#[derive(Debug)]
+struct Admission<'a, S> {
+    /// breaker
+    breaker: &'a CircuitBreaker<S>,
+    /// whether the request is a probe
+    probe: bool,
+}
+
+impl<S> CircuitBreaker<S> {
+    /// Wraps `inner` with `config`
+    pub fn new(inner: S, config: CircuitBreakerConfig) -> Self {
+        Self::with_clock(inner, config, Instant::now)
+    }
+
+    /// Wraps `inner` with `config`, reading the time from `clock`
+    pub fn with_clock(
+        inner: S,
+        config: CircuitBreakerConfig,
+        clock: impl Fn() -> Instant + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Returns the inner storage
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns the current state
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        match *self.lock() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// locks the state
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// admits a request or fails fast
+    fn admit(&self) -> S3Result<Admission<'_, S>> {
+        let now = (self.clock)();
+        let mut state = self.lock();
+        if let State::Open { until } = *state {
+            if now < until {
+                return Err(unavailable(until.saturating_duration_since(now)));
+            }
+            info!("circuit breaker half-opens");
+            *state = State::HalfOpen {
+                in_flight: 0,
+                successes: 0,
+            };
+        }
+        match *state {
+            State::Closed { .. } => Ok(Admission {
+                breaker: self,
+                probe: false,
+            }),
+            State::HalfOpen {
+                ref mut in_flight, ..
+            } => {
+                if *in_flight >= self.config.probes {
+                    return Err(unavailable(self.config.cool_down));
+                }
+                *in_flight = in_flight.saturating_add(1);
+                Ok(Admission {
+                    breaker: self,
+                    probe: true,
+                })
+            }
+            State::Open { .. } => Err(unavailable(self.config.cool_down)),
+        }
+    }
+
+    /// records the result of an admitted request
+    fn record(&self, mut admission: Admission<'_, S>, failed: bool) {
+        let now = (self.clock)();
+        let mut state = self.lock();
+        match *state {
+            State::Closed { ref mut failures } => {
+                if failed {
+                    *failures = failures.saturating_add(1);
+                    if *failures >= self.config.failure_threshold {
+                        warn!(failures = *failures, "circuit breaker opens");
+                        *state = self.open(now);
+                    }
+                } else {
+                    *failures = 0;
+                }
+            }
+            State::HalfOpen {
+                ref mut in_flight,
+                ref mut successes,
+            } if admission.probe => {
+                *in_flight = in_flight.saturating_sub(1);
+                if failed {
+                    warn!("circuit breaker probe failed, opens again");
+                    *state = self.open(now);
+                } else {
+                    *successes = successes.saturating_add(1);
+                    if *successes >= self.config.probes {
+                        info!("circuit breaker closes");
+                        *state = State::Closed { failures: 0 };
+                    }
+                }
+            }
+            // a request admitted before the breaker opened
+            State::HalfOpen { .. } | State::Open { .. } => {}
+        }
+        drop(state);
+        // the probe has been counted
+        admission.probe = false;
+    }
+
+    /// the open state from `now`
+    fn open(&self, now: Instant) -> State {
+        State::Open {
+            until: now.checked_add(self.config.cool_down).unwrap_or(now),
+        }
+    }
+
+    /// calls the storage through the breaker
+    async fn call<T, E>(
+        &self,
+        fut: impl Future<Output = S3StorageResult<T, E>> + Send,
+    ) -> S3StorageResult<T, E>
+    where
+        S: Sync,
+        T: Send,
+        E: Send,
+    {
+        let admission = self.admit()?;
+        let ret = fut.await;
+        let failed = matches!(ret, Err(S3StorageError::Other(ref e)) if is_failure(e));
+        self.record(admission, failed);
+        ret
+    }
+}
+
+impl<S> Drop for Admission<'_, S> {
+    fn drop(&mut self) {
+        // the request was dropped before it finished
+        if self.probe {
+            if let State::HalfOpen {
+                ref mut in_flight, ..
+            } = *self.breaker.lock()
+            {
+                *in_flight = in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// whether an error means that the storage is unavailable
+fn is_failure(err: &S3Error) -> bool {
+    err.code() == S3ErrorCode::InternalError
+}
+
+/// a fast failure
+fn unavailable(retry_after: Duration) -> S3Error {
+    S3Error::from_code(S3ErrorCode::ServiceUnavailable)
+        .message("The storage is temporarily unavailable. Please try again.")
+        .retry_after(retry_after)
+        .finish()
+}
+
+/// forwards the operations through the breaker
+macro_rules! forward_operations {
+    ($($op:ident($input:ty) -> ($output:ty, $error:ty);)+) => {
+        #[async_trait]
+        impl<S: S3Storage + Send + Sync> S3Storage for CircuitBreaker<S> {
+            $(
+                async fn $op(&self, input: $input) -> S3StorageResult<$output, $error> {
+                    self.call(self.inner.$op(input)).await
+                }
+            )+
+
+            async fn get_bucket_serving_policy(
+                &self,
+                bucket: &str,
+            ) -> S3Result<Option<ServingPolicy>> {
+                let admission = self.admit()?;
+                let ret = self.inner.get_bucket_serving_policy(bucket).await;
+                let failed = matches!(ret, Err(ref e) if is_failure(e));
+                self.record(admission, failed);
+                ret
+            }
+
+            async fn get_bucket_freeze(&self, bucket: &str) -> S3Result<Option<BucketFreeze>> {
+                let admission = self.admit()?;
+                let ret = self.inner.get_bucket_freeze(bucket).await;
+                let failed = matches!(ret, Err(ref e) if is_failure(e));
+                self.record(admission, failed);
+                ret
+            }
+
+            async fn presign_get(
+                &self,
+                bucket: &str,
+                key: &str,
+                expiry: Duration,
+            ) -> S3Result<Option<String>> {
+                let admission = self.admit()?;
+                let ret = self.inner.presign_get(bucket, key, expiry).await;
+                let failed = matches!(ret, Err(ref e) if is_failure(e));
+                self.record(admission, failed);
+                ret
+            }
+
+            async fn wait_for(
+                &self,
+                token: &ConsistencyToken,
+                timeout: Duration,
+            ) -> S3Result<bool> {
+                let admission = self.admit()?;
+                let ret = self.inner.wait_for(token, timeout).await;
+                let failed = matches!(ret, Err(ref e) if is_failure(e));
+                self.record(admission, failed);
+                ret
+            }
+
+            fn storage_config(&self) -> StorageConfig {
+                self.inner.storage_config().wrapped("circuit-breaker")
+            }
+        }
+    };
+}
+
+forward_operations! {
+    abort_multipart_upload(AbortMultipartUploadRequest) -> (AbortMultipartUploadOutput, AbortMultipartUploadError);
+    complete_multipart_upload(CompleteMultipartUploadRequest) -> (CompleteMultipartUploadOutput, CompleteMultipartUploadError);
+    copy_object(CopyObjectRequest) -> (CopyObjectOutput, CopyObjectError);
+    create_multipart_upload(CreateMultipartUploadRequest) -> (CreateMultipartUploadOutput, CreateMultipartUploadError);
+    create_bucket(CreateBucketRequest) -> (CreateBucketOutput, CreateBucketError);
+    delete_bucket(DeleteBucketRequest) -> (DeleteBucketOutput, DeleteBucketError);
+    delete_bucket_cors(DeleteBucketCorsRequest) -> (DeleteBucketCorsOutput, DeleteBucketCorsError);
+    delete_bucket_policy(DeleteBucketPolicyRequest) -> (DeleteBucketPolicyOutput, DeleteBucketPolicyError);
+    delete_bucket_tagging(DeleteBucketTaggingRequest) -> (DeleteBucketTaggingOutput, DeleteBucketTaggingError);
+    delete_object(DeleteObjectRequest) -> (DeleteObjectOutput, DeleteObjectError);
+    delete_objects(DeleteObjectsRequest) -> (DeleteObjectsOutput, DeleteObjectsError);
+    get_bucket_acl(GetBucketAclRequest) -> (GetBucketAclOutput, GetBucketAclError);
+    get_bucket_cors(GetBucketCorsRequest) -> (GetBucketCorsOutput, GetBucketCorsError);
+    get_bucket_location(GetBucketLocationRequest) -> (GetBucketLocationOutput, GetBucketLocationError);
+    get_bucket_policy(GetBucketPolicyRequest) -> (GetBucketPolicyOutput, GetBucketPolicyError);
+    get_bucket_tagging(GetBucketTaggingRequest) -> (GetBucketTaggingOutput, GetBucketTaggingError);
+    get_bucket_versioning(GetBucketVersioningRequest) -> (GetBucketVersioningOutput, GetBucketVersioningError);
+    get_object(GetObjectRequest) -> (GetObjectOutput, GetObjectError);
+    get_object_acl(GetObjectAclRequest) -> (GetObjectAclOutput, GetObjectAclError);
+    get_object_legal_hold(GetObjectLegalHoldRequest) -> (GetObjectLegalHoldOutput, GetObjectLegalHoldError);
+    get_object_lock_configuration(GetObjectLockConfigurationRequest) -> (GetObjectLockConfigurationOutput, GetObjectLockConfigurationError);
+    get_object_retention(GetObjectRetentionRequest) -> (GetObjectRetentionOutput, GetObjectRetentionError);
+    head_bucket(HeadBucketRequest) -> (HeadBucketOutput, HeadBucketError);
+    head_object(HeadObjectRequest) -> (HeadObjectOutput, HeadObjectError);
+    list_buckets(ListBucketsRequest) -> (ListBucketsOutput, ListBucketsError);
+    list_multipart_uploads(ListMultipartUploadsRequest) -> (ListMultipartUploadsOutput, ListMultipartUploadsError);
+    list_object_versions(ListObjectVersionsRequest) -> (ListObjectVersionsOutput, ListObjectVersionsError);
+    list_objects(ListObjectsRequest) -> (ListObjectsOutput, ListObjectsError);
+    list_objects_v2(ListObjectsV2Request) -> (ListObjectsV2Output, ListObjectsV2Error);
+    list_parts(ListPartsRequest) -> (ListPartsOutput, ListPartsError);
+    put_bucket_acl(PutBucketAclRequest) -> (PutBucketAclOutput, PutBucketAclError);
+    put_bucket_cors(PutBucketCorsRequest) -> (PutBucketCorsOutput, PutBucketCorsError);
+    put_bucket_policy(PutBucketPolicyRequest) -> (PutBucketPolicyOutput, PutBucketPolicyError);
+    put_bucket_tagging(PutBucketTaggingRequest) -> (PutBucketTaggingOutput, PutBucketTaggingError);
+    put_bucket_versioning(PutBucketVersioningRequest) -> (PutBucketVersioningOutput, PutBucketVersioningError);
+    put_object(PutObjectRequest) -> (PutObjectOutput, PutObjectError);
+    put_object_acl(PutObjectAclRequest) -> (PutObjectAclOutput, PutObjectAclError);
+    put_object_legal_hold(PutObjectLegalHoldRequest) -> (PutObjectLegalHoldOutput, PutObjectLegalHoldError);
+    put_object_lock_configuration(PutObjectLockConfigurationRequest) -> (PutObjectLockConfigurationOutput, PutObjectLockConfigurationError);
+    put_object_retention(PutObjectRetentionRequest) -> (PutObjectRetentionOutput, PutObjectRetentionError);
+    restore_object(RestoreObjectRequest) -> (RestoreObjectOutput, RestoreObjectError);
+    upload_part(UploadPartRequest) -> (UploadPartOutput, UploadPartError);
+    upload_part_copy(UploadPartCopyRequest) -> (UploadPartCopyOutput, UploadPartCopyError);
+}
+
+/// A storage wrapper which rejects writes
+///
+/// Reads go to the inner storage, while writes fail with `AccessDenied` (403)
+/// without reaching it. Unlike a [`BucketFreeze`], it covers all buckets and can not be lifted.
+#[derive(Debug)]
+pub struct ReadOnly<S> {
+    /// inner storage
+    inner: S,
+}
+
+impl<S> ReadOnly<S> {
+    /// Wraps `inner`
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the inner storage
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+/// the error of a rejected write
+fn read_only() -> S3Error {
+    S3Error::from_code(S3ErrorCode::AccessDenied)
+        .message("The storage is read-only.")
+        .finish()
+}
+
+/// forwards the reads and rejects the writes
+macro_rules! read_only_operations {
+    (
+        reads { $($read:ident($read_input:ty) -> ($read_output:ty, $read_error:ty);)+ }
+        writes { $($write:ident($write_input:ty) -> ($write_output:ty, $write_error:ty);)+ }
+    ) => {
+        #[async_trait]
+        impl<S: S3Storage + Send + Sync> S3Storage for ReadOnly<S> {
+            $(
+                async fn $read(&self, input: $read_input) -> S3StorageResult<$read_output, $read_error> {
+                    self.inner.$read(input).await
+                }
+            )+
+
+            $(
+                async fn $write(&self, _: $write_input) -> S3StorageResult<$write_output, $write_error> {
+                    Err(read_only().into())
+                }
+            )+
+
+            async fn get_bucket_serving_policy(
+                &self,
+                bucket: &str,
+            ) -> S3Result<Option<ServingPolicy>> {
+                self.inner.get_bucket_serving_policy(bucket).await
+            }
+
+            async fn get_bucket_freeze(&self, bucket: &str) -> S3Result<Option<BucketFreeze>> {
+                self.inner.get_bucket_freeze(bucket).await
+            }
+
+            async fn presign_get(
+                &self,
+                bucket: &str,
+                key: &str,
+                expiry: Duration,
+            ) -> S3Result<Option<String>> {
+                self.inner.presign_get(bucket, key, expiry).await
+            }
+
+            async fn wait_for(
+                &self,
+                token: &ConsistencyToken,
+                timeout: Duration,
+            ) -> S3Result<bool> {
+                self.inner.wait_for(token, timeout).await
+            }
+
+            fn storage_config(&self) -> StorageConfig {
+                self.inner.storage_config().wrapped("read-only")
+            }
+        }
+    };
+}
+
+read_only_operations! {
+    reads {
+        get_bucket_acl(GetBucketAclRequest) -> (GetBucketAclOutput, GetBucketAclError);
+        get_bucket_cors(GetBucketCorsRequest) -> (GetBucketCorsOutput, GetBucketCorsError);
+        get_bucket_location(GetBucketLocationRequest) -> (GetBucketLocationOutput, GetBucketLocationError);
+        get_bucket_policy(GetBucketPolicyRequest) -> (GetBucketPolicyOutput, GetBucketPolicyError);
+        get_bucket_tagging(GetBucketTaggingRequest) -> (GetBucketTaggingOutput, GetBucketTaggingError);
+        get_bucket_versioning(GetBucketVersioningRequest) -> (GetBucketVersioningOutput, GetBucketVersioningError);
+        get_object(GetObjectRequest) -> (GetObjectOutput, GetObjectError);
+        get_object_acl(GetObjectAclRequest) -> (GetObjectAclOutput, GetObjectAclError);
+        get_object_legal_hold(GetObjectLegalHoldRequest) -> (GetObjectLegalHoldOutput, GetObjectLegalHoldError);
+        get_object_lock_configuration(GetObjectLockConfigurationRequest) -> (GetObjectLockConfigurationOutput, GetObjectLockConfigurationError);
+        get_object_retention(GetObjectRetentionRequest) -> (GetObjectRetentionOutput, GetObjectRetentionError);
+        head_bucket(HeadBucketRequest) -> (HeadBucketOutput, HeadBucketError);
+        head_object(HeadObjectRequest) -> (HeadObjectOutput, HeadObjectError);
+        list_buckets(ListBucketsRequest) -> (ListBucketsOutput, ListBucketsError);
+        list_multipart_uploads(ListMultipartUploadsRequest) -> (ListMultipartUploadsOutput, ListMultipartUploadsError);
+        list_object_versions(ListObjectVersionsRequest) -> (ListObjectVersionsOutput, ListObjectVersionsError);
+        list_objects(ListObjectsRequest) -> (ListObjectsOutput, ListObjectsError);
+        list_objects_v2(ListObjectsV2Request) -> (ListObjectsV2Output, ListObjectsV2Error);
+        list_parts(ListPartsRequest) -> (ListPartsOutput, ListPartsError);
+    }
+    writes {
+        abort_multipart_upload(AbortMultipartUploadRequest) -> (AbortMultipartUploadOutput, AbortMultipartUploadError);
+        complete_multipart_upload(CompleteMultipartUploadRequest) -> (CompleteMultipartUploadOutput, CompleteMultipartUploadError);
+        copy_object(CopyObjectRequest) -> (CopyObjectOutput, CopyObjectError);
+        create_multipart_upload(CreateMultipartUploadRequest) -> (CreateMultipartUploadOutput, CreateMultipartUploadError);
+        create_bucket(CreateBucketRequest) -> (CreateBucketOutput, CreateBucketError);
+        delete_bucket(DeleteBucketRequest) -> (DeleteBucketOutput, DeleteBucketError);
+        delete_bucket_cors(DeleteBucketCorsRequest) -> (DeleteBucketCorsOutput, DeleteBucketCorsError);
+        delete_bucket_policy(DeleteBucketPolicyRequest) -> (DeleteBucketPolicyOutput, DeleteBucketPolicyError);
+        delete_bucket_tagging(DeleteBucketTaggingRequest) -> (DeleteBucketTaggingOutput, DeleteBucketTaggingError);
+        delete_object(DeleteObjectRequest) -> (DeleteObjectOutput, DeleteObjectError);
+        delete_objects(DeleteObjectsRequest) -> (DeleteObjectsOutput, DeleteObjectsError);
+        put_bucket_acl(PutBucketAclRequest) -> (PutBucketAclOutput, PutBucketAclError);
+        put_bucket_cors(PutBucketCorsRequest) -> (PutBucketCorsOutput, PutBucketCorsError);
+        put_bucket_policy(PutBucketPolicyRequest) -> (PutBucketPolicyOutput, PutBucketPolicyError);
+        put_bucket_tagging(PutBucketTaggingRequest) -> (PutBucketTaggingOutput, PutBucketTaggingError);
+        put_bucket_versioning(PutBucketVersioningRequest) -> (PutBucketVersioningOutput, PutBucketVersioningError);
+        put_object(PutObjectRequest) -> (PutObjectOutput, PutObjectError);
+        put_object_acl(PutObjectAclRequest) -> (PutObjectAclOutput, PutObjectAclError);
+        put_object_legal_hold(PutObjectLegalHoldRequest) -> (PutObjectLegalHoldOutput, PutObjectLegalHoldError);
+        put_object_lock_configuration(PutObjectLockConfigurationRequest) -> (PutObjectLockConfigurationOutput, PutObjectLockConfigurationError);
+        put_object_retention(PutObjectRetentionRequest) -> (PutObjectRetentionOutput, PutObjectRetentionError);
+        restore_object(RestoreObjectRequest) -> (RestoreObjectOutput, RestoreObjectError);
+        upload_part(UploadPartRequest) -> (UploadPartOutput, UploadPartError);
+        upload_part_copy(UploadPartCopyRequest) -> (UploadPartCopyOutput, UploadPartCopyError);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::output::S3Output;
+
+    use std::sync::Arc;
+
+    use futures::executor::block_on;
+
+    /// a manual clock
+    #[derive(Clone)]
+    struct MockClock(Arc<Mutex<Instant>>);
+
+    impl MockClock {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+
+        fn advance(&self, d: Duration) {
+            let mut now = self.0.lock().unwrap();
+            *now += d;
+        }
+    }
+
+    fn breaker(clock: &MockClock) -> CircuitBreaker<()> {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 3,
+            cool_down: Duration::from_secs(5),
+            probes: 2,
+        };
+        let clock = clock.clone();
+        CircuitBreaker::with_clock((), config, move || clock.now())
+    }
+
+    /// injects a result
+    fn inject(breaker: &CircuitBreaker<()>, code: Option<S3ErrorCode>) -> S3StorageResult<(), ()> {
+        let result = code.map_or(Ok(()), |code| Err(S3Error::new(code, "injected").into()));
+        block_on(breaker.call(async { result }))
+    }
+
+    fn unwrap_code(ret: S3StorageResult<(), ()>) -> S3ErrorCode {
+        match ret {
+            Err(S3StorageError::Other(e)) => e.code(),
+            _ => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn open_half_open_closed() {
+        let clock = MockClock::new();
+        let breaker = breaker(&clock);
+        let internal = Some(S3ErrorCode::InternalError);
+
+        // operation errors and successes reset the count
+        let _err = inject(&breaker, internal);
+        let _err = inject(&breaker, internal);
+        let _err = inject(&breaker, Some(S3ErrorCode::NoSuchKey));
+        let _err = inject(&breaker, internal);
+        let _err = inject(&breaker, internal);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let _err = inject(&breaker, internal);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // fast-fail with Retry-After
+        let err = match block_on(breaker.call(async { Ok::<(), S3StorageError<()>>(()) })) {
+            Err(S3StorageError::Other(e)) => e,
+            _ => panic!("expected a fast failure"),
+        };
+        assert_eq!(err.code(), S3ErrorCode::ServiceUnavailable);
+        let res = err.into_xml_response().try_into_response().unwrap();
+        assert_eq!(res.status(), 503);
+        assert_eq!(res.headers()["retry-after"], "5");
+
+        // a failed probe opens the breaker again
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(
+            unwrap_code(inject(&breaker, internal)),
+            S3ErrorCode::InternalError
+        );
+        assert_eq!(breaker.state(), CircuitState::Open);
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(
+            unwrap_code(inject(&breaker, None)),
+            S3ErrorCode::ServiceUnavailable
+        );
+
+        // successful probes close the breaker
+        clock.advance(Duration::from_secs(1));
+        inject(&breaker, None).unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        inject(&breaker, None).unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn read_only() {
+        let storage = ReadOnly::new(crate::storages::memory::MemoryStorage::new());
+        let input = CreateBucketRequest {
+            bucket: "asd".into(),
+            ..CreateBucketRequest::default()
+        };
+        match block_on(storage.create_bucket(input)) {
+            Err(S3StorageError::Other(e)) => assert_eq!(e.code(), S3ErrorCode::AccessDenied),
+            _ => panic!("expected AccessDenied"),
+        }
+        let output = block_on(storage.list_buckets(ListBucketsRequest::default())).unwrap();
+        assert_eq!(output.buckets.map(|buckets| buckets.len()), Some(0));
+    }
+
+    #[test]
+    fn probe_limit() {
+        let clock = MockClock::new();
+        let breaker = breaker(&clock);
+        for _ in 0..3 {
+            let _err = inject(&breaker, Some(S3ErrorCode::InternalError));
+        }
+        clock.advance(Duration::from_secs(5));
+
+        let first = breaker.admit().unwrap();
+        let second = breaker.admit().unwrap();
+        assert_eq!(
+            breaker.admit().unwrap_err().code(),
+            S3ErrorCode::ServiceUnavailable
+        );
+
+        // a dropped probe frees its slot
+        drop(first);
+        let third = breaker.admit().unwrap();
+        breaker.record(second, false);
+        breaker.record(third, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
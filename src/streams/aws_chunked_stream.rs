@@ -1,10 +1,13 @@
 //! aws-chunked stream
+//!
+//! A body in the `aws-chunked` encoding is a list of chunks of `<hex-size>;chunk-signature=<signature>\r\n<data>\r\n`,
+//! which ends with a chunk of zero size. The signature of a chunk is optional if the request is not signed.
 
 use crate::headers::AmzDate;
 use crate::signature_v4;
 use crate::utils::Apply;
 
-use std::convert::TryInto;
+use std::convert::TryFrom;
 use std::fmt::{self, Debug};
 use std::io;
 use std::pin::Pin;
@@ -66,31 +69,30 @@ struct ChunkMeta<'a> {
     /// chunk size
     size: usize,
     /// chunk signature
-    signature: &'a [u8],
+    signature: Option<&'a [u8]>,
 }
 
 /// nom parser
 fn parse_chunk_meta(mut input: &[u8]) -> nom::IResult<&[u8], ChunkMeta<'_>> {
     use nom::{
         bytes::complete::{tag, take, take_till1},
-        combinator::{all_consuming, map_res},
+        combinator::{all_consuming, map_res, opt},
         number::complete::hex_u32,
-        sequence::tuple,
+        sequence::{preceded, tuple},
     };
 
     let mut parser = all_consuming(tuple((
-        take_till1(|c| c == b';'),
-        tag(b";chunk-signature="),
-        take(64_usize),
+        take_till1(|c| c == b';' || c == b'\r'),
+        opt(preceded(tag(b";chunk-signature="), take(64_usize))),
         tag(b"\r\n"),
     )));
 
-    let (size_str, signature) = parser(input)?.apply(|(remain, (size_str, _, signature, _))| {
+    let (size_str, signature) = parser(input)?.apply(|(remain, (size_str, signature, _))| {
         input = remain;
         (size_str, signature)
     });
 
-    let (_, size) = map_res(hex_u32, |n| n.try_into())(size_str)?;
+    let (_, size) = all_consuming(map_res(hex_u32, usize::try_from))(size_str)?;
 
     Ok((input, ChunkMeta { size, signature }))
 }
@@ -98,7 +100,7 @@ fn parse_chunk_meta(mut input: &[u8]) -> nom::IResult<&[u8], ChunkMeta<'_>> {
 /// check signature
 fn check_signature(
     ctx: &SignatureCtx,
-    expected_signature: &[u8],
+    expected_signature: &str,
     chunk_data: &[Bytes],
 ) -> Option<Box<str>> {
     let string_to_sign = signature_v4::create_chunk_string_to_sign(
@@ -115,15 +117,11 @@ fn check_signature(
         &ctx.region,
     );
 
-    if chunk_signature.as_bytes() == expected_signature {
-        Some(chunk_signature.into())
-    } else {
-        None
-    }
+    (chunk_signature == expected_signature).then(|| chunk_signature.into())
 }
 
 impl AwsChunkedStream {
-    /// Constructs a `ChunkedStream`
+    /// Constructs a `ChunkedStream` which verifies the signature of each chunk
     pub fn new<S>(
         body: S,
         seed_signature: Box<str>,
@@ -133,22 +131,41 @@ impl AwsChunkedStream {
     ) -> Self
     where
         S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        let mut ctx = SignatureCtx {
+            amz_date,
+            region,
+            secret_key,
+            prev_signature: seed_signature,
+        };
+        Self::with_chunk_callback(body, move |signature, data| {
+            match signature.and_then(|signature| check_signature(&ctx, signature, data)) {
+                None => false,
+                Some(signature) => {
+                    ctx.prev_signature = signature;
+                    true
+                }
+            }
+        })
+    }
+
+    /// Constructs a `ChunkedStream` which passes the signature and the data of each chunk to `on_chunk`
+    ///
+    /// The stream fails with `SignatureMismatch` if `on_chunk` returns `false`.
+    pub fn with_chunk_callback<S, F>(body: S, mut on_chunk: F) -> Self
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+        F: FnMut(Option<&str>, &[Bytes]) -> bool + Send + 'static,
     {
         <AsyncTryStream<Bytes, AwsChunkedStreamError>>::new_boxed(|mut y| async move {
             pin_mut!(body);
             let mut prev_bytes = Bytes::new();
             let mut buf: Vec<u8> = Vec::new();
-            let mut ctx = SignatureCtx {
-                amz_date,
-                region,
-                secret_key,
-                prev_signature: seed_signature,
-            };
 
             loop {
                 let meta = {
                     match Self::read_meta_bytes(body.as_mut(), prev_bytes, &mut buf).await {
-                        None => break,
+                        None => return Err(AwsChunkedStreamError::Incomplete),
                         Some(Err(e)) => return Err(AwsChunkedStreamError::Io(e)),
                         Some(Ok(remaining_bytes)) => prev_bytes = remaining_bytes,
                     };
@@ -170,9 +187,18 @@ impl AwsChunkedStream {
                     }
                 };
 
-                match check_signature(&ctx, meta.signature, &data) {
-                    None => return Err(AwsChunkedStreamError::SignatureMismatch),
-                    Some(signature) => ctx.prev_signature = signature,
+                let signature = match meta.signature.map(std::str::from_utf8) {
+                    None => None,
+                    Some(Ok(signature)) => Some(signature),
+                    Some(Err(_)) => return Err(AwsChunkedStreamError::FormatError),
+                };
+                if !on_chunk(signature, &data) {
+                    return Err(AwsChunkedStreamError::SignatureMismatch);
+                }
+
+                // the last chunk
+                if meta.size == 0 {
+                    break;
                 }
 
                 for bytes in data {
@@ -356,4 +382,63 @@ mod tests {
         assert!(chunked_stream.next().await.is_none());
         assert!(chunked_stream.next().await.is_none());
     }
+
+    async fn decode(
+        chunks: &[&'static str],
+    ) -> (Result<Vec<u8>, AwsChunkedStreamError>, Vec<String>) {
+        let signatures = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let collected = std::sync::Arc::clone(&signatures);
+        let stream = futures::stream::iter(chunks.to_vec())
+            .map(|chunk| Ok(Bytes::from_static(chunk.as_bytes())));
+        let mut chunked_stream =
+            AwsChunkedStream::with_chunk_callback(stream, move |signature, _| {
+                let mut signatures = collected.lock().unwrap();
+                signatures.push(signature.unwrap_or_default().to_owned());
+                true
+            });
+
+        let mut data = Vec::new();
+        let mut ans = Ok(());
+        while let Some(bytes) = chunked_stream.next().await {
+            match bytes {
+                Ok(bytes) => data.extend_from_slice(&bytes),
+                Err(e) => {
+                    ans = Err(e);
+                    break;
+                }
+            }
+        }
+        let signatures = signatures.lock().unwrap().clone();
+        (ans.map(|()| data), signatures)
+    }
+
+    #[tokio::test]
+    async fn decode_chunks() {
+        let signature = "ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648";
+        let (data, signatures) = decode(&[
+            "5;chunk-signature=ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648\r\nhello\r\n",
+            "0;chunk-signature=ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648\r\n\r\n",
+        ])
+        .await;
+        assert_eq!(data.unwrap(), b"hello");
+        assert_eq!(signatures, [signature, signature]);
+
+        // frames split anywhere and chunks without signatures
+        let (data, signatures) = decode(&["3\r", "\nabc\r\n", "2\r\nde", "\r\n0\r\n\r\n"]).await;
+        assert_eq!(data.unwrap(), b"abcde");
+        assert_eq!(signatures, ["", "", ""]);
+
+        let (data, _) = decode(&["5;chunk-signature=short\r\nhello\r\n0\r\n\r\n"]).await;
+        assert!(matches!(data, Err(AwsChunkedStreamError::FormatError)));
+        let (data, _) = decode(&["xyz\r\nhello\r\n0\r\n\r\n"]).await;
+        assert!(matches!(data, Err(AwsChunkedStreamError::FormatError)));
+        let (data, _) = decode(&["5\r\nhelloXX0\r\n\r\n"]).await;
+        assert!(matches!(data, Err(AwsChunkedStreamError::FormatError)));
+
+        // without the last chunk
+        let (data, _) = decode(&["5\r\nhello\r\n"]).await;
+        assert!(matches!(data, Err(AwsChunkedStreamError::Incomplete)));
+        let (data, _) = decode(&["5\r\nhel"]).await;
+        assert!(matches!(data, Err(AwsChunkedStreamError::Incomplete)));
+    }
 }
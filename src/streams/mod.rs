@@ -2,3 +2,4 @@
 
 pub mod aws_chunked_stream;
 pub mod multipart;
+pub mod sha256_stream;
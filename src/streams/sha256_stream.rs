@@ -0,0 +1,117 @@
+//! sha256 stream
+//!
+//! A body whose `x-amz-content-sha256` is a hex SHA256 is hashed while it is streamed,
+//! and the stream fails at its end, instead of ending, if the digest does not match.
+
+use crate::utils::crypto;
+
+use std::fmt::{self, Debug};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::ready;
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use pin_project_lite::pin_project;
+use sha2::{Digest, Sha256};
+
+pin_project! {
+    /// Sha256 stream
+    pub struct Sha256Stream<S> {
+        #[pin]
+        inner: S,
+        // the digest of the bytes so far, or `None` after the end
+        hasher: Option<Sha256>,
+        // the expected hex SHA256
+        expected: Box<str>,
+    }
+}
+
+impl<S> Debug for Sha256Stream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sha256Stream {{...}}")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// `Sha256StreamError`
+pub enum Sha256StreamError {
+    /// IO error
+    #[error("Sha256StreamError: IO: {}",.0)]
+    Io(io::Error),
+    /// Checksum mismatch
+    #[error("Sha256StreamError: Mismatch")]
+    Mismatch,
+}
+
+impl<S> Sha256Stream<S> {
+    /// Constructs a `Sha256Stream` which expects the hex SHA256 `expected`
+    pub fn new(body: S, expected: impl Into<Box<str>>) -> Self {
+        Self {
+            inner: body,
+            hasher: Some(Sha256::new()),
+            expected: expected.into(),
+        }
+    }
+}
+
+impl<S> Stream for Sha256Stream<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    type Item = Result<Bytes, Sha256StreamError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let hasher = match *this.hasher {
+            Some(ref mut hasher) => hasher,
+            None => return Poll::Ready(None),
+        };
+        let ret = match ready!(this.inner.as_mut().poll_next(cx)) {
+            Some(Ok(chunk)) => {
+                hasher.update(&chunk);
+                Some(Ok(chunk))
+            }
+            Some(Err(e)) => Some(Err(Sha256StreamError::Io(e))),
+            None => {
+                let digest = this.hasher.take().map(Sha256::finalize);
+                let matched = digest.map_or(false, |digest| {
+                    crypto::to_hex_string(digest) == **this.expected
+                });
+                (!matched).then(|| Err(Sha256StreamError::Mismatch))
+            }
+        };
+        Poll::Ready(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::{self, StreamExt};
+
+    fn collect(chunks: &[&'static str], expected: &str) -> Vec<Result<Bytes, Sha256StreamError>> {
+        let body = stream::iter(chunks.iter().map(|&chunk| Ok(Bytes::from(chunk))));
+        futures::executor::block_on(Sha256Stream::new(body, expected).collect())
+    }
+
+    #[test]
+    fn verified() {
+        let expected = crypto::hex_sha256(b"Hello World!");
+        let items = collect(&["Hello", " World!"], &expected);
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(Result::is_ok));
+
+        let items = collect(&["Hello", " World?"], &expected);
+        assert_eq!(items.len(), 3);
+        assert!(matches!(
+            items.last(),
+            Some(Err(Sha256StreamError::Mismatch))
+        ));
+
+        let empty = crypto::hex_sha256(b"");
+        assert!(collect(&[], &empty).is_empty());
+    }
+}
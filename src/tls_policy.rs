@@ -0,0 +1,180 @@
+//! Rejection of credentials sent over plaintext HTTP
+
+use crate::errors::S3Result;
+use crate::headers::{AUTHORIZATION, HOST, LOCATION};
+use crate::ops::ReqContext;
+use crate::{Body, Method, Request, Response, StatusCode};
+
+use hyper::header::HeaderValue;
+use serde::Serialize;
+
+/// the header set by a proxy which terminates TLS
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// A request extension carrying the scheme of the connection
+///
+/// Servers insert it into each request, like [`ClientAddr`](crate::ClientAddr).
+/// A request without it is plaintext unless its URI has the `https` scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionScheme {
+    /// plaintext HTTP
+    Http,
+    /// HTTP over TLS
+    Https,
+}
+
+impl Default for ConnectionScheme {
+    fn default() -> Self {
+        Self::Http
+    }
+}
+
+/// Options of TLS enforcement, see [`S3Service::set_tls_policy`](crate::S3Service::set_tls_policy)
+///
+/// Nothing is enforced by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TlsPolicy {
+    /// whether requests with credentials are rejected over plaintext
+    require_tls_for_auth: bool,
+    /// whether such `GET` and `HEAD` requests are redirected instead
+    redirect: bool,
+    /// whether `X-Forwarded-Proto` decides the scheme
+    trust_forwarded_proto: bool,
+}
+
+impl TlsPolicy {
+    /// Rejects requests with an `Authorization` header or a presigned URL over plaintext
+    /// with `AccessDenied` (403), before their signatures are checked.
+    ///
+    /// Anonymous requests are still served over plaintext.
+    /// POST form uploads carry their signature in the body, which is not inspected.
+    #[must_use]
+    pub const fn require_tls_for_auth(mut self, enabled: bool) -> Self {
+        self.require_tls_for_auth = enabled;
+        self
+    }
+
+    /// Redirects the rejected `GET` and `HEAD` requests to the same URL with `https`
+    /// by `307 Temporary Redirect`, instead of `AccessDenied`.
+    ///
+    /// Requests without a `Host` header are still rejected.
+    #[must_use]
+    pub const fn redirect(mut self, enabled: bool) -> Self {
+        self.redirect = enabled;
+        self
+    }
+
+    /// Takes the scheme from the first value of `X-Forwarded-Proto` if it is present.
+    ///
+    /// Enable it only behind a proxy which terminates TLS and overwrites the header,
+    /// because a client can send any value.
+    #[must_use]
+    pub const fn trust_forwarded_proto(mut self, enabled: bool) -> Self {
+        self.trust_forwarded_proto = enabled;
+        self
+    }
+
+    /// the scheme by which `req` was received
+    pub(crate) fn scheme(self, req: &Request) -> ConnectionScheme {
+        let forwarded = req
+            .headers()
+            .get(X_FORWARDED_PROTO)
+            .filter(|_| self.trust_forwarded_proto)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next());
+        if let Some(proto) = forwarded {
+            return if proto.trim().eq_ignore_ascii_case("https") {
+                ConnectionScheme::Https
+            } else {
+                ConnectionScheme::Http
+            };
+        }
+        if let Some(&scheme) = req.extensions().get::<ConnectionScheme>() {
+            return scheme;
+        }
+        if req.uri().scheme_str() == Some("https") {
+            ConnectionScheme::Https
+        } else {
+            ConnectionScheme::Http
+        }
+    }
+
+    /// checks a request before its signature,
+    /// returning the redirect if it is to be served over TLS instead
+    pub(crate) fn check(self, ctx: &ReqContext<'_>) -> S3Result<Option<Response>> {
+        if !self.require_tls_for_auth || ctx.scheme == ConnectionScheme::Https {
+            return Ok(None);
+        }
+        let has_credentials = ctx.headers.get(AUTHORIZATION).is_some()
+            || ctx.presigned_qs.is_some()
+            || ctx
+                .query_strings
+                .as_ref()
+                .map_or(false, |qs| qs.get("X-Amz-Credential").is_some());
+        if !has_credentials {
+            return Ok(None);
+        }
+
+        let method = ctx.req.method();
+        let is_read = *method == Method::GET || *method == Method::HEAD;
+        if let (true, Some(host)) = (self.redirect && is_read, ctx.headers.get(HOST)) {
+            let path_and_query = ctx
+                .req
+                .uri()
+                .path_and_query()
+                .map_or("/", |path_and_query| path_and_query.as_str());
+            let location = format!("https://{}{}", host, path_and_query);
+            if let Ok(location) = HeaderValue::from_str(&location) {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = StatusCode::TEMPORARY_REDIRECT;
+                let _prev = res.headers_mut().insert(LOCATION, location);
+                return Ok(Some(res));
+            }
+        }
+        Err(code_error!(
+            AccessDenied,
+            "Requests with credentials must be sent over HTTPS."
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&'static str, &'static str)]) -> Request {
+        let mut req = Request::new(Body::empty());
+        *req.uri_mut() = uri.parse().unwrap();
+        for &(name, value) in headers {
+            let _prev = req
+                .headers_mut()
+                .insert(name, HeaderValue::from_static(value));
+        }
+        req
+    }
+
+    #[test]
+    fn scheme() {
+        let policy = TlsPolicy::default();
+        assert_eq!(policy.scheme(&request("/", &[])), ConnectionScheme::Http);
+        assert_eq!(
+            policy.scheme(&request("https://localhost/", &[])),
+            ConnectionScheme::Https
+        );
+
+        let mut req = request("/", &[]);
+        let _prev = req.extensions_mut().insert(ConnectionScheme::Https);
+        assert_eq!(policy.scheme(&req), ConnectionScheme::Https);
+
+        // the header is ignored unless it is trusted
+        let proxied = request("/", &[(X_FORWARDED_PROTO, "HTTPS, http")]);
+        assert_eq!(policy.scheme(&proxied), ConnectionScheme::Http);
+        let policy = policy.trust_forwarded_proto(true);
+        assert_eq!(policy.scheme(&proxied), ConnectionScheme::Https);
+
+        let mut req = request("/", &[(X_FORWARDED_PROTO, "http")]);
+        let _prev = req.extensions_mut().insert(ConnectionScheme::Https);
+        assert_eq!(policy.scheme(&req), ConnectionScheme::Http);
+    }
+}
@@ -4,6 +4,7 @@ use crate::utils::Also;
 
 use hmac::{Hmac, Mac, NewMac};
 use hyper::body::Bytes;
+use md5::Md5;
 use sha2::{Digest, Sha256};
 
 /// convert bytes to hex string
@@ -39,6 +40,16 @@ pub fn hex_sha256_chunk(chunk_data: &[Bytes]) -> String {
     to_hex_string(src)
 }
 
+/// the quoted `ETag` of a multipart object: the MD5 of the binary MD5s of its parts, followed by the part count
+///
+/// An object of one part has the suffix `-1` too, so it never equals the plain MD5 of the content.
+pub fn multipart_e_tag(part_md5_sums: &[impl AsRef<[u8]>]) -> String {
+    let src = Md5::new()
+        .also(|h| part_md5_sums.iter().for_each(|md5_sum| h.update(md5_sum)))
+        .finalize();
+    format!("\"{}-{}\"", to_hex_string(src), part_md5_sums.len())
+}
+
 /// `hmac_sha256(key, data)`
 pub fn hmac_sha256(key: &[u8], data: &[u8]) -> impl AsRef<[u8]> {
     let m = <Hmac<Sha256>>::new_from_slice(key)
@@ -56,6 +67,31 @@ pub fn hex_hmac_sha256(key: &[u8], data: &[u8]) -> String {
     to_hex_string(src)
 }
 
+/// `hmac_sha1(key, data)`, which signs requests of Signature Version 2
+pub fn hmac_sha1(key: &[u8], data: &[u8]) -> [u8; 20] {
+    /// the block size of SHA1
+    const BLOCK_SIZE: usize = 64;
+
+    let hashed_key;
+    let key = if key.len() > BLOCK_SIZE {
+        hashed_key = sha1::Sha1::from(key).digest().bytes();
+        &hashed_key[..]
+    } else {
+        key
+    };
+    let pad = |byte: u8| -> Vec<u8> {
+        (0..BLOCK_SIZE)
+            .map(|i| key.get(i).copied().unwrap_or(0) ^ byte)
+            .collect()
+    };
+
+    let mut inner = sha1::Sha1::from(pad(0x36));
+    inner.update(data);
+    let mut outer = sha1::Sha1::from(pad(0x5c));
+    outer.update(&inner.digest().bytes());
+    outer.digest().bytes()
+}
+
 /// is base64 encoded
 pub fn is_base64_encoded(bytes: &[u8]) -> bool {
     if bytes.len().wrapping_rem(4) != 0 {
@@ -12,4 +12,6 @@ pub use self::xml::XmlWriterExt;
 
 pub mod body;
 pub mod crypto;
+pub mod percent;
+pub mod scope;
 pub mod time;
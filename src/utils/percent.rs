@@ -0,0 +1,73 @@
+//! percent encoding
+
+/// decodes `%XX` escapes, returns `None` if an escape is malformed
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    let src = s.as_bytes();
+    let mut ans = Vec::with_capacity(src.len());
+    let mut idx = 0;
+    while let Some(&b) = src.get(idx) {
+        if b == b'%' {
+            let hi = hex_value(*src.get(idx.wrapping_add(1))?)?;
+            let lo = hex_value(*src.get(idx.wrapping_add(2))?)?;
+            ans.push((hi << 4_u8) | lo);
+            idx = idx.wrapping_add(3);
+        } else {
+            ans.push(b);
+            idx = idx.wrapping_add(1);
+        }
+    }
+    Some(ans)
+}
+
+/// encodes all bytes except unreserved characters and `/` as `%XX`
+///
+/// This is the `encoding-type=url` format of listings.
+pub fn encode(src: &[u8]) -> String {
+    let mut ans = String::with_capacity(src.len());
+    for &b in src {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~' | b'/') {
+            ans.push(char::from(b));
+        } else {
+            push_escape(&mut ans, b);
+        }
+    }
+    ans
+}
+
+/// pushes `%XX`
+pub fn push_escape(s: &mut String, b: u8) {
+    /// uppercase hex digits
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    s.push('%');
+    for &nibble in &[b >> 4_u8, b & 0xf] {
+        s.push(char::from(*HEX.get(usize::from(nibble)).unwrap_or(&b'0')));
+    }
+}
+
+/// parses a hex digit
+const fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b.wrapping_sub(b'0')),
+        b'a'..=b'f' => Some(b.wrapping_sub(b'a').wrapping_add(10)),
+        b'A'..=b'F' => Some(b.wrapping_sub(b'A').wrapping_add(10)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(decode("a%20b%2fc").unwrap(), b"a b/c");
+        assert_eq!(decode("%ff%FE").unwrap(), [0xff, 0xfe]);
+        assert!(decode("%").is_none());
+        assert!(decode("%4").is_none());
+        assert!(decode("%zz").is_none());
+
+        let bytes = b"dir/a b+c%\xff~";
+        assert_eq!(encode(bytes), "dir/a%20b%2Bc%25%FF~");
+        assert_eq!(decode(&encode(bytes)).unwrap(), bytes);
+    }
+}
@@ -0,0 +1,63 @@
+//! values which are current while a future is polled
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::LocalKey;
+
+use pin_project_lite::pin_project;
+
+/// the thread-local slot of a current value
+pub type Slot<T> = LocalKey<RefCell<Option<T>>>;
+
+/// returns the value of `slot` on this thread
+pub fn current<T: Clone + 'static>(slot: &'static Slot<T>) -> Option<T> {
+    slot.with(|current| current.borrow().clone())
+}
+
+/// makes `value` current in `slot` while polling `future`
+pub const fn scope<T: 'static, F>(slot: &'static Slot<T>, value: T, future: F) -> Scoped<T, F> {
+    Scoped {
+        slot,
+        value,
+        future,
+    }
+}
+
+pin_project! {
+    /// a future which has a current value
+    pub struct Scoped<T: 'static, F> {
+        slot: &'static Slot<T>,
+        value: T,
+        #[pin]
+        future: F,
+    }
+}
+
+impl<T: Clone + 'static, F: Future> Future for Scoped<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        /// restores the previous value, even if the inner future panics
+        struct Restore<T: 'static> {
+            /// slot
+            slot: &'static Slot<T>,
+            /// previous value
+            prev: Option<T>,
+        }
+
+        impl<T: 'static> Drop for Restore<T> {
+            fn drop(&mut self) {
+                let prev = self.prev.take();
+                self.slot.with(|current| *current.borrow_mut() = prev);
+            }
+        }
+
+        let this = self.project();
+        let slot = *this.slot;
+        let prev = slot.with(|current| current.replace(Some(this.value.clone())));
+        let _restore = Restore { slot, prev };
+        this.future.poll(cx)
+    }
+}
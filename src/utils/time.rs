@@ -43,3 +43,16 @@ where
         (ans, dur)
     })
 }
+
+/// Waits for `duration` without a runtime timer
+///
+/// The storages do not depend on a runtime, so the timer is a thread which sleeps.
+/// It is meant for rare waits, such as the backoff of a retry.
+pub async fn sleep(duration: Duration) {
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    let _handle = std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        drop(tx);
+    });
+    let _canceled = rx.await;
+}
@@ -79,7 +79,7 @@ pub fn generate_path(root: impl AsRef<Path>, path: S3Path) -> PathBuf {
     match path {
         S3Path::Root => root.as_ref().to_owned(),
         S3Path::Bucket { bucket } => root.as_ref().join(bucket),
-        S3Path::Object { bucket, key } => root.as_ref().join(bucket).join(key),
+        S3Path::Object { bucket, key } => root.as_ref().join(bucket).join(key.as_ref()),
     }
 }
 
@@ -96,3 +96,29 @@ pub fn parse_mime(res: &Response) -> Result<Mime> {
         Some(v) => Ok(v.to_str()?.parse::<Mime>()?),
     }
 }
+
+/// serves `service` on a local port
+pub async fn serve(service: s3_server::S3Service) -> Result<std::net::SocketAddr> {
+    use hyper::server::Server;
+    use hyper::service::make_service_fn;
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+    let addr = listener.local_addr()?;
+    let service = service.into_shared();
+    let make_service =
+        make_service_fn(move |_| futures::future::ready(Ok::<_, anyhow::Error>(service.clone())));
+    let server = Server::from_tcp(listener)?.serve(make_service);
+    let _handle = tokio::spawn(server);
+    Ok(addr)
+}
+
+/// sends raw bytes and returns the whole response, decoded lossily
+pub async fn raw_request(addr: std::net::SocketAddr, request: &[u8]) -> Result<String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+    stream.write_all(request).await?;
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}